@@ -0,0 +1,375 @@
+//! Registry of files produced by tasks and pipeline stages
+//!
+//! A task or stage that declares `outputs` registers the files it wrote each
+//! time it succeeds: which task or stage wrote each file, when, and its
+//! SHA-256. The registry lives in `.stacy/artifacts.json` and answers "where
+//! does this table come from?" for `stacy outputs list`; `stacy outputs
+//! clean` removes the files it knows about.
+
+use crate::cache::hash::hash_inputs;
+use crate::error::{Error, Result};
+use crate::history::unix_now;
+use crate::utils::lock::{project_lock_path, with_state_write_lock};
+use crate::utils::paths::write_atomically;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Registry format version; a file with another version is started afresh
+const ARTIFACTS_VERSION: u32 = 1;
+
+/// Registry path within the project root
+const ARTIFACTS_FILE: &str = ".stacy/artifacts.json";
+
+/// What kind of step produced an artifact
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProducerKind {
+    Task,
+    Stage,
+}
+
+impl std::fmt::Display for ProducerKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProducerKind::Task => write!(f, "task"),
+            ProducerKind::Stage => write!(f, "stage"),
+        }
+    }
+}
+
+/// One registered file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Artifact {
+    /// Task or stage that wrote the file
+    pub producer: String,
+    /// Whether the producer is a task or a pipeline stage
+    pub kind: ProducerKind,
+    /// When the producing step finished, in seconds since the Unix epoch
+    pub produced_at: u64,
+    /// SHA-256 of the file as the step left it
+    pub sha256: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ArtifactFile {
+    version: u32,
+    /// Artifacts keyed by path relative to the project root
+    artifacts: BTreeMap<PathBuf, Artifact>,
+}
+
+impl Default for ArtifactFile {
+    fn default() -> Self {
+        Self {
+            version: ARTIFACTS_VERSION,
+            artifacts: BTreeMap::new(),
+        }
+    }
+}
+
+impl ArtifactFile {
+    /// Read the registry at `path` (empty if there is none yet)
+    fn read(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)?;
+        let file: ArtifactFile = serde_json::from_str(&content)
+            .map_err(|e| Error::Parse(format!("Failed to parse {}: {}", path.display(), e)))?;
+        if file.version == ARTIFACTS_VERSION {
+            Ok(file)
+        } else {
+            Ok(Self::default())
+        }
+    }
+}
+
+/// The registry as loaded, and what this process changed in it since
+struct State {
+    file: ArtifactFile,
+    /// Paths registered (`Some`) or forgotten (`None`) since the load
+    changes: BTreeMap<PathBuf, Option<Artifact>>,
+}
+
+impl State {
+    fn insert(&mut self, path: PathBuf, artifact: Artifact) {
+        self.file.artifacts.insert(path.clone(), artifact.clone());
+        self.changes.insert(path, Some(artifact));
+    }
+
+    fn remove(&mut self, path: &Path) {
+        self.file.artifacts.remove(path);
+        self.changes.insert(path.to_path_buf(), None);
+    }
+}
+
+/// The project's artifact registry
+///
+/// Loaded once and written back with [`Artifacts::save`], like the build
+/// cache, so parallel task steps don't race on the file. Saving merges this
+/// process's changes into the registry on disk, so concurrent runs keep each
+/// other's entries.
+pub struct Artifacts {
+    project_root: PathBuf,
+    state: Mutex<State>,
+}
+
+impl Artifacts {
+    /// Load the registry (empty if there is none yet)
+    pub fn load(project_root: &Path) -> Result<Self> {
+        let file = ArtifactFile::read(&Self::path(project_root))?;
+        Ok(Self {
+            project_root: project_root.to_path_buf(),
+            state: Mutex::new(State {
+                file,
+                changes: BTreeMap::new(),
+            }),
+        })
+    }
+
+    /// Path of the registry file for a project
+    pub fn path(project_root: &Path) -> PathBuf {
+        project_root.join(ARTIFACTS_FILE)
+    }
+
+    /// Register the files under `outputs` (files or directories, relative to
+    /// the project root) as written by `producer` just now. Files the
+    /// producer registered before under those outputs that are gone now are
+    /// dropped. Returns how many files were registered.
+    pub fn record(&self, kind: ProducerKind, producer: &str, outputs: &[PathBuf]) -> Result<usize> {
+        let hashes = hash_inputs(&self.project_root, outputs)?;
+        let produced_at = unix_now();
+
+        let mut state = self.state.lock().unwrap();
+        let gone: Vec<PathBuf> = state
+            .file
+            .artifacts
+            .iter()
+            .filter(|(path, artifact)| {
+                artifact.kind == kind
+                    && artifact.producer == producer
+                    && outputs.iter().any(|o| path.starts_with(o))
+                    && !hashes.contains_key(*path)
+            })
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in &gone {
+            state.remove(path);
+        }
+        for (path, sha256) in &hashes {
+            state.insert(
+                path.clone(),
+                Artifact {
+                    producer: producer.to_string(),
+                    kind,
+                    produced_at,
+                    sha256: sha256.clone(),
+                },
+            );
+        }
+        Ok(hashes.len())
+    }
+
+    /// All registered artifacts, by path
+    pub fn list(&self) -> Vec<(PathBuf, Artifact)> {
+        let state = self.state.lock().unwrap();
+        state
+            .file
+            .artifacts
+            .iter()
+            .map(|(path, artifact)| (path.clone(), artifact.clone()))
+            .collect()
+    }
+
+    /// Delete the registered files of the given producers (all when empty)
+    /// and forget them. Returns the paths removed; files already gone are
+    /// forgotten without being listed.
+    pub fn clean(&self, producers: &[String]) -> Result<Vec<PathBuf>> {
+        let mut state = self.state.lock().unwrap();
+        let selected: Vec<PathBuf> = state
+            .file
+            .artifacts
+            .iter()
+            .filter(|(_, a)| producers.is_empty() || producers.contains(&a.producer))
+            .map(|(path, _)| path.clone())
+            .collect();
+
+        let mut removed = Vec::new();
+        for path in selected {
+            let full = self.project_root.join(&path);
+            match std::fs::remove_file(&full) {
+                Ok(()) => removed.push(path.clone()),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => {
+                    return Err(Error::Io(std::io::Error::new(
+                        e.kind(),
+                        format!("Failed to remove {}: {}", full.display(), e),
+                    )))
+                }
+            }
+            state.remove(&path);
+        }
+        Ok(removed)
+    }
+
+    /// Write this process's changes to `.stacy/artifacts.json`: under the
+    /// project lock, the registry is read again, the changes applied to it
+    /// and the result written back, like [`BuildCache::update`]
+    ///
+    /// [`BuildCache::update`]: crate::cache::BuildCache::update
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path(&self.project_root);
+        let mut state = self.state.lock().unwrap();
+        let merged = with_state_write_lock(&project_lock_path(&self.project_root), || {
+            let mut file = ArtifactFile::read(&path)?;
+            for (changed, artifact) in &state.changes {
+                match artifact {
+                    Some(artifact) => file.artifacts.insert(changed.clone(), artifact.clone()),
+                    None => file.artifacts.remove(changed),
+                };
+            }
+            write_atomically(&path, serde_json::to_string_pretty(&file)?.as_bytes())?;
+            Ok::<_, Error>(file)
+        })??;
+        state.file = merged;
+        state.changes.clear();
+        Ok(())
+    }
+}
+
+/// Format seconds since the Unix epoch as `YYYY-MM-DD HH:MM:SS` (UTC)
+pub fn format_timestamp(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        rem / 3_600,
+        rem % 3_600 / 60,
+        rem % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_record_list_and_save() {
+        let temp = TempDir::new().unwrap();
+        fs::create_dir_all(temp.path().join("output/tables")).unwrap();
+        fs::write(temp.path().join("output/tables/t1.tex"), "1").unwrap();
+        fs::write(temp.path().join("output/tables/t2.tex"), "2").unwrap();
+
+        let artifacts = Artifacts::load(temp.path()).unwrap();
+        let count = artifacts
+            .record(
+                ProducerKind::Stage,
+                "tables",
+                &[PathBuf::from("output/tables")],
+            )
+            .unwrap();
+        assert_eq!(count, 2);
+        artifacts.save().unwrap();
+
+        let loaded = Artifacts::load(temp.path()).unwrap().list();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded[0].0, PathBuf::from("output/tables/t1.tex"));
+        assert_eq!(loaded[0].1.producer, "tables");
+        assert_eq!(loaded[0].1.kind, ProducerKind::Stage);
+        assert_eq!(loaded[0].1.sha256.len(), 64);
+
+        // A file the stage no longer writes drops out on the next record
+        fs::remove_file(temp.path().join("output/tables/t2.tex")).unwrap();
+        artifacts
+            .record(
+                ProducerKind::Stage,
+                "tables",
+                &[PathBuf::from("output/tables")],
+            )
+            .unwrap();
+        assert_eq!(artifacts.list().len(), 1);
+    }
+
+    #[test]
+    fn test_clean_by_producer() {
+        let temp = TempDir::new().unwrap();
+        fs::write(temp.path().join("a.dta"), "a").unwrap();
+        fs::write(temp.path().join("b.dta"), "b").unwrap();
+
+        let artifacts = Artifacts::load(temp.path()).unwrap();
+        artifacts
+            .record(ProducerKind::Task, "clean", &[PathBuf::from("a.dta")])
+            .unwrap();
+        artifacts
+            .record(ProducerKind::Stage, "merge", &[PathBuf::from("b.dta")])
+            .unwrap();
+
+        let removed = artifacts.clean(&["clean".to_string()]).unwrap();
+        assert_eq!(removed, vec![PathBuf::from("a.dta")]);
+        assert!(!temp.path().join("a.dta").exists());
+        assert!(temp.path().join("b.dta").exists());
+        assert_eq!(artifacts.list().len(), 1);
+
+        fs::remove_file(temp.path().join("b.dta")).unwrap();
+        assert!(artifacts.clean(&[]).unwrap().is_empty());
+        assert!(artifacts.list().is_empty());
+    }
+
+    #[test]
+    fn test_save_keeps_entries_saved_meanwhile() {
+        let temp = TempDir::new().unwrap();
+        fs::write(temp.path().join("a.dta"), "a").unwrap();
+        fs::write(temp.path().join("b.dta"), "b").unwrap();
+        fs::write(temp.path().join("old.dta"), "old").unwrap();
+        let setup = Artifacts::load(temp.path()).unwrap();
+        setup
+            .record(ProducerKind::Task, "old", &[PathBuf::from("old.dta")])
+            .unwrap();
+        setup.save().unwrap();
+
+        // Two runs load the registry before either saves
+        let first = Artifacts::load(temp.path()).unwrap();
+        let second = Artifacts::load(temp.path()).unwrap();
+        first
+            .record(ProducerKind::Task, "clean", &[PathBuf::from("a.dta")])
+            .unwrap();
+        first.save().unwrap();
+        second
+            .record(ProducerKind::Stage, "merge", &[PathBuf::from("b.dta")])
+            .unwrap();
+        second.clean(&["old".to_string()]).unwrap();
+        second.save().unwrap();
+
+        let paths: Vec<PathBuf> = Artifacts::load(temp.path())
+            .unwrap()
+            .list()
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+        assert_eq!(paths, vec![PathBuf::from("a.dta"), PathBuf::from("b.dta")]);
+        // The saving run sees the merged registry too
+        assert_eq!(second.list().len(), 2);
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00:00");
+        assert_eq!(format_timestamp(951_827_696), "2000-02-29 12:34:56");
+    }
+}
@@ -3,7 +3,8 @@
 //! Compares current script and dependency hashes against cached values
 //! to determine if a rebuild is necessary.
 
-use super::hash::{hash_dependency_tree, hash_lockfile};
+use super::engine::EngineIdentity;
+use super::hash::{hash_data_dependencies, hash_dependency_tree, hash_lockfile};
 use super::{BuildCache, CacheEntry};
 use crate::error::Result;
 use std::path::Path;
@@ -25,6 +26,22 @@ pub enum RebuildReason {
     DependencyRemoved(String),
     /// The working directory has changed
     WorkingDirChanged,
+    /// A data file the script reads has changed
+    DataChanged(String),
+    /// The script reads a data file it didn't before, or one has appeared
+    DataAdded(String),
+    /// The script no longer reads a data file, or one has disappeared
+    DataRemoved(String),
+    /// A different Stata binary would run the script
+    EngineChanged,
+    /// The Stata version has changed
+    StataVersionChanged,
+    /// A declared input file has changed
+    InputChanged(String),
+    /// A declared input file was added
+    InputAdded(String),
+    /// A declared input file was removed
+    InputRemoved(String),
     /// Force rebuild was requested
     ForceRebuild,
 }
@@ -39,6 +56,14 @@ impl std::fmt::Display for RebuildReason {
             RebuildReason::DependencyAdded(dep) => write!(f, "dependency added: {}", dep),
             RebuildReason::DependencyRemoved(dep) => write!(f, "dependency removed: {}", dep),
             RebuildReason::WorkingDirChanged => write!(f, "working directory changed"),
+            RebuildReason::DataChanged(path) => write!(f, "data changed: {}", path),
+            RebuildReason::DataAdded(path) => write!(f, "data added: {}", path),
+            RebuildReason::DataRemoved(path) => write!(f, "data removed: {}", path),
+            RebuildReason::EngineChanged => write!(f, "Stata engine changed"),
+            RebuildReason::StataVersionChanged => write!(f, "Stata version changed"),
+            RebuildReason::InputChanged(input) => write!(f, "input changed: {}", input),
+            RebuildReason::InputAdded(input) => write!(f, "input added: {}", input),
+            RebuildReason::InputRemoved(input) => write!(f, "input removed: {}", input),
             RebuildReason::ForceRebuild => write!(f, "forced rebuild"),
         }
     }
@@ -48,7 +73,7 @@ impl std::fmt::Display for RebuildReason {
 #[derive(Debug)]
 pub enum CacheStatus {
     /// Cache hit - no changes detected, can use cached result
-    Hit(CacheEntry),
+    Hit(Box<CacheEntry>),
     /// Cache miss - rebuild required
     Miss(RebuildReason),
 }
@@ -105,8 +130,35 @@ pub fn check_cache_with_working_dir(
     working_dir: Option<&Path>,
     force: bool,
 ) -> Result<CacheStatus> {
-    use super::hash::hash_string;
+    check_cache_with_engine(cache, script, project_root, working_dir, None, force)
+}
+
+/// Check if a script needs to be rebuilt on a given Stata engine
+///
+/// Like check_cache_with_working_dir, and an entry recorded on a different
+/// binary or Stata version is a cache miss. With `engine = None` the engine is
+/// not compared.
+pub fn check_cache_with_engine(
+    cache: &BuildCache,
+    script: &Path,
+    project_root: Option<&Path>,
+    working_dir: Option<&Path>,
+    engine: Option<&EngineIdentity>,
+    force: bool,
+) -> Result<CacheStatus> {
+    let status = lookup(cache, script, project_root, working_dir, engine, force)?;
+    crate::metrics::counters::note_cache(status.is_hit());
+    Ok(status)
+}
 
+fn lookup(
+    cache: &BuildCache,
+    script: &Path,
+    project_root: Option<&Path>,
+    working_dir: Option<&Path>,
+    engine: Option<&EngineIdentity>,
+    force: bool,
+) -> Result<CacheStatus> {
     // Force rebuild if requested
     if force {
         return Ok(CacheStatus::Miss(RebuildReason::ForceRebuild));
@@ -118,18 +170,47 @@ pub fn check_cache_with_working_dir(
         None => return Ok(CacheStatus::Miss(RebuildReason::NotCached)),
     };
 
+    match compare_entry(cached, script, project_root, working_dir, engine)? {
+        Some(reason) => Ok(CacheStatus::Miss(reason)),
+        None => Ok(CacheStatus::Hit(Box::new(cached.clone()))),
+    }
+}
+
+/// Compare a cached entry against the script, its dependencies, the lockfile
+/// and the working directory as they are now.
+///
+/// Returns the first difference found, or None if the entry is still valid.
+pub(crate) fn compare_entry(
+    cached: &CacheEntry,
+    script: &Path,
+    project_root: Option<&Path>,
+    working_dir: Option<&Path>,
+    engine: Option<&EngineIdentity>,
+) -> Result<Option<RebuildReason>> {
+    use super::hash::hash_string;
+
     // Compute current hashes
     let current_hashes = hash_dependency_tree(script)?;
 
     // Check script hash
     if cached.script_hash != current_hashes.script_hash {
-        return Ok(CacheStatus::Miss(RebuildReason::ScriptChanged));
+        return Ok(Some(RebuildReason::ScriptChanged));
+    }
+
+    // Check the engine: same binary, same reported version
+    if let Some(engine) = engine {
+        if cached.stata_binary.as_deref() != Some(engine.binary.as_str()) {
+            return Ok(Some(RebuildReason::EngineChanged));
+        }
+        if cached.stata_version != engine.version {
+            return Ok(Some(RebuildReason::StataVersionChanged));
+        }
     }
 
     // Check working directory hash
     let current_working_dir_hash = working_dir.map(|d| hash_string(&d.display().to_string()));
     if cached.working_dir_hash != current_working_dir_hash {
-        return Ok(CacheStatus::Miss(RebuildReason::WorkingDirChanged));
+        return Ok(Some(RebuildReason::WorkingDirChanged));
     }
 
     // Check lockfile hash (if we have a project root)
@@ -138,13 +219,13 @@ pub fn check_cache_with_working_dir(
 
         match (&cached.lockfile_hash, &current_lockfile_hash) {
             (Some(cached_hash), Some(current_hash)) if cached_hash != current_hash => {
-                return Ok(CacheStatus::Miss(RebuildReason::LockfileChanged));
+                return Ok(Some(RebuildReason::LockfileChanged));
             }
             (None, Some(_)) => {
-                return Ok(CacheStatus::Miss(RebuildReason::LockfileChanged));
+                return Ok(Some(RebuildReason::LockfileChanged));
             }
             (Some(_), None) => {
-                return Ok(CacheStatus::Miss(RebuildReason::LockfileChanged));
+                return Ok(Some(RebuildReason::LockfileChanged));
             }
             _ => {}
         }
@@ -153,7 +234,7 @@ pub fn check_cache_with_working_dir(
     // Check for added dependencies
     for path in current_hashes.dependency_hashes.keys() {
         if !cached.dependency_hashes.contains_key(path) {
-            return Ok(CacheStatus::Miss(RebuildReason::DependencyAdded(
+            return Ok(Some(RebuildReason::DependencyAdded(
                 path.display().to_string(),
             )));
         }
@@ -162,7 +243,7 @@ pub fn check_cache_with_working_dir(
     // Check for removed dependencies
     for path in cached.dependency_hashes.keys() {
         if !current_hashes.dependency_hashes.contains_key(path) {
-            return Ok(CacheStatus::Miss(RebuildReason::DependencyRemoved(
+            return Ok(Some(RebuildReason::DependencyRemoved(
                 path.display().to_string(),
             )));
         }
@@ -172,15 +253,32 @@ pub fn check_cache_with_working_dir(
     for (path, current_hash) in &current_hashes.dependency_hashes {
         if let Some(cached_hash) = cached.dependency_hashes.get(path) {
             if cached_hash != current_hash {
-                return Ok(CacheStatus::Miss(RebuildReason::DependencyChanged(
+                return Ok(Some(RebuildReason::DependencyChanged(
                     path.display().to_string(),
                 )));
             }
         }
     }
 
+    // Check the data files the script tree reads
+    let current_data = hash_data_dependencies(script, working_dir)?;
+    for path in current_data.keys() {
+        if !cached.data_hashes.contains_key(path) {
+            return Ok(Some(RebuildReason::DataAdded(path.display().to_string())));
+        }
+    }
+    for (path, cached_hash) in &cached.data_hashes {
+        match current_data.get(path) {
+            None => return Ok(Some(RebuildReason::DataRemoved(path.display().to_string()))),
+            Some(hash) if hash != cached_hash => {
+                return Ok(Some(RebuildReason::DataChanged(path.display().to_string())))
+            }
+            Some(_) => {}
+        }
+    }
+
     // All checks passed - cache hit!
-    Ok(CacheStatus::Hit(cached.clone()))
+    Ok(None)
 }
 
 /// Convenience function to check if rebuild is needed (returns bool)
@@ -380,6 +478,10 @@ mod tests {
             RebuildReason::DependencyChanged("helper.do".to_string()).to_string(),
             "dependency changed: helper.do"
         );
+        assert_eq!(
+            RebuildReason::DataChanged("data/raw.csv".to_string()).to_string(),
+            "data changed: data/raw.csv"
+        );
     }
 
     #[test]
@@ -418,6 +520,114 @@ mod tests {
         assert_eq!(status.reason(), Some(&RebuildReason::WorkingDirChanged));
     }
 
+    #[test]
+    fn test_data_changed() {
+        let temp = TempDir::new().unwrap();
+        let script = temp.path().join("clean.do");
+        fs::write(&script, "import delimited raw.csv, clear\n").unwrap();
+        fs::write(temp.path().join("raw.csv"), "x\n1\n").unwrap();
+
+        let mut cache = BuildCache::new();
+        let hash = crate::cache::hash::hash_file(&script).unwrap();
+        let data = hash_data_dependencies(&script, Some(temp.path())).unwrap();
+        let mut entry = create_cache_entry(&hash, HashMap::new()).with_data(data);
+        entry.working_dir_hash = hash_working_dir(Some(temp.path()));
+        cache.insert(&script, entry);
+
+        let check = || {
+            check_cache_with_working_dir(&cache, &script, None, Some(temp.path()), false).unwrap()
+        };
+        assert!(check().is_hit());
+
+        // The do-file is untouched, the CSV it imports is not
+        fs::write(temp.path().join("raw.csv"), "x\n2\n").unwrap();
+        assert!(matches!(
+            check().reason(),
+            Some(RebuildReason::DataChanged(p)) if p.ends_with("raw.csv")
+        ));
+
+        fs::remove_file(temp.path().join("raw.csv")).unwrap();
+        assert!(matches!(
+            check().reason(),
+            Some(RebuildReason::DataRemoved(_))
+        ));
+    }
+
+    #[test]
+    fn test_engine_changed() {
+        let temp = TempDir::new().unwrap();
+        let script = temp.path().join("test.do");
+        fs::write(&script, "display 1").unwrap();
+
+        let se = EngineIdentity {
+            binary: "/usr/local/stata/stata-se".to_string(),
+            version: Some("18.0".to_string()),
+        };
+        let mut cache = BuildCache::new();
+        let hash = crate::cache::hash::hash_file(&script).unwrap();
+        cache.insert(
+            &script,
+            create_cache_entry(&hash, HashMap::new()).with_engine(Some(&se)),
+        );
+
+        let check = |engine: Option<&EngineIdentity>| {
+            check_cache_with_engine(&cache, &script, None, None, engine, false).unwrap()
+        };
+
+        assert!(check(Some(&se)).is_hit());
+        // Callers that don't know the engine don't compare it
+        assert!(check(None).is_hit());
+
+        let mp = EngineIdentity {
+            binary: "/usr/local/stata/stata-mp".to_string(),
+            ..se.clone()
+        };
+        assert_eq!(
+            check(Some(&mp)).reason(),
+            Some(&RebuildReason::EngineChanged)
+        );
+
+        let upgraded = EngineIdentity {
+            version: Some("19.5".to_string()),
+            ..se.clone()
+        };
+        assert_eq!(
+            check(Some(&upgraded)).reason(),
+            Some(&RebuildReason::StataVersionChanged)
+        );
+    }
+
+    #[test]
+    fn test_stata_upgraded_in_place() {
+        let temp = TempDir::new().unwrap();
+        let script = temp.path().join("test.do");
+        fs::write(&script, "display 1").unwrap();
+        let binary = temp.path().join("stata-mp");
+        fs::write(&binary, "#!/bin/sh\n# 18.0\n").unwrap();
+        let before = EngineIdentity::resolve(binary.to_str().unwrap());
+
+        let mut cache = BuildCache::new();
+        let hash = crate::cache::hash::hash_file(&script).unwrap();
+        cache.insert(
+            &script,
+            create_cache_entry(&hash, HashMap::new()).with_engine(Some(&before)),
+        );
+        let check = |engine: &EngineIdentity| {
+            check_cache_with_engine(&cache, &script, None, None, Some(engine), false).unwrap()
+        };
+        assert!(check(&before).is_hit());
+
+        // Same path, new build: whatever version was saved at the last
+        // refresh, the entry no longer matches
+        fs::write(&binary, "#!/bin/sh\n# 19.5 StataNow\n").unwrap();
+        let after = EngineIdentity::resolve(binary.to_str().unwrap());
+        assert_eq!(after.binary, before.binary);
+        assert_eq!(
+            check(&after).reason(),
+            Some(&RebuildReason::StataVersionChanged)
+        );
+    }
+
     #[test]
     fn test_cache_hit_with_dependencies() {
         let temp = TempDir::new().unwrap();
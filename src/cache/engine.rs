@@ -0,0 +1,153 @@
+//! Stata engine identity for cache keys
+//!
+//! A cached result is only as good as the Stata that produced it: StataSE and
+//! StataMP differ (matsize, processors), and a version upgrade can change
+//! estimation output. Entries record the engine they ran on, and a different
+//! engine is a cache miss.
+
+use crate::error::error_db::{ErrorCodeCache, ErrorDatabase};
+use crate::error::{Error, Result};
+use crate::executor::binary::detect_stata_binary;
+use crate::utils::paths;
+use std::path::Path;
+
+/// The Stata a script ran (or would run) on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EngineIdentity {
+    /// Resolved binary path
+    pub binary: String,
+    /// Version the engine reports, if stacy has seen it report one, and the
+    /// binary's size and modification time (see [`EngineIdentity::resolve`])
+    pub version: Option<String>,
+}
+
+impl EngineIdentity {
+    /// Identify the engine stacy would run, if one can be found
+    pub fn detect(cli_engine: Option<&str>) -> Option<Self> {
+        detect_stata_binary(cli_engine)
+            .ok()
+            .map(|binary| Self::resolve(&binary))
+    }
+
+    /// Identify the engine behind a resolved binary path.
+    ///
+    /// Asking Stata for `c(stata_version)` would cost a Stata startup on every
+    /// cache check, so the version is the one Stata reported when stacy last
+    /// extracted its error codes (`stacy doctor --refresh`), if that install
+    /// is the one `binary` belongs to, followed by the binary's size and
+    /// modification time. The reported version goes stale when Stata is
+    /// upgraded in place before the next refresh; the size and time don't,
+    /// so an upgrade always changes the identity.
+    pub fn resolve(binary: &str) -> Self {
+        let path = Path::new(binary);
+        let resolved = paths::canonicalize(path)
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|_| binary.to_string());
+
+        let version = match (reported_version(Path::new(&resolved)), build_stamp(path)) {
+            (Some(reported), Some(stamp)) => Some(format!("{} ({})", reported, stamp)),
+            (reported, stamp) => reported.or(stamp),
+        };
+
+        Self {
+            binary: resolved,
+            version,
+        }
+    }
+}
+
+/// Processor count the license of the Stata at `binary` allows, as reported
+/// during error-code extraction (`stacy doctor --refresh`). None when stacy
+/// hasn't seen this install report it.
+pub fn licensed_processors(binary: &str) -> Option<u32> {
+    let path = Path::new(binary);
+    let resolved = paths::canonicalize_or_self(path);
+    extraction_for(&resolved)?.processors_lic
+}
+
+/// Reject a `set processors` count the license can't honour.
+///
+/// Stata itself only fails at `set processors` time, after startup; checking
+/// up front fails the run before any script starts. Without a known license
+/// the request passes through and Stata has the last word.
+pub fn check_processors(requested: u32, licensed: Option<u32>) -> Result<()> {
+    if requested == 0 {
+        return Err(Error::Config("processors must be at least 1".to_string()));
+    }
+    match licensed {
+        Some(licensed) if requested > licensed => Err(Error::Config(format!(
+            "processors = {} exceeds this Stata's license ({} {})",
+            requested,
+            licensed,
+            if licensed == 1 {
+                "core; only Stata/MP runs on more"
+            } else {
+                "cores"
+            }
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Version recorded by error-code extraction, when it came from this install
+fn reported_version(binary: &Path) -> Option<String> {
+    extraction_for(binary)?.stata_version
+}
+
+/// The cached extraction results, if they came from the install `binary`
+/// belongs to
+fn extraction_for(binary: &Path) -> Option<ErrorDatabase> {
+    let db = ErrorCodeCache::load().ok()??;
+    let sysdir = db.sysdir.as_deref()?;
+    let sysdir = paths::canonicalize_or_self(Path::new(sysdir));
+    binary.starts_with(&sysdir).then_some(db)
+}
+
+/// Size and modification time of the binary, as a stand-in for its version
+fn build_stamp(binary: &Path) -> Option<String> {
+    let meta = std::fs::metadata(binary).ok()?;
+    let mtime = meta
+        .modified()
+        .ok()?
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()?
+        .as_secs();
+    Some(format!("build:{}-{}", meta.len(), mtime))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_resolve_uses_build_stamp_without_reported_version() {
+        let temp = TempDir::new().unwrap();
+        let binary = temp.path().join("stata-mp");
+        std::fs::write(&binary, "#!/bin/sh\n").unwrap();
+
+        let engine = EngineIdentity::resolve(binary.to_str().unwrap());
+        assert!(engine.binary.ends_with("stata-mp"));
+        assert!(engine.version.unwrap().starts_with("build:10-"));
+    }
+
+    #[test]
+    fn test_check_processors_against_license() {
+        assert!(check_processors(4, Some(8)).is_ok());
+        assert!(check_processors(8, Some(8)).is_ok());
+        assert!(check_processors(4, None).is_ok());
+        assert!(check_processors(0, None).is_err());
+
+        let err = check_processors(16, Some(8)).unwrap_err().to_string();
+        assert!(err.contains("16") && err.contains("8 cores"), "{}", err);
+        let err = check_processors(2, Some(1)).unwrap_err().to_string();
+        assert!(err.contains("Stata/MP"), "{}", err);
+    }
+
+    #[test]
+    fn test_resolve_missing_binary_keeps_path() {
+        let engine = EngineIdentity::resolve("/nonexistent/stata-se");
+        assert_eq!(engine.binary, "/nonexistent/stata-se");
+        assert_eq!(engine.version, None);
+    }
+}
@@ -2,8 +2,11 @@
 //!
 //! Provides file and dependency tree hashing for change detection.
 
+use crate::deps::data::parse_data_references;
+use crate::deps::parser::{is_dynamic_path, DependencyType};
 use crate::deps::tree::{build_tree, DependencyTree};
 use crate::error::Result;
+use crate::utils::paths;
 use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
@@ -94,10 +97,7 @@ fn hash_tree_recursive(tree: &DependencyTree, hashes: &mut DependencyHashes) ->
         }
 
         // Hash this dependency if we haven't already
-        let canonical = child
-            .path
-            .canonicalize()
-            .unwrap_or_else(|_| child.path.clone());
+        let canonical = paths::canonicalize_or_self(&child.path);
 
         if let Entry::Vacant(e) = hashes.dependency_hashes.entry(canonical) {
             let hash = hash_file(&child.path)?;
@@ -111,9 +111,9 @@ fn hash_tree_recursive(tree: &DependencyTree, hashes: &mut DependencyHashes) ->
     Ok(())
 }
 
-/// Hash the lockfile if it exists
+/// Hash the lockfile (the profile's under `--env-profile`) if it exists
 pub fn hash_lockfile(project_root: &Path) -> Result<Option<String>> {
-    let lockfile_path = project_root.join("stacy.lock");
+    let lockfile_path = project_root.join(crate::project::profile::lockfile_name());
 
     if lockfile_path.exists() {
         Ok(Some(hash_file(&lockfile_path)?))
@@ -122,6 +122,111 @@ pub fn hash_lockfile(project_root: &Path) -> Result<Option<String>> {
     }
 }
 
+/// Hash the data files a script and the do-files it runs read.
+///
+/// Files that don't exist yet are left out (see [`data_dependency_paths`]).
+pub fn hash_data_dependencies(
+    script: &Path,
+    working_dir: Option<&Path>,
+) -> Result<HashMap<PathBuf, String>> {
+    let mut hashes = HashMap::new();
+    for path in data_dependency_paths(script, working_dir)? {
+        if let Ok(canonical) = paths::canonicalize(&path) {
+            if canonical.is_file() && !hashes.contains_key(&canonical) {
+                let hash = hash_file(&canonical)?;
+                hashes.insert(canonical, hash);
+            }
+        }
+    }
+
+    Ok(hashes)
+}
+
+/// The data files a script and the do-files it runs read, whether or not
+/// they exist yet.
+///
+/// Stata resolves relative data paths against its working directory, so they
+/// are resolved against `working_dir` (the current directory when `None`).
+/// Paths built from macros are skipped, as stacy can't know what they expand
+/// to.
+pub fn data_dependency_paths(script: &Path, working_dir: Option<&Path>) -> Result<Vec<PathBuf>> {
+    let base = match working_dir {
+        Some(dir) => dir.to_path_buf(),
+        None => std::env::current_dir()?,
+    };
+
+    let tree = build_tree(script)?;
+    let mut scripts = vec![tree.path.clone()];
+    collect_scripts(&tree, &mut scripts);
+
+    let mut paths = Vec::new();
+    for script in scripts {
+        for reference in parse_data_references(&script)? {
+            if is_dynamic_path(&reference.path) {
+                continue;
+            }
+            let path = if reference.path.is_absolute() {
+                reference.path
+            } else {
+                base.join(&reference.path)
+            };
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+    }
+
+    Ok(paths)
+}
+
+/// Every existing do-file below `tree`, excluding packages and unresolved paths
+fn collect_scripts(tree: &DependencyTree, scripts: &mut Vec<PathBuf>) {
+    for child in &tree.children {
+        if child.is_circular
+            || !child.exists
+            || child.is_dynamic
+            || child.dep_type == Some(DependencyType::Require)
+        {
+            continue;
+        }
+        if !scripts.contains(&child.path) {
+            scripts.push(child.path.clone());
+        }
+        collect_scripts(child, scripts);
+    }
+}
+
+/// Hash declared input files, keyed by path relative to `base`.
+///
+/// A directory contributes every file beneath it. Inputs that don't exist are
+/// left out, so one appearing or disappearing changes the key.
+pub fn hash_inputs(base: &Path, inputs: &[PathBuf]) -> Result<HashMap<PathBuf, String>> {
+    let mut hashes = HashMap::new();
+
+    for input in inputs {
+        let path = if input.is_absolute() {
+            input.clone()
+        } else {
+            base.join(input)
+        };
+
+        for entry in walkdir::WalkDir::new(&path)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+        {
+            let key = entry
+                .path()
+                .strip_prefix(base)
+                .unwrap_or(entry.path())
+                .to_path_buf();
+            hashes.insert(key, hash_file(entry.path())?);
+        }
+    }
+
+    Ok(hashes)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -284,4 +389,56 @@ mod tests {
         let result = hash_file(Path::new("/nonexistent/file.txt"));
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_hash_inputs_files_and_dirs() {
+        let temp = TempDir::new().unwrap();
+        fs::create_dir_all(temp.path().join("data/raw")).unwrap();
+        fs::write(temp.path().join("data/raw/a.csv"), "x\n1\n").unwrap();
+        fs::write(temp.path().join("data/raw/b.csv"), "y\n2\n").unwrap();
+        fs::write(temp.path().join("codebook.txt"), "x: int").unwrap();
+
+        let hashes = hash_inputs(
+            temp.path(),
+            &[PathBuf::from("data/raw"), PathBuf::from("codebook.txt")],
+        )
+        .unwrap();
+
+        assert_eq!(hashes.len(), 3);
+        assert!(hashes.contains_key(&PathBuf::from("data/raw/a.csv")));
+        assert!(hashes.contains_key(&PathBuf::from("codebook.txt")));
+    }
+
+    #[test]
+    fn test_hash_inputs_missing_input_is_omitted() {
+        let temp = TempDir::new().unwrap();
+        let hashes = hash_inputs(temp.path(), &[PathBuf::from("nope.dta")]).unwrap();
+        assert!(hashes.is_empty());
+    }
+
+    #[test]
+    fn test_hash_data_dependencies_follows_do_files() {
+        let temp = TempDir::new().unwrap();
+        fs::create_dir_all(temp.path().join("data")).unwrap();
+        fs::write(temp.path().join("data/raw.csv"), "x\n1\n").unwrap();
+        fs::write(temp.path().join("data/lookup.dta"), "dta").unwrap();
+        fs::write(
+            temp.path().join("main.do"),
+            "import delimited data/raw, clear\ndo merge.do\nuse `tmp', clear\n",
+        )
+        .unwrap();
+        fs::write(
+            temp.path().join("merge.do"),
+            "merge 1:1 id using data/lookup\nsave data/clean, replace\nuse data/later.dta\n",
+        )
+        .unwrap();
+
+        let hashes =
+            hash_data_dependencies(&temp.path().join("main.do"), Some(temp.path())).unwrap();
+
+        let data = temp.path().canonicalize().unwrap().join("data");
+        assert_eq!(hashes.len(), 2);
+        assert!(hashes.contains_key(&data.join("raw.csv")));
+        assert!(hashes.contains_key(&data.join("lookup.dta")));
+    }
 }
@@ -2,11 +2,20 @@
 //!
 //! Stores execution results based on script content hashes and dependency trees.
 //! Cache is opt-in via `--cache` flag and stored in `.stacy/cache/build.json`.
+//! `stacy run` caches single scripts; `stacy task` and `stacy test` cache each
+//! script they run as a step (see [`step`]), in entries of their own: a step
+//! and a plain run of the same script, or two tasks running it with other
+//! arguments, don't replace each other's entry.
 
 pub mod detect;
+pub mod engine;
 pub mod hash;
+pub mod step;
+pub mod verify;
 
 use crate::error::{Error, Result};
+use crate::utils::lock::{project_lock_path, with_state_write_lock};
+use crate::utils::paths::{self, write_atomically};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
@@ -26,8 +35,11 @@ const CACHE_FILE: &str = "cache/build.json";
 pub struct BuildCache {
     /// Cache format version
     pub version: u32,
-    /// Map of script paths to their cached entries
+    /// Map of script paths to their cached entries (`stacy run --cache`)
     pub entries: HashMap<PathBuf, CacheEntry>,
+    /// Entries of task and test steps, by definition hash and then script
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub steps: HashMap<String, HashMap<PathBuf, CacheEntry>>,
 }
 
 impl Default for BuildCache {
@@ -35,6 +47,7 @@ impl Default for BuildCache {
         Self {
             version: CACHE_VERSION,
             entries: HashMap::new(),
+            steps: HashMap::new(),
         }
     }
 }
@@ -61,7 +74,7 @@ impl BuildCache {
             ))
         })?;
 
-        let cache: BuildCache = serde_json::from_str(&content).map_err(|e| {
+        let mut cache: BuildCache = serde_json::from_str(&content).map_err(|e| {
             Error::Config(format!(
                 "Failed to parse cache file {}: {}",
                 cache_path.display(),
@@ -74,31 +87,42 @@ impl BuildCache {
             // Version mismatch - return empty cache (will be rebuilt)
             return Ok(Self::new());
         }
+        // Steps recorded before they had entries of their own
+        cache
+            .entries
+            .retain(|_, entry| entry.definition_hash.is_none());
 
         Ok(cache)
     }
 
-    /// Save cache to the project's .stacy directory
+    /// Save cache to the project's .stacy directory, replacing what is there
+    ///
+    /// Written whole through a temporary file while holding the project lock,
+    /// so stacy processes saving side by side never leave half a file.
     pub fn save(&self, project_root: &Path) -> Result<()> {
-        let stacy_dir = project_root.join(STACY_DIR);
-        let cache_dir = stacy_dir.join("cache");
-        let cache_path = stacy_dir.join(CACHE_FILE);
-
-        // Ensure directories exist
-        if !cache_dir.exists() {
-            std::fs::create_dir_all(&cache_dir).map_err(|e| {
-                Error::Config(format!(
-                    "Failed to create cache directory {}: {}",
-                    cache_dir.display(),
-                    e
-                ))
-            })?;
-        }
+        with_state_write_lock(&project_lock_path(project_root), || {
+            self.write(project_root)
+        })?
+    }
+
+    /// Load the cache, change it and save it, all under the project lock so
+    /// another stacy process can't save in between: entries it recorded
+    /// meanwhile are kept
+    pub fn update(project_root: &Path, f: impl FnOnce(&mut BuildCache)) -> Result<()> {
+        with_state_write_lock(&project_lock_path(project_root), || {
+            let mut cache = Self::load(project_root).unwrap_or_default();
+            f(&mut cache);
+            cache.write(project_root)
+        })?
+    }
+
+    fn write(&self, project_root: &Path) -> Result<()> {
+        let cache_path = project_root.join(STACY_DIR).join(CACHE_FILE);
 
         let content = serde_json::to_string_pretty(self)
             .map_err(|e| Error::Config(format!("Failed to serialize cache: {}", e)))?;
 
-        std::fs::write(&cache_path, content).map_err(|e| {
+        write_atomically(&cache_path, content.as_bytes()).map_err(|e| {
             Error::Config(format!(
                 "Failed to write cache file {}: {}",
                 cache_path.display(),
@@ -112,32 +136,49 @@ impl BuildCache {
     /// Get a cached entry for a script
     pub fn get(&self, script: &Path) -> Option<&CacheEntry> {
         // Try to canonicalize the path for consistent lookup
-        let key = script
-            .canonicalize()
-            .unwrap_or_else(|_| script.to_path_buf());
+        let key = paths::canonicalize_or_self(script);
         self.entries.get(&key)
     }
 
     /// Insert or update a cache entry for a script
     pub fn insert(&mut self, script: &Path, entry: CacheEntry) {
         // Use canonicalized path for consistent storage
-        let key = script
-            .canonicalize()
-            .unwrap_or_else(|_| script.to_path_buf());
+        let key = paths::canonicalize_or_self(script);
         self.entries.insert(key, entry);
     }
 
     /// Remove a cached entry for a script
     pub fn remove(&mut self, script: &Path) -> Option<CacheEntry> {
-        let key = script
-            .canonicalize()
-            .unwrap_or_else(|_| script.to_path_buf());
+        let key = paths::canonicalize_or_self(script);
         self.entries.remove(&key)
     }
 
+    /// Get the cached entry of a step running `script`
+    pub fn get_step(&self, definition_hash: &str, script: &Path) -> Option<&CacheEntry> {
+        let key = paths::canonicalize_or_self(script);
+        self.steps.get(definition_hash)?.get(&key)
+    }
+
+    /// Insert or update the entry of a step running `script`
+    pub fn insert_step(&mut self, definition_hash: &str, script: &Path, entry: CacheEntry) {
+        let key = paths::canonicalize_or_self(script);
+        self.steps
+            .entry(definition_hash.to_string())
+            .or_default()
+            .insert(key, entry);
+    }
+
+    /// Every entry, of runs and of steps
+    pub fn all_entries(&self) -> impl Iterator<Item = &CacheEntry> {
+        self.entries
+            .values()
+            .chain(self.steps.values().flat_map(HashMap::values))
+    }
+
     /// Clear all entries from the cache
     pub fn clear(&mut self) {
         self.entries.clear();
+        self.steps.clear();
     }
 
     /// Remove entries older than a given number of days
@@ -146,19 +187,23 @@ impl BuildCache {
             .checked_sub(std::time::Duration::from_secs(days as u64 * 24 * 60 * 60))
             .unwrap_or(SystemTime::UNIX_EPOCH);
 
-        let initial_count = self.entries.len();
+        let initial_count = self.len();
         self.entries.retain(|_, entry| entry.cached_at >= cutoff);
-        initial_count - self.entries.len()
+        for scripts in self.steps.values_mut() {
+            scripts.retain(|_, entry| entry.cached_at >= cutoff);
+        }
+        self.steps.retain(|_, scripts| !scripts.is_empty());
+        initial_count - self.len()
     }
 
     /// Get the number of cached entries
     pub fn len(&self) -> usize {
-        self.entries.len()
+        self.all_entries().count()
     }
 
     /// Check if cache is empty
     pub fn is_empty(&self) -> bool {
-        self.entries.is_empty()
+        self.len() == 0
     }
 
     /// Get total size of cached entries in bytes (approximate)
@@ -174,18 +219,20 @@ impl BuildCache {
     /// Delete the cache file from disk
     pub fn delete_file(project_root: &Path) -> Result<bool> {
         let cache_path = Self::cache_path(project_root);
-        if cache_path.exists() {
-            std::fs::remove_file(&cache_path).map_err(|e| {
-                Error::Config(format!(
-                    "Failed to delete cache file {}: {}",
-                    cache_path.display(),
-                    e
-                ))
-            })?;
-            Ok(true)
-        } else {
-            Ok(false)
+        if !cache_path.exists() {
+            return Ok(false);
         }
+        with_state_write_lock(&project_lock_path(project_root), || {
+            std::fs::remove_file(&cache_path)
+        })?
+        .map_err(|e| {
+            Error::Config(format!(
+                "Failed to delete cache file {}: {}",
+                cache_path.display(),
+                e
+            ))
+        })?;
+        Ok(true)
     }
 }
 
@@ -202,6 +249,21 @@ pub struct CacheEntry {
     /// SHA256 hash of the working directory path (if set via -C or --cd)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub working_dir_hash: Option<String>,
+    /// Resolved path of the Stata binary the script ran on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stata_binary: Option<String>,
+    /// Version that Stata reported (see [`engine::EngineIdentity`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stata_version: Option<String>,
+    /// SHA256 hashes of the data files the script reads (path -> hash)
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub data_hashes: HashMap<PathBuf, String>,
+    /// SHA256 hash of the task definition and arguments (task/test steps only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub definition_hash: Option<String>,
+    /// SHA256 hashes of declared input files (task steps only)
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub input_hashes: HashMap<PathBuf, String>,
     /// Cached execution result
     pub result: CachedResult,
     /// When this entry was cached
@@ -222,6 +284,11 @@ impl CacheEntry {
             dependency_hashes,
             lockfile_hash,
             working_dir_hash: None,
+            stata_binary: None,
+            stata_version: None,
+            data_hashes: HashMap::new(),
+            definition_hash: None,
+            input_hashes: HashMap::new(),
             result,
             cached_at: SystemTime::now(),
         }
@@ -240,11 +307,42 @@ impl CacheEntry {
             dependency_hashes,
             lockfile_hash,
             working_dir_hash,
+            stata_binary: None,
+            stata_version: None,
+            data_hashes: HashMap::new(),
+            definition_hash: None,
+            input_hashes: HashMap::new(),
             result,
             cached_at: SystemTime::now(),
         }
     }
 
+    /// Record the data files the script read
+    pub fn with_data(mut self, data_hashes: HashMap<PathBuf, String>) -> Self {
+        self.data_hashes = data_hashes;
+        self
+    }
+
+    /// Record the engine the script ran on
+    pub fn with_engine(mut self, engine: Option<&engine::EngineIdentity>) -> Self {
+        if let Some(engine) = engine {
+            self.stata_binary = Some(engine.binary.clone());
+            self.stata_version = engine.version.clone();
+        }
+        self
+    }
+
+    /// Key the entry on a step definition and its declared inputs
+    pub fn with_step(
+        mut self,
+        definition_hash: String,
+        input_hashes: HashMap<PathBuf, String>,
+    ) -> Self {
+        self.definition_hash = Some(definition_hash);
+        self.input_hashes = input_hashes;
+        self
+    }
+
     /// Get the age of this cache entry in seconds
     pub fn age_secs(&self) -> u64 {
         SystemTime::now()
@@ -267,6 +365,42 @@ pub struct CachedResult {
     pub errors: Vec<CachedError>,
 }
 
+impl CachedResult {
+    /// Capture the cacheable part of an execution result
+    pub fn from_execution(result: &crate::executor::ExecutionResult) -> Self {
+        let errors = result
+            .errors
+            .iter()
+            .map(|e| match e {
+                crate::error::StataError::StataCode {
+                    r_code,
+                    message,
+                    line_number,
+                    ..
+                } => CachedError {
+                    error_type: "StataCode".to_string(),
+                    r_code: Some(*r_code),
+                    message: message.clone(),
+                    line_number: *line_number,
+                },
+                crate::error::StataError::ProcessKilled { exit_code } => CachedError {
+                    error_type: "ProcessKilled".to_string(),
+                    r_code: None,
+                    message: format!("Process killed with exit code {}", exit_code),
+                    line_number: None,
+                },
+            })
+            .collect();
+
+        Self {
+            exit_code: result.exit_code,
+            success: result.success,
+            duration_secs: result.duration.as_secs_f64(),
+            errors,
+        }
+    }
+}
+
 /// Cached error information
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CachedError {
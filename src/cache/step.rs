@@ -0,0 +1,380 @@
+//! Build cache for scripts run as steps of `stacy task` and `stacy test`
+//!
+//! A step is keyed on everything `stacy run --cache` keys a script on (script,
+//! dependency tree, lockfile, working directory), plus the step's definition —
+//! which task or test ran it, with which arguments — and the content of the
+//! inputs it declares. Each definition has entries of its own, so neither a
+//! plain `stacy run --cache` of the script nor another task running it with
+//! other arguments replaces a step's entry. Only successful steps are
+//! recorded: a failed step runs again next time, whatever changed.
+
+use super::detect::{compare_entry, CacheStatus, RebuildReason};
+use super::engine::EngineIdentity;
+use super::hash::{
+    hash_data_dependencies, hash_dependency_tree, hash_inputs, hash_lockfile, hash_string,
+};
+use super::{BuildCache, CacheEntry, CachedResult};
+use crate::error::Result;
+use crate::executor::ExecutionResult;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// What a step is besides its script: its definition and declared inputs
+#[derive(Debug, Clone)]
+pub struct StepKey {
+    definition_hash: String,
+    inputs: Vec<PathBuf>,
+}
+
+impl StepKey {
+    /// Key a step on a textual description of its definition
+    pub fn new(definition: &str) -> Self {
+        Self {
+            definition_hash: hash_string(definition),
+            inputs: Vec::new(),
+        }
+    }
+
+    /// Declared input files or directories, relative to the project root
+    pub fn with_inputs(mut self, inputs: Vec<PathBuf>) -> Self {
+        self.inputs = inputs;
+        self
+    }
+}
+
+/// The build cache, shared by the steps of one task or test run
+///
+/// Loaded once up front and written back with [`StepCache::save`], so steps
+/// running in parallel don't race on `.stacy/cache/build.json`. Saving merges
+/// the steps recorded into the file as it is then, keeping what other stacy
+/// processes saved meanwhile.
+pub struct StepCache {
+    project_root: PathBuf,
+    force: bool,
+    engine: Option<EngineIdentity>,
+    cache: Mutex<BuildCache>,
+    /// Entries recorded by this run
+    recorded: Mutex<BuildCache>,
+}
+
+impl StepCache {
+    /// Load the project's build cache. With `force`, every check misses.
+    pub fn load(project_root: &Path, force: bool) -> Result<Self> {
+        Ok(Self {
+            project_root: project_root.to_path_buf(),
+            force,
+            engine: None,
+            cache: Mutex::new(BuildCache::load(project_root)?),
+            recorded: Mutex::new(BuildCache::new()),
+        })
+    }
+
+    /// Key entries on the Stata engine the steps run on
+    pub fn with_engine(mut self, engine: Option<EngineIdentity>) -> Self {
+        self.engine = engine;
+        self
+    }
+
+    /// Check whether a step can be skipped
+    pub fn check(
+        &self,
+        script: &Path,
+        working_dir: Option<&Path>,
+        key: &StepKey,
+    ) -> Result<CacheStatus> {
+        let status = self.lookup(script, working_dir, key)?;
+        match &status {
+            CacheStatus::Hit(_) => tracing::debug!(script = %script.display(), "build cache hit"),
+            CacheStatus::Miss(reason) => {
+                tracing::debug!(script = %script.display(), %reason, "build cache miss")
+            }
+        }
+        crate::metrics::counters::note_cache(status.is_hit());
+        Ok(status)
+    }
+
+    fn lookup(
+        &self,
+        script: &Path,
+        working_dir: Option<&Path>,
+        key: &StepKey,
+    ) -> Result<CacheStatus> {
+        if self.force {
+            return Ok(CacheStatus::Miss(RebuildReason::ForceRebuild));
+        }
+
+        let Some(cached) = self
+            .cache
+            .lock()
+            .unwrap()
+            .get_step(&key.definition_hash, script)
+            .cloned()
+        else {
+            return Ok(CacheStatus::Miss(RebuildReason::NotCached));
+        };
+
+        if let Some(reason) = compare_entry(
+            &cached,
+            script,
+            Some(&self.project_root),
+            working_dir,
+            self.engine.as_ref(),
+        )? {
+            return Ok(CacheStatus::Miss(reason));
+        }
+
+        let current = hash_inputs(&self.project_root, &key.inputs)?;
+        for path in current.keys() {
+            if !cached.input_hashes.contains_key(path) {
+                return Ok(CacheStatus::Miss(RebuildReason::InputAdded(
+                    path.display().to_string(),
+                )));
+            }
+        }
+        for (path, cached_hash) in &cached.input_hashes {
+            match current.get(path) {
+                None => {
+                    return Ok(CacheStatus::Miss(RebuildReason::InputRemoved(
+                        path.display().to_string(),
+                    )))
+                }
+                Some(hash) if hash != cached_hash => {
+                    return Ok(CacheStatus::Miss(RebuildReason::InputChanged(
+                        path.display().to_string(),
+                    )))
+                }
+                Some(_) => {}
+            }
+        }
+
+        Ok(CacheStatus::Hit(Box::new(cached)))
+    }
+
+    /// Record a step that ran. Failed steps are not recorded.
+    pub fn record(
+        &self,
+        script: &Path,
+        working_dir: Option<&Path>,
+        key: &StepKey,
+        result: &ExecutionResult,
+    ) -> Result<()> {
+        if !result.success {
+            return Ok(());
+        }
+
+        let hashes = hash_dependency_tree(script)?;
+        let entry = CacheEntry::with_working_dir(
+            hashes.script_hash,
+            hashes.dependency_hashes,
+            hash_lockfile(&self.project_root)?,
+            super::detect::hash_working_dir(working_dir),
+            CachedResult::from_execution(result),
+        )
+        .with_engine(self.engine.as_ref())
+        .with_data(hash_data_dependencies(script, working_dir)?)
+        .with_step(
+            key.definition_hash.clone(),
+            hash_inputs(&self.project_root, &key.inputs)?,
+        );
+
+        self.cache
+            .lock()
+            .unwrap()
+            .insert_step(&key.definition_hash, script, entry.clone());
+        self.recorded
+            .lock()
+            .unwrap()
+            .insert_step(&key.definition_hash, script, entry);
+        Ok(())
+    }
+
+    /// Write the cache back to `.stacy/cache/build.json`
+    pub fn save(&self) -> Result<()> {
+        let recorded = self.recorded.lock().unwrap();
+        BuildCache::update(&self.project_root, |cache| {
+            for (definition_hash, scripts) in &recorded.steps {
+                cache
+                    .steps
+                    .entry(definition_hash.clone())
+                    .or_default()
+                    .extend(scripts.clone());
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::detect::check_cache;
+    use std::fs;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    fn success() -> ExecutionResult {
+        ExecutionResult {
+            exit_code: 0,
+            success: true,
+            duration: Duration::from_secs(2),
+            log_file: PathBuf::from("step.log"),
+            errors: vec![],
+            parse_duration: Duration::ZERO,
+            metrics: None,
+            preamble: vec![],
+            discarded_installs: vec![],
+            adopath_changes: vec![],
+            error_trace: Vec::new(),
+            post_mortem: None,
+        }
+    }
+
+    fn project() -> (TempDir, PathBuf) {
+        let temp = TempDir::new().unwrap();
+        let script = temp.path().join("clean.do");
+        fs::write(&script, "use data/raw.dta\n").unwrap();
+        fs::create_dir_all(temp.path().join("data")).unwrap();
+        fs::write(temp.path().join("data/raw.csv"), "x\n1\n").unwrap();
+        (temp, script)
+    }
+
+    #[test]
+    fn test_step_hit_after_record() {
+        let (temp, script) = project();
+        let key = StepKey::new("task:clean").with_inputs(vec![PathBuf::from("data")]);
+
+        let cache = StepCache::load(temp.path(), false).unwrap();
+        assert!(cache.check(&script, None, &key).unwrap().is_miss());
+
+        cache.record(&script, None, &key, &success()).unwrap();
+        assert!(cache.check(&script, None, &key).unwrap().is_hit());
+
+        // Survives a save/load round trip
+        cache.save().unwrap();
+        let reloaded = StepCache::load(temp.path(), false).unwrap();
+        assert!(reloaded.check(&script, None, &key).unwrap().is_hit());
+    }
+
+    #[test]
+    fn test_save_keeps_entries_saved_meanwhile() {
+        let (temp, script) = project();
+        let other = temp.path().join("other.do");
+        fs::write(&other, "display 1\n").unwrap();
+        let key = StepKey::new("task:clean");
+
+        // Two runs of the same project, loaded before either saved
+        let first = StepCache::load(temp.path(), false).unwrap();
+        let second = StepCache::load(temp.path(), false).unwrap();
+        first.record(&script, None, &key, &success()).unwrap();
+        second.record(&other, None, &key, &success()).unwrap();
+        first.save().unwrap();
+        second.save().unwrap();
+
+        let reloaded = StepCache::load(temp.path(), false).unwrap();
+        assert!(reloaded.check(&script, None, &key).unwrap().is_hit());
+        assert!(reloaded.check(&other, None, &key).unwrap().is_hit());
+    }
+
+    #[test]
+    fn test_step_miss_on_input_change() {
+        let (temp, script) = project();
+        let key = StepKey::new("task:clean").with_inputs(vec![PathBuf::from("data")]);
+        let cache = StepCache::load(temp.path(), false).unwrap();
+        cache.record(&script, None, &key, &success()).unwrap();
+
+        fs::write(temp.path().join("data/raw.csv"), "x\n2\n").unwrap();
+        let status = cache.check(&script, None, &key).unwrap();
+        assert!(matches!(
+            status.reason(),
+            Some(RebuildReason::InputChanged(_))
+        ));
+
+        fs::write(temp.path().join("data/extra.csv"), "y\n").unwrap();
+        cache.record(&script, None, &key, &success()).unwrap();
+        fs::remove_file(temp.path().join("data/extra.csv")).unwrap();
+        let status = cache.check(&script, None, &key).unwrap();
+        assert!(matches!(
+            status.reason(),
+            Some(RebuildReason::InputRemoved(_))
+        ));
+    }
+
+    #[test]
+    fn test_steps_with_other_definitions_keep_their_entries() {
+        let (temp, script) = project();
+        let plain = StepKey::new("task:clean");
+        let robust = StepKey::new("task:clean robust=1");
+        let cache = StepCache::load(temp.path(), false).unwrap();
+        cache.record(&script, None, &plain, &success()).unwrap();
+        assert_eq!(
+            cache.check(&script, None, &robust).unwrap().reason(),
+            Some(&RebuildReason::NotCached)
+        );
+
+        // Both recorded: neither invalidates the other
+        cache.record(&script, None, &robust, &success()).unwrap();
+        cache.save().unwrap();
+        let cache = StepCache::load(temp.path(), false).unwrap();
+        assert!(cache.check(&script, None, &plain).unwrap().is_hit());
+        assert!(cache.check(&script, None, &robust).unwrap().is_hit());
+    }
+
+    #[test]
+    fn test_step_and_run_of_one_script_keep_their_entries() {
+        let (temp, script) = project();
+        let key = StepKey::new("task:clean");
+        let cache = StepCache::load(temp.path(), false).unwrap();
+        cache.record(&script, None, &key, &success()).unwrap();
+        cache.save().unwrap();
+
+        // A plain run of the script doesn't see the step's entry...
+        let runs = BuildCache::load(temp.path()).unwrap();
+        let status = check_cache(&runs, &script, Some(temp.path()), false).unwrap();
+        assert_eq!(status.reason(), Some(&RebuildReason::NotCached));
+
+        // ...and recording one leaves the step's entry alone
+        let hashes = hash_dependency_tree(&script).unwrap();
+        let entry = CacheEntry::new(
+            hashes.script_hash,
+            hashes.dependency_hashes,
+            hash_lockfile(temp.path()).unwrap(),
+            CachedResult::from_execution(&success()),
+        );
+        BuildCache::update(temp.path(), |cache| cache.insert(&script, entry)).unwrap();
+        let cache = StepCache::load(temp.path(), false).unwrap();
+        assert!(cache.check(&script, None, &key).unwrap().is_hit());
+        assert_eq!(BuildCache::load(temp.path()).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_step_failure_not_recorded() {
+        let (temp, script) = project();
+        let key = StepKey::new("task:clean");
+        let cache = StepCache::load(temp.path(), false).unwrap();
+
+        let mut failed = success();
+        failed.success = false;
+        failed.exit_code = 1;
+        cache.record(&script, None, &key, &failed).unwrap();
+
+        assert_eq!(
+            cache.check(&script, None, &key).unwrap().reason(),
+            Some(&RebuildReason::NotCached)
+        );
+    }
+
+    #[test]
+    fn test_step_force_misses() {
+        let (temp, script) = project();
+        let key = StepKey::new("task:clean");
+        StepCache::load(temp.path(), false)
+            .unwrap()
+            .record(&script, None, &key, &success())
+            .unwrap();
+
+        let forced = StepCache::load(temp.path(), true).unwrap();
+        assert_eq!(
+            forced.check(&script, None, &key).unwrap().reason(),
+            Some(&RebuildReason::ForceRebuild)
+        );
+    }
+}
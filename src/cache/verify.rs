@@ -0,0 +1,362 @@
+//! Consistency check for the build cache (`stacy cache verify`)
+//!
+//! Reads `.stacy/cache/build.json` leniently, entry by entry, so one corrupt
+//! entry doesn't hide the others, and reports what `BuildCache::load` would
+//! otherwise paper over: an unreadable file or a format version it ignores.
+
+use super::hash::hash_file;
+use super::{BuildCache, CacheEntry, CACHE_VERSION};
+use crate::error::{Error, Result};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// What is wrong with (part of) the build cache
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProblemKind {
+    /// The cache file is not valid JSON, or not a cache at all
+    UnreadableFile,
+    /// The cache was written in another format version and is ignored
+    VersionDrift,
+    /// An entry doesn't deserialize
+    CorruptEntry,
+    /// The entry's script no longer exists
+    MissingScript,
+    /// The script changed since it was cached, so the entry can never hit
+    StaleHash,
+    /// The entry holds something that isn't a SHA256 hash
+    InvalidHash,
+}
+
+impl std::fmt::Display for ProblemKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProblemKind::UnreadableFile => write!(f, "unreadable cache file"),
+            ProblemKind::VersionDrift => write!(f, "cache format version drift"),
+            ProblemKind::CorruptEntry => write!(f, "corrupt entry"),
+            ProblemKind::MissingScript => write!(f, "script no longer exists"),
+            ProblemKind::StaleHash => write!(f, "script hash mismatch"),
+            ProblemKind::InvalidHash => write!(f, "invalid hash"),
+        }
+    }
+}
+
+/// One problem found by [`verify`]
+#[derive(Debug, Clone, Serialize)]
+pub struct CacheProblem {
+    pub kind: ProblemKind,
+    /// Script the entry belongs to (None for whole-file problems)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub script: Option<PathBuf>,
+    pub detail: String,
+}
+
+/// Result of checking a project's build cache
+#[derive(Debug)]
+pub struct VerifyReport {
+    /// Number of entries found in the file
+    pub entries_checked: usize,
+    pub problems: Vec<CacheProblem>,
+    /// The entries that passed every check
+    pub valid: BuildCache,
+    /// Whether the file must go as a whole (unreadable, or another version)
+    file_unusable: bool,
+}
+
+impl VerifyReport {
+    pub fn is_healthy(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    /// Drop every bad entry, keeping the valid ones. Returns the number of
+    /// entries removed.
+    pub fn repair(&self, project_root: &Path) -> Result<usize> {
+        if self.is_healthy() {
+            return Ok(0);
+        }
+        if self.file_unusable {
+            BuildCache::delete_file(project_root)?;
+            return Ok(self.entries_checked);
+        }
+        self.valid.save(project_root)?;
+        Ok(self.entries_checked - self.valid.len())
+    }
+}
+
+/// Check every entry in the project's build cache
+pub fn verify(project_root: &Path) -> Result<VerifyReport> {
+    let cache_path = BuildCache::cache_path(project_root);
+    let mut report = VerifyReport {
+        entries_checked: 0,
+        problems: Vec::new(),
+        valid: BuildCache::new(),
+        file_unusable: false,
+    };
+
+    if !cache_path.exists() {
+        return Ok(report);
+    }
+
+    let content = std::fs::read_to_string(&cache_path).map_err(|e| {
+        Error::Config(format!(
+            "Failed to read cache file {}: {}",
+            cache_path.display(),
+            e
+        ))
+    })?;
+
+    let json: serde_json::Value = match serde_json::from_str(&content) {
+        Ok(json) => json,
+        Err(e) => {
+            report.file_unusable = true;
+            report
+                .problems
+                .push(file_problem(ProblemKind::UnreadableFile, e));
+            return Ok(report);
+        }
+    };
+
+    let entries = json.get("entries").and_then(|e| e.as_object());
+    let version = json.get("version").and_then(|v| v.as_u64());
+    let Some(entries) = entries else {
+        report.file_unusable = true;
+        report.problems.push(file_problem(
+            ProblemKind::UnreadableFile,
+            "no \"entries\" table",
+        ));
+        return Ok(report);
+    };
+    // Entries of task and test steps, by definition hash
+    let steps = json.get("steps").and_then(|s| s.as_object());
+    report.entries_checked = entries.len()
+        + steps
+            .into_iter()
+            .flat_map(|steps| steps.values())
+            .filter_map(|scripts| scripts.as_object())
+            .map(|scripts| scripts.len())
+            .sum::<usize>();
+
+    if version != Some(CACHE_VERSION as u64) {
+        report.file_unusable = true;
+        report.problems.push(file_problem(
+            ProblemKind::VersionDrift,
+            format!(
+                "written as version {}, this stacy reads version {}; every entry is ignored",
+                version.map_or("(none)".to_string(), |v| v.to_string()),
+                CACHE_VERSION
+            ),
+        ));
+        return Ok(report);
+    }
+
+    for (path, entry) in check_entries(entries, &mut report.problems) {
+        report.valid.entries.insert(path, entry);
+    }
+    let mut definitions: Vec<_> = steps.into_iter().flatten().collect();
+    definitions.sort_by_key(|(definition, _)| *definition);
+    for (definition, scripts) in definitions {
+        let Some(scripts) = scripts.as_object() else {
+            report.problems.push(CacheProblem {
+                kind: ProblemKind::CorruptEntry,
+                script: None,
+                detail: format!("steps of definition {} are not a table", definition),
+            });
+            continue;
+        };
+        for (path, entry) in check_entries(scripts, &mut report.problems) {
+            report
+                .valid
+                .steps
+                .entry(definition.clone())
+                .or_default()
+                .insert(path, entry);
+        }
+    }
+
+    Ok(report)
+}
+
+/// The entries of `entries` (by script) that pass every check; a problem is
+/// recorded for each of the others
+fn check_entries(
+    entries: &serde_json::Map<String, serde_json::Value>,
+    problems: &mut Vec<CacheProblem>,
+) -> Vec<(PathBuf, CacheEntry)> {
+    let mut valid = Vec::new();
+    let mut scripts: Vec<_> = entries.keys().collect();
+    scripts.sort();
+    for script in scripts {
+        let path = PathBuf::from(script);
+        let problem = match serde_json::from_value::<CacheEntry>(entries[script].clone()) {
+            Err(e) => Some((ProblemKind::CorruptEntry, e.to_string())),
+            Ok(entry) => match check_entry(&path, &entry) {
+                Some(problem) => Some(problem),
+                None => {
+                    valid.push((path.clone(), entry));
+                    None
+                }
+            },
+        };
+
+        if let Some((kind, detail)) = problem {
+            problems.push(CacheProblem {
+                kind,
+                script: Some(path),
+                detail,
+            });
+        }
+    }
+    valid
+}
+
+fn check_entry(script: &Path, entry: &CacheEntry) -> Option<(ProblemKind, String)> {
+    let hashes = std::iter::once(&entry.script_hash)
+        .chain(entry.dependency_hashes.values())
+        .chain(entry.data_hashes.values())
+        .chain(entry.input_hashes.values())
+        .chain(entry.lockfile_hash.iter())
+        .chain(entry.working_dir_hash.iter())
+        .chain(entry.definition_hash.iter());
+    for hash in hashes {
+        if !is_sha256(hash) {
+            return Some((ProblemKind::InvalidHash, format!("'{}'", hash)));
+        }
+    }
+
+    if !script.is_file() {
+        return Some((ProblemKind::MissingScript, script.display().to_string()));
+    }
+
+    match hash_file(script) {
+        Ok(current) if current == entry.script_hash => None,
+        Ok(_) => Some((
+            ProblemKind::StaleHash,
+            "script changed since it was cached".to_string(),
+        )),
+        Err(e) => Some((ProblemKind::MissingScript, e.to_string())),
+    }
+}
+
+fn is_sha256(hash: &str) -> bool {
+    hash.len() == 64 && hash.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+fn file_problem(kind: ProblemKind, detail: impl ToString) -> CacheProblem {
+    CacheProblem {
+        kind,
+        script: None,
+        detail: detail.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::CachedResult;
+    use std::collections::HashMap;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn entry(script_hash: String) -> CacheEntry {
+        CacheEntry::new(
+            script_hash,
+            HashMap::new(),
+            None,
+            CachedResult {
+                exit_code: 0,
+                success: true,
+                duration_secs: 1.0,
+                errors: vec![],
+            },
+        )
+    }
+
+    fn write_cache(root: &Path, content: &str) {
+        let path = BuildCache::cache_path(root);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_verify_missing_cache_is_healthy() {
+        let temp = TempDir::new().unwrap();
+        let report = verify(temp.path()).unwrap();
+        assert!(report.is_healthy());
+        assert_eq!(report.entries_checked, 0);
+    }
+
+    #[test]
+    fn test_verify_and_repair_bad_entries() {
+        let temp = TempDir::new().unwrap();
+        let good = temp.path().join("good.do");
+        let stale = temp.path().join("stale.do");
+        fs::write(&good, "display 1").unwrap();
+        fs::write(&stale, "display 2").unwrap();
+
+        let mut cache = BuildCache::new();
+        cache.insert(&good, entry(hash_file(&good).unwrap()));
+        cache.insert(&stale, entry(hash_file(&stale).unwrap()));
+        cache.insert(
+            &temp.path().join("gone.do"),
+            entry(hash_file(&good).unwrap()),
+        );
+        cache.insert(&temp.path().join("bad.do"), entry("not-a-hash".to_string()));
+        // Steps are checked like runs
+        cache.insert_step("clean", &good, entry(hash_file(&good).unwrap()));
+        cache.insert_step("clean", &stale, entry(hash_file(&stale).unwrap()));
+        cache.save(temp.path()).unwrap();
+        fs::write(&stale, "display 3").unwrap();
+
+        // Corrupt one entry in place
+        let path = BuildCache::cache_path(temp.path());
+        let mut json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        json["entries"]["/corrupt.do"] = serde_json::json!({"script_hash": 42});
+        fs::write(&path, json.to_string()).unwrap();
+
+        let report = verify(temp.path()).unwrap();
+        assert_eq!(report.entries_checked, 7);
+        let mut kinds: Vec<_> = report.problems.iter().map(|p| p.kind).collect();
+        kinds.sort_by_key(|k| k.to_string());
+        assert_eq!(
+            kinds,
+            vec![
+                ProblemKind::CorruptEntry,
+                ProblemKind::InvalidHash,
+                ProblemKind::StaleHash,
+                ProblemKind::StaleHash,
+                ProblemKind::MissingScript,
+            ]
+        );
+
+        assert_eq!(report.repair(temp.path()).unwrap(), 5);
+        let repaired = BuildCache::load(temp.path()).unwrap();
+        assert_eq!(repaired.len(), 2);
+        assert!(repaired.get(&good).is_some());
+        assert!(repaired.get_step("clean", &good).is_some());
+        assert!(verify(temp.path()).unwrap().is_healthy());
+    }
+
+    #[test]
+    fn test_verify_unreadable_file() {
+        let temp = TempDir::new().unwrap();
+        write_cache(temp.path(), "{ \"version\": 1, \"entries\": {");
+
+        let report = verify(temp.path()).unwrap();
+        assert_eq!(report.problems[0].kind, ProblemKind::UnreadableFile);
+
+        report.repair(temp.path()).unwrap();
+        assert!(!BuildCache::cache_path(temp.path()).exists());
+    }
+
+    #[test]
+    fn test_verify_version_drift() {
+        let temp = TempDir::new().unwrap();
+        write_cache(temp.path(), r#"{"version": 0, "entries": {"a.do": {}}}"#);
+
+        let report = verify(temp.path()).unwrap();
+        assert_eq!(report.problems.len(), 1);
+        assert_eq!(report.problems[0].kind, ProblemKind::VersionDrift);
+        assert_eq!(report.repair(temp.path()).unwrap(), 1);
+    }
+}
@@ -5,16 +5,25 @@
 use crate::cli::output_format::OutputFormat;
 use crate::cli::output_types::{AddOutput, CommandOutput};
 use crate::error::{Error, Result};
+use crate::executor::previous_log::unified_diff;
+use crate::packages::collisions;
 use crate::packages::dep_scan;
 use crate::packages::global_cache;
 use crate::packages::hints;
 use crate::packages::installer::{
-    install_from_local, install_from_net, install_from_ssc, install_package_github,
+    install_from_local, install_from_net, install_from_ssc, install_package_github, link_local,
+    preview_package,
 };
-use crate::project::config::{load_config, write_config, DependencyGroup, PackageSpec};
-use crate::project::Project;
+use crate::packages::lockfile::{self, load_lockfile, save_lockfile, saved_content};
+use crate::packages::requirements::read_requirements;
+use crate::project::config::{
+    read_config, render_config, write_config, Config, DependencyGroup, PackageSpec, PackagesSection,
+};
+use crate::project::{profile, Lockfile, PackageSource, Project};
+use crate::utils::lock::{project_lock_path, with_state_lock};
 use clap::Args;
 use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 
 #[derive(Args)]
 #[command(after_help = "\
@@ -27,12 +36,22 @@ Examples:
                                           Add from URL (net install)
   stacy add myutils --source local:./lib/myutils/
                                           Add from local directory
-  stacy add texdoc --dev                  Add as dev dependency")]
+  stacy add myutils --source local:../myutils --editable
+                                          Link a package you are writing
+  stacy add texdoc --dev                  Add as dev dependency
+  stacy add reghdfe --dry-run             Show what adding reghdfe would change
+  stacy add --from-file packages.txt      Add every package listed in a file
+  stacy add --from-file requirements.do   Add the packages a do-file installs")]
 pub struct AddArgs {
     /// Package names to add
-    #[arg(value_name = "PACKAGE", required = true)]
+    #[arg(value_name = "PACKAGE", required_unless_present = "from_file")]
     pub packages: Vec<String>,
 
+    /// Also add the packages listed in FILE: one `name [source]` per line, or
+    /// the `ssc install`/`net install`/`github install` lines of a .do file
+    #[arg(long, value_name = "FILE")]
+    pub from_file: Option<PathBuf>,
+
     /// Package source: `ssc` (default), `github:user/repo[@ref]`, `net:URL`, or `local:path`
     #[arg(long, default_value = "ssc")]
     pub source: String,
@@ -45,9 +64,33 @@ pub struct AddArgs {
     #[arg(long, conflicts_with = "dev")]
     pub test: bool,
 
+    /// Link a `local:` directory instead of copying it, so edits to its .ado
+    /// files take effect in the next run (`dev = true` in stacy.toml)
+    #[arg(long)]
+    pub editable: bool,
+
+    /// Fetch the packages and show what would be installed and how stacy.toml
+    /// and the lockfile would change, without writing anything
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Retries for a failed download, with exponential backoff (default 3,
+    /// or $STACY_NETWORK_RETRIES)
+    #[arg(long, value_name = "N")]
+    pub network_retries: Option<u32>,
+
+    /// Report every failed download attempt (global `-v`)
+    #[arg(skip)]
+    pub verbose: bool,
+
     /// Output format: human (default), json, or stata
     #[arg(long, value_enum, default_value = "human")]
     pub format: OutputFormat,
+
+    /// Leave out progress and summary lines (global `-q`); failures are
+    /// still reported
+    #[arg(skip)]
+    pub quiet: bool,
 }
 
 /// Parsed package source for internal use
@@ -78,20 +121,73 @@ struct AddedPackage {
 }
 
 pub fn execute(args: &AddArgs) -> Result<()> {
-    let format = args.format;
+    crate::packages::http::configure(args.network_retries, args.verbose);
 
     // Find project (must exist for add)
     let project = Project::find()?.ok_or_else(|| {
         Error::Config("Not in a stacy project. Run 'stacy init' first.".to_string())
     })?;
 
+    if args.dry_run {
+        return add_to_project(args, &project);
+    }
+    with_state_lock(&project_lock_path(&project.root), || {
+        add_to_project(args, &project)
+    })?
+}
+
+fn add_to_project(args: &AddArgs, project: &Project) -> Result<()> {
+    let format = args.format;
+    let chatty = format == OutputFormat::Human && !args.quiet;
+
     // Load config
-    let mut config = load_config(&project.root)?
+    let mut config = read_config(&project.root)?
         .ok_or_else(|| Error::Config("No stacy.toml found. Run 'stacy init' first.".to_string()))?;
+    // What the commands see: under --env-profile, the profile's packages
+    let mut packages = profile::active_packages(&config)?;
 
-    // Parse source
-    let source = parse_source(&args.source)?;
-    let source_str = args.source.clone();
+    // Kept to undo the lockfile changes if the new packages collide
+    let original_lockfile = load_lockfile(&project.root)?;
+
+    // Every package to add, with its source. A source named in --from-file
+    // wins over --source. All sources are checked before anything installs.
+    let mut requests: Vec<(String, String, ParsedSource)> = Vec::new();
+    for package in &args.packages {
+        let source = parse_source(&args.source)?;
+        requests.push((package.to_lowercase(), args.source.clone(), source));
+    }
+    if let Some(path) = &args.from_file {
+        for req in read_requirements(path)? {
+            let source_str = req.source.unwrap_or_else(|| args.source.clone());
+            let source = parse_source(&source_str).map_err(|e| match e {
+                Error::Config(msg) => {
+                    Error::Config(format!("{}:{}: {}", path.display(), req.line, msg))
+                }
+                other => other,
+            })?;
+            requests.push((req.name, source_str, source));
+        }
+    }
+    if args.editable {
+        if let Some((name, source_str, _)) = requests
+            .iter()
+            .find(|(_, _, source)| !matches!(source, ParsedSource::Local { .. }))
+        {
+            return Err(Error::Config(format!(
+                "--editable links a local: directory; {} comes from '{}'",
+                name, source_str
+            )));
+        }
+    }
+    if requests.is_empty() {
+        return Err(Error::Config(format!(
+            "No packages listed in {}",
+            args.from_file
+                .as_deref()
+                .map(|p| p.display().to_string())
+                .unwrap_or_default()
+        )));
+    }
 
     // Determine dependency group
     let group = if args.test {
@@ -102,11 +198,23 @@ pub fn execute(args: &AddArgs) -> Result<()> {
         DependencyGroup::Production
     };
 
-    if format == OutputFormat::Human {
+    if args.dry_run {
+        return preview(
+            args,
+            &project.root,
+            config,
+            packages,
+            original_lockfile,
+            requests,
+            group,
+        );
+    }
+
+    if chatty {
         let dep_type = group.as_str();
         println!(
             "Adding {} package(s) as {} dependency...",
-            args.packages.len(),
+            requests.len(),
             dep_type
         );
         println!();
@@ -114,12 +222,10 @@ pub fn execute(args: &AddArgs) -> Result<()> {
 
     let mut results: Vec<AddedPackage> = Vec::new();
 
-    for package in &args.packages {
-        let package_lower = package.to_lowercase();
-
+    for (package_lower, source_str, source) in requests {
         // Check if already in config
-        if config.packages.has_package(&package_lower) {
-            if format == OutputFormat::Human {
+        if packages.has_package(&package_lower) {
+            if chatty {
                 println!("  {} is already in dependencies, skipping", package_lower);
             }
             results.push(AddedPackage {
@@ -150,6 +256,9 @@ pub fn execute(args: &AddArgs) -> Result<()> {
             ParsedSource::Net { url } => {
                 install_from_net(&package_lower, url, &project.root, group.as_str())
             }
+            ParsedSource::Local { path } if args.editable => {
+                link_local(&package_lower, path, &project.root, group.as_str())
+            }
             ParsedSource::Local { path } => {
                 install_from_local(&package_lower, path, &project.root, group.as_str())
             }
@@ -158,64 +267,38 @@ pub fn execute(args: &AddArgs) -> Result<()> {
         match install_result {
             Ok(result) => {
                 // Add to config
-                let spec = PackageSpec::simple(source_str.clone());
-                config
-                    .packages
-                    .add_dependency(package_lower.clone(), spec, group);
-
-                if format == OutputFormat::Human {
+                let spec = if args.editable {
+                    PackageSpec::editable(source_str.clone())
+                } else {
+                    PackageSpec::simple(source_str.clone())
+                };
+                packages.add_dependency(package_lower.clone(), spec.clone(), group);
+                profile::add_package(&mut config, package_lower.clone(), spec, group);
+
+                if chatty {
                     println!("  + {} ({})", package_lower, result.version);
 
-                    // Warn if the package declares a newer minimum Stata version
-                    // than the one stacy last detected. Silent when either
-                    // version is unknown (e.g. error codes never extracted).
-                    if let Some(required) = &result.required_stata_version {
-                        if let Ok(Some(db)) = crate::error::error_db::ErrorCodeCache::load() {
-                            if let Some(detected) = db.stata_version.as_deref() {
-                                if let Some(warning) =
-                                    stata_version_warning(&package_lower, required, detected)
-                                {
-                                    println!("    warning: {}", warning);
-                                }
-                            }
-                        }
+                    if let Some(warning) = detected_stata_warning(
+                        &package_lower,
+                        result.required_stata_version.as_deref(),
+                    ) {
+                        println!("    warning: {}", warning);
                     }
 
                     // Scan installed files for implicit dependencies
                     let installed: HashSet<String> =
-                        config.packages.all_package_names().into_iter().collect();
-                    if let Ok(cache_dir) =
-                        global_cache::package_path(&package_lower, &result.version)
-                    {
-                        let mut missing =
-                            dep_scan::find_missing_deps(&package_lower, &cache_dir, &installed);
-                        // Supplement the .ado scan with deps the author declared
-                        // on the manifest's `Requires:` line — these catch
-                        // dependencies resolved dynamically at runtime that
-                        // static scanning misses. Names the package ships itself
-                        // are internal files, not packages to install.
-                        let provided = dep_scan::provided_names(&package_lower, &cache_dir);
-                        for dep in &result.declared_deps {
-                            if !provided.contains(dep)
-                                && !installed.contains(dep)
-                                && !missing.contains(dep)
-                            {
-                                missing.push(dep.clone());
-                            }
-                        }
-                        missing.sort();
-                        if !missing.is_empty() {
-                            println!(
-                                "    hint: {} appears to need {}. Run: stacy add {}",
-                                package_lower,
-                                missing.join(", "),
-                                missing.join(" ")
-                            );
-                        } else if let Some(hint) = hints::get_hint(&package_lower) {
-                            // Fallback to hardcoded hints for edge cases
-                            println!("    hint: {}", hint);
-                        }
-                    } else if let Some(hint) = hints::get_hint(&package_lower) {
+                        packages.all_package_names().into_iter().collect();
+                    let hint = match global_cache::package_path(&package_lower, &result.version) {
+                        Ok(cache_dir) => dependency_hint(
+                            &package_lower,
+                            dep_scan::find_missing_deps(&package_lower, &cache_dir, &installed),
+                            &result.declared_deps,
+                            &dep_scan::provided_names(&package_lower, &cache_dir),
+                            &installed,
+                        ),
+                        Err(_) => hints::get_hint(&package_lower).map(str::to_string),
+                    };
+                    if let Some(hint) = hint {
                         println!("    hint: {}", hint);
                     }
                 }
@@ -243,10 +326,39 @@ pub fn execute(args: &AddArgs) -> Result<()> {
         }
     }
 
+    // A new package shipping an .ado file another package already provides:
+    // under the error policy, undo the lockfile changes and add nothing
+    if results.iter().any(|r| r.success && r.error.is_none()) {
+        if let Some(lockfile) = load_lockfile(&project.root)? {
+            let found = collisions::find_collisions(&collisions::locked_ado_files(&lockfile, None));
+            if let Err(e) = collisions::enforce(&found, packages.on_collision) {
+                match &original_lockfile {
+                    Some(original) => save_lockfile(&project.root, original)?,
+                    None => std::fs::remove_file(project.root.join(profile::lockfile_name()))?,
+                }
+                return Err(e);
+            }
+        }
+    }
+
     // Write updated config
     write_config(&config, &project.root)?;
 
-    // Calculate summary
+    let output = summarize(&results, group, false);
+
+    // Output results
+    match format {
+        OutputFormat::Json => print_json_output(&results, &output),
+        OutputFormat::Stata => println!("{}", output.to_stata()),
+        OutputFormat::Human if args.quiet => {}
+        OutputFormat::Human => print_human_summary(&output),
+    }
+
+    failed_error(&results)
+}
+
+/// Counts and status of a batch of packages
+fn summarize(results: &[AddedPackage], group: DependencyGroup, dry_run: bool) -> AddOutput {
     let added_count = results
         .iter()
         .filter(|r| r.success && r.error.is_none())
@@ -265,38 +377,273 @@ pub fn execute(args: &AddArgs) -> Result<()> {
         "success"
     };
 
-    let output = AddOutput {
+    AddOutput {
         status: status.to_string(),
         added: added_count,
         skipped: skipped_count,
         failed: failed_count,
         total: results.len() as i32,
         group: group.as_str().to_string(),
-    };
+        dry_run,
+    }
+}
+
+/// Any package that failed to install is a package the caller asked for and
+/// did not get — a partial batch is a failure, not a success.
+fn failed_error(results: &[AddedPackage]) -> Result<()> {
+    let failed_names: Vec<&str> = results
+        .iter()
+        .filter(|r| !r.success)
+        .map(|r| r.name.as_str())
+        .collect();
+    if failed_names.is_empty() {
+        return Ok(());
+    }
+    Err(Error::Config(format!(
+        "{} package(s) failed to add: {}",
+        failed_names.len(),
+        failed_names.join(", ")
+    )))
+}
+
+/// `--dry-run`: fetch every package and show what adding it would record and
+/// install, and how stacy.toml and the lockfile would change. Nothing is
+/// written, not even to the package cache.
+fn preview(
+    args: &AddArgs,
+    project_root: &Path,
+    mut config: Config,
+    mut packages: PackagesSection,
+    lockfile: Option<Lockfile>,
+    requests: Vec<(String, String, ParsedSource)>,
+    group: DependencyGroup,
+) -> Result<()> {
+    let format = args.format;
+    let chatty = format == OutputFormat::Human && !args.quiet;
+    if chatty {
+        println!(
+            "Resolving {} package(s) as {} dependency (dry run)...",
+            requests.len(),
+            group.as_str()
+        );
+        println!();
+    }
+
+    let mut new_lockfile = lockfile.unwrap_or_else(lockfile::create_lockfile);
+    let mut results: Vec<AddedPackage> = Vec::new();
+    // What each package would bring, for --format json
+    let mut details: Vec<serde_json::Value> = Vec::new();
+
+    for (package_lower, source_str, source) in requests {
+        if packages.has_package(&package_lower) {
+            if chatty {
+                println!("  {} is already in dependencies, skipping", package_lower);
+            }
+            results.push(AddedPackage {
+                name: package_lower,
+                version: "existing".to_string(),
+                source: source_str,
+                success: true,
+                error: Some("Already in dependencies".to_string()),
+            });
+            details.push(serde_json::Value::Null);
+            continue;
+        }
+
+        let source = package_source(&package_lower, &source, args.editable);
+        let preview = match preview_package(&package_lower, source, project_root, group.as_str()) {
+            Ok(preview) => preview,
+            Err(e) => {
+                if format == OutputFormat::Human {
+                    eprintln!("  x {} failed: {}", package_lower, e);
+                }
+                results.push(AddedPackage {
+                    name: package_lower,
+                    version: "".to_string(),
+                    source: source_str,
+                    success: false,
+                    error: Some(e.to_string()),
+                });
+                details.push(serde_json::Value::Null);
+                continue;
+            }
+        };
+
+        let installed: HashSet<String> = packages.all_package_names().into_iter().collect();
+        let hint = dependency_hint(
+            &package_lower,
+            dep_scan::find_missing_deps_in_files(&package_lower, &preview.files, &installed),
+            &preview.declared_deps,
+            &dep_scan::provided_names_in_files(&package_lower, &preview.files),
+            &installed,
+        );
+        let warning =
+            detected_stata_warning(&package_lower, preview.required_stata_version.as_deref());
+        let files: Vec<&str> = preview.files.iter().map(|f| f.name.as_str()).collect();
+        if chatty {
+            println!(
+                "  + {} {} ({})",
+                package_lower, preview.entry.version, source_str
+            );
+            if let Some(checksum) = &preview.entry.checksum {
+                println!("    checksum: {}", checksum);
+            }
+            println!("    files: {}", files.join(", "));
+            if let Some(warning) = &warning {
+                println!("    warning: {}", warning);
+            }
+            if let Some(hint) = &hint {
+                println!("    hint: {}", hint);
+            }
+        }
+        details.push(serde_json::json!({
+            "checksum": preview.entry.checksum,
+            "files": files,
+            "warning": warning,
+            "hint": hint,
+        }));
+
+        let spec = if args.editable {
+            PackageSpec::editable(source_str.clone())
+        } else {
+            PackageSpec::simple(source_str.clone())
+        };
+        packages.add_dependency(package_lower.clone(), spec.clone(), group);
+        profile::add_package(&mut config, package_lower.clone(), spec, group);
+        results.push(AddedPackage {
+            name: package_lower.clone(),
+            version: preview.entry.version.clone(),
+            source: source_str,
+            success: true,
+            error: None,
+        });
+        lockfile::add_package(&mut new_lockfile, &package_lower, preview.entry);
+    }
+
+    let output = summarize(&results, group, true);
+
+    // The files as `add` would write them, against what is there now
+    let current = |name: &str| std::fs::read_to_string(project_root.join(name)).unwrap_or_default();
+    let mut changes = vec![(
+        "stacy.toml".to_string(),
+        file_diff(
+            &current("stacy.toml"),
+            &render_config(&config)?,
+            "stacy.toml",
+        ),
+    )];
+    if output.added > 0 {
+        let name = profile::lockfile_name();
+        let diff = file_diff(&current(&name), &saved_content(&new_lockfile)?, &name);
+        changes.push((name, diff));
+    }
 
-    // Output results
     match format {
-        OutputFormat::Json => print_json_output(&results, &output),
+        OutputFormat::Json => {
+            let mut json_output = json_output(&results, &output);
+            for (package, detail) in json_output["packages"]
+                .as_array_mut()
+                .into_iter()
+                .flatten()
+                .zip(details)
+            {
+                if let serde_json::Value::Object(detail) = detail {
+                    package.as_object_mut().unwrap().extend(detail);
+                }
+            }
+            json_output["changes"] = changes
+                .iter()
+                .map(|(file, diff)| (file.clone(), serde_json::json!(diff)))
+                .collect::<serde_json::Map<_, _>>()
+                .into();
+            println!("{}", serde_json::to_string_pretty(&json_output).unwrap());
+        }
         OutputFormat::Stata => println!("{}", output.to_stata()),
-        OutputFormat::Human => print_human_summary(&output),
+        OutputFormat::Human if args.quiet => {}
+        OutputFormat::Human => {
+            for (file, diff) in &changes {
+                println!();
+                match diff {
+                    Some(diff) => print!("{}", diff),
+                    None => println!("{} would not change", file),
+                }
+            }
+            println!();
+            println!("Nothing written (--dry-run).");
+        }
     }
 
-    // Any package that failed to install is a package the caller asked for and
-    // did not get — a partial batch is a failure, not a success.
-    if failed_count > 0 {
-        let failed_names: Vec<&str> = results
-            .iter()
-            .filter(|r| !r.success)
-            .map(|r| r.name.as_str())
-            .collect();
-        return Err(Error::Config(format!(
-            "{} package(s) failed to add: {}",
-            failed_count,
-            failed_names.join(", ")
-        )));
+    failed_error(&results)
+}
+
+/// The source `add` records for a package requested from `source`
+fn package_source(name: &str, source: &ParsedSource, editable: bool) -> PackageSource {
+    match source {
+        ParsedSource::SSC => PackageSource::SSC {
+            name: name.to_string(),
+        },
+        ParsedSource::GitHub {
+            user,
+            repo,
+            git_ref,
+        } => PackageSource::GitHub {
+            repo: format!("{}/{}", user, repo),
+            tag: git_ref.clone().unwrap_or_else(|| "main".to_string()),
+            commit: None,
+            pkg_dir: None,
+            synthesized: false,
+        },
+        ParsedSource::Net { url } => PackageSource::Net { url: url.clone() },
+        ParsedSource::Local { path } => PackageSource::Local {
+            path: path.clone(),
+            editable,
+        },
+    }
+}
+
+/// Unified diff of a file `add` would rewrite, or `None` when it would stay
+/// the same
+fn file_diff(current: &str, new: &str, file: &str) -> Option<String> {
+    let after = format!("{} (after stacy add)", file);
+    unified_diff(current, new, (file, &after), usize::MAX)
+}
+
+/// The hint for a package that appears to need packages the project lacks:
+/// those `missing` from a scan of its files, and those its manifest's
+/// `Requires:` line declares, which catch dependencies resolved dynamically
+/// at runtime that static scanning misses. Names the package ships itself
+/// are internal files, not packages to install. With none, the hardcoded
+/// hint for the package, if any.
+fn dependency_hint(
+    package: &str,
+    mut missing: Vec<String>,
+    declared: &[String],
+    provided: &HashSet<String>,
+    installed: &HashSet<String>,
+) -> Option<String> {
+    for dep in declared {
+        if !provided.contains(dep) && !installed.contains(dep) && !missing.contains(dep) {
+            missing.push(dep.clone());
+        }
+    }
+    missing.sort();
+    if missing.is_empty() {
+        return hints::get_hint(package).map(str::to_string);
     }
+    Some(format!(
+        "{} appears to need {}. Run: stacy add {}",
+        package,
+        missing.join(", "),
+        missing.join(" ")
+    ))
+}
 
-    Ok(())
+/// Warn if a package declares a newer minimum Stata version than the one
+/// stacy last detected. Silent when either version is unknown (e.g. error
+/// codes never extracted).
+fn detected_stata_warning(package: &str, required: Option<&str>) -> Option<String> {
+    let db = crate::error::error_db::ErrorCodeCache::load().ok()??;
+    stata_version_warning(package, required?, db.stata_version.as_deref()?)
 }
 
 /// Build a warning when a package's declared minimum Stata version is newer
@@ -401,6 +748,13 @@ fn parse_source(source: &str) -> Result<ParsedSource> {
 }
 
 fn print_json_output(results: &[AddedPackage], output: &AddOutput) {
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&json_output(results, output)).unwrap()
+    );
+}
+
+fn json_output(results: &[AddedPackage], output: &AddOutput) -> serde_json::Value {
     use serde_json::json;
 
     let packages: Vec<_> = results
@@ -420,8 +774,9 @@ fn print_json_output(results: &[AddedPackage], output: &AddOutput) {
         .map(|p| p.display().to_string())
         .unwrap_or_default();
 
-    let json_output = json!({
+    json!({
         "status": output.status,
+        "dry_run": output.dry_run,
         "packages": packages,
         "summary": {
             "added": output.added,
@@ -431,9 +786,7 @@ fn print_json_output(results: &[AddedPackage], output: &AddOutput) {
             "group": output.group,
         },
         "cache_dir": cache_dir,
-    });
-
-    println!("{}", serde_json::to_string_pretty(&json_output).unwrap());
+    })
 }
 
 fn print_human_summary(output: &AddOutput) {
@@ -2,15 +2,15 @@
 //!
 //! Performance profiling for Stata scripts with statistics.
 
-use crate::cli::output_format::OutputFormat;
-use crate::cli::output_types::{BenchOutput, CommandOutput};
-use crate::error::Result;
+use crate::cli::output_format::{format_csv, format_markdown_table, OutputFormat, TableFormat};
+use crate::cli::output_types::{BenchOutput, CommandOutput, EngineBenchOutput, SweepPointOutput};
+use crate::error::{Error, Result};
 use crate::executor::log_policy::LogPolicy;
 use crate::executor::{verbosity::Verbosity, StataExecutor};
 use crate::project::Project;
 use clap::Args;
-use std::path::PathBuf;
-use std::process;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
 #[derive(Args)]
@@ -20,7 +20,15 @@ Examples:
   stacy bench analysis.do              Run 10 times with 2 warmups (defaults)
   stacy bench analysis.do -n 20        Run 20 times
   stacy bench analysis.do --warmup 0   No warmup runs
-  stacy bench analysis.do --format json   Machine-readable output")]
+  stacy bench analysis.do --processors 4   Pin Stata/MP to 4 cores
+  stacy bench analysis.do --engine stata-se --engine stata-mp
+                                       Compare two engines
+  stacy bench analysis.do --arg obs=1e5 --arg obs=1e6 --csv scaling.csv
+                                       Time the script for each value of obs
+  stacy bench analysis.do --format json   Machine-readable output
+  stacy bench analysis.do --format csv    Results as CSV on stdout
+  stacy bench analysis.do --format markdown
+                                       Results as a table for a README")]
 pub struct BenchArgs {
     /// Stata script to benchmark
     #[arg(value_name = "SCRIPT")]
@@ -38,17 +46,32 @@ pub struct BenchArgs {
     #[arg(long)]
     pub no_warmup: bool,
 
-    /// Output format: human (default), json, or stata
+    /// Output format: human (default), json, stata, csv, or markdown
     #[arg(long, value_enum, default_value = "human")]
-    pub format: OutputFormat,
+    pub format: TableFormat,
 
-    /// Stata engine to use (overrides config and auto-detection)
+    /// Stata engine to use (overrides config and auto-detection). Repeat to
+    /// compare engines side by side.
     #[arg(long, value_name = "ENGINE")]
-    pub engine: Option<String>,
+    pub engine: Vec<String>,
 
-    /// Suppress progress output
-    #[arg(short, long)]
+    /// Argument for the script (KEY=VALUE), which reads it with `local key :
+    /// environment STACY_ARG_KEY`. Repeat a key to benchmark each value.
+    #[arg(long, value_name = "KEY=VALUE")]
+    pub arg: Vec<String>,
+
+    /// Also write the results as CSV to this file
+    #[arg(long, value_name = "FILE")]
+    pub csv: Option<PathBuf>,
+
+    /// Suppress progress output (global `-q`)
+    #[arg(skip)]
     pub quiet: bool,
+
+    /// Cores Stata/MP may use (`set processors N`), checked against the
+    /// license. Overrides [execution] processors in stacy.toml.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub processors: Option<u32>,
 }
 
 /// Benchmark statistics
@@ -113,40 +136,47 @@ impl BenchStats {
     }
 }
 
+/// One engine and set of `--arg` values, measured
+struct Measurement {
+    engine: Option<String>,
+    args: Vec<(String, String)>,
+    stats: BenchStats,
+}
+
 /// Execute the bench command
 pub fn execute(args: &BenchArgs) -> Result<()> {
-    let format = args.format;
+    let format = args.format.output();
+    let human = !args.quiet && format == OutputFormat::Human;
 
     // Verify script exists
     if !args.script.exists() {
         if format == OutputFormat::Human {
             eprintln!("Error: Script not found: {}", args.script.display());
         }
-        process::exit(3);
+        crate::session::exit(3);
     }
 
     // Determine warmup count
     let warmup_count = if args.no_warmup { 0 } else { args.warmup };
+    let points = sweep_points(&args.arg)?;
 
     // Find project
     let project = Project::find()?;
-    let local_ado_paths: Vec<std::path::PathBuf> = project
-        .as_ref()
-        .map(|p| p.resolve_local_ado_paths())
-        .unwrap_or_default();
     let project_root = project.as_ref().map(|p| p.root.as_path());
 
-    // Create executor (quiet mode for benchmarking)
-    let engine_ref = args.engine.as_deref();
-    let executor =
-        StataExecutor::try_new(engine_ref, Verbosity::Quiet)?.with_local_ado_paths(local_ado_paths);
+    // Without --engine the usual detection picks one
+    let engines: Vec<Option<&str>> = if args.engine.is_empty() {
+        vec![None]
+    } else {
+        args.engine.iter().map(|e| Some(e.as_str())).collect()
+    };
 
     // Benchmarks run the script many times; their logs are internal. Removed on
     // success, kept on failure so the run can be diagnosed (#98).
     let policy = LogPolicy::for_project(project.as_ref());
 
     // Print header
-    if !args.quiet && format == OutputFormat::Human {
+    if human {
         println!("Benchmarking: {}", args.script.display());
         if warmup_count > 0 {
             println!(
@@ -163,17 +193,218 @@ pub fn execute(args: &BenchArgs) -> Result<()> {
                 if args.runs == 1 { "run" } else { "runs" }
             );
         }
+        if engines.len() > 1 {
+            println!("  on each of {}", args.engine.join(", "));
+        }
+        if points.len() > 1 {
+            println!("  for each of {} argument values", points.len());
+        }
         println!();
     }
 
+    let mut measurements = Vec::with_capacity(engines.len() * points.len());
+    for engine in &engines {
+        // Create executor (quiet mode for benchmarking)
+        let executor = create_executor(args, project.as_ref(), *engine)?;
+        for point in &points {
+            if human && (engines.len() > 1 || points.len() > 1) {
+                println!("{}:", point_label(*engine, point));
+            }
+            let durations = measure(
+                args,
+                &executor,
+                project_root,
+                &policy,
+                warmup_count,
+                &point.iter().cloned().collect(),
+            )?;
+
+            // Compute statistics
+            measurements.push(Measurement {
+                engine: engine.map(str::to_string),
+                args: point.clone(),
+                stats: BenchStats::from_durations(&durations)
+                    .expect("Should have at least one run"),
+            });
+        }
+    }
+    let stats = &measurements[0].stats;
+
+    if let Some(csv) = &args.csv {
+        std::fs::write(csv, measurements_csv(&measurements))
+            .map_err(|e| Error::Config(format!("Cannot write {}: {}", csv.display(), e)))?;
+    }
+
+    // Build output
+    // Note: if we get here, all runs succeeded (we exit early on failure)
+    let output = BenchOutput {
+        script: args.script.clone(),
+        measured_runs: stats.count,
+        warmup_runs: warmup_count,
+        mean_secs: stats.mean.as_secs_f64(),
+        median_secs: stats.median.as_secs_f64(),
+        min_secs: stats.min.as_secs_f64(),
+        max_secs: stats.max.as_secs_f64(),
+        stddev_secs: stats.stddev.as_secs_f64(),
+        success: true,
+        engines: (engines.len() > 1 && points.len() == 1).then(|| {
+            measurements
+                .iter()
+                .map(|m| EngineBenchOutput {
+                    engine: m.engine.clone().unwrap_or_default(),
+                    mean_secs: m.stats.mean.as_secs_f64(),
+                    median_secs: m.stats.median.as_secs_f64(),
+                    min_secs: m.stats.min.as_secs_f64(),
+                    max_secs: m.stats.max.as_secs_f64(),
+                    stddev_secs: m.stats.stddev.as_secs_f64(),
+                    speedup: speedup(stats, &m.stats),
+                })
+                .collect()
+        }),
+        sweep: (points.len() > 1).then(|| {
+            measurements
+                .iter()
+                .map(|m| SweepPointOutput {
+                    engine: m.engine.clone().filter(|_| engines.len() > 1),
+                    args: m.args.iter().cloned().collect(),
+                    mean_secs: m.stats.mean.as_secs_f64(),
+                    median_secs: m.stats.median.as_secs_f64(),
+                    min_secs: m.stats.min.as_secs_f64(),
+                    max_secs: m.stats.max.as_secs_f64(),
+                    stddev_secs: m.stats.stddev.as_secs_f64(),
+                    relative: relative(&measurements, m),
+                })
+                .collect()
+        }),
+    };
+
+    // Handle output
+    match format {
+        OutputFormat::Json if args.format.is_csv() => print!("{}", measurements_csv(&measurements)),
+        OutputFormat::Json if args.format.is_markdown() => {
+            print!("{}", measurements_markdown(&measurements))
+        }
+        OutputFormat::Json => println!("{}", output.to_json()),
+        OutputFormat::Stata => println!("{}", output.to_stata()),
+        OutputFormat::Human => {
+            println!("Benchmark Results: {}", args.script.display());
+            println!(
+                "  Runs:     {}{} ({})",
+                stats.count,
+                if measurements.len() > 1 { " each" } else { "" },
+                if warmup_count > 0 {
+                    format!("{} warmup", warmup_count)
+                } else {
+                    "no warmup".to_string()
+                }
+            );
+            println!();
+            if points.len() > 1 {
+                print!("{}", format_sweep(&measurements, engines.len() > 1));
+            } else if engines.len() > 1 {
+                let stats: Vec<BenchStats> = measurements.iter().map(|m| m.stats.clone()).collect();
+                print!("{}", format_comparison(&args.engine, &stats));
+            } else {
+                println!("  Time (seconds):");
+                println!(
+                    "    mean      {:.3} +/- {:.3}",
+                    stats.mean.as_secs_f64(),
+                    stats.stddev.as_secs_f64()
+                );
+                println!("    median    {:.3}", stats.median.as_secs_f64());
+                println!("    min       {:.3}", stats.min.as_secs_f64());
+                println!("    max       {:.3}", stats.max.as_secs_f64());
+            }
+            if let Some(csv) = &args.csv {
+                println!();
+                println!("Wrote {}", csv.display());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The argument values to benchmark: each value of an `--arg` key given more
+/// than once, crossed with the values of the other keys
+fn sweep_points(args: &[String]) -> Result<Vec<Vec<(String, String)>>> {
+    let mut keys: Vec<(String, Vec<String>)> = Vec::new();
+    for arg in args {
+        let Some((key, value)) = arg.split_once('=') else {
+            return Err(Error::Config(format!(
+                "Invalid argument '{}'. Expected format: key=value",
+                arg
+            )));
+        };
+        match keys.iter_mut().find(|(k, _)| k == key) {
+            Some((_, values)) => values.push(value.to_string()),
+            None => keys.push((key.to_string(), vec![value.to_string()])),
+        }
+    }
+
+    let mut points = vec![Vec::new()];
+    for (key, values) in &keys {
+        points = points
+            .into_iter()
+            .flat_map(|point: Vec<(String, String)>| {
+                values.iter().map(move |value| {
+                    let mut point = point.clone();
+                    point.push((key.clone(), value.clone()));
+                    point
+                })
+            })
+            .collect();
+    }
+    Ok(points)
+}
+
+/// `stata-mp, obs=1e6`
+fn point_label(engine: Option<&str>, point: &[(String, String)]) -> String {
+    engine
+        .map(str::to_string)
+        .into_iter()
+        .chain(point.iter().map(|(k, v)| format!("{}={}", k, v)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn create_executor(
+    args: &BenchArgs,
+    project: Option<&Project>,
+    engine: Option<&str>,
+) -> Result<StataExecutor> {
+    let local_ado_paths: Vec<std::path::PathBuf> = project
+        .map(|p| p.resolve_local_ado_paths())
+        .unwrap_or_default();
+    let processors = args
+        .processors
+        .or_else(|| project.and_then(|p| p.processors()));
+    Ok(StataExecutor::try_new(engine, Verbosity::Quiet)?
+        .with_local_ado_paths(local_ado_paths)
+        .with_processors(processors)
+        .with_reproducibility(project.map(|p| p.reproducibility()).unwrap_or_default()))
+}
+
+/// Run the warmup and measured runs on one engine, exiting if a run fails
+fn measure(
+    args: &BenchArgs,
+    executor: &StataExecutor,
+    project_root: Option<&Path>,
+    policy: &LogPolicy,
+    warmup_count: usize,
+    script_args: &HashMap<String, String>,
+) -> Result<Vec<Duration>> {
+    let format = args.format.output();
+    let human = !args.quiet && format == OutputFormat::Human;
+
     // Warmup runs
     if warmup_count > 0 {
-        if !args.quiet && format == OutputFormat::Human {
+        if human {
             print!("Warming up");
         }
 
         for i in 0..warmup_count {
-            let result = executor.run(&args.script, project_root)?;
+            let result = executor.run_with_args(&args.script, project_root, script_args)?;
             let log_file = policy.finalize(&result.log_file, result.success);
 
             if !result.success {
@@ -183,17 +414,17 @@ pub fn execute(args: &BenchArgs) -> Result<()> {
                         eprintln!("Log: {}", log.display());
                     }
                 }
-                process::exit(result.exit_code);
+                crate::session::exit(result.exit_code);
             }
 
-            if !args.quiet && format == OutputFormat::Human {
+            if human {
                 print!(".");
                 use std::io::Write;
                 std::io::stdout().flush().ok();
             }
         }
 
-        if !args.quiet && format == OutputFormat::Human {
+        if human {
             println!(" done");
         }
     }
@@ -201,12 +432,12 @@ pub fn execute(args: &BenchArgs) -> Result<()> {
     // Measured runs
     let mut durations: Vec<Duration> = Vec::with_capacity(args.runs);
 
-    if !args.quiet && format == OutputFormat::Human {
+    if human {
         print!("Measuring");
     }
 
     for i in 0..args.runs {
-        let result = executor.run(&args.script, project_root)?;
+        let result = executor.run_with_args(&args.script, project_root, script_args)?;
         let log_file = policy.finalize(&result.log_file, result.success);
 
         if !result.success {
@@ -216,68 +447,223 @@ pub fn execute(args: &BenchArgs) -> Result<()> {
                     eprintln!("Log: {}", log.display());
                 }
             }
-            process::exit(result.exit_code);
+            crate::session::exit(result.exit_code);
         }
 
         durations.push(result.duration);
 
-        if !args.quiet && format == OutputFormat::Human {
+        if human {
             print!(".");
             use std::io::Write;
             std::io::stdout().flush().ok();
         }
     }
 
-    if !args.quiet && format == OutputFormat::Human {
+    if human {
         println!(" done\n");
     }
 
-    // Compute statistics
-    let stats = BenchStats::from_durations(&durations).expect("Should have at least one run");
+    Ok(durations)
+}
+
+/// How many times faster `stats` ran than `baseline`, by mean
+fn speedup(baseline: &BenchStats, stats: &BenchStats) -> f64 {
+    let mean = stats.mean.as_secs_f64();
+    if mean > 0.0 {
+        baseline.mean.as_secs_f64() / mean
+    } else {
+        0.0
+    }
+}
+
+/// Mean time of `m` relative to the first argument values on its engine
+fn relative(measurements: &[Measurement], m: &Measurement) -> f64 {
+    let base = measurements
+        .iter()
+        .find(|other| other.engine == m.engine)
+        .map_or(0.0, |other| other.stats.mean.as_secs_f64());
+    if base > 0.0 {
+        m.stats.mean.as_secs_f64() / base
+    } else {
+        0.0
+    }
+}
 
-    // Build output
-    // Note: if we get here, all runs succeeded (we exit early on failure)
-    let output = BenchOutput {
-        script: args.script.clone(),
-        measured_runs: stats.count,
-        warmup_runs: warmup_count,
-        mean_secs: stats.mean.as_secs_f64(),
-        median_secs: stats.median.as_secs_f64(),
-        min_secs: stats.min.as_secs_f64(),
-        max_secs: stats.max.as_secs_f64(),
-        stddev_secs: stats.stddev.as_secs_f64(),
-        success: true,
+/// Scaling table of a sweep, each row relative to the first on its engine
+///
+/// ```text
+///   obs     mean  median  stddev     min     max  relative
+///   1e5    0.121   0.120   0.002   0.119   0.125     1.00x
+///   1e6    1.153   1.150   0.011   1.139   1.170     9.53x
+/// ```
+fn format_sweep(measurements: &[Measurement], show_engine: bool) -> String {
+    let mut columns: Vec<(String, Vec<String>)> = Vec::new();
+    if show_engine {
+        columns.push((
+            "Engine".to_string(),
+            measurements
+                .iter()
+                .map(|m| m.engine.clone().unwrap_or_default())
+                .collect(),
+        ));
+    }
+    for (i, (key, _)) in measurements[0].args.iter().enumerate() {
+        columns.push((
+            key.clone(),
+            measurements.iter().map(|m| m.args[i].1.clone()).collect(),
+        ));
+    }
+    let widths: Vec<usize> = columns
+        .iter()
+        .map(|(header, values)| {
+            values
+                .iter()
+                .map(|v| v.len())
+                .chain([header.len()])
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let label = |cells: Vec<&str>| -> String {
+        cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ")
     };
+    let mut out = format!(
+        "  {}  {:>7} {:>7} {:>7} {:>7} {:>7} {:>9}\n",
+        label(columns.iter().map(|(h, _)| h.as_str()).collect()),
+        "mean",
+        "median",
+        "stddev",
+        "min",
+        "max",
+        "relative"
+    );
+    for (row, m) in measurements.iter().enumerate() {
+        let s = &m.stats;
+        out.push_str(&format!(
+            "  {}  {:>7.3} {:>7.3} {:>7.3} {:>7.3} {:>7.3} {:>8.2}x\n",
+            label(columns.iter().map(|(_, v)| v[row].as_str()).collect()),
+            s.mean.as_secs_f64(),
+            s.median.as_secs_f64(),
+            s.stddev.as_secs_f64(),
+            s.min.as_secs_f64(),
+            s.max.as_secs_f64(),
+            relative(measurements, m)
+        ));
+    }
+    out
+}
 
-    // Handle output
-    match format {
-        OutputFormat::Json => println!("{}", output.to_json()),
-        OutputFormat::Stata => println!("{}", output.to_stata()),
-        OutputFormat::Human => {
-            println!("Benchmark Results: {}", args.script.display());
-            println!(
-                "  Runs:     {} ({})",
-                stats.count,
-                if warmup_count > 0 {
-                    format!("{} warmup", warmup_count)
-                } else {
-                    "no warmup".to_string()
-                }
-            );
-            println!();
-            println!("  Time (seconds):");
-            println!(
-                "    mean      {:.3} +/- {:.3}",
-                stats.mean.as_secs_f64(),
-                stats.stddev.as_secs_f64()
-            );
-            println!("    median    {:.3}", stats.median.as_secs_f64());
-            println!("    min       {:.3}", stats.min.as_secs_f64());
-            println!("    max       {:.3}", stats.max.as_secs_f64());
-        }
+/// The measurements as CSV, one row per engine and set of argument values
+fn measurements_csv(measurements: &[Measurement]) -> String {
+    let show_engine = measurements.iter().any(|m| m.engine.is_some());
+    let mut header: Vec<&str> = Vec::new();
+    if show_engine {
+        header.push("engine");
+    }
+    header.extend(measurements[0].args.iter().map(|(k, _)| k.as_str()));
+    header.extend([
+        "runs",
+        "mean_secs",
+        "median_secs",
+        "stddev_secs",
+        "min_secs",
+        "max_secs",
+    ]);
+
+    let rows: Vec<Vec<String>> = measurements
+        .iter()
+        .map(|m| {
+            let mut row: Vec<String> = Vec::new();
+            if show_engine {
+                row.push(m.engine.clone().unwrap_or_default());
+            }
+            row.extend(m.args.iter().map(|(_, v)| v.clone()));
+            let s = &m.stats;
+            row.push(s.count.to_string());
+            for d in [s.mean, s.median, s.stddev, s.min, s.max] {
+                row.push(format!("{:.6}", d.as_secs_f64()));
+            }
+            row
+        })
+        .collect();
+    format_csv(&header, &rows)
+}
+
+/// The measurements as a Markdown table, one row per engine and set of
+/// argument values, times in seconds
+fn measurements_markdown(measurements: &[Measurement]) -> String {
+    let show_engine = measurements.iter().any(|m| m.engine.is_some());
+    let mut header: Vec<&str> = Vec::new();
+    if show_engine {
+        header.push("Engine");
     }
+    header.extend(measurements[0].args.iter().map(|(k, _)| k.as_str()));
+    header.extend(["Runs", "Mean", "Median", "Std dev", "Min", "Max"]);
+
+    let rows: Vec<Vec<String>> = measurements
+        .iter()
+        .map(|m| {
+            let mut row: Vec<String> = Vec::new();
+            if show_engine {
+                row.push(m.engine.clone().unwrap_or_default());
+            }
+            row.extend(m.args.iter().map(|(_, v)| format!("`{}`", v)));
+            let s = &m.stats;
+            row.push(s.count.to_string());
+            for d in [s.mean, s.median, s.stddev, s.min, s.max] {
+                row.push(format!("{:.3}s", d.as_secs_f64()));
+            }
+            row
+        })
+        .collect();
+    format_markdown_table(&header, &rows)
+}
 
-    Ok(())
+/// Side-by-side table of engines, each compared with the first
+///
+/// ```text
+///   Engine      mean  median  stddev     min     max  speedup
+///   stata-se  12.402  12.390   0.105  12.281  12.598    1.00x
+///   stata-mp   4.337   4.310   0.061   4.270   4.452    2.86x
+/// ```
+fn format_comparison(engines: &[String], stats: &[BenchStats]) -> String {
+    let width = engines
+        .iter()
+        .map(|e| e.len())
+        .max()
+        .unwrap_or(0)
+        .max("Engine".len());
+    let mut out = format!(
+        "  {:<width$}  {:>7} {:>7} {:>7} {:>7} {:>7} {:>8}\n",
+        "Engine",
+        "mean",
+        "median",
+        "stddev",
+        "min",
+        "max",
+        "speedup",
+        width = width
+    );
+    for (engine, s) in engines.iter().zip(stats) {
+        out.push_str(&format!(
+            "  {:<width$}  {:>7.3} {:>7.3} {:>7.3} {:>7.3} {:>7.3} {:>7.2}x\n",
+            engine,
+            s.mean.as_secs_f64(),
+            s.median.as_secs_f64(),
+            s.stddev.as_secs_f64(),
+            s.min.as_secs_f64(),
+            s.max.as_secs_f64(),
+            speedup(&stats[0], s),
+            width = width
+        ));
+    }
+    out
 }
 
 #[cfg(test)]
@@ -360,6 +746,87 @@ mod tests {
         assert!(stddev_diff < 0.001, "stddev should be ~2.0");
     }
 
+    #[test]
+    fn test_format_comparison() {
+        let se = BenchStats::from_durations(&[Duration::from_secs(6)]).unwrap();
+        let mp = BenchStats::from_durations(&[Duration::from_secs(2)]).unwrap();
+        let table = format_comparison(&["stata-se".to_string(), "stata-mp".to_string()], &[se, mp]);
+
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].contains("speedup"));
+        assert!(lines[1].starts_with("  stata-se") && lines[1].ends_with("1.00x"));
+        assert!(lines[2].starts_with("  stata-mp") && lines[2].ends_with("3.00x"));
+    }
+
+    #[test]
+    fn test_sweep_points() {
+        let args: Vec<String> = ["obs=1e5", "obs=1e6", "reps=10", "reps=20", "seed=1"]
+            .map(String::from)
+            .into();
+        let points = sweep_points(&args).unwrap();
+        assert_eq!(points.len(), 4);
+        assert_eq!(
+            point_label(None, &points[0]),
+            "obs=1e5, reps=10, seed=1".to_string()
+        );
+        assert_eq!(
+            point_label(Some("stata-mp"), &points[3]),
+            "stata-mp, obs=1e6, reps=20, seed=1".to_string()
+        );
+
+        // No --arg: one run without arguments
+        assert_eq!(sweep_points(&[]).unwrap(), vec![Vec::new()]);
+        assert!(sweep_points(&["obs".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_measurements_csv() {
+        let point = |obs: &str, secs: u64| Measurement {
+            engine: None,
+            args: vec![("obs".to_string(), obs.to_string())],
+            stats: BenchStats::from_durations(&[Duration::from_secs(secs)]).unwrap(),
+        };
+        let csv = measurements_csv(&[point("1e5", 1), point("1,000,000", 10)]);
+        assert_eq!(
+            csv,
+            "obs,runs,mean_secs,median_secs,stddev_secs,min_secs,max_secs\n\
+             1e5,1,1.000000,1.000000,0.000000,1.000000,1.000000\n\
+             \"1,000,000\",1,10.000000,10.000000,0.000000,10.000000,10.000000\n"
+        );
+    }
+
+    #[test]
+    fn test_measurements_markdown() {
+        let point = |obs: &str, secs: u64| Measurement {
+            engine: None,
+            args: vec![("obs".to_string(), obs.to_string())],
+            stats: BenchStats::from_durations(&[Duration::from_secs(secs)]).unwrap(),
+        };
+        let table = measurements_markdown(&[point("1e5", 1), point("1e6", 10)]);
+        assert_eq!(
+            table,
+            "| obs | Runs | Mean | Median | Std dev | Min | Max |\n\
+             | --- | --- | --- | --- | --- | --- | --- |\n\
+             | `1e5` | 1 | 1.000s | 1.000s | 0.000s | 1.000s | 1.000s |\n\
+             | `1e6` | 1 | 10.000s | 10.000s | 0.000s | 10.000s | 10.000s |\n"
+        );
+    }
+
+    #[test]
+    fn test_format_sweep_relative() {
+        let point = |obs: &str, secs: u64| Measurement {
+            engine: None,
+            args: vec![("obs".to_string(), obs.to_string())],
+            stats: BenchStats::from_durations(&[Duration::from_secs(secs)]).unwrap(),
+        };
+        let table = format_sweep(&[point("1e5", 2), point("1e6", 20)], false);
+        let lines: Vec<&str> = table.lines().collect();
+        assert!(lines[0].starts_with("  obs") && lines[0].ends_with("relative"));
+        assert!(lines[1].starts_with("  1e5") && lines[1].ends_with("1.00x"));
+        assert!(lines[2].ends_with("10.00x"));
+    }
+
     #[test]
     fn test_bench_stats_unsorted_input() {
         // Make sure order doesn't matter
@@ -0,0 +1,243 @@
+//! CLI implementation for `stacy build` command
+//!
+//! Build the stages declared in stacy.toml's `[pipeline]` section, running
+//! only the ones that are out of date.
+
+use crate::artifacts::Artifacts;
+use crate::cache::engine::EngineIdentity;
+use crate::cache::step::StepCache;
+use crate::cli::output_format::{resolve_verbosity, OutputFormat};
+use crate::cli::output_types::{BuildOutput, CommandOutput, StageOutput};
+use crate::dvc;
+use crate::error::{Error, Result};
+use crate::executor::log_policy::LogPolicy;
+use crate::executor::StataExecutor;
+use crate::pipeline::executor::{BuildResult, PipelineExecutor, StageStatus};
+use crate::pipeline::Pipeline;
+use crate::project::Project;
+use clap::Args;
+use std::path::PathBuf;
+
+#[derive(Args)]
+#[command(after_help = "\
+Examples:
+  stacy build                    Build every out-of-date stage
+  stacy build report             Build 'report' and the stages it depends on
+  stacy build --dry-run          Show which stages are out of date
+  stacy build --force            Rebuild every stage
+  stacy build --dvc-pull --dvc-push
+                                 Fetch inputs from and push outputs to DVC
+  stacy build --export-dvc > dvc.yaml
+                                 Write the pipeline as DVC stages")]
+pub struct BuildArgs {
+    /// Stages to build, with everything they depend on (default: all)
+    #[arg(value_name = "STAGE")]
+    pub stages: Vec<String>,
+
+    /// Rebuild stages even if they are up to date
+    #[arg(long)]
+    pub force: bool,
+
+    /// Show which stages are out of date without running them
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Before building, `dvc pull` the stages' inputs that DVC tracks
+    #[arg(long)]
+    pub dvc_pull: bool,
+
+    /// After a successful build, `dvc add` and `dvc push` the outputs of the
+    /// stages that ran that DVC tracks
+    #[arg(long, conflicts_with = "dry_run")]
+    pub dvc_push: bool,
+
+    /// Print the pipeline as a dvc.yaml instead of building
+    #[arg(long, conflicts_with_all = ["force", "dry_run", "dvc_pull", "dvc_push"])]
+    pub export_dvc: bool,
+
+    /// Output format: human (default), json, or stata
+    #[arg(long, value_enum, default_value = "human")]
+    pub format: OutputFormat,
+}
+
+pub fn execute(args: &BuildArgs) -> Result<()> {
+    let format = args.format;
+
+    let project = Project::find()?.ok_or(Error::ProjectNotFound)?;
+    let config = project.config.clone().unwrap_or_default();
+
+    let pipeline = Pipeline::from_config(&config.pipeline)?;
+    if pipeline.is_empty() {
+        return Err(Error::Config(
+            "No stages defined. Add [pipeline.<name>] sections to stacy.toml:\n\n  [pipeline.clean]\n  script = \"src/01_clean.do\"\n  inputs = [\"data/raw.csv\"]\n  outputs = [\"data/clean.dta\"]".to_string(),
+        ));
+    }
+
+    if args.export_dvc {
+        print!("{}", dvc::dvc_yaml(&pipeline));
+        return Ok(());
+    }
+
+    if args.dvc_pull {
+        // Inputs another stage writes are built, not pulled
+        let produced: Vec<_> = config
+            .pipeline
+            .values()
+            .flat_map(|s| s.outputs.iter())
+            .collect();
+        let inputs: Vec<PathBuf> = pipeline
+            .select(&args.stages)?
+            .into_iter()
+            .filter_map(|name| pipeline.stage(name))
+            .flat_map(|s| s.inputs.iter())
+            .filter(|input| !produced.iter().any(|out| input.starts_with(out)))
+            .cloned()
+            .collect();
+        let targets = dvc::tracked(&project.root, &inputs);
+        if format == OutputFormat::Human && !targets.is_empty() {
+            eprintln!(
+                "Pulling {} DVC-tracked {}...",
+                targets.len(),
+                if targets.len() == 1 {
+                    "input"
+                } else {
+                    "inputs"
+                }
+            );
+        }
+        dvc::pull(&project.root, &targets)?;
+    }
+
+    // Machine-readable formats suppress streaming (#84)
+    let executor = StataExecutor::try_new(None, resolve_verbosity(false, 0, format))?
+        .with_local_ado_paths(project.resolve_local_ado_paths())
+        .with_processors(project.processors())
+        .with_reproducibility(project.reproducibility());
+
+    let cache =
+        StepCache::load(&project.root, args.force)?.with_engine(EngineIdentity::detect(None));
+
+    let artifacts = Artifacts::load(&project.root)?;
+
+    let result = PipelineExecutor::new(&pipeline, &executor, &project.root, &cache)
+        .with_log_policy(LogPolicy::for_project(Some(&project)))
+        .with_artifacts(&artifacts)
+        .with_dry_run(args.dry_run)
+        .build(&args.stages)?;
+
+    if !args.dry_run {
+        if let Err(e) = cache.save() {
+            if format == OutputFormat::Human {
+                eprintln!("Warning: Failed to update cache: {}", e);
+            }
+        }
+        if let Err(e) = artifacts.save() {
+            if format == OutputFormat::Human {
+                eprintln!("Warning: Failed to update artifact registry: {}", e);
+            }
+        }
+    }
+
+    if args.dvc_push && result.success {
+        let outputs: Vec<PathBuf> = result
+            .stages
+            .iter()
+            .filter(|s| s.status == StageStatus::Ran)
+            .filter_map(|s| pipeline.stage(&s.name))
+            .flat_map(|s| s.outputs.iter().cloned())
+            .collect();
+        let targets = dvc::tracked(&project.root, &outputs);
+        if format == OutputFormat::Human && !targets.is_empty() {
+            eprintln!(
+                "Pushing {} DVC-tracked {}...",
+                targets.len(),
+                if targets.len() == 1 {
+                    "output"
+                } else {
+                    "outputs"
+                }
+            );
+        }
+        dvc::push(&project.root, &targets)?;
+    }
+
+    let output = build_output(&result, args.dry_run);
+    match format {
+        OutputFormat::Json => println!("{}", output.to_json()),
+        OutputFormat::Stata => println!("{}", output.to_stata()),
+        OutputFormat::Human => print_human(&result, args.dry_run),
+    }
+
+    crate::session::exit(result.exit_code);
+}
+
+fn build_output(result: &BuildResult, dry_run: bool) -> BuildOutput {
+    BuildOutput {
+        success: result.success,
+        exit_code: result.exit_code,
+        duration_secs: result.duration.as_secs_f64(),
+        dry_run,
+        stage_count: result.stages.len(),
+        ran_count: result.count(StageStatus::Ran) + result.count(StageStatus::Stale),
+        fresh_count: result.count(StageStatus::Fresh),
+        failed_count: result.count(StageStatus::Failed),
+        skipped_count: result.count(StageStatus::Skipped),
+        stages: result
+            .stages
+            .iter()
+            .map(|s| StageOutput {
+                name: s.name.clone(),
+                script: s.script.clone(),
+                status: s.status,
+                reason: s.reason.clone(),
+                exit_code: s.exit_code,
+                duration_secs: s.duration.as_secs_f64(),
+            })
+            .collect(),
+    }
+}
+
+fn print_human(result: &BuildResult, dry_run: bool) {
+    for stage in &result.stages {
+        let label = match stage.status {
+            StageStatus::Failed => format!("\x1b[31m{:<5}\x1b[0m", stage.status.label()),
+            StageStatus::Ran => format!("\x1b[32m{:<5}\x1b[0m", stage.status.label()),
+            _ => format!("{:<5}", stage.status.label()),
+        };
+        let mut line = format!("  {}  {:<15}", label, stage.name);
+        if stage.status == StageStatus::Ran || stage.status == StageStatus::Failed {
+            line.push_str(&format!("  ({:.2}s)", stage.duration.as_secs_f64()));
+        }
+        if let Some(ref reason) = stage.reason {
+            line.push_str(&format!("  {}", reason));
+        }
+        if stage.status == StageStatus::Failed && !stage.log_file.as_os_str().is_empty() {
+            line.push_str(&format!("  (log: {})", stage.log_file.display()));
+        }
+        println!("{}", line.trim_end());
+    }
+
+    let fresh = result.count(StageStatus::Fresh);
+    if dry_run {
+        let stale = result.count(StageStatus::Stale);
+        if stale == 0 {
+            println!("Everything is up to date.");
+        } else {
+            println!("{} stale, {} up to date", stale, fresh);
+        }
+    } else if result.success {
+        println!(
+            "\x1b[32mPASS\x1b[0m  Build  {} ran, {} up to date  ({:.2}s)",
+            result.count(StageStatus::Ran),
+            fresh,
+            result.duration.as_secs_f64()
+        );
+    } else {
+        eprintln!(
+            "\x1b[31mFAIL\x1b[0m  Build  {} failed, {} skipped  ({:.2}s)",
+            result.count(StageStatus::Failed),
+            result.count(StageStatus::Skipped),
+            result.duration.as_secs_f64()
+        );
+    }
+}
@@ -4,14 +4,15 @@
 //! - Build cache for incremental builds
 //! - Package cache for installed packages
 
-use crate::cache::BuildCache;
+use crate::cache::{verify, BuildCache};
 use crate::cli::output_format::OutputFormat;
-use crate::cli::output_types::{CacheCleanOutput, CacheInfoOutput, CommandOutput};
-use crate::error::Result;
-use crate::packages::global_cache;
+use crate::cli::output_types::{
+    CacheCleanOutput, CacheInfoOutput, CacheVerifyOutput, CommandOutput,
+};
+use crate::error::{Error, Result};
+use crate::packages::{cache_layout, global_cache, ssc_index};
 use crate::project::Project;
 use clap::{Args, Subcommand};
-use std::process;
 
 #[derive(Args)]
 #[command(about = "Manage the build cache", long_about = None)]
@@ -26,6 +27,8 @@ pub enum CacheCommand {
     Clean(CleanArgs),
     /// Show build cache statistics
     Info(InfoArgs),
+    /// Check the build cache for corrupt and stale entries
+    Verify(VerifyArgs),
     /// Manage the global package cache
     Packages(PackagesArgs),
 }
@@ -40,8 +43,8 @@ pub struct CleanArgs {
     #[arg(long, value_enum, default_value = "human")]
     pub format: OutputFormat,
 
-    /// Suppress output
-    #[arg(short, long)]
+    /// Suppress output (global `-q`)
+    #[arg(skip)]
     pub quiet: bool,
 }
 
@@ -52,6 +55,17 @@ pub struct InfoArgs {
     pub format: OutputFormat,
 }
 
+#[derive(Args)]
+pub struct VerifyArgs {
+    /// Drop bad entries (or the whole file if it can't be read)
+    #[arg(long)]
+    pub repair: bool,
+
+    /// Output format: human (default), json, or stata
+    #[arg(long, value_enum, default_value = "human")]
+    pub format: OutputFormat,
+}
+
 #[derive(Args)]
 pub struct PackagesArgs {
     #[command(subcommand)]
@@ -66,6 +80,10 @@ pub enum PackagesCommand {
     List(PackagesListArgs),
     /// Clean the package cache
     Clean(PackagesCleanArgs),
+    /// Upgrade a package cache written by an older stacy to the current layout
+    Migrate(PackagesMigrateArgs),
+    /// Download the SSC package listing `stacy search` and hints read
+    RefreshIndex(PackagesRefreshIndexArgs),
 }
 
 #[derive(Args)]
@@ -93,11 +111,44 @@ pub struct PackagesCleanArgs {
     pub format: OutputFormat,
 }
 
+#[derive(Args)]
+pub struct PackagesMigrateArgs {
+    /// Output format: human (default), json, or stata
+    #[arg(long, value_enum, default_value = "human")]
+    pub format: OutputFormat,
+}
+
+#[derive(Args)]
+pub struct PackagesRefreshIndexArgs {
+    /// Output format: human (default), json, or stata
+    #[arg(long, value_enum, default_value = "human")]
+    pub format: OutputFormat,
+}
+
+impl CacheArgs {
+    /// Output format of the subcommand
+    pub fn format(&self) -> OutputFormat {
+        match &self.command {
+            CacheCommand::Clean(args) => args.format,
+            CacheCommand::Info(args) => args.format,
+            CacheCommand::Verify(args) => args.format,
+            CacheCommand::Packages(args) => match &args.command {
+                PackagesCommand::Path(args) => args.format,
+                PackagesCommand::List(args) => args.format,
+                PackagesCommand::Clean(args) => args.format,
+                PackagesCommand::Migrate(args) => args.format,
+                PackagesCommand::RefreshIndex(args) => args.format,
+            },
+        }
+    }
+}
+
 /// Execute the cache command
 pub fn execute(args: &CacheArgs) -> Result<()> {
     match &args.command {
         CacheCommand::Clean(clean_args) => execute_clean(clean_args),
         CacheCommand::Info(info_args) => execute_info(info_args),
+        CacheCommand::Verify(verify_args) => execute_verify(verify_args),
         CacheCommand::Packages(pkg_args) => execute_packages(pkg_args),
     }
 }
@@ -108,6 +159,8 @@ fn execute_packages(args: &PackagesArgs) -> Result<()> {
         PackagesCommand::Path(path_args) => execute_packages_path(path_args),
         PackagesCommand::List(list_args) => execute_packages_list(list_args),
         PackagesCommand::Clean(clean_args) => execute_packages_clean(clean_args),
+        PackagesCommand::Migrate(migrate_args) => execute_packages_migrate(migrate_args),
+        PackagesCommand::RefreshIndex(index_args) => execute_packages_refresh_index(index_args),
     }
 }
 
@@ -121,7 +174,7 @@ fn execute_clean(args: &CleanArgs) -> Result<()> {
             if !args.quiet && args.format == OutputFormat::Human {
                 eprintln!("Error: Not in a stacy project (no stacy.toml found)");
             }
-            process::exit(10);
+            crate::session::exit(10);
         }
     };
 
@@ -202,7 +255,7 @@ fn execute_info(args: &InfoArgs) -> Result<()> {
             if args.format == OutputFormat::Human {
                 eprintln!("Error: Not in a stacy project (no stacy.toml found)");
             }
-            process::exit(10);
+            crate::session::exit(10);
         }
     };
 
@@ -214,7 +267,7 @@ fn execute_info(args: &InfoArgs) -> Result<()> {
     let mut oldest_age_secs: Option<u64> = None;
     let mut newest_age_secs: Option<u64> = None;
 
-    for entry in cache.entries.values() {
+    for entry in cache.all_entries() {
         let age = entry.age_secs();
         oldest_age_secs = Some(oldest_age_secs.map_or(age, |o| o.max(age)));
         newest_age_secs = Some(newest_age_secs.map_or(age, |n| n.min(age)));
@@ -255,6 +308,96 @@ fn execute_info(args: &InfoArgs) -> Result<()> {
     Ok(())
 }
 
+/// Execute `stacy cache verify`
+///
+/// Exits 1 when problems remain (found without `--repair`).
+fn execute_verify(args: &VerifyArgs) -> Result<()> {
+    let project = match Project::find()? {
+        Some(p) => p,
+        None => {
+            if args.format == OutputFormat::Human {
+                eprintln!("Error: Not in a stacy project (no stacy.toml found)");
+            }
+            crate::session::exit(10);
+        }
+    };
+
+    let report = verify::verify(&project.root)?;
+    let entries_removed = if args.repair {
+        report.repair(&project.root)?
+    } else {
+        0
+    };
+
+    let status = if report.is_healthy() {
+        "healthy"
+    } else if args.repair {
+        "repaired"
+    } else {
+        "problems"
+    };
+    let output = CacheVerifyOutput {
+        entries_checked: report.entries_checked,
+        problems: report.problems.clone(),
+        entries_removed,
+        status: status.to_string(),
+    };
+
+    match args.format {
+        OutputFormat::Json => println!("{}", output.to_json()),
+        OutputFormat::Stata => println!("{}", output.to_stata()),
+        OutputFormat::Human => {
+            for problem in &report.problems {
+                match &problem.script {
+                    Some(script) => println!(
+                        "  {}: {} ({})",
+                        script.display(),
+                        problem.kind,
+                        problem.detail
+                    ),
+                    None => println!("  {}: {}", problem.kind, problem.detail),
+                }
+            }
+            if report.is_healthy() {
+                println!(
+                    "Build cache OK ({} {} checked).",
+                    report.entries_checked,
+                    if report.entries_checked == 1 {
+                        "entry"
+                    } else {
+                        "entries"
+                    }
+                );
+            } else if args.repair {
+                println!(
+                    "Removed {} bad {}.",
+                    entries_removed,
+                    if entries_removed == 1 {
+                        "entry"
+                    } else {
+                        "entries"
+                    }
+                );
+            } else {
+                println!(
+                    "{} {} found. Run `stacy cache verify --repair` to drop bad entries.",
+                    report.problems.len(),
+                    if report.problems.len() == 1 {
+                        "problem"
+                    } else {
+                        "problems"
+                    }
+                );
+            }
+        }
+    }
+
+    if status == "problems" {
+        crate::session::exit(1);
+    }
+    Ok(())
+}
+
 /// Format bytes in human-readable form
 fn format_bytes(bytes: usize) -> String {
     const KB: usize = 1024;
@@ -354,6 +497,18 @@ fn execute_packages_list(args: &PackagesListArgs) -> Result<()> {
                 if let Ok(size) = global_cache::cache_size_bytes() {
                     println!("Size:  {}", format_bytes(size as usize));
                 }
+
+                let cache = global_cache::cache_dir()?;
+                if let Ok(Some(layout)) = cache_layout::detect(&cache) {
+                    if layout < cache_layout::LAYOUT_VERSION {
+                        println!();
+                        println!(
+                            "The cache is in layout {} (current: {}). Upgrade it with: stacy cache packages migrate",
+                            layout,
+                            cache_layout::LAYOUT_VERSION
+                        );
+                    }
+                }
             }
         }
         OutputFormat::Json => {
@@ -397,8 +552,9 @@ fn execute_packages_list(args: &PackagesListArgs) -> Result<()> {
 /// Execute `stacy cache packages clean`
 fn execute_packages_clean(args: &PackagesCleanArgs) -> Result<()> {
     if args.all {
-        // Remove all cached packages
+        // Remove all cached packages, and the HTTP responses they came from
         let removed = global_cache::clean_cache()?;
+        crate::packages::http::clear_response_cache()?;
 
         match args.format {
             OutputFormat::Human => {
@@ -448,6 +604,100 @@ fn execute_packages_clean(args: &PackagesCleanArgs) -> Result<()> {
     Ok(())
 }
 
+/// Execute `stacy cache packages refresh-index`
+fn execute_packages_refresh_index(args: &PackagesRefreshIndexArgs) -> Result<()> {
+    if crate::update_check::is_offline() {
+        return Err(Error::Network(
+            "STACY_OFFLINE is set; not downloading the SSC index".to_string(),
+        ));
+    }
+    if args.format == OutputFormat::Human {
+        println!("Downloading the SSC package listing...");
+    }
+    let index = ssc_index::refresh()?;
+    let path = ssc_index::index_path()?;
+
+    match args.format {
+        OutputFormat::Human => {
+            println!(
+                "Indexed {} SSC packages in {}",
+                index.packages.len(),
+                path.display()
+            );
+        }
+        OutputFormat::Json => {
+            use serde_json::json;
+            let output = json!({
+                "status": "success",
+                "package_count": index.packages.len(),
+                "path": path.display().to_string(),
+            });
+            println!("{}", serde_json::to_string_pretty(&output).unwrap());
+        }
+        OutputFormat::Stata => {
+            println!("global stacy_status \"success\"");
+            println!("scalar stacy_package_count = {}", index.packages.len());
+        }
+    }
+
+    Ok(())
+}
+
+/// Execute `stacy cache packages migrate`
+fn execute_packages_migrate(args: &PackagesMigrateArgs) -> Result<()> {
+    let cache = global_cache::cache_dir()?;
+    let report = cache_layout::migrate(&cache)?;
+
+    match args.format {
+        OutputFormat::Human => {
+            if !report.changed() {
+                println!(
+                    "Package cache is in the current layout ({}), nothing to migrate.",
+                    report.to
+                );
+            } else {
+                println!(
+                    "Migrated package cache from layout {} to {}",
+                    report.from, report.to
+                );
+                for rename in &report.renamed {
+                    println!("  renamed {}", rename);
+                }
+                for path in &report.removed {
+                    println!("  removed {}", path.display());
+                }
+                println!("  recorded checksums of {} package(s)", report.recorded);
+            }
+        }
+        OutputFormat::Json => {
+            use serde_json::json;
+            let output = json!({
+                "status": "success",
+                "path": cache.display().to_string(),
+                "from": report.from,
+                "to": report.to,
+                "migrated": report.changed(),
+                "renamed": report.renamed,
+                "removed": report
+                    .removed
+                    .iter()
+                    .map(|p| p.display().to_string())
+                    .collect::<Vec<_>>(),
+                "recorded": report.recorded,
+            });
+            println!("{}", serde_json::to_string_pretty(&output).unwrap());
+        }
+        OutputFormat::Stata => {
+            println!("global stacy_status \"success\"");
+            println!("scalar stacy_layout_from = {}", report.from);
+            println!("scalar stacy_layout_to = {}", report.to);
+            println!("scalar stacy_recorded = {}", report.recorded);
+        }
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
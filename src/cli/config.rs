@@ -0,0 +1,154 @@
+//! `stacy config` command implementation
+//!
+//! Reads and writes the user config (`~/.config/stacy/config.toml`):
+//! - `stacy config get <key>` prints a setting
+//! - `stacy config set <key> <value>` changes it
+//!
+//! `stacy config validate` checks the project's stacy.toml instead, and
+//! `stacy config schema` prints the JSON Schema editors check it against.
+
+use crate::cli::output_format::OutputFormat;
+use crate::cli::output_types::{CommandOutput, ConfigValidateOutput};
+use crate::error::{Error, Result};
+use crate::project::config::JSON_SCHEMA;
+use crate::project::root::find_project_root_unchecked;
+use crate::project::user_config::{get_config_path, read_user_config, save_user_config};
+use crate::project::validate;
+use clap::{Args, Subcommand};
+use colored::Colorize;
+use std::path::PathBuf;
+
+#[derive(Args)]
+#[command(about = "Read or change user settings, or check stacy.toml", long_about = None)]
+pub struct ConfigArgs {
+    #[command(subcommand)]
+    pub command: ConfigCommand,
+}
+
+impl ConfigArgs {
+    pub fn format(&self) -> OutputFormat {
+        match &self.command {
+            ConfigCommand::Validate { format } => *format,
+            _ => OutputFormat::Human,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+pub enum ConfigCommand {
+    /// Print a setting from the user config
+    #[command(after_help = "\
+Examples:
+  stacy config get update_channel
+  stacy config get telemetry.enabled")]
+    Get {
+        /// Setting name (e.g. proxy, cache.shared_path, telemetry.enabled)
+        key: String,
+    },
+    /// Change a setting in the user config
+    #[command(after_help = "\
+Examples:
+  stacy config set update_channel beta
+  stacy config set telemetry.enabled true
+  stacy config set proxy http://proxy.example.com:3128")]
+    Set {
+        /// Setting name (e.g. proxy, cache.shared_path, telemetry.enabled)
+        key: String,
+        /// New value
+        value: String,
+    },
+    /// Check the project's stacy.toml: unknown keys, wrong types, and tasks
+    /// or stages that refer to missing scripts
+    #[command(after_help = "\
+Examples:
+  stacy config validate
+  stacy config validate --format json
+
+Exits with 1 when stacy.toml has errors.")]
+    Validate {
+        /// Output format: human (default), json, or stata
+        #[arg(long, value_enum, default_value = "human")]
+        format: OutputFormat,
+    },
+    /// Print the JSON Schema of stacy.toml, for editor completion and checks
+    #[command(after_help = "\
+Examples:
+  stacy config schema > stacy.schema.json
+
+Then start stacy.toml with `#:schema ./stacy.schema.json` for editors using
+Taplo (Even Better TOML in VS Code).")]
+    Schema,
+}
+
+/// Execute the config command
+pub fn execute(args: &ConfigArgs) -> Result<()> {
+    match &args.command {
+        ConfigCommand::Validate { format } => return execute_validate(*format),
+        ConfigCommand::Schema => {
+            print!("{}", JSON_SCHEMA);
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    let config_path = get_config_path()
+        .ok_or_else(|| Error::Config("Unable to determine user config directory".to_string()))?;
+    let mut config = read_user_config(&config_path)?.unwrap_or_default();
+
+    match &args.command {
+        ConfigCommand::Get { key } => match config.get(key)? {
+            Some(value) => println!("{}", value),
+            None => eprintln!("{} is not set", key),
+        },
+        ConfigCommand::Set { key, value } => {
+            config.set(key, value)?;
+            save_user_config(&config)?;
+            println!("Set {} = {} in {}", key, value, config_path.display());
+        }
+        ConfigCommand::Validate { .. } | ConfigCommand::Schema => unreachable!("handled above"),
+    }
+    Ok(())
+}
+
+fn execute_validate(format: OutputFormat) -> Result<()> {
+    let root = stacy_toml_dir()?;
+    let errors = validate::validate(&root)?;
+    let output = ConfigValidateOutput {
+        path: root.join("stacy.toml"),
+        valid: errors.is_empty(),
+        error_count: errors.len(),
+        errors,
+    };
+
+    match format {
+        OutputFormat::Json => println!("{}", output.to_json()),
+        OutputFormat::Stata => println!("{}", output.to_stata()),
+        OutputFormat::Human => {
+            for error in &output.errors {
+                eprintln!("{}: {}\n", "error".red().bold(), error);
+            }
+            if output.valid {
+                println!("{} is valid", output.path.display());
+            }
+        }
+    }
+
+    if output.valid {
+        Ok(())
+    } else {
+        Err(Error::Config(format!(
+            "{} has {} error(s)",
+            output.path.display(),
+            output.error_count
+        )))
+    }
+}
+
+/// The directory of the project's stacy.toml
+fn stacy_toml_dir() -> Result<PathBuf> {
+    find_project_root_unchecked()?
+        .filter(|root| root.join("stacy.toml").is_file())
+        .ok_or_else(|| {
+            Error::Config("No stacy.toml found here or in any parent directory".to_string())
+        })
+}
@@ -0,0 +1,587 @@
+//! `stacy daemon` command implementation
+//!
+//! Keeps one stacy process running for editor plugins, which would otherwise
+//! start stacy, read stacy.toml and stacy.lock and look for Stata on every
+//! request. The daemon listens on a Unix socket (`.stacy/daemon.sock` by
+//! default) and speaks JSON-RPC 2.0, one request per line and one response
+//! per line:
+//!
+//! - `ping` — the stacy version and project root
+//! - `run` — run a script (`{"script": ..., "args": {...}}`) and report the outcome
+//! - `deps` — the dependency tree of a script, as `stacy deps --format json`
+//! - `lint` — diagnostics for an editor buffer (`{"text": ..., "path": ...}`)
+//! - `tasks` — the tasks in stacy.toml, as `stacy task --list --format json`
+//! - `shutdown` — stop the daemon
+//!
+//! The project is read again only when stacy.toml or stacy.lock change, and
+//! Stata is looked up on the first `run` and then reused. Paths in requests
+//! are relative to the project root, which is also where scripts run.
+
+use crate::cli::deps::deps_json;
+use crate::cli::output_types::{TaskInfo, TaskListOutput};
+use crate::deps::lint::lint_buffer;
+use crate::deps::tree::analyze_dependencies;
+use crate::error::{Error, Result, StataError};
+use crate::executor::log_policy::LogPolicy;
+use crate::executor::verbosity::Verbosity;
+use crate::executor::StataExecutor;
+use crate::project::{profile, Project};
+use crate::task::{task_description, TaskGraph};
+use clap::Args;
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::SystemTime;
+
+#[derive(Args)]
+#[command(after_help = "\
+Examples:
+  stacy daemon                            Listen on .stacy/daemon.sock
+  stacy daemon --socket /tmp/stacy.sock   Listen on another socket")]
+pub struct DaemonArgs {
+    /// Socket to listen on (default: .stacy/daemon.sock in the project)
+    #[arg(long, value_name = "PATH")]
+    pub socket: Option<PathBuf>,
+
+    /// Stata engine to use (overrides config and auto-detection)
+    #[arg(long, value_name = "ENGINE")]
+    pub engine: Option<String>,
+}
+
+// JSON-RPC 2.0 error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// stacy failed while handling the request
+const SERVER_ERROR: i64 = -32000;
+
+pub fn execute(args: &DaemonArgs) -> Result<()> {
+    let project = Project::find()?.ok_or_else(|| {
+        Error::Config("Not in a stacy project. Run 'stacy init' first.".to_string())
+    })?;
+    let socket = args
+        .socket
+        .clone()
+        .unwrap_or_else(|| project.root.join(".stacy").join("daemon.sock"));
+    std::env::set_current_dir(&project.root)?;
+    serve(&socket, Arc::new(Daemon::new(project, args.engine.clone())))
+}
+
+#[cfg(unix)]
+fn serve(socket: &Path, daemon: Arc<Daemon>) -> Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    if let Ok(metadata) = std::fs::symlink_metadata(socket) {
+        if !metadata.file_type().is_socket() {
+            return Err(Error::Config(format!(
+                "{} exists and is not a socket; give --socket a path that is free",
+                socket.display()
+            )));
+        }
+        if UnixStream::connect(socket).is_ok() {
+            return Err(Error::Config(format!(
+                "A stacy daemon is already listening on {}",
+                socket.display()
+            )));
+        }
+        // Left behind by a daemon that was killed
+        std::fs::remove_file(socket)?;
+    }
+    if let Some(dir) = socket.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    // `run` executes code: only the owner may connect. The socket is created
+    // that way rather than narrowed after `bind`, which would leave it open
+    // in between. No other thread runs yet to see the umask.
+    // SAFETY: umask only swaps the process's file mode mask
+    let umask = unsafe { libc::umask(0o177) };
+    let bound = UnixListener::bind(socket);
+    // SAFETY: as above, restoring the mask
+    unsafe { libc::umask(umask) };
+    let listener = bound
+        .map_err(|e| Error::Config(format!("Failed to listen on {}: {}", socket.display(), e)))?;
+    eprintln!("stacy daemon listening on {}", socket.display());
+
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let daemon = Arc::clone(&daemon);
+        let socket = socket.to_path_buf();
+        std::thread::spawn(move || {
+            let Ok(reader) = stream.try_clone() else {
+                return;
+            };
+            if daemon.serve_stream(std::io::BufReader::new(reader), stream) {
+                let _ = std::fs::remove_file(&socket);
+                crate::session::exit(0);
+            }
+        });
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn serve(_socket: &Path, _daemon: Arc<Daemon>) -> Result<()> {
+    Err(Error::Config(
+        "stacy daemon needs Unix domain sockets and is not available on Windows".to_string(),
+    ))
+}
+
+/// A failed request, answered as a JSON-RPC error object
+#[derive(Debug)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn invalid_params(message: impl Into<String>) -> Self {
+        Self {
+            code: INVALID_PARAMS,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<Error> for RpcError {
+    fn from(e: Error) -> Self {
+        Self {
+            code: SERVER_ERROR,
+            message: e.to_string(),
+        }
+    }
+}
+
+type RpcResult = std::result::Result<Value, RpcError>;
+
+/// What the daemon keeps from the project between requests
+struct State {
+    project: Project,
+    /// Modification times of stacy.toml and stacy.lock when `project` was read
+    stamp: [Option<SystemTime>; 2],
+    /// Packages in stacy.toml and stacy.lock, lowercase
+    declared: HashSet<String>,
+    /// Set up on the first `run`: finding Stata is the slow part
+    executor: Option<StataExecutor>,
+}
+
+impl State {
+    fn load(project: Project) -> Self {
+        Self {
+            stamp: stamp(&project.root),
+            declared: declared_packages(&project),
+            project,
+            executor: None,
+        }
+    }
+
+    fn executor(&mut self, engine: Option<&str>) -> Result<StataExecutor> {
+        if let Some(executor) = &self.executor {
+            return Ok(executor.clone());
+        }
+        let executor = StataExecutor::try_new(engine, Verbosity::Quiet)?
+            .with_local_ado_paths(self.project.resolve_local_ado_paths())
+            .with_processors(self.project.processors())
+            .with_reproducibility(self.project.reproducibility());
+        self.executor = Some(executor.clone());
+        Ok(executor)
+    }
+}
+
+pub struct Daemon {
+    root: PathBuf,
+    engine: Option<String>,
+    state: Mutex<State>,
+}
+
+impl Daemon {
+    pub fn new(project: Project, engine: Option<String>) -> Self {
+        Self {
+            root: project.root.clone(),
+            engine,
+            state: Mutex::new(State::load(project)),
+        }
+    }
+
+    /// Answer the requests read from `reader` on `writer` until the connection
+    /// closes. Returns `true` once a `shutdown` request was answered.
+    pub fn serve_stream(&self, reader: impl BufRead, mut writer: impl Write) -> bool {
+        for line in reader.lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (response, shutdown) = self.handle_line(&line);
+            if let Some(response) = response {
+                if writeln!(writer, "{}", response)
+                    .and_then(|_| writer.flush())
+                    .is_err()
+                {
+                    return shutdown;
+                }
+            }
+            if shutdown {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// The response to one request line, `None` for a notification (no
+    /// `id`), and whether the request was `shutdown`
+    fn handle_line(&self, line: &str) -> (Option<String>, bool) {
+        let request: Value = match serde_json::from_str(line) {
+            Ok(request) => request,
+            Err(e) => {
+                let error = RpcError {
+                    code: PARSE_ERROR,
+                    message: e.to_string(),
+                };
+                return (Some(response(Value::Null, Err(error))), false);
+            }
+        };
+        let id = request.get("id").cloned();
+        let Some(method) = request.get("method").and_then(Value::as_str) else {
+            let error = RpcError {
+                code: INVALID_REQUEST,
+                message: "Request has no method".to_string(),
+            };
+            return (Some(response(id.unwrap_or(Value::Null), Err(error))), false);
+        };
+        let params = request.get("params").cloned().unwrap_or(Value::Null);
+        let result = self.dispatch(method, &params);
+        (id.map(|id| response(id, result)), method == "shutdown")
+    }
+
+    fn dispatch(&self, method: &str, params: &Value) -> RpcResult {
+        match method {
+            "ping" => Ok(json!({
+                "version": env!("CARGO_PKG_VERSION"),
+                "project": self.root.display().to_string(),
+            })),
+            "run" => self.run(params),
+            "deps" => self.deps(params),
+            "lint" => self.lint(params),
+            "tasks" => self.tasks(),
+            "shutdown" => Ok(Value::Null),
+            _ => Err(RpcError {
+                code: METHOD_NOT_FOUND,
+                message: format!("Unknown method: {}", method),
+            }),
+        }
+    }
+
+    /// The project state, read again if stacy.toml or stacy.lock changed
+    fn state(&self) -> Result<MutexGuard<'_, State>> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if stamp(&self.root) != state.stamp {
+            let project = Project::find_from(&self.root)?.ok_or_else(|| {
+                Error::Config(format!(
+                    "{} is no longer a stacy project",
+                    self.root.display()
+                ))
+            })?;
+            *state = State::load(project);
+        }
+        Ok(state)
+    }
+
+    /// A path parameter, resolved against the project root
+    fn path_param(&self, params: &Value, name: &str) -> std::result::Result<PathBuf, RpcError> {
+        params
+            .get(name)
+            .and_then(Value::as_str)
+            .map(|path| self.root.join(path))
+            .ok_or_else(|| RpcError::invalid_params(format!("Missing parameter: {}", name)))
+    }
+
+    fn script_param(&self, params: &Value) -> std::result::Result<PathBuf, RpcError> {
+        let script = self.path_param(params, "script")?;
+        if !script.is_file() {
+            return Err(RpcError::invalid_params(format!(
+                "Script not found: {}",
+                script.display()
+            )));
+        }
+        Ok(script)
+    }
+
+    fn run(&self, params: &Value) -> RpcResult {
+        let script = self.script_param(params)?;
+        let args: HashMap<String, String> = match params.get("args") {
+            None | Some(Value::Null) => HashMap::new(),
+            Some(args) => serde_json::from_value(args.clone()).map_err(|_| {
+                RpcError::invalid_params("args must be an object of strings".to_string())
+            })?,
+        };
+
+        // Stata may take a while: other requests go on while it works
+        let (executor, log_policy) = {
+            let mut state = self.state()?;
+            let executor = state.executor(self.engine.as_deref())?;
+            (executor, LogPolicy::for_project(Some(&state.project)))
+        };
+        let result = executor.run_with_args(&script, Some(&self.root), &args)?;
+        let log_file = log_policy.finalize_for(&script, &result.log_file, result.success);
+
+        Ok(json!({
+            "script": script.display().to_string(),
+            "success": result.success,
+            "exit_code": result.exit_code,
+            "duration_secs": result.duration.as_secs_f64(),
+            "log_file": log_file.map(|p| p.display().to_string()),
+            "error_count": result.errors.len(),
+            "errors": result.errors.iter().map(error_json).collect::<Vec<_>>(),
+        }))
+    }
+
+    fn deps(&self, params: &Value) -> RpcResult {
+        let script = self.script_param(params)?;
+        let analysis = analyze_dependencies(&script)?;
+        let status = if analysis.has_missing || analysis.has_circular {
+            "error"
+        } else {
+            "success"
+        };
+        Ok(deps_json(&analysis.tree, &script, status))
+    }
+
+    fn lint(&self, params: &Value) -> RpcResult {
+        let text = params
+            .get("text")
+            .and_then(Value::as_str)
+            .ok_or_else(|| RpcError::invalid_params("Missing parameter: text".to_string()))?;
+        // An unsaved buffer has no path: resolve against the project root
+        let base_dir = match params.get("path").and_then(Value::as_str) {
+            Some(path) => self
+                .root
+                .join(path)
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_else(|| self.root.clone()),
+            None => self.root.clone(),
+        };
+        let state = self.state()?;
+        let diagnostics = lint_buffer(text, &base_dir, &self.root, Some(&state.declared))?;
+        Ok(json!({ "diagnostics": diagnostics }))
+    }
+
+    fn tasks(&self) -> RpcResult {
+        let state = self.state()?;
+        let tasks = match &state.project.config {
+            Some(config) => TaskGraph::from_config(&config.scripts)?
+                .list_tasks()
+                .iter()
+                .map(|(name, def)| TaskInfo {
+                    name: name.to_string(),
+                    description: task_description(def),
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+        let output = TaskListOutput {
+            task_count: tasks.len(),
+            tasks,
+        };
+        Ok(json!(output))
+    }
+}
+
+/// One JSON-RPC response line
+fn response(id: Value, result: RpcResult) -> String {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(e) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": e.code, "message": e.message },
+        }),
+    }
+    .to_string()
+}
+
+fn error_json(error: &StataError) -> Value {
+    match error {
+        StataError::StataCode {
+            r_code,
+            message,
+            line_number,
+            ..
+        } => json!({ "r_code": r_code, "line_number": line_number, "message": message }),
+        StataError::ProcessKilled { exit_code } => json!({
+            "r_code": null,
+            "line_number": null,
+            "message": format!("Process killed (exit code {})", exit_code),
+        }),
+    }
+}
+
+/// Modification times of stacy.toml and the lockfile
+fn stamp(root: &Path) -> [Option<SystemTime>; 2] {
+    ["stacy.toml".to_string(), profile::lockfile_name()].map(|name| {
+        std::fs::metadata(root.join(name))
+            .and_then(|m| m.modified())
+            .ok()
+    })
+}
+
+fn declared_packages(project: &Project) -> HashSet<String> {
+    let mut names = HashSet::new();
+    if let Some(config) = &project.config {
+        let packages = &config.packages;
+        names.extend(
+            packages
+                .dependencies
+                .keys()
+                .chain(packages.dev.keys())
+                .chain(packages.test.keys())
+                .map(|name| name.to_lowercase()),
+        );
+    }
+    if let Some(lockfile) = &project.lockfile {
+        names.extend(lockfile.packages.keys().map(|name| name.to_lowercase()));
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::Cursor;
+    use tempfile::TempDir;
+
+    fn daemon(stacy_toml: &str) -> (TempDir, Daemon) {
+        let temp = TempDir::new().unwrap();
+        fs::write(temp.path().join("stacy.toml"), stacy_toml).unwrap();
+        let project = Project::find_from(temp.path()).unwrap().unwrap();
+        let daemon = Daemon::new(project, None);
+        (temp, daemon)
+    }
+
+    fn call(daemon: &Daemon, request: Value) -> Value {
+        let (response, _) = daemon.handle_line(&request.to_string());
+        serde_json::from_str(&response.unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_ping_and_errors() {
+        let (_temp, daemon) = daemon("[project]\n");
+        let pong = call(
+            &daemon,
+            json!({"jsonrpc": "2.0", "id": 1, "method": "ping"}),
+        );
+        assert_eq!(pong["id"], 1);
+        assert_eq!(pong["result"]["version"], env!("CARGO_PKG_VERSION"));
+
+        let unknown = call(
+            &daemon,
+            json!({"jsonrpc": "2.0", "id": 2, "method": "nope"}),
+        );
+        assert_eq!(unknown["error"]["code"], METHOD_NOT_FOUND);
+
+        let (response, _) = daemon.handle_line("{not json");
+        let parsed: Value = serde_json::from_str(&response.unwrap()).unwrap();
+        assert_eq!(parsed["error"]["code"], PARSE_ERROR);
+        assert_eq!(parsed["id"], Value::Null);
+
+        // Notifications get no response
+        let (response, _) = daemon.handle_line(r#"{"jsonrpc": "2.0", "method": "ping"}"#);
+        assert!(response.is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_serve_refuses_a_path_that_is_not_a_socket() {
+        let (temp, daemon) = daemon("[project]\n");
+        let notes = temp.path().join("notes.txt");
+        fs::write(&notes, "keep me").unwrap();
+        let err = serve(&notes, Arc::new(daemon)).unwrap_err();
+        assert!(err.to_string().contains("is not a socket"), "{}", err);
+        assert_eq!(fs::read_to_string(&notes).unwrap(), "keep me");
+    }
+
+    #[test]
+    fn test_tasks_reload_after_config_change() {
+        let (temp, daemon) = daemon("[scripts]\nclean = \"clean.do\"\n");
+        let tasks = call(
+            &daemon,
+            json!({"jsonrpc": "2.0", "id": 1, "method": "tasks"}),
+        );
+        assert_eq!(tasks["result"]["task_count"], 1);
+        assert_eq!(tasks["result"]["tasks"][0]["name"], "clean");
+
+        fs::write(
+            temp.path().join("stacy.toml"),
+            "[scripts]\nclean = \"clean.do\"\nanalyze = \"analyze.do\"\n",
+        )
+        .unwrap();
+        // Written within the same mtime tick the change could go unseen
+        let later = SystemTime::now() + std::time::Duration::from_secs(5);
+        fs::File::options()
+            .write(true)
+            .open(temp.path().join("stacy.toml"))
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        let tasks = call(
+            &daemon,
+            json!({"jsonrpc": "2.0", "id": 2, "method": "tasks"}),
+        );
+        assert_eq!(tasks["result"]["task_count"], 2);
+    }
+
+    #[test]
+    fn test_lint_and_deps_resolve_against_project() {
+        let (temp, daemon) = daemon("[packages.dependencies]\nestout = \"ssc\"\n");
+        fs::write(temp.path().join("main.do"), "do clean.do\n").unwrap();
+
+        let lint = call(
+            &daemon,
+            json!({"jsonrpc": "2.0", "id": 1, "method": "lint", "params": {
+                "path": "main.do",
+                "text": "require estout\nrequire reghdfe\ndo clean.do\n",
+            }}),
+        );
+        let diagnostics = lint["result"]["diagnostics"].as_array().unwrap();
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0]["line"], 2);
+        assert_eq!(diagnostics[1]["severity"], "error");
+
+        let deps = call(
+            &daemon,
+            json!({"jsonrpc": "2.0", "id": 2, "method": "deps", "params": {"script": "main.do"}}),
+        );
+        assert_eq!(deps["result"]["summary"]["missing_count"], 1);
+
+        let missing = call(
+            &daemon,
+            json!({"jsonrpc": "2.0", "id": 3, "method": "run", "params": {"script": "gone.do"}}),
+        );
+        assert_eq!(missing["error"]["code"], INVALID_PARAMS);
+    }
+
+    #[test]
+    fn test_serve_stream_stops_on_shutdown() {
+        let (_temp, daemon) = daemon("[project]\n");
+        let input = "{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"ping\"}\n\n\
+                     {\"jsonrpc\":\"2.0\",\"id\":2,\"method\":\"shutdown\"}\n\
+                     {\"jsonrpc\":\"2.0\",\"id\":3,\"method\":\"ping\"}\n";
+        let mut output = Vec::new();
+        assert!(daemon.serve_stream(Cursor::new(input), &mut output));
+        let lines: Vec<Value> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1]["id"], 2);
+    }
+}
@@ -0,0 +1,143 @@
+//! `stacy data` command implementation
+//!
+//! Fetches the input data declared in stacy.toml's `[data]` section, from
+//! URLs or from Dataverse and Zenodo by DOI:
+//! - `stacy data fetch` downloads what is missing and verifies checksums
+
+use crate::cli::output_format::OutputFormat;
+use crate::cli::output_types::{CommandOutput, DataFetchOutput, DataFileInfo};
+use crate::data::{DataFetcher, FetchOutcome};
+use crate::error::{Error, Result};
+use crate::project::Project;
+use clap::{Args, Subcommand};
+
+#[derive(Args)]
+#[command(about = "Download the input data declared in stacy.toml", long_about = None)]
+pub struct DataArgs {
+    #[command(subcommand)]
+    pub command: DataCommand,
+}
+
+#[derive(Subcommand)]
+pub enum DataCommand {
+    /// Download missing data files and verify their checksums
+    #[command(after_help = "\
+Examples:
+  stacy data fetch                   Fetch every file in [data]
+  stacy data fetch survey            Fetch only 'survey'
+  stacy data fetch --force           Download again, ignoring local copies")]
+    Fetch(FetchArgs),
+}
+
+#[derive(Args)]
+pub struct FetchArgs {
+    /// Data files to fetch, by name in [data] (default: all)
+    #[arg(value_name = "NAME")]
+    pub names: Vec<String>,
+
+    /// Download even if the file is present or cached
+    #[arg(long)]
+    pub force: bool,
+
+    /// Output format: human (default), json, or stata
+    #[arg(long, value_enum, default_value = "human")]
+    pub format: OutputFormat,
+}
+
+impl DataArgs {
+    /// Output format of the subcommand
+    pub fn format(&self) -> OutputFormat {
+        match &self.command {
+            DataCommand::Fetch(args) => args.format,
+        }
+    }
+}
+
+/// Execute the data command
+pub fn execute(args: &DataArgs) -> Result<()> {
+    let project = Project::find()?.ok_or(Error::ProjectNotFound)?;
+
+    match &args.command {
+        DataCommand::Fetch(fetch_args) => execute_fetch(fetch_args, &project),
+    }
+}
+
+/// Execute `stacy data fetch`
+fn execute_fetch(args: &FetchArgs, project: &Project) -> Result<()> {
+    let config = project.config.clone().unwrap_or_default();
+    if config.data.is_empty() {
+        return Err(Error::Config(
+            "No data declared. Add [data.<name>] sections to stacy.toml:\n\n  [data.survey]\n  url = \"https://example.org/survey.csv\"\n  path = \"data/raw/survey.csv\"\n  sha256 = \"...\"".to_string(),
+        ));
+    }
+    if let Some(unknown) = args.names.iter().find(|n| !config.data.contains_key(*n)) {
+        return Err(Error::Config(format!(
+            "No data named '{}' in [data]",
+            unknown
+        )));
+    }
+
+    let fetcher = DataFetcher::new(&project.root)?.with_force(args.force);
+    let mut files = Vec::new();
+    for (name, source) in &config.data {
+        if !args.names.is_empty() && !args.names.contains(name) {
+            continue;
+        }
+        for result in fetcher.fetch(name, source)? {
+            if args.format == OutputFormat::Human {
+                println!(
+                    "  {:<10}  {:<15}  {}",
+                    result.outcome.label(),
+                    name,
+                    result.path.display()
+                );
+            }
+            files.push(DataFileInfo {
+                name: result.name,
+                path: result.path,
+                status: result.outcome.label().to_string(),
+                sha256: result.sha256,
+                // Files from a DOI are checked against the repository's checksums
+                pinned: source.sha256.is_some() || source.doi.is_some(),
+            });
+        }
+    }
+
+    let count =
+        |outcome: FetchOutcome| files.iter().filter(|f| f.status == outcome.label()).count();
+    let output = DataFetchOutput {
+        file_count: files.len(),
+        downloaded_count: count(FetchOutcome::Downloaded),
+        cached_count: count(FetchOutcome::Cached),
+        present_count: count(FetchOutcome::Present),
+        files,
+    };
+
+    match args.format {
+        OutputFormat::Json => println!("{}", output.to_json()),
+        OutputFormat::Stata => println!("{}", output.to_stata()),
+        OutputFormat::Human => {
+            println!(
+                "{} {}: {} downloaded, {} from cache, {} already present.",
+                output.file_count,
+                if output.file_count == 1 {
+                    "file"
+                } else {
+                    "files"
+                },
+                output.downloaded_count,
+                output.cached_count,
+                output.present_count
+            );
+            let unpinned: Vec<&DataFileInfo> = output.files.iter().filter(|f| !f.pinned).collect();
+            if !unpinned.is_empty() {
+                println!();
+                println!("Not pinned; add the checksum to stacy.toml so a changed file is caught:");
+                for file in unpinned {
+                    println!("  [data.{}]  sha256 = \"{}\"", file.name, file.sha256);
+                }
+            }
+        }
+    }
+    Ok(())
+}
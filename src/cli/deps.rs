@@ -1,24 +1,37 @@
 //! `stacy deps` command implementation
 //!
 //! Analyzes Stata scripts for dependencies (do/run/include statements)
-//! and displays them as a tree or flat list.
+//! and displays them as a tree or flat list. With `--all`, analyzes every
+//! script of the project at once (see [`crate::deps::graph`]).
 
 use crate::cli::output_format::OutputFormat;
-use crate::cli::output_types::{CommandOutput, DepsOutput};
-use crate::deps::tree::{analyze_dependencies, DependencyTree};
+use crate::cli::output_types::{CommandOutput, DepsGraphOutput, DepsOutput};
+use crate::deps::cache::DepsCache;
+use crate::deps::graph::{build_project_graph, Call, ProjectGraph};
+use crate::deps::tree::{analyze_dependencies, AdopathChange, DependencyAnalysis, DependencyTree};
 use crate::error::Result;
+use crate::project::config::TaskDef;
+use crate::project::Project;
+use crate::utils::encoding;
+use crate::utils::paths::normalize;
 use clap::Args;
-use std::path::PathBuf;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 
 #[derive(Args)]
 #[command(after_help = "\
 Examples:
   stacy deps main.do                      Show dependency tree
-  stacy deps main.do --flat               Show as flat list")]
+  stacy deps main.do --flat               Show as flat list
+  stacy deps --all                        Entry points, orphans and shared scripts")]
 pub struct DepsArgs {
     /// Stata script to analyze
-    #[arg(value_name = "SCRIPT")]
-    pub script: PathBuf,
+    #[arg(value_name = "SCRIPT", required_unless_present = "all")]
+    pub script: Option<PathBuf>,
+
+    /// Analyze every .do file in the project instead of one script
+    #[arg(long, conflicts_with_all = ["script", "flat"])]
+    pub all: bool,
 
     /// Show flat list instead of tree
     #[arg(long)]
@@ -31,11 +44,15 @@ pub struct DepsArgs {
 
 pub fn execute(args: &DepsArgs) -> Result<()> {
     let format = args.format;
+    let script = match &args.script {
+        Some(script) if !args.all => script,
+        _ => return execute_all(format),
+    };
 
     // Verify script exists
-    if !args.script.exists() {
+    if !script.exists() {
         let output = DepsOutput {
-            script: args.script.clone(),
+            script: script.clone(),
             unique_count: 0,
             has_circular: false,
             has_missing: true,
@@ -48,17 +65,17 @@ pub fn execute(args: &DepsArgs) -> Result<()> {
             OutputFormat::Json => {
                 println!(
                     r#"{{"status": "error", "error": "Script not found: {}"}}"#,
-                    args.script.display()
+                    script.display()
                 );
             }
             OutputFormat::Stata => println!("{}", output.to_stata()),
-            OutputFormat::Human => eprintln!("Error: Script not found: {}", args.script.display()),
+            OutputFormat::Human => eprintln!("Error: Script not found: {}", script.display()),
         }
-        std::process::exit(3); // File error exit code
+        crate::session::exit(3); // File error exit code
     }
 
     // Analyze dependencies
-    let analysis = analyze_dependencies(&args.script)?;
+    let analysis = analyze_dependencies(script)?;
 
     // A missing or circular dependency means the graph could not be resolved:
     // the analysis is incomplete, so it must not report success.
@@ -66,7 +83,7 @@ pub fn execute(args: &DepsArgs) -> Result<()> {
 
     // Build output struct
     let output = DepsOutput {
-        script: args.script.clone(),
+        script: script.clone(),
         unique_count: analysis.tree.unique_count() as i32,
         has_circular: analysis.has_circular,
         has_missing: analysis.has_missing,
@@ -77,7 +94,7 @@ pub fn execute(args: &DepsArgs) -> Result<()> {
 
     // Output result
     match format {
-        OutputFormat::Json => print_json_output(&analysis.tree, &args.script, &output.status)?,
+        OutputFormat::Json => print_json_output(&analysis, script, &output.status)?,
         OutputFormat::Stata => println!("{}", output.to_stata()),
         OutputFormat::Human => {
             if args.flat {
@@ -113,6 +130,9 @@ pub fn execute(args: &DepsArgs) -> Result<()> {
                     println!("  - {}", path.display());
                 }
             }
+
+            print_adopath_warning(&analysis.adopath_changes);
+            print_latin1_warning(&analysis.latin1_scripts);
         }
     }
 
@@ -120,15 +140,255 @@ pub fn execute(args: &DepsArgs) -> Result<()> {
     // A cycle is not a file error — the files are all there — so it maps to
     // the generic failure code (1).
     if analysis.has_missing {
-        std::process::exit(3);
+        crate::session::exit(3);
     }
     if analysis.has_circular {
-        std::process::exit(1);
+        crate::session::exit(1);
+    }
+
+    Ok(())
+}
+
+/// `stacy deps --all`: every script of the project in one graph
+fn execute_all(format: OutputFormat) -> Result<()> {
+    let project = Project::find()?;
+    let root = match &project {
+        Some(project) => project.root.clone(),
+        None => std::env::current_dir()?,
+    };
+
+    // Installed packages ship their own .do files: not scripts of the project
+    let mut skip = vec![root.join("ado")];
+    let mut runs = HashSet::new();
+    if let Some(config) = project.as_ref().and_then(|p| p.config.as_ref()) {
+        skip.extend(config.paths.ado.iter().map(|dir| root.join(dir)));
+        for def in config.scripts.tasks.values() {
+            let script = match def {
+                TaskDef::Simple(script) => Some(script),
+                TaskDef::Complex(task) => task.script.as_ref(),
+                TaskDef::Sequential(_) => None,
+            };
+            runs.extend(script.map(|s| normalize(s)));
+        }
+        runs.extend(
+            config
+                .pipeline
+                .values()
+                .map(|stage| normalize(&stage.script)),
+        );
+    }
+    let test_settings = project
+        .as_ref()
+        .and_then(|p| p.config.as_ref())
+        .map(|config| config.test.clone())
+        .unwrap_or_default();
+    for test in crate::test::discovery::discover_tests(&root, &test_settings, &[])? {
+        let rel = test.path.strip_prefix(&root).unwrap_or(&test.path);
+        runs.insert(rel.to_path_buf());
+    }
+
+    let mut cache = match &project {
+        Some(project) => DepsCache::load(&project.root),
+        None => DepsCache::default(),
+    };
+    let graph = build_project_graph(&root, &runs, &skip, &mut cache)?;
+    cache.save();
+    let incomplete = graph.has_missing() || graph.has_circular();
+    let output = DepsGraphOutput {
+        root: root.clone(),
+        script_count: graph.scripts.len(),
+        entry_count: graph.entry_points.len(),
+        orphan_count: graph.orphans.len(),
+        shared_count: graph.shared.len(),
+        has_circular: graph.has_circular(),
+        has_missing: graph.has_missing(),
+        circular_count: graph.circular.len(),
+        missing_count: graph.missing.len(),
+        status: if incomplete { "error" } else { "success" }.to_string(),
+    };
+
+    match format {
+        OutputFormat::Json => {
+            let json = graph_json(&graph, &root, &output.status);
+            println!("{}", serde_json::to_string_pretty(&json)?);
+        }
+        OutputFormat::Stata => println!("{}", output.to_stata()),
+        OutputFormat::Human => print_graph_output(&graph, &root),
+    }
+
+    // Same exit codes as for a single script
+    if graph.has_missing() {
+        crate::session::exit(3);
+    }
+    if graph.has_circular() {
+        crate::session::exit(1);
     }
 
     Ok(())
 }
 
+fn print_graph_output(graph: &ProjectGraph, root: &Path) {
+    if graph.scripts.is_empty() {
+        println!("No .do files found in {}", root.display());
+        return;
+    }
+
+    println!(
+        "{} scripts: {} entry {}, {} {}, {} shared",
+        graph.scripts.len(),
+        graph.entry_points.len(),
+        if graph.entry_points.len() == 1 {
+            "point"
+        } else {
+            "points"
+        },
+        graph.orphans.len(),
+        if graph.orphans.len() == 1 {
+            "orphan"
+        } else {
+            "orphans"
+        },
+        graph.shared.len()
+    );
+
+    if !graph.entry_points.is_empty() {
+        println!();
+        println!("Entry points:");
+        for path in &graph.entry_points {
+            println!("  {}", path.display());
+        }
+    }
+
+    if !graph.orphans.is_empty() {
+        println!();
+        println!("Orphans (nothing calls them and stacy.toml doesn't run them):");
+        for path in &graph.orphans {
+            println!("  {}", path.display());
+        }
+    }
+
+    if !graph.shared.is_empty() {
+        println!();
+        println!("Called from more than one script:");
+        for (path, callers) in &graph.shared {
+            println!("  {}", path.display());
+            for caller in callers {
+                println!("    <- {}", caller.display());
+            }
+        }
+    }
+
+    if graph.has_circular() {
+        println!();
+        eprintln!("Error: Circular dependencies detected:");
+        for path in &graph.circular {
+            eprintln!("  - {}", path.display());
+        }
+    }
+
+    if graph.has_missing() {
+        println!();
+        eprintln!("Error: Missing files:");
+        for call in &graph.missing {
+            eprintln!(
+                "  - {} ({}:{})",
+                call.to.display(),
+                call.from.display(),
+                call.line
+            );
+        }
+    }
+
+    print_adopath_warning(&graph.adopath);
+}
+
+fn print_adopath_warning(changes: &[AdopathChange]) {
+    if changes.is_empty() {
+        return;
+    }
+    println!();
+    eprintln!("Warning: these statements change the adopath, so Stata can run commands stacy didn't lock:");
+    for change in changes {
+        eprintln!(
+            "  - {}:{}: {}",
+            change.script.display(),
+            change.line_number,
+            change.statement
+        );
+    }
+    eprintln!(
+        "  hint: use `stacy add` for packages and [paths] ado in stacy.toml for project .ado files"
+    );
+}
+
+fn print_latin1_warning(scripts: &[PathBuf]) {
+    let Some(first) = scripts.first() else {
+        return;
+    };
+    println!();
+    eprintln!("Warning: these scripts aren't UTF-8 and were read as Latin-1; Stata 14 and later garble their accented characters:");
+    for script in scripts {
+        eprintln!("  - {}", script.display());
+    }
+    eprintln!("  hint: {}", encoding::translate_hint(first));
+}
+
+/// The `--format json` document for `--all`
+fn graph_json(graph: &ProjectGraph, root: &Path, status: &str) -> serde_json::Value {
+    use serde_json::json;
+
+    let paths = |paths: &[PathBuf]| -> Vec<String> {
+        paths.iter().map(|p| p.display().to_string()).collect()
+    };
+    let calls = |calls: &[Call]| -> Vec<serde_json::Value> {
+        calls
+            .iter()
+            .map(|call| {
+                json!({
+                    "from": call.from.display().to_string(),
+                    "line_number": call.line,
+                    "type": call.dep_type.to_string(),
+                    "to": call.to.display().to_string(),
+                })
+            })
+            .collect()
+    };
+    let shared: Vec<serde_json::Value> = graph
+        .shared
+        .iter()
+        .map(|(path, callers)| {
+            json!({
+                "path": path.display().to_string(),
+                "callers": paths(callers),
+            })
+        })
+        .collect();
+
+    json!({
+        "status": status,
+        "root": root.display().to_string(),
+        "scripts": paths(&graph.scripts),
+        "entry_points": paths(&graph.entry_points),
+        "orphans": paths(&graph.orphans),
+        "shared": shared,
+        "calls": calls(&graph.calls),
+        "missing": calls(&graph.missing),
+        "adopath_changes": adopath_json(&graph.adopath),
+        "summary": {
+            "script_count": graph.scripts.len(),
+            "entry_count": graph.entry_points.len(),
+            "orphan_count": graph.orphans.len(),
+            "shared_count": graph.shared.len(),
+            "has_circular": graph.has_circular(),
+            "has_missing": graph.has_missing(),
+            "circular_paths": paths(&graph.circular),
+            "missing_paths": graph.missing.iter().map(|c| c.to.display().to_string()).collect::<Vec<_>>(),
+            "circular_count": graph.circular.len(),
+            "missing_count": graph.missing.len(),
+        }
+    })
+}
+
 fn print_tree_output(tree: &DependencyTree) {
     print!("{}", tree.format_tree());
 }
@@ -185,10 +445,31 @@ fn print_summary(tree: &DependencyTree) {
     }
 }
 
-fn print_json_output(tree: &DependencyTree, script: &std::path::Path, status: &str) -> Result<()> {
+fn print_json_output(
+    analysis: &DependencyAnalysis,
+    script: &std::path::Path,
+    status: &str,
+) -> Result<()> {
+    let mut output = deps_json(&analysis.tree, script, status);
+    output["adopath_changes"] = adopath_json(&analysis.adopath_changes);
+    output["latin1_scripts"] = analysis
+        .latin1_scripts
+        .iter()
+        .map(|p| p.display().to_string())
+        .collect();
+    println!("{}", serde_json::to_string_pretty(&output)?);
+    Ok(())
+}
+
+/// The `--format json` document (also answered by `deps` in `stacy daemon`)
+pub fn deps_json(
+    tree: &DependencyTree,
+    script: &std::path::Path,
+    status: &str,
+) -> serde_json::Value {
     use serde_json::json;
 
-    let output = json!({
+    json!({
         "status": status,
         "script": script.display().to_string(),
         "dependencies": tree_to_json(tree),
@@ -203,10 +484,20 @@ fn print_json_output(tree: &DependencyTree, script: &std::path::Path, status: &s
             "missing_count": tree.missing_paths().len(),
             "unresolved_count": tree.dynamic_paths().len(),
         }
-    });
+    })
+}
 
-    println!("{}", serde_json::to_string_pretty(&output)?);
-    Ok(())
+fn adopath_json(changes: &[AdopathChange]) -> serde_json::Value {
+    changes
+        .iter()
+        .map(|change| {
+            serde_json::json!({
+                "script": change.script.display().to_string(),
+                "line_number": change.line_number,
+                "statement": change.statement,
+            })
+        })
+        .collect()
 }
 
 fn tree_to_json(tree: &DependencyTree) -> serde_json::Value {
@@ -243,7 +534,8 @@ mod tests {
         let script = create_test_file(temp.path(), "main.do", "display \"hello\"");
 
         let args = DepsArgs {
-            script,
+            script: Some(script),
+            all: false,
             flat: false,
             format: OutputFormat::Human,
         };
@@ -259,7 +551,8 @@ mod tests {
         let script = create_test_file(temp.path(), "main.do", "do \"helper.do\"");
 
         let args = DepsArgs {
-            script,
+            script: Some(script),
+            all: false,
             flat: false,
             format: OutputFormat::Human,
         };
@@ -274,7 +567,8 @@ mod tests {
         let script = create_test_file(temp.path(), "main.do", "do \"helper.do\"");
 
         let args = DepsArgs {
-            script,
+            script: Some(script),
+            all: false,
             flat: true,
             format: OutputFormat::Human,
         };
@@ -0,0 +1,274 @@
+//! `stacy dev` command implementation
+//!
+//! The inner loop for writing a Stata package: watch the package's directory
+//! and, whenever a file in it changes, run a smoke script and/or tests again.
+//! By default the editable packages (`dev = true`) and `[paths] ado`
+//! directories are watched, which are on the adopath as they are, so every
+//! run sees the edit. Each run is a `stacy run`/`stacy test` of its own with a
+//! fresh Stata, and the smoke script is preceded by `discard`, so no program
+//! compiled from an older version of an .ado file survives.
+//!
+//! Files are polled rather than watched through OS notifications: package
+//! directories are small, and polling behaves the same on every platform and
+//! on network drives.
+
+use crate::error::{Error, Result};
+use crate::project::{profile, Project};
+use clap::Args;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, SystemTime};
+use walkdir::WalkDir;
+
+#[derive(Args)]
+#[command(after_help = "\
+Examples:
+  stacy dev --run smoke.do                Re-run smoke.do whenever an editable package changes
+  stacy dev --test                        Re-run all tests instead
+  stacy dev lib/myutils -f myutils        Watch lib/myutils, re-run the tests matching 'myutils'
+  stacy dev --run smoke.do --test         Both, smoke script first")]
+pub struct DevArgs {
+    /// Directories to watch (default: the editable packages and `[paths] ado`
+    /// directories in stacy.toml)
+    #[arg(value_name = "DIR")]
+    pub dirs: Vec<PathBuf>,
+
+    /// Smoke script to run after `discard` on every change (relative to the
+    /// project root)
+    #[arg(long, value_name = "SCRIPT", required_unless_present_any = ["test", "filter"])]
+    pub run: Option<PathBuf>,
+
+    /// Run the project's tests on every change
+    #[arg(long)]
+    pub test: bool,
+
+    /// Only run the tests matching this pattern (repeatable; implies --test)
+    #[arg(long, short = 'f', value_name = "PATTERN")]
+    pub filter: Vec<String>,
+
+    /// How often to look for changes, in milliseconds
+    #[arg(long, value_name = "MS", default_value = "500")]
+    pub interval: u64,
+}
+
+/// Size and modification time of every file under the watched directories
+type Snapshot = BTreeMap<PathBuf, (u64, Option<SystemTime>)>;
+
+pub fn execute(args: &DevArgs) -> Result<()> {
+    let project = Project::find()?.ok_or_else(|| {
+        Error::Config("Not in a stacy project. Run 'stacy init' first.".to_string())
+    })?;
+
+    let dirs = if args.dirs.is_empty() {
+        project.resolve_local_ado_paths()
+    } else {
+        args.dirs
+            .iter()
+            .map(|dir| std::env::current_dir().unwrap_or_default().join(dir))
+            .collect()
+    };
+    if dirs.is_empty() {
+        return Err(Error::Config(
+            "Nothing to watch: name a directory, or add your package with \
+             `stacy add <name> --source local:<dir> --editable`"
+                .to_string(),
+        ));
+    }
+    if let Some(dir) = dirs.iter().find(|dir| !dir.is_dir()) {
+        return Err(Error::Config(format!(
+            "{} is not a directory",
+            dir.display()
+        )));
+    }
+    if let Some(script) = &args.run {
+        if !project.root.join(script).is_file() {
+            return Err(Error::Config(format!(
+                "Smoke script {} not found in {}",
+                script.display(),
+                project.root.display()
+            )));
+        }
+    }
+    for hint in copied_package_hints(&project, &dirs) {
+        eprintln!("hint: {}", hint);
+    }
+
+    let interval = Duration::from_millis(args.interval.max(50));
+    let names: Vec<String> = dirs.iter().map(|d| display(d, &project.root)).collect();
+    println!("Watching {} (Ctrl-C to stop)", names.join(", "));
+
+    run_cycle(args, &project.root);
+    let mut last = snapshot(&dirs);
+    loop {
+        std::thread::sleep(interval);
+        let current = snapshot(&dirs);
+        if changed(&last, &current).is_empty() {
+            continue;
+        }
+        // An editor saves in more than one write: wait for it to settle
+        std::thread::sleep(interval);
+        let current = snapshot(&dirs);
+        let changes = changed(&last, &current);
+        last = current;
+        if changes.is_empty() {
+            continue;
+        }
+
+        println!();
+        let mut shown: Vec<String> = changes
+            .iter()
+            .take(3)
+            .map(|p| display(p, &project.root))
+            .collect();
+        if changes.len() > 3 {
+            shown.push(format!("{} more", changes.len() - 3));
+        }
+        println!("Changed: {}", shown.join(", "));
+        run_cycle(args, &project.root);
+    }
+}
+
+/// Run the smoke script, then the tests, each as a stacy command of its own
+fn run_cycle(args: &DevArgs, root: &Path) {
+    if let Some(script) = &args.run {
+        let script = script.display().to_string();
+        let code = format!("do `\"{}\"'", script);
+        report(
+            &format!("run {}", script),
+            stacy(root)
+                .args(["run", "-c", "discard", "-c", &code])
+                .status(),
+        );
+    }
+    if args.test || !args.filter.is_empty() {
+        let mut cmd = stacy(root);
+        cmd.arg("test");
+        for pattern in &args.filter {
+            cmd.args(["--filter", pattern]);
+        }
+        report("test", cmd.status());
+    }
+}
+
+/// This stacy, run in the project root with the same profile
+fn stacy(root: &Path) -> Command {
+    let exe = std::env::current_exe().unwrap_or_else(|_| PathBuf::from("stacy"));
+    let mut cmd = Command::new(exe);
+    cmd.current_dir(root).arg("--project").arg(root);
+    if let Some(name) = profile::active() {
+        cmd.args(["--env-profile", name]);
+    }
+    cmd
+}
+
+fn report(what: &str, status: std::io::Result<std::process::ExitStatus>) {
+    match status {
+        Ok(status) if status.success() => println!("stacy dev: {} passed", what),
+        Ok(status) => println!(
+            "stacy dev: {} failed (exit {}), waiting for changes",
+            what,
+            status.code().unwrap_or(1)
+        ),
+        Err(e) => eprintln!("stacy dev: could not start {}: {}", what, e),
+    }
+}
+
+/// Every file under `dirs`, leaving out hidden files and directories
+fn snapshot(dirs: &[PathBuf]) -> Snapshot {
+    let mut files = Snapshot::new();
+    for dir in dirs {
+        let entries = WalkDir::new(dir)
+            .into_iter()
+            .filter_entry(|e| e.depth() == 0 || !e.file_name().to_string_lossy().starts_with('.'))
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file());
+        for entry in entries {
+            if let Ok(meta) = entry.metadata() {
+                files.insert(entry.into_path(), (meta.len(), meta.modified().ok()));
+            }
+        }
+    }
+    files
+}
+
+/// Files added, removed or modified between two snapshots
+fn changed(old: &Snapshot, new: &Snapshot) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = new
+        .iter()
+        .filter(|(path, stamp)| old.get(*path) != Some(stamp))
+        .map(|(path, _)| path.clone())
+        .collect();
+    paths.extend(old.keys().filter(|p| !new.contains_key(*p)).cloned());
+    paths.sort();
+    paths
+}
+
+/// Watched directories holding a `local:` package that is copied into the
+/// cache: runs keep seeing the copy, not the edits
+fn copied_package_hints(project: &Project, dirs: &[PathBuf]) -> Vec<String> {
+    let Some(config) = &project.config else {
+        return Vec::new();
+    };
+    let watched: Vec<PathBuf> = dirs
+        .iter()
+        .filter_map(|d| crate::utils::paths::canonicalize(d).ok())
+        .collect();
+    config
+        .packages
+        .all_packages()
+        .filter(|(_, spec, _)| !spec.is_editable())
+        .filter_map(|(name, spec, _)| {
+            let path = spec.source().strip_prefix("local:")?;
+            let dir = crate::utils::paths::canonicalize(&project.root.join(path)).ok()?;
+            watched.contains(&dir).then(|| {
+                format!(
+                    "{} is copied into the cache, so runs won't see edits; \
+                     set dev = true on it (stacy add {} --source {} --editable)",
+                    name,
+                    name,
+                    spec.source()
+                )
+            })
+        })
+        .collect()
+}
+
+fn display(path: &Path, root: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .display()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_changed_files() {
+        let temp = TempDir::new().unwrap();
+        let dir = temp.path().to_path_buf();
+        std::fs::write(dir.join("a.ado"), "program define a\nend\n").unwrap();
+        std::fs::write(dir.join("b.sthlp"), "{smcl}\n").unwrap();
+        std::fs::create_dir(dir.join(".git")).unwrap();
+        std::fs::write(dir.join(".git").join("index"), "x").unwrap();
+
+        let before = snapshot(std::slice::from_ref(&dir));
+        assert_eq!(before.len(), 2);
+        assert!(changed(&before, &before).is_empty());
+
+        std::fs::write(dir.join("a.ado"), "program define a\ndisplay 1\nend\n").unwrap();
+        std::fs::remove_file(dir.join("b.sthlp")).unwrap();
+        std::fs::write(dir.join("c.ado"), "").unwrap();
+        // Hidden directories are not watched
+        std::fs::write(dir.join(".git").join("HEAD"), "x").unwrap();
+
+        let after = snapshot(std::slice::from_ref(&dir));
+        assert_eq!(
+            changed(&before, &after),
+            vec![dir.join("a.ado"), dir.join("b.sthlp"), dir.join("c.ado")]
+        );
+    }
+}
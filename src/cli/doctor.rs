@@ -6,12 +6,17 @@
 //! - Write permissions
 //! - Environment variables
 //! - Error code cache status
+//!
+//! `--fix` applies the fixes that are safe to automate: creating missing
+//! directories, repairing cache permissions, writing a starter user config,
+//! repairing a corrupt build cache, and installing the Stata wrappers.
 
 use crate::cli::output_format::OutputFormat;
 use crate::cli::output_types::{CommandOutput, DoctorOutput};
 use crate::error::error_db::ErrorCodeCache;
 use crate::error::Result;
 use crate::executor::binary::detect_stata_binary;
+use crate::packages::collisions;
 use crate::packages::dep_scan;
 use crate::packages::global_cache;
 use crate::packages::lockfile;
@@ -20,12 +25,17 @@ use crate::project::Project;
 use crate::update_check;
 use clap::Args;
 use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// Where the Stata wrappers are published (`net install stacy, from(...)`)
+const WRAPPERS_URL: &str = "https://stacy.janfasnacht.com/stata";
 
 #[derive(Args)]
 #[command(after_help = "\
 Examples:
   stacy doctor                            Run system diagnostics
-  stacy doctor --refresh                  Re-extract error codes from Stata")]
+  stacy doctor --refresh                  Re-extract error codes from Stata
+  stacy doctor --fix                      Apply safe automatic fixes")]
 pub struct DoctorArgs {
     /// Output format: human (default), json, or stata
     #[arg(long, value_enum, default_value = "human")]
@@ -34,6 +44,10 @@ pub struct DoctorArgs {
     /// Re-extract error codes from Stata
     #[arg(long)]
     pub refresh: bool,
+
+    /// Apply safe automatic fixes, then re-run the checks
+    #[arg(long)]
+    pub fix: bool,
 }
 
 /// Result of a diagnostic check
@@ -42,6 +56,69 @@ struct DiagnosticResult {
     status: CheckStatus,
     message: String,
     suggestion: Option<String>,
+    /// What `--fix` can do about it
+    fix: Option<Fix>,
+}
+
+/// A fix `stacy doctor --fix` applies without asking
+#[derive(Debug, Clone, PartialEq)]
+enum Fix {
+    /// Create missing directories
+    CreateDirs(Vec<PathBuf>),
+    /// Give the owner read/write access to a directory tree again
+    RepairPermissions(PathBuf),
+    /// Write `~/.config/stacy/config.toml` pointing at the detected binary
+    WriteUserConfig(String),
+    /// Drop the corrupt entries of a project's build cache
+    RepairBuildCache(PathBuf),
+    /// `net install` the Stata wrappers with the given binary
+    InstallWrappers(String),
+}
+
+impl Fix {
+    fn apply(&self) -> Result<String> {
+        match self {
+            Fix::CreateDirs(dirs) => {
+                for dir in dirs {
+                    std::fs::create_dir_all(dir)?;
+                }
+                let names: Vec<String> = dirs.iter().map(|d| d.display().to_string()).collect();
+                Ok(format!("Created {}", names.join(", ")))
+            }
+            Fix::RepairPermissions(dir) => {
+                repair_permissions(dir)?;
+                Ok(format!("Repaired permissions on {}", dir.display()))
+            }
+            Fix::WriteUserConfig(binary) => {
+                let config = crate::project::user_config::UserConfig {
+                    stata_binary: Some(binary.clone()),
+                    ..Default::default()
+                };
+                crate::project::user_config::save_user_config(&config)?;
+                let path = crate::project::user_config::get_config_path().unwrap_or_default();
+                Ok(format!(
+                    "Wrote {} (stata_binary = {})",
+                    path.display(),
+                    binary
+                ))
+            }
+            Fix::RepairBuildCache(root) => {
+                let removed = crate::cache::verify::verify(root)?.repair(root)?;
+                Ok(format!(
+                    "Repaired the build cache ({} bad {} removed)",
+                    removed,
+                    if removed == 1 { "entry" } else { "entries" }
+                ))
+            }
+            Fix::InstallWrappers(binary) => {
+                install_wrappers(binary)?;
+                Ok(format!(
+                    "Installed the Stata wrappers from {}",
+                    WRAPPERS_URL
+                ))
+            }
+        }
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -77,7 +154,27 @@ pub fn execute(args: &DoctorArgs) -> Result<()> {
         refresh_error_codes(format)?;
     }
 
-    let checks = run_all_checks()?;
+    let mut checks = run_all_checks()?;
+
+    // Apply --fix, then check again so the report shows the result
+    let mut fixed = Vec::new();
+    if args.fix {
+        for fix in checks.iter().filter_map(|c| c.fix.as_ref()) {
+            match fix.apply() {
+                Ok(description) => {
+                    if matches!(format, OutputFormat::Human) {
+                        eprintln!("Fixed: {}", description);
+                    }
+                    fixed.push(description);
+                }
+                Err(e) => eprintln!("Could not fix: {}", e),
+            }
+        }
+        if matches!(format, OutputFormat::Human) && !fixed.is_empty() {
+            eprintln!();
+        }
+        checks = run_all_checks()?;
+    }
 
     // Build output struct
     let passed = checks
@@ -99,17 +196,18 @@ pub fn execute(args: &DoctorArgs) -> Result<()> {
         warnings,
         failed,
         check_count: checks.len(),
+        fixed_count: fixed.len(),
     };
 
     match format {
-        OutputFormat::Human => print_human_output(&checks),
-        OutputFormat::Json => print_json_output(&checks),
+        OutputFormat::Human => print_human_output(&checks, args.fix),
+        OutputFormat::Json => print_json_output(&checks, &fixed),
         OutputFormat::Stata => println!("{}", output.to_stata()),
     }
 
     // Exit with error if any check failed
     if failed > 0 {
-        std::process::exit(1);
+        crate::session::exit(1);
     }
 
     Ok(())
@@ -148,13 +246,22 @@ fn run_all_checks() -> Result<Vec<DiagnosticResult>> {
         check_config(),
         check_local_ado_paths(),
         check_package_dependencies(),
+        check_ado_collisions(),
         check_cache_dir(),
+        check_build_cache(),
+        check_user_config(),
+        check_stata_wrappers(),
         check_error_codes(),
         check_write_permissions(),
         check_env_vars(),
         check_update_status(),
     ];
 
+    // Only problems are fixed: a passing check never offers a fix
+    debug_assert!(checks
+        .iter()
+        .all(|c| c.fix.is_none() || !matches!(c.status, CheckStatus::Pass)));
+
     Ok(checks)
 }
 
@@ -165,6 +272,7 @@ fn check_stata_binary() -> DiagnosticResult {
             status: CheckStatus::Pass,
             message: format!("Found: {}", binary),
             suggestion: None,
+            fix: None,
         },
         Err(_) => DiagnosticResult {
             name: "Stata Installation".to_string(),
@@ -174,6 +282,7 @@ fn check_stata_binary() -> DiagnosticResult {
                 "Install Stata, set $STATA_BINARY env var, or configure ~/.config/stacy/config.toml"
                     .to_string(),
             ),
+            fix: None,
         },
     }
 }
@@ -185,18 +294,21 @@ fn check_project() -> DiagnosticResult {
             status: CheckStatus::Pass,
             message: format!("Project root: {}", project.root.display()),
             suggestion: None,
+            fix: None,
         },
         Ok(None) => DiagnosticResult {
             name: "Project Detection".to_string(),
             status: CheckStatus::Warn,
             message: "Not in a stacy project".to_string(),
             suggestion: Some("Run 'stacy init' to create a project".to_string()),
+            fix: None,
         },
         Err(e) => DiagnosticResult {
             name: "Project Detection".to_string(),
             status: CheckStatus::Fail,
             message: format!("Error: {}", e),
             suggestion: None,
+            fix: None,
         },
     }
 }
@@ -210,6 +322,7 @@ fn check_config() -> DiagnosticResult {
                     status: CheckStatus::Pass,
                     message: "stacy.toml found and valid".to_string(),
                     suggestion: None,
+                    fix: None,
                 }
             } else {
                 let config_path = project.root.join("stacy.toml");
@@ -219,6 +332,7 @@ fn check_config() -> DiagnosticResult {
                         status: CheckStatus::Warn,
                         message: "stacy.toml exists but may be empty".to_string(),
                         suggestion: None,
+                        fix: None,
                     }
                 } else {
                     DiagnosticResult {
@@ -226,6 +340,7 @@ fn check_config() -> DiagnosticResult {
                         status: CheckStatus::Pass,
                         message: "No stacy.toml (using defaults)".to_string(),
                         suggestion: None,
+                        fix: None,
                     }
                 }
             }
@@ -235,12 +350,14 @@ fn check_config() -> DiagnosticResult {
             status: CheckStatus::Warn,
             message: "No project found (configuration not applicable)".to_string(),
             suggestion: None,
+            fix: None,
         },
         Err(e) => DiagnosticResult {
             name: "Configuration".to_string(),
             status: CheckStatus::Fail,
             message: format!("Error loading config: {}", e),
             suggestion: Some("Check stacy.toml syntax".to_string()),
+            fix: None,
         },
     }
 }
@@ -256,6 +373,7 @@ fn check_local_ado_paths() -> DiagnosticResult {
                         status: CheckStatus::Pass,
                         message: "No paths configured".to_string(),
                         suggestion: None,
+                        fix: None,
                     }
                 }
             };
@@ -266,14 +384,17 @@ fn check_local_ado_paths() -> DiagnosticResult {
                     status: CheckStatus::Pass,
                     message: "No paths configured".to_string(),
                     suggestion: None,
+                    fix: None,
                 };
             }
 
             let mut missing = Vec::new();
+            let mut missing_dirs = Vec::new();
             for rel_path in &config.paths.ado {
                 let abs_path = project.root.join(rel_path);
                 if !abs_path.exists() {
                     missing.push(rel_path.display().to_string());
+                    missing_dirs.push(abs_path);
                 }
             }
 
@@ -283,6 +404,7 @@ fn check_local_ado_paths() -> DiagnosticResult {
                     status: CheckStatus::Pass,
                     message: format!("{} path(s) configured, all exist", config.paths.ado.len()),
                     suggestion: None,
+                    fix: None,
                 }
             } else {
                 DiagnosticResult {
@@ -293,6 +415,7 @@ fn check_local_ado_paths() -> DiagnosticResult {
                         "Create the directories or remove them from [paths].ado in stacy.toml"
                             .to_string(),
                     ),
+                    fix: Some(Fix::CreateDirs(missing_dirs)),
                 }
             }
         }
@@ -301,12 +424,14 @@ fn check_local_ado_paths() -> DiagnosticResult {
             status: CheckStatus::Pass,
             message: "No project found".to_string(),
             suggestion: None,
+            fix: None,
         },
         Err(_) => DiagnosticResult {
             name: "Local Ado Paths".to_string(),
             status: CheckStatus::Pass,
             message: "Could not check (config error)".to_string(),
             suggestion: None,
+            fix: None,
         },
     }
 }
@@ -320,6 +445,7 @@ fn check_package_dependencies() -> DiagnosticResult {
                 status: CheckStatus::Pass,
                 message: "No project found".to_string(),
                 suggestion: None,
+                fix: None,
             }
         }
     };
@@ -332,6 +458,7 @@ fn check_package_dependencies() -> DiagnosticResult {
                 status: CheckStatus::Pass,
                 message: "No configuration found".to_string(),
                 suggestion: None,
+                fix: None,
             }
         }
     };
@@ -344,6 +471,7 @@ fn check_package_dependencies() -> DiagnosticResult {
                 status: CheckStatus::Pass,
                 message: "No lockfile found".to_string(),
                 suggestion: None,
+                fix: None,
             }
         }
     };
@@ -352,7 +480,7 @@ fn check_package_dependencies() -> DiagnosticResult {
     let mut all_missing: Vec<(String, Vec<String>)> = Vec::new();
 
     for (pkg_name, entry) in &lock.packages {
-        if let Ok(cache_dir) = global_cache::package_path(pkg_name, &entry.version) {
+        if let Ok(cache_dir) = global_cache::resolve_package_path(pkg_name, &entry.version) {
             let missing = dep_scan::find_missing_deps(pkg_name, &cache_dir, &installed);
             if !missing.is_empty() {
                 all_missing.push((pkg_name.clone(), missing));
@@ -366,6 +494,7 @@ fn check_package_dependencies() -> DiagnosticResult {
             status: CheckStatus::Pass,
             message: "No missing dependencies detected".to_string(),
             suggestion: None,
+            fix: None,
         }
     } else {
         let details: Vec<String> = all_missing
@@ -385,6 +514,57 @@ fn check_package_dependencies() -> DiagnosticResult {
             status: CheckStatus::Warn,
             message: format!("Missing dependencies: {}", details.join("; ")),
             suggestion: Some(format!("Run: stacy add {}", sorted_deps.join(" "))),
+            fix: None,
+        }
+    }
+}
+
+fn check_ado_collisions() -> DiagnosticResult {
+    let name = "Ado Collisions".to_string();
+    let lock = match Project::find() {
+        Ok(Some(project)) => match lockfile::load_lockfile(&project.root) {
+            Ok(Some(lock)) => lock,
+            _ => {
+                return DiagnosticResult {
+                    name,
+                    status: CheckStatus::Pass,
+                    message: "No lockfile found".to_string(),
+                    suggestion: None,
+                    fix: None,
+                }
+            }
+        },
+        _ => {
+            return DiagnosticResult {
+                name,
+                status: CheckStatus::Pass,
+                message: "No project found".to_string(),
+                suggestion: None,
+                fix: None,
+            }
+        }
+    };
+
+    let found = collisions::find_collisions(&collisions::locked_ado_files(&lock, None));
+    if found.is_empty() {
+        DiagnosticResult {
+            name,
+            status: CheckStatus::Pass,
+            message: "No package shadows another's commands".to_string(),
+            suggestion: None,
+            fix: None,
+        }
+    } else {
+        let details: Vec<String> = found.iter().map(|c| c.describe()).collect();
+        DiagnosticResult {
+            name,
+            status: CheckStatus::Warn,
+            message: details.join("; "),
+            suggestion: Some(
+                "Remove one of the packages; Stata runs the copy that comes first on the adopath"
+                    .to_string(),
+            ),
+            fix: None,
         }
     }
 }
@@ -393,9 +573,9 @@ fn check_cache_dir() -> DiagnosticResult {
     match global_cache::cache_dir() {
         Ok(cache_dir) => {
             if cache_dir.exists() {
-                // Check if we can list packages
+                // Check if we can list packages and write new ones
                 match global_cache::list_cached_packages() {
-                    Ok(packages) => DiagnosticResult {
+                    Ok(packages) if is_writable(&cache_dir) => DiagnosticResult {
                         name: "Package Cache".to_string(),
                         status: CheckStatus::Pass,
                         message: format!(
@@ -404,12 +584,22 @@ fn check_cache_dir() -> DiagnosticResult {
                             packages.len()
                         ),
                         suggestion: None,
+                        fix: None,
                     },
-                    Err(_) => DiagnosticResult {
+                    listed => DiagnosticResult {
                         name: "Package Cache".to_string(),
                         status: CheckStatus::Warn,
-                        message: format!("{} (unreadable)", cache_dir.display()),
+                        message: format!(
+                            "{} ({})",
+                            cache_dir.display(),
+                            if listed.is_ok() {
+                                "not writable"
+                            } else {
+                                "unreadable"
+                            }
+                        ),
                         suggestion: Some("Check cache directory permissions".to_string()),
+                        fix: Some(Fix::RepairPermissions(cache_dir)),
                     },
                 }
             } else {
@@ -418,6 +608,7 @@ fn check_cache_dir() -> DiagnosticResult {
                     status: CheckStatus::Pass,
                     message: format!("{} (will be created on first install)", cache_dir.display()),
                     suggestion: None,
+                    fix: None,
                 }
             }
         }
@@ -426,10 +617,150 @@ fn check_cache_dir() -> DiagnosticResult {
             status: CheckStatus::Warn,
             message: "Could not determine cache directory".to_string(),
             suggestion: Some("Home directory may not be set".to_string()),
+            fix: None,
+        },
+    }
+}
+
+fn check_build_cache() -> DiagnosticResult {
+    let name = "Build Cache".to_string();
+    let project = match Project::find() {
+        Ok(Some(p)) => p,
+        _ => {
+            return DiagnosticResult {
+                name,
+                status: CheckStatus::Pass,
+                message: "No project found".to_string(),
+                suggestion: None,
+                fix: None,
+            }
+        }
+    };
+
+    match crate::cache::verify::verify(&project.root) {
+        Ok(report) if report.is_healthy() => DiagnosticResult {
+            name,
+            status: CheckStatus::Pass,
+            message: format!("{} entries, all valid", report.entries_checked),
+            suggestion: None,
+            fix: None,
+        },
+        Ok(report) => DiagnosticResult {
+            name,
+            status: CheckStatus::Warn,
+            message: format!(
+                "{} problem(s): {}",
+                report.problems.len(),
+                report.problems[0].kind
+            ),
+            suggestion: Some("Run 'stacy cache verify --repair'".to_string()),
+            fix: Some(Fix::RepairBuildCache(project.root)),
+        },
+        Err(e) => DiagnosticResult {
+            name,
+            status: CheckStatus::Warn,
+            message: format!("Could not read: {}", e),
+            suggestion: Some("Check .stacy/cache permissions".to_string()),
+            fix: None,
+        },
+    }
+}
+
+fn check_user_config() -> DiagnosticResult {
+    let name = "User Config".to_string();
+    let Some(path) = crate::project::user_config::get_config_path() else {
+        return DiagnosticResult {
+            name,
+            status: CheckStatus::Warn,
+            message: "Could not determine config directory".to_string(),
+            suggestion: Some("Home directory may not be set".to_string()),
+            fix: None,
+        };
+    };
+
+    if path.exists() {
+        return match crate::project::user_config::load_user_config() {
+            Ok(_) => DiagnosticResult {
+                name,
+                status: CheckStatus::Pass,
+                message: path.display().to_string(),
+                suggestion: None,
+                fix: None,
+            },
+            Err(e) => DiagnosticResult {
+                name,
+                status: CheckStatus::Warn,
+                message: e.to_string(),
+                suggestion: Some("Fix the file with 'stacy config set'".to_string()),
+                fix: None,
+            },
+        };
+    }
+
+    match detect_stata_binary(None) {
+        Ok(binary) => DiagnosticResult {
+            name,
+            status: CheckStatus::Warn,
+            message: format!("{} not found", path.display()),
+            suggestion: Some(format!(
+                "Run 'stacy config set stata_binary {}' to pin the detected binary",
+                binary
+            )),
+            fix: Some(Fix::WriteUserConfig(binary)),
+        },
+        Err(_) => DiagnosticResult {
+            name,
+            status: CheckStatus::Pass,
+            message: format!("{} not found (using defaults)", path.display()),
+            suggestion: None,
+            fix: None,
         },
     }
 }
 
+fn check_stata_wrappers() -> DiagnosticResult {
+    let name = "Stata Wrappers".to_string();
+    let Ok(binary) = detect_stata_binary(None) else {
+        return DiagnosticResult {
+            name,
+            status: CheckStatus::Pass,
+            message: "Skipped (no Stata binary)".to_string(),
+            suggestion: None,
+            fix: None,
+        };
+    };
+    let Some(plus) = plus_dir() else {
+        return DiagnosticResult {
+            name,
+            status: CheckStatus::Pass,
+            message: "Skipped (could not determine the PLUS directory)".to_string(),
+            suggestion: None,
+            fix: None,
+        };
+    };
+
+    if plus.join("s").join("stacy.ado").exists() {
+        DiagnosticResult {
+            name,
+            status: CheckStatus::Pass,
+            message: format!("Installed in {}", plus.display()),
+            suggestion: None,
+            fix: None,
+        }
+    } else {
+        DiagnosticResult {
+            name,
+            status: CheckStatus::Warn,
+            message: format!("Not installed in {}", plus.display()),
+            suggestion: Some(format!(
+                "In Stata: net install stacy, from(\"{}\")",
+                WRAPPERS_URL
+            )),
+            fix: Some(Fix::InstallWrappers(binary)),
+        }
+    }
+}
+
 fn check_error_codes() -> DiagnosticResult {
     match ErrorCodeCache::load() {
         Ok(Some(db)) => {
@@ -444,6 +775,7 @@ fn check_error_codes() -> DiagnosticResult {
                     db.extracted_at
                 ),
                 suggestion: None,
+                fix: None,
             }
         }
         Ok(None) => DiagnosticResult {
@@ -453,12 +785,14 @@ fn check_error_codes() -> DiagnosticResult {
             suggestion: Some(
                 "Run 'stacy doctor --refresh' to extract error codes from Stata".to_string(),
             ),
+            fix: None,
         },
         Err(e) => DiagnosticResult {
             name: "Error Codes".to_string(),
             status: CheckStatus::Warn,
             message: format!("Cache unreadable: {}", e),
             suggestion: Some("Run 'stacy doctor --refresh' to re-extract".to_string()),
+            fix: None,
         },
     }
 }
@@ -475,6 +809,7 @@ fn check_write_permissions() -> DiagnosticResult {
                 status: CheckStatus::Pass,
                 message: "Can write to current directory".to_string(),
                 suggestion: None,
+                fix: None,
             }
         }
         Err(_) => DiagnosticResult {
@@ -482,6 +817,7 @@ fn check_write_permissions() -> DiagnosticResult {
             status: CheckStatus::Fail,
             message: "Cannot write to current directory".to_string(),
             suggestion: Some("Check directory permissions".to_string()),
+            fix: None,
         },
     }
 }
@@ -496,6 +832,7 @@ fn check_env_vars() -> DiagnosticResult {
                 status: CheckStatus::Pass,
                 message: format!("STATA_ENGINE={} (exists)", engine),
                 suggestion: None,
+                fix: None,
             }
         } else {
             DiagnosticResult {
@@ -503,6 +840,7 @@ fn check_env_vars() -> DiagnosticResult {
                 status: CheckStatus::Warn,
                 message: format!("STATA_ENGINE={} (file not found)", engine),
                 suggestion: Some("Check if the path is correct".to_string()),
+                fix: None,
             }
         }
     } else {
@@ -511,6 +849,7 @@ fn check_env_vars() -> DiagnosticResult {
             status: CheckStatus::Pass,
             message: "STATA_ENGINE not set (using auto-detection)".to_string(),
             suggestion: None,
+            fix: None,
         }
     }
 }
@@ -528,6 +867,7 @@ fn check_update_status() -> DiagnosticResult {
                     cache.latest_version, cache.current_version
                 ),
                 suggestion: Some(format!("Run '{instruction}' to update")),
+                fix: None,
             }
         }
         Some(cache) => DiagnosticResult {
@@ -535,6 +875,7 @@ fn check_update_status() -> DiagnosticResult {
             status: CheckStatus::Pass,
             message: format!("v{} (latest)", cache.current_version),
             suggestion: None,
+            fix: None,
         },
         None => DiagnosticResult {
             name: "Update Status".to_string(),
@@ -544,11 +885,109 @@ fn check_update_status() -> DiagnosticResult {
                 env!("CARGO_PKG_VERSION")
             ),
             suggestion: None,
+            fix: None,
         },
     }
 }
 
-fn print_human_output(checks: &[DiagnosticResult]) {
+/// Whether a file can be created in `dir`
+fn is_writable(dir: &Path) -> bool {
+    let probe = dir.join(".stacy_write_test");
+    let ok = std::fs::write(&probe, "test").is_ok();
+    let _ = std::fs::remove_file(&probe);
+    ok
+}
+
+/// Give the owner read and write access (and search access on directories)
+/// throughout `dir`
+#[cfg(unix)]
+fn repair_permissions(dir: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let metadata = std::fs::metadata(dir)?;
+    let mode = metadata.permissions().mode();
+    let wanted = if metadata.is_dir() { 0o700 } else { 0o600 };
+    if mode & wanted != wanted {
+        std::fs::set_permissions(dir, std::fs::Permissions::from_mode(mode | wanted))?;
+    }
+    if metadata.is_dir() {
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if !path.is_symlink() {
+                repair_permissions(&path)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Clear the read-only attribute throughout `dir`
+#[cfg(not(unix))]
+fn repair_permissions(dir: &Path) -> Result<()> {
+    let metadata = std::fs::metadata(dir)?;
+    let mut permissions = metadata.permissions();
+    if permissions.readonly() {
+        #[allow(clippy::permissions_set_readonly_false)]
+        permissions.set_readonly(false);
+        std::fs::set_permissions(dir, permissions)?;
+    }
+    if metadata.is_dir() {
+        for entry in std::fs::read_dir(dir)? {
+            repair_permissions(&entry?.path())?;
+        }
+    }
+    Ok(())
+}
+
+/// Stata's default PLUS directory, where `net install` puts packages
+fn plus_dir() -> Option<PathBuf> {
+    let home = dirs::home_dir()?;
+    if cfg!(target_os = "macos") {
+        Some(home.join("Library/Application Support/Stata/ado/plus"))
+    } else {
+        Some(home.join("ado").join("plus"))
+    }
+}
+
+/// Install the Stata wrappers by running `net install` in batch Stata
+fn install_wrappers(binary: &str) -> Result<()> {
+    if update_check::is_offline() {
+        return Err(crate::error::Error::Network(
+            "Cannot install the Stata wrappers offline (STACY_OFFLINE is set)".to_string(),
+        ));
+    }
+
+    let tmp_dir = tempfile::TempDir::new()?;
+    let script = tmp_dir.path().join("stacy_install_wrappers.do");
+    std::fs::write(
+        &script,
+        format!("net install stacy, from(\"{}\") replace\n", WRAPPERS_URL),
+    )?;
+
+    let status = std::process::Command::new(binary)
+        .args(crate::executor::binary::BatchStyle::for_binary(binary).args())
+        .arg(crate::executor::run_paths::stata_path(&script))
+        .current_dir(tmp_dir.path())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .map_err(|e| crate::error::Error::Execution(format!("Failed to run Stata: {}", e)))?;
+
+    // Batch Stata exits 0 even when the do-file fails, so check the result
+    let installed = plus_dir().is_some_and(|plus| plus.join("s").join("stacy.ado").exists());
+    if !status.success() || !installed {
+        let log = std::fs::read_to_string(tmp_dir.path().join("stacy_install_wrappers.log"))
+            .unwrap_or_default();
+        let tail: Vec<&str> = log.lines().filter(|l| !l.trim().is_empty()).collect();
+        return Err(crate::error::Error::Execution(format!(
+            "net install of the Stata wrappers failed:\n{}",
+            tail[tail.len().saturating_sub(5)..].join("\n")
+        )));
+    }
+    Ok(())
+}
+
+fn print_human_output(checks: &[DiagnosticResult], fixed: bool) {
     println!("stacy System Diagnostics");
     println!("======================");
     println!();
@@ -585,9 +1024,19 @@ fn print_human_output(checks: &[DiagnosticResult]) {
         println!();
         println!("stacy is ready to use.");
     }
+
+    let fixable = checks.iter().filter(|c| c.fix.is_some()).count();
+    if fixable > 0 && !fixed {
+        println!();
+        println!(
+            "Run 'stacy doctor --fix' to apply {} automatic fix{}.",
+            fixable,
+            if fixable == 1 { "" } else { "es" }
+        );
+    }
 }
 
-fn print_json_output(checks: &[DiagnosticResult]) {
+fn print_json_output(checks: &[DiagnosticResult], fixed: &[String]) {
     use serde_json::json;
 
     let results: Vec<_> = checks
@@ -598,6 +1047,7 @@ fn print_json_output(checks: &[DiagnosticResult]) {
                 "status": c.status.as_str(),
                 "message": c.message,
                 "suggestion": c.suggestion,
+                "fixable": c.fix.is_some(),
             })
         })
         .collect();
@@ -624,7 +1074,45 @@ fn print_json_output(checks: &[DiagnosticResult]) {
         },
         "ready": failed == 0,
         "check_count": checks.len(),
+        "fixed": fixed,
     });
 
     println!("{}", serde_json::to_string_pretty(&output).unwrap());
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_fix_create_dirs() {
+        let temp = TempDir::new().unwrap();
+        let dir = temp.path().join("a").join("b");
+        let description = Fix::CreateDirs(vec![dir.clone()]).apply().unwrap();
+        assert!(dir.is_dir());
+        assert!(description.starts_with("Created "));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_repair_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = TempDir::new().unwrap();
+        let dir = temp.path().join("cache");
+        std::fs::create_dir(&dir).unwrap();
+        std::fs::write(dir.join("pkg.ado"), "").unwrap();
+        std::fs::set_permissions(dir.join("pkg.ado"), std::fs::Permissions::from_mode(0o400))
+            .unwrap();
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o500)).unwrap();
+
+        repair_permissions(&dir).unwrap();
+        assert!(is_writable(&dir));
+        let mode = std::fs::metadata(dir.join("pkg.ado"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o600, 0o600);
+    }
+}
@@ -5,6 +5,9 @@
 //! - Project root and configuration
 //! - Global cache path
 //! - Adopath search order (constructed from lockfile)
+//!
+//! `--shell` prints the same environment as `export` lines for
+//! `eval "$(stacy env --shell)"` in shell scripts and Makefiles.
 
 use crate::cli::output_format::OutputFormat;
 use crate::cli::output_types::{CommandOutput, EnvOutput};
@@ -13,14 +16,21 @@ use crate::executor::binary::detect_stata_binary;
 use crate::packages::global_cache;
 use crate::packages::lockfile::load_lockfile;
 use crate::project::Project;
+use crate::utils::shell_quote;
 use clap::Args;
 use std::path::PathBuf;
 
 #[derive(Args)]
 #[command(after_help = "\
 Examples:
-  stacy env                               Show environment configuration")]
+  stacy env                               Show environment configuration
+  eval \"$(stacy env --shell)\"             Export S_ADO and paths into the shell")]
 pub struct EnvArgs {
+    /// Print shell `export` lines (S_ADO, STATA_BINARY, STACY_PROJECT_ROOT,
+    /// STACY_CACHE_DIR, STACY_LOG_DIR) for `eval`
+    #[arg(long)]
+    pub shell: bool,
+
     /// Output format: human (default), json, or stata
     #[arg(long, value_enum, default_value = "human")]
     pub format: OutputFormat,
@@ -57,6 +67,11 @@ pub fn execute(args: &EnvArgs) -> Result<()> {
     let format = args.format;
     let info = gather_environment_info()?;
 
+    if args.shell {
+        print!("{}", shell_exports(&info));
+        return Ok(());
+    }
+
     // Build output struct
     let output = EnvOutput {
         has_config: info.has_config,
@@ -184,13 +199,19 @@ fn build_adopath_from_lockfile(
         match load_lockfile(root) {
             Ok(Some(lockfile)) => {
                 // Sort packages alphabetically for deterministic output
-                let mut sorted_packages: Vec<_> = lockfile.packages.iter().collect();
+                // Editable packages are among the local ado paths above
+                let mut sorted_packages: Vec<_> = lockfile
+                    .packages
+                    .iter()
+                    .filter(|(_, e)| !e.is_editable())
+                    .collect();
                 sorted_packages.sort_by_key(|(a, _)| *a);
 
                 for (name, entry) in sorted_packages {
-                    if let Ok(pkg_path) = global_cache::package_path(name, &entry.version) {
-                        let installed =
-                            global_cache::is_cached(name, &entry.version).unwrap_or(false);
+                    if let Ok(pkg_path) = global_cache::resolve_package_path(name, &entry.version) {
+                        let installed = global_cache::locate_package(name, &entry.version)
+                            .map(|found| found.is_some())
+                            .unwrap_or(false);
                         if installed {
                             package_count += 1;
                         } else {
@@ -291,6 +312,30 @@ fn print_human_output(info: &EnvironmentInfo) {
     println!("Run 'stacy doctor' for system diagnostics.");
 }
 
+/// `export NAME='value'` lines for a POSIX shell. Variables with no value
+/// (no Stata found, not in a project) are left out rather than set empty.
+fn shell_exports(info: &EnvironmentInfo) -> String {
+    let s_ado: Vec<&str> = info.adopath.iter().map(|e| e.path.as_str()).collect();
+    let log_dir = match info.project_root {
+        Some(ref root) => root.join(&info.log_dir),
+        None => info.log_dir.clone(),
+    };
+
+    let mut vars = vec![("S_ADO", s_ado.join(";"))];
+    if let Some(ref binary) = info.stata_binary {
+        vars.push(("STATA_BINARY", binary.clone()));
+    }
+    if let Some(ref root) = info.project_root {
+        vars.push(("STACY_PROJECT_ROOT", root.display().to_string()));
+        vars.push(("STACY_LOG_DIR", log_dir.display().to_string()));
+    }
+    vars.push(("STACY_CACHE_DIR", info.cache_dir.display().to_string()));
+
+    vars.iter()
+        .map(|(name, value)| format!("export {}={}\n", name, shell_quote(value)))
+        .collect()
+}
+
 fn print_json_output(info: &EnvironmentInfo) {
     use serde_json::json;
 
@@ -330,3 +375,59 @@ fn print_json_output(info: &EnvironmentInfo) {
 
     println!("{}", serde_json::to_string_pretty(&output).unwrap());
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(project_root: Option<PathBuf>) -> EnvironmentInfo {
+        EnvironmentInfo {
+            stata_binary: Some("/usr/local/stata18/stata-mp".to_string()),
+            stata_source: "auto-detected".to_string(),
+            config_file: None,
+            has_config: true,
+            project_root,
+            cache_dir: PathBuf::from("/home/me/.cache/stacy/packages"),
+            log_dir: PathBuf::from("logs"),
+            show_progress: true,
+            adopath: vec![
+                AdopathEntry {
+                    path: "/home/me/.cache/stacy/packages/estout/1.0.0".to_string(),
+                    source: "package",
+                    label: Some("estout".to_string()),
+                    installed: true,
+                },
+                AdopathEntry {
+                    path: "BASE".to_string(),
+                    source: "builtin",
+                    label: None,
+                    installed: true,
+                },
+            ],
+            package_count: 1,
+            missing_package_count: 0,
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_shell_exports() {
+        let exports = shell_exports(&info(Some(PathBuf::from("/work/my project"))));
+        assert_eq!(
+            exports,
+            "export S_ADO='/home/me/.cache/stacy/packages/estout/1.0.0;BASE'\n\
+             export STATA_BINARY='/usr/local/stata18/stata-mp'\n\
+             export STACY_PROJECT_ROOT='/work/my project'\n\
+             export STACY_LOG_DIR='/work/my project/logs'\n\
+             export STACY_CACHE_DIR='/home/me/.cache/stacy/packages'\n"
+        );
+    }
+
+    #[test]
+    fn test_shell_exports_outside_project() {
+        let exports = shell_exports(&info(None));
+        assert!(!exports.contains("STACY_PROJECT_ROOT"));
+        assert!(!exports.contains("STACY_LOG_DIR"));
+        assert!(exports.contains("export S_ADO="));
+    }
+}
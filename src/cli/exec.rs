@@ -0,0 +1,132 @@
+//! `stacy exec` command implementation
+//!
+//! Runs an arbitrary command with the environment `stacy run` gives Stata:
+//! `S_ADO` built from the lockfile (checked against the package cache first),
+//! the detected Stata binary and the project paths. Mixed Stata/Python
+//! pipelines get the same isolation for their non-Stata steps.
+
+use crate::error::{Error, Result};
+use crate::executor::binary::detect_stata_binary;
+use crate::packages::global_cache;
+use crate::packages::lockfile::{create_lockfile, load_lockfile};
+use crate::project::Project;
+use clap::Args;
+use std::path::PathBuf;
+use std::process::Command;
+
+#[derive(Args)]
+#[command(after_help = "\
+The command sees:
+  S_ADO               Locked packages, local ado paths and BASE (as in `stacy run`)
+  STATA_BINARY        The Stata binary stacy would use, if one is found
+  STACY_PROJECT_ROOT  The project root
+  STACY_CACHE_DIR     The global package cache
+  STACY_LOG_DIR       The project's log directory ([run] log_dir)
+
+Examples:
+  stacy exec -- python extract_tables.py  Run a Python step with the project environment
+  stacy exec -C analysis -- make tables   Run make in analysis/
+  stacy exec --allow-global -- ./run.sh   Include PLUS and PERSONAL in S_ADO")]
+pub struct ExecArgs {
+    /// Command to run, and its arguments
+    #[arg(
+        value_name = "COMMAND",
+        required = true,
+        trailing_var_arg = true,
+        allow_hyphen_values = true
+    )]
+    pub command: Vec<String>,
+
+    /// Run the command in this directory instead of the current directory
+    #[arg(short = 'C', long = "directory", value_name = "DIR")]
+    pub directory: Option<PathBuf>,
+
+    /// Allow globally installed packages (PLUS, PERSONAL, etc.) in S_ADO
+    #[arg(long)]
+    pub allow_global: bool,
+
+    /// Skip the check of the package cache against stacy.lock
+    #[arg(long)]
+    pub no_verify: bool,
+}
+
+pub fn execute(args: &ExecArgs) -> Result<()> {
+    let project = Project::find()?.ok_or_else(|| {
+        Error::Config("Not in a stacy project. Run 'stacy init' first.".to_string())
+    })?;
+
+    let (program, program_args) = args
+        .command
+        .split_first()
+        .ok_or_else(|| Error::Config("No command given. Usage: stacy exec -- <COMMAND>".into()))?;
+
+    let mut cmd = Command::new(program);
+    cmd.args(program_args);
+    for (name, value) in project_env(&project, args.allow_global, !args.no_verify)? {
+        cmd.env(name, value);
+    }
+    if let Some(ref dir) = args.directory {
+        if !dir.is_dir() {
+            return Err(Error::Config(format!(
+                "Directory not found: {}",
+                dir.display()
+            )));
+        }
+        cmd.current_dir(dir);
+    }
+
+    let status = cmd.status().map_err(|e| {
+        Error::Config(if e.kind() == std::io::ErrorKind::NotFound {
+            format!("Command not found: {}", program)
+        } else {
+            format!("Failed to start {}: {}", program, e)
+        })
+    })?;
+
+    // Pass the command's exit code through, so `stacy exec` is transparent to
+    // Make and CI. A command killed by a signal has none.
+    crate::session::exit(status.code().unwrap_or(1));
+}
+
+/// The environment variables `stacy exec` sets, in the order it sets them.
+/// `stacy shell` starts Stata with them too.
+pub(crate) fn project_env(
+    project: &Project,
+    allow_global: bool,
+    verify: bool,
+) -> Result<Vec<(&'static str, String)>> {
+    // A missing lockfile is fine (no packages yet); the cache must match one
+    // that exists, as it must before `stacy run` starts Stata.
+    let lockfile = match load_lockfile(&project.root)? {
+        Some(lockfile) => {
+            if verify {
+                global_cache::verify_lockfile_against_cache(&lockfile)?;
+            }
+            lockfile
+        }
+        None => create_lockfile(),
+    };
+    let s_ado =
+        global_cache::build_s_ado(&lockfile, allow_global, &project.resolve_local_ado_paths())?;
+
+    let log_dir = project
+        .config
+        .as_ref()
+        .map(|c| c.run.log_dir.clone())
+        .unwrap_or_else(|| PathBuf::from("logs"));
+
+    let mut vars = vec![("S_ADO", s_ado)];
+    if let Ok(binary) = detect_stata_binary(None) {
+        vars.push(("STATA_BINARY", binary));
+    }
+    vars.push(("STACY_PROJECT_ROOT", project.root.display().to_string()));
+    vars.push((
+        "STACY_CACHE_DIR",
+        global_cache::cache_dir()?.display().to_string(),
+    ));
+    vars.push((
+        "STACY_LOG_DIR",
+        project.root.join(log_dir).display().to_string(),
+    ));
+    Ok(vars)
+}
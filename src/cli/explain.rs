@@ -1,10 +1,13 @@
 //! `stacy explain` command implementation
 //!
-//! Displays detailed information about Stata error codes.
+//! Displays detailed information about Stata error codes, or with
+//! `--exit-codes` the exit codes stacy reports failures with.
 
 use crate::cli::output_format::OutputFormat;
+use crate::cli::output_types::{exit_codes_stata_matrix, ExitCodeOutput};
 use crate::error::categories::category_for_code;
 use crate::error::error_db::{lookup_error, ErrorCodeEntry};
+use crate::error::exit_codes::{ExitCodeMap, EXIT_KINDS};
 use crate::error::Result;
 use clap::Args;
 
@@ -13,10 +16,17 @@ use clap::Args;
 Examples:
   stacy explain 199                   Look up error code 199
   stacy explain r(199)                Also accepts r() syntax
-  stacy explain 111 --format json     Output as JSON")]
+  stacy explain 111 --format json     Output as JSON
+  stacy explain --exit-codes          Show the exit codes in effect")]
 pub struct ExplainArgs {
     /// Error code to look up (e.g., 199 or r(199))
-    pub code: String,
+    #[arg(required_unless_present = "exit_codes", conflicts_with = "exit_codes")]
+    pub code: Option<String>,
+
+    /// Show the exit code for each kind of failure, after any remapping
+    /// under [exit_codes] in stacy.toml or the user config
+    #[arg(long)]
+    pub exit_codes: bool,
 
     /// Output format: human (default), json, or stata
     #[arg(long, value_enum, default_value = "human")]
@@ -24,17 +34,23 @@ pub struct ExplainArgs {
 }
 
 pub fn execute(args: &ExplainArgs) -> Result<()> {
+    let Some(ref arg) = args.code else {
+        let map = ExitCodeMap::load()?;
+        match args.format {
+            OutputFormat::Human => print_human_exit_codes(&map),
+            OutputFormat::Json => print_json_exit_codes(&map),
+            OutputFormat::Stata => print_stata_exit_codes(&map),
+        }
+        return Ok(());
+    };
+
     // Parse the error code - accept both "199" and "r(199)"
-    let code_str = args
-        .code
-        .trim()
-        .trim_start_matches("r(")
-        .trim_end_matches(')');
+    let code_str = arg.trim().trim_start_matches("r(").trim_end_matches(')');
 
     let code: u32 = code_str.parse().map_err(|_| {
         crate::error::Error::Parse(format!(
             "Invalid error code '{}'. Expected a number like 199 or r(199)",
-            args.code
+            arg
         ))
     })?;
 
@@ -129,3 +145,53 @@ fn print_stata_fallback(code: u32, category: &str) {
         category.replace('"', "'")
     );
 }
+
+fn print_human_exit_codes(map: &ExitCodeMap) {
+    println!("{:>4}  {:<18} Meaning", "Code", "Kind");
+    println!("{:>4}  {:<18} Success", 0, "success");
+    for kind in EXIT_KINDS {
+        let remapped = if map.is_remapped(kind) {
+            format!(" (standard: {})", kind.code)
+        } else {
+            String::new()
+        };
+        println!(
+            "{:>4}  {:<18} {}{}",
+            map.code(kind),
+            kind.name,
+            kind.description,
+            remapped
+        );
+    }
+}
+
+fn print_json_exit_codes(map: &ExitCodeMap) {
+    let output = serde_json::json!({ "exit_codes": exit_code_rows(map) });
+    println!("{}", serde_json::to_string_pretty(&output).unwrap());
+}
+
+fn print_stata_exit_codes(map: &ExitCodeMap) {
+    // Success is always 0 and has no row
+    let rows: Vec<ExitCodeOutput> = exit_code_rows(map).into_iter().skip(1).collect();
+    if let Some(matrix) = exit_codes_stata_matrix(&rows) {
+        println!("{}", matrix);
+    }
+}
+
+/// Success, then each kind of failure
+fn exit_code_rows(map: &ExitCodeMap) -> Vec<ExitCodeOutput> {
+    let success = ExitCodeOutput {
+        kind: "success".to_string(),
+        code: 0,
+        standard: 0,
+        description: "Success".to_string(),
+    };
+    std::iter::once(success)
+        .chain(EXIT_KINDS.iter().map(|kind| ExitCodeOutput {
+            kind: kind.name.to_string(),
+            code: map.code(kind),
+            standard: kind.code,
+            description: kind.description.to_string(),
+        }))
+        .collect()
+}
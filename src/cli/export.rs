@@ -0,0 +1,322 @@
+//! `stacy export` command implementation
+//!
+//! Writes the locked package environment in a form that works without stacy.
+//! `--format do` produces an `install_packages.do` that a collaborator runs
+//! in plain Stata: `ssc install` for SSC packages and `net install` from the
+//! pinned location for everything else. A GitHub package without a `.pkg`
+//! manifest cannot be `net install`ed; the do-file says so instead.
+
+use crate::error::{Error, Result};
+use crate::packages::lockfile::load_lockfile;
+use crate::project::{Lockfile, PackageEntry, PackageSource, Project};
+use clap::{Args, ValueEnum};
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+#[derive(Args)]
+#[command(after_help = "\
+Examples:
+  stacy export --format do                Write install_packages.do
+  stacy export --format do -o setup.do    Write it to setup.do
+  stacy export --format do -o -           Print it instead
+  stacy export --format do --with dev     Include dev dependencies")]
+pub struct ExportArgs {
+    /// What to export: `do` writes a do-file that installs the locked packages
+    #[arg(long, value_enum, default_value = "do")]
+    pub format: ExportFormat,
+
+    /// File to write (`-` for stdout)
+    #[arg(
+        short,
+        long,
+        value_name = "FILE",
+        default_value = "install_packages.do"
+    )]
+    pub output: PathBuf,
+
+    /// Include additional dependency groups (comma-separated: dev, test)
+    #[arg(long, value_delimiter = ',')]
+    pub with: Vec<String>,
+}
+
+/// Export formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// A do-file of `ssc install` / `net install` commands
+    Do,
+}
+
+pub fn execute(args: &ExportArgs) -> Result<()> {
+    let project = Project::find()?.ok_or_else(|| {
+        Error::Config("Not in a stacy project. Run 'stacy init' first.".to_string())
+    })?;
+
+    let lockfile = load_lockfile(&project.root)?.ok_or_else(|| {
+        Error::Config("No stacy.lock found. Use 'stacy add <package>' to add packages.".to_string())
+    })?;
+
+    let mut groups: HashSet<&str> = HashSet::new();
+    groups.insert("production");
+    for group_name in &args.with {
+        match group_name.to_lowercase().as_str() {
+            "dev" => groups.insert("dev"),
+            "test" => groups.insert("test"),
+            other => {
+                return Err(Error::Config(format!(
+                    "Unknown dependency group: '{}'. Valid groups: dev, test",
+                    other
+                )));
+            }
+        };
+    }
+
+    let script = match args.format {
+        ExportFormat::Do => install_script(&lockfile, &groups),
+    };
+
+    if args.output.as_os_str() == "-" {
+        print!("{}", script);
+        return Ok(());
+    }
+
+    let path = if args.output.is_absolute() {
+        args.output.clone()
+    } else {
+        project.root.join(&args.output)
+    };
+    std::fs::write(&path, script)?;
+    println!(
+        "Wrote {} ({} package(s))",
+        path.display(),
+        exported_entries(&lockfile, &groups).len()
+    );
+    Ok(())
+}
+
+/// Lockfile entries in `groups`, sorted by name
+fn exported_entries<'a>(
+    lockfile: &'a Lockfile,
+    groups: &HashSet<&str>,
+) -> Vec<(&'a String, &'a PackageEntry)> {
+    let mut entries: Vec<_> = lockfile
+        .packages
+        .iter()
+        .filter(|(_, entry)| groups.contains(entry.group.as_str()))
+        .collect();
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+    entries
+}
+
+/// A do-file installing the locked packages of `groups` with Stata's own
+/// commands
+fn install_script(lockfile: &Lockfile, groups: &HashSet<&str>) -> String {
+    let mut out = String::new();
+    out.push_str("* install_packages.do - install this project's Stata packages\n");
+    out.push_str(&format!(
+        "* Generated by stacy {} from stacy.lock. With stacy, run `stacy install` instead.\n",
+        env!("CARGO_PKG_VERSION")
+    ));
+    out.push_str("*\n");
+    out.push_str("* SSC serves only the latest version of a package, so SSC installs may\n");
+    out.push_str("* differ from the locked versions noted below. Other sources are pinned.\n");
+    out.push('\n');
+    out.push_str("version 14.0\n");
+
+    let entries = exported_entries(lockfile, groups);
+    if entries.is_empty() {
+        out.push_str("\n* No packages locked.\n");
+        return out;
+    }
+
+    for (name, entry) in entries {
+        out.push('\n');
+        out.push_str(&install_command(name, entry));
+    }
+    out
+}
+
+/// The comment and install command for one locked package
+fn install_command(name: &str, entry: &PackageEntry) -> String {
+    let group = if entry.group == "production" {
+        String::new()
+    } else {
+        format!(", {} dependency", entry.group)
+    };
+    match &entry.source {
+        PackageSource::SSC { .. } => format!(
+            "* {} {} (SSC{})\nssc install {}, replace\n",
+            name, entry.version, group, name
+        ),
+        PackageSource::GitHub {
+            repo,
+            tag,
+            commit,
+            pkg_dir,
+            synthesized,
+        } => {
+            let header = format!("* {} {} (github:{}@{}{})\n", name, entry.version, repo, tag, group);
+            if *synthesized {
+                // `net install` needs a .pkg; stacy picked the files itself.
+                return format!(
+                    "{}* {}@{} has no {}.pkg, so Stata cannot install it. Copy its .ado and\n\
+                     * help files onto the adopath by hand, or run `stacy install`.\n\
+                     display as error \"{}: no .pkg in {}, install it by hand\"\n",
+                    header, repo, tag, name, name, repo
+                );
+            }
+            let git_ref = commit.as_deref().unwrap_or(tag);
+            let dir = pkg_dir
+                .as_deref()
+                .map(|dir| format!("{}/", dir))
+                .unwrap_or_default();
+            format!(
+                "{}net install {}, from(\"https://raw.githubusercontent.com/{}/{}/{}\") replace\n",
+                header, name, repo, git_ref, dir
+            )
+        }
+        PackageSource::Net { url } => format!(
+            "* {} {} (net{})\nnet install {}, from(\"{}\") replace\n",
+            name, entry.version, group, name, url
+        ),
+        PackageSource::Local { path, .. } => format!(
+            "* {} {} (local directory, relative to the project root{})\nnet install {}, from(\"{}\") replace\n",
+            name, entry.version, group, name, path
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn entry(source: PackageSource, group: &str) -> PackageEntry {
+        PackageEntry {
+            version: "1.0.0".to_string(),
+            source,
+            checksum: None,
+            group: group.to_string(),
+        }
+    }
+
+    fn lockfile() -> Lockfile {
+        let mut packages = HashMap::new();
+        packages.insert(
+            "estout".to_string(),
+            entry(
+                PackageSource::SSC {
+                    name: "estout".to_string(),
+                },
+                "production",
+            ),
+        );
+        packages.insert(
+            "rdrobust".to_string(),
+            entry(
+                PackageSource::GitHub {
+                    repo: "rdpackages/rdrobust".to_string(),
+                    tag: "v9.1.0".to_string(),
+                    commit: Some("abc123".to_string()),
+                    pkg_dir: None,
+                    synthesized: false,
+                },
+                "production",
+            ),
+        );
+        packages.insert(
+            "grc1leg".to_string(),
+            entry(
+                PackageSource::Net {
+                    url: "http://www.stata.com/users/vwiggins/".to_string(),
+                },
+                "production",
+            ),
+        );
+        packages.insert(
+            "assert".to_string(),
+            entry(
+                PackageSource::SSC {
+                    name: "assert".to_string(),
+                },
+                "dev",
+            ),
+        );
+        Lockfile {
+            version: "1".to_string(),
+            stacy_version: None,
+            packages,
+        }
+    }
+
+    #[test]
+    fn test_install_script_pins_non_ssc_sources() {
+        let groups: HashSet<&str> = ["production"].into_iter().collect();
+        let script = install_script(&lockfile(), &groups);
+
+        assert!(script.contains("ssc install estout, replace\n"));
+        assert!(script.contains(
+            "net install rdrobust, from(\"https://raw.githubusercontent.com/rdpackages/rdrobust/abc123/\") replace\n"
+        ));
+        assert!(script.contains(
+            "net install grc1leg, from(\"http://www.stata.com/users/vwiggins/\") replace\n"
+        ));
+        assert!(!script.contains("assert"));
+
+        // Sorted by name, so the file is stable across runs
+        let estout = script.find("ssc install estout").unwrap();
+        let grc1leg = script.find("net install grc1leg").unwrap();
+        assert!(estout < grc1leg);
+    }
+
+    #[test]
+    fn test_install_script_includes_requested_groups() {
+        let groups: HashSet<&str> = ["production", "dev"].into_iter().collect();
+        let script = install_script(&lockfile(), &groups);
+        assert!(
+            script.contains("* assert 1.0.0 (SSC, dev dependency)\nssc install assert, replace\n")
+        );
+    }
+
+    #[test]
+    fn test_install_script_github_pkg_locations() {
+        let mut lockfile = lockfile();
+        lockfile.packages.insert(
+            "ftools".to_string(),
+            entry(
+                PackageSource::GitHub {
+                    repo: "sergiocorreia/ftools".to_string(),
+                    tag: "master".to_string(),
+                    commit: Some("def456".to_string()),
+                    pkg_dir: Some("src".to_string()),
+                    synthesized: false,
+                },
+                "production",
+            ),
+        );
+        lockfile.packages.insert(
+            "nopkg".to_string(),
+            entry(
+                PackageSource::GitHub {
+                    repo: "me/nopkg".to_string(),
+                    tag: "main".to_string(),
+                    commit: None,
+                    pkg_dir: None,
+                    synthesized: true,
+                },
+                "production",
+            ),
+        );
+        let groups: HashSet<&str> = ["production"].into_iter().collect();
+        let script = install_script(&lockfile, &groups);
+
+        // The .pkg lives under src/, so that is where Stata must look
+        assert!(script.contains(
+            "net install ftools, from(\"https://raw.githubusercontent.com/sergiocorreia/ftools/def456/src/\") replace\n"
+        ));
+        // No .pkg at all: nothing Stata could install, so say so loudly
+        assert!(!script.contains("net install nopkg"));
+        assert!(script.contains("* me/nopkg@main has no nopkg.pkg"));
+        assert!(script
+            .contains("display as error \"nopkg: no .pkg in me/nopkg, install it by hand\"\n"));
+    }
+}
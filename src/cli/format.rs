@@ -75,8 +75,12 @@ pub fn wrap_text(text: &str, width: usize, indent: &str) -> String {
     result
 }
 
-/// Print detailed error information with Stata documentation
-pub fn print_error_details(error: &crate::error::StataError) {
+/// Print detailed error information with Stata documentation, and the
+/// do-file line that raised it when known
+pub fn print_error_details(
+    error: &crate::error::StataError,
+    trace: &[crate::error::location::SourceLocation],
+) {
     use crate::error::StataError;
 
     match error {
@@ -85,6 +89,15 @@ pub fn print_error_details(error: &crate::error::StataError) {
         } => {
             // message is already the best available: log-extracted, error-db, or category fallback
             eprintln!("\n   Error: r({}) - {}", r_code, message);
+            // Through the nested do-files: `main.do:3 → helpers/labels.do:42`
+            if !trace.is_empty() {
+                eprintln!("   At:    {}", crate::error::location::format_trace(trace));
+            }
+            if let Some(hint) =
+                unrecognized_command(message).and_then(crate::packages::naming::command_hint)
+            {
+                eprintln!("   Hint:  {}", hint);
+            }
             eprintln!();
             eprintln!(
                 "   See: https://www.stata.com/manuals/perror.pdf#r{}",
@@ -101,6 +114,19 @@ pub fn print_error_details(error: &crate::error::StataError) {
     }
 }
 
+/// The command an r(199) message names: `unrecognized command:  foo` or
+/// `command foo is unrecognized`
+fn unrecognized_command(message: &str) -> Option<&str> {
+    let command = match message.strip_prefix("unrecognized command:") {
+        Some(rest) => rest.split_whitespace().next(),
+        None => message
+            .strip_prefix("command ")?
+            .strip_suffix(" is unrecognized")
+            .map(str::trim),
+    }?;
+    (!command.is_empty()).then_some(command)
+}
+
 /// Print suggestions for common errors
 pub fn print_suggestions(suggestions: &[&str]) {
     if !suggestions.is_empty() {
@@ -127,6 +153,64 @@ pub fn format_duration_secs(secs: f64) -> String {
     }
 }
 
+/// One row of the `--summary` table
+pub struct SummaryRow {
+    pub name: String,
+    pub passed: bool,
+    /// Result taken from the build cache
+    pub cached: bool,
+    pub duration_secs: f64,
+    /// The log the run kept, if any (failed runs keep theirs)
+    pub log: Option<std::path::PathBuf>,
+}
+
+/// The `--summary` table: a line per script with its status, duration and
+/// kept log, then the totals. Plain text, so it reads the same in a mail.
+pub fn summary_table(heading: &str, rows: &[SummaryRow], total_secs: f64) -> String {
+    let width = rows
+        .iter()
+        .map(|r| r.name.chars().count())
+        .chain(std::iter::once(heading.len()))
+        .max()
+        .unwrap_or(0);
+    let mut out = format!(
+        "{:<width$}  {:<6}  {:<8}  LOG\n",
+        heading,
+        "STATUS",
+        "DURATION",
+        width = width
+    );
+    for row in rows {
+        let status = match (row.passed, row.cached) {
+            (false, _) => "FAIL",
+            (true, true) => "CACHED",
+            (true, false) => "PASS",
+        };
+        let log = row
+            .log
+            .as_ref()
+            .filter(|l| !l.as_os_str().is_empty())
+            .map(|l| l.display().to_string())
+            .unwrap_or_else(|| "-".to_string());
+        out.push_str(&format!(
+            "{:<width$}  {:<6}  {:<8}  {}\n",
+            row.name,
+            status,
+            format_duration_secs(row.duration_secs),
+            log,
+            width = width
+        ));
+    }
+    let failed = rows.iter().filter(|r| !r.passed).count();
+    out.push_str(&format!(
+        "\n{} passed, {} failed ({})\n",
+        rows.len() - failed,
+        failed,
+        format_duration_secs(total_secs)
+    ));
+    out
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -160,7 +244,21 @@ mod tests {
             line_number: Some(1),
         };
         // Should not panic; output goes to stderr
-        print_error_details(&error);
+        print_error_details(&error, &[]);
+    }
+
+    #[test]
+    fn test_unrecognized_command() {
+        assert_eq!(
+            unrecognized_command("unrecognized command:  esttab"),
+            Some("esttab")
+        );
+        assert_eq!(
+            unrecognized_command("command labmask is unrecognized"),
+            Some("labmask")
+        );
+        assert_eq!(unrecognized_command("unrecognized command"), None);
+        assert_eq!(unrecognized_command("variable foo not found"), None);
     }
 
     #[test]
@@ -172,14 +270,42 @@ mod tests {
             message: "something weird".to_string(),
             line_number: None,
         };
-        print_error_details(&error);
+        print_error_details(&error, &[]);
     }
 
     #[test]
     fn test_print_error_details_process_killed() {
         use crate::error::StataError;
         let error = StataError::ProcessKilled { exit_code: 137 };
-        print_error_details(&error);
+        print_error_details(&error, &[]);
+    }
+
+    #[test]
+    fn test_summary_table() {
+        let rows = vec![
+            SummaryRow {
+                name: "clean.do".to_string(),
+                passed: true,
+                cached: false,
+                duration_secs: 1.5,
+                log: None,
+            },
+            SummaryRow {
+                name: "tables/report.do".to_string(),
+                passed: false,
+                cached: false,
+                duration_secs: 0.5,
+                log: Some(std::path::PathBuf::from("logs/report.log")),
+            },
+        ];
+        assert_eq!(
+            summary_table("SCRIPT", &rows, 2.0),
+            "SCRIPT            STATUS  DURATION  LOG\n\
+             clean.do          PASS    1.50s     -\n\
+             tables/report.do  FAIL    500ms     logs/report.log\n\
+             \n\
+             1 passed, 1 failed (2.00s)\n"
+        );
     }
 
     #[test]
@@ -0,0 +1,181 @@
+//! `stacy history` command implementation
+//!
+//! Lists the scripts `stacy run`, `task`, `build` and `test` ran in the project,
+//! newest first, filtered by script, outcome and age. `stacy history show
+//! <id>` shows one run in full.
+
+use crate::artifacts::format_timestamp;
+use crate::cli::output_format::OutputFormat;
+use crate::cli::output_types::{CommandOutput, HistoryOutput};
+use crate::error::{Error, Result};
+use crate::history::{self, RunRecord};
+use crate::project::Project;
+use clap::{Args, Subcommand};
+use colored::Colorize;
+use std::path::PathBuf;
+
+#[derive(Args)]
+#[command(args_conflicts_with_subcommands = true)]
+#[command(after_help = "\
+Examples:
+  stacy history                             The last 20 runs
+  stacy history --script src/analysis.do    Runs of one script
+  stacy history --failed --since 7d         Failures in the last week
+  stacy history --since 2026-03-01 -n 100   Runs since a date (UTC)
+  stacy history show 42                     Everything recorded about run 42")]
+pub struct HistoryArgs {
+    #[command(subcommand)]
+    pub command: Option<HistoryCommand>,
+
+    /// Only runs of this script
+    #[arg(long, value_name = "SCRIPT")]
+    pub script: Option<PathBuf>,
+
+    /// Only runs that failed
+    #[arg(long)]
+    pub failed: bool,
+
+    /// Only runs since an age (12h, 7d, 2w) or a UTC date (2026-03-01)
+    #[arg(long, value_name = "WHEN")]
+    pub since: Option<String>,
+
+    /// Show at most this many runs, newest first
+    #[arg(short = 'n', long, default_value_t = 20)]
+    pub limit: usize,
+
+    /// Output format: human (default), json, or stata
+    #[arg(long, value_enum, default_value = "human")]
+    pub format: OutputFormat,
+}
+
+#[derive(Subcommand)]
+pub enum HistoryCommand {
+    /// Show one run in full
+    Show(ShowArgs),
+}
+
+#[derive(Args)]
+pub struct ShowArgs {
+    /// Run to show (the ID `stacy history` lists)
+    #[arg(value_name = "ID")]
+    pub id: u64,
+
+    /// Output format: human (default), json, or stata
+    #[arg(long, value_enum, default_value = "human")]
+    pub format: OutputFormat,
+}
+
+impl HistoryArgs {
+    pub fn format(&self) -> OutputFormat {
+        match &self.command {
+            Some(HistoryCommand::Show(args)) => args.format,
+            None => self.format,
+        }
+    }
+}
+
+/// Execute the history command
+pub fn execute(args: &HistoryArgs) -> Result<()> {
+    let project = Project::find()?.ok_or(Error::ProjectNotFound)?;
+    let mut runs = history::load(&project.root);
+    runs.reverse();
+
+    if let Some(HistoryCommand::Show(show)) = &args.command {
+        let run = runs
+            .into_iter()
+            .find(|run| run.id == show.id)
+            .ok_or_else(|| {
+                Error::Config(format!(
+                    "No run {} in the history (stacy history lists them)",
+                    show.id
+                ))
+            })?;
+        let output = HistoryOutput {
+            run_count: 1,
+            runs: vec![run],
+        };
+        match show.format {
+            OutputFormat::Json => println!("{}", output.to_json()),
+            OutputFormat::Stata => println!("{}", output.to_stata()),
+            OutputFormat::Human => print_run(&output.runs[0]),
+        }
+        return Ok(());
+    }
+
+    if let Some(script) = &args.script {
+        let script = history::relative_to(&project.root, script);
+        runs.retain(|run| run.script == script);
+    }
+    if args.failed {
+        runs.retain(|run| !run.success);
+    }
+    if let Some(since) = &args.since {
+        let since = history::parse_since(since, history::unix_now())?;
+        runs.retain(|run| run.started_at >= since);
+    }
+    runs.truncate(args.limit);
+
+    let output = HistoryOutput {
+        run_count: runs.len(),
+        runs,
+    };
+    match args.format {
+        OutputFormat::Json => println!("{}", output.to_json()),
+        OutputFormat::Stata => println!("{}", output.to_stata()),
+        OutputFormat::Human => {
+            if output.runs.is_empty() {
+                println!(
+                    "No runs recorded yet: stacy run, task, build and test record the scripts they run."
+                );
+                return Ok(());
+            }
+            println!(
+                "{:>5}  {:<20} {:>9}  {:<5} {:<30} LOG",
+                "ID", "STARTED (UTC)", "DURATION", "EXIT", "SCRIPT"
+            );
+            for run in &output.runs {
+                let exit = format!("{:<5}", run.exit_code);
+                println!(
+                    "{:>5}  {:<20} {:>8.2}s  {} {:<30} {}",
+                    run.id,
+                    format_timestamp(run.started_at),
+                    run.duration_secs,
+                    if run.success {
+                        exit.green()
+                    } else {
+                        exit.red()
+                    },
+                    run.script.display(),
+                    run.log_file
+                        .as_ref()
+                        .map(|log| log.display().to_string())
+                        .unwrap_or_else(|| "-".to_string())
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+fn print_run(run: &RunRecord) {
+    let status = if run.success {
+        "PASS".green()
+    } else {
+        "FAIL".red()
+    };
+    println!("Run {}  {}  {}", run.id, status, run.script.display());
+    println!("  command:   stacy {}", run.args.join(" "));
+    println!("  started:   {} UTC", format_timestamp(run.started_at));
+    println!("  duration:  {:.2}s", run.duration_secs);
+    println!("  exit code: {}", run.exit_code);
+    if let Some(error) = &run.error {
+        println!("  error:     {}", error);
+    }
+    if let Some(commit) = &run.commit {
+        println!("  commit:    {}", commit);
+    }
+    match &run.log_file {
+        Some(log) => println!("  log:       {}", log.display()),
+        None => println!("  log:       not kept"),
+    }
+}
@@ -14,6 +14,7 @@ use crate::project::structure::{
     create_project_structure, create_project_structure_with_metadata, has_project_markers,
     PackageSource, PackageToInstall, ProjectMetadata,
 };
+use crate::utils::paths;
 use clap::Args;
 use std::path::PathBuf;
 
@@ -52,13 +53,12 @@ pub fn execute(args: &InitArgs) -> Result<()> {
 
     // Canonicalize if exists, otherwise use as-is
     let path = if path.exists() {
-        path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+        paths::canonicalize_or_self(path)
     } else {
         // For new directories, canonicalize the parent if it exists
         if let Some(parent) = path.parent() {
             if parent.exists() {
-                parent
-                    .canonicalize()
+                paths::canonicalize(parent)
                     .map(|p| p.join(path.file_name().unwrap_or_default()))
                     .unwrap_or_else(|_| path.to_path_buf())
             } else {
@@ -94,7 +94,7 @@ pub fn execute(args: &InitArgs) -> Result<()> {
                 eprintln!("Use --force to overwrite existing project files.");
             }
         }
-        std::process::exit(1);
+        crate::session::exit(1);
     }
 
     // Interactive or standard mode
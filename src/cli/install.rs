@@ -5,14 +5,17 @@
 use crate::cli::output_format::OutputFormat;
 use crate::cli::output_types::{CommandOutput, InstallOutput};
 use crate::error::{Error, Result};
+use crate::executor::progress::MultiProgressDisplay;
 use crate::packages::global_cache;
 use crate::packages::installer::{install_locked, is_package_installed};
-use crate::packages::lockfile::{check_version_mismatch, load_lockfile, verify_lockfile_sync};
+use crate::packages::lockfile::{check_frozen, check_version_mismatch, frozen_mode, load_lockfile};
 use crate::project::config::load_config;
-use crate::project::Project;
+use crate::project::{profile, PackageSource, Project};
 use clap::Args;
 use std::collections::HashSet;
 use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
 
 #[derive(Args)]
 #[command(after_help = "\
@@ -20,7 +23,8 @@ Examples:
   stacy install                           Install all packages from lockfile
   stacy install --with dev                Include dev dependencies
   stacy install --no-verify               Skip checksum verification
-  stacy install --frozen                  Fail if lockfile is out of sync (for CI)")]
+  stacy install --frozen                  Fail if lockfile is out of sync (for CI)
+  stacy install --target ado/             Install into the project instead of the global cache")]
 pub struct InstallArgs {
     /// Skip checksum verification (the version pin is still enforced)
     #[arg(long)]
@@ -30,22 +34,45 @@ pub struct InstallArgs {
     #[arg(long, value_delimiter = ',')]
     pub with: Vec<String>,
 
-    /// Fail if lockfile doesn't match stacy.toml (for CI reproducibility)
+    /// Fail if lockfile doesn't match stacy.toml (for CI reproducibility).
+    /// Also on with STACY_FROZEN=1 or [install] frozen = true.
     #[arg(long)]
     pub frozen: bool,
 
+    /// Install into this project directory instead of the global package
+    /// cache (default with `[packages] install_mode = "local"`: its
+    /// install_dir, or ado/)
+    #[arg(long, value_name = "DIR")]
+    pub target: Option<std::path::PathBuf>,
+
+    /// Retries for a failed download, with exponential backoff (default 3,
+    /// or $STACY_NETWORK_RETRIES)
+    #[arg(long, value_name = "N")]
+    pub network_retries: Option<u32>,
+
+    /// Report every failed download attempt (global `-v`)
+    #[arg(skip)]
+    pub verbose: bool,
+
     /// Output format: human (default), json, or stata
     #[arg(long, value_enum, default_value = "human")]
     pub format: OutputFormat,
+
+    /// Leave out progress and summary lines (global `-q`); failures are
+    /// still reported
+    #[arg(skip)]
+    pub quiet: bool,
 }
 
 pub fn execute(args: &InstallArgs) -> Result<()> {
+    crate::packages::http::configure(args.network_retries, args.verbose);
     install_from_lockfile(args)
 }
 
 /// Install all packages from lockfile (replaces sync command)
 fn install_from_lockfile(args: &InstallArgs) -> Result<()> {
     let format = args.format;
+    let chatty = format == OutputFormat::Human && !args.quiet;
 
     // Find project
     let project = Project::find()?.ok_or_else(|| {
@@ -53,10 +80,39 @@ fn install_from_lockfile(args: &InstallArgs) -> Result<()> {
     })?;
 
     // Load lockfile
-    let lockfile = load_lockfile(&project.root)?.ok_or_else(|| {
-        Error::Config("No stacy.lock found. Use 'stacy add <package>' to add packages.".to_string())
+    let lockfile = load_lockfile(&project.root)?.ok_or_else(|| match profile::active() {
+        Some(name) => Error::Config(format!(
+            "No {} found. Run 'stacy --env-profile {} lock' to create it.",
+            profile::lockfile_name(),
+            name
+        )),
+        None => Error::Config(
+            "No stacy.lock found. Use 'stacy add <package>' to add packages.".to_string(),
+        ),
     })?;
 
+    // --target: this run's packages go to (and are looked up in) the
+    // directory, as with `install_mode = "local"`
+    if let Some(target) = &args.target {
+        let configured = project
+            .config
+            .as_ref()
+            .and_then(|c| c.packages.local_install_dir());
+        if chatty && configured.as_deref() != Some(target.as_path()) {
+            use colored::Colorize;
+            eprintln!(
+                "{} stacy run looks for packages in {}; set `install_mode = \"local\"` and `install_dir = \"{}\"` under [packages] for it to use {}",
+                "note:".cyan().bold(),
+                configured
+                    .map(|d| d.display().to_string())
+                    .unwrap_or_else(|| "the global cache".to_string()),
+                target.display(),
+                target.display()
+            );
+        }
+        global_cache::set_project_store(project.root.join(target));
+    }
+
     // Check for stacy version mismatch
     if args.format == OutputFormat::Human {
         if let Some((lockfile_ver, current_ver)) = check_version_mismatch(&lockfile) {
@@ -71,40 +127,14 @@ fn install_from_lockfile(args: &InstallArgs) -> Result<()> {
         }
     }
 
-    // If --frozen, verify lockfile is in sync with manifest
-    if args.frozen {
-        let config = load_config(&project.root)?
+    // Frozen mode (--frozen, STACY_FROZEN or [install] frozen): verify the
+    // lockfile is in sync with the manifest
+    if frozen_mode(args.frozen, project.config.as_ref()) {
+        let config = project
+            .config
+            .as_ref()
             .ok_or_else(|| Error::Config("No stacy.toml found.".to_string()))?;
-
-        let config_package_names: Vec<&str> = config
-            .packages
-            .all_packages()
-            .map(|(name, _, _)| name.as_str())
-            .collect();
-
-        let sync_result = verify_lockfile_sync(&lockfile, &config_package_names);
-
-        if !sync_result.in_sync {
-            let mut msg = String::from("Lockfile out of sync with stacy.toml (--frozen mode)\n");
-
-            if !sync_result.missing_in_lock.is_empty() {
-                msg.push_str("\n  Missing from stacy.lock:\n");
-                for name in &sync_result.missing_in_lock {
-                    msg.push_str(&format!("    - {}\n", name));
-                }
-            }
-
-            if !sync_result.extra_in_lock.is_empty() {
-                msg.push_str("\n  Extra in stacy.lock (not in stacy.toml):\n");
-                for name in &sync_result.extra_in_lock {
-                    msg.push_str(&format!("    - {}\n", name));
-                }
-            }
-
-            msg.push_str("\n  hint: run `stacy lock` then commit stacy.lock");
-
-            return Err(Error::Config(msg));
-        }
+        check_frozen(Some(&lockfile), config)?;
     }
 
     // Determine which groups to install
@@ -154,6 +184,7 @@ fn install_from_lockfile(args: &InstallArgs) -> Result<()> {
                 );
             }
             OutputFormat::Stata => println!("{}", output.to_stata()),
+            OutputFormat::Human if args.quiet => {}
             OutputFormat::Human => {
                 if args.with.is_empty() {
                     println!("No production packages in stacy.lock.");
@@ -166,20 +197,30 @@ fn install_from_lockfile(args: &InstallArgs) -> Result<()> {
         return Ok(());
     }
 
-    if format == OutputFormat::Human {
+    // A read-only cache is provisioned by its administrators: check that
+    // every package is there instead of installing the ones that are not
+    if global_cache::project_store().is_none() && global_cache::cache_read_only() {
+        let missing: Vec<String> = packages_to_install
+            .iter()
+            .filter(|(_, entry)| {
+                !matches!(entry.source, PackageSource::Local { editable: true, .. })
+            })
+            .filter(|(name, entry)| !is_package_installed(name, &entry.version))
+            .map(|(name, entry)| format!("{} {}", name, entry.version))
+            .collect();
+        if !missing.is_empty() {
+            return Err(global_cache::read_only_cache_error(&missing));
+        }
+    }
+
+    if chatty {
         let groups: Vec<_> = groups_to_install.iter().copied().collect();
         println!("Installing packages ({})...", groups.join(", "));
         println!();
     }
 
-    let mut results: Vec<SyncedPackage> = Vec::new();
-
-    // Process each package
     let verify = !args.no_verify;
-    for (name, entry) in packages_to_install {
-        let result = sync_package(name, entry, &project.root, verify)?;
-        results.push(result);
-    }
+    let results = sync_packages(&packages_to_install, &project.root, verify, chatty)?;
 
     // Build output struct
     let installed_count = results
@@ -244,6 +285,20 @@ fn install_from_lockfile(args: &InstallArgs) -> Result<()> {
         ));
     }
 
+    // Two installed packages shipping the same .ado file: one shadows the other
+    let groups: Vec<&str> = groups_to_install.iter().copied().collect();
+    let collisions = crate::packages::collisions::find_collisions(
+        &crate::packages::collisions::locked_ado_files(&lockfile, Some(&groups)),
+    );
+    let policy = load_config(&project.root)
+        .ok()
+        .flatten()
+        .map(|c| c.packages.on_collision)
+        .unwrap_or_default();
+    if let Err(Error::Config(msg)) = crate::packages::collisions::enforce(&collisions, policy) {
+        problems.push(msg);
+    }
+
     let error_message: Option<String> = if problems.is_empty() {
         None
     } else {
@@ -269,6 +324,7 @@ fn install_from_lockfile(args: &InstallArgs) -> Result<()> {
     match format {
         OutputFormat::Json => print_sync_json_output(&results, &output),
         OutputFormat::Stata => println!("{}", output.to_stata()),
+        OutputFormat::Human if args.quiet => {}
         OutputFormat::Human => print_sync_human_output(&results),
     }
 
@@ -290,6 +346,57 @@ fn install_from_lockfile(args: &InstallArgs) -> Result<()> {
 // Sync functionality (install from lockfile)
 // ============================================================================
 
+/// Most packages fetched at once. Enough to hide per-request latency
+/// without hammering SSC or the GitHub API.
+const MAX_CONCURRENT_DOWNLOADS: usize = 4;
+
+/// Sync `packages` on up to [`MAX_CONCURRENT_DOWNLOADS`] threads, showing
+/// progress when `show_progress` is set. Results keep the lockfile's order.
+fn sync_packages(
+    packages: &[(&String, &crate::project::PackageEntry)],
+    project_root: &Path,
+    verify: bool,
+    show_progress: bool,
+) -> Result<Vec<SyncedPackage>> {
+    let progress = MultiProgressDisplay::new(packages.len(), "Installing", show_progress);
+    let next = AtomicUsize::new(0);
+    let slots: Vec<Mutex<Option<Result<SyncedPackage>>>> =
+        packages.iter().map(|_| Mutex::new(None)).collect();
+
+    std::thread::scope(|scope| {
+        for _ in 0..MAX_CONCURRENT_DOWNLOADS.min(packages.len()) {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::SeqCst);
+                let Some((name, entry)) = packages.get(i) else {
+                    break;
+                };
+                let item = progress.start(&format!("{} ({})", name, entry.version));
+                let result = sync_package(name, entry, project_root, verify);
+                let outcome = match &result {
+                    Ok(synced) => match synced.action {
+                        SyncAction::Installed => "installed",
+                        SyncAction::AlreadyInstalled => "already installed",
+                        SyncAction::Skipped(_) | SyncAction::Mismatched(_) => "failed",
+                    },
+                    Err(_) => "failed",
+                };
+                progress.finish(item, outcome);
+                *slots[i].lock().unwrap() = Some(result);
+            });
+        }
+    });
+    progress.clear();
+
+    slots
+        .into_iter()
+        .map(|slot| {
+            slot.into_inner()
+                .unwrap()
+                .expect("every package was synced")
+        })
+        .collect()
+}
+
 /// Result of syncing a package
 #[derive(Debug)]
 struct SyncedPackage {
@@ -315,8 +422,33 @@ fn sync_package(
     project_root: &Path,
     verify: bool,
 ) -> Result<SyncedPackage> {
-    // Check if already installed in global cache
-    if is_package_installed(name, &entry.version) {
+    // An editable package is linked, not installed: its directory only has to
+    // be there
+    if let PackageSource::Local {
+        path,
+        editable: true,
+    } = &entry.source
+    {
+        let action = if project_root.join(path).is_dir() {
+            SyncAction::AlreadyInstalled
+        } else {
+            SyncAction::Skipped(format!("editable package directory {} not found", path))
+        };
+        return Ok(SyncedPackage {
+            name: name.to_string(),
+            version: entry.version.clone(),
+            action,
+            checksum_ok: None,
+        });
+    }
+
+    // Check if already installed: in the project's store if it has one,
+    // which the global cache doesn't stand in for
+    let installed = match global_cache::project_store() {
+        Some(store) => global_cache::store_package_path(store, name, &entry.version).is_some(),
+        None => is_package_installed(name, &entry.version),
+    };
+    if installed {
         // Verify checksum if requested
         let checksum_ok = if verify {
             verify_package_checksum(name, entry)
@@ -650,4 +782,36 @@ mod tests {
             assert_eq!(result, None);
         });
     }
+
+    #[test]
+    #[serial]
+    fn test_sync_packages_keeps_lockfile_order() {
+        with_test_cache(|temp| {
+            use crate::project::{PackageEntry, PackageSource};
+
+            let names: Vec<String> = (0..6).map(|i| format!("orderpkg{}", i)).collect();
+            let entries: Vec<PackageEntry> = names
+                .iter()
+                .map(|name| {
+                    let dir = global_cache::ensure_package_cache_dir(name, "1.0.0").unwrap();
+                    std::fs::write(dir.join(format!("{}.ado", name)), b"program x\nend\n").unwrap();
+                    PackageEntry {
+                        version: "1.0.0".to_string(),
+                        source: PackageSource::SSC { name: name.clone() },
+                        checksum: None,
+                        group: "production".to_string(),
+                    }
+                })
+                .collect();
+            let packages: Vec<_> = names.iter().zip(entries.iter()).collect();
+
+            let results = sync_packages(&packages, temp.path(), true, false).unwrap();
+
+            let synced: Vec<&str> = results.iter().map(|r| r.name.as_str()).collect();
+            assert_eq!(synced, names.iter().map(String::as_str).collect::<Vec<_>>());
+            assert!(results
+                .iter()
+                .all(|r| matches!(r.action, SyncAction::AlreadyInstalled)));
+        });
+    }
 }
@@ -0,0 +1,136 @@
+//! `stacy jobs` command implementation
+//!
+//! Lists the jobs `stacy submit` submitted from the project, asking the
+//! scheduler how each is doing. With a job ID, shows that job, and once it
+//! has finished, its output.
+
+use crate::artifacts::format_timestamp;
+use crate::cli::output_format::OutputFormat;
+use crate::cli::output_types::{CommandOutput, JobInfo, JobsOutput};
+use crate::error::{Error, Result};
+use crate::jobs::{self, scheduler};
+use crate::project::Project;
+use clap::Args;
+use colored::Colorize;
+
+#[derive(Args)]
+#[command(after_help = "\
+Examples:
+  stacy jobs                         Jobs submitted from this project
+  stacy jobs 12345                   One job, and its output once finished")]
+pub struct JobsArgs {
+    /// Job to show, with its output once it has finished
+    #[arg(value_name = "ID")]
+    pub id: Option<String>,
+
+    /// Output format: human (default), json, or stata
+    #[arg(long, value_enum, default_value = "human")]
+    pub format: OutputFormat,
+}
+
+/// Execute the jobs command
+pub fn execute(args: &JobsArgs) -> Result<()> {
+    let project = Project::find()?.ok_or(Error::ProjectNotFound)?;
+    let mut records = jobs::load(&project.root)?;
+    records.reverse();
+    if let Some(id) = &args.id {
+        records.retain(|job| &job.id == id);
+        if records.is_empty() {
+            return Err(Error::Config(format!(
+                "No job {} was submitted from this project (stacy jobs lists them)",
+                id
+            )));
+        }
+    }
+
+    let infos: Vec<JobInfo> = records
+        .iter()
+        .map(|job| {
+            let status = scheduler::status(job.scheduler, &job.id);
+            JobInfo {
+                id: job.id.clone(),
+                scheduler: job.scheduler.to_string(),
+                command: job.command.clone(),
+                submitted_at: format_timestamp(job.submitted_at),
+                state: status.state.as_str().to_string(),
+                detail: status.detail,
+                output: job.output.clone(),
+            }
+        })
+        .collect();
+    let output = JobsOutput {
+        job_count: infos.len(),
+        jobs: infos,
+    };
+
+    match args.format {
+        OutputFormat::Json => println!("{}", output.to_json()),
+        OutputFormat::Stata => println!("{}", output.to_stata()),
+        OutputFormat::Human if args.id.is_some() => print_job(&project, &output.jobs[0]),
+        OutputFormat::Human => {
+            if output.jobs.is_empty() {
+                println!("No jobs submitted yet: stacy submit <script> submits one.");
+                return Ok(());
+            }
+            println!(
+                "{:<14} {:<26} {:<20} COMMAND",
+                "JOB", "STATE", "SUBMITTED (UTC)"
+            );
+            for job in &output.jobs {
+                println!(
+                    "{:<14} {:<26} {:<20} {}",
+                    job.id,
+                    paint(job, &format!("{:<26}", state_label(job))),
+                    job.submitted_at,
+                    job.command
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A job's state, with the scheduler's word for it when that says more
+/// (`failed (TIMEOUT)`)
+fn state_label(job: &JobInfo) -> String {
+    if job.detail.eq_ignore_ascii_case(&job.state) {
+        job.state.clone()
+    } else {
+        format!("{} ({})", job.state, job.detail)
+    }
+}
+
+/// `text` in the color of the job's state
+fn paint(job: &JobInfo, text: &str) -> String {
+    match job.state.as_str() {
+        "completed" => text.green().to_string(),
+        "failed" | "cancelled" => text.red().to_string(),
+        _ => text.to_string(),
+    }
+}
+
+fn print_job(project: &Project, job: &JobInfo) {
+    println!("Job {} ({}): stacy {}", job.id, job.scheduler, job.command);
+    println!("  submitted: {} UTC", job.submitted_at);
+    println!("  state:     {}", paint(job, &state_label(job)));
+    println!("  output:    {}", job.output.display());
+
+    // A job the scheduler has forgotten has long finished
+    let finished = matches!(
+        job.state.as_str(),
+        "completed" | "failed" | "cancelled" | "unknown"
+    );
+    if !finished {
+        return;
+    }
+    match std::fs::read_to_string(project.root.join(&job.output)) {
+        Ok(text) => {
+            println!();
+            print!("{}", text);
+        }
+        Err(_) if job.state != "unknown" => {
+            println!("The job left no output at {}", job.output.display())
+        }
+        Err(_) => {}
+    }
+}
@@ -0,0 +1,101 @@
+//! `stacy kernel` command implementation
+//!
+//! - `stacy kernel install` registers a Jupyter kernel that runs Stata cells
+//!   through stacy (strict adopath, error detection)
+
+use crate::error::{Error, Result};
+use crate::kernel::{self, KernelBackend, KernelSpec};
+use clap::{Args, Subcommand};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+#[derive(Args)]
+#[command(about = "Run Stata in Jupyter notebooks through stacy", long_about = None)]
+pub struct KernelArgs {
+    #[command(subcommand)]
+    pub command: KernelCommand,
+}
+
+#[derive(Subcommand)]
+pub enum KernelCommand {
+    /// Install the stacy Jupyter kernel
+    #[command(after_help = "\
+Examples:
+  stacy kernel install                      Install for the current user
+  stacy kernel install --backend batch      Fresh Stata per cell, no PyStata
+  stacy kernel install --prefix .venv       Install into a virtualenv")]
+    Install(InstallArgs),
+}
+
+#[derive(Args)]
+pub struct InstallArgs {
+    /// Kernel name (directory under kernels/)
+    #[arg(long, default_value = "stacy")]
+    pub name: String,
+
+    /// Name shown in Jupyter's kernel picker
+    #[arg(long, default_value = "Stata (stacy)")]
+    pub display_name: String,
+
+    /// How cells run: auto (PyStata if available), pystata, or batch
+    #[arg(long, value_enum, default_value = "auto")]
+    pub backend: KernelBackend,
+
+    /// Python interpreter with ipykernel installed
+    #[arg(long, default_value = if cfg!(windows) { "python" } else { "python3" })]
+    pub python: String,
+
+    /// Install under PREFIX/share/jupyter (e.g. a virtualenv) instead of the
+    /// user's Jupyter directory
+    #[arg(long, value_name = "PREFIX")]
+    pub prefix: Option<PathBuf>,
+}
+
+/// Execute the kernel command
+pub fn execute(args: &KernelArgs) -> Result<()> {
+    match &args.command {
+        KernelCommand::Install(install_args) => execute_install(install_args),
+    }
+}
+
+fn execute_install(args: &InstallArgs) -> Result<()> {
+    let data_dir = match &args.prefix {
+        Some(prefix) => prefix.join("share").join("jupyter"),
+        None => kernel::user_data_dir().ok_or_else(|| {
+            Error::Config("Unable to determine the Jupyter data directory".to_string())
+        })?,
+    };
+    let stacy_bin = std::env::current_exe()?;
+    let spec = KernelSpec {
+        name: &args.name,
+        display_name: &args.display_name,
+        python: &args.python,
+        stacy_bin: &stacy_bin,
+        backend: args.backend,
+    };
+
+    let kernel_dir = kernel::install(&spec, &data_dir)?;
+    println!(
+        "Installed kernel '{}' ({}) in {}",
+        args.name,
+        args.backend,
+        kernel_dir.display()
+    );
+
+    if !has_ipykernel(&args.python) {
+        eprintln!(
+            "Warning: {} cannot import ipykernel; install it with `{} -m pip install ipykernel`",
+            args.python, args.python
+        );
+    }
+    Ok(())
+}
+
+fn has_ipykernel(python: &str) -> bool {
+    Command::new(python)
+        .args(["-c", "import ipykernel"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|s| s.success())
+}
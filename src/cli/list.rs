@@ -2,8 +2,10 @@
 //!
 //! Lists installed packages from the lockfile with their versions and sources.
 
-use crate::cli::output_format::OutputFormat;
-use crate::cli::output_types::{CommandOutput, ListOutput, ListPackageInfo};
+use crate::cli::output_format::{OutputFormat, TableFormat};
+use crate::cli::output_types::{
+    CommandOutput, CsvOutput, ListOutput, ListPackageInfo, MarkdownOutput,
+};
 use crate::error::{Error, Result};
 use crate::packages::lockfile::load_lockfile;
 use crate::project::{PackageSource, Project};
@@ -13,15 +15,17 @@ use clap::Args;
 #[command(after_help = "\
 Examples:
   stacy list                              List all packages
-  stacy list --tree                       Group by dependency type")]
+  stacy list --tree                       Group by dependency type
+  stacy list --format csv > packages.csv  Export for a spreadsheet
+  stacy list --format markdown            Table for a README")]
 pub struct ListArgs {
     /// Group packages by dependency type (production, dev, test)
     #[arg(long)]
     pub tree: bool,
 
-    /// Output format: human (default), json, or stata
+    /// Output format: human (default), json, stata, csv, or markdown
     #[arg(long, value_enum, default_value = "human")]
-    pub format: OutputFormat,
+    pub format: TableFormat,
 }
 
 /// Package info for display
@@ -30,10 +34,14 @@ struct PackageInfo {
     version: String,
     source: String,
     group: String,
+    /// Locked from an `[overrides]` source
+    overridden: bool,
+    /// Linked local directory (`dev = true`)
+    editable: bool,
 }
 
 pub fn execute(args: &ListArgs) -> Result<()> {
-    let format = args.format;
+    let format = args.format.output();
 
     // Find project
     let project = Project::find()?.ok_or_else(|| {
@@ -43,6 +51,13 @@ pub fn execute(args: &ListArgs) -> Result<()> {
     // Load lockfile
     let lockfile = load_lockfile(&project.root)?;
 
+    let overridden = |name: &str| {
+        project
+            .config
+            .as_ref()
+            .is_some_and(|c| c.overrides.contains_key(name))
+    };
+
     // Collect package info from lockfile
     let mut packages: Vec<PackageInfo> = Vec::new();
 
@@ -51,7 +66,7 @@ pub fn execute(args: &ListArgs) -> Result<()> {
             let source = match &entry.source {
                 PackageSource::SSC { name: _ } => "ssc".to_string(),
                 PackageSource::GitHub { repo, .. } => format!("github:{}", repo),
-                PackageSource::Local { path } => format!("local:{}", path),
+                PackageSource::Local { path, .. } => format!("local:{}", path),
                 PackageSource::Net { url } => format!("net:{}", url),
             };
 
@@ -60,6 +75,8 @@ pub fn execute(args: &ListArgs) -> Result<()> {
                 version: entry.version.clone(),
                 source,
                 group: entry.group.clone(),
+                overridden: overridden(name),
+                editable: entry.is_editable(),
             });
         }
     }
@@ -75,6 +92,8 @@ pub fn execute(args: &ListArgs) -> Result<()> {
             version: p.version.clone(),
             source: p.source.clone(),
             group: p.group.as_str().to_string(),
+            overridden: p.overridden,
+            editable: p.editable,
         })
         .collect();
 
@@ -85,6 +104,8 @@ pub fn execute(args: &ListArgs) -> Result<()> {
     };
 
     match format {
+        OutputFormat::Json if args.format.is_csv() => print!("{}", output.to_csv()),
+        OutputFormat::Json if args.format.is_markdown() => print!("{}", output.to_markdown()),
         OutputFormat::Json => println!("{}", output.to_json()),
         OutputFormat::Stata => println!("{}", output.to_stata()),
         OutputFormat::Human => {
@@ -103,6 +124,17 @@ pub fn execute(args: &ListArgs) -> Result<()> {
     Ok(())
 }
 
+fn markers(pkg: &PackageInfo) -> String {
+    let mut markers = String::new();
+    if pkg.overridden {
+        markers.push_str(" (override)");
+    }
+    if pkg.editable {
+        markers.push_str(" (editable)");
+    }
+    markers
+}
+
 fn print_flat(packages: &[PackageInfo]) {
     // Calculate column widths
     let name_width = packages.iter().map(|p| p.name.len()).max().unwrap_or(10);
@@ -117,10 +149,11 @@ fn print_flat(packages: &[PackageInfo]) {
         };
 
         println!(
-            "{:name_width$}  {:version_width$}  {}{}",
+            "{:name_width$}  {:version_width$}  {}{}{}",
             pkg.name,
             pkg.version,
             pkg.source,
+            markers(pkg),
             group_suffix,
             name_width = name_width,
             version_width = version_width
@@ -156,10 +189,11 @@ fn print_tree(packages: &[PackageInfo]) {
         println!("production ({} {}):", prod.len(), label);
         for pkg in &prod {
             println!(
-                "  {:name_width$}  {:version_width$}  {}",
+                "  {:name_width$}  {:version_width$}  {}{}",
                 pkg.name,
                 pkg.version,
                 pkg.source,
+                markers(pkg),
                 name_width = name_width,
                 version_width = version_width
             );
@@ -178,10 +212,11 @@ fn print_tree(packages: &[PackageInfo]) {
         println!("dev ({} {}):", dev.len(), label);
         for pkg in &dev {
             println!(
-                "  {:name_width$}  {:version_width$}  {}",
+                "  {:name_width$}  {:version_width$}  {}{}",
                 pkg.name,
                 pkg.version,
                 pkg.source,
+                markers(pkg),
                 name_width = name_width,
                 version_width = version_width
             );
@@ -200,10 +235,11 @@ fn print_tree(packages: &[PackageInfo]) {
         println!("test ({} {}):", test.len(), label);
         for pkg in &test {
             println!(
-                "  {:name_width$}  {:version_width$}  {}",
+                "  {:name_width$}  {:version_width$}  {}{}",
                 pkg.name,
                 pkg.version,
                 pkg.source,
+                markers(pkg),
                 name_width = name_width,
                 version_width = version_width
             );
@@ -223,12 +259,16 @@ mod tests {
                 version: "1.0".to_string(),
                 source: "ssc".to_string(),
                 group: "production".to_string(),
+                overridden: false,
+                editable: false,
             },
             PackageInfo {
                 name: "alpha".to_string(),
                 version: "2.0".to_string(),
                 source: "ssc".to_string(),
                 group: "production".to_string(),
+                overridden: false,
+                editable: false,
             },
         ];
 
@@ -7,12 +7,16 @@ use crate::cli::output_format::OutputFormat;
 use crate::cli::output_types::{CommandOutput, LockOutput};
 use crate::error::{Error, Result};
 use crate::packages::github::GitHubDownloader;
+use crate::packages::installer::{editable_entry, fetch_for_lock};
 use crate::packages::lockfile::{
-    add_package, create_lockfile, create_package_entry, load_lockfile, save_lockfile,
+    add_package, create_lockfile, create_package_entry, has_conflict_markers, load_lockfile,
+    merge_conflicted, save_lockfile,
 };
 use crate::packages::ssc::SscDownloader;
+use crate::packages::{collisions, global_cache};
 use crate::project::config::load_config;
-use crate::project::{PackageSource, Project};
+use crate::project::{overrides, profile, PackageSource, Project};
+use crate::utils::lock::{project_lock_path, with_state_lock};
 use clap::Args;
 
 #[derive(Args)]
@@ -20,7 +24,10 @@ use clap::Args;
 Examples:
   stacy lock                              Generate/update lockfile
   stacy lock --check                      Verify lockfile is in sync
-  stacy lock --refresh                    Recompute checksums from installed packages")]
+  stacy lock --refresh                    Recompute checksums from installed packages
+  stacy lock --package reghdfe            Re-resolve reghdfe, leave other entries as they are
+  stacy lock --prune                      Drop entries stacy.toml no longer declares
+  stacy lock --resolve-conflicts          Merge a stacy.lock git left conflict markers in")]
 pub struct LockArgs {
     /// Verify lockfile matches stacy.toml without updating (exit 1 if out of sync)
     #[arg(long, conflicts_with = "refresh")]
@@ -31,30 +38,109 @@ pub struct LockArgs {
     #[arg(long)]
     pub refresh: bool,
 
+    /// Re-resolve only this package, leaving every other entry untouched
+    /// (repeatable)
+    #[arg(short, long = "package", value_name = "NAME", conflicts_with = "check")]
+    pub packages: Vec<String>,
+
+    /// Merge both sides of a stacy.lock with git conflict markers, resolving
+    /// the packages they disagree on again from stacy.toml
+    #[arg(long, conflicts_with = "check")]
+    pub resolve_conflicts: bool,
+
+    /// Drop lockfile entries stacy.toml no longer declares, and remove the
+    /// package versions only they and superseded pins used from the cache
+    #[arg(long, conflicts_with = "check")]
+    pub prune: bool,
+
+    /// Retries for a failed download, with exponential backoff (default 3,
+    /// or $STACY_NETWORK_RETRIES)
+    #[arg(long, value_name = "N")]
+    pub network_retries: Option<u32>,
+
+    /// Report every failed download attempt (global `-v`)
+    #[arg(skip)]
+    pub verbose: bool,
+
     /// Output format: human (default), json, or stata
     #[arg(long, value_enum, default_value = "human")]
     pub format: OutputFormat,
+
+    /// Leave out progress and summary lines (global `-q`); failures are
+    /// still reported
+    #[arg(skip)]
+    pub quiet: bool,
 }
 
 pub fn execute(args: &LockArgs) -> Result<()> {
-    let format = args.format;
+    crate::packages::http::configure(args.network_retries, args.verbose);
 
     // Find project
     let project = Project::find()?.ok_or_else(|| {
         Error::Config("Not in a stacy project. Run 'stacy init' first.".to_string())
     })?;
 
+    if args.check {
+        return lock_project(args, &project);
+    }
+    with_state_lock(&project_lock_path(&project.root), || {
+        lock_project(args, &project)
+    })?
+}
+
+fn lock_project(args: &LockArgs, project: &Project) -> Result<()> {
+    let format = args.format;
+    let chatty = format == OutputFormat::Human && !args.quiet;
+
     // Load config
     let config = load_config(&project.root)?
         .ok_or_else(|| Error::Config("No stacy.toml found. Run 'stacy init' first.".to_string()))?;
 
-    // Load existing lockfile (or create new one)
-    let existing_lockfile = load_lockfile(&project.root)?;
+    // Load existing lockfile (or create new one). With --resolve-conflicts a
+    // conflicted lockfile is merged instead; the packages its two sides
+    // disagree on are left out and resolved again below, like any package
+    // not yet locked.
+    let mut merge_conflicts: Option<Vec<String>> = None;
+    let existing_lockfile = if args.resolve_conflicts {
+        let content = std::fs::read_to_string(project.root.join(profile::lockfile_name()))
+            .unwrap_or_default();
+        if has_conflict_markers(&content) {
+            let merge = merge_conflicted(&content)?;
+            merge_conflicts = Some(merge.conflicting);
+            Some(merge.lockfile)
+        } else {
+            load_lockfile(&project.root)?
+        }
+    } else {
+        load_lockfile(&project.root)?
+    };
     let mut lockfile = existing_lockfile.clone().unwrap_or_else(create_lockfile);
 
     // Get all packages from config
     let config_packages: Vec<_> = config.packages.all_packages().collect();
 
+    // Locked packages stacy.toml now names another source for: an override
+    // added or dropped, or the declaration edited
+    let source_changed: Vec<String> = config_packages
+        .iter()
+        .filter(|(name, spec, _)| {
+            lockfile.packages.get(*name).is_some_and(|entry| {
+                !overrides::source_matches(spec.source(), &entry.source)
+                    || spec.is_editable() != entry.is_editable()
+            })
+        })
+        .map(|(name, _, _)| name.to_string())
+        .collect();
+    // `lock` resolves SSC and GitHub sources; `local:`/`net:` ones are
+    // recorded by `stacy add`, unless an override names them or they replace
+    // a locked source
+    let resolvable = |name: &str, source: &str| {
+        source == "ssc"
+            || source.starts_with("github:")
+            || config.overrides.contains_key(name)
+            || source_changed.iter().any(|n| n == name)
+    };
+
     if args.check {
         // Verify mode: check if lockfile matches config
         let mut in_sync = true;
@@ -69,6 +155,10 @@ pub fn execute(args: &LockArgs) -> Result<()> {
             }
         }
 
+        if !source_changed.is_empty() {
+            in_sync = false;
+        }
+
         // Check for packages in lockfile but not in config
         let config_names: std::collections::HashSet<&str> =
             config_packages.iter().map(|(n, _, _)| n.as_str()).collect();
@@ -85,6 +175,9 @@ pub fn execute(args: &LockArgs) -> Result<()> {
             updated: false,
             in_sync,
             failed: 0,
+            orphans: extra_in_lock.iter().map(|n| n.to_string()).collect(),
+            pruned: Vec::new(),
+            cache_pruned: Vec::new(),
             error: if in_sync {
                 None
             } else {
@@ -95,6 +188,7 @@ pub fn execute(args: &LockArgs) -> Result<()> {
         match format {
             OutputFormat::Json => println!("{}", output.to_json()),
             OutputFormat::Stata => println!("{}", output.to_stata()),
+            OutputFormat::Human if args.quiet => {}
             OutputFormat::Human => {
                 if in_sync {
                     println!("Lockfile is in sync with stacy.toml.");
@@ -102,56 +196,157 @@ pub fn execute(args: &LockArgs) -> Result<()> {
                     println!("Lockfile is out of sync with stacy.toml:");
                     if !missing_in_lock.is_empty() {
                         println!();
-                        println!("  Missing from stacy.lock:");
+                        println!("  Missing from {}:", profile::lockfile_name());
                         for name in &missing_in_lock {
                             println!("    - {}", name);
                         }
                     }
+                    if !source_changed.is_empty() {
+                        println!();
+                        println!("  Locked from another source than stacy.toml names:");
+                        for name in &source_changed {
+                            println!("    - {}", name);
+                        }
+                    }
                     if !extra_in_lock.is_empty() {
                         println!();
-                        println!("  Extra in stacy.lock (not in stacy.toml):");
+                        println!(
+                            "  Extra in {} (not in stacy.toml):",
+                            profile::lockfile_name()
+                        );
                         for name in &extra_in_lock {
                             println!("    - {}", name);
                         }
                     }
                     println!();
-                    println!("Run 'stacy lock' to update the lockfile.");
+                    if !missing_in_lock.is_empty() || !source_changed.is_empty() {
+                        println!("Run 'stacy lock' to update the lockfile.");
+                    }
+                    if !extra_in_lock.is_empty() {
+                        println!("Run 'stacy lock --prune' to drop the extra entries.");
+                    }
                 }
             }
         }
 
         if !in_sync {
-            std::process::exit(1);
+            crate::session::exit(1);
         }
 
         return Ok(());
     }
 
     // Update mode: resolve dependencies and update lockfile
-    if format == OutputFormat::Human {
+    if chatty {
+        if let Some(conflicting) = &merge_conflicts {
+            println!("Merged both sides of the conflicted stacy.lock.");
+            if !conflicting.is_empty() {
+                println!(
+                    "  Locked differently on each side: {}",
+                    conflicting.join(", ")
+                );
+            }
+            println!();
+        } else if args.resolve_conflicts {
+            println!("stacy.lock has no merge conflicts.");
+            println!();
+        }
         println!("Resolving dependencies from stacy.toml...");
         println!();
     }
 
     let downloader = SscDownloader::new();
-    let mut updated = false;
+    // A merged lockfile is written even when nothing needs resolving
+    let mut updated = merge_conflicts.is_some();
     let mut added_count = 0;
-    let mut removed_count = 0;
+    // Entries stacy.toml no longer declares: dropped with --prune, else kept
+    let mut pruned: Vec<String> = Vec::new();
+    let mut orphans: Vec<String> = Vec::new();
     // Packages in stacy.toml that could not be recorded in the lockfile.
     let mut failures: Vec<String> = Vec::new();
+    // The .ado files of the packages downloaded to resolve them
+    let mut downloaded_ado = std::collections::BTreeMap::new();
+
+    // --package: only the named packages are resolved, removed or refreshed.
+    // Their entries are set aside so the loop below resolves them again.
+    let mut relocked = Vec::new();
+    let only: Vec<String> = args.packages.iter().map(|p| p.to_lowercase()).collect();
+    let selected = |name: &str| only.is_empty() || only.iter().any(|n| n == name);
+    for name in &only {
+        match config_packages.iter().find(|(n, _, _)| n.as_str() == name) {
+            Some((_, spec, _)) => {
+                let source_str = spec.source();
+                if resolvable(name, source_str) {
+                    if let Some(entry) = lockfile.packages.remove(name) {
+                        relocked.push((name.clone(), entry));
+                    }
+                } else {
+                    // Kept as is: `lock` cannot resolve these sources
+                    failures.push(name.clone());
+                    if format == OutputFormat::Human {
+                        eprintln!(
+                            "  x could not resolve {}: '{}' sources are re-fetched by `stacy update {}`",
+                            name, source_str, name
+                        );
+                    }
+                }
+            }
+            // Locked but no longer in stacy.toml: dropped below
+            None if lockfile.packages.contains_key(name) => {}
+            None => {
+                return Err(Error::Config(format!(
+                    "Package '{}' is not in stacy.toml",
+                    name
+                )));
+            }
+        }
+    }
+
+    // Packages locked from another source are resolved again, like those
+    // named with --package
+    for name in source_changed.iter().filter(|n| selected(n)) {
+        if let Some(entry) = lockfile.packages.remove(name) {
+            relocked.push((name.clone(), entry));
+        }
+    }
 
     // Add packages from config that aren't in lockfile
     let github_downloader = GitHubDownloader::new();
 
     for (name, spec, group) in &config_packages {
-        if lockfile.packages.contains_key(*name) {
+        if lockfile.packages.contains_key(*name) || !selected(name) {
             continue;
         }
 
         let source_str = spec.source();
         let group_str = group.as_str();
 
-        if source_str == "ssc" {
+        if spec.is_editable() {
+            // Linked, not fetched: the directory is only checked to hold
+            // the package
+            let path = &source_str["local:".len()..];
+            match editable_entry(name, path, &project.root, group_str) {
+                Ok((entry, files)) => {
+                    add_package(&mut lockfile, name, entry);
+                    downloaded_ado.insert(
+                        name.to_string(),
+                        collisions::ado_files_from_download(&files),
+                    );
+                    updated = true;
+                    added_count += 1;
+
+                    if chatty {
+                        println!("  + {} ({}, editable)", name, source_str);
+                    }
+                }
+                Err(e) => {
+                    failures.push(name.to_string());
+                    if format == OutputFormat::Human {
+                        eprintln!("  x could not resolve {}: {}", name, e);
+                    }
+                }
+            }
+        } else if source_str == "ssc" {
             // Resolve SSC package - download full package for checksum
             match downloader.download_package(name) {
                 Ok(download) => {
@@ -172,10 +367,14 @@ pub fn execute(args: &LockArgs) -> Result<()> {
                     );
 
                     add_package(&mut lockfile, name, entry);
+                    downloaded_ado.insert(
+                        name.to_string(),
+                        collisions::ado_files_from_download(&download.files),
+                    );
                     updated = true;
                     added_count += 1;
 
-                    if format == OutputFormat::Human {
+                    if chatty {
                         println!("  + {} ({})", name, version);
                     }
                 }
@@ -223,6 +422,8 @@ pub fn execute(args: &LockArgs) -> Result<()> {
                             repo: repo_part.to_string(),
                             tag: git_ref.unwrap_or("main").to_string(),
                             commit: commit_sha,
+                            pkg_dir: download.pkg_dir.clone(),
+                            synthesized: download.synthesized,
                         };
                         let entry = create_package_entry(
                             &version,
@@ -232,10 +433,14 @@ pub fn execute(args: &LockArgs) -> Result<()> {
                         );
 
                         add_package(&mut lockfile, name, entry);
+                        downloaded_ado.insert(
+                            name.to_string(),
+                            collisions::ado_files_from_download(&download.files),
+                        );
                         updated = true;
                         added_count += 1;
 
-                        if format == OutputFormat::Human {
+                        if chatty {
                             println!(
                                 "  + {} (github:{}@{})",
                                 name,
@@ -260,6 +465,31 @@ pub fn execute(args: &LockArgs) -> Result<()> {
                     );
                 }
             }
+        } else if let Some(source) =
+            net_or_local(source_str).filter(|_| resolvable(name, source_str))
+        {
+            // Fetched as `stacy add` would: `add` never sees an override, and
+            // a changed source would otherwise keep its old pin
+            match fetch_for_lock(name, source, &project.root, group_str) {
+                Ok((entry, files)) => {
+                    if chatty {
+                        println!("  + {} ({})", name, source_str);
+                    }
+                    add_package(&mut lockfile, name, entry);
+                    downloaded_ado.insert(
+                        name.to_string(),
+                        collisions::ado_files_from_download(&files),
+                    );
+                    updated = true;
+                    added_count += 1;
+                }
+                Err(e) => {
+                    failures.push(name.to_string());
+                    if format == OutputFormat::Human {
+                        eprintln!("  x could not resolve {}: {}", name, e);
+                    }
+                }
+            }
         } else {
             // net: and local: packages carry no resolvable version — they are
             // recorded in the lockfile by `stacy add`, not by `stacy lock`.
@@ -273,23 +503,50 @@ pub fn execute(args: &LockArgs) -> Result<()> {
         }
     }
 
-    // Remove packages from lockfile that aren't in config
+    // A re-resolved package only counts as a change if its entry changed.
+    // One that failed to resolve keeps its old pin.
+    let mut relocked_count = 0;
+    for (name, old) in relocked {
+        match lockfile.packages.get(&name) {
+            Some(new) => {
+                added_count -= 1;
+                if *new != old {
+                    relocked_count += 1;
+                }
+            }
+            None => {
+                lockfile.packages.insert(name, old);
+            }
+        }
+    }
+    if !only.is_empty() {
+        updated = merge_conflicts.is_some() || relocked_count > 0 || added_count > 0;
+    }
+
+    // Packages locked but no longer in stacy.toml. --prune drops them, as
+    // does naming them with --package; otherwise they are only reported.
     let config_names: std::collections::HashSet<&str> =
         config_packages.iter().map(|(n, _, _)| n.as_str()).collect();
-    let to_remove: Vec<String> = lockfile
+    let not_declared: Vec<String> = lockfile
         .packages
         .keys()
-        .filter(|n: &&String| !config_names.contains(n.as_str()))
+        .filter(|n: &&String| !config_names.contains(n.as_str()) && selected(n))
         .cloned()
         .collect();
 
-    for name in to_remove {
-        lockfile.packages.remove(&name);
-        updated = true;
-        removed_count += 1;
-
-        if format == OutputFormat::Human {
-            println!("  - {}", name);
+    for name in not_declared {
+        if args.prune || !only.is_empty() {
+            lockfile.packages.remove(&name);
+            updated = true;
+            if chatty {
+                println!("  - {} (not in stacy.toml)", name);
+            }
+            pruned.push(name);
+        } else {
+            if chatty {
+                println!("  ? {} (not in stacy.toml, kept)", name);
+            }
+            orphans.push(name);
         }
     }
 
@@ -298,12 +555,15 @@ pub fn execute(args: &LockArgs) -> Result<()> {
     // entries recorded before duplicate manifest entries were deduped (#68).
     let mut refreshed_count = 0;
     if args.refresh {
-        for (name, entry) in lockfile.packages.iter_mut() {
-            let Ok(cache_dir) = crate::packages::global_cache::package_path(name, &entry.version)
-            else {
+        for (name, entry) in lockfile
+            .packages
+            .iter_mut()
+            .filter(|(name, entry)| selected(name) && !entry.is_editable())
+        {
+            let Ok(cache_dir) = global_cache::resolve_package_path(name, &entry.version) else {
                 continue;
             };
-            let Some(actual) = crate::packages::global_cache::hash_package_dir(&cache_dir) else {
+            let Some(actual) = global_cache::hash_package_dir(&cache_dir) else {
                 if format == OutputFormat::Human {
                     eprintln!("  Warning: {} not in cache, checksum left unchanged", name);
                 }
@@ -314,20 +574,69 @@ pub fn execute(args: &LockArgs) -> Result<()> {
                 entry.checksum = Some(new_checksum);
                 updated = true;
                 refreshed_count += 1;
-                if format == OutputFormat::Human {
+                if chatty {
                     println!("  ~ {} (checksum recomputed)", name);
                 }
             }
         }
     }
 
+    // Refuse (or warn about) two packages shipping the same .ado file.
+    // Packages resolved earlier are looked up in the cache.
+    let mut ado_files = collisions::locked_ado_files(&lockfile, None);
+    ado_files.extend(downloaded_ado);
+    collisions::enforce(
+        &collisions::find_collisions(&ado_files),
+        config.packages.on_collision,
+    )?;
+
+    // A merge leaves out the packages its sides disagree on. Unless every one
+    // was locked again, writing it would lose their pins: stacy.lock keeps
+    // its conflict markers instead.
+    let conflicts_kept = merge_conflicts.is_some() && !failures.is_empty();
+    if conflicts_kept {
+        updated = false;
+    }
+
     // Save lockfile if updated
     if updated {
         save_lockfile(&project.root, &lockfile)?;
     }
 
+    // --prune: the versions the lockfile pinned before and no longer does
+    // leave the cache (and the project's package store)
+    let mut cache_pruned = Vec::new();
+    if args.prune && !conflicts_kept {
+        let before = existing_lockfile.as_ref().unwrap_or(&lockfile);
+        for (name, entry) in before.packages.iter().filter(|(name, entry)| {
+            selected(name)
+                && !entry.is_editable()
+                && lockfile
+                    .packages
+                    .get(*name)
+                    .is_none_or(|now| now.version != entry.version)
+        }) {
+            if prune_installed(name, &entry.version)? {
+                if chatty {
+                    println!("  - {} {} (removed from the cache)", name, entry.version);
+                }
+                cache_pruned.push(format!("{}@{}", name, entry.version));
+            }
+        }
+    }
+
     let error_message = if failures.is_empty() {
         None
+    } else if conflicts_kept {
+        Some(format!(
+            "{} package(s) could not be resolved: {}\n\n{} was left as it was, \
+             merge conflicts included. Run 'stacy lock --resolve-conflicts' again once \
+             they can be resolved, or keep one side (`git checkout --ours {}`).",
+            failures.len(),
+            failures.join(", "),
+            profile::lockfile_name(),
+            profile::lockfile_name()
+        ))
     } else {
         Some(format!(
             "{} package(s) could not be resolved: {}",
@@ -346,12 +655,16 @@ pub fn execute(args: &LockArgs) -> Result<()> {
         updated,
         in_sync: failures.is_empty(),
         failed: failures.len(),
+        orphans: orphans.clone(),
+        pruned: pruned.clone(),
+        cache_pruned: cache_pruned.clone(),
         error: error_message.clone(),
     };
 
     match format {
         OutputFormat::Json => println!("{}", output.to_json()),
         OutputFormat::Stata => println!("{}", output.to_stata()),
+        OutputFormat::Human if args.quiet => {}
         OutputFormat::Human => {
             println!();
             if updated {
@@ -359,14 +672,21 @@ pub fn execute(args: &LockArgs) -> Result<()> {
                 if added_count > 0 {
                     summary.push(format!("{} added", added_count));
                 }
-                if removed_count > 0 {
-                    summary.push(format!("{} removed", removed_count));
+                if relocked_count > 0 {
+                    summary.push(format!("{} re-resolved", relocked_count));
+                }
+                if !pruned.is_empty() {
+                    summary.push(format!("{} removed", pruned.len()));
                 }
                 if refreshed_count > 0 {
                     summary.push(format!("{} checksum(s) recomputed", refreshed_count));
                 }
+                if merge_conflicts.is_some() {
+                    summary.push("merge conflicts resolved".to_string());
+                }
                 println!(
-                    "Updated stacy.lock: {} ({} total packages)",
+                    "Updated {}: {} ({} total packages)",
+                    profile::lockfile_name(),
                     summary.join(", "),
                     lockfile.packages.len()
                 );
@@ -376,6 +696,20 @@ pub fn execute(args: &LockArgs) -> Result<()> {
                     lockfile.packages.len()
                 );
             }
+            if !cache_pruned.is_empty() {
+                println!(
+                    "Removed {} package version(s) from the cache: {}",
+                    cache_pruned.len(),
+                    cache_pruned.join(", ")
+                );
+            }
+            if !orphans.is_empty() {
+                println!(
+                    "{} locked package(s) not in stacy.toml: {}. Run 'stacy lock --prune' to drop them.",
+                    orphans.len(),
+                    orphans.join(", ")
+                );
+            }
         }
     }
 
@@ -386,6 +720,42 @@ pub fn execute(args: &LockArgs) -> Result<()> {
     Ok(())
 }
 
+/// Remove a package version from the project's package store and the local
+/// cache. The shared cache, and a local cache provisioned read-only
+/// (`[cache] read_only`), are left alone. Returns whether anything was
+/// removed.
+fn prune_installed(name: &str, version: &str) -> Result<bool> {
+    let mut removed = false;
+    if let Some(dir) = global_cache::project_store()
+        .and_then(|store| global_cache::store_package_path(store, name, version))
+    {
+        std::fs::remove_dir_all(&dir)?;
+        removed = true;
+    }
+    if !global_cache::cache_read_only() && global_cache::is_cached(name, version)? {
+        global_cache::remove_cached_package(name, version)?;
+        removed = true;
+    }
+    Ok(removed)
+}
+
+/// The `net:` or `local:` source a stacy.toml spec names
+fn net_or_local(spec: &str) -> Option<PackageSource> {
+    let (scheme, rest) = spec.split_once(':')?;
+    if scheme.eq_ignore_ascii_case("net") {
+        Some(PackageSource::Net {
+            url: rest.to_string(),
+        })
+    } else if scheme.eq_ignore_ascii_case("local") {
+        Some(PackageSource::Local {
+            path: rest.to_string(),
+            editable: false,
+        })
+    } else {
+        None
+    }
+}
+
 #[cfg(test)]
 mod tests {
     // Integration tests in tests/integration_cli.rs
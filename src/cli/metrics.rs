@@ -0,0 +1,94 @@
+//! `stacy metrics` command implementation
+//!
+//! - `stacy metrics export` prints the project's totals of runs, tests, cache
+//!   lookups and installs, for Prometheus or as JSON
+
+use crate::cli::output_format::OutputFormat;
+use crate::error::{Error, Result};
+use crate::metrics::counters::{project_name, Totals};
+use crate::metrics::prometheus;
+use crate::project::Project;
+use crate::utils::paths::write_atomically;
+use clap::{Args, Subcommand, ValueEnum};
+use std::path::PathBuf;
+
+#[derive(Args)]
+#[command(about = "Export run, test, cache and install totals", long_about = None)]
+pub struct MetricsArgs {
+    #[command(subcommand)]
+    pub command: MetricsCommand,
+}
+
+#[derive(Subcommand)]
+pub enum MetricsCommand {
+    /// Print the project's totals for a metrics scraper
+    #[command(after_help = "\
+Examples:
+  stacy metrics export                         Prometheus text format on stdout
+  stacy metrics export --format json           The same totals as JSON
+  stacy metrics export -o /var/lib/node_exporter/textfile_collector/stacy.prom")]
+    Export(ExportArgs),
+}
+
+#[derive(Args)]
+pub struct ExportArgs {
+    /// Output format
+    #[arg(long, value_enum, default_value = "prometheus")]
+    pub format: MetricsFormat,
+
+    /// Write to this file instead of stdout, replacing it whole
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum MetricsFormat {
+    /// Prometheus text exposition format
+    Prometheus,
+    /// JSON object of the totals
+    Json,
+}
+
+impl MetricsArgs {
+    /// Output format of the subcommand, as far as errors go: JSON or human
+    pub fn format(&self) -> OutputFormat {
+        match &self.command {
+            MetricsCommand::Export(args) => match args.format {
+                MetricsFormat::Json => OutputFormat::Json,
+                MetricsFormat::Prometheus => OutputFormat::Human,
+            },
+        }
+    }
+}
+
+/// Execute the metrics command
+pub fn execute(args: &MetricsArgs) -> Result<()> {
+    match &args.command {
+        MetricsCommand::Export(export_args) => execute_export(export_args),
+    }
+}
+
+/// Execute `stacy metrics export`
+fn execute_export(args: &ExportArgs) -> Result<()> {
+    let project = Project::find()?.ok_or(Error::ProjectNotFound)?;
+    let totals = Totals::load(&project.root);
+    let name = project_name(&project.root);
+
+    let content = match args.format {
+        MetricsFormat::Prometheus => prometheus::render(&name, &totals),
+        MetricsFormat::Json => {
+            let mut json = serde_json::to_value(&totals)?;
+            if let Some(object) = json.as_object_mut() {
+                object.remove("version");
+                object.insert("project".to_string(), name.into());
+            }
+            format!("{}\n", serde_json::to_string_pretty(&json)?)
+        }
+    };
+
+    match &args.output {
+        Some(path) => write_atomically(path, content.as_bytes())?,
+        None => print!("{}", content),
+    }
+    Ok(())
+}
@@ -0,0 +1,58 @@
+//! `stacy migrate` command implementation
+//!
+//! Rewrites a stacy.toml or stacy.lock written for an older stacy in the
+//! current format, keeping a backup of each file it changes, and lists what
+//! changed. See [`crate::project::migrate`] for the formats it updates.
+
+use crate::error::{Error, Result};
+use crate::project::migrate;
+use crate::project::root::find_project_root_unchecked;
+use clap::Args;
+
+#[derive(Args)]
+#[command(after_help = "\
+Examples:
+  stacy migrate             Update stacy.toml and stacy.lock, keeping backups
+  stacy migrate --dry-run   Only list what would change
+
+Each file changed is first copied to <file>.bak.")]
+pub struct MigrateArgs {
+    /// List what would change without writing anything
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+/// Execute the migrate command
+pub fn execute(args: &MigrateArgs) -> Result<()> {
+    let root = find_project_root_unchecked()?.ok_or(Error::ProjectNotFound)?;
+    let migrations = migrate::plan(&root)?;
+
+    if migrations.is_empty() {
+        println!("Project files are in the current format: nothing to migrate.");
+        return Ok(());
+    }
+
+    if args.dry_run {
+        for migration in &migrations {
+            println!("Would migrate {}:", migration.file.display());
+            for change in &migration.changes {
+                println!("  - {}", change);
+            }
+        }
+        println!("Nothing written (--dry-run).");
+        return Ok(());
+    }
+
+    let backups = migrate::apply(&root, &migrations)?;
+    for (migration, backup) in migrations.iter().zip(&backups) {
+        println!(
+            "Migrated {} (backup in {}):",
+            migration.file.display(),
+            backup.display()
+        );
+        for change in &migration.changes {
+            println!("  - {}", change);
+        }
+    }
+    Ok(())
+}
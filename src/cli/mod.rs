@@ -1,22 +1,43 @@
 pub mod add;
 pub mod bench;
+pub mod build;
 pub mod cache;
+pub mod config;
+pub mod daemon;
+pub mod data;
 pub mod deps;
+pub mod dev;
 pub mod doctor;
 pub mod env;
+pub mod exec;
 
 pub mod explain;
+pub mod export;
 pub mod format;
+pub mod history;
 pub mod init;
 pub mod install;
+pub mod jobs;
+pub mod kernel;
 pub mod list;
 pub mod lock;
+pub mod metrics;
+pub mod migrate;
 pub mod outdated;
 pub mod output_format;
 pub mod output_types;
+pub mod outputs;
 pub mod remove;
+pub mod report;
+pub mod rerun;
 pub mod run;
+pub mod schedule;
+pub mod search;
+pub mod self_update;
+pub mod shell;
+pub mod submit;
 pub mod task;
+pub mod telemetry;
 pub mod test;
 pub mod test_output;
 pub mod update;
@@ -1,26 +1,59 @@
 //! `stacy outdated` command implementation
 //!
 //! Checks for package updates by comparing installed versions with latest available.
+//! Packages are checked in parallel, with the requests to each host paced so
+//! SSC and the GitHub API aren't hammered.
+//!
+//! Under `STACY_OFFLINE=1` SSC packages are compared with the latest versions
+//! recorded in the SSC index (see `packages::ssc_index`), and GitHub packages
+//! can't be checked.
 
-use crate::cli::output_format::OutputFormat;
-use crate::cli::output_types::{CommandOutput, OutdatedOutput, OutdatedPackageInfo};
+use crate::cli::output_format::{OutputFormat, TableFormat};
+use crate::cli::output_types::{
+    CommandOutput, CsvOutput, MarkdownOutput, OutdatedOutput, OutdatedPackageInfo,
+};
 use crate::error::{Error, Result};
 use crate::packages::github::GitHubDownloader;
+use crate::packages::http::HostLimiter;
 use crate::packages::lockfile::load_lockfile;
 use crate::packages::ssc::SscDownloader;
-use crate::project::{PackageSource, Project};
+use crate::packages::ssc_index;
+use crate::project::{Lockfile, PackageEntry, PackageSource, Project};
 use clap::Args;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
 
 #[derive(Args)]
 #[command(after_help = "\
 Examples:
-  stacy outdated                          Check for package updates")]
+  stacy outdated                          Check for package updates
+  stacy outdated --format csv             One row per outdated package")]
 pub struct OutdatedArgs {
-    /// Output format: human (default), json, or stata
+    /// Retries for a failed download, with exponential backoff (default 3,
+    /// or $STACY_NETWORK_RETRIES)
+    #[arg(long, value_name = "N")]
+    pub network_retries: Option<u32>,
+
+    /// Report every failed download attempt (global `-v`)
+    #[arg(skip)]
+    pub verbose: bool,
+
+    /// Output format: human (default), json, stata, csv, or markdown
     #[arg(long, value_enum, default_value = "human")]
-    pub format: OutputFormat,
+    pub format: TableFormat,
 }
 
+/// Most packages checked at once
+const MAX_CONCURRENT_CHECKS: usize = 8;
+
+/// Most requests in flight to one host, as many as `stacy install` downloads
+/// at once: SSC and the GitHub API are shared services
+const MAX_CHECKS_PER_HOST: usize = 4;
+
+/// Least time between two requests started to one host
+const HOST_REQUEST_SPACING: Duration = Duration::from_millis(100);
+
 /// Information about an outdated package for internal use
 struct OutdatedInfo {
     name: String,
@@ -30,7 +63,8 @@ struct OutdatedInfo {
 }
 
 pub fn execute(args: &OutdatedArgs) -> Result<()> {
-    let format = args.format;
+    crate::packages::http::configure(args.network_retries, args.verbose);
+    let format = args.format.output();
 
     // Find project
     let project = Project::find()?.ok_or_else(|| {
@@ -53,6 +87,8 @@ pub fn execute(args: &OutdatedArgs) -> Result<()> {
         };
 
         match format {
+            OutputFormat::Json if args.format.is_csv() => print!("{}", output.to_csv()),
+            OutputFormat::Json if args.format.is_markdown() => print!("{}", output.to_markdown()),
             OutputFormat::Json => println!("{}", output.to_json()),
             OutputFormat::Stata => println!("{}", output.to_stata()),
             OutputFormat::Human => println!("No packages installed."),
@@ -65,93 +101,34 @@ pub fn execute(args: &OutdatedArgs) -> Result<()> {
         println!();
     }
 
-    let ssc_downloader = SscDownloader::new();
-    let github_downloader = GitHubDownloader::new();
     let mut outdated: Vec<OutdatedInfo> = Vec::new();
-    let mut checked_count = 0;
+    let checked_count = lockfile.packages.len();
     // Packages whose latest version could not be determined.
     let mut failures: Vec<String> = Vec::new();
 
-    for (name, entry) in &lockfile.packages {
-        match &entry.source {
-            PackageSource::SSC { name: pkg_name } => {
-                checked_count += 1;
-
-                // Get latest version from SSC
-                match ssc_downloader.get_manifest(pkg_name) {
-                    Ok(manifest) => {
-                        let latest_version = manifest
-                            .distribution_date
-                            .clone()
-                            .unwrap_or_else(crate::utils::date::today_yyyymmdd);
-
-                        // Compare versions (simple string comparison for dates)
-                        if latest_version != entry.version {
-                            outdated.push(OutdatedInfo {
-                                name: name.clone(),
-                                current: entry.version.clone(),
-                                latest: latest_version,
-                                source: "ssc".to_string(),
-                            });
-                        }
-                    }
-                    Err(e) => {
-                        failures.push(name.clone());
-                        if format == OutputFormat::Human {
-                            eprintln!("  x could not check {}: {}", name, e);
-                        }
-                    }
-                }
-            }
-            PackageSource::GitHub { repo, tag, .. } => {
-                checked_count += 1;
-
-                // Parse user/repo from repo string
-                if let Some(slash_pos) = repo.find('/') {
-                    let user = &repo[..slash_pos];
-                    let repo_name = &repo[slash_pos + 1..];
-
-                    match github_downloader.check_for_updates(user, repo_name, tag) {
-                        Ok(info) => {
-                            if info.has_update {
-                                if let Some(latest) = info.latest_tag {
-                                    outdated.push(OutdatedInfo {
-                                        name: name.clone(),
-                                        current: tag.clone(),
-                                        latest,
-                                        source: format!("github:{}", repo),
-                                    });
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            failures.push(name.clone());
-                            if format == OutputFormat::Human {
-                                eprintln!("  x could not check {}: {}", name, e);
-                            }
-                        }
-                    }
-                } else {
-                    failures.push(name.clone());
-                    if format == OutputFormat::Human {
-                        eprintln!(
-                            "  x could not check {}: invalid repo format '{}'",
-                            name, repo
-                        );
-                    }
-                }
-            }
-            PackageSource::Local { path } => {
-                if format == OutputFormat::Human {
+    if format == OutputFormat::Human {
+        for (name, entry) in &lockfile.packages {
+            match &entry.source {
+                PackageSource::Local { path, .. } => {
                     eprintln!("  Skipping {} (local package at {})", name, path);
                 }
-                checked_count += 1;
+                PackageSource::Net { url } => {
+                    eprintln!("  Skipping {} (net package at {})", name, url);
+                }
+                _ => {}
             }
-            PackageSource::Net { url } => {
+        }
+    }
+
+    for (name, result) in check_packages(&lockfile) {
+        match result {
+            Ok(Some(info)) => outdated.push(info),
+            Ok(None) => {}
+            Err(e) => {
                 if format == OutputFormat::Human {
-                    eprintln!("  Skipping {} (net package at {})", name, url);
+                    eprintln!("  x could not check {}: {}", name, e);
                 }
-                checked_count += 1;
+                failures.push(name);
             }
         }
     }
@@ -194,6 +171,8 @@ pub fn execute(args: &OutdatedArgs) -> Result<()> {
     };
 
     match format {
+        OutputFormat::Json if args.format.is_csv() => print!("{}", output.to_csv()),
+        OutputFormat::Json if args.format.is_markdown() => print!("{}", output.to_markdown()),
         OutputFormat::Json => println!("{}", output.to_json()),
         OutputFormat::Stata => println!("{}", output.to_stata()),
         OutputFormat::Human => {
@@ -258,6 +237,134 @@ pub fn execute(args: &OutdatedArgs) -> Result<()> {
     Ok(())
 }
 
+/// Compare one locked package with the latest version at its source.
+///
+/// `Ok(None)` when it is current or can't be compared (local and net
+/// packages, branches); `Err` says why its latest version is unknown.
+fn check_package(
+    name: &str,
+    entry: &PackageEntry,
+    ssc_downloader: &SscDownloader,
+    github_downloader: &GitHubDownloader,
+) -> std::result::Result<Option<OutdatedInfo>, String> {
+    match &entry.source {
+        PackageSource::SSC { name: pkg_name } => {
+            let latest_version = if crate::update_check::is_offline() {
+                // The latest version seen online, from the SSC index
+                ssc_index::load()
+                    .and_then(|index| index.packages.get(&pkg_name.to_lowercase()).cloned())
+                    .and_then(|entry| entry.version)
+                    .ok_or_else(|| {
+                        "STACY_OFFLINE is set and no version of it has been seen online".to_string()
+                    })?
+            } else {
+                ssc_downloader
+                    .get_manifest(pkg_name)
+                    .map_err(|e| e.to_string())?
+                    .distribution_date
+                    .unwrap_or_else(crate::utils::date::today_yyyymmdd)
+            };
+
+            // Compare versions (simple string comparison for dates)
+            Ok((latest_version != entry.version).then(|| OutdatedInfo {
+                name: name.to_string(),
+                current: entry.version.clone(),
+                latest: latest_version,
+                source: "ssc".to_string(),
+            }))
+        }
+        PackageSource::GitHub { .. } if crate::update_check::is_offline() => {
+            Err("STACY_OFFLINE is set; GitHub releases can't be checked".to_string())
+        }
+        PackageSource::GitHub { repo, tag, .. } => {
+            // Parse user/repo from repo string
+            let (user, repo_name) = repo
+                .split_once('/')
+                .ok_or_else(|| format!("invalid repo format '{}'", repo))?;
+            let info = github_downloader
+                .check_for_updates(user, repo_name, tag)
+                .map_err(|e| e.to_string())?;
+            Ok(match info.latest_tag {
+                Some(latest) if info.has_update => Some(OutdatedInfo {
+                    name: name.to_string(),
+                    current: tag.clone(),
+                    latest,
+                    source: format!("github:{}", repo),
+                }),
+                _ => None,
+            })
+        }
+        PackageSource::Local { .. } | PackageSource::Net { .. } => Ok(None),
+    }
+}
+
+/// The host a package's latest version is looked up at, if any
+fn check_host(source: &PackageSource) -> Option<&'static str> {
+    match source {
+        PackageSource::SSC { .. } => Some("fmwww.bc.edu"),
+        PackageSource::GitHub { .. } => Some("api.github.com"),
+        PackageSource::Local { .. } | PackageSource::Net { .. } => None,
+    }
+}
+
+/// Check the SSC and GitHub packages of `lockfile` on up to
+/// [`MAX_CONCURRENT_CHECKS`] threads, each host paced by a [`HostLimiter`].
+/// Results are sorted by name, the order of stacy.lock.
+fn check_packages(
+    lockfile: &Lockfile,
+) -> Vec<(String, std::result::Result<Option<OutdatedInfo>, String>)> {
+    let mut packages: Vec<(&String, &PackageEntry)> = lockfile
+        .packages
+        .iter()
+        .filter(|(_, entry)| check_host(&entry.source).is_some())
+        .collect();
+    packages.sort_by_key(|(name, _)| *name);
+    let ssc_downloader = SscDownloader::new();
+    let github_downloader = GitHubDownloader::new();
+    let limiter = HostLimiter::new(MAX_CHECKS_PER_HOST, HOST_REQUEST_SPACING);
+    let offline = crate::update_check::is_offline();
+    let next = AtomicUsize::new(0);
+    let slots: Vec<Mutex<Option<_>>> = packages.iter().map(|_| Mutex::new(None)).collect();
+
+    std::thread::scope(|scope| {
+        for _ in 0..MAX_CONCURRENT_CHECKS.min(packages.len()) {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::SeqCst);
+                let Some((name, entry)) = packages.get(i) else {
+                    break;
+                };
+                // Offline checks read the SSC index: no request to pace
+                let _permit = check_host(&entry.source)
+                    .filter(|_| !offline)
+                    .map(|host| limiter.acquire(host));
+                let result = check_package(name, entry, &ssc_downloader, &github_downloader);
+                *slots[i].lock().unwrap() = Some(result);
+            });
+        }
+    });
+
+    packages
+        .iter()
+        .zip(slots)
+        .map(|((name, _), slot)| {
+            let result = slot
+                .into_inner()
+                .unwrap()
+                .expect("every package was checked");
+            (name.to_string(), result)
+        })
+        .collect()
+}
+
+/// Names of the packages in `lockfile` with a newer version at their source,
+/// for the update check's nudge. Packages that can't be checked are left out.
+pub(crate) fn outdated_package_names(lockfile: &Lockfile) -> Vec<String> {
+    check_packages(lockfile)
+        .into_iter()
+        .filter_map(|(_, result)| result.ok().flatten().map(|info| info.name))
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
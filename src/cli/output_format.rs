@@ -1,7 +1,7 @@
 //! Output format handling for CLI commands
 //!
 //! Provides the `OutputFormat` enum and utilities for formatting command output
-//! in human-readable, JSON, or Stata-native formats.
+//! in human-readable, JSON, Stata-native, CSV or Markdown formats.
 
 use crate::executor::verbosity::Verbosity;
 use clap::ValueEnum;
@@ -30,6 +30,128 @@ impl OutputFormat {
     }
 }
 
+/// `--format` for the commands whose output is a table (`list`, `outdated`,
+/// `bench`)
+///
+/// The output formats, plus `csv`, one header line and one row per item for
+/// spreadsheets, and `markdown`, a table to paste into a README or pull
+/// request. It is not an [`OutputFormat`] because other commands have no
+/// rows to write.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum TableFormat {
+    /// Human-readable colored output (default)
+    #[default]
+    Human,
+    /// Machine-readable JSON output
+    Json,
+    /// Stata-native commands for direct execution
+    Stata,
+    /// Comma-separated values with a header line
+    Csv,
+    /// Markdown table
+    Markdown,
+}
+
+impl TableFormat {
+    /// The output format everything but the table follows. Under `csv` and
+    /// `markdown` that is `Json`, which keeps human output off stdout; the
+    /// JSON document is replaced by the table (see [`TableFormat::is_csv`]
+    /// and [`TableFormat::is_markdown`]).
+    pub fn output(self) -> OutputFormat {
+        match self {
+            TableFormat::Human => OutputFormat::Human,
+            TableFormat::Json | TableFormat::Csv | TableFormat::Markdown => OutputFormat::Json,
+            TableFormat::Stata => OutputFormat::Stata,
+        }
+    }
+
+    /// Whether the output is CSV
+    pub fn is_csv(self) -> bool {
+        self == TableFormat::Csv
+    }
+
+    /// Whether the output is a Markdown table
+    pub fn is_markdown(self) -> bool {
+        self == TableFormat::Markdown
+    }
+}
+
+/// `--format` for the commands that run Stata (`run`, `task`, `test`)
+///
+/// The output formats, plus two written while scripts run: `json-lines`,
+/// newline-delimited JSON events (see `executor::events`), and `vscode`,
+/// `file:line:` problem lines for editors (see `executor::problems`). It is
+/// not an [`OutputFormat`] because no other command has anything to stream.
+/// `csv` and `markdown` are only for `stacy test`, whose results are a table.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum RunFormat {
+    /// Human-readable colored output (default)
+    #[default]
+    Human,
+    /// Machine-readable JSON output
+    Json,
+    /// Stata-native commands for direct execution
+    Stata,
+    /// One JSON event per line, as it happens
+    #[value(name = "json-lines")]
+    JsonLines,
+    /// One `file:line: error r(N): message` line per error
+    Vscode,
+    /// Comma-separated values (`stacy test --list` only)
+    Csv,
+    /// Markdown table (`stacy test` only)
+    Markdown,
+}
+
+impl RunFormat {
+    /// The output format everything but the stream follows. Under
+    /// `json-lines` and `vscode` that is `Json`, which keeps human output off
+    /// stdout; the final JSON document is left out (see
+    /// [`RunFormat::is_streamed`]).
+    pub fn output(self) -> OutputFormat {
+        match self {
+            RunFormat::Human => OutputFormat::Human,
+            RunFormat::Json
+            | RunFormat::JsonLines
+            | RunFormat::Vscode
+            | RunFormat::Csv
+            | RunFormat::Markdown => OutputFormat::Json,
+            RunFormat::Stata => OutputFormat::Stata,
+        }
+    }
+
+    /// Whether the run is reported while it happens, with nothing at the end
+    pub fn is_streamed(self) -> bool {
+        matches!(self, RunFormat::JsonLines | RunFormat::Vscode)
+    }
+
+    /// Whether the run is reported as an event stream
+    pub fn is_events(self) -> bool {
+        self == RunFormat::JsonLines
+    }
+
+    /// Whether errors are reported as problem lines
+    pub fn is_problems(self) -> bool {
+        self == RunFormat::Vscode
+    }
+
+    /// Whether the output is CSV
+    pub fn is_csv(self) -> bool {
+        self == RunFormat::Csv
+    }
+
+    /// Whether the output is a Markdown table
+    pub fn is_markdown(self) -> bool {
+        self == RunFormat::Markdown
+    }
+
+    /// Whether the output is a table (`csv` or `markdown`), which only
+    /// `stacy test` writes
+    pub fn is_table(self) -> bool {
+        matches!(self, RunFormat::Csv | RunFormat::Markdown)
+    }
+}
+
 /// Resolve executor verbosity from CLI flags with TTY-awareness
 ///
 /// Machine-readable formats force `Quiet`: stdout is the payload channel
@@ -96,10 +218,275 @@ pub fn format_stata_local_opt(name: &str, value: Option<&str>) -> Option<String>
     value.map(|v| format_stata_local(name, v))
 }
 
+/// Quote a CSV field when it holds a comma, quote or line break (RFC 4180)
+pub fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Format a table as CSV: a header line, then one line per row
+pub fn format_csv(header: &[&str], rows: &[Vec<String>]) -> String {
+    let line = |fields: Vec<String>| fields.join(",") + "\n";
+    let mut out = line(header.iter().map(|h| csv_field(h)).collect());
+    for row in rows {
+        out.push_str(&line(row.iter().map(|v| csv_field(v)).collect()));
+    }
+    out
+}
+
+/// Format a table as Markdown (GitHub-flavored). A `|` in a cell is escaped
+/// and line breaks become spaces, so each row stays on one line.
+pub fn format_markdown_table(header: &[&str], rows: &[Vec<String>]) -> String {
+    fn cell(value: &str) -> String {
+        value.replace('|', "\\|").replace(['\n', '\r'], " ")
+    }
+    let line = |cells: Vec<String>| format!("| {} |\n", cells.join(" | "));
+
+    let mut out = line(header.iter().map(|h| cell(h)).collect());
+    out.push_str(&line(header.iter().map(|_| "---".to_string()).collect()));
+    for row in rows {
+        out.push_str(&line(row.iter().map(|v| cell(v)).collect()));
+    }
+    out
+}
+
+/// One column of a list output, for [`format_stata_frame`]
+pub enum StataColumn<'a> {
+    Str(&'a str, Vec<String>),
+    Num(&'a str, Vec<f64>),
+}
+
+impl StataColumn<'_> {
+    fn name(&self) -> &str {
+        match self {
+            StataColumn::Str(name, _) | StataColumn::Num(name, _) => name,
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            StataColumn::Str(_, values) => values.len(),
+            StataColumn::Num(_, values) => values.len(),
+        }
+    }
+
+    /// Storage type: `double`, or a `str#` as wide as the longest value
+    fn storage_type(&self) -> String {
+        match self {
+            StataColumn::Str(_, values) => {
+                let width = values.iter().map(|v| v.len()).max().unwrap_or(1);
+                format!("str{}", width.clamp(1, STATA_MAX_STR))
+            }
+            StataColumn::Num(..) => "double".to_string(),
+        }
+    }
+
+    /// Value in row `row`, as an expression for `frame post`
+    fn cell(&self, row: usize) -> String {
+        match self {
+            StataColumn::Str(_, values) => {
+                let value: String = values[row].chars().take(STATA_MAX_STR).collect();
+                format!("({})", compound_quote(&value))
+            }
+            StataColumn::Num(_, values) => format!("({})", values[row]),
+        }
+    }
+}
+
+/// A Stata string literal holding `s` unchanged: compound quotes
+/// (`` `"..."' ``) keep embedded double quotes, and `$` and `` ` `` are
+/// escaped so nothing in the value is taken for a macro. Line breaks become
+/// spaces — a raw newline would end the command.
+fn compound_quote(s: &str) -> String {
+    let escaped = s
+        .replace('$', "\\$")
+        .replace('`', "\\`")
+        .replace(['\n', '\r'], " ");
+    format!("`\"{}\"'", escaped)
+}
+
+/// Widest `str#` variable
+const STATA_MAX_STR: usize = 2045;
+
+/// Format a list as a Stata frame `stacy_<name>` with one observation per item
+///
+/// Unlike comma-joined globals, a frame keeps values that contain commas
+/// intact. Frames need Stata 16; older versions skip the block.
+pub fn format_stata_frame(name: &str, columns: &[StataColumn]) -> String {
+    let frame = format!("stacy_{}", name);
+    let rows = columns.first().map_or(0, |c| c.len());
+    let variables: Vec<String> = columns
+        .iter()
+        .map(|c| format!("{} {}", c.storage_type(), c.name()))
+        .collect();
+
+    let mut lines = vec![
+        "if c(stata_version) >= 16 {".to_string(),
+        format!("    capture frame drop {}", frame),
+        format!("    frame create {} {}", frame, variables.join(" ")),
+    ];
+    for row in 0..rows {
+        let cells: Vec<String> = columns.iter().map(|c| c.cell(row)).collect();
+        lines.push(format!("    frame post {} {}", frame, cells.join(" ")));
+    }
+    lines.push("}".to_string());
+    lines.join("\n")
+}
+
+/// Format numeric columns of a list as a Stata matrix `stacy_<name>`, one row
+/// per item, with the items' names as row names. `None` for an empty list:
+/// Stata has no empty matrices.
+pub fn format_stata_matrix(
+    name: &str,
+    row_names: &[&str],
+    columns: &[(&str, Vec<f64>)],
+) -> Option<String> {
+    if row_names.is_empty() || columns.is_empty() {
+        return None;
+    }
+    let matrix = format!("stacy_{}", name);
+    let rows: Vec<String> = (0..row_names.len())
+        .map(|row| {
+            columns
+                .iter()
+                .map(|(_, values)| values[row].to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+        .collect();
+    let col_names: Vec<&str> = columns.iter().map(|(name, _)| *name).collect();
+    let row_names: Vec<String> = row_names.iter().map(|n| stata_name(n)).collect();
+    Some(
+        [
+            format!("matrix {} = ({})", matrix, rows.join(" \\ ")),
+            format!("matrix colnames {} = {}", matrix, col_names.join(" ")),
+            format!("matrix rownames {} = {}", matrix, row_names.join(" ")),
+        ]
+        .join("\n"),
+    )
+}
+
+/// A valid Stata name for `s`: letters, digits and underscores, not starting
+/// with a digit, at most 32 characters
+fn stata_name(s: &str) -> String {
+    let mut name: String = s
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        name.insert(0, '_');
+    }
+    name.truncate(32);
+    name
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_run_format_json_lines_follows_json() {
+        assert_eq!(RunFormat::JsonLines.output(), OutputFormat::Json);
+        assert!(RunFormat::JsonLines.is_events());
+        assert!(!RunFormat::Json.is_events());
+        assert_eq!(RunFormat::Human.output(), OutputFormat::Human);
+    }
+
+    #[test]
+    fn test_run_format_vscode_is_streamed_without_events() {
+        assert_eq!(RunFormat::Vscode.output(), OutputFormat::Json);
+        assert!(RunFormat::Vscode.is_streamed());
+        assert!(RunFormat::Vscode.is_problems());
+        assert!(!RunFormat::Vscode.is_events());
+        assert!(!RunFormat::Json.is_streamed());
+    }
+
+    #[test]
+    fn test_format_csv() {
+        let csv = format_csv(
+            &["name", "source"],
+            &[
+                vec!["estout".into(), "ssc".into()],
+                vec!["my, pkg".into(), "net:\"x\"".into()],
+            ],
+        );
+        assert_eq!(
+            csv,
+            "name,source\nestout,ssc\n\"my, pkg\",\"net:\"\"x\"\"\"\n"
+        );
+        assert_eq!(csv_field("a\nb"), "\"a\nb\"");
+    }
+
+    #[test]
+    fn test_format_markdown_table() {
+        let table = format_markdown_table(
+            &["Package", "Source"],
+            &[vec!["a|b".into(), "line\nbreak".into()]],
+        );
+        assert_eq!(
+            table,
+            "| Package | Source |\n| --- | --- |\n| a\\|b | line break |\n"
+        );
+    }
+
+    #[test]
+    fn test_format_stata_frame() {
+        let frame = format_stata_frame(
+            "packages",
+            &[
+                StataColumn::Str("name", vec!["estout".into(), "a,b".into()]),
+                StataColumn::Num("size", vec![1.5, 2.0]),
+            ],
+        );
+        assert_eq!(
+            frame,
+            "if c(stata_version) >= 16 {\n\
+             \x20   capture frame drop stacy_packages\n\
+             \x20   frame create stacy_packages str6 name double size\n\
+             \x20   frame post stacy_packages (`\"estout\"') (1.5)\n\
+             \x20   frame post stacy_packages (`\"a,b\"') (2)\n\
+             }"
+        );
+
+        // Quotes survive; macro characters are escaped, not expanded
+        let quoted = format_stata_frame(
+            "tests",
+            &[StataColumn::Str(
+                "name",
+                vec![r#"the "main" spec, $x `y'"#.into()],
+            )],
+        );
+        assert!(quoted.contains(r#"frame post stacy_tests (`"the "main" spec, \$x \`y'"')"#));
+
+        // An empty list still creates the frame, with its variables
+        let empty = format_stata_frame("packages", &[StataColumn::Str("name", vec![])]);
+        assert!(empty.contains("frame create stacy_packages str1 name"));
+        assert!(!empty.contains("frame post"));
+    }
+
+    #[test]
+    fn test_format_stata_matrix() {
+        let matrix = format_stata_matrix(
+            "tests",
+            &["test_a", "2nd check"],
+            &[
+                ("passed", vec![1.0, 0.0]),
+                ("duration_secs", vec![0.5, 1.25]),
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            matrix,
+            "matrix stacy_tests = (1, 0.5 \\ 0, 1.25)\n\
+             matrix colnames stacy_tests = passed duration_secs\n\
+             matrix rownames stacy_tests = test_a _2nd_check"
+        );
+        assert!(format_stata_matrix("tests", &[], &[("passed", vec![])]).is_none());
+    }
+
     #[test]
     fn test_escape_stata_string_simple() {
         assert_eq!(escape_stata_string("hello world"), "hello world");
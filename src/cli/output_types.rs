@@ -8,10 +8,14 @@
 #![allow(clippy::vec_init_then_push)]
 
 use crate::cli::output_format::{
-    format_stata_local, format_stata_scalar_bool, format_stata_scalar_float,
-    format_stata_scalar_int, format_stata_scalar_usize,
+    format_csv, format_markdown_table, format_stata_frame, format_stata_local, format_stata_matrix,
+    format_stata_scalar_bool, format_stata_scalar_float, format_stata_scalar_int,
+    format_stata_scalar_usize, StataColumn,
 };
+use crate::history::RunRecord;
+use crate::project::validate::Diagnostic;
 use serde::Serialize;
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
 /// Trait for command outputs that can be serialized to JSON or Stata format
@@ -28,6 +32,19 @@ pub trait CommandOutput: Serialize {
     fn to_stata(&self) -> String;
 }
 
+/// Command outputs that are a table, for `--format csv`
+pub trait CsvOutput {
+    /// Serialize to CSV: a header line and one row per item
+    fn to_csv(&self) -> String;
+}
+
+/// Command outputs that are a table, for `--format markdown`
+pub trait MarkdownOutput {
+    /// Serialize to a Markdown table, for pasting into a README or pull
+    /// request
+    fn to_markdown(&self) -> String;
+}
+
 // =============================================================================
 // RunOutput
 // =============================================================================
@@ -129,6 +146,53 @@ impl CommandOutput for ParallelRunOutput {
     }
 }
 
+// =============================================================================
+// ReproducibilityCheckOutput
+// =============================================================================
+
+/// Output for `stacy run --check-reproducible`
+#[derive(Debug, Serialize)]
+pub struct ReproducibilityCheckOutput {
+    pub script: PathBuf,
+    /// Whether every compared output was identical across runs
+    pub reproducible: bool,
+    /// How many times the script ran
+    pub runs: usize,
+    /// Outputs compared (declared, or every file a run wrote)
+    pub outputs: Vec<PathBuf>,
+    pub differences: Vec<crate::reproducible::OutputDiff>,
+    /// Likely causes of the differences
+    pub sources: Vec<crate::reproducible::Source>,
+}
+
+impl CommandOutput for ReproducibilityCheckOutput {
+    fn command_name(&self) -> &'static str {
+        "run-check-reproducible"
+    }
+
+    fn to_stata(&self) -> String {
+        let mut lines = Vec::new();
+        lines.push("* stacy run --check-reproducible output".to_string());
+        lines.push(format_stata_scalar_bool("reproducible", self.reproducible));
+        lines.push(format_stata_scalar_usize("runs", self.runs));
+        lines.push(format_stata_scalar_usize(
+            "outputs_compared",
+            self.outputs.len(),
+        ));
+        lines.push(format_stata_scalar_usize(
+            "outputs_differ",
+            self.differences.len(),
+        ));
+        let differing: Vec<String> = self
+            .differences
+            .iter()
+            .map(|d| d.path.display().to_string())
+            .collect();
+        lines.push(format_stata_local("differing", &differing.join(",")));
+        lines.join("\n")
+    }
+}
+
 // =============================================================================
 // BenchOutput
 // =============================================================================
@@ -154,6 +218,56 @@ pub struct BenchOutput {
     pub stddev_secs: f64,
     /// Whether all runs succeeded
     pub success: bool,
+    /// Each engine's statistics when `--engine` was given more than once; the
+    /// figures above are then the first engine's
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub engines: Option<Vec<EngineBenchOutput>>,
+    /// Statistics for each set of argument values when an `--arg` key was
+    /// given more than once; the figures above are then the first set's
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sweep: Option<Vec<SweepPointOutput>>,
+}
+
+/// Statistics of one engine in `stacy bench --engine A --engine B`
+#[derive(Debug, Serialize)]
+pub struct EngineBenchOutput {
+    /// Engine as given to `--engine`
+    pub engine: String,
+    /// Mean execution time in seconds
+    pub mean_secs: f64,
+    /// Median execution time in seconds
+    pub median_secs: f64,
+    /// Minimum execution time in seconds
+    pub min_secs: f64,
+    /// Maximum execution time in seconds
+    pub max_secs: f64,
+    /// Standard deviation in seconds
+    pub stddev_secs: f64,
+    /// Mean time of the first engine divided by this one's
+    pub speedup: f64,
+}
+
+/// Statistics of one set of argument values in `stacy bench --arg k=a --arg k=b`
+#[derive(Debug, Serialize)]
+pub struct SweepPointOutput {
+    /// Engine, when `--engine` was given more than once
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub engine: Option<String>,
+    /// Argument values passed to the script
+    pub args: BTreeMap<String, String>,
+    /// Mean execution time in seconds
+    pub mean_secs: f64,
+    /// Median execution time in seconds
+    pub median_secs: f64,
+    /// Minimum execution time in seconds
+    pub min_secs: f64,
+    /// Maximum execution time in seconds
+    pub max_secs: f64,
+    /// Standard deviation in seconds
+    pub stddev_secs: f64,
+    /// Mean time divided by that of the first argument values on the same
+    /// engine
+    pub relative: f64,
 }
 
 impl CommandOutput for BenchOutput {
@@ -179,6 +293,36 @@ impl CommandOutput for BenchOutput {
         lines.push(format_stata_scalar_float("max_secs", self.max_secs));
         lines.push(format_stata_scalar_float("stddev_secs", self.stddev_secs));
         lines.push(format_stata_scalar_bool("success", self.success));
+        if let Some(engines) = &self.engines {
+            let names: Vec<&str> = engines.iter().map(|e| e.engine.as_str()).collect();
+            lines.push(format_stata_local("engines", &names.join(",")));
+            let means: Vec<String> = engines
+                .iter()
+                .map(|e| format!("{:.6}", e.mean_secs))
+                .collect();
+            lines.push(format_stata_local("engine_means", &means.join(",")));
+            let speedups: Vec<String> = engines
+                .iter()
+                .map(|e| format!("{:.6}", e.speedup))
+                .collect();
+            lines.push(format_stata_local("engine_speedups", &speedups.join(",")));
+        }
+        if let Some(sweep) = &self.sweep {
+            let points: Vec<String> = sweep
+                .iter()
+                .map(|p| {
+                    let args: Vec<String> =
+                        p.args.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+                    args.join(" ")
+                })
+                .collect();
+            lines.push(format_stata_local("sweep_args", &points.join(",")));
+            let means: Vec<String> = sweep
+                .iter()
+                .map(|p| format!("{:.6}", p.mean_secs))
+                .collect();
+            lines.push(format_stata_local("sweep_means", &means.join(",")));
+        }
         lines.join("\n")
     }
 }
@@ -219,6 +363,288 @@ impl CommandOutput for CacheCleanOutput {
     }
 }
 
+// =============================================================================
+// OutputsListOutput / OutputsCleanOutput
+// =============================================================================
+
+/// Output for `stacy outputs list` command
+#[derive(Debug, Serialize)]
+pub struct OutputsListOutput {
+    /// Number of registered artifacts listed
+    pub artifact_count: usize,
+    /// Artifacts by path
+    pub artifacts: Vec<ArtifactInfo>,
+}
+
+/// A registered artifact
+#[derive(Debug, Serialize)]
+pub struct ArtifactInfo {
+    /// Path relative to the project root
+    pub path: PathBuf,
+    /// Task or stage that produced the file
+    pub producer: String,
+    /// 'task' or 'stage'
+    pub kind: String,
+    /// When the producer finished (UTC, `YYYY-MM-DD HH:MM:SS`)
+    pub produced_at: String,
+    /// SHA-256 recorded when the file was produced
+    pub sha256: String,
+    /// 'ok', 'modified' (changed since) or 'missing'
+    pub status: String,
+}
+
+impl CommandOutput for OutputsListOutput {
+    fn command_name(&self) -> &'static str {
+        "outputs-list"
+    }
+
+    fn to_stata(&self) -> String {
+        let mut lines = Vec::new();
+        lines.push("* stacy outputs list output".to_string());
+        lines.push(format_stata_scalar_usize(
+            "artifact_count",
+            self.artifact_count,
+        ));
+        let paths: Vec<String> = self
+            .artifacts
+            .iter()
+            .map(|a| a.path.display().to_string())
+            .collect();
+        lines.push(format_stata_local("artifacts", &paths.join(" ")));
+        lines.join("\n")
+    }
+}
+
+/// Output for `stacy outputs clean` command
+#[derive(Debug, Serialize)]
+pub struct OutputsCleanOutput {
+    /// Number of files deleted
+    pub removed_count: usize,
+    /// Files deleted, relative to the project root
+    pub removed: Vec<PathBuf>,
+}
+
+impl CommandOutput for OutputsCleanOutput {
+    fn command_name(&self) -> &'static str {
+        "outputs-clean"
+    }
+
+    fn to_stata(&self) -> String {
+        let mut lines = Vec::new();
+        lines.push("* stacy outputs clean output".to_string());
+        lines.push(format_stata_scalar_usize(
+            "removed_count",
+            self.removed_count,
+        ));
+        lines.join("\n")
+    }
+}
+
+// =============================================================================
+// DataFetchOutput
+// =============================================================================
+
+/// Output for `stacy data fetch` command
+#[derive(Debug, Serialize)]
+pub struct DataFetchOutput {
+    /// Number of data files fetched or checked
+    pub file_count: usize,
+    /// Number downloaded
+    pub downloaded_count: usize,
+    /// Number restored from the data cache
+    pub cached_count: usize,
+    /// Number already present
+    pub present_count: usize,
+    /// Data files by name
+    pub files: Vec<DataFileInfo>,
+}
+
+/// A fetched data file
+#[derive(Debug, Serialize)]
+pub struct DataFileInfo {
+    /// Name in `[data]`
+    pub name: String,
+    /// Path relative to the project root
+    pub path: PathBuf,
+    /// 'downloaded', 'cached' or 'present'
+    pub status: String,
+    /// SHA-256 of the file in place
+    pub sha256: String,
+    /// Whether stacy.toml declares the checksum
+    pub pinned: bool,
+}
+
+impl CommandOutput for DataFetchOutput {
+    fn command_name(&self) -> &'static str {
+        "data-fetch"
+    }
+
+    fn to_stata(&self) -> String {
+        let mut lines = Vec::new();
+        lines.push("* stacy data fetch output".to_string());
+        lines.push(format_stata_scalar_usize("file_count", self.file_count));
+        lines.push(format_stata_scalar_usize(
+            "downloaded_count",
+            self.downloaded_count,
+        ));
+        lines.push(format_stata_scalar_usize("cached_count", self.cached_count));
+        lines.push(format_stata_scalar_usize(
+            "present_count",
+            self.present_count,
+        ));
+        let names: Vec<&str> = self.files.iter().map(|f| f.name.as_str()).collect();
+        lines.push(format_stata_local("files", &names.join(" ")));
+        lines.join("\n")
+    }
+}
+
+// =============================================================================
+// SubmitOutput / JobsOutput
+// =============================================================================
+
+/// Output for `stacy submit` command
+#[derive(Debug, Serialize)]
+pub struct SubmitOutput {
+    /// Job ID the scheduler gave the job (empty with --dry-run)
+    pub job_id: String,
+    /// 'slurm' or 'pbs'
+    pub scheduler: String,
+    /// The stacy command the job runs
+    pub command: String,
+    /// Batch script, relative to the project root
+    pub script: PathBuf,
+    /// File the job's output goes to, relative to the project root
+    pub output: PathBuf,
+    /// Whether the script was only printed
+    pub dry_run: bool,
+}
+
+impl CommandOutput for SubmitOutput {
+    fn command_name(&self) -> &'static str {
+        "submit"
+    }
+
+    fn to_stata(&self) -> String {
+        let mut lines = Vec::new();
+        lines.push("* stacy submit output".to_string());
+        lines.push(format_stata_local("job_id", &self.job_id));
+        lines.push(format_stata_local("scheduler", &self.scheduler));
+        lines.push(format_stata_local(
+            "output",
+            &self.output.display().to_string(),
+        ));
+        lines.push(format_stata_scalar_bool("dry_run", self.dry_run));
+        lines.join("\n")
+    }
+}
+
+/// Output for `stacy jobs` command
+#[derive(Debug, Serialize)]
+pub struct JobsOutput {
+    /// Number of jobs listed
+    pub job_count: usize,
+    /// Jobs, newest first
+    pub jobs: Vec<JobInfo>,
+}
+
+/// A submitted job and how it is doing
+#[derive(Debug, Serialize)]
+pub struct JobInfo {
+    /// Job ID the scheduler gave it
+    pub id: String,
+    /// 'slurm' or 'pbs'
+    pub scheduler: String,
+    /// The stacy command the job runs
+    pub command: String,
+    /// When it was submitted (UTC, `YYYY-MM-DD HH:MM:SS`)
+    pub submitted_at: String,
+    /// 'pending', 'running', 'completed', 'failed', 'cancelled' or 'unknown'
+    pub state: String,
+    /// The scheduler's own word for the state (`TIMEOUT`, `exit status 1`)
+    pub detail: String,
+    /// File the job's output goes to, relative to the project root
+    pub output: PathBuf,
+}
+
+impl CommandOutput for JobsOutput {
+    fn command_name(&self) -> &'static str {
+        "jobs"
+    }
+
+    fn to_stata(&self) -> String {
+        let mut lines = Vec::new();
+        lines.push("* stacy jobs output".to_string());
+        lines.push(format_stata_scalar_usize("job_count", self.job_count));
+        let ids: Vec<&str> = self.jobs.iter().map(|j| j.id.as_str()).collect();
+        lines.push(format_stata_local("jobs", &ids.join(" ")));
+        let states: Vec<&str> = self.jobs.iter().map(|j| j.state.as_str()).collect();
+        lines.push(format_stata_local("states", &states.join(" ")));
+        lines.join("\n")
+    }
+}
+
+// =============================================================================
+// HistoryOutput
+// =============================================================================
+
+/// Output for `stacy history` and `stacy history show`
+#[derive(Debug, Serialize)]
+pub struct HistoryOutput {
+    /// Number of runs listed
+    pub run_count: usize,
+    /// Runs, newest first
+    pub runs: Vec<RunRecord>,
+}
+
+impl CommandOutput for HistoryOutput {
+    fn command_name(&self) -> &'static str {
+        "history"
+    }
+
+    fn to_stata(&self) -> String {
+        let mut lines = Vec::new();
+        lines.push("* stacy history output".to_string());
+        lines.push(format_stata_scalar_usize("run_count", self.run_count));
+        let ids: Vec<String> = self.runs.iter().map(|r| r.id.to_string()).collect();
+        lines.push(format_stata_local("runs", &ids.join(" ")));
+        let codes: Vec<String> = self.runs.iter().map(|r| r.exit_code.to_string()).collect();
+        lines.push(format_stata_local("exit_codes", &codes.join(" ")));
+        lines.join("\n")
+    }
+}
+
+// =============================================================================
+// ConfigValidateOutput
+// =============================================================================
+
+/// Output for `stacy config validate`
+#[derive(Debug, Serialize)]
+pub struct ConfigValidateOutput {
+    /// The stacy.toml checked
+    pub path: PathBuf,
+    /// Whether it has no errors
+    pub valid: bool,
+    /// Number of errors found
+    pub error_count: usize,
+    /// Errors, in the order found
+    pub errors: Vec<Diagnostic>,
+}
+
+impl CommandOutput for ConfigValidateOutput {
+    fn command_name(&self) -> &'static str {
+        "config validate"
+    }
+
+    fn to_stata(&self) -> String {
+        let mut lines = Vec::new();
+        lines.push("* stacy config validate output".to_string());
+        lines.push(format_stata_local("path", &self.path.display().to_string()));
+        lines.push(format_stata_scalar_bool("valid", self.valid));
+        lines.push(format_stata_scalar_usize("error_count", self.error_count));
+        lines.join("\n")
+    }
+}
+
 // =============================================================================
 // CacheInfoOutput
 // =============================================================================
@@ -273,6 +699,48 @@ impl CommandOutput for CacheInfoOutput {
     }
 }
 
+// =============================================================================
+// CacheVerifyOutput
+// =============================================================================
+
+/// Output for `stacy cache verify` command
+#[derive(Debug, Serialize)]
+pub struct CacheVerifyOutput {
+    /// Number of entries checked
+    pub entries_checked: usize,
+    /// Problems found
+    pub problems: Vec<crate::cache::verify::CacheProblem>,
+    /// Number of entries dropped by `--repair`
+    pub entries_removed: usize,
+    /// 'healthy', 'repaired' or 'problems'
+    pub status: String,
+}
+
+impl CommandOutput for CacheVerifyOutput {
+    fn command_name(&self) -> &'static str {
+        "cache-verify"
+    }
+
+    fn to_stata(&self) -> String {
+        let mut lines = Vec::new();
+        lines.push("* stacy cache verify output".to_string());
+        lines.push(format_stata_local("status", &self.status));
+        lines.push(format_stata_scalar_usize(
+            "entries_checked",
+            self.entries_checked,
+        ));
+        lines.push(format_stata_scalar_usize(
+            "problem_count",
+            self.problems.len(),
+        ));
+        lines.push(format_stata_scalar_usize(
+            "entries_removed",
+            self.entries_removed,
+        ));
+        lines.join("\n")
+    }
+}
+
 // =============================================================================
 // CacheHitOutput
 // =============================================================================
@@ -374,6 +842,8 @@ pub struct DoctorOutput {
     pub check_count: usize,
     /// Number of failed checks
     pub failed: i32,
+    /// Number of fixes applied by --fix
+    pub fixed_count: usize,
     /// Number of checks passed
     pub passed: i32,
     /// System is ready to use (1=yes, 0=no)
@@ -395,6 +865,7 @@ impl CommandOutput for DoctorOutput {
         lines.push(format_stata_scalar_int("warnings", self.warnings as i64));
         lines.push(format_stata_scalar_int("failed", self.failed as i64));
         lines.push(format_stata_scalar_usize("check_count", self.check_count));
+        lines.push(format_stata_scalar_usize("fixed_count", self.fixed_count));
         lines.join("\n")
     }
 }
@@ -503,6 +974,35 @@ pub struct ExplainOutput {
     pub description: String,
     /// Link to Stata documentation
     pub url: String,
+    /// Exit code of each kind of failure (`--exit-codes`)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub exit_codes: Vec<ExitCodeOutput>,
+}
+
+/// A kind of failure and the exit code it is reported with
+#[derive(Debug, Serialize)]
+pub struct ExitCodeOutput {
+    pub kind: String,
+    /// Code in effect, after any `[exit_codes]` remapping
+    pub code: i32,
+    pub standard: i32,
+    pub description: String,
+}
+
+/// The exit codes as a Stata matrix `stacy_exit_codes`, one row per kind
+pub fn exit_codes_stata_matrix(exit_codes: &[ExitCodeOutput]) -> Option<String> {
+    let kinds: Vec<&str> = exit_codes.iter().map(|e| e.kind.as_str()).collect();
+    format_stata_matrix(
+        "exit_codes",
+        &kinds,
+        &[
+            ("code", exit_codes.iter().map(|e| e.code as f64).collect()),
+            (
+                "standard",
+                exit_codes.iter().map(|e| e.standard as f64).collect(),
+            ),
+        ],
+    )
 }
 
 impl CommandOutput for ExplainOutput {
@@ -524,6 +1024,7 @@ impl CommandOutput for ExplainOutput {
         };
         lines.push(format_stata_local("description", &desc.replace('\n', " ")));
         lines.push(format_stata_local("url", &self.url));
+        lines.extend(exit_codes_stata_matrix(&self.exit_codes));
         lines.join("\n")
     }
 }
@@ -630,6 +1131,8 @@ impl CommandOutput for InstallOutput {
 pub struct AddOutput {
     /// Number of packages added
     pub added: i32,
+    /// Whether this was a dry run (`--dry-run`): nothing was written
+    pub dry_run: bool,
     /// Dependency group: "production", "dev", or "test"
     pub group: String,
     /// Number of packages that failed
@@ -656,6 +1159,7 @@ impl CommandOutput for AddOutput {
         lines.push(format_stata_scalar_int("failed", self.failed as i64));
         lines.push(format_stata_scalar_int("total", self.total as i64));
         lines.push(format_stata_local("group", &self.group));
+        lines.push(format_stata_scalar_bool("dry_run", self.dry_run));
         lines.join("\n")
     }
 }
@@ -792,6 +1296,118 @@ impl CommandOutput for DepsOutput {
     }
 }
 
+/// Output for `stacy deps --all`
+#[derive(Debug, Serialize)]
+pub struct DepsGraphOutput {
+    /// Project root the scripts were found under
+    pub root: PathBuf,
+    /// Number of .do files in the project
+    pub script_count: usize,
+    /// Number of scripts nothing else calls that start a run
+    pub entry_count: usize,
+    /// Number of scripts nothing calls or runs
+    pub orphan_count: usize,
+    /// Number of scripts called from more than one script
+    pub shared_count: usize,
+    /// Circular deps found (1=yes, 0=no)
+    pub has_circular: bool,
+    /// Missing files found (1=yes, 0=no)
+    pub has_missing: bool,
+    /// Number of scripts in a cycle
+    pub circular_count: usize,
+    /// Number of calls to missing files
+    pub missing_count: usize,
+    /// 'success' or 'error'
+    pub status: String,
+}
+
+impl CommandOutput for DepsGraphOutput {
+    fn command_name(&self) -> &'static str {
+        "deps"
+    }
+
+    fn to_stata(&self) -> String {
+        let mut lines = Vec::new();
+        lines.push("* stacy deps --all output".to_string());
+        lines.push(format_stata_local("status", &self.status));
+        lines.push(format_stata_local("root", &self.root.display().to_string()));
+        lines.push(format_stata_scalar_usize("script_count", self.script_count));
+        lines.push(format_stata_scalar_usize("entry_count", self.entry_count));
+        lines.push(format_stata_scalar_usize("orphan_count", self.orphan_count));
+        lines.push(format_stata_scalar_usize("shared_count", self.shared_count));
+        lines.push(format_stata_scalar_bool("has_circular", self.has_circular));
+        lines.push(format_stata_scalar_bool("has_missing", self.has_missing));
+        lines.push(format_stata_scalar_usize(
+            "circular_count",
+            self.circular_count,
+        ));
+        lines.push(format_stata_scalar_usize(
+            "missing_count",
+            self.missing_count,
+        ));
+        lines.join("\n")
+    }
+}
+
+// =============================================================================
+// ErrorOutput
+// =============================================================================
+
+/// Output for any command that fails with an error, under `--format json`
+#[derive(Debug, Serialize)]
+pub struct ErrorOutput {
+    /// Always false
+    pub success: bool,
+    pub error: ErrorInfo,
+}
+
+/// What went wrong
+#[derive(Debug, Serialize)]
+pub struct ErrorInfo {
+    /// Kind of error (`config`, `network`, `project_not_found`, ...)
+    pub kind: String,
+    /// Error message
+    pub message: String,
+    /// What to try next, when there is a usual remedy
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+    /// Exit code stacy exits with
+    pub exit_code: i32,
+}
+
+impl ErrorOutput {
+    pub fn new(error: &crate::error::Error, exit_code: i32) -> Self {
+        Self {
+            success: false,
+            error: ErrorInfo {
+                kind: error.category().to_string(),
+                message: error.to_string(),
+                hint: error.hint().map(str::to_string),
+                exit_code,
+            },
+        }
+    }
+}
+
+impl CommandOutput for ErrorOutput {
+    fn command_name(&self) -> &'static str {
+        "error"
+    }
+
+    fn to_stata(&self) -> String {
+        let mut lines = Vec::new();
+        lines.push("* stacy error".to_string());
+        lines.push(format_stata_scalar_bool("success", false));
+        lines.push(format_stata_scalar_int(
+            "exit_code",
+            self.error.exit_code as i64,
+        ));
+        lines.push(format_stata_local("error_kind", &self.error.kind));
+        lines.push(format_stata_local("error", &self.error.message));
+        lines.join("\n")
+    }
+}
+
 // =============================================================================
 // TaskOutput
 // =============================================================================
@@ -838,6 +1454,39 @@ impl CommandOutput for TaskOutput {
             self.success_count,
         ));
         lines.push(format_stata_scalar_usize("failed_count", self.failed_count));
+
+        let names: Vec<_> = self.scripts.iter().map(|s| s.name.as_str()).collect();
+        let success: Vec<_> = self.scripts.iter().map(|s| flag(s.success)).collect();
+        let exit_codes: Vec<_> = self.scripts.iter().map(|s| s.exit_code as f64).collect();
+        let durations: Vec<_> = self.scripts.iter().map(|s| s.duration_secs).collect();
+        let cached: Vec<_> = self.scripts.iter().map(|s| flag(s.cached)).collect();
+        lines.push(format_stata_frame(
+            "scripts",
+            &[
+                StataColumn::Str("name", strings(&names)),
+                StataColumn::Str(
+                    "script",
+                    self.scripts
+                        .iter()
+                        .map(|s| s.script.display().to_string())
+                        .collect(),
+                ),
+                StataColumn::Num("success", success.clone()),
+                StataColumn::Num("exit_code", exit_codes.clone()),
+                StataColumn::Num("duration_secs", durations.clone()),
+                StataColumn::Num("cached", cached.clone()),
+            ],
+        ));
+        lines.extend(format_stata_matrix(
+            "scripts",
+            &names,
+            &[
+                ("success", success),
+                ("exit_code", exit_codes),
+                ("duration_secs", durations),
+                ("cached", cached),
+            ],
+        ));
         lines.join("\n")
     }
 }
@@ -853,7 +1502,86 @@ pub struct ScriptResultOutput {
     pub success: bool,
     /// Exit code
     pub exit_code: i32,
-    /// Execution time in seconds
+    /// Execution time in seconds (of the cached run, for a cache hit)
+    pub duration_secs: f64,
+    /// Whether the result came from the build cache
+    pub cached: bool,
+    /// Path to the kept log; absent when the log was removed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log_file: Option<PathBuf>,
+}
+
+// =============================================================================
+// BuildOutput
+// =============================================================================
+
+/// Output for `stacy build` command
+#[derive(Debug, Serialize)]
+pub struct BuildOutput {
+    /// Whether every stage that ran succeeded
+    pub success: bool,
+    /// Exit code (0=success)
+    pub exit_code: i32,
+    /// Total build time in seconds
+    pub duration_secs: f64,
+    /// Whether stages were only checked, not run (`--dry-run`)
+    pub dry_run: bool,
+    /// Number of stages considered
+    pub stage_count: usize,
+    /// Number of stages that ran (or, with `--dry-run`, would run)
+    pub ran_count: usize,
+    /// Number of stages that were up to date
+    pub fresh_count: usize,
+    /// Number of stages that failed
+    pub failed_count: usize,
+    /// Number of stages skipped because an upstream stage failed
+    pub skipped_count: usize,
+    /// Per-stage results in execution order
+    pub stages: Vec<StageOutput>,
+}
+
+impl CommandOutput for BuildOutput {
+    fn command_name(&self) -> &'static str {
+        "build"
+    }
+
+    fn to_stata(&self) -> String {
+        let mut lines = Vec::new();
+        lines.push("* stacy build output".to_string());
+        lines.push(format_stata_scalar_bool("success", self.success));
+        lines.push(format_stata_scalar_int("exit_code", self.exit_code as i64));
+        lines.push(format_stata_scalar_float(
+            "duration_secs",
+            self.duration_secs,
+        ));
+        lines.push(format_stata_scalar_bool("dry_run", self.dry_run));
+        lines.push(format_stata_scalar_usize("stage_count", self.stage_count));
+        lines.push(format_stata_scalar_usize("ran_count", self.ran_count));
+        lines.push(format_stata_scalar_usize("fresh_count", self.fresh_count));
+        lines.push(format_stata_scalar_usize("failed_count", self.failed_count));
+        lines.push(format_stata_scalar_usize(
+            "skipped_count",
+            self.skipped_count,
+        ));
+        lines.join("\n")
+    }
+}
+
+/// Individual stage result within a build
+#[derive(Debug, Serialize)]
+pub struct StageOutput {
+    /// Stage name
+    pub name: String,
+    /// Path to the stage's script
+    pub script: PathBuf,
+    /// fresh, stale, ran, failed or skipped
+    pub status: crate::pipeline::executor::StageStatus,
+    /// Why the stage ran, failed or was skipped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Exit code
+    pub exit_code: i32,
+    /// Execution time in seconds (0 unless the stage ran)
     pub duration_secs: f64,
 }
 
@@ -921,6 +1649,10 @@ pub struct ListPackageInfo {
     pub source: String,
     /// Dependency group (production, dev, test)
     pub group: String,
+    /// Whether the source comes from `[overrides]` in stacy.toml
+    pub overridden: bool,
+    /// Whether the package is a linked local directory (`dev = true`)
+    pub editable: bool,
 }
 
 impl CommandOutput for ListOutput {
@@ -945,10 +1677,56 @@ impl CommandOutput for ListOutput {
         lines.push(format_stata_local("package_versions", &versions.join(",")));
         lines.push(format_stata_local("package_sources", &sources.join(",")));
         lines.push(format_stata_local("package_groups", &groups.join(",")));
+        // The lists above break on values containing commas; the frame doesn't
+        lines.push(format_stata_frame(
+            "packages",
+            &[
+                StataColumn::Str("name", strings(&names)),
+                StataColumn::Str("version", strings(&versions)),
+                StataColumn::Str("source", strings(&sources)),
+                StataColumn::Str("group", strings(&groups)),
+            ],
+        ));
         lines.join("\n")
     }
 }
 
+impl CsvOutput for ListOutput {
+    fn to_csv(&self) -> String {
+        let rows: Vec<Vec<String>> = self
+            .packages
+            .iter()
+            .map(|p| {
+                vec![
+                    p.name.clone(),
+                    p.version.clone(),
+                    p.source.clone(),
+                    p.group.clone(),
+                ]
+            })
+            .collect();
+        format_csv(&["name", "version", "source", "group"], &rows)
+    }
+}
+
+impl MarkdownOutput for ListOutput {
+    fn to_markdown(&self) -> String {
+        let rows: Vec<Vec<String>> = self
+            .packages
+            .iter()
+            .map(|p| {
+                vec![
+                    format!("`{}`", p.name),
+                    p.version.clone(),
+                    p.source.clone(),
+                    p.group.clone(),
+                ]
+            })
+            .collect();
+        format_markdown_table(&["Package", "Version", "Source", "Group"], &rows)
+    }
+}
+
 // =============================================================================
 // OutdatedOutput
 // =============================================================================
@@ -1006,6 +1784,16 @@ impl CommandOutput for OutdatedOutput {
         lines.push(format_stata_local("outdated_names", &names.join(",")));
         lines.push(format_stata_local("outdated_currents", &currents.join(",")));
         lines.push(format_stata_local("outdated_latests", &latests.join(",")));
+        let sources: Vec<_> = self.packages.iter().map(|p| p.source.as_str()).collect();
+        lines.push(format_stata_frame(
+            "outdated",
+            &[
+                StataColumn::Str("name", strings(&names)),
+                StataColumn::Str("current", strings(&currents)),
+                StataColumn::Str("latest", strings(&latests)),
+                StataColumn::Str("source", strings(&sources)),
+            ],
+        ));
         if let Some(msg) = &self.error {
             lines.push(format_stata_local("error", msg));
         }
@@ -1013,6 +1801,50 @@ impl CommandOutput for OutdatedOutput {
     }
 }
 
+impl CsvOutput for OutdatedOutput {
+    fn to_csv(&self) -> String {
+        let rows: Vec<Vec<String>> = self
+            .packages
+            .iter()
+            .map(|p| {
+                vec![
+                    p.name.clone(),
+                    p.current.clone(),
+                    p.latest.clone(),
+                    p.source.clone(),
+                ]
+            })
+            .collect();
+        format_csv(&["name", "current", "latest", "source"], &rows)
+    }
+}
+
+impl MarkdownOutput for OutdatedOutput {
+    fn to_markdown(&self) -> String {
+        let rows: Vec<Vec<String>> = self
+            .packages
+            .iter()
+            .map(|p| {
+                vec![
+                    format!("`{}`", p.name),
+                    p.current.clone(),
+                    p.latest.clone(),
+                    p.source.clone(),
+                ]
+            })
+            .collect();
+        let mut out = format_markdown_table(&["Package", "Current", "Latest", "Source"], &rows);
+        out.push_str(&format!(
+            "\n{} of {} packages outdated\n",
+            self.outdated_count, self.total_count
+        ));
+        if let Some(error) = &self.error {
+            out.push_str(&format!("\n**Error:** {}\n", error));
+        }
+        out
+    }
+}
+
 // =============================================================================
 // LockOutput
 // =============================================================================
@@ -1030,6 +1862,12 @@ pub struct LockOutput {
     pub in_sync: bool,
     /// Number of packages that could not be resolved
     pub failed: usize,
+    /// Locked packages stacy.toml no longer declares, left in the lockfile
+    pub orphans: Vec<String>,
+    /// Entries `--prune` dropped from the lockfile
+    pub pruned: Vec<String>,
+    /// Package versions `--prune` removed from the cache, as `name@version`
+    pub cache_pruned: Vec<String>,
     /// Error summary (present iff status == "error")
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
@@ -1051,6 +1889,12 @@ impl CommandOutput for LockOutput {
         lines.push(format_stata_scalar_bool("updated", self.updated));
         lines.push(format_stata_scalar_bool("in_sync", self.in_sync));
         lines.push(format_stata_scalar_usize("failed", self.failed));
+        lines.push(format_stata_local("orphans", &self.orphans.join(" ")));
+        lines.push(format_stata_local("pruned", &self.pruned.join(" ")));
+        lines.push(format_stata_local(
+            "cache_pruned",
+            &self.cache_pruned.join(" "),
+        ));
         if let Some(msg) = &self.error {
             lines.push(format_stata_local("error", msg));
         }
@@ -1079,6 +1923,22 @@ pub struct TestOutput {
     pub success: bool,
     /// Individual test results
     pub tests: Vec<TestResultOutput>,
+    /// Tests that passed and failed on the same code (`--detect-flaky`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flaky: Option<Vec<FlakyTestOutput>>,
+}
+
+/// A test whose history shows both outcomes on the same code
+#[derive(Debug, Serialize)]
+pub struct FlakyTestOutput {
+    /// Test name
+    pub name: String,
+    /// Path to the test file
+    pub path: PathBuf,
+    /// Passing runs on its most inconsistent code state
+    pub passed: usize,
+    /// Failing runs on that state
+    pub failed: usize,
 }
 
 impl CommandOutput for TestOutput {
@@ -1098,10 +1958,89 @@ impl CommandOutput for TestOutput {
             "duration_secs",
             self.duration_secs,
         ));
+
+        let names: Vec<_> = self.tests.iter().map(|t| t.name.as_str()).collect();
+        let passed: Vec<_> = self
+            .tests
+            .iter()
+            .map(|t| flag(t.status == "passed"))
+            .collect();
+        let durations: Vec<_> = self.tests.iter().map(|t| t.duration_secs).collect();
+        let exit_codes: Vec<_> = self.tests.iter().map(|t| t.exit_code as f64).collect();
+        let cached: Vec<_> = self.tests.iter().map(|t| flag(t.cached)).collect();
+        let retries: Vec<_> = self.tests.iter().map(|t| t.retries as f64).collect();
+        lines.push(format_stata_frame(
+            "tests",
+            &[
+                StataColumn::Str("name", strings(&names)),
+                StataColumn::Str(
+                    "path",
+                    self.tests
+                        .iter()
+                        .map(|t| t.path.display().to_string())
+                        .collect(),
+                ),
+                StataColumn::Str(
+                    "status",
+                    self.tests.iter().map(|t| t.status.clone()).collect(),
+                ),
+                StataColumn::Num("duration_secs", durations.clone()),
+                StataColumn::Num("exit_code", exit_codes.clone()),
+                StataColumn::Num("cached", cached.clone()),
+                StataColumn::Num("retries", retries.clone()),
+                StataColumn::Str(
+                    "error_message",
+                    self.tests
+                        .iter()
+                        .map(|t| t.error_message.clone().unwrap_or_default())
+                        .collect(),
+                ),
+            ],
+        ));
+        lines.extend(format_stata_matrix(
+            "tests",
+            &names,
+            &[
+                ("passed", passed),
+                ("duration_secs", durations),
+                ("exit_code", exit_codes),
+                ("cached", cached),
+                ("retries", retries),
+            ],
+        ));
         lines.join("\n")
     }
 }
 
+impl MarkdownOutput for TestOutput {
+    fn to_markdown(&self) -> String {
+        let rows: Vec<Vec<String>> = self
+            .tests
+            .iter()
+            .map(|t| {
+                let mut status = t.status.clone();
+                if t.cached {
+                    status.push_str(" (cached)");
+                } else if t.retries > 0 {
+                    status.push_str(&format!(" (retried {}x)", t.retries));
+                }
+                vec![
+                    format!("`{}`", t.name),
+                    status,
+                    format!("{:.2}s", t.duration_secs),
+                    t.error_message.clone().unwrap_or_default(),
+                ]
+            })
+            .collect();
+        let mut out = format_markdown_table(&["Test", "Status", "Duration", "Error"], &rows);
+        out.push_str(&format!(
+            "\n**{} passed, {} failed** in {:.2}s\n",
+            self.passed, self.failed, self.duration_secs
+        ));
+        out
+    }
+}
+
 /// Individual test result
 #[derive(Debug, Serialize)]
 pub struct TestResultOutput {
@@ -1118,6 +2057,14 @@ pub struct TestResultOutput {
     /// Error message if test failed
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error_message: Option<String>,
+    /// Whether the result came from the build cache
+    pub cached: bool,
+    /// Path to the kept log; absent when the log was removed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log_file: Option<PathBuf>,
+    /// Failed runs before it passed, for a flaky test retried with
+    /// `--retry-flaky`
+    pub retries: u32,
 }
 
 // =============================================================================
@@ -1154,10 +2101,58 @@ impl CommandOutput for TestListOutput {
         // Create a comma-separated list of test names
         let test_names: Vec<_> = self.tests.iter().map(|t| t.name.as_str()).collect();
         lines.push(format_stata_local("test_names", &test_names.join(",")));
+        lines.push(format_stata_frame(
+            "tests",
+            &[
+                StataColumn::Str("name", strings(&test_names)),
+                StataColumn::Str(
+                    "path",
+                    self.tests
+                        .iter()
+                        .map(|t| t.path.display().to_string())
+                        .collect(),
+                ),
+            ],
+        ));
         lines.join("\n")
     }
 }
 
+impl CsvOutput for TestListOutput {
+    fn to_csv(&self) -> String {
+        let rows: Vec<Vec<String>> = self
+            .tests
+            .iter()
+            .map(|t| vec![t.name.clone(), t.path.display().to_string()])
+            .collect();
+        format_csv(&["name", "path"], &rows)
+    }
+}
+
+impl MarkdownOutput for TestListOutput {
+    fn to_markdown(&self) -> String {
+        let rows: Vec<Vec<String>> = self
+            .tests
+            .iter()
+            .map(|t| vec![format!("`{}`", t.name), t.path.display().to_string()])
+            .collect();
+        format_markdown_table(&["Test", "Path"], &rows)
+    }
+}
+
+/// A bool as a 0/1 matrix or frame value
+fn flag(value: bool) -> f64 {
+    if value {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+fn strings(values: &[&str]) -> Vec<String> {
+    values.iter().map(|v| v.to_string()).collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1211,6 +2206,7 @@ mod tests {
             warnings: 1,
             failed: 0,
             check_count: 6,
+            fixed_count: 0,
         };
 
         let stata = output.to_stata();
@@ -1550,6 +2546,8 @@ mod tests {
             max_secs: 1.800,
             stddev_secs: 0.150,
             success: true,
+            engines: None,
+            sweep: None,
         };
 
         let stata = output.to_stata();
@@ -1562,6 +2560,72 @@ mod tests {
         assert!(stata.contains("scalar stacy_max_secs = 1.800000"));
         assert!(stata.contains("scalar stacy_stddev_secs = 0.150000"));
         assert!(stata.contains("scalar stacy_success = 1"));
+        assert!(!stata.contains("stacy_engines"));
+    }
+
+    #[test]
+    fn test_bench_output_engines_to_stata() {
+        let engine = |name: &str, mean_secs: f64, speedup: f64| EngineBenchOutput {
+            engine: name.to_string(),
+            mean_secs,
+            median_secs: mean_secs,
+            min_secs: mean_secs,
+            max_secs: mean_secs,
+            stddev_secs: 0.0,
+            speedup,
+        };
+        let output = BenchOutput {
+            script: PathBuf::from("bench.do"),
+            measured_runs: 10,
+            warmup_runs: 2,
+            mean_secs: 4.0,
+            median_secs: 4.0,
+            min_secs: 4.0,
+            max_secs: 4.0,
+            stddev_secs: 0.0,
+            success: true,
+            engines: Some(vec![
+                engine("stata-se", 4.0, 1.0),
+                engine("stata-mp", 2.0, 2.0),
+            ]),
+            sweep: None,
+        };
+
+        let stata = output.to_stata();
+        assert!(stata.contains("global stacy_engines \"stata-se,stata-mp\""));
+        assert!(stata.contains("global stacy_engine_means \"4.000000,2.000000\""));
+        assert!(stata.contains("global stacy_engine_speedups \"1.000000,2.000000\""));
+    }
+
+    #[test]
+    fn test_bench_output_sweep_to_stata() {
+        let point = |obs: &str, mean_secs: f64| SweepPointOutput {
+            engine: None,
+            args: [("obs".to_string(), obs.to_string())].into(),
+            mean_secs,
+            median_secs: mean_secs,
+            min_secs: mean_secs,
+            max_secs: mean_secs,
+            stddev_secs: 0.0,
+            relative: mean_secs,
+        };
+        let output = BenchOutput {
+            script: PathBuf::from("bench.do"),
+            measured_runs: 10,
+            warmup_runs: 2,
+            mean_secs: 1.0,
+            median_secs: 1.0,
+            min_secs: 1.0,
+            max_secs: 1.0,
+            stddev_secs: 0.0,
+            success: true,
+            engines: None,
+            sweep: Some(vec![point("1e5", 1.0), point("1e6", 9.5)]),
+        };
+
+        let stata = output.to_stata();
+        assert!(stata.contains("global stacy_sweep_args \"obs=1e5,obs=1e6\""));
+        assert!(stata.contains("global stacy_sweep_means \"1.000000,9.500000\""));
     }
 
     // =========================================================================
@@ -1661,6 +2725,7 @@ mod tests {
             category: "Syntax".to_string(),
             description: "The command is not recognized.".to_string(),
             url: "https://www.stata.com/help.cgi?r(198)".to_string(),
+            exit_codes: vec![],
         };
 
         let stata = output.to_stata();
@@ -1679,6 +2744,7 @@ mod tests {
     fn test_add_output_to_stata() {
         let output = AddOutput {
             added: 2,
+            dry_run: false,
             group: "production".to_string(),
             failed: 0,
             skipped: 1,
@@ -1741,6 +2807,29 @@ mod tests {
         assert!(stata.contains("scalar stacy_dry_run = 1"));
     }
 
+    // =========================================================================
+    // ErrorOutput tests
+    // =========================================================================
+
+    #[test]
+    fn test_error_output_to_json() {
+        let output = ErrorOutput::new(&crate::error::Error::ProjectNotFound, 1);
+        let json: serde_json::Value = serde_json::from_str(&output.to_json()).unwrap();
+        assert_eq!(json["success"], false);
+        assert_eq!(json["error"]["kind"], "project_not_found");
+        assert_eq!(json["error"]["exit_code"], 1);
+        assert!(json["error"]["hint"]
+            .as_str()
+            .unwrap()
+            .contains("stacy init"));
+
+        // No usual remedy: no hint
+        let output = ErrorOutput::new(&crate::error::Error::Config("bad".to_string()), 1);
+        let json: serde_json::Value = serde_json::from_str(&output.to_json()).unwrap();
+        assert_eq!(json["error"]["message"], "Configuration error: bad");
+        assert!(json["error"].get("hint").is_none());
+    }
+
     // =========================================================================
     // TaskOutput tests
     // =========================================================================
@@ -1768,6 +2857,47 @@ mod tests {
         assert!(stata.contains("scalar stacy_failed_count = 0"));
     }
 
+    #[test]
+    fn test_task_output_to_stata_scripts() {
+        let output = TaskOutput {
+            task_name: "build".to_string(),
+            success: false,
+            exit_code: 1,
+            duration_secs: 2.5,
+            script_count: 2,
+            success_count: 1,
+            failed_count: 1,
+            scripts: vec![
+                ScriptResultOutput {
+                    name: "clean".to_string(),
+                    script: PathBuf::from("clean.do"),
+                    success: true,
+                    exit_code: 0,
+                    duration_secs: 1.0,
+                    cached: true,
+                    log_file: None,
+                },
+                ScriptResultOutput {
+                    name: "fit".to_string(),
+                    script: PathBuf::from("fit.do"),
+                    success: false,
+                    exit_code: 198,
+                    duration_secs: 1.5,
+                    cached: false,
+                    log_file: None,
+                },
+            ],
+        };
+
+        let stata = output.to_stata();
+        assert!(stata.contains("matrix stacy_scripts = (1, 0, 1, 1 \\ 0, 198, 1.5, 0)"));
+        assert!(stata
+            .contains("matrix colnames stacy_scripts = success exit_code duration_secs cached"));
+        assert!(stata.contains("matrix rownames stacy_scripts = clean fit"));
+        assert!(stata
+            .contains("frame post stacy_scripts (`\"fit\"') (`\"fit.do\"') (0) (198) (1.5) (0)"));
+    }
+
     // =========================================================================
     // TaskListOutput tests
     // =========================================================================
@@ -1808,12 +2938,16 @@ mod tests {
                     version: "3.31".to_string(),
                     source: "ssc".to_string(),
                     group: "production".to_string(),
+                    overridden: false,
+                    editable: false,
                 },
                 ListPackageInfo {
                     name: "reghdfe".to_string(),
                     version: "6.0".to_string(),
                     source: "github:sergiocorreia/reghdfe".to_string(),
                     group: "production".to_string(),
+                    overridden: false,
+                    editable: false,
                 },
             ],
         };
@@ -1825,6 +2959,12 @@ mod tests {
         assert!(stata.contains("global stacy_package_versions \"3.31,6.0\""));
         assert!(stata.contains("global stacy_package_sources \"ssc,github:sergiocorreia/reghdfe\""));
         assert!(stata.contains("global stacy_package_groups \"production,production\""));
+        assert!(stata.contains(
+            "frame create stacy_packages str7 name str4 version str28 source str10 group"
+        ));
+        assert!(stata.contains(
+            "frame post stacy_packages (`\"reghdfe\"') (`\"6.0\"') (`\"github:sergiocorreia/reghdfe\"') (`\"production\"')"
+        ));
     }
 
     // =========================================================================
@@ -1857,6 +2997,74 @@ mod tests {
         assert!(stata.contains("global stacy_outdated_latests \"3.31\""));
     }
 
+    #[test]
+    fn test_test_output_to_markdown() {
+        let output = TestOutput {
+            test_count: 2,
+            passed: 1,
+            failed: 1,
+            skipped: 0,
+            duration_secs: 1.5,
+            success: false,
+            tests: vec![
+                TestResultOutput {
+                    name: "test_clean".to_string(),
+                    path: PathBuf::from("tests/test_clean.do"),
+                    status: "passed".to_string(),
+                    duration_secs: 0.5,
+                    exit_code: 0,
+                    error_message: None,
+                    cached: true,
+                    log_file: None,
+                    retries: 0,
+                },
+                TestResultOutput {
+                    name: "test_merge".to_string(),
+                    path: PathBuf::from("tests/test_merge.do"),
+                    status: "failed".to_string(),
+                    duration_secs: 1.0,
+                    exit_code: 1,
+                    error_message: Some("r(459) - id not unique".to_string()),
+                    cached: false,
+                    log_file: None,
+                    retries: 0,
+                },
+            ],
+            flaky: None,
+        };
+
+        assert_eq!(
+            output.to_markdown(),
+            "| Test | Status | Duration | Error |\n\
+             | --- | --- | --- | --- |\n\
+             | `test_clean` | passed (cached) | 0.50s |  |\n\
+             | `test_merge` | failed | 1.00s | r(459) - id not unique |\n\
+             \n**1 passed, 1 failed** in 1.50s\n"
+        );
+    }
+
+    #[test]
+    fn test_outdated_output_to_csv() {
+        let output = OutdatedOutput {
+            status: "success".to_string(),
+            outdated_count: 1,
+            total_count: 3,
+            failed: 0,
+            error: None,
+            packages: vec![OutdatedPackageInfo {
+                name: "reghdfe".to_string(),
+                current: "6.0".to_string(),
+                latest: "6.12.3".to_string(),
+                source: "github:sergiocorreia/reghdfe".to_string(),
+            }],
+        };
+
+        assert_eq!(
+            output.to_csv(),
+            "name,current,latest,source\nreghdfe,6.0,6.12.3,github:sergiocorreia/reghdfe\n"
+        );
+    }
+
     // =========================================================================
     // LockOutput tests
     // =========================================================================
@@ -1869,11 +3077,16 @@ mod tests {
             updated: false,
             in_sync: true,
             failed: 0,
+            orphans: vec![],
+            pruned: vec!["oldpkg".to_string()],
+            cache_pruned: vec!["oldpkg@2023.01.01".to_string()],
             error: None,
         };
 
         let stata = output.to_stata();
         assert!(stata.contains("global stacy_status \"success\""));
+        assert!(stata.contains("global stacy_pruned \"oldpkg\""));
+        assert!(stata.contains("global stacy_cache_pruned \"oldpkg@2023.01.01\""));
         assert!(stata.contains("scalar stacy_package_count = 5"));
         assert!(stata.contains("scalar stacy_updated = 0"));
         assert!(stata.contains("scalar stacy_in_sync = 1"));
@@ -1888,6 +3101,9 @@ mod tests {
             updated: false,
             in_sync: false,
             failed: 1,
+            orphans: vec![],
+            pruned: vec![],
+            cache_pruned: vec![],
             error: Some("1 package(s) could not be resolved: badpkg".to_string()),
         };
 
@@ -1912,6 +3128,7 @@ mod tests {
             duration_secs: 5.5,
             success: false,
             tests: vec![],
+            flaky: None,
         };
 
         let stata = output.to_stata();
@@ -1921,6 +3138,41 @@ mod tests {
         assert!(stata.contains("scalar stacy_failed = 1"));
         assert!(stata.contains("scalar stacy_skipped = 1"));
         assert!(stata.contains("scalar stacy_duration_secs = 5.500000"));
+        // No tests: an empty frame, and no matrix
+        assert!(stata.contains("frame create stacy_tests"));
+        assert!(!stata.contains("matrix stacy_tests"));
+    }
+
+    #[test]
+    fn test_test_output_to_stata_results() {
+        let output = TestOutput {
+            test_count: 1,
+            passed: 0,
+            failed: 1,
+            skipped: 0,
+            duration_secs: 0.5,
+            success: false,
+            tests: vec![TestResultOutput {
+                name: "test_merge, by id".to_string(),
+                path: PathBuf::from("tests/test_merge.do"),
+                status: "failed".to_string(),
+                duration_secs: 0.5,
+                exit_code: 1,
+                error_message: Some("r(459) - \"id\" not unique".to_string()),
+                cached: false,
+                log_file: None,
+                retries: 0,
+            }],
+            flaky: None,
+        };
+
+        let stata = output.to_stata();
+        // A comma in the name survives in the frame
+        assert!(stata.contains("frame post stacy_tests (`\"test_merge, by id\"')"));
+        // and so do its quotes
+        assert!(stata.contains("(`\"r(459) - \"id\" not unique\"')"));
+        assert!(stata.contains("matrix stacy_tests = (0, 0.5, 1, 0, 0)"));
+        assert!(stata.contains("matrix rownames stacy_tests = test_merge__by_id"));
     }
 
     // =========================================================================
@@ -1955,6 +3207,11 @@ mod tests {
     /// Helper: construct every output type and return their to_stata() results.
     fn all_output_stata_strings() -> Vec<(&'static str, String)> {
         vec![
+            (
+                "ErrorOutput",
+                ErrorOutput::new(&crate::error::Error::Network("timed out".to_string()), 1)
+                    .to_stata(),
+            ),
             (
                 "RunOutput",
                 RunOutput {
@@ -1983,6 +3240,37 @@ mod tests {
                 }
                 .to_stata(),
             ),
+            (
+                "ReproducibilityCheckOutput",
+                ReproducibilityCheckOutput {
+                    script: PathBuf::from("analysis.do"),
+                    reproducible: false,
+                    runs: 2,
+                    outputs: vec![PathBuf::from("out.dta")],
+                    differences: vec![crate::reproducible::OutputDiff {
+                        path: PathBuf::from("out.dta"),
+                        kind: crate::reproducible::DiffKind::Differs,
+                    }],
+                    sources: vec![],
+                }
+                .to_stata(),
+            ),
+            (
+                "BuildOutput",
+                BuildOutput {
+                    success: true,
+                    exit_code: 0,
+                    duration_secs: 1.0,
+                    dry_run: false,
+                    stage_count: 2,
+                    ran_count: 1,
+                    fresh_count: 1,
+                    failed_count: 0,
+                    skipped_count: 0,
+                    stages: vec![],
+                }
+                .to_stata(),
+            ),
             (
                 "BenchOutput",
                 BenchOutput {
@@ -1995,6 +3283,8 @@ mod tests {
                     max_secs: 1.5,
                     stddev_secs: 0.1,
                     success: true,
+                    engines: None,
+                    sweep: None,
                 }
                 .to_stata(),
             ),
@@ -2007,6 +3297,106 @@ mod tests {
                 }
                 .to_stata(),
             ),
+            (
+                "OutputsListOutput",
+                OutputsListOutput {
+                    artifact_count: 1,
+                    artifacts: vec![ArtifactInfo {
+                        path: PathBuf::from("output/table1.tex"),
+                        producer: "tables".to_string(),
+                        kind: "stage".to_string(),
+                        produced_at: "2026-01-01 12:00:00".to_string(),
+                        sha256: "abc".to_string(),
+                        status: "ok".to_string(),
+                    }],
+                }
+                .to_stata(),
+            ),
+            (
+                "OutputsCleanOutput",
+                OutputsCleanOutput {
+                    removed_count: 1,
+                    removed: vec![PathBuf::from("output/table1.tex")],
+                }
+                .to_stata(),
+            ),
+            (
+                "SubmitOutput",
+                SubmitOutput {
+                    job_id: "12345".to_string(),
+                    scheduler: "slurm".to_string(),
+                    command: "run analysis.do".to_string(),
+                    script: PathBuf::from(".stacy/jobs/analysis.sh"),
+                    output: PathBuf::from(".stacy/jobs/analysis.out"),
+                    dry_run: false,
+                }
+                .to_stata(),
+            ),
+            (
+                "JobsOutput",
+                JobsOutput {
+                    job_count: 1,
+                    jobs: vec![JobInfo {
+                        id: "12345".to_string(),
+                        scheduler: "slurm".to_string(),
+                        command: "run analysis.do".to_string(),
+                        submitted_at: "2024-01-01 00:00:00".to_string(),
+                        state: "running".to_string(),
+                        detail: "RUNNING".to_string(),
+                        output: PathBuf::from(".stacy/jobs/analysis.out"),
+                    }],
+                }
+                .to_stata(),
+            ),
+            (
+                "ConfigValidateOutput",
+                ConfigValidateOutput {
+                    path: PathBuf::from("/project/stacy.toml"),
+                    valid: true,
+                    error_count: 0,
+                    errors: Vec::new(),
+                }
+                .to_stata(),
+            ),
+            (
+                "HistoryOutput",
+                HistoryOutput {
+                    run_count: 1,
+                    runs: vec![RunRecord {
+                        id: 7,
+                        invocation: 3,
+                        command: "run".to_string(),
+                        args: vec!["run".to_string(), "analysis.do".to_string()],
+                        dir: PathBuf::new(),
+                        script: PathBuf::from("analysis.do"),
+                        started_at: 1_700_000_000,
+                        duration_secs: 1.5,
+                        exit_code: 0,
+                        success: true,
+                        error: None,
+                        log_file: None,
+                        commit: Some("abc1234".to_string()),
+                    }],
+                }
+                .to_stata(),
+            ),
+            (
+                "DataFetchOutput",
+                DataFetchOutput {
+                    file_count: 1,
+                    downloaded_count: 1,
+                    cached_count: 0,
+                    present_count: 0,
+                    files: vec![DataFileInfo {
+                        name: "survey".to_string(),
+                        path: PathBuf::from("data/raw/survey.csv"),
+                        status: "downloaded".to_string(),
+                        sha256: "abc".to_string(),
+                        pinned: true,
+                    }],
+                }
+                .to_stata(),
+            ),
             (
                 "CacheInfoOutput",
                 CacheInfoOutput {
@@ -2040,6 +3430,7 @@ mod tests {
                     warnings: 0,
                     failed: 0,
                     check_count: 5,
+                    fixed_count: 0,
                 }
                 .to_stata(),
             ),
@@ -2067,6 +3458,12 @@ mod tests {
                     category: "General".to_string(),
                     description: "Explanation text".to_string(),
                     url: "https://stata.com/r100".to_string(),
+                    exit_codes: vec![ExitCodeOutput {
+                        kind: "syntax_error".to_string(),
+                        code: 65,
+                        standard: 2,
+                        description: "Syntax error".to_string(),
+                    }],
                 }
                 .to_stata(),
             ),
@@ -2098,6 +3495,7 @@ mod tests {
                 "AddOutput",
                 AddOutput {
                     added: 1,
+                    dry_run: false,
                     group: "production".to_string(),
                     failed: 0,
                     skipped: 0,
@@ -2196,6 +3594,9 @@ mod tests {
                     updated: false,
                     in_sync: true,
                     failed: 0,
+                    orphans: vec![],
+                    pruned: vec![],
+                    cache_pruned: vec![],
                     error: None,
                 }
                 .to_stata(),
@@ -2210,6 +3611,7 @@ mod tests {
                     duration_secs: 0.0,
                     success: true,
                     tests: vec![],
+                    flaky: None,
                 }
                 .to_stata(),
             ),
@@ -2232,9 +3634,22 @@ mod tests {
                 if line.is_empty() || line.starts_with('*') {
                     continue;
                 }
+                // Frame blocks are wrapped in a Stata version check
+                if line == "if c(stata_version) >= 16 {" || line == "}" {
+                    continue;
+                }
+                let target = line
+                    .strip_prefix("capture frame drop ")
+                    .or_else(|| line.strip_prefix("frame create "))
+                    .or_else(|| line.strip_prefix("frame post "))
+                    .or_else(|| line.strip_prefix("matrix colnames "))
+                    .or_else(|| line.strip_prefix("matrix rownames "))
+                    .or_else(|| line.strip_prefix("matrix "))
+                    .or_else(|| line.strip_prefix("scalar "))
+                    .or_else(|| line.strip_prefix("global "));
                 assert!(
-                    line.starts_with("scalar stacy_") || line.starts_with("global stacy_"),
-                    "{}: line should start with 'scalar stacy_' or 'global stacy_', got: {}",
+                    target.is_some_and(|t| t.starts_with("stacy_")),
+                    "{}: line should define a stacy_ scalar, global, matrix or frame, got: {}",
                     name,
                     line
                 );
@@ -0,0 +1,197 @@
+//! `stacy outputs` command implementation
+//!
+//! Shows and removes the artifacts tasks and pipeline stages registered:
+//! - `stacy outputs list` shows each file with its producer, time and hash
+//! - `stacy outputs clean` deletes them
+
+use crate::artifacts::{format_timestamp, Artifacts};
+use crate::cache::hash::hash_file;
+use crate::cli::output_format::OutputFormat;
+use crate::cli::output_types::{
+    ArtifactInfo, CommandOutput, OutputsCleanOutput, OutputsListOutput,
+};
+use crate::error::{Error, Result};
+use crate::project::Project;
+use clap::{Args, Subcommand};
+
+#[derive(Args)]
+#[command(about = "Show and remove files produced by tasks and pipeline stages", long_about = None)]
+pub struct OutputsArgs {
+    #[command(subcommand)]
+    pub command: OutputsCommand,
+}
+
+#[derive(Subcommand)]
+pub enum OutputsCommand {
+    /// List registered artifacts with the task or stage that produced them
+    #[command(after_help = "\
+Examples:
+  stacy outputs list                 Every registered artifact
+  stacy outputs list --from tables   Artifacts of the 'tables' stage")]
+    List(ListArgs),
+    /// Delete registered artifacts
+    #[command(after_help = "\
+Examples:
+  stacy outputs clean                Delete every registered artifact
+  stacy outputs clean clean merge    Delete what 'clean' and 'merge' produced")]
+    Clean(CleanArgs),
+}
+
+#[derive(Args)]
+pub struct ListArgs {
+    /// Only artifacts of this task or stage
+    #[arg(long, value_name = "NAME")]
+    pub from: Option<String>,
+
+    /// Output format: human (default), json, or stata
+    #[arg(long, value_enum, default_value = "human")]
+    pub format: OutputFormat,
+}
+
+#[derive(Args)]
+pub struct CleanArgs {
+    /// Tasks or stages whose artifacts to delete (default: all)
+    #[arg(value_name = "NAME")]
+    pub producers: Vec<String>,
+
+    /// Output format: human (default), json, or stata
+    #[arg(long, value_enum, default_value = "human")]
+    pub format: OutputFormat,
+}
+
+impl OutputsArgs {
+    /// Output format of the subcommand
+    pub fn format(&self) -> OutputFormat {
+        match &self.command {
+            OutputsCommand::List(args) => args.format,
+            OutputsCommand::Clean(args) => args.format,
+        }
+    }
+}
+
+/// Execute the outputs command
+pub fn execute(args: &OutputsArgs) -> Result<()> {
+    let project = Project::find()?.ok_or(Error::ProjectNotFound)?;
+    let artifacts = Artifacts::load(&project.root)?;
+
+    match &args.command {
+        OutputsCommand::List(list_args) => execute_list(list_args, &project, &artifacts),
+        OutputsCommand::Clean(clean_args) => execute_clean(clean_args, &artifacts),
+    }
+}
+
+/// Execute `stacy outputs list`
+fn execute_list(args: &ListArgs, project: &Project, artifacts: &Artifacts) -> Result<()> {
+    let infos: Vec<ArtifactInfo> = artifacts
+        .list()
+        .into_iter()
+        .filter(|(_, a)| args.from.as_ref().is_none_or(|from| &a.producer == from))
+        .map(|(path, artifact)| {
+            let full = project.root.join(&path);
+            let status = if !full.exists() {
+                "missing"
+            } else if hash_file(&full).ok().as_deref() != Some(artifact.sha256.as_str()) {
+                "modified"
+            } else {
+                "ok"
+            };
+            ArtifactInfo {
+                path,
+                producer: artifact.producer,
+                kind: artifact.kind.to_string(),
+                produced_at: format_timestamp(artifact.produced_at),
+                sha256: artifact.sha256,
+                status: status.to_string(),
+            }
+        })
+        .collect();
+
+    let output = OutputsListOutput {
+        artifact_count: infos.len(),
+        artifacts: infos,
+    };
+
+    match args.format {
+        OutputFormat::Json => println!("{}", output.to_json()),
+        OutputFormat::Stata => println!("{}", output.to_stata()),
+        OutputFormat::Human => {
+            if output.artifacts.is_empty() {
+                println!("No artifacts registered.");
+                println!();
+                println!("Declare `outputs` on a [pipeline] stage or a [scripts] task; its files");
+                println!("are registered each time it succeeds.");
+                return Ok(());
+            }
+            let width = output
+                .artifacts
+                .iter()
+                .map(|a| a.path.display().to_string().len())
+                .max()
+                .unwrap_or(0);
+            for artifact in &output.artifacts {
+                let status = match artifact.status.as_str() {
+                    "ok" => String::new(),
+                    other => format!("  ({})", other),
+                };
+                println!(
+                    "  {:<width$}  {} {:<12}  {} UTC  {}{}",
+                    artifact.path.display(),
+                    artifact.kind,
+                    artifact.producer,
+                    artifact.produced_at,
+                    &artifact.sha256[..12.min(artifact.sha256.len())],
+                    status,
+                    width = width
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Execute `stacy outputs clean`
+fn execute_clean(args: &CleanArgs, artifacts: &Artifacts) -> Result<()> {
+    let known: Vec<String> = artifacts
+        .list()
+        .into_iter()
+        .map(|(_, a)| a.producer)
+        .collect();
+    if let Some(unknown) = args.producers.iter().find(|p| !known.contains(p)) {
+        return Err(Error::Config(format!(
+            "No artifacts registered for '{}'",
+            unknown
+        )));
+    }
+
+    let removed = artifacts.clean(&args.producers)?;
+    artifacts.save()?;
+
+    let output = OutputsCleanOutput {
+        removed_count: removed.len(),
+        removed,
+    };
+
+    match args.format {
+        OutputFormat::Json => println!("{}", output.to_json()),
+        OutputFormat::Stata => println!("{}", output.to_stata()),
+        OutputFormat::Human => {
+            if output.removed.is_empty() {
+                println!("Nothing to remove.");
+            } else {
+                for path in &output.removed {
+                    println!("  removed  {}", path.display());
+                }
+                println!(
+                    "Removed {} {}.",
+                    output.removed_count,
+                    if output.removed_count == 1 {
+                        "artifact"
+                    } else {
+                        "artifacts"
+                    }
+                );
+            }
+        }
+    }
+    Ok(())
+}
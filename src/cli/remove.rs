@@ -2,28 +2,51 @@
 //!
 //! Removes packages from stacy.toml and lockfile.
 //! Packages remain in the global cache for potential reuse by other projects.
+//!
+//! `--unused` removes the packages no project script uses (see
+//! [`crate::packages::usage`]), after listing how each package is used and
+//! asking.
 
 use crate::cli::output_format::OutputFormat;
 use crate::cli::output_types::{CommandOutput, RemoveOutput};
 use crate::error::{Error, Result};
 use crate::packages::lockfile::{load_lockfile, remove_package as lockfile_remove, save_lockfile};
-use crate::project::config::{load_config, write_config};
-use crate::project::Project;
+use crate::packages::usage::{self, UsageReport};
+use crate::project::config::{read_config, write_config};
+use crate::project::{overrides, profile, Project};
+use crate::utils::lock::{project_lock_path, with_state_lock};
 use clap::Args;
+use dialoguer::Confirm;
+use std::io::IsTerminal;
 
 #[derive(Args)]
 #[command(after_help = "\
 Examples:
   stacy remove estout                     Remove a package
-  stacy remove reghdfe ftools             Remove multiple packages")]
+  stacy remove reghdfe ftools             Remove multiple packages
+  stacy remove --unused                   Remove the packages no script uses, after asking")]
 pub struct RemoveArgs {
     /// Package names to remove
-    #[arg(value_name = "PACKAGE", required = true)]
+    #[arg(value_name = "PACKAGE", required_unless_present = "unused")]
     pub packages: Vec<String>,
 
+    /// Remove every package no project script uses, after listing how each
+    /// package is used and asking
+    #[arg(long, conflicts_with = "packages")]
+    pub unused: bool,
+
+    /// With --unused, remove without asking
+    #[arg(short, long, requires = "unused")]
+    pub yes: bool,
+
     /// Output format: human (default), json, or stata
     #[arg(long, value_enum, default_value = "human")]
     pub format: OutputFormat,
+
+    /// Leave out progress and summary lines (global `-q`); failures are
+    /// still reported
+    #[arg(skip)]
+    pub quiet: bool,
 }
 
 /// Result of removing a single package
@@ -35,41 +58,97 @@ struct RemovedPackage {
 }
 
 pub fn execute(args: &RemoveArgs) -> Result<()> {
-    let format = args.format;
-
     // Find project (must exist for remove)
     let project = Project::find()?.ok_or_else(|| {
         Error::Config("Not in a stacy project. Run 'stacy init' first.".to_string())
     })?;
 
+    with_state_lock(&project_lock_path(&project.root), || {
+        remove_from_project(args, &project)
+    })?
+}
+
+fn remove_from_project(args: &RemoveArgs, project: &Project) -> Result<()> {
+    let format = args.format;
+    let chatty = format == OutputFormat::Human && !args.quiet;
+
     // Load config
-    let mut config = load_config(&project.root)?
+    let mut config = read_config(&project.root)?
         .ok_or_else(|| Error::Config("No stacy.toml found. Run 'stacy init' first.".to_string()))?;
 
     // Load lockfile (may not exist)
     let mut lockfile =
         load_lockfile(&project.root)?.unwrap_or_else(crate::packages::lockfile::create_lockfile);
 
-    if format == OutputFormat::Human {
-        println!("Removing {} package(s)...", args.packages.len());
+    let mut report = None;
+    let packages: Vec<String> = if args.unused {
+        let found = usage::analyze(project, Some(&lockfile))?;
+        let unused: Vec<String> = found.unused().map(|p| p.name.clone()).collect();
+        if chatty {
+            print_usage(&found);
+        }
+        report = Some(found);
+        if unused.is_empty() {
+            if chatty {
+                println!("No unused packages.");
+            }
+            if format == OutputFormat::Json {
+                print_json_output(&[], &summarize(&[]), report.as_ref());
+            }
+            return Ok(());
+        }
+        if !args.yes {
+            if !(std::io::stdin().is_terminal() && std::io::stderr().is_terminal()) {
+                return Err(Error::Config(format!(
+                    "{} unused package(s) found ({}); stacy remove --unused asks before \
+                     removing them, so pass --yes where it can't ask",
+                    unused.len(),
+                    unused.join(", ")
+                )));
+            }
+            let confirmed = Confirm::new()
+                .with_prompt(format!(
+                    "Remove {} from stacy.toml and {}?",
+                    unused.join(", "),
+                    profile::lockfile_name()
+                ))
+                .default(false)
+                .interact()
+                .unwrap_or(false);
+            if !confirmed {
+                if chatty {
+                    println!("Nothing removed.");
+                }
+                return Ok(());
+            }
+        }
+        unused
+    } else {
+        args.packages.iter().map(|p| p.to_lowercase()).collect()
+    };
+
+    if chatty {
+        println!("Removing {} package(s)...", packages.len());
         println!();
     }
 
     let mut results: Vec<RemovedPackage> = Vec::new();
 
-    for package in &args.packages {
-        let package_lower = package.to_lowercase();
-
+    for package_lower in packages {
         let mut result = RemovedPackage {
             name: package_lower.clone(),
             removed_from_config: false,
             removed_from_lockfile: false,
         };
 
-        // Remove from config (dependencies or dev)
-        if config.packages.remove_dependency(&package_lower).is_some() {
+        // Remove from config (dependencies or dev, or the profile's own)
+        if profile::remove_package(&mut config, &package_lower).is_some() {
             result.removed_from_config = true;
         }
+        // An override goes once nothing declares the package
+        if !overrides::is_declared(&config, &package_lower) {
+            config.overrides.remove(&package_lower);
+        }
 
         // Remove from lockfile
         if lockfile_remove(&mut lockfile, &package_lower).is_some() {
@@ -81,7 +160,7 @@ pub fn execute(args: &RemoveArgs) -> Result<()> {
 
         let was_found = result.removed_from_config || result.removed_from_lockfile;
 
-        if format == OutputFormat::Human {
+        if chatty {
             if was_found {
                 println!(
                     "  - {} (config: {}, lock: {})",
@@ -111,7 +190,24 @@ pub fn execute(args: &RemoveArgs) -> Result<()> {
     // Write updated lockfile
     save_lockfile(&project.root, &lockfile)?;
 
-    // Calculate summary
+    let output = summarize(&results);
+
+    // Output results
+    match format {
+        OutputFormat::Json => print_json_output(&results, &output, report.as_ref()),
+        OutputFormat::Stata => println!("{}", output.to_stata()),
+        OutputFormat::Human if args.quiet => {}
+        OutputFormat::Human => print_human_summary(&output),
+    }
+
+    if output.removed == 0 {
+        crate::session::exit(1);
+    }
+
+    Ok(())
+}
+
+fn summarize(results: &[RemovedPackage]) -> RemoveOutput {
     let removed_count = results
         .iter()
         .filter(|r| r.removed_from_config || r.removed_from_lockfile)
@@ -121,34 +217,56 @@ pub fn execute(args: &RemoveArgs) -> Result<()> {
         .filter(|r| !r.removed_from_config && !r.removed_from_lockfile)
         .count() as i32;
 
-    let status = if removed_count == 0 {
+    // Nothing to remove is no failure with --unused, where nothing was asked for
+    let status = if removed_count == 0 && !results.is_empty() {
         "error"
     } else {
         "success"
     };
 
-    let output = RemoveOutput {
+    RemoveOutput {
         status: status.to_string(),
         removed: removed_count,
         not_found: not_found_count,
         total: results.len() as i32,
-    };
-
-    // Output results
-    match format {
-        OutputFormat::Json => print_json_output(&results, &output),
-        OutputFormat::Stata => println!("{}", output.to_stata()),
-        OutputFormat::Human => print_human_summary(&output),
     }
+}
 
-    if removed_count == 0 {
-        std::process::exit(1);
+/// How each package is used, with the evidence, for `--unused`
+fn print_usage(report: &UsageReport) {
+    println!(
+        "Scanned {} script(s) for the commands of {} package(s):",
+        report.scripts,
+        report.packages.len()
+    );
+    println!();
+    let width = report
+        .packages
+        .iter()
+        .map(|p| p.name.len())
+        .max()
+        .unwrap_or(0);
+    for package in &report.packages {
+        let status = match package.status() {
+            "unknown" => "kept",
+            status => status,
+        };
+        println!(
+            "  {:<6}  {:<width$}  {}",
+            status,
+            package.name,
+            package.evidence(report.scripts),
+            width = width
+        );
     }
-
-    Ok(())
+    println!();
 }
 
-fn print_json_output(results: &[RemovedPackage], output: &RemoveOutput) {
+fn print_json_output(
+    results: &[RemovedPackage],
+    output: &RemoveOutput,
+    report: Option<&UsageReport>,
+) {
     use serde_json::json;
 
     let packages: Vec<_> = results
@@ -164,7 +282,7 @@ fn print_json_output(results: &[RemovedPackage], output: &RemoveOutput) {
         })
         .collect();
 
-    let json_output = json!({
+    let mut json_output = json!({
         "status": output.status,
         "packages": packages,
         "summary": {
@@ -173,6 +291,21 @@ fn print_json_output(results: &[RemovedPackage], output: &RemoveOutput) {
             "total": output.total,
         }
     });
+    if let Some(report) = report {
+        json_output["usage"] = report
+            .packages
+            .iter()
+            .map(|p| {
+                json!({
+                    "name": p.name,
+                    "group": p.group,
+                    "status": p.status(),
+                    "evidence": p.evidence(report.scripts),
+                })
+            })
+            .collect();
+        json_output["scripts_scanned"] = report.scripts.into();
+    }
 
     println!("{}", serde_json::to_string_pretty(&json_output).unwrap());
 }
@@ -0,0 +1,262 @@
+//! `stacy report` command implementation
+//!
+//! Collects what a bug report needs into one zip: the environment and doctor
+//! results (as `stacy env` and `stacy doctor` report them in JSON), stacy's
+//! internal diagnostics while gathering them, the project's stacy.toml and
+//! stacy.lock, and the log of the failing run. Secrets are masked in every
+//! file of the bundle (see `executor::redact`); `--redact-paths` also
+//! replaces the home directory with `~` and the user name in paths with
+//! `<user>`.
+
+use crate::error::{Error, Result};
+use crate::project::Project;
+use crate::utils::zip::ZipWriter;
+use clap::Args;
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[derive(Args)]
+#[command(after_help = "\
+Examples:
+  stacy report                            Bundle environment, doctor, config and the newest log
+  stacy report --redact-paths             Same, without home directory and user name
+  stacy report --log logs/clean.log       Include this log instead of the newest
+  stacy report --attach debug.jsonl       Also include a file (e.g. a --log-file of the failing command)")]
+pub struct ReportArgs {
+    /// Zip file to write (default: stacy-report-<date>-<time>.zip)
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    /// Log of the failing run (default: the newest log in the project's
+    /// log_dir)
+    #[arg(long, value_name = "PATH")]
+    pub log: Option<PathBuf>,
+
+    /// Also include this file (repeatable)
+    #[arg(long, value_name = "PATH")]
+    pub attach: Vec<PathBuf>,
+
+    /// Replace the home directory with ~ and the user name in paths with
+    /// <user>
+    #[arg(long)]
+    pub redact_paths: bool,
+}
+
+pub fn execute(args: &ReportArgs) -> Result<()> {
+    let project = Project::find()?;
+    let scratch = tempfile::TempDir::with_prefix("stacy-report-")?;
+    let diagnostics = scratch.path().join("diagnostics.jsonl");
+
+    let mut files: Vec<(String, Vec<u8>)> = vec![
+        ("environment.txt".to_string(), environment().into_bytes()),
+        ("env.json".to_string(), stacy_json(&["env"], &diagnostics)),
+        (
+            "doctor.json".to_string(),
+            stacy_json(&["doctor"], &diagnostics),
+        ),
+    ];
+    if let Ok(events) = std::fs::read(&diagnostics) {
+        files.push(("diagnostics.jsonl".to_string(), events));
+    }
+
+    if let Some(project) = &project {
+        for name in ["stacy.toml", "stacy.lock"] {
+            if let Ok(contents) = std::fs::read(project.root.join(name)) {
+                files.push((name.to_string(), contents));
+            }
+        }
+    }
+
+    let log = match &args.log {
+        Some(log) if !log.is_file() => {
+            return Err(Error::Config(format!("Log {} not found", log.display())));
+        }
+        Some(log) => Some(log.clone()),
+        None => project.as_ref().and_then(newest_log),
+    };
+    if let Some(log) = &log {
+        files.push((format!("logs/{}", file_name(log)), std::fs::read(log)?));
+    }
+    for path in &args.attach {
+        let contents = std::fs::read(path)
+            .map_err(|e| Error::Config(format!("Cannot attach {}: {}", path.display(), e)))?;
+        files.push((format!("attachments/{}", file_name(path)), contents));
+    }
+
+    let redaction = args.redact_paths.then(Redaction::current);
+    let secrets = crate::executor::redact::current();
+    let mut zip = ZipWriter::new();
+    for (name, contents) in &files {
+        let contents = secrets.redact_bytes(contents);
+        match &redaction {
+            Some(redaction) => zip.add(
+                name,
+                redaction
+                    .apply(&String::from_utf8_lossy(&contents))
+                    .as_bytes(),
+            ),
+            None => zip.add(name, &contents),
+        }
+    }
+
+    let output = args.output.clone().unwrap_or_else(|| {
+        let (date, time) = crate::utils::date::now_utc();
+        PathBuf::from(format!("stacy-report-{}-{}.zip", date, time))
+    });
+    let mut out = std::fs::File::create(&output)
+        .map_err(|e| Error::Config(format!("Cannot write {}: {}", output.display(), e)))?;
+    zip.finish(&mut out)?;
+
+    println!("Wrote {} ({} files)", output.display(), files.len());
+    for (name, _) in &files {
+        println!("  {}", name);
+    }
+    if log.is_none() {
+        println!("No log included: pass the failing run's log with --log");
+    }
+    if redaction.is_none() {
+        println!("hint: it holds paths with your user name; --redact-paths leaves them out");
+    }
+    Ok(())
+}
+
+/// stacy, the platform, and the environment variables that change its
+/// behavior
+fn environment() -> String {
+    let mut text = format!(
+        "stacy {}\nplatform: {} {}\n",
+        env!("CARGO_PKG_VERSION"),
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    );
+    for (key, value) in std::env::vars() {
+        if key.starts_with("STACY_") || key == "STATA_BINARY" {
+            text.push_str(&format!("{}={}\n", key, value));
+        }
+    }
+    text
+}
+
+/// `stacy <command> --format json`, run as a child with its diagnostics
+/// appended to `diagnostics`. What it printed, even when it failed.
+fn stacy_json(command: &[&str], diagnostics: &Path) -> Vec<u8> {
+    let exe = std::env::current_exe().unwrap_or_else(|_| PathBuf::from("stacy"));
+    let events = diagnostics.with_extension(format!("{}.jsonl", command.join("-")));
+    let output = Command::new(exe)
+        .args(command)
+        .args(["--format", "json", "--log-file"])
+        .arg(&events)
+        .output();
+    if let Ok(text) = std::fs::read(&events) {
+        if let Ok(mut file) = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(diagnostics)
+        {
+            let _ = std::io::Write::write_all(&mut file, &text);
+        }
+    }
+    match output {
+        Ok(output) if !output.stdout.is_empty() => output.stdout,
+        Ok(output) => output.stderr,
+        Err(e) => format!("could not run stacy {}: {}\n", command.join(" "), e).into_bytes(),
+    }
+}
+
+/// The most recently written log in the project's log_dir
+fn newest_log(project: &Project) -> Option<PathBuf> {
+    let log_dir = project
+        .config
+        .as_ref()
+        .map(|c| c.run.log_dir.clone())
+        .unwrap_or_else(|| PathBuf::from("logs"));
+    std::fs::read_dir(project.root.join(log_dir))
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|e| e == "log"))
+        .filter_map(|path| Some((path.metadata().ok()?.modified().ok()?, path)))
+        .max()
+        .map(|(_, path)| path)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "file".to_string())
+}
+
+/// What `--redact-paths` takes out
+struct Redaction {
+    /// Spellings of the home directory: as the OS writes it, with `/`, and
+    /// escaped in JSON
+    home: Vec<String>,
+    /// The user name as a path component
+    user: Option<Regex>,
+}
+
+impl Redaction {
+    fn current() -> Self {
+        let user = std::env::var("USER")
+            .or_else(|_| std::env::var("USERNAME"))
+            .ok();
+        Self::new(dirs::home_dir().as_deref(), user.as_deref())
+    }
+
+    fn new(home: Option<&Path>, user: Option<&str>) -> Self {
+        let home = home
+            .map(|h| h.display().to_string())
+            .filter(|h| h.len() > 1)
+            .map(|h| {
+                // JSON escapes Windows separators
+                let mut spellings = vec![h.replace('\\', "\\\\"), h.replace('\\', "/"), h];
+                spellings.dedup();
+                spellings
+            })
+            .unwrap_or_default();
+        let user = user.filter(|u| !u.is_empty()).map(|u| {
+            Regex::new(&format!(r"(^|[/\\]){}([/\\]|$)", regex::escape(u)))
+                .expect("escaped user name")
+        });
+        Self { home, user }
+    }
+
+    fn apply(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        for home in &self.home {
+            text = text.replace(home.as_str(), "~");
+        }
+        if let Some(user) = &self.user {
+            // Twice: a match takes the separators on both sides with it
+            for _ in 0..2 {
+                text = user.replace_all(&text, "${1}<user>${2}").into_owned();
+            }
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redaction() {
+        let redaction = Redaction::new(Some(Path::new("/home/jdoe")), Some("jdoe"));
+        assert_eq!(
+            redaction.apply("log: /home/jdoe/project/logs/a.log\n"),
+            "log: ~/project/logs/a.log\n"
+        );
+        assert_eq!(
+            redaction.apply("C:/Users/jdoe/ado and /mnt/jdoe/jdoe/x, not jdoes"),
+            "C:/Users/<user>/ado and /mnt/<user>/<user>/x, not jdoes"
+        );
+
+        let windows = Redaction::new(Some(Path::new(r"C:\Users\jdoe")), None);
+        assert_eq!(
+            windows.apply(r#"{"cache_dir": "C:\\Users\\jdoe\\AppData"} C:/Users/jdoe/x"#),
+            r#"{"cache_dir": "~\\AppData"} ~/x"#
+        );
+    }
+}
@@ -0,0 +1,146 @@
+//! `stacy rerun` command implementation
+//!
+//! Replays the last `stacy run`, `task`, `build` or `test` recorded in the
+//! run history, with the same arguments and from the same directory.
+//! `--failed-only` runs just the scripts that failed in it.
+
+use crate::error::{Error, Result};
+use crate::history::{self, RunRecord};
+use crate::project::{profile, Project};
+use clap::Args;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[derive(Args)]
+#[command(after_help = "\
+Examples:
+  stacy rerun                 Run the last run, task, build or test command again
+  stacy rerun --failed-only   Run just the scripts that failed in it
+
+stacy history lists the runs recorded.")]
+pub struct RerunArgs {
+    /// Only the scripts that failed, with stacy run (stacy test for tests)
+    #[arg(long)]
+    pub failed_only: bool,
+}
+
+/// Execute the rerun command
+pub fn execute(args: &RerunArgs) -> Result<()> {
+    let project = Project::find()?.ok_or(Error::ProjectNotFound)?;
+    let runs = history::load(&project.root);
+    let last = history::last_invocation(&runs);
+    let Some(first) = last.first() else {
+        return Err(Error::Config(
+            "No command recorded yet to rerun: stacy run, task, build and test record theirs"
+                .to_string(),
+        ));
+    };
+
+    let mut cmd = if args.failed_only {
+        let failed = failed_scripts(&last);
+        if failed.is_empty() {
+            println!(
+                "Nothing to rerun: every script of `stacy {}` passed.",
+                first.args.join(" ")
+            );
+            return Ok(());
+        }
+        failed_only_command(&project.root, &first.command, &failed)
+    } else {
+        let exe = std::env::current_exe().unwrap_or_else(|_| PathBuf::from("stacy"));
+        let mut cmd = Command::new(exe);
+        cmd.args(&first.args)
+            .current_dir(project.root.join(&first.dir));
+        cmd
+    };
+
+    eprintln!("Rerunning: stacy {}", shown_args(&cmd).join(" "));
+    let status = cmd.status()?;
+    crate::session::exit(status.code().unwrap_or(1));
+}
+
+/// Scripts whose last run in `runs` failed, in the order they first ran
+fn failed_scripts(runs: &[&RunRecord]) -> Vec<PathBuf> {
+    let mut last_success: HashMap<&Path, bool> = HashMap::new();
+    let mut order = Vec::new();
+    for run in runs {
+        if last_success
+            .insert(run.script.as_path(), run.success)
+            .is_none()
+        {
+            order.push(run.script.as_path());
+        }
+    }
+    order
+        .into_iter()
+        .filter(|script| !last_success[script])
+        .map(Path::to_path_buf)
+        .collect()
+}
+
+/// `stacy test --filter` for failed tests, else `stacy run`, from the project
+/// root where the history's script paths are relative to
+fn failed_only_command(root: &Path, command: &str, failed: &[PathBuf]) -> Command {
+    let exe = std::env::current_exe().unwrap_or_else(|_| PathBuf::from("stacy"));
+    let mut cmd = Command::new(exe);
+    cmd.current_dir(root).arg("--project").arg(root);
+    if let Some(name) = profile::active() {
+        cmd.args(["--env-profile", name]);
+    }
+    if command == "test" {
+        cmd.arg("test");
+        for script in failed {
+            cmd.arg("--filter").arg(script);
+        }
+    } else {
+        cmd.arg("run").args(failed);
+    }
+    cmd
+}
+
+fn shown_args(cmd: &Command) -> Vec<String> {
+    cmd.get_args()
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(script: &str, success: bool) -> RunRecord {
+        RunRecord {
+            id: 1,
+            invocation: 1,
+            command: "test".to_string(),
+            args: vec!["test".to_string()],
+            dir: PathBuf::new(),
+            script: PathBuf::from(script),
+            started_at: 0,
+            duration_secs: 0.0,
+            exit_code: if success { 0 } else { 2 },
+            success,
+            error: None,
+            log_file: None,
+            commit: None,
+        }
+    }
+
+    #[test]
+    fn test_failed_scripts_uses_last_attempt() {
+        // b.do failed, then passed on a flaky retry
+        let runs = [
+            record("a.do", false),
+            record("b.do", false),
+            record("b.do", true),
+            record("c.do", true),
+            record("d.do", false),
+        ];
+        let runs: Vec<&RunRecord> = runs.iter().collect();
+        assert_eq!(
+            failed_scripts(&runs),
+            [PathBuf::from("a.do"), PathBuf::from("d.do")]
+        );
+    }
+}
@@ -1,12 +1,22 @@
-use crate::cache::detect::{check_cache_with_working_dir, hash_working_dir, CacheStatus};
-use crate::cache::hash::{hash_dependency_tree, hash_lockfile};
-use crate::cache::{BuildCache, CacheEntry, CachedError, CachedResult};
-use crate::cli::output_format::{resolve_verbosity, OutputFormat};
+use crate::cache::detect::{check_cache_with_engine, hash_working_dir, CacheStatus};
+use crate::cache::engine::EngineIdentity;
+use crate::cache::hash::{
+    data_dependency_paths, hash_data_dependencies, hash_dependency_tree, hash_lockfile,
+};
+use crate::cache::{BuildCache, CacheEntry, CachedResult};
+use crate::cli::output_format::{resolve_verbosity, OutputFormat, RunFormat};
 use crate::cli::output_types::{
-    CacheHitOutput, CommandOutput, ParallelRunOutput, RunOutput, ScriptRunResult,
+    CacheHitOutput, CommandOutput, ParallelRunOutput, ReproducibilityCheckOutput, RunOutput,
+    ScriptRunResult,
 };
 use crate::error::{Error, Result};
+use crate::executor::events::{self, Event};
 use crate::executor::log_policy::LogPolicy;
+use crate::executor::open_log::LogViewer;
+use crate::executor::problems;
+use crate::executor::runner::Backend;
+use crate::executor::trace::{TraceFilter, TraceView};
+use crate::utils::paths;
 use crate::utils::temp::TempScript;
 use clap::Args;
 use std::io::{IsTerminal, Read};
@@ -158,9 +168,12 @@ Examples:
                                         Use specific Stata binary
   stacy run script.do -v                  Stream the raw log in real-time
   stacy run script.do --log run.log       Also write the raw Stata log to run.log
+  stacy run *.do --log-dir logs/ci        Keep each log as logs/ci/<script>.log
   stacy run script.do --format json       Machine-readable output
   stacy run script.do --trace 2           Trace execution at depth 2
   stacy run script.do --trace 2 -v        Trace + stream live
+  stacy run script.do --trace 3 --trace-filter user --trace-output trace.log
+                                        Trace only your own programs, into trace.log
 
 Tips:
   Use -c (not -e) for inline code")]
@@ -201,18 +214,35 @@ pub struct RunArgs {
     #[arg(short = 'j', long, requires = "parallel")]
     pub jobs: Option<usize>,
 
-    /// Suppress all output, even error context (for CI/batch)
-    #[arg(short, long, conflicts_with_all = ["verbose"])]
+    /// Keep running the remaining scripts after one fails (sequential runs
+    /// stop at the first failure otherwise). Exits with the highest exit code.
+    #[arg(short, long, conflicts_with = "parallel")]
+    pub keep_going: bool,
+
+    /// With --parallel -v, print each script's log as one block when it
+    /// finishes instead of streaming its lines prefixed with [script]
+    #[arg(long, requires = "parallel")]
+    pub no_interleave: bool,
+
+    /// Suppress all output, even error context (global `-q`, for CI/batch)
+    #[arg(skip)]
     pub quiet: bool,
 
-    /// Verbose output: stream log file in real-time (-v)
-    /// Very verbose: show execution details + stream (-vv)
-    #[arg(short, long, action = clap::ArgAction::Count)]
+    /// Stream the log in real time (global `-v`); `-vv` also shows
+    /// execution details
+    #[arg(skip)]
     pub verbose: u8,
 
-    /// Output format: human (default), json, or stata
+    /// Print only a table of the scripts (status, duration, kept log) at
+    /// the end, without their output
+    #[arg(long, conflicts_with_all = ["quiet", "verbose", "trace", "code", "check_reproducible"])]
+    pub summary: bool,
+
+    /// Output format: human (default), json, stata, json-lines (one JSON
+    /// event per line while the scripts run), or vscode (one file:line: line
+    /// per error, for editor problem matchers)
     #[arg(long, value_enum, default_value = "human")]
-    pub format: OutputFormat,
+    pub format: RunFormat,
 
     /// Stata engine to use (overrides config and auto-detection)
     #[arg(long, value_name = "ENGINE")]
@@ -250,6 +280,16 @@ pub struct RunArgs {
     #[arg(long, value_name = "DEPTH", conflicts_with_all = ["quiet", "parallel"])]
     pub trace: Option<u32>,
 
+    /// Which programs the trace shows: all, or user (leaves out the programs
+    /// that come with Stata)
+    #[arg(long, value_enum, value_name = "WHICH", requires = "trace")]
+    pub trace_filter: Option<TraceFilter>,
+
+    /// Write the trace to this file instead of the console, which keeps the
+    /// script's output only
+    #[arg(long, value_name = "PATH", requires = "trace")]
+    pub trace_output: Option<PathBuf>,
+
     /// Kill script if it exceeds this many seconds (SIGTERM, then SIGKILL after 5s grace)
     #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..))]
     pub timeout: Option<u64>,
@@ -259,6 +299,96 @@ pub struct RunArgs {
     /// failure in the project's log_dir ([run] log_dir in stacy.toml).
     #[arg(long, value_name = "PATH", conflicts_with = "parallel")]
     pub log: Option<PathBuf>,
+
+    /// Keep every log in this directory, pass or fail, as <script>.log
+    #[arg(long, value_name = "DIR", conflicts_with = "log")]
+    pub log_dir: Option<PathBuf>,
+
+    /// Name kept logs after a pattern ({script}, {date}, {time}, {status});
+    /// keeps every log, pass or fail, in --log-dir or [run] log_dir
+    #[arg(long, value_name = "PATTERN", conflicts_with = "log")]
+    pub log_name: Option<String>,
+
+    /// How to drive Stata: batch (default) polls the log Stata writes;
+    /// console reads Stata's console output directly over a pty (Unix only)
+    #[arg(long, value_enum, default_value = "batch")]
+    pub backend: Backend,
+
+    /// Cores Stata/MP may use (`set processors N`), checked against the
+    /// license. Overrides [execution] processors in stacy.toml.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub processors: Option<u32>,
+
+    /// Run the script twice, each time in a fresh copy of the project, and
+    /// compare the files the runs write. Fails if any output differs.
+    #[arg(long, conflicts_with_all = ["code", "parallel", "cache", "trace", "log"])]
+    pub check_reproducible: bool,
+
+    /// On failure, diff the last lines of the log against the last
+    /// successful run of the script (kept in .stacy/logs/previous/)
+    #[arg(long, conflicts_with_all = ["code", "parallel", "check_reproducible", "trace"])]
+    pub diff_previous: bool,
+
+    /// On failure, load the data the script left behind in a fresh Stata and
+    /// describe it: the checkpoint saved to $STACY_CHECKPOINT, else the last
+    /// file the script saved
+    #[arg(long, conflicts_with_all = ["parallel", "check_reproducible"])]
+    pub post_mortem: bool,
+
+    /// Output to compare under --check-reproducible, relative to the project
+    /// root (repeatable). Default: every file the script writes.
+    #[arg(long = "output", value_name = "PATH", requires = "check_reproducible")]
+    pub outputs: Vec<PathBuf>,
+
+    /// Before running, `dvc pull` the data files the scripts read that DVC
+    /// tracks, plus the declared inputs of pipeline stages running them
+    #[arg(long, conflicts_with = "code")]
+    pub dvc_pull: bool,
+
+    /// Refuse to run while a file declared in [data] is missing (default:
+    /// [run] verify_data in stacy.toml)
+    #[arg(long)]
+    pub verify_data: bool,
+
+    /// Fail when a script runs this much slower (20%) than the median of its
+    /// last passing runs in the history. Without it, 50% slower warns.
+    #[arg(long, value_name = "PERCENT", value_parser = crate::history::regression::parse_percent)]
+    pub fail_on_regression: Option<f64>,
+
+    /// Fail if lockfile doesn't match stacy.toml (for CI reproducibility).
+    /// Also on with STACY_FROZEN=1 or [install] frozen = true.
+    #[arg(long)]
+    pub frozen: bool,
+
+    /// Run against a clean `git worktree` of HEAD (or --pristine=REF)
+    /// instead of the working directory. Uncommitted changes are left out
+    /// and listed; files declared in [data] are linked in.
+    #[arg(
+        long,
+        value_name = "REF",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "HEAD",
+        conflicts_with_all = ["code", "check_reproducible"]
+    )]
+    pub pristine: Option<String>,
+
+    /// Open the log of a failed run at the error line, in $VISUAL/$EDITOR
+    /// (editor, the default) or less (pager). Default: [ui]
+    /// open_log_on_failure in the user config. Only in a terminal.
+    #[arg(
+        long,
+        value_enum,
+        value_name = "VIEWER",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "editor"
+    )]
+    pub open_log: Option<LogViewer>,
+
+    /// Don't open the log of a failed run, whatever the user config says
+    #[arg(long, conflicts_with = "open_log")]
+    pub no_open_log: bool,
 }
 
 /// Check if a path is the stdin marker "-"
@@ -276,14 +406,31 @@ fn read_stdin() -> Result<String> {
                 .into(),
         ));
     }
-    let mut buffer = String::new();
-    stdin.lock().read_to_string(&mut buffer)?;
+    let mut bytes = Vec::new();
+    stdin.lock().read_to_end(&mut bytes)?;
+    let buffer = crate::utils::encoding::decode_text(&bytes);
     if buffer.trim().is_empty() {
         return Err(Error::Config("stdin is empty".into()));
     }
     Ok(buffer)
 }
 
+/// `--processors`, else `[execution] processors` from stacy.toml
+fn resolve_processors(args: &RunArgs, project: &Option<crate::project::Project>) -> Option<u32> {
+    args.processors
+        .or_else(|| project.as_ref().and_then(|p| p.processors()))
+}
+
+/// `[reproducibility]` from stacy.toml, if in a project
+fn resolve_reproducibility(
+    project: &Option<crate::project::Project>,
+) -> crate::project::config::ReproducibilitySection {
+    project
+        .as_ref()
+        .map(|p| p.reproducibility())
+        .unwrap_or_default()
+}
+
 /// Resolve `config.paths.ado` entries relative to project root into absolute paths.
 fn resolve_local_ado_paths(project: &Option<crate::project::Project>) -> Vec<PathBuf> {
     match project {
@@ -294,7 +441,21 @@ fn resolve_local_ado_paths(project: &Option<crate::project::Project>) -> Vec<Pat
 
 /// Main entry point - dispatches to appropriate execution mode
 pub fn execute(args: &RunArgs) -> Result<()> {
-    use std::process;
+    if args.format.is_table() {
+        return Err(Error::Config(
+            "--format csv and markdown are only for tables, such as `stacy test --list`".into(),
+        ));
+    }
+
+    if let Some(percent) = args.fail_on_regression {
+        crate::history::regression::set_fail_threshold(percent);
+    }
+
+    // The run itself happens in a child stacy inside the worktree, which
+    // does every check below against the committed tree
+    if let Some(ref rev) = args.pristine {
+        return execute_pristine(args, rev);
+    }
 
     // --log writes a single artifact; ambiguous with multiple scripts
     if args.log.is_some() && args.scripts.len() > 1 {
@@ -302,6 +463,14 @@ pub fn execute(args: &RunArgs) -> Result<()> {
             "--log requires a single script (or inline code)".into(),
         ));
     }
+    if args.diff_previous && args.scripts.len() > 1 {
+        return Err(Error::Config(
+            "--diff-previous requires a single script".into(),
+        ));
+    }
+
+    verify_data(args)?;
+    verify_frozen(args)?;
 
     // Check for stdin marker
     if args.scripts.len() == 1 && is_stdin_marker(&args.scripts[0]) {
@@ -320,19 +489,207 @@ pub fn execute(args: &RunArgs) -> Result<()> {
         return execute_inline(args);
     }
 
+    if args.dvc_pull {
+        dvc_pull_inputs(args)?;
+    }
+
+    if args.check_reproducible {
+        execute_check_reproducible(args)?;
+        crate::session::exit(0);
+    }
+
     // Dispatch based on number of scripts and parallel flag
     match (args.scripts.len(), args.parallel) {
         (0, _) => {
             // This shouldn't happen due to required_unless_present
             unreachable!("clap should require either script or code")
         }
-        (1, _) => execute_single(&args.scripts[0], args),
+        // --summary reports even a single script in the multi-script table
+        (1, _) if !args.summary => execute_single(&args.scripts[0], args),
         (_, true) => execute_parallel(args),
         (_, false) => execute_sequential(args),
     }?;
 
     // Note: execute_* functions call process::exit() internally
-    process::exit(0);
+    crate::session::exit(0);
+}
+
+/// `--pristine`: run this same command, minus `--pristine`, from the
+/// matching directory of a temporary worktree of `rev`
+fn execute_pristine(args: &RunArgs, rev: &str) -> Result<()> {
+    use crate::pristine::{link_data, repo_root, resolve_commit, uncommitted_changes, Worktree};
+
+    let cwd = paths::canonicalize(&std::env::current_dir()?)?;
+    let repo = paths::canonicalize(&repo_root(&cwd)?)?;
+    let commit = resolve_commit(&repo, rev)?;
+    let excluded = uncommitted_changes(&repo)?;
+    // Progress goes to stderr in every format; stdout is the child's
+    let report = !args.quiet;
+
+    if report {
+        eprintln!("Running {} ({}) in a clean worktree", rev, commit);
+        if excluded.is_empty() {
+            eprintln!("  No uncommitted changes");
+        } else {
+            eprintln!("  Excluded {} uncommitted change(s):", excluded.len());
+            for change in &excluded {
+                eprintln!("    {:<9}  {}", change.label(), change.path);
+            }
+        }
+    }
+
+    let mut worktree = Worktree::add(&repo, &commit)?;
+    let rel_cwd = cwd.strip_prefix(&repo).unwrap_or(Path::new(""));
+    let tree_cwd = worktree.path().join(rel_cwd);
+    if !tree_cwd.is_dir() {
+        return Err(Error::Config(format!(
+            "{} does not exist at {}",
+            rel_cwd.display(),
+            rev
+        )));
+    }
+
+    if let Some(project) = crate::project::Project::find()? {
+        if let Some(ref config) = project.config {
+            let root = paths::canonicalize(&project.root)?;
+            let rel_root = root.strip_prefix(&repo).unwrap_or(Path::new(""));
+            let linked = link_data(&root, &worktree.path().join(rel_root), &config.data)?;
+            if report && !linked.is_empty() {
+                eprintln!("  Linked {} data file(s) from [data]", linked.len());
+            }
+        }
+    }
+    if report {
+        eprintln!();
+    }
+
+    let status = std::process::Command::new(std::env::current_exe()?)
+        .args(pristine_args(&repo, worktree.path()))
+        .current_dir(&tree_cwd)
+        .status()?;
+    let code = status.code().unwrap_or(1);
+
+    // A failed run's log lives in the worktree; keep it to look at
+    if code != 0 {
+        let kept = worktree.keep();
+        if report {
+            eprintln!(
+                "\nWorktree kept at {}\n  Remove it with: git worktree remove --force {}",
+                kept.display(),
+                kept.display()
+            );
+        }
+    }
+    drop(worktree);
+    crate::session::exit(code);
+}
+
+/// This process's arguments without `--pristine`, with absolute paths into
+/// the repository moved into the worktree
+fn pristine_args(repo: &Path, tree: &Path) -> Vec<std::ffi::OsString> {
+    std::env::args_os()
+        .skip(1)
+        .filter(|arg| {
+            let arg = arg.to_string_lossy();
+            arg != "--pristine" && !arg.starts_with("--pristine=")
+        })
+        .map(|arg| {
+            let moved = Path::new(&arg)
+                .strip_prefix(repo)
+                .ok()
+                .map(|rel| tree.join(rel));
+            moved.map(PathBuf::into_os_string).unwrap_or(arg)
+        })
+        .collect()
+}
+
+/// Frozen mode (`--frozen`, `STACY_FROZEN` or `[install] frozen`): fail
+/// before Stata starts when stacy.lock is out of sync with stacy.toml
+fn verify_frozen(args: &RunArgs) -> Result<()> {
+    let Some(project) = crate::project::Project::find()? else {
+        return Ok(());
+    };
+    let config = project.config.clone().unwrap_or_default();
+    if !crate::packages::lockfile::frozen_mode(args.frozen, Some(&config)) {
+        return Ok(());
+    }
+    let lockfile = crate::packages::lockfile::load_lockfile(&project.root)?;
+    crate::packages::lockfile::check_frozen(lockfile.as_ref(), &config)
+}
+
+/// `--verify-data` (or `[run] verify_data`): fail before Stata starts when
+/// a file declared in `[data]` is missing
+fn verify_data(args: &RunArgs) -> Result<()> {
+    let Some(project) = crate::project::Project::find()? else {
+        return Ok(());
+    };
+    let Some(ref config) = project.config else {
+        return Ok(());
+    };
+    if !args.verify_data && !config.run.verify_data {
+        return Ok(());
+    }
+    let missing = crate::data::missing(&project.root, &config.data);
+    if missing.is_empty() {
+        return Ok(());
+    }
+    let list: Vec<String> = missing
+        .iter()
+        .map(|(name, path)| format!("  {:<15}  {}", name, path.display()))
+        .collect();
+    Err(Error::Config(format!(
+        "Data declared in stacy.toml is missing:\n{}\n\nRun `stacy data fetch` to download it.",
+        list.join("\n")
+    )))
+}
+
+/// `--dvc-pull`: fetch the DVC-tracked data the scripts read before running
+/// them. Data references are found the way `--cache` finds them; stages in
+/// `[pipeline]` that run the same script add their declared inputs.
+fn dvc_pull_inputs(args: &RunArgs) -> Result<()> {
+    let project = crate::project::Project::find()?;
+    let root = match project {
+        Some(ref p) => p.root.clone(),
+        None => std::env::current_dir()?,
+    };
+    let stages = project
+        .as_ref()
+        .and_then(|p| p.config.as_ref())
+        .map(|c| c.pipeline.clone())
+        .unwrap_or_default();
+
+    let mut inputs = Vec::new();
+    for script in &args.scripts {
+        let (abs_script, working_dir) = resolve_working_dir(script, args)?;
+        if !abs_script.exists() {
+            // Reported by the run itself
+            continue;
+        }
+        inputs.extend(data_dependency_paths(&abs_script, working_dir.as_deref())?);
+        for stage in stages.values() {
+            if root.join(&stage.script) == abs_script {
+                inputs.extend(stage.inputs.iter().map(|p| root.join(p)));
+            }
+        }
+    }
+
+    let targets = crate::dvc::tracked(&root, &inputs);
+    if args.format.output() == OutputFormat::Human {
+        if targets.is_empty() {
+            eprintln!("No DVC-tracked inputs to pull.");
+        } else {
+            eprintln!(
+                "Pulling {} DVC-tracked {}...",
+                targets.len(),
+                if targets.len() == 1 {
+                    "input"
+                } else {
+                    "inputs"
+                }
+            );
+        }
+    }
+    crate::dvc::pull(&root, &targets)
 }
 
 /// Resolve the effective working directory from --cd or -C flags.
@@ -379,11 +736,19 @@ fn resolve_working_dir(script: &Path, args: &RunArgs) -> Result<(PathBuf, Option
 
 /// Build the log-retention policy for a run.
 ///
-/// `--log <path>` makes the log a durable artifact at that path. Otherwise it is
+/// `--log <path>` makes the log a durable artifact at that path, as do
+/// `--log-dir` and `--log-name` under a predictable name. Otherwise it is
 /// internal: removed on success, kept on failure, whatever the output format.
 /// Kept logs go to `[run] log_dir`.
-fn log_policy(project: &Option<crate::project::Project>, dest: Option<PathBuf>) -> LogPolicy {
-    LogPolicy::for_project(project.as_ref()).with_dest(dest)
+fn log_policy(
+    project: &Option<crate::project::Project>,
+    args: &RunArgs,
+    dest: Option<PathBuf>,
+) -> Result<LogPolicy> {
+    LogPolicy::for_project(project.as_ref())
+        .with_dest(dest)
+        .with_dir(args.log_dir.clone())
+        .with_name(args.log_name.clone())
 }
 
 /// Warn if semicolons detected in inline code (Stata uses newlines)
@@ -414,9 +779,8 @@ fn warn_if_semicolons(code_snippets: &[String]) {
 fn execute_inline(args: &RunArgs) -> Result<()> {
     use crate::executor::StataExecutor;
     use crate::metrics::Metrics;
-    use std::process;
 
-    let format = args.format;
+    let format = args.format.output();
 
     // Warn about semicolons before joining
     warn_if_semicolons(&args.code);
@@ -454,10 +818,17 @@ fn execute_inline(args: &RunArgs) -> Result<()> {
     let local_ado_paths = resolve_local_ado_paths(&project);
     let engine_ref = args.engine.as_deref();
     let executor = StataExecutor::try_new(engine_ref, verbosity)?
+        .with_events(args.format.is_events())
+        .with_problems(args.format.is_problems())
         .with_allow_global(args.allow_global)
         .with_local_ado_paths(local_ado_paths)
         .with_timeout(args.timeout.map(Duration::from_secs))
-        .with_verify_packages(!args.no_verify);
+        .with_verify_packages(!args.no_verify)
+        .with_backend(args.backend)
+        .with_processors(resolve_processors(args, &project))
+        .with_reproducibility(resolve_reproducibility(&project))
+        .with_trace(trace_view(args)?)
+        .with_post_mortem(args.post_mortem);
     let project_root = project.as_ref().map(|p| p.root.as_path());
 
     if let Some(ref mut m) = metrics {
@@ -475,8 +846,8 @@ fn execute_inline(args: &RunArgs) -> Result<()> {
     // The log is owned by the retention policy, not by TempScript: an inline run
     // that failed keeps its log (in log_dir when configured) so the path printed
     // below actually resolves. A successful run has no log, and reports none.
-    result.log_file = log_policy(&project, args.log.clone())
-        .finalize(&result.log_file, result.success)
+    result.log_file = log_policy(&project, args, args.log.clone())?
+        .finalize_for(Path::new("inline"), &result.log_file, result.success)
         .unwrap_or_default();
 
     if let Some(ref mut m) = metrics {
@@ -499,6 +870,8 @@ fn execute_inline(args: &RunArgs) -> Result<()> {
 
     // Handle output based on format
     match format {
+        // The stream already reported the run
+        OutputFormat::Json if args.format.is_streamed() => {}
         OutputFormat::Json => {
             print_json_output(&result, &script_path, CodeSource::Inline, args.profile)?;
         }
@@ -520,7 +893,14 @@ fn execute_inline(args: &RunArgs) -> Result<()> {
                     result.duration.as_secs_f64()
                 );
                 if let Some(error) = result.errors.first() {
-                    print_error_details(error);
+                    // The temp script's own lines mean nothing to the user
+                    let trace: Vec<_> = result
+                        .error_trace
+                        .iter()
+                        .filter(|l| l.file != script_path)
+                        .cloned()
+                        .collect();
+                    print_error_details(error, &trace);
                 }
                 // Failure keeps its log — always say where it is (CI/batch too).
                 // Streaming shows the log's content, not the kept file's path.
@@ -538,12 +918,17 @@ fn execute_inline(args: &RunArgs) -> Result<()> {
                         FAILURE_CONTEXT_LINES
                     };
                     if let Ok(raw) = crate::executor::log_reader::read_full_log(&result.log_file) {
-                        let clean = crate::executor::log_reader::strip_boilerplate(&raw);
+                        let mut clean = crate::executor::log_reader::strip_boilerplate(&raw);
+                        if let Some(view) = executor.trace() {
+                            clean = crate::executor::trace::split(&clean, view).0;
+                        }
                         if !clean.is_empty() {
                             print_log_context_n(&clean, context_lines);
                         }
                     }
                 }
+                print_post_mortem(args, &result);
+                open_failed_log(args, &result.log_file);
             } else if !verbosity.is_quiet() {
                 eprintln!(
                     "\x1b[32mPASS\x1b[0m  <inline code>  ({:.2}s)",
@@ -561,16 +946,15 @@ fn execute_inline(args: &RunArgs) -> Result<()> {
     }
 
     drop(temp_script);
-    process::exit(result.exit_code);
+    crate::session::exit(result.exit_code);
 }
 
 /// Execute a single script (original behavior)
 fn execute_single(script_path: &Path, args: &RunArgs) -> Result<()> {
     use crate::executor::StataExecutor;
     use crate::metrics::Metrics;
-    use std::process;
 
-    let format = args.format;
+    let format = args.format.output();
 
     // Resolve working directory from --cd or -C flags
     let (resolved_script, working_dir) = resolve_working_dir(script_path, args)?;
@@ -604,6 +988,33 @@ fn execute_single(script_path: &Path, args: &RunArgs) -> Result<()> {
                 log_file: PathBuf::new(),
             };
             match format {
+                OutputFormat::Json if args.format.is_problems() => {
+                    problems::emit(&[problems::format_problem(
+                        script_path,
+                        None,
+                        None,
+                        "Script not found",
+                    )]);
+                }
+                OutputFormat::Json if args.format.is_events() => {
+                    let message = format!("Script not found: {}", script_path.display());
+                    events::emit(&Event::ErrorDetected {
+                        script: script_path,
+                        r_code: None,
+                        line_number: None,
+                        location: None,
+                        trace: Vec::new(),
+                        message,
+                    });
+                    events::emit(&Event::Finished {
+                        script: script_path,
+                        success: false,
+                        exit_code: 3,
+                        duration_secs: 0.0,
+                        error_count: 1,
+                        cached: false,
+                    });
+                }
                 OutputFormat::Json => println!("{}", output.to_json()),
                 OutputFormat::Stata => println!("{}", output.to_stata()),
                 OutputFormat::Human => {}
@@ -611,7 +1022,7 @@ fn execute_single(script_path: &Path, args: &RunArgs) -> Result<()> {
         } else {
             eprintln!("Error: Script not found: {}", script_path.display());
         }
-        process::exit(3);
+        crate::session::exit(3);
     }
 
     // Find project for cache operations
@@ -627,14 +1038,20 @@ fn execute_single(script_path: &Path, args: &RunArgs) -> Result<()> {
     }
 
     // Check cache if enabled (skip when tracing)
+    let engine = if args.cache && !tracing {
+        EngineIdentity::detect(args.engine.as_deref())
+    } else {
+        None
+    };
     if args.cache && !tracing {
         if let Some(root) = project_root {
             let cache = BuildCache::load(root)?;
-            let cache_status = check_cache_with_working_dir(
+            let cache_status = check_cache_with_engine(
                 &cache,
                 effective_script,
                 Some(root),
                 working_dir.as_deref(),
+                engine.as_ref(),
                 args.force,
             )?;
 
@@ -652,6 +1069,12 @@ fn execute_single(script_path: &Path, args: &RunArgs) -> Result<()> {
                     };
 
                     match format {
+                        OutputFormat::Json if args.format.is_events() => {
+                            events::emit(&Event::cached(script_path, &entry.result))
+                        }
+                        OutputFormat::Json if args.format.is_problems() => {
+                            problems::emit(&problems::cached_problems(script_path, &entry.result))
+                        }
                         OutputFormat::Json => println!("{}", output.to_json()),
                         OutputFormat::Stata => println!("{}", output.to_stata()),
                         OutputFormat::Human => {
@@ -673,7 +1096,7 @@ fn execute_single(script_path: &Path, args: &RunArgs) -> Result<()> {
                         }
                     }
 
-                    process::exit(entry.result.exit_code);
+                    crate::session::exit(entry.result.exit_code);
                 }
                 CacheStatus::Miss(reason) => {
                     // Cache miss - if cache_only mode, fail
@@ -681,7 +1104,7 @@ fn execute_single(script_path: &Path, args: &RunArgs) -> Result<()> {
                         if !args.quiet && format == OutputFormat::Human {
                             eprintln!("Error: Cache miss ({}): {}", reason, script_path.display());
                         }
-                        process::exit(5);
+                        crate::session::exit(5);
                     }
 
                     // Otherwise, continue with execution
@@ -701,10 +1124,17 @@ fn execute_single(script_path: &Path, args: &RunArgs) -> Result<()> {
     let local_ado_paths = resolve_local_ado_paths(&project);
     let engine_ref = args.engine.as_deref();
     let executor = StataExecutor::try_new(engine_ref, verbosity)?
+        .with_events(args.format.is_events())
+        .with_problems(args.format.is_problems())
         .with_allow_global(args.allow_global)
         .with_local_ado_paths(local_ado_paths)
         .with_timeout(args.timeout.map(Duration::from_secs))
-        .with_verify_packages(!args.no_verify);
+        .with_verify_packages(!args.no_verify)
+        .with_backend(args.backend)
+        .with_processors(resolve_processors(args, &project))
+        .with_reproducibility(resolve_reproducibility(&project))
+        .with_trace(trace_view(args)?)
+        .with_post_mortem(args.post_mortem);
 
     if let Some(ref mut m) = metrics {
         m.end_phase("setup");
@@ -724,13 +1154,14 @@ fn execute_single(script_path: &Path, args: &RunArgs) -> Result<()> {
     let mut _trace_temp_script: Option<TempScript> = None; // keep TempScript alive until after execution
     let mut result = if let Some(depth) = args.trace {
         // Read the original script, prepend trace commands, run via TempScript
-        let original_code = std::fs::read_to_string(effective_script).map_err(|e| {
-            crate::error::Error::Config(format!(
-                "Cannot read script for tracing: {}: {}",
-                effective_script.display(),
-                e
-            ))
-        })?;
+        let (original_code, _) =
+            crate::utils::encoding::read_text(effective_script).map_err(|e| {
+                crate::error::Error::Config(format!(
+                    "Cannot read script for tracing: {}: {}",
+                    effective_script.display(),
+                    e
+                ))
+            })?;
         let traced_code = prepend_trace(&original_code, depth);
         let temp_dir = working_dir
             .as_deref()
@@ -759,7 +1190,13 @@ fn execute_single(script_path: &Path, args: &RunArgs) -> Result<()> {
     // Update cache if enabled and we have a project root (skip when tracing)
     if args.cache && !tracing {
         if let Some(root) = project_root {
-            if let Err(e) = update_cache(root, effective_script, &result, working_dir.as_deref()) {
+            if let Err(e) = update_cache(
+                root,
+                effective_script,
+                &result,
+                working_dir.as_deref(),
+                engine.as_ref(),
+            ) {
                 // Log warning but don't fail execution
                 if !args.quiet && format == OutputFormat::Human {
                     eprintln!("Warning: Failed to update cache: {}", e);
@@ -768,10 +1205,19 @@ fn execute_single(script_path: &Path, args: &RunArgs) -> Result<()> {
         }
     }
 
-    // Log retention: --log moves it aside; otherwise internal — removed on
-    // success, kept on failure so the path printed below resolves.
-    result.log_file = log_policy(&project, args.log.clone())
-        .finalize(&result.log_file, result.success)
+    // A passing run's log is what the next failure gets diffed against
+    // (--diff-previous). A traced log isn't comparable.
+    if result.success && !tracing {
+        if let Some(root) = project_root {
+            let _ = crate::executor::previous_log::save(root, effective_script, &result.log_file);
+        }
+    }
+
+    // Log retention: --log, --log-dir and --log-name move it aside; otherwise
+    // internal — removed on success, kept on failure so the path printed below
+    // resolves.
+    result.log_file = log_policy(&project, args, args.log.clone())?
+        .finalize_for(effective_script, &result.log_file, result.success)
         .unwrap_or_default();
 
     // Build output
@@ -787,6 +1233,8 @@ fn execute_single(script_path: &Path, args: &RunArgs) -> Result<()> {
 
     // Handle output based on format
     match format {
+        // The stream already reported the run
+        OutputFormat::Json if args.format.is_streamed() => {}
         OutputFormat::Json => {
             print_json_output(&result, script_path, CodeSource::File, args.profile)?;
         }
@@ -807,7 +1255,7 @@ fn execute_single(script_path: &Path, args: &RunArgs) -> Result<()> {
                     result.duration.as_secs_f64()
                 );
                 if let Some(error) = result.errors.first() {
-                    print_error_details(error);
+                    print_error_details(error, &result.error_trace);
                 }
                 // Failure keeps its log — always say where it is (CI/batch too).
                 // Streaming shows the log's content, not the kept file's path.
@@ -825,12 +1273,20 @@ fn execute_single(script_path: &Path, args: &RunArgs) -> Result<()> {
                         FAILURE_CONTEXT_LINES
                     };
                     if let Ok(raw) = crate::executor::log_reader::read_full_log(&result.log_file) {
-                        let clean = crate::executor::log_reader::strip_boilerplate(&raw);
+                        let mut clean = crate::executor::log_reader::strip_boilerplate(&raw);
+                        if let Some(view) = executor.trace() {
+                            clean = crate::executor::trace::split(&clean, view).0;
+                        }
                         if !clean.is_empty() {
                             print_log_context_n(&clean, context_lines);
                         }
                     }
                 }
+                if args.diff_previous {
+                    print_previous_diff(project_root, effective_script, &result.log_file);
+                }
+                print_post_mortem(args, &result);
+                open_failed_log(args, &result.log_file);
             } else if !verbosity.is_quiet() {
                 eprintln!(
                     "\x1b[32mPASS\x1b[0m  {}  ({:.2}s)",
@@ -851,7 +1307,117 @@ fn execute_single(script_path: &Path, args: &RunArgs) -> Result<()> {
     // process::exit skips destructors — drop explicitly so the trace
     // TempScript cleans up its wrapper and log.
     drop(_trace_temp_script);
-    process::exit(result.exit_code);
+    crate::session::exit(result.exit_code);
+}
+
+/// `--trace-filter` and `--trace-output`, starting the trace file afresh.
+/// The executor appends each script's trace to it.
+fn trace_view(args: &RunArgs) -> Result<Option<TraceView>> {
+    if args.trace.is_none() || (args.trace_filter.is_none() && args.trace_output.is_none()) {
+        return Ok(None);
+    }
+    if let Some(path) = &args.trace_output {
+        std::fs::write(path, "").map_err(|e| {
+            Error::Config(format!(
+                "Cannot write the trace to {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+    }
+    Ok(Some(TraceView {
+        filter: args.trace_filter.unwrap_or_default(),
+        base_dir: None,
+        output: args.trace_output.clone(),
+    }))
+}
+
+/// `--post-mortem`: the data the failed script left behind, as described
+/// by a fresh Stata
+fn print_post_mortem(args: &RunArgs, result: &crate::executor::ExecutionResult) {
+    if !args.post_mortem || result.success {
+        return;
+    }
+    let Some(post_mortem) = &result.post_mortem else {
+        eprintln!(
+            "\n   No data to describe: save a checkpoint with \
+             `save \"$STACY_CHECKPOINT\", replace`"
+        );
+        return;
+    };
+    if post_mortem.checkpoint {
+        eprintln!("\n   State at the last checkpoint:");
+    } else {
+        eprintln!(
+            "\n   State of {}, the last file saved:",
+            post_mortem.dataset.display()
+        );
+    }
+    for line in post_mortem.output.lines() {
+        eprintln!("   {}", line);
+    }
+}
+
+/// `--open-log`, else `[ui] open_log_on_failure`: open a failed run's log
+/// at the error line
+fn open_failed_log(args: &RunArgs, log: &Path) {
+    if args.no_open_log {
+        return;
+    }
+    let viewer = args.open_log.or_else(|| {
+        crate::project::user_config::load_user_config()
+            .ok()
+            .flatten()
+            .and_then(|config| config.ui.open_log_on_failure)
+    });
+    if let Some(viewer) = viewer {
+        crate::executor::open_log::open(log, viewer);
+    }
+}
+
+/// `--diff-previous`: what the end of the failed log looks like next to the
+/// last successful run of the script
+fn print_previous_diff(project_root: Option<&Path>, script: &Path, log: &Path) {
+    use crate::executor::previous_log::{diff_tail, load, DIFF_WINDOW};
+
+    let Some(root) = project_root else {
+        eprintln!("\n   No previous log: --diff-previous needs a stacy project");
+        return;
+    };
+    let Some(previous) = load(root, script) else {
+        eprintln!(
+            "\n   No earlier successful run of {} to compare with",
+            script.display()
+        );
+        return;
+    };
+    let Ok(raw) = crate::executor::log_reader::read_full_log(log) else {
+        return;
+    };
+    let current = crate::executor::log_reader::strip_boilerplate(&raw);
+    let Some(diff) = diff_tail(&previous, &current, DIFF_WINDOW) else {
+        eprintln!(
+            "\n   No differences from the last successful run in the last {} lines",
+            DIFF_WINDOW
+        );
+        return;
+    };
+
+    eprintln!("\n   Changes since the last successful run:\n");
+    let color = std::io::stderr().is_terminal();
+    for line in diff.lines() {
+        let code = match line.chars().next() {
+            _ if !color => None,
+            Some('+') => Some("32"),
+            Some('-') => Some("31"),
+            Some('@') => Some("36"),
+            _ => None,
+        };
+        match code {
+            Some(code) => eprintln!("   \x1b[{}m{}\x1b[0m", code, line),
+            None => eprintln!("   {}", line),
+        }
+    }
 }
 
 /// Update the build cache after execution
@@ -860,68 +1426,41 @@ fn update_cache(
     script_path: &Path,
     result: &crate::executor::ExecutionResult,
     working_dir: Option<&Path>,
+    engine: Option<&EngineIdentity>,
 ) -> Result<()> {
-    let mut cache = BuildCache::load(project_root)?;
-
     // Compute hashes
     let hashes = hash_dependency_tree(script_path)?;
     let lockfile_hash = hash_lockfile(project_root)?;
     let working_dir_hash = hash_working_dir(working_dir);
 
-    // Convert errors to cached format
-    let cached_errors: Vec<CachedError> = result
-        .errors
-        .iter()
-        .map(|e| match e {
-            crate::error::StataError::StataCode {
-                r_code,
-                message,
-                line_number,
-                ..
-            } => CachedError {
-                error_type: "StataCode".to_string(),
-                r_code: Some(*r_code),
-                message: message.clone(),
-                line_number: *line_number,
-            },
-            crate::error::StataError::ProcessKilled { exit_code } => CachedError {
-                error_type: "ProcessKilled".to_string(),
-                r_code: None,
-                message: format!("Process killed with exit code {}", exit_code),
-                line_number: None,
-            },
-        })
-        .collect();
-
     // Create cache entry
     let entry = CacheEntry::with_working_dir(
         hashes.script_hash,
         hashes.dependency_hashes,
         lockfile_hash,
         working_dir_hash,
-        CachedResult {
-            exit_code: result.exit_code,
-            success: result.success,
-            duration_secs: result.duration.as_secs_f64(),
-            errors: cached_errors,
-        },
-    );
-
-    cache.insert(script_path, entry);
-    cache.save(project_root)?;
+        CachedResult::from_execution(result),
+    )
+    .with_engine(engine)
+    .with_data(hash_data_dependencies(script_path, working_dir)?);
 
-    Ok(())
+    // Merged into the cache as it is now: another run may have saved since
+    BuildCache::update(project_root, |cache| cache.insert(script_path, entry))
 }
 
 /// Execute multiple scripts sequentially (fail-fast)
 fn execute_sequential(args: &RunArgs) -> Result<()> {
     use crate::executor::StataExecutor;
-    use std::process;
 
-    let format = args.format;
+    let format = args.format.output();
     let scripts = &args.scripts;
 
-    let verbosity = resolve_verbosity(args.quiet, args.verbose, format);
+    // --summary runs the scripts silently and prints only the final table
+    let verbosity = if args.summary {
+        crate::executor::verbosity::Verbosity::Quiet
+    } else {
+        resolve_verbosity(args.quiet, args.verbose, format)
+    };
 
     // Resolve working directories and check all scripts exist first
     let mut resolved_scripts: Vec<(PathBuf, Option<PathBuf>)> = Vec::new();
@@ -931,7 +1470,7 @@ fn execute_sequential(args: &RunArgs) -> Result<()> {
             if !args.quiet && format == OutputFormat::Human {
                 eprintln!("Error: Script not found: {}", script.display());
             }
-            process::exit(3);
+            crate::session::exit(3);
         }
         resolved_scripts.push((abs_script, work_dir));
     }
@@ -941,12 +1480,19 @@ fn execute_sequential(args: &RunArgs) -> Result<()> {
     let local_ado_paths = resolve_local_ado_paths(&project);
     let engine_ref = args.engine.as_deref();
     let executor = StataExecutor::try_new(engine_ref, verbosity)?
+        .with_events(args.format.is_events())
+        .with_problems(args.format.is_problems())
         .with_allow_global(args.allow_global)
         .with_local_ado_paths(local_ado_paths)
         .with_timeout(args.timeout.map(Duration::from_secs))
-        .with_verify_packages(!args.no_verify);
+        .with_verify_packages(!args.no_verify)
+        .with_backend(args.backend)
+        .with_processors(resolve_processors(args, &project))
+        .with_reproducibility(resolve_reproducibility(&project))
+        .with_trace(trace_view(args)?)
+        .with_post_mortem(args.post_mortem);
     let project_root = project.as_ref().map(|p| p.root.as_path());
-    let policy = log_policy(&project, None);
+    let policy = log_policy(&project, args, None)?;
 
     let start = Instant::now();
     let mut results: Vec<ScriptRunResult> = Vec::new();
@@ -955,20 +1501,21 @@ fn execute_sequential(args: &RunArgs) -> Result<()> {
         eprintln!("Running {} scripts sequentially...\n", scripts.len());
     }
 
-    // Execute scripts sequentially, fail-fast on error
+    // Execute scripts sequentially, fail-fast on error unless --keep-going
     for (i, script) in scripts.iter().enumerate() {
         let (ref abs_script, ref work_dir) = resolved_scripts[i];
 
         // When tracing, read file, prepend trace commands, run via TempScript
         let mut _trace_temp_script: Option<TempScript> = None;
         let result = if let Some(depth) = args.trace {
-            let original_code = std::fs::read_to_string(abs_script).map_err(|e| {
-                crate::error::Error::Config(format!(
-                    "Cannot read script for tracing: {}: {}",
-                    abs_script.display(),
-                    e
-                ))
-            })?;
+            let (original_code, _) =
+                crate::utils::encoding::read_text(abs_script).map_err(|e| {
+                    crate::error::Error::Config(format!(
+                        "Cannot read script for tracing: {}: {}",
+                        abs_script.display(),
+                        e
+                    ))
+                })?;
             let traced_code = prepend_trace(&original_code, depth);
             let temp_dir = work_dir
                 .as_deref()
@@ -987,9 +1534,10 @@ fn execute_sequential(args: &RunArgs) -> Result<()> {
             executor.run(script, project_root)?
         };
 
-        // Log retention: internal file — removed on success, kept on failure.
+        // Log retention: internal file — removed on success, kept on failure —
+        // unless --log-dir or --log-name asked for it.
         let final_log = policy
-            .finalize(&result.log_file, result.success)
+            .finalize_for(script, &result.log_file, result.success)
             .unwrap_or_default();
 
         let script_result = ScriptRunResult {
@@ -999,7 +1547,10 @@ fn execute_sequential(args: &RunArgs) -> Result<()> {
             duration_secs: result.duration.as_secs_f64(),
             log_file: final_log,
             error_message: if !result.success {
-                result.errors.first().map(format_stata_error)
+                result
+                    .errors
+                    .first()
+                    .map(|e| format_stata_error(e, result.error_location()))
             } else {
                 None
             },
@@ -1008,13 +1559,14 @@ fn execute_sequential(args: &RunArgs) -> Result<()> {
         // Print progress in human mode
         if !verbosity.is_quiet() && format == OutputFormat::Human {
             print_script_result(&script_result, i + 1, scripts.len());
+            print_post_mortem(args, &result);
         }
 
         let failed = !script_result.success;
         results.push(script_result);
 
         // Fail-fast: stop on first error
-        if failed {
+        if failed && !args.keep_going {
             break;
         }
     }
@@ -1041,6 +1593,8 @@ fn execute_sequential(args: &RunArgs) -> Result<()> {
 
     // Handle output based on format
     match format {
+        // The stream already reported the run
+        OutputFormat::Json if args.format.is_streamed() => {}
         OutputFormat::Json => {
             println!("{}", output.to_json());
         }
@@ -1048,34 +1602,43 @@ fn execute_sequential(args: &RunArgs) -> Result<()> {
             println!("{}", output.to_stata());
         }
         OutputFormat::Human => {
-            if !verbosity.is_quiet() {
+            if args.summary {
+                print_summary_table(&output);
+            } else if !verbosity.is_quiet() {
                 print_summary(&output);
             } else if failed > 0 {
                 eprintln!("{} of {} scripts failed", failed, output.total);
             }
+            if let Some(first) = output.scripts.iter().find(|r| !r.success) {
+                open_failed_log(args, &first.log_file);
+            }
         }
     }
 
-    process::exit(exit_code);
+    crate::session::exit(exit_code);
 }
 
 /// Execute multiple scripts in parallel
 fn execute_parallel(args: &RunArgs) -> Result<()> {
     use crate::executor::{verbosity::Verbosity, StataExecutor};
-    use std::process;
     use std::sync::mpsc;
 
-    let format = args.format;
+    let format = args.format.output();
     let scripts = &args.scripts;
     let total_scripts = scripts.len();
 
-    // Verbose mode disabled with parallel (log interleaving would be unreadable)
-    if args.verbose > 0 && !args.quiet && format == OutputFormat::Human {
-        eprintln!("Warning: --verbose is ignored with --parallel (logs would interleave)");
-    }
-
-    // Always use quiet verbosity for parallel execution
-    let verbosity = Verbosity::Quiet;
+    // With -v the logs stream live, every line prefixed with its script's
+    // name; otherwise (and with --no-interleave) each script's output is
+    // printed as one block when it finishes
+    let interleave =
+        args.verbose > 0 && !args.quiet && format == OutputFormat::Human && !args.no_interleave;
+    // Progress and script output; --summary prints just the final table
+    let chatty = !args.quiet && !args.summary && format == OutputFormat::Human;
+    let verbosity = if interleave {
+        resolve_verbosity(args.quiet, args.verbose, format)
+    } else {
+        Verbosity::Quiet
+    };
 
     // Resolve working directories and check all scripts exist first
     let mut resolved_scripts: Vec<(PathBuf, PathBuf, Option<PathBuf>)> = Vec::new();
@@ -1085,7 +1648,7 @@ fn execute_parallel(args: &RunArgs) -> Result<()> {
             if !args.quiet && format == OutputFormat::Human {
                 eprintln!("Error: Script not found: {}", script.display());
             }
-            process::exit(3);
+            crate::session::exit(3);
         }
         resolved_scripts.push((script.clone(), abs_script, work_dir));
     }
@@ -1102,14 +1665,19 @@ fn execute_parallel(args: &RunArgs) -> Result<()> {
     let local_ado_paths = resolve_local_ado_paths(&project);
     let engine_ref = args.engine.as_deref();
     let executor = StataExecutor::try_new(engine_ref, verbosity)?
+        .with_events(args.format.is_events())
+        .with_problems(args.format.is_problems())
         .with_allow_global(args.allow_global)
         .with_local_ado_paths(local_ado_paths)
         .with_timeout(args.timeout.map(Duration::from_secs))
-        .with_verify_packages(!args.no_verify);
+        .with_verify_packages(!args.no_verify)
+        .with_backend(args.backend)
+        .with_processors(resolve_processors(args, &project))
+        .with_reproducibility(resolve_reproducibility(&project));
     let project_root = project.as_ref().map(|p| p.root.as_path());
-    let policy = log_policy(&project, None);
+    let policy = log_policy(&project, args, None)?;
 
-    if !args.quiet && format == OutputFormat::Human {
+    if chatty {
         eprintln!(
             "Running {} scripts in parallel ({} jobs)...\n",
             total_scripts, max_jobs
@@ -1122,15 +1690,20 @@ fn execute_parallel(args: &RunArgs) -> Result<()> {
     let (tx, rx) = mpsc::channel::<ScriptRunResult>();
     let semaphore = Arc::new(Semaphore::new(max_jobs));
 
+    let labels = stream_labels(scripts);
+
     // Spawn all threads
     std::thread::scope(|s| {
-        for (script, abs_script, work_dir) in &resolved_scripts {
+        for ((script, abs_script, work_dir), label) in resolved_scripts.iter().zip(&labels) {
             let tx = tx.clone();
             let semaphore = Arc::clone(&semaphore);
             let executor = &executor;
+            let label = interleave.then(|| label.clone());
 
             s.spawn(move || {
                 let _permit = semaphore.acquire();
+                let labeled = label.map(|label| executor.clone().with_stream_label(label));
+                let executor = labeled.as_ref().unwrap_or(executor);
                 let result = if let Some(ref dir) = work_dir {
                     executor.run_in_dir(abs_script, project_root, dir)
                 } else {
@@ -1145,7 +1718,10 @@ fn execute_parallel(args: &RunArgs) -> Result<()> {
                         duration_secs: result.duration.as_secs_f64(),
                         log_file: result.log_file.clone(),
                         error_message: if !result.success {
-                            result.errors.first().map(format_stata_error)
+                            result
+                                .errors
+                                .first()
+                                .map(|e| format_stata_error(e, result.error_location()))
                         } else {
                             None
                         },
@@ -1176,16 +1752,23 @@ fn execute_parallel(args: &RunArgs) -> Result<()> {
             completed += 1;
 
             // Print progress in human mode (streaming output)
-            if !args.quiet && format == OutputFormat::Human {
+            if chatty {
                 print_script_result(&result, completed, total_scripts);
                 // Script output as a grouped block (status on stderr,
-                // output on stdout) — never interleaved across scripts.
-                if let Ok(raw) = crate::executor::log_reader::read_full_log(&result.log_file) {
-                    let clean = crate::executor::log_reader::strip_boilerplate(&raw);
-                    if !clean.is_empty() {
-                        println!("==> {} <==", result.script.display());
-                        println!("{}", clean);
-                        println!();
+                // output on stdout), unless it already streamed. -v shows
+                // the whole log.
+                if !interleave {
+                    if let Ok(raw) = crate::executor::log_reader::read_full_log(&result.log_file) {
+                        let shown = if args.verbose > 0 {
+                            raw.trim_end().to_string()
+                        } else {
+                            crate::executor::log_reader::strip_boilerplate(&raw)
+                        };
+                        if !shown.is_empty() {
+                            println!("==> {} <==", result.script.display());
+                            println!("{}", shown);
+                            println!();
+                        }
                     }
                 }
             }
@@ -1193,7 +1776,7 @@ fn execute_parallel(args: &RunArgs) -> Result<()> {
             // Log retention: removed on success (the output was shown above),
             // kept on failure.
             result.log_file = policy
-                .finalize(&result.log_file, result.success)
+                .finalize_for(&result.script, &result.log_file, result.success)
                 .unwrap_or_default();
 
             script_results.push(result);
@@ -1226,6 +1809,8 @@ fn execute_parallel(args: &RunArgs) -> Result<()> {
 
         // Handle output based on format
         match format {
+            // The stream already reported the runs
+            OutputFormat::Json if args.format.is_streamed() => {}
             OutputFormat::Json => {
                 println!("{}", output.to_json());
             }
@@ -1233,15 +1818,20 @@ fn execute_parallel(args: &RunArgs) -> Result<()> {
                 println!("{}", output.to_stata());
             }
             OutputFormat::Human => {
-                if !args.quiet {
+                if args.summary {
+                    print_summary_table(&output);
+                } else if !args.quiet {
                     print_summary(&output);
                 } else if failed > 0 {
                     eprintln!("{} of {} scripts failed", failed, output.total);
                 }
+                if let Some(first) = output.scripts.iter().find(|r| !r.success) {
+                    open_failed_log(args, &first.log_file);
+                }
             }
         }
 
-        process::exit(exit_code);
+        crate::session::exit(exit_code);
     });
 
     // This is unreachable due to process::exit above, but needed for type checker
@@ -1249,8 +1839,43 @@ fn execute_parallel(args: &RunArgs) -> Result<()> {
     Ok(())
 }
 
+/// Colors cycled through for the `[script]` prefixes of interleaved logs
+const LABEL_COLORS: &[&str] = &["36", "35", "33", "34", "32", "96", "95", "93"];
+
+/// `[script]` prefixes for interleaved parallel logs, padded to one width
+/// and colored when stdout is a terminal
+fn stream_labels(scripts: &[PathBuf]) -> Vec<String> {
+    let names: Vec<String> = scripts
+        .iter()
+        .map(|script| {
+            script
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| script.display().to_string())
+        })
+        .collect();
+    let width = names.iter().map(|n| n.chars().count()).max().unwrap_or(0);
+    let color = std::io::stdout().is_terminal();
+    names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let pad = " ".repeat(width - name.chars().count() + 1);
+            if color {
+                let code = LABEL_COLORS[i % LABEL_COLORS.len()];
+                format!("\x1b[{}m[{}]\x1b[0m{}", code, name, pad)
+            } else {
+                format!("[{}]{}", name, pad)
+            }
+        })
+        .collect()
+}
+
 /// Format a StataError into a human-readable string
-fn format_stata_error(err: &crate::error::StataError) -> String {
+fn format_stata_error(
+    err: &crate::error::StataError,
+    location: Option<&crate::error::location::SourceLocation>,
+) -> String {
     use crate::error::StataError;
 
     match err {
@@ -1260,9 +1885,12 @@ fn format_stata_error(err: &crate::error::StataError) -> String {
             line_number,
             ..
         } => {
-            let line_info = line_number
-                .map(|l| format!(" at line {}", l))
-                .unwrap_or_default();
+            // The do-file line when known, else the line of the log
+            let line_info = match (location, line_number) {
+                (Some(location), _) => format!(" at {}", location),
+                (None, Some(line)) => format!(" at line {}", line),
+                (None, None) => String::new(),
+            };
             format!("r({}){} - {}", r_code, line_info, message)
         }
         StataError::ProcessKilled { exit_code } => {
@@ -1323,11 +1951,211 @@ fn print_summary(output: &ParallelRunOutput) {
             output.passed, output.failed
         );
     }
+    for script in output.scripts.iter().filter(|r| !r.success) {
+        eprintln!(
+            "  \x1b[31mFAIL\x1b[0m  {} (exit code {})",
+            script.script.display(),
+            script.exit_code
+        );
+    }
     eprintln!("Time:    {:.2}s ({})", output.duration_secs, mode);
 }
 
+/// `--summary`: the table of scripts, on stdout
+fn print_summary_table(output: &ParallelRunOutput) {
+    use crate::cli::format::{summary_table, SummaryRow};
+
+    let rows: Vec<SummaryRow> = output
+        .scripts
+        .iter()
+        .map(|r| SummaryRow {
+            name: r.script.display().to_string(),
+            passed: r.success,
+            cached: false,
+            duration_secs: r.duration_secs,
+            log: Some(r.log_file.clone()),
+        })
+        .collect();
+    print!("{}", summary_table("SCRIPT", &rows, output.duration_secs));
+}
+
 use super::format::print_error_details;
 
+/// `stacy run --check-reproducible`: run the script [`RUNS`] times, each in
+/// its own copy of the project, and compare what the runs wrote
+///
+/// [`RUNS`]: crate::reproducible::RUNS
+fn execute_check_reproducible(args: &RunArgs) -> Result<()> {
+    use crate::executor::{verbosity::Verbosity, StataExecutor};
+    use crate::reproducible::{compare, copy_project, likely_sources, snapshot, RunOutputs, RUNS};
+
+    if args.scripts.len() != 1 {
+        return Err(Error::Config(
+            "--check-reproducible takes a single script".into(),
+        ));
+    }
+    if args.format.is_streamed() {
+        return Err(Error::Config(
+            "--check-reproducible reports once the runs are compared; use --format json".into(),
+        ));
+    }
+    let format = args.format.output();
+    let human = format == OutputFormat::Human;
+    let script = &args.scripts[0];
+    let (abs_script, working_dir) = resolve_working_dir(script, args)?;
+    if !abs_script.is_file() {
+        return Err(Error::Config(format!(
+            "Script not found: {}",
+            script.display()
+        )));
+    }
+
+    // The copy is of the project, or of the script's directory outside one
+    let project = crate::project::Project::find()?;
+    let root = match &project {
+        Some(p) => p.root.clone(),
+        None => abs_script.parent().unwrap_or(Path::new(".")).to_path_buf(),
+    };
+    let root = paths::canonicalize(&root)?;
+    let abs_script = paths::canonicalize(&abs_script)?;
+    let rel_script = abs_script
+        .strip_prefix(&root)
+        .map(Path::to_path_buf)
+        .map_err(|_| {
+            Error::Config(format!(
+                "{} is outside the project at {}; --check-reproducible copies the project",
+                script.display(),
+                root.display()
+            ))
+        })?;
+    // A working directory outside the copied tree falls back to its root
+    let rel_working_dir = paths::canonicalize(&match working_dir {
+        Some(dir) => dir,
+        None => std::env::current_dir()?,
+    })
+    .ok()
+    .and_then(|dir| dir.strip_prefix(&root).ok().map(Path::to_path_buf))
+    .unwrap_or_default();
+
+    let executor = StataExecutor::try_new(args.engine.as_deref(), Verbosity::Quiet)?
+        .with_allow_global(args.allow_global)
+        .with_local_ado_paths(resolve_local_ado_paths(&project))
+        .with_timeout(args.timeout.map(Duration::from_secs))
+        .with_verify_packages(!args.no_verify)
+        .with_backend(args.backend)
+        .with_processors(resolve_processors(args, &project))
+        .with_reproducibility(resolve_reproducibility(&project));
+
+    if human && !args.quiet {
+        eprintln!(
+            "Checking reproducibility: {} ({} runs)",
+            script.display(),
+            RUNS
+        );
+    }
+
+    let mut runs = Vec::with_capacity(RUNS);
+    for run in 1..=RUNS {
+        let sandbox = tempfile::TempDir::with_prefix("stacy-repro-")?;
+        copy_project(&root, sandbox.path())?;
+        let before = snapshot(sandbox.path())?;
+
+        let result = executor.run_in_dir(
+            &sandbox.path().join(&rel_script),
+            Some(sandbox.path()),
+            &sandbox.path().join(&rel_working_dir),
+        )?;
+
+        if !result.success {
+            if human {
+                eprintln!(
+                    "\x1b[31mFAIL\x1b[0m  {}  (run {} of {})",
+                    script.display(),
+                    run,
+                    RUNS
+                );
+                if let Some(error) = result.errors.first() {
+                    print_error_details(error, &result.error_trace);
+                }
+            }
+            // The sandbox goes away with this function; keep the log where
+            // failed logs go
+            if let Some(log) =
+                log_policy(&project, args, None)?.finalize_for(script, &result.log_file, false)
+            {
+                if human && !log.starts_with(sandbox.path()) {
+                    eprintln!("\n   Log: {}", log.display());
+                }
+            }
+            crate::session::exit(result.exit_code);
+        }
+        // Stata's own log is not an output of the script
+        let _ = std::fs::remove_file(&result.log_file);
+
+        if human && !args.quiet {
+            eprintln!("  run {}: {:.2}s", run, result.duration.as_secs_f64());
+        }
+        runs.push(RunOutputs::between(&before, snapshot(sandbox.path())?));
+    }
+
+    let mut report = compare(&runs[0], &runs[1], &args.outputs);
+    if !report.is_reproducible() {
+        report.sources = likely_sources(&abs_script, &resolve_reproducibility(&project));
+    }
+    let reproducible = report.is_reproducible();
+
+    let output = ReproducibilityCheckOutput {
+        script: script.clone(),
+        reproducible,
+        runs: RUNS,
+        outputs: report.outputs,
+        differences: report.differences,
+        sources: report.sources,
+    };
+    match format {
+        OutputFormat::Json => println!("{}", output.to_json()),
+        OutputFormat::Stata => println!("{}", output.to_stata()),
+        OutputFormat::Human => print_reproducibility_report(&output, &root),
+    }
+
+    if !reproducible {
+        crate::session::exit(1);
+    }
+    Ok(())
+}
+
+fn print_reproducibility_report(output: &ReproducibilityCheckOutput, root: &Path) {
+    if output.reproducible {
+        eprintln!(
+            "\x1b[32mREPRODUCIBLE\x1b[0m  {} output{} identical across {} runs",
+            output.outputs.len(),
+            if output.outputs.len() == 1 { "" } else { "s" },
+            output.runs
+        );
+        if output.outputs.is_empty() {
+            eprintln!("   (the script wrote no files; declare outputs with --output)");
+        }
+        return;
+    }
+
+    eprintln!(
+        "\x1b[31mNOT REPRODUCIBLE\x1b[0m  {} of {} outputs differ between runs",
+        output.differences.len(),
+        output.outputs.len()
+    );
+    for diff in &output.differences {
+        eprintln!("   {:<12} {}", diff.kind.to_string(), diff.path.display());
+    }
+    if !output.sources.is_empty() {
+        eprintln!();
+        eprintln!("Likely sources:");
+        for source in &output.sources {
+            let file = source.file.strip_prefix(root).unwrap_or(&source.file);
+            eprintln!("   {}:{}  {}", file.display(), source.line, source.reason);
+        }
+    }
+}
+
 /// Print machine-readable JSON output (includes full error details)
 fn print_json_output(
     result: &crate::executor::ExecutionResult,
@@ -1348,17 +2176,33 @@ fn print_json_output(
         "duration_secs": result.duration.as_secs_f64(),
         "log_file": result.log_file.display().to_string(),
         "error_count": result.errors.len(),
-        "errors": result.errors.iter().map(|e| {
-            json!({
-                "type": format!("{:?}", e),
-                "r_code": match e {
-                    crate::error::StataError::StataCode { r_code, .. } => Some(r_code),
-                    _ => None,
-                },
-            })
-        }).collect::<Vec<_>>(),
+        "errors": errors_json(result),
     });
 
+    // The data the failed script left behind (--post-mortem)
+    if let Some(post_mortem) = &result.post_mortem {
+        output["post_mortem"] = json!({
+            "dataset": post_mortem.dataset.display().to_string(),
+            "checkpoint": post_mortem.checkpoint,
+            "output": post_mortem.output,
+        });
+    }
+
+    // What ran before the script ([reproducibility], --processors)
+    if !result.preamble.is_empty() {
+        output["preamble"] = json!(result.preamble);
+    }
+
+    // Packages the script installed into its throwaway PLUS (strict mode)
+    if !result.discarded_installs.is_empty() {
+        output["discarded_installs"] = json!(result.discarded_installs);
+    }
+
+    // adopath/sysdir set commands that reached past the locked packages
+    if !result.adopath_changes.is_empty() {
+        output["adopath_changes"] = json!(result.adopath_changes);
+    }
+
     // Add metrics if profiling enabled
     if include_metrics {
         if let Some(ref metrics) = result.metrics {
@@ -1370,6 +2214,37 @@ fn print_json_output(
     Ok(())
 }
 
+/// The `errors` array of `--format json`
+fn errors_json(result: &crate::executor::ExecutionResult) -> Vec<serde_json::Value> {
+    use serde_json::json;
+
+    result
+        .errors
+        .iter()
+        .enumerate()
+        .map(|(i, e)| {
+            let mut error = json!({
+                "type": format!("{:?}", e),
+                "r_code": match e {
+                    crate::error::StataError::StataCode { r_code, .. } => Some(r_code),
+                    _ => None,
+                },
+            });
+            // The do-file line of the failing command and the nested do-files
+            // that led there, for the first error
+            if let Some(location) = result.error_location().filter(|_| i == 0) {
+                error["location"] = json!(location.to_string());
+                error["trace"] = json!(result
+                    .error_trace
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>());
+            }
+            error
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1462,7 +2337,7 @@ mod tests {
             line_number: Some(42),
         };
 
-        let formatted = format_stata_error(&err);
+        let formatted = format_stata_error(&err, None);
         assert!(formatted.contains("r(198)"));
         assert!(formatted.contains("at line 42"));
         assert!(formatted.contains("invalid syntax"));
@@ -1479,7 +2354,7 @@ mod tests {
             line_number: None,
         };
 
-        let formatted = format_stata_error(&err);
+        let formatted = format_stata_error(&err, None);
         assert!(formatted.contains("r(111)"));
         assert!(!formatted.contains("at line"));
         assert!(formatted.contains("observation out of range"));
@@ -1491,7 +2366,7 @@ mod tests {
 
         let err = StataError::ProcessKilled { exit_code: 137 };
 
-        let formatted = format_stata_error(&err);
+        let formatted = format_stata_error(&err, None);
         assert!(formatted.contains("Process killed"));
         assert!(formatted.contains("137"));
     }
@@ -1589,4 +2464,67 @@ mod tests {
         assert!(result.starts_with("set trace on\nset tracedepth 1\n"));
         assert!(result.ends_with("sysuse auto, clear\nsummarize price"));
     }
+
+    // =========================================================================
+    // stream_labels tests
+    // =========================================================================
+
+    #[test]
+    fn test_stream_labels_padded_to_one_width() {
+        // stdout is not a terminal under the test harness: no color
+        let labels = stream_labels(&[
+            PathBuf::from("analysis/clean.do"),
+            PathBuf::from("regressions.do"),
+        ]);
+        assert_eq!(labels, vec!["[clean.do]       ", "[regressions.do] "]);
+    }
+
+    // =========================================================================
+    // errors_json tests
+    // =========================================================================
+
+    #[test]
+    fn test_errors_json_trace_for_first_error() {
+        use crate::error::location::SourceLocation;
+        use crate::error::{ErrorType, StataError};
+
+        let error = |r_code| StataError::new(ErrorType::StataError, "failed".to_string(), r_code);
+        let result = crate::executor::ExecutionResult {
+            exit_code: 1,
+            success: false,
+            duration: Duration::from_secs(1),
+            log_file: PathBuf::from("main.log"),
+            errors: vec![error(111), error(198)],
+            parse_duration: Duration::ZERO,
+            metrics: None,
+            preamble: vec![],
+            discarded_installs: vec![],
+            adopath_changes: vec![],
+            error_trace: vec![
+                SourceLocation {
+                    file: PathBuf::from("main.do"),
+                    line: 3,
+                },
+                SourceLocation {
+                    file: PathBuf::from("helpers/prep.do"),
+                    line: 12,
+                },
+            ],
+            post_mortem: None,
+        };
+        let errors = errors_json(&result);
+        assert_eq!(errors[0]["r_code"], 111);
+        assert_eq!(errors[0]["location"], "helpers/prep.do:12");
+        assert_eq!(
+            errors[0]["trace"],
+            serde_json::json!(["main.do:3", "helpers/prep.do:12"])
+        );
+        assert!(errors[1].get("trace").is_none());
+
+        let result = crate::executor::ExecutionResult {
+            error_trace: Vec::new(),
+            ..result
+        };
+        assert!(errors_json(&result)[0].get("trace").is_none());
+    }
 }
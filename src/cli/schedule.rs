@@ -0,0 +1,162 @@
+//! `stacy schedule` command implementation
+//!
+//! - `stacy schedule list` shows the tasks that declare a `schedule`
+//! - `stacy schedule install` writes cron, launchd or systemd entries that
+//!   run them, replacing the project's earlier ones
+//! - `stacy schedule uninstall` removes the project's entries
+
+use crate::error::{Error, Result};
+use crate::project::Project;
+use crate::schedule::{self, Backend, Invocation, ScheduledTask, SCHEDULE_DIR};
+use clap::{Args, Subcommand};
+use std::path::Path;
+
+#[derive(Args)]
+#[command(about = "Run tasks on their schedule with cron, launchd or systemd", long_about = None)]
+pub struct ScheduleArgs {
+    #[command(subcommand)]
+    pub command: ScheduleCommand,
+}
+
+#[derive(Subcommand)]
+pub enum ScheduleCommand {
+    /// Show the tasks that declare a schedule
+    List,
+    /// Write the scheduler entries that run the tasks on their schedule
+    #[command(after_help = "\
+Examples:
+  stacy schedule install                      cron (launchd on macOS)
+  stacy schedule install --backend systemd    systemd user timers
+  stacy schedule install --dry-run            Print the entries only
+
+Declare a schedule on a task in stacy.toml:
+  nightly = { script = \"src/refresh.do\", schedule = \"0 3 * * *\" }")]
+    Install(InstallArgs),
+    /// Remove the project's scheduler entries
+    Uninstall(UninstallArgs),
+}
+
+#[derive(Args)]
+pub struct InstallArgs {
+    /// Scheduler to write entries for (default: launchd on macOS, else cron)
+    #[arg(long, value_enum)]
+    pub backend: Option<Backend>,
+
+    /// Print the entries instead of installing them
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Args)]
+pub struct UninstallArgs {
+    /// Scheduler to remove entries from (default: launchd on macOS, else cron)
+    #[arg(long, value_enum)]
+    pub backend: Option<Backend>,
+}
+
+/// Execute the schedule command
+pub fn execute(args: &ScheduleArgs) -> Result<()> {
+    let project = Project::find()?.ok_or(Error::ProjectNotFound)?;
+    let config = project.config.clone().unwrap_or_default();
+    let tasks = schedule::scheduled_tasks(&config.scripts)?;
+
+    match &args.command {
+        ScheduleCommand::List => {
+            if tasks.is_empty() {
+                print_none();
+                return Ok(());
+            }
+            println!("{:<15} {:<20} LOG", "TASK", "SCHEDULE");
+            for task in &tasks {
+                println!(
+                    "{:<15} {:<20} {}",
+                    task.task,
+                    task.schedule.expression,
+                    Path::new(SCHEDULE_DIR)
+                        .join(format!("{}.log", task.task))
+                        .display()
+                );
+            }
+            Ok(())
+        }
+        ScheduleCommand::Install(install_args) => {
+            let backend = install_args
+                .backend
+                .unwrap_or_else(Backend::default_for_platform);
+            let invocation = Invocation::current(&project.root, crate::project::profile::active());
+            if install_args.dry_run {
+                return print_entries(backend, &invocation, &tasks);
+            }
+            if tasks.is_empty() {
+                print_none();
+                let removed = schedule::uninstall(backend, &project.root)?;
+                if removed > 0 {
+                    println!(
+                        "Removed {} earlier {} {}.",
+                        removed,
+                        backend,
+                        entries(removed)
+                    );
+                }
+                return Ok(());
+            }
+            schedule::install(backend, &invocation, &tasks)?;
+            println!("Scheduled {} task(s) with {}:", tasks.len(), backend);
+            for task in &tasks {
+                println!("  {:<15} {}", task.task, task.schedule.expression);
+            }
+            println!(
+                "Output of each run is appended to {}/<task>.log.",
+                SCHEDULE_DIR
+            );
+            Ok(())
+        }
+        ScheduleCommand::Uninstall(uninstall_args) => {
+            let backend = uninstall_args
+                .backend
+                .unwrap_or_else(Backend::default_for_platform);
+            let removed = schedule::uninstall(backend, &project.root)?;
+            if removed == 0 {
+                println!("No {} entries were installed for this project.", backend);
+            } else {
+                println!("Removed {} {} {}.", removed, backend, entries(removed));
+            }
+            Ok(())
+        }
+    }
+}
+
+fn entries(count: usize) -> &'static str {
+    if count == 1 {
+        "entry"
+    } else {
+        "entries"
+    }
+}
+
+fn print_none() {
+    println!("No task declares a schedule. Add one to a task in stacy.toml:");
+    println!();
+    println!("  [scripts]");
+    println!("  nightly = {{ script = \"src/refresh.do\", schedule = \"0 3 * * *\" }}");
+}
+
+/// `--dry-run`: what `install` would write
+fn print_entries(backend: Backend, invocation: &Invocation, tasks: &[ScheduledTask]) -> Result<()> {
+    if backend == Backend::Cron {
+        println!("# Lines for `crontab -e`:");
+        print!("{}", schedule::crontab_block(invocation, tasks));
+        return Ok(());
+    }
+    for (i, (path, contents)) in schedule::unit_files(backend, invocation, tasks)?
+        .iter()
+        .enumerate()
+    {
+        if i > 0 {
+            println!();
+        }
+        println!("# {}", path.display());
+        print!("{}", contents);
+    }
+    Ok(())
+}
@@ -0,0 +1,99 @@
+//! `stacy search` command implementation
+//!
+//! Searches the SSC package listing by name and description. Reads the local
+//! SSC index (see `packages::ssc_index`), downloading it first when it is
+//! missing or more than a week old.
+
+use crate::cli::output_format::{format_stata_local, format_stata_scalar_usize, OutputFormat};
+use crate::error::Result;
+use crate::packages::ssc_index;
+use clap::Args;
+
+#[derive(Args)]
+#[command(after_help = "\
+Examples:
+  stacy search reghdfe                    Find a package by name
+  stacy search regression tables          Packages whose description has both words
+  stacy search fixed effects --limit 5    Show only the 5 best matches")]
+pub struct SearchArgs {
+    /// Words to look for in package names and descriptions
+    #[arg(required = true, num_args = 1..)]
+    pub query: Vec<String>,
+
+    /// Show at most this many packages
+    #[arg(long, value_name = "N", default_value_t = 20)]
+    pub limit: usize,
+
+    /// Output format: human (default), json, or stata
+    #[arg(long, value_enum, default_value = "human")]
+    pub format: OutputFormat,
+}
+
+pub fn execute(args: &SearchArgs) -> Result<()> {
+    let query = args.query.join(" ");
+    let format = args.format;
+    let index = ssc_index::ensure_fresh(|| {
+        if format == OutputFormat::Human {
+            eprintln!("Updating the SSC package index...");
+        }
+    })?;
+    if !index.is_fresh() && format == OutputFormat::Human {
+        eprintln!("warning: the SSC index is out of date; results may miss recent packages");
+    }
+
+    let matches = index.search(&query);
+    let shown = &matches[..matches.len().min(args.limit)];
+
+    match format {
+        OutputFormat::Human => {
+            if matches.is_empty() {
+                println!("No SSC packages match '{}'.", query);
+                return Ok(());
+            }
+            let width = shown.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+            for (name, entry) in shown {
+                println!("{:width$}  {}", name, entry.description, width = width);
+            }
+            if matches.len() > shown.len() {
+                println!();
+                println!(
+                    "{} more; narrow the search or raise --limit",
+                    matches.len() - shown.len()
+                );
+            }
+            println!();
+            println!("Add one with: stacy add <package>");
+        }
+        OutputFormat::Json => {
+            use serde_json::json;
+            let packages: Vec<_> = shown
+                .iter()
+                .map(|(name, entry)| {
+                    json!({
+                        "name": name,
+                        "description": entry.description,
+                    })
+                })
+                .collect();
+            let output = json!({
+                "status": "success",
+                "query": query,
+                "match_count": matches.len(),
+                "packages": packages,
+                "index_fetched_at": index.fetched_at_unix,
+            });
+            println!("{}", serde_json::to_string_pretty(&output).unwrap());
+        }
+        OutputFormat::Stata => {
+            let names: Vec<&str> = shown.iter().map(|(name, _)| *name).collect();
+            println!("{}", format_stata_local("status", "success"));
+            println!(
+                "{}",
+                format_stata_scalar_usize("match_count", matches.len())
+            );
+            println!("{}", format_stata_local("packages", &names.join(" ")));
+        }
+    }
+
+    Ok(())
+}
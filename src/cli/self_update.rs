@@ -0,0 +1,170 @@
+//! `stacy self` command implementation
+//!
+//! Manages the stacy installation itself:
+//! - `stacy self update` replaces the binary with a published release
+//! - `stacy self verify` checks the installed binary against its signed release
+
+use crate::cache::hash::hash_file;
+use crate::error::{Error, Result};
+use crate::packages::http::StacyHttpClient;
+use crate::self_update;
+use crate::update_check::{self, compare_versions, detect_install_method, InstallMethod};
+use clap::{Args, Subcommand};
+
+#[derive(Args)]
+#[command(about = "Manage the stacy installation", long_about = None)]
+pub struct SelfArgs {
+    #[command(subcommand)]
+    pub command: SelfCommand,
+}
+
+#[derive(Subcommand)]
+pub enum SelfCommand {
+    /// Update stacy to the latest release on the configured channel (or a
+    /// pinned version)
+    #[command(after_help = "\
+Examples:
+  stacy self update                  Update to the latest release
+  stacy self update --check          Only report whether an update exists
+  stacy self update --version 0.4.0  Install a specific version")]
+    Update(UpdateArgs),
+
+    /// Check that the installed binary is the one its signed release published
+    #[command(after_help = "\
+Examples:
+  stacy self verify                  Compare this binary with its release")]
+    Verify,
+}
+
+#[derive(Args)]
+pub struct UpdateArgs {
+    /// Install this version instead of the latest (e.g. 0.4.0)
+    #[arg(long, value_name = "VERSION")]
+    pub version: Option<String>,
+
+    /// Only check for an update, don't install it
+    #[arg(long)]
+    pub check: bool,
+
+    /// Update even if stacy was installed by Homebrew or cargo, or is current
+    #[arg(long)]
+    pub force: bool,
+}
+
+/// Execute the self command
+pub fn execute(args: &SelfArgs) -> Result<()> {
+    match &args.command {
+        SelfCommand::Update(update_args) => execute_update(update_args),
+        SelfCommand::Verify => execute_verify(),
+    }
+}
+
+/// Execute `stacy self update`
+fn execute_update(args: &UpdateArgs) -> Result<()> {
+    if update_check::is_offline() {
+        return Err(Error::Config(
+            "STACY_OFFLINE is set; not contacting the release server".to_string(),
+        ));
+    }
+
+    let current = env!("CARGO_PKG_VERSION");
+    let client = StacyHttpClient::new().with_response_cache(None);
+    let channel = update_check::configured_channel();
+    let release = self_update::fetch_release(&client, args.version.as_deref(), channel)?;
+    let target = release.version().to_string();
+
+    let newer = compare_versions(current, &target);
+    if args.check {
+        if newer {
+            println!("Update available: v{} -> v{}", current, target);
+        } else {
+            println!("stacy v{} is up to date.", current);
+        }
+        return Ok(());
+    }
+
+    if target == current && !args.force {
+        println!("stacy v{} is already installed.", current);
+        return Ok(());
+    }
+    if args.version.is_none() && !newer && !args.force {
+        println!("stacy v{} is up to date.", current);
+        return Ok(());
+    }
+
+    // A package manager owns its binary; replacing it behind its back breaks
+    // the manager's next upgrade.
+    let method = detect_install_method();
+    if method != InstallMethod::Manual && !args.force {
+        return Err(Error::Config(format!(
+            "stacy was installed with {}; update with `{}` (or pass --force)",
+            match method {
+                InstallMethod::Homebrew => "Homebrew",
+                _ => "cargo",
+            },
+            update_check::upgrade_instruction(&method)
+        )));
+    }
+
+    let exe = std::env::current_exe()?;
+    let exe = exe.canonicalize().unwrap_or(exe);
+
+    eprintln!("Downloading stacy v{}...", target);
+    let temp = tempfile::TempDir::new()?;
+    let binary = self_update::download_binary(&client, &release, temp.path())?;
+    self_update::replace_binary(&binary, &exe)?;
+    update_check::clear_cached_update();
+
+    println!(
+        "Updated stacy v{} -> v{} ({})",
+        current,
+        target,
+        exe.display()
+    );
+    Ok(())
+}
+
+/// Execute `stacy self verify`
+fn execute_verify() -> Result<()> {
+    if update_check::is_offline() {
+        return Err(Error::Config(
+            "STACY_OFFLINE is set; not contacting the release server".to_string(),
+        ));
+    }
+    if detect_install_method() == InstallMethod::Cargo {
+        return Err(Error::Config(
+            "stacy was built from source by cargo; there is no release binary to compare it with"
+                .to_string(),
+        ));
+    }
+
+    let current = env!("CARGO_PKG_VERSION");
+    let client = StacyHttpClient::new().with_response_cache(None);
+    let channel = update_check::configured_channel();
+    let release = self_update::fetch_release(&client, Some(current), channel)?;
+
+    let exe = std::env::current_exe()?;
+    let exe = exe.canonicalize().unwrap_or(exe);
+
+    eprintln!("Downloading the stacy v{} release...", current);
+    let temp = tempfile::TempDir::new()?;
+    let published = self_update::download_binary(&client, &release, temp.path())?;
+    let expected = hash_file(&published)?;
+    let actual = hash_file(&exe)?;
+    if expected != actual {
+        return Err(Error::Integrity(format!(
+            "{} does not match the signed stacy v{} release (sha256 {}, release {})",
+            exe.display(),
+            current,
+            actual,
+            expected
+        )));
+    }
+
+    println!(
+        "{} matches the signed stacy v{} release.",
+        exe.display(),
+        current
+    );
+    Ok(())
+}
@@ -0,0 +1,131 @@
+//! `stacy shell` command implementation
+//!
+//! Starts an interactive Stata session that sees exactly the locked packages.
+//! Stata is launched with the `stacy exec` environment and runs a generated
+//! profile do-file first, which sets the adopath to the project's strict
+//! `S_ADO`, changes to the project root and applies `[reproducibility]` and
+//! `[execution]` settings, so exploratory work matches what `stacy run` does.
+
+use crate::cli::exec::project_env;
+use crate::error::{Error, Result};
+use crate::executor::binary::{detect_stata_binary, gui_counterpart, BatchStyle};
+use crate::executor::run_paths::stata_path;
+use crate::project::Project;
+use clap::Args;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[derive(Args)]
+#[command(after_help = "\
+Examples:
+  stacy shell                             Interactive console Stata with the locked packages
+  stacy shell --gui                       The Stata GUI instead
+  stacy shell --allow-global              Include PLUS and PERSONAL in the adopath")]
+pub struct ShellArgs {
+    /// Start the Stata GUI (xstata-mp, StataMP.app) instead of console Stata
+    #[arg(long)]
+    pub gui: bool,
+
+    /// Stata engine to use (overrides config and auto-detection)
+    #[arg(long, value_name = "ENGINE")]
+    pub engine: Option<String>,
+
+    /// Allow globally installed packages (PLUS, PERSONAL, etc.) in the adopath
+    #[arg(long)]
+    pub allow_global: bool,
+
+    /// Skip the check of the package cache against stacy.lock
+    #[arg(long)]
+    pub no_verify: bool,
+}
+
+pub fn execute(args: &ShellArgs) -> Result<()> {
+    let project = Project::find()?.ok_or_else(|| {
+        Error::Config("Not in a stacy project. Run 'stacy init' first.".to_string())
+    })?;
+
+    let console = detect_stata_binary(args.engine.as_deref())?;
+    let binary = if args.gui {
+        gui_counterpart(&console)
+            .filter(|gui| gui.exists())
+            .ok_or_else(|| Error::Config(format!("No Stata GUI found next to {}", console)))?
+    } else {
+        PathBuf::from(&console)
+    };
+
+    let env = project_env(&project, args.allow_global, !args.no_verify)?;
+    let s_ado = env
+        .iter()
+        .find(|(name, _)| *name == "S_ADO")
+        .map(|(_, value)| value.clone())
+        .unwrap_or_default();
+
+    let mut setup = project.reproducibility().preamble();
+    if let Some(n) = project.processors() {
+        crate::cache::engine::check_processors(
+            n,
+            crate::cache::engine::licensed_processors(&console),
+        )?;
+        setup.push(format!("quietly set processors {}", n));
+    }
+
+    let profile_dir = project.root.join(".stacy").join("shell");
+    std::fs::create_dir_all(&profile_dir)?;
+    let profile_path = profile_dir.join("profile.do");
+    std::fs::write(&profile_path, profile_do(&project.root, &s_ado, &setup))?;
+
+    let mut cmd = Command::new(&binary);
+    // Quiet startup, without the batch flag: the session stays open
+    match BatchStyle::for_binary(&binary.to_string_lossy()) {
+        BatchStyle::Unix => cmd.arg("-q"),
+        BatchStyle::Windows => cmd.arg("/q"),
+    };
+    cmd.arg("do").arg(stata_path(&profile_path));
+    cmd.current_dir(&project.root);
+    for (name, value) in env {
+        cmd.env(name, value);
+    }
+
+    if args.gui {
+        cmd.spawn()
+            .map_err(|e| Error::Config(format!("Failed to start {}: {}", binary.display(), e)))?;
+        println!("Started {} with the project adopath.", binary.display());
+        return Ok(());
+    }
+
+    let status = cmd
+        .status()
+        .map_err(|e| Error::Config(format!("Failed to start {}: {}", binary.display(), e)))?;
+    crate::session::exit(status.code().unwrap_or(1));
+}
+
+/// The do-file the session runs first
+fn profile_do(project_root: &Path, s_ado: &str, setup: &[String]) -> String {
+    let mut out = String::new();
+    out.push_str("* Generated by `stacy shell` - do not edit\n");
+    out.push_str(&format!("global S_ADO `\"{}\"'\n", s_ado));
+    out.push_str(&format!("quietly cd `\"{}\"'\n", project_root.display()));
+    for line in setup {
+        out.push_str(line);
+        out.push('\n');
+    }
+    out.push_str("display as text \"stacy: adopath set to the packages in stacy.lock (type {cmd:adopath} to list)\"\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_do_sets_adopath_and_settings() {
+        let profile = profile_do(
+            Path::new("/work/project"),
+            "/cache/estout/1.0.0;BASE",
+            &["set seed 12345".to_string()],
+        );
+        assert!(profile.contains("global S_ADO `\"/cache/estout/1.0.0;BASE\"'\n"));
+        assert!(profile.contains("quietly cd `\"/work/project\"'\n"));
+        assert!(profile.contains("\nset seed 12345\n"));
+    }
+}
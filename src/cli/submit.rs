@@ -0,0 +1,205 @@
+//! `stacy submit` command implementation
+//!
+//! Wraps `stacy run <script>` or `stacy task <name>` in a batch script with
+//! the `[submit]` resources (overridable per submission), submits it to
+//! SLURM or PBS, and records the job for `stacy jobs`. `--dry-run` prints
+//! the script instead.
+
+use crate::cli::output_format::OutputFormat;
+use crate::cli::output_types::{CommandOutput, SubmitOutput};
+use crate::error::{Error, Result};
+use crate::history::unix_now;
+use crate::jobs::{self, scheduler, JobRecord, JOBS_DIR};
+use crate::project::config::Scheduler;
+use crate::project::Project;
+use crate::utils::paths::canonicalize_or_self;
+use clap::{ArgGroup, Args};
+use std::path::{Path, PathBuf};
+
+#[derive(Args)]
+#[command(group(ArgGroup::new("job").required(true).args(["script", "task"])))]
+#[command(after_help = "\
+Examples:
+  stacy submit analysis.do                       Run a script as a batch job
+  stacy submit --task build --time 04:00:00      Run a task, with a longer time limit
+  stacy submit analysis.do --dry-run             Print the batch script only
+
+Resources default to the [submit] section of stacy.toml.")]
+pub struct SubmitArgs {
+    /// Script to run in the job
+    #[arg(value_name = "SCRIPT")]
+    pub script: Option<PathBuf>,
+
+    /// Run this task from stacy.toml instead of a script
+    #[arg(long, value_name = "NAME")]
+    pub task: Option<String>,
+
+    /// Scheduler to submit to (default: [submit] scheduler, or slurm)
+    #[arg(long, value_enum)]
+    pub scheduler: Option<Scheduler>,
+
+    /// SLURM partition or PBS queue
+    #[arg(long, value_name = "NAME")]
+    pub partition: Option<String>,
+
+    /// Wall time limit (`02:00:00`)
+    #[arg(long, value_name = "TIME")]
+    pub time: Option<String>,
+
+    /// Memory (`16G`)
+    #[arg(long, value_name = "SIZE")]
+    pub mem: Option<String>,
+
+    /// CPU cores
+    #[arg(long, value_name = "N")]
+    pub cpus: Option<u32>,
+
+    /// Account to charge the job to
+    #[arg(long, value_name = "NAME")]
+    pub account: Option<String>,
+
+    /// Environment module to load, after those in [submit] (repeatable)
+    #[arg(long = "module", value_name = "NAME")]
+    pub modules: Vec<String>,
+
+    /// Print the batch script instead of submitting it
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Output format: human (default), json, or stata
+    #[arg(long, value_enum, default_value = "human")]
+    pub format: OutputFormat,
+}
+
+/// Execute the submit command
+pub fn execute(args: &SubmitArgs) -> Result<()> {
+    let project = Project::find()?.ok_or(Error::ProjectNotFound)?;
+    let config = project.config.clone().unwrap_or_default();
+
+    let mut resources = config.submit.clone();
+    let scheduler = args.scheduler.or(resources.scheduler).unwrap_or_default();
+    if args.partition.is_some() {
+        resources.partition = args.partition.clone();
+    }
+    if args.time.is_some() {
+        resources.time = args.time.clone();
+    }
+    if args.mem.is_some() {
+        resources.memory = args.mem.clone();
+    }
+    if args.cpus.is_some() {
+        resources.cpus = args.cpus;
+    }
+    if args.account.is_some() {
+        resources.account = args.account.clone();
+    }
+    resources.modules.extend(args.modules.iter().cloned());
+
+    let (name, command) = match (&args.script, &args.task) {
+        (Some(script), _) => {
+            if !script.is_file() {
+                return Err(Error::Config(format!(
+                    "Script not found: {}",
+                    script.display()
+                )));
+            }
+            let name = script
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_else(|| "script".to_string());
+            let path = in_project(&project.root, script);
+            (name, vec!["run".to_string(), path.display().to_string()])
+        }
+        (None, Some(task)) => {
+            if !config.scripts.tasks.contains_key(task) {
+                return Err(Error::Config(format!(
+                    "Unknown task '{}' (stacy task --list shows the tasks)",
+                    task
+                )));
+            }
+            (task.clone(), vec!["task".to_string(), task.clone()])
+        }
+        (None, None) => unreachable!("clap requires a script or --task"),
+    };
+
+    let (date, time) = crate::utils::date::now_utc();
+    let base = format!("{}-{}-{}", name, date, time);
+    let script = Path::new(JOBS_DIR).join(format!("{}.sh", base));
+    let output = Path::new(JOBS_DIR).join(format!("{}.out", base));
+
+    let exe = std::env::current_exe().unwrap_or_else(|_| PathBuf::from("stacy"));
+    let mut argv = vec![exe.display().to_string()];
+    // The job runs with the packages of the profile this was submitted with
+    if let Some(profile) = crate::project::profile::active() {
+        argv.extend(["--env-profile".to_string(), profile.to_string()]);
+    }
+    argv.extend(command.iter().cloned());
+    let text = scheduler::batch_script(
+        scheduler,
+        &format!("stacy-{}", name),
+        &resources,
+        &project.root,
+        &project.root.join(&output),
+        &argv,
+    );
+
+    let mut result = SubmitOutput {
+        job_id: String::new(),
+        scheduler: scheduler.to_string(),
+        command: command.join(" "),
+        script: script.clone(),
+        output: output.clone(),
+        dry_run: args.dry_run,
+    };
+
+    if !args.dry_run {
+        let script_path = project.root.join(&script);
+        std::fs::create_dir_all(project.root.join(JOBS_DIR))?;
+        std::fs::write(&script_path, &text)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&script_path, std::fs::Permissions::from_mode(0o755))?;
+        }
+
+        result.job_id = scheduler::submit(scheduler, &script_path, &project.root)?;
+        let submitted_at = unix_now();
+        jobs::record(
+            &project.root,
+            JobRecord {
+                id: result.job_id.clone(),
+                scheduler,
+                name: format!("stacy-{}", name),
+                command: result.command.clone(),
+                script,
+                output,
+                submitted_at,
+            },
+        )?;
+    }
+
+    match args.format {
+        OutputFormat::Json => println!("{}", result.to_json()),
+        OutputFormat::Stata => println!("{}", result.to_stata()),
+        OutputFormat::Human if args.dry_run => print!("{}", text),
+        OutputFormat::Human => {
+            println!(
+                "Submitted job {} ({}): stacy {}",
+                result.job_id, result.scheduler, result.command
+            );
+            println!("  output: {}", result.output.display());
+            println!("Run `stacy jobs` to see how it is doing.");
+        }
+    }
+    Ok(())
+}
+
+/// `path` relative to the project root if it is inside it, else absolute:
+/// the job runs from the root
+fn in_project(root: &Path, path: &Path) -> PathBuf {
+    let path = canonicalize_or_self(path);
+    let root = canonicalize_or_self(root);
+    path.strip_prefix(&root)
+        .map(Path::to_path_buf)
+        .unwrap_or(path)
+}
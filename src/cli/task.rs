@@ -2,21 +2,26 @@
 //!
 //! Run defined tasks from stacy.toml's `[scripts]` section.
 
-use crate::cli::output_format::{resolve_verbosity, OutputFormat};
+use crate::artifacts::Artifacts;
+use crate::cache::engine::EngineIdentity;
+use crate::cache::step::StepCache;
+use crate::cli::output_format::{resolve_verbosity, OutputFormat, RunFormat};
 use crate::cli::output_types::{
     CommandOutput, ScriptResultOutput, TaskInfo, TaskListOutput, TaskOutput,
 };
 use crate::error::{Error, Result};
 use crate::executor::log_policy::LogPolicy;
 use crate::executor::StataExecutor;
-use crate::packages::lockfile::{load_lockfile, verify_lockfile_sync};
+use crate::packages::lockfile::{check_frozen, frozen_mode, load_lockfile};
 use crate::project::config::TaskDef;
 use crate::project::Project;
-use crate::task::executor::TaskExecutor;
+use crate::task::executor::{TaskExecutor, TaskResult};
+use crate::task::run_state::RunState;
+use crate::task::selection::Selection;
 use crate::task::{task_description, TaskGraph};
 use clap::Args;
 use std::collections::HashMap;
-use std::process;
+use std::path::PathBuf;
 
 #[derive(Args)]
 #[command(after_help = "\
@@ -24,7 +29,14 @@ Examples:
   stacy task build                        Run the 'build' task
   stacy task analyze -- robust=1          Pass arguments to task scripts
   stacy task --list                       List available tasks
-  stacy task build --frozen               Verify lockfile sync before running")]
+  stacy task build --frozen               Verify lockfile sync before running
+  stacy task build --cache                Skip scripts whose inputs are unchanged
+  stacy task reports --keep-going         Run every script, even after a failure
+  stacy task all --resume                 Continue a failed run where it stopped
+  stacy task all --from analyze           Run the task from its 'analyze' step on
+  stacy task all --only tables,figures    Run just the 'tables' and 'figures' steps
+  stacy task nightly --summary            Just a table of scripts, status and logs
+  stacy task build --log-dir logs/ci      Keep each script's log as logs/ci/<script>.log")]
 pub struct TaskArgs {
     /// Task name to run
     #[arg(value_name = "TASK")]
@@ -34,13 +46,56 @@ pub struct TaskArgs {
     #[arg(long, conflicts_with = "task")]
     pub list: bool,
 
-    /// Fail if lockfile doesn't match stacy.toml (for CI reproducibility)
+    /// Fail if lockfile doesn't match stacy.toml (for CI reproducibility).
+    /// Also on with STACY_FROZEN=1 or [install] frozen = true.
     #[arg(long)]
     pub frozen: bool,
 
-    /// Output format: human (default), json, or stata
+    /// Enable build cache (skip scripts whose script, deps and inputs are unchanged)
+    #[arg(long)]
+    pub cache: bool,
+
+    /// Force rebuild even if cached
+    #[arg(long, requires = "cache")]
+    pub force: bool,
+
+    /// Keep running the remaining scripts after one fails. The task still
+    /// fails, with the highest exit code of the failed scripts.
+    #[arg(short, long)]
+    pub keep_going: bool,
+
+    /// Continue the last run of the task from where it failed, skipping the
+    /// scripts it completed (those unchanged since)
+    #[arg(long)]
+    pub resume: bool,
+
+    /// Run the task from this step on, skipping the scripts listed before it
+    #[arg(long, value_name = "STEP")]
+    pub from: Option<String>,
+
+    /// Run only these steps of the task (comma-separated)
+    #[arg(long, value_name = "STEPS", value_delimiter = ',')]
+    pub only: Vec<String>,
+
+    /// Print only a table of the scripts (status, duration, kept log) at
+    /// the end, without their output
+    #[arg(long)]
+    pub summary: bool,
+
+    /// Keep every script's log in this directory, pass or fail, as <script>.log
+    #[arg(long, value_name = "DIR")]
+    pub log_dir: Option<PathBuf>,
+
+    /// Name kept logs after a pattern ({script}, {date}, {time}, {status});
+    /// keeps every log, pass or fail, in --log-dir or [run] log_dir
+    #[arg(long, value_name = "PATTERN")]
+    pub log_name: Option<String>,
+
+    /// Output format: human (default), json, stata, json-lines (one JSON
+    /// event per line while the scripts run), or vscode (one file:line: line
+    /// per error, for editor problem matchers)
     #[arg(long, value_enum, default_value = "human")]
-    pub format: OutputFormat,
+    pub format: RunFormat,
 
     /// Arguments to pass to scripts (after --)
     /// Format: key=value pairs
@@ -49,7 +104,12 @@ pub struct TaskArgs {
 }
 
 pub fn execute(args: &TaskArgs) -> Result<()> {
-    let format = args.format;
+    let format = args.format.output();
+    if args.format.is_table() {
+        return Err(Error::Config(
+            "--format csv and markdown are only for tables, such as `stacy test --list`".into(),
+        ));
+    }
 
     // Find project
     let project = Project::find()?;
@@ -58,54 +118,10 @@ pub fn execute(args: &TaskArgs) -> Result<()> {
     // Get config, using default if none exists
     let config = project.config.clone().unwrap_or_default();
 
-    // If --frozen, verify lockfile is in sync with manifest
-    if args.frozen {
-        let lockfile = load_lockfile(&project.root)?;
-
-        match lockfile {
-            Some(lockfile) => {
-                let config_package_names: Vec<&str> = config
-                    .packages
-                    .all_packages()
-                    .map(|(name, _, _)| name.as_str())
-                    .collect();
-
-                let sync_result = verify_lockfile_sync(&lockfile, &config_package_names);
-
-                if !sync_result.in_sync {
-                    let mut msg =
-                        String::from("Lockfile out of sync with stacy.toml (--frozen mode)\n");
-
-                    if !sync_result.missing_in_lock.is_empty() {
-                        msg.push_str("\n  Missing from stacy.lock:\n");
-                        for name in &sync_result.missing_in_lock {
-                            msg.push_str(&format!("    - {}\n", name));
-                        }
-                    }
-
-                    if !sync_result.extra_in_lock.is_empty() {
-                        msg.push_str("\n  Extra in stacy.lock (not in stacy.toml):\n");
-                        for name in &sync_result.extra_in_lock {
-                            msg.push_str(&format!("    - {}\n", name));
-                        }
-                    }
-
-                    msg.push_str("\n  hint: run `stacy lock` then commit stacy.lock");
-
-                    return Err(Error::Config(msg));
-                }
-            }
-            None => {
-                // No lockfile exists - check if there are packages that should be locked
-                let has_packages = config.packages.all_packages().next().is_some();
-                if has_packages {
-                    return Err(Error::Config(
-                        "No stacy.lock found but stacy.toml has packages (--frozen mode)\n\n  hint: run `stacy lock` then commit stacy.lock".to_string()
-                    ));
-                }
-                // No packages defined, no lockfile needed - this is fine
-            }
-        }
+    // Frozen mode (--frozen, STACY_FROZEN or [install] frozen): verify the
+    // lockfile is in sync with the manifest
+    if frozen_mode(args.frozen, Some(&config)) {
+        check_frozen(load_lockfile(&project.root)?.as_ref(), &config)?;
     }
 
     // Build task graph
@@ -145,7 +161,8 @@ pub fn execute(args: &TaskArgs) -> Result<()> {
             )
         };
 
-        if format.is_machine_readable() {
+        // A stream has nothing to report for a task that doesn't exist
+        if format.is_machine_readable() && !args.format.is_streamed() {
             let output = TaskOutput {
                 task_name: task_name.clone(),
                 success: false,
@@ -164,24 +181,96 @@ pub fn execute(args: &TaskArgs) -> Result<()> {
         } else {
             eprintln!("Error: {}", msg);
         }
-        process::exit(5); // Internal error
+        crate::session::exit(5); // Internal error
     }
 
     // Parse arguments
     let task_args = parse_task_args(&args.args)?;
 
+    let selection = if args.from.is_some() || !args.only.is_empty() {
+        Some(Selection::new(
+            &graph,
+            task_name,
+            args.from.as_deref(),
+            &args.only,
+        )?)
+    } else {
+        None
+    };
+
+    let (run_state, resumed) = RunState::start(&project.root, task_name, &task_args, args.resume)?;
+    if args.resume && format == OutputFormat::Human {
+        match resumed {
+            Some(completed) => eprintln!(
+                "Resuming task '{}': {} script(s) completed in the last run",
+                task_name, completed
+            ),
+            None => eprintln!(
+                "Nothing to resume for task '{}' (its last run succeeded, or used other arguments); running it from the start",
+                task_name
+            ),
+        }
+    }
+
     // Create Stata executor (machine-readable formats suppress streaming, #84)
-    let executor = StataExecutor::try_new(None, resolve_verbosity(false, 0, format))?
-        .with_local_ado_paths(project.resolve_local_ado_paths());
+    let executor = StataExecutor::try_new(None, resolve_verbosity(args.summary, 0, format))?
+        .with_events(args.format.is_events())
+        .with_problems(args.format.is_problems())
+        .with_local_ado_paths(project.resolve_local_ado_paths())
+        .with_processors(project.processors())
+        .with_reproducibility(project.reproducibility());
+
+    let step_cache = if args.cache {
+        Some(StepCache::load(&project.root, args.force)?.with_engine(EngineIdentity::detect(None)))
+    } else {
+        None
+    };
+
+    let artifacts = Artifacts::load(&project.root)?;
 
     // Create task executor. Each script's log follows the same retention rule as
-    // `stacy run`: removed on success, kept (in `[run] log_dir`) on failure (#98).
-    let task_executor = TaskExecutor::new(&graph, &executor, &project.root)
+    // `stacy run`: removed on success, kept (in `[run] log_dir`) on failure (#98),
+    // kept either way with --log-dir or --log-name.
+    let log_policy = LogPolicy::for_project(Some(&project))
+        .with_dir(args.log_dir.clone())
+        .with_name(args.log_name.clone())?;
+    let mut task_executor = TaskExecutor::new(&graph, &executor, &project.root)
         .with_args(task_args)
-        .with_log_policy(LogPolicy::for_project(Some(&project)));
+        .with_log_policy(log_policy)
+        .with_artifacts(&artifacts)
+        .with_keep_going(args.keep_going)
+        .with_run_state(&run_state);
+    if let Some(ref cache) = step_cache {
+        task_executor = task_executor.with_cache(cache);
+    }
+    if let Some(ref selection) = selection {
+        task_executor = task_executor.with_selection(selection);
+    }
 
     // Run the task
     let result = task_executor.execute(task_name)?;
+    let skipped = run_state.skipped();
+    if result.success {
+        if let Err(e) = run_state.finish() {
+            if format == OutputFormat::Human {
+                eprintln!("Warning: Failed to clear the task's run state: {}", e);
+            }
+        }
+    }
+
+    if let Some(ref cache) = step_cache {
+        if let Err(e) = cache.save() {
+            if format == OutputFormat::Human {
+                eprintln!("Warning: Failed to update cache: {}", e);
+            }
+        }
+    }
+
+    if let Err(e) = artifacts.save() {
+        if format == OutputFormat::Human {
+            eprintln!("Warning: Failed to update artifact registry: {}", e);
+        }
+    }
 
     // Build output
     let output = TaskOutput {
@@ -201,18 +290,25 @@ pub fn execute(args: &TaskArgs) -> Result<()> {
                 success: r.success,
                 exit_code: r.exit_code,
                 duration_secs: r.duration.as_secs_f64(),
+                cached: r.cached,
+                log_file: Some(r.log_file.clone()).filter(|log| !log.as_os_str().is_empty()),
             })
             .collect(),
     };
 
     // Output results
     match format {
+        // The stream already reported each script
+        OutputFormat::Json if args.format.is_streamed() => {}
         OutputFormat::Json => {
             println!("{}", output.to_json());
         }
         OutputFormat::Stata => {
             println!("{}", output.to_stata());
         }
+        OutputFormat::Human if args.summary => {
+            print_summary_table(&result, &project.root);
+        }
         OutputFormat::Human => {
             if result.success {
                 println!(
@@ -220,12 +316,19 @@ pub fn execute(args: &TaskArgs) -> Result<()> {
                     task_name,
                     result.duration.as_secs_f64()
                 );
+                let cached = result.script_results.iter().filter(|r| r.cached).count();
                 if result.script_results.len() > 1 {
                     println!(
                         "      {} scripts executed successfully",
                         result.script_results.len()
                     );
                 }
+                if cached > 0 {
+                    println!("      {} unchanged (cached)", cached);
+                }
+                if skipped > 0 {
+                    println!("      {} completed in the last run (resumed)", skipped);
+                }
             } else {
                 eprintln!(
                     "\x1b[31mFAIL\x1b[0m  Task '{}'  ({:.2}s)",
@@ -248,11 +351,43 @@ pub fn execute(args: &TaskArgs) -> Result<()> {
                         );
                     }
                 }
+                if result.success_count() + skipped > 0 {
+                    eprintln!(
+                        "      Continue after the completed scripts: stacy task {} --resume",
+                        task_name
+                    );
+                }
             }
         }
     }
 
-    process::exit(result.exit_code);
+    crate::session::exit(result.exit_code);
+}
+
+/// `--summary`: the table of the task's scripts, on stdout
+fn print_summary_table(result: &TaskResult, project_root: &std::path::Path) {
+    use crate::cli::format::{summary_table, SummaryRow};
+
+    let rows: Vec<SummaryRow> = result
+        .script_results
+        .iter()
+        .map(|r| SummaryRow {
+            name: r
+                .script
+                .strip_prefix(project_root)
+                .unwrap_or(&r.script)
+                .display()
+                .to_string(),
+            passed: r.success,
+            cached: r.cached,
+            duration_secs: r.duration.as_secs_f64(),
+            log: Some(r.log_file.clone()),
+        })
+        .collect();
+    print!(
+        "{}",
+        summary_table("SCRIPT", &rows, result.duration.as_secs_f64())
+    );
 }
 
 /// Execute --list to show available tasks
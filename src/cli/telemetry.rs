@@ -0,0 +1,72 @@
+//! `stacy telemetry` command implementation
+//!
+//! - `stacy telemetry status` shows whether usage metrics are recorded,
+//!   where they go, and exactly what is collected
+
+use crate::error::Result;
+use crate::telemetry;
+use clap::{Args, Subcommand};
+
+#[derive(Args)]
+#[command(about = "Show anonymous usage metrics settings", long_about = None)]
+pub struct TelemetryArgs {
+    #[command(subcommand)]
+    pub command: TelemetryCommand,
+}
+
+#[derive(Subcommand)]
+pub enum TelemetryCommand {
+    /// Show whether telemetry is on and what it collects
+    Status,
+}
+
+/// Execute the telemetry command
+pub fn execute(args: &TelemetryArgs) -> Result<()> {
+    match args.command {
+        TelemetryCommand::Status => print_status(),
+    }
+    Ok(())
+}
+
+fn print_status() {
+    let status = telemetry::status();
+
+    println!(
+        "Telemetry:  {}",
+        if status.enabled {
+            "enabled"
+        } else {
+            "disabled (default)"
+        }
+    );
+    println!(
+        "Endpoint:   {}",
+        status
+            .endpoint
+            .as_deref()
+            .unwrap_or("(none; events stay on this machine)")
+    );
+    if let Some(path) = &status.queue_path {
+        println!(
+            "Queued:     {} event(s) in {}",
+            status.queued,
+            path.display()
+        );
+    }
+    if status.enabled && crate::update_check::is_offline() {
+        println!("            STACY_OFFLINE is set: nothing is recorded or sent");
+    }
+
+    println!();
+    println!("Each stacy invocation records only:");
+    println!("  - the command name (e.g. run, install)");
+    println!("  - how long it took");
+    println!("  - on failure, the error category (e.g. network, syntax_error)");
+    println!("No arguments, paths, package names, or machine identifiers are collected.");
+    println!();
+    if status.enabled {
+        println!("Disable with: stacy config set telemetry.enabled false");
+    } else {
+        println!("Enable with:  stacy config set telemetry.enabled true");
+    }
+}
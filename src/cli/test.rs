@@ -2,20 +2,26 @@
 //!
 //! Run tests by convention from the project directory.
 
-use crate::cli::output_format::OutputFormat;
+use crate::cache::engine::EngineIdentity;
+use crate::cache::step::StepCache;
+use crate::cli::output_format::{OutputFormat, RunFormat};
 use crate::cli::output_types::{
-    CommandOutput, TestInfo, TestListOutput, TestOutput, TestResultOutput,
+    CommandOutput, CsvOutput, FlakyTestOutput, MarkdownOutput, TestInfo, TestListOutput,
+    TestOutput, TestResultOutput,
 };
 use crate::cli::test_output;
 use crate::error::{Error, Result};
 use crate::executor::log_policy::LogPolicy;
 use crate::executor::StataExecutor;
+use crate::packages::lockfile::{check_frozen, frozen_mode, load_lockfile};
 use crate::project::Project;
-use crate::test::discovery::{discover_tests, find_test};
-use crate::test::runner::{TestRunner, TestWorkingDir};
+use crate::test::discovery::{discover_tests, find_test, TestFile};
+use crate::test::history::{code_state, TestHistory};
+use crate::test::runner::{TestResult, TestRunner, TestWorkingDir};
 use clap::Args;
+use colored::Colorize;
+use std::collections::HashSet;
 use std::path::PathBuf;
-use std::process;
 
 #[derive(Args)]
 #[command(after_help = "\
@@ -28,7 +34,12 @@ Examples:
   stacy test -f \"clean*\"                  Filter tests by pattern
   stacy test --list                       List tests without running
   stacy test -C data/                     Run tests in data/ directory
-  stacy test --cd                         Run each test in its own directory")]
+  stacy test --cd                         Run each test in its own directory
+  stacy test --cache                      Skip tests that passed and are unchanged
+  stacy test --summary                    Just a table of tests, status and logs
+  stacy test --detect-flaky               Report tests that passed and failed on the same code
+  stacy test --retry-flaky 2              Rerun failing flaky tests up to twice
+  stacy test --log-dir logs/ci            Keep each test's log as logs/ci/<test>.log")]
 pub struct TestArgs {
     /// Specific test to run (name or path)
     #[arg(value_name = "TEST")]
@@ -55,21 +66,61 @@ pub struct TestArgs {
     #[arg(long)]
     pub parallel: bool,
 
+    /// Enable build cache (skip tests that passed and whose script/deps are unchanged)
+    #[arg(long)]
+    pub cache: bool,
+
+    /// Force rebuild even if cached
+    #[arg(long, requires = "cache")]
+    pub force: bool,
+
+    /// Fail if lockfile doesn't match stacy.toml (for CI reproducibility).
+    /// Also on with STACY_FROZEN=1 or [install] frozen = true.
+    #[arg(long)]
+    pub frozen: bool,
+
     /// List tests without running them
     #[arg(long)]
     pub list: bool,
 
-    /// Output format: human (default), json, or stata
+    /// Keep every test's log in this directory, pass or fail, as <test>.log
+    #[arg(long, value_name = "DIR")]
+    pub log_dir: Option<PathBuf>,
+
+    /// Name kept logs after a pattern ({script}, {date}, {time}, {status});
+    /// keeps every log, pass or fail, in --log-dir or [run] log_dir
+    #[arg(long, value_name = "PATTERN")]
+    pub log_name: Option<String>,
+
+    /// Output format: human (default), json, stata, json-lines (one JSON
+    /// event per line while the tests run), vscode (one file:line: line
+    /// per error, for editor problem matchers), markdown (a table of the
+    /// results), or csv (with --list)
     #[arg(long, value_enum, default_value = "human")]
-    pub format: OutputFormat,
+    pub format: RunFormat,
 
-    /// Suppress progress output
-    #[arg(short, long)]
+    /// Suppress progress output (global `-q`)
+    #[arg(skip)]
     pub quiet: bool,
 
-    /// Show full log context for failures
-    #[arg(short = 'V', long)]
+    /// Show full log context for failures (global `-v`, or `-V`)
+    #[arg(id = "full_log", short = 'V', hide = true)]
     pub verbose: bool,
+
+    /// Print only a table of the tests (status, duration, kept log) at the
+    /// end, without progress output
+    #[arg(long, conflicts_with_all = ["quiet", "verbose", "full_log"])]
+    pub summary: bool,
+
+    /// After the run, report tests whose history shows both passes and
+    /// failures on the same code
+    #[arg(long)]
+    pub detect_flaky: bool,
+
+    /// Rerun a failing test up to N times when its history shows it is
+    /// flaky; a rerun that passes passes the test, with a warning
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    pub retry_flaky: u32,
 }
 
 /// Resolve the working-directory mode from the --cd / -C flags.
@@ -96,40 +147,69 @@ fn resolve_working_dir_mode(args: &TestArgs) -> Result<TestWorkingDir> {
 }
 
 pub fn execute(args: &TestArgs) -> Result<()> {
-    let format = args.format;
+    let format = args.format.output();
+    if args.format.is_csv() && !args.list {
+        return Err(Error::Config(
+            "--format csv requires --list: only the test list is a table".into(),
+        ));
+    }
 
     // Resolve working directory from --cd or -C flags (validates -C early)
     let working_dir = resolve_working_dir_mode(args)?;
 
     // Find project (optional for test command)
     let project = Project::find()?;
-    let local_ado_paths: Vec<std::path::PathBuf> = project
-        .as_ref()
-        .map(|p| p.resolve_local_ado_paths())
-        .unwrap_or_default();
     let project_root = project
         .as_ref()
         .map(|p| p.root.clone())
         .unwrap_or_else(|| std::env::current_dir().unwrap_or_default());
+    let test_settings = project
+        .as_ref()
+        .and_then(|p| p.config.as_ref())
+        .map(|config| config.test.clone())
+        .unwrap_or_default();
+
+    // Frozen mode (--frozen, STACY_FROZEN or [install] frozen): verify the
+    // lockfile is in sync with the manifest
+    if let Some(ref p) = project {
+        let config = p.config.clone().unwrap_or_default();
+        if frozen_mode(args.frozen, Some(&config)) {
+            check_frozen(load_lockfile(&p.root)?.as_ref(), &config)?;
+        }
+    }
 
     // Log retention (#98): a passing test's log is internal, a failing test's
-    // log is kept — in `[run] log_dir` when the project sets one.
-    let policy = LogPolicy::for_project(project.as_ref());
+    // log is kept — in `[run] log_dir` when the project sets one. --log-dir
+    // and --log-name keep every log.
+    let policy = LogPolicy::for_project(project.as_ref())
+        .with_dir(args.log_dir.clone())
+        .with_name(args.log_name.clone())?;
+
+    // Build cache (`--cache`) lives in the project's `.stacy/cache/`, so tests
+    // outside a project always run.
+    let step_cache = match project {
+        Some(ref p) if args.cache => {
+            Some(StepCache::load(&p.root, args.force)?.with_engine(EngineIdentity::detect(None)))
+        }
+        _ => None,
+    };
 
     // Handle specific test
     if let Some(ref test_name) = args.test {
-        if let Some(test) = find_test(&project_root, test_name)? {
+        if let Some(test) = find_test(&project_root, &test_settings, test_name)? {
             return run_single_test(
                 args,
                 &project_root,
                 &test,
-                &local_ado_paths,
+                project.as_ref(),
                 working_dir,
                 policy,
+                step_cache.as_ref(),
             );
         } else {
             let msg = format!("Test '{}' not found", test_name);
-            if format.is_machine_readable() {
+            // A stream has nothing to report for a test that doesn't exist
+            if format.is_machine_readable() && !args.format.is_streamed() {
                 let output = TestOutput {
                     test_count: 0,
                     passed: 0,
@@ -138,8 +218,12 @@ pub fn execute(args: &TestArgs) -> Result<()> {
                     duration_secs: 0.0,
                     success: false,
                     tests: vec![],
+                    flaky: None,
                 };
                 match format {
+                    OutputFormat::Json if args.format.is_markdown() => {
+                        print!("{}", output.to_markdown())
+                    }
                     OutputFormat::Json => println!("{}", output.to_json()),
                     OutputFormat::Stata => println!("{}", output.to_stata()),
                     OutputFormat::Human => {}
@@ -147,16 +231,16 @@ pub fn execute(args: &TestArgs) -> Result<()> {
             } else {
                 eprintln!("Error: {}", msg);
             }
-            process::exit(5); // Internal error
+            crate::session::exit(5); // Internal error
         }
     }
 
     // Discover tests
-    let tests = discover_tests(&project_root, &args.filter)?;
+    let tests = discover_tests(&project_root, &test_settings, &args.filter)?;
 
     // Handle --list flag
     if args.list {
-        return execute_list(&tests, format);
+        return execute_list(&tests, args.format);
     }
 
     // Check if there are any tests
@@ -170,8 +254,14 @@ pub fn execute(args: &TestArgs) -> Result<()> {
                 duration_secs: 0.0,
                 success: true, // No tests = success
                 tests: vec![],
+                flaky: None,
             };
             match format {
+                // No tests, nothing to stream
+                OutputFormat::Json if args.format.is_streamed() => {}
+                OutputFormat::Json if args.format.is_markdown() => {
+                    print!("{}", output.to_markdown())
+                }
                 OutputFormat::Json => println!("{}", output.to_json()),
                 OutputFormat::Stata => println!("{}", output.to_stata()),
                 OutputFormat::Human => {}
@@ -191,39 +281,77 @@ pub fn execute(args: &TestArgs) -> Result<()> {
         args,
         &project_root,
         &tests,
-        &local_ado_paths,
+        project.as_ref(),
         working_dir,
         policy,
+        step_cache.as_ref(),
     )
 }
 
+/// Stata executor for tests, with the project's ado paths and
+/// `[reproducibility]` settings. Quiet verbosity suppresses error context:
+/// test results show their own error messages.
+fn test_executor(project: Option<&Project>) -> Result<StataExecutor> {
+    let executor = StataExecutor::try_new(None, crate::executor::verbosity::Verbosity::Quiet)?;
+    Ok(match project {
+        Some(p) => executor
+            .with_local_ado_paths(p.resolve_local_ado_paths())
+            .with_reproducibility(p.reproducibility()),
+        None => executor,
+    })
+}
+
 fn run_single_test(
     args: &TestArgs,
     project_root: &std::path::Path,
     test: &crate::test::discovery::TestFile,
-    local_ado_paths: &[std::path::PathBuf],
+    project: Option<&Project>,
     working_dir: TestWorkingDir,
     log_policy: LogPolicy,
+    step_cache: Option<&StepCache>,
 ) -> Result<()> {
-    let format = args.format;
+    let format = args.format.output();
 
-    // Create Stata executor with Quiet verbosity to suppress error context
-    // (we show our own error messages in test results)
-    let executor = StataExecutor::try_new(None, crate::executor::verbosity::Verbosity::Quiet)?
-        .with_local_ado_paths(local_ado_paths.to_vec());
+    let executor = test_executor(project)?
+        .with_events(args.format.is_events())
+        .with_problems(args.format.is_problems());
 
     // Create test runner
-    let runner = TestRunner::new(&executor, project_root)
+    let mut history = project.map(|p| TestHistory::load(&p.root));
+    let mut runner = TestRunner::new(&executor, project_root)
         .with_working_dir(working_dir)
-        .with_log_policy(log_policy);
+        .with_log_policy(log_policy)
+        .with_flaky_retries(
+            known_flaky(
+                history.as_ref(),
+                project,
+                std::slice::from_ref(test),
+                args.retry_flaky,
+            ),
+            args.retry_flaky,
+        );
+    if let Some(cache) = step_cache {
+        runner = runner.with_cache(cache);
+    }
 
     // Run the test
-    if !args.quiet && format == OutputFormat::Human {
+    if !args.quiet && !args.summary && format == OutputFormat::Human {
         println!("Running test: {}", test.name);
         println!();
     }
 
     let result = runner.run_test(test)?;
+    save_step_cache(step_cache, format);
+    let flaky = track_flaky(
+        args,
+        history.as_mut(),
+        project,
+        std::slice::from_ref(&result),
+    );
+
+    if args.summary && format == OutputFormat::Human {
+        print_summary_table(std::slice::from_ref(&result), result.duration);
+    }
 
     // Build output
     let output = TestOutput {
@@ -244,15 +372,22 @@ fn run_single_test(
             duration_secs: result.duration.as_secs_f64(),
             exit_code: result.exit_code,
             error_message: result.error_message,
+            cached: result.cached,
+            log_file: result.log_file,
+            retries: result.retries,
         }],
+        flaky,
     };
 
-    output_result(&output, format);
+    // The stream already reported each test
+    if !(args.format.is_streamed() || args.summary && format == OutputFormat::Human) {
+        output_result(&output, args.format);
+    }
 
     if output.success {
         Ok(())
     } else {
-        process::exit(1);
+        crate::session::exit(1);
     }
 }
 
@@ -260,37 +395,52 @@ fn run_tests(
     args: &TestArgs,
     project_root: &std::path::Path,
     tests: &[crate::test::discovery::TestFile],
-    local_ado_paths: &[std::path::PathBuf],
+    project: Option<&Project>,
     working_dir: TestWorkingDir,
     log_policy: LogPolicy,
+    step_cache: Option<&StepCache>,
 ) -> Result<()> {
-    let format = args.format;
+    let format = args.format.output();
 
-    // Create Stata executor with Quiet verbosity to suppress error context
-    // (we show our own error messages in test results)
-    let executor = StataExecutor::try_new(None, crate::executor::verbosity::Verbosity::Quiet)?
-        .with_local_ado_paths(local_ado_paths.to_vec());
+    let executor = test_executor(project)?
+        .with_events(args.format.is_events())
+        .with_problems(args.format.is_problems());
 
     // Create test runner
-    let runner = TestRunner::new(&executor, project_root)
+    let mut history = project.map(|p| TestHistory::load(&p.root));
+    let mut runner = TestRunner::new(&executor, project_root)
         .with_parallel(args.parallel)
         .with_working_dir(working_dir)
-        .with_log_policy(log_policy);
+        .with_log_policy(log_policy)
+        .with_suite(tests)
+        .with_flaky_retries(
+            known_flaky(history.as_ref(), project, tests, args.retry_flaky),
+            args.retry_flaky,
+        );
+    if let Some(cache) = step_cache {
+        runner = runner.with_cache(cache);
+    }
 
     // Print header
-    if !args.quiet && format == OutputFormat::Human {
+    if !args.quiet && !args.summary && format == OutputFormat::Human {
         let mode = if args.parallel { " (parallel)" } else { "" };
         println!("Running {} tests{}...", tests.len(), mode);
         println!();
     }
 
     // Run tests with progress reporting
-    let suite_result = if args.quiet || format.is_machine_readable() {
+    let suite_result = if args.quiet || args.summary || format.is_machine_readable() {
         runner.run_all(tests)?
     } else {
         // Run with progress output
         run_with_progress(&runner, tests, args.verbose)?
     };
+    save_step_cache(step_cache, format);
+    let flaky = track_flaky(args, history.as_mut(), project, &suite_result.results);
+
+    if args.summary && format == OutputFormat::Human {
+        print_summary_table(&suite_result.results, suite_result.duration);
+    }
 
     // Build output
     let output = TestOutput {
@@ -314,16 +464,149 @@ fn run_tests(
                 duration_secs: r.duration.as_secs_f64(),
                 exit_code: r.exit_code,
                 error_message: r.error_message.clone(),
+                cached: r.cached,
+                log_file: r.log_file.clone(),
+                retries: r.retries,
             })
             .collect(),
+        flaky,
     };
 
-    output_result(&output, format);
+    // The stream already reported each test
+    if !(args.format.is_streamed() || args.summary && format == OutputFormat::Human) {
+        output_result(&output, args.format);
+    }
 
     if output.success {
         Ok(())
     } else {
-        process::exit(1);
+        crate::session::exit(1);
+    }
+}
+
+/// Tests among `tests` whose history marks them flaky, for `--retry-flaky`
+fn known_flaky(
+    history: Option<&TestHistory>,
+    project: Option<&Project>,
+    tests: &[TestFile],
+    retries: u32,
+) -> HashSet<PathBuf> {
+    let (Some(history), Some(project)) = (history, project) else {
+        return HashSet::new();
+    };
+    if retries == 0 {
+        return HashSet::new();
+    }
+    let threshold = flaky_threshold(project);
+    tests
+        .iter()
+        .filter(|test| history.flakiness(&test.path, threshold).is_some())
+        .map(|test| test.path.clone())
+        .collect()
+}
+
+/// Add the outcomes of a run to the project's test history, and with
+/// `--detect-flaky` report the tests of the run that are flaky. The report
+/// goes to stderr in human output and into the JSON as `flaky`.
+fn track_flaky(
+    args: &TestArgs,
+    history: Option<&mut TestHistory>,
+    project: Option<&Project>,
+    results: &[TestResult],
+) -> Option<Vec<FlakyTestOutput>> {
+    let (Some(history), Some(project)) = (history, project) else {
+        if args.detect_flaky {
+            eprintln!("warning: --detect-flaky needs a project: test history is kept in .stacy/");
+        }
+        return None;
+    };
+    // A cached result didn't run: nothing new to learn
+    for result in results.iter().filter(|r| !r.cached) {
+        let Ok(state) = code_state(&project.root, &result.path) else {
+            continue;
+        };
+        for _ in 0..result.retries {
+            history.record(&result.path, &state, false);
+        }
+        history.record(&result.path, &state, result.passed);
+    }
+    history.save();
+
+    if !args.detect_flaky {
+        return None;
+    }
+    let threshold = flaky_threshold(project);
+    let flaky: Vec<FlakyTestOutput> = results
+        .iter()
+        .filter_map(|r| {
+            history
+                .flakiness(&r.path, threshold)
+                .map(|f| FlakyTestOutput {
+                    name: r.name.clone(),
+                    path: r.path.clone(),
+                    passed: f.passed,
+                    failed: f.failed,
+                })
+        })
+        .collect();
+    if args.format.output() == OutputFormat::Human {
+        print_flaky_report(&flaky);
+    }
+    Some(flaky)
+}
+
+fn flaky_threshold(project: &Project) -> f64 {
+    project
+        .config
+        .as_ref()
+        .map(|config| config.test.flaky_threshold())
+        .unwrap_or(crate::project::config::TestSection::DEFAULT_FLAKY_THRESHOLD)
+}
+
+fn print_flaky_report(flaky: &[FlakyTestOutput]) {
+    if flaky.is_empty() {
+        eprintln!("No flaky tests: each test had one outcome per state of its code.");
+        return;
+    }
+    eprintln!(
+        "{}",
+        "Flaky tests (passed and failed on the same code):".yellow()
+    );
+    for test in flaky {
+        eprintln!(
+            "  {}  {} passed, {} failed",
+            test.name, test.passed, test.failed
+        );
+    }
+    eprintln!(
+        "  hint: look for network access, the clock or files shared between tests; --retry-flaky N reruns them"
+    );
+}
+
+/// `--summary`: the table of tests, on stdout
+fn print_summary_table(results: &[crate::test::runner::TestResult], duration: std::time::Duration) {
+    use crate::cli::format::{summary_table, SummaryRow};
+
+    let rows: Vec<SummaryRow> = results
+        .iter()
+        .map(|r| SummaryRow {
+            name: r.name.clone(),
+            passed: r.passed,
+            cached: r.cached,
+            duration_secs: r.duration.as_secs_f64(),
+            log: r.log_file.clone(),
+        })
+        .collect();
+    print!("{}", summary_table("TEST", &rows, duration.as_secs_f64()));
+}
+
+fn save_step_cache(cache: Option<&StepCache>, format: OutputFormat) {
+    if let Some(cache) = cache {
+        if let Err(e) = cache.save() {
+            if format == OutputFormat::Human {
+                eprintln!("Warning: Failed to update cache: {}", e);
+            }
+        }
     }
 }
 
@@ -349,8 +632,9 @@ fn run_with_progress(
     Ok(suite_result)
 }
 
-fn output_result(output: &TestOutput, format: OutputFormat) {
-    match format {
+fn output_result(output: &TestOutput, format: RunFormat) {
+    match format.output() {
+        OutputFormat::Json if format.is_markdown() => print!("{}", output.to_markdown()),
         OutputFormat::Json => {
             println!("{}", output.to_json());
         }
@@ -368,34 +652,22 @@ fn output_result(output: &TestOutput, format: OutputFormat) {
     }
 }
 
-fn execute_list(tests: &[crate::test::discovery::TestFile], format: OutputFormat) -> Result<()> {
-    match format {
-        OutputFormat::Json => {
-            let output = TestListOutput {
-                test_count: tests.len(),
-                tests: tests
-                    .iter()
-                    .map(|t| TestInfo {
-                        name: t.name.clone(),
-                        path: t.path.clone(),
-                    })
-                    .collect(),
-            };
-            println!("{}", output.to_json());
-        }
-        OutputFormat::Stata => {
-            let output = TestListOutput {
-                test_count: tests.len(),
-                tests: tests
-                    .iter()
-                    .map(|t| TestInfo {
-                        name: t.name.clone(),
-                        path: t.path.clone(),
-                    })
-                    .collect(),
-            };
-            println!("{}", output.to_stata());
-        }
+fn execute_list(tests: &[crate::test::discovery::TestFile], format: RunFormat) -> Result<()> {
+    let output = TestListOutput {
+        test_count: tests.len(),
+        tests: tests
+            .iter()
+            .map(|t| TestInfo {
+                name: t.name.clone(),
+                path: t.path.clone(),
+            })
+            .collect(),
+    };
+    match format.output() {
+        OutputFormat::Json if format.is_csv() => print!("{}", output.to_csv()),
+        OutputFormat::Json if format.is_markdown() => print!("{}", output.to_markdown()),
+        OutputFormat::Json => println!("{}", output.to_json()),
+        OutputFormat::Stata => println!("{}", output.to_stata()),
         OutputFormat::Human => {
             if tests.is_empty() {
                 println!("No tests found.");
@@ -25,7 +25,11 @@ pub fn format_status(passed: bool) -> String {
 /// Example: "  PASS  test_success                    0.05s"
 pub fn format_test_line(result: &TestResult) -> String {
     let status = format_status(result.passed);
-    let duration = format!("{:.2}s", result.duration.as_secs_f64());
+    let duration = if result.cached {
+        format!("{:.2}s cached", result.duration.as_secs_f64())
+    } else {
+        format!("{:.2}s", result.duration.as_secs_f64())
+    };
 
     // Calculate padding for right-alignment
     // Status is 4 chars visible, name varies, duration at end
@@ -86,6 +90,14 @@ fn extract_line_number(error_message: &str) -> Option<u32> {
     None
 }
 
+/// Extract the `file.do:line` an error message names (`r(111) at
+/// helpers/prep.do:12 - ...`); a log line (`at line N`) is not one
+fn extract_location(error_message: &str) -> Option<&str> {
+    let rest = &error_message[error_message.find(") at ")? + 5..];
+    let location = rest.split(" - ").next()?;
+    (!location.starts_with("line ")).then_some(location)
+}
+
 /// Format error context for a failed test
 ///
 /// Shows human-readable error description and location.
@@ -99,7 +111,9 @@ pub fn format_error_context(result: &TestResult, verbose: bool) -> String {
         output.push_str(&format!("\n        {}\n", description.red()));
 
         // Show location if available
-        if let Some(line_num) = extract_line_number(error_msg) {
+        if let Some(location) = extract_location(error_msg) {
+            output.push_str(&format!("        {} {}\n", "at".dimmed(), location));
+        } else if let Some(line_num) = extract_line_number(error_msg) {
             output.push_str(&format!(
                 "        {} {}:{}\n",
                 "at".dimmed(),
@@ -127,6 +141,17 @@ pub fn format_error_context(result: &TestResult, verbose: bool) -> String {
     output
 }
 
+/// Warning under a flaky test that was rerun (`--retry-flaky`)
+pub fn format_retry_note(result: &TestResult) -> Option<String> {
+    let note = match (result.retries, result.passed) {
+        (0, _) => return None,
+        (1, true) => "flaky: passed on the retry, after 1 failure".to_string(),
+        (n, true) => format!("flaky: passed on retry {}, after {} failures", n, n),
+        (n, false) => format!("flaky: failed {} retries as well", n),
+    };
+    Some(format!("        {}", note.yellow()))
+}
+
 /// Format a heavy horizontal separator line
 pub fn format_separator() -> String {
     "\u{2501}".repeat(TERM_WIDTH)
@@ -170,6 +195,9 @@ pub fn format_summary(passed: usize, failed: usize, duration: Duration) -> Strin
 pub fn print_test_result(result: &TestResult, verbose: bool) {
     println!("{}", format_test_line(result));
 
+    if let Some(note) = format_retry_note(result) {
+        println!("{}", note);
+    }
     if !result.passed {
         print!("{}", format_error_context(result, verbose));
     }
@@ -213,6 +241,16 @@ mod tests {
         assert_eq!(extract_line_number("no line number"), None);
     }
 
+    #[test]
+    fn test_extract_location() {
+        assert_eq!(
+            extract_location("r(111) at helpers/prep.do:12 - variable x not found"),
+            Some("helpers/prep.do:12")
+        );
+        assert_eq!(extract_location("r(9) at line 13 - r(9);"), None);
+        assert_eq!(extract_location("Exit code 1"), None);
+    }
+
     #[test]
     fn test_get_error_description_with_code() {
         // Without a cache, falls through to the original message
@@ -237,6 +275,8 @@ mod tests {
             duration: Duration::from_millis(50),
             error_message: None,
             log_file: None,
+            cached: false,
+            retries: 0,
         };
 
         let line = format_test_line(&result);
@@ -245,6 +285,44 @@ mod tests {
         assert!(line.contains("0.05s"));
     }
 
+    #[test]
+    fn test_format_retry_note() {
+        let mut result = TestResult {
+            name: "test_fetch".to_string(),
+            path: PathBuf::from("tests/test_fetch.do"),
+            passed: true,
+            exit_code: 0,
+            duration: Duration::from_millis(50),
+            error_message: None,
+            log_file: None,
+            cached: false,
+            retries: 0,
+        };
+        assert!(format_retry_note(&result).is_none());
+
+        result.retries = 2;
+        let note = format_retry_note(&result).unwrap();
+        assert!(note.contains("passed on retry 2"));
+    }
+
+    #[test]
+    fn test_format_test_line_cached() {
+        let result = TestResult {
+            name: "test_success".to_string(),
+            path: PathBuf::from("tests/test_success.do"),
+            passed: true,
+            exit_code: 0,
+            duration: Duration::from_millis(50),
+            error_message: None,
+            log_file: None,
+            cached: true,
+            retries: 0,
+        };
+
+        let line = format_test_line(&result);
+        assert!(line.contains("0.05s cached"));
+    }
+
     #[test]
     fn test_format_test_line_fail() {
         let result = TestResult {
@@ -255,6 +333,8 @@ mod tests {
             duration: Duration::from_millis(50),
             error_message: Some("r(9) at line 8".to_string()),
             log_file: None,
+            cached: false,
+            retries: 0,
         };
 
         let line = format_test_line(&result);
@@ -7,7 +7,7 @@ use crate::cli::output_types::{CommandOutput, UpdateOutput};
 use crate::error::{Error, Result};
 use crate::packages::github::GitHubDownloader;
 use crate::packages::installer::{install_from_ssc, install_package_github};
-use crate::packages::lockfile::{load_lockfile, save_lockfile};
+use crate::packages::lockfile::load_lockfile;
 use crate::packages::net::NetDownloader;
 use crate::packages::ssc::SscDownloader;
 use crate::project::config::load_config;
@@ -29,9 +29,23 @@ pub struct UpdateArgs {
     #[arg(long)]
     pub dry_run: bool,
 
+    /// Retries for a failed download, with exponential backoff (default 3,
+    /// or $STACY_NETWORK_RETRIES)
+    #[arg(long, value_name = "N")]
+    pub network_retries: Option<u32>,
+
+    /// Report every failed download attempt (global `-v`)
+    #[arg(skip)]
+    pub verbose: bool,
+
     /// Output format: human (default), json, or stata
     #[arg(long, value_enum, default_value = "human")]
     pub format: OutputFormat,
+
+    /// Leave out progress and summary lines (global `-q`); failures are
+    /// still reported
+    #[arg(skip)]
+    pub quiet: bool,
 }
 
 /// Outcome of a version check (dry run) or an install (real update)
@@ -78,7 +92,9 @@ struct UpdatedPackage {
 }
 
 pub fn execute(args: &UpdateArgs) -> Result<()> {
+    crate::packages::http::configure(args.network_retries, args.verbose);
     let format = args.format;
+    let chatty = format == OutputFormat::Human && !args.quiet;
 
     // Find project (must exist for update)
     let project = Project::find()?.ok_or_else(|| {
@@ -95,7 +111,7 @@ pub fn execute(args: &UpdateArgs) -> Result<()> {
     })?;
 
     if lockfile.packages.is_empty() {
-        if format == OutputFormat::Human {
+        if chatty {
             println!("No packages to update.");
         }
         return Ok(());
@@ -117,7 +133,7 @@ pub fn execute(args: &UpdateArgs) -> Result<()> {
         }
     }
 
-    if format == OutputFormat::Human {
+    if chatty {
         if args.dry_run {
             println!("Checking for updates (dry run)...");
         } else {
@@ -185,7 +201,7 @@ pub fn execute(args: &UpdateArgs) -> Result<()> {
             // A local package is a directory in the project, not something to
             // fetch: there is no newer version to find. Skipping it is the
             // right answer, not a failure — the same call `outdated` makes.
-            PackageSource::Local { path } => {
+            PackageSource::Local { path, .. } => {
                 Ok(Outcome::Skipped(format!("local package at {}", path)))
             }
             PackageSource::Net { url } => {
@@ -215,7 +231,7 @@ pub fn execute(args: &UpdateArgs) -> Result<()> {
             })) => {
                 let updated = !args.dry_run && has_update;
 
-                if format == OutputFormat::Human {
+                if chatty {
                     if args.dry_run {
                         if has_update {
                             println!("  {} {} -> {}", pkg_name, old_version, new_version);
@@ -240,7 +256,7 @@ pub fn execute(args: &UpdateArgs) -> Result<()> {
                 });
             }
             Ok(Outcome::Skipped(reason)) => {
-                if format == OutputFormat::Human {
+                if chatty {
                     println!("  = {} (skipped: {})", pkg_name, reason);
                 }
 
@@ -271,16 +287,6 @@ pub fn execute(args: &UpdateArgs) -> Result<()> {
         }
     }
 
-    // Reload lockfile to get updated versions (install functions update it)
-    if !args.dry_run {
-        // The lockfile was already updated by install functions
-        // Just reload it for consistency
-        if let Ok(Some(updated_lockfile)) = load_lockfile(&project.root) {
-            // Use the updated lockfile
-            let _ = save_lockfile(&project.root, &updated_lockfile);
-        }
-    }
-
     // Calculate summary
     let updated_count = results.iter().filter(|r| r.updated).count() as i32;
     let updates_available = results.iter().filter(|r| r.has_update).count() as i32;
@@ -309,6 +315,7 @@ pub fn execute(args: &UpdateArgs) -> Result<()> {
     match format {
         OutputFormat::Json => print_json_output(&results, &output),
         OutputFormat::Stata => println!("{}", output.to_stata()),
+        OutputFormat::Human if args.quiet => {}
         OutputFormat::Human => print_human_summary(&output, args.dry_run),
     }
 
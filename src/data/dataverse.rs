@@ -0,0 +1,175 @@
+//! Dataverse datasets
+//!
+//! A dataset version's files are listed by the native API
+//! (`/api/datasets/:persistentId/versions/<version>`), each with its MD5 (or
+//! another checksum), and downloaded through `/api/access/datafile/<id>`.
+//! Dataverse converts tabular uploads such as `.dta` files to `.tab`; stacy
+//! asks for the original format, which is what the checksum covers and what
+//! a Stata script reads.
+
+use super::{Checksum, DatasetListing, RemoteFile};
+use crate::error::{Error, Result};
+use serde_json::Value;
+use std::path::PathBuf;
+
+/// Harvard Dataverse, home of most DOIs starting `10.7910/DVN/`
+pub const DEFAULT_SERVER: &str = "https://dataverse.harvard.edu";
+
+/// API endpoint listing a dataset version's files (default: the latest
+/// published version)
+pub fn listing_url(server: &str, doi: &str, version: Option<&str>) -> String {
+    format!(
+        "{}/api/datasets/:persistentId/versions/{}?persistentId=doi:{}",
+        server,
+        version.unwrap_or(":latest-published"),
+        doi
+    )
+}
+
+/// Parse the native API's version response
+pub fn parse_listing(json: &Value, server: &str) -> Result<DatasetListing> {
+    let data = json.get("data").ok_or_else(|| invalid("missing 'data'"))?;
+    let version = match (
+        data.get("versionNumber").and_then(Value::as_u64),
+        data.get("versionMinorNumber").and_then(Value::as_u64),
+    ) {
+        (Some(major), Some(minor)) => format!("{}.{}", major, minor),
+        _ => data
+            .get("versionState")
+            .and_then(Value::as_str)
+            .unwrap_or("draft")
+            .to_lowercase(),
+    };
+
+    let files = data
+        .get("files")
+        .and_then(Value::as_array)
+        .ok_or_else(|| invalid("missing 'files'"))?
+        .iter()
+        .map(|file| parse_file(file, server))
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(DatasetListing { version, files })
+}
+
+fn parse_file(file: &Value, server: &str) -> Result<RemoteFile> {
+    let data_file = file
+        .get("dataFile")
+        .ok_or_else(|| invalid("file without 'dataFile'"))?;
+    let id = data_file
+        .get("id")
+        .and_then(Value::as_u64)
+        .ok_or_else(|| invalid("file without 'id'"))?;
+
+    let original = data_file.get("originalFileFormat").is_some();
+    let name = data_file
+        .get("originalFileName")
+        .filter(|_| original)
+        .or_else(|| data_file.get("filename"))
+        .or_else(|| file.get("label"))
+        .and_then(Value::as_str)
+        .ok_or_else(|| invalid("file without a name"))?;
+
+    let mut path = PathBuf::new();
+    if let Some(dir) = file.get("directoryLabel").and_then(Value::as_str) {
+        path.push(dir);
+    }
+    path.push(name);
+
+    let checksum = data_file
+        .get("checksum")
+        .and_then(|c| {
+            Checksum::parse(
+                c.get("type").and_then(Value::as_str)?,
+                c.get("value").and_then(Value::as_str)?,
+            )
+        })
+        .or_else(|| {
+            data_file
+                .get("md5")
+                .and_then(Value::as_str)
+                .and_then(|md5| Checksum::parse("md5", md5))
+        });
+
+    let mut url = format!("{}/api/access/datafile/{}", server, id);
+    if original {
+        url.push_str("?format=original");
+    }
+
+    Ok(RemoteFile {
+        url,
+        path,
+        checksum,
+    })
+}
+
+fn invalid(what: &str) -> Error {
+    Error::Parse(format!("Unexpected Dataverse API response: {}", what))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_listing() {
+        let json: Value = serde_json::from_str(
+            r#"{
+              "status": "OK",
+              "data": {
+                "versionNumber": 2,
+                "versionMinorNumber": 1,
+                "versionState": "RELEASED",
+                "files": [
+                  {
+                    "label": "survey.tab",
+                    "directoryLabel": "raw",
+                    "dataFile": {
+                      "id": 101,
+                      "filename": "survey.tab",
+                      "originalFileFormat": "application/x-stata-14",
+                      "originalFileName": "survey.dta",
+                      "md5": "0cc175b9c0f1b6a831c399e269772661",
+                      "checksum": { "type": "MD5", "value": "0cc175b9c0f1b6a831c399e269772661" }
+                    }
+                  },
+                  {
+                    "label": "README.txt",
+                    "dataFile": { "id": 102, "filename": "README.txt" }
+                  }
+                ]
+              }
+            }"#,
+        )
+        .unwrap();
+
+        let listing = parse_listing(&json, "https://dv.example.org").unwrap();
+        assert_eq!(listing.version, "2.1");
+        assert_eq!(listing.files.len(), 2);
+        assert_eq!(listing.files[0].path, PathBuf::from("raw/survey.dta"));
+        assert_eq!(
+            listing.files[0].url,
+            "https://dv.example.org/api/access/datafile/101?format=original"
+        );
+        assert_eq!(
+            listing.files[0].checksum,
+            Some(Checksum::Md5(
+                "0cc175b9c0f1b6a831c399e269772661".to_string()
+            ))
+        );
+        assert_eq!(listing.files[1].path, PathBuf::from("README.txt"));
+        assert_eq!(listing.files[1].checksum, None);
+    }
+
+    #[test]
+    fn test_listing_url() {
+        assert_eq!(
+            listing_url(DEFAULT_SERVER, "10.7910/DVN/ABCDEF", None),
+            "https://dataverse.harvard.edu/api/datasets/:persistentId/versions/:latest-published?persistentId=doi:10.7910/DVN/ABCDEF"
+        );
+        assert!(
+            listing_url(DEFAULT_SERVER, "10.7910/DVN/ABCDEF", Some("2.0"))
+                .contains("/versions/2.0?")
+        );
+    }
+}
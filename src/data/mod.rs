@@ -0,0 +1,743 @@
+//! Downloadable input data
+//!
+//! The `[data]` section of stacy.toml declares the data a project downloads
+//! before it can run: a single file from a URL, or the files of a dataset
+//! published on Dataverse or Zenodo, found by its DOI. `stacy data fetch`
+//! downloads them, rejects a download whose checksum doesn't match (the
+//! declared SHA-256, or the checksum the repository lists), and keeps a copy
+//! in a content-addressed cache (`~/.cache/stacy/data/`), so a second
+//! checkout or a file deleted by accident is restored without the network.
+
+pub mod dataverse;
+pub mod zenodo;
+
+use crate::error::{Error, Result};
+use crate::packages::http::client_builder;
+use crate::project::config::DataSource;
+use md5::Md5;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+
+/// Time allowed to connect; the download itself may take as long as it takes
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Get the data cache directory, next to the package cache
+/// (`~/.cache/stacy/data/` on Linux and macOS).
+pub fn cache_dir() -> Result<PathBuf> {
+    Ok(crate::packages::global_cache::cache_dir()?.with_file_name("data"))
+}
+
+/// A checksum a file must match
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase", tag = "type", content = "value")]
+pub enum Checksum {
+    Sha256(String),
+    /// What Dataverse and Zenodo list for most files
+    Md5(String),
+}
+
+impl Checksum {
+    /// Parse a checksum as repositories label it (`MD5`, `SHA-256`, ...).
+    /// Algorithms stacy doesn't check return `None`.
+    pub fn parse(algorithm: &str, value: &str) -> Option<Self> {
+        let value = value.trim().to_ascii_lowercase();
+        match algorithm.to_ascii_lowercase().replace('-', "").as_str() {
+            "sha256" => Some(Checksum::Sha256(value)),
+            "md5" => Some(Checksum::Md5(value)),
+            _ => None,
+        }
+    }
+
+    /// Parse a `sha256:<hex>` or `md5:<hex>` string; a bare value is SHA-256
+    pub fn parse_prefixed(value: &str) -> Option<Self> {
+        match value.trim().split_once(':') {
+            Some((algorithm, value)) => Self::parse(algorithm, value),
+            None => Self::parse("sha256", value),
+        }
+    }
+
+    /// Name of the file holding this content in the data cache
+    fn cache_key(&self) -> String {
+        match self {
+            Checksum::Sha256(value) => value.clone(),
+            Checksum::Md5(value) => format!("md5-{}", value),
+        }
+    }
+
+    /// Does the file at `path` match?
+    fn matches(&self, path: &Path) -> Result<bool> {
+        Ok(match self {
+            Checksum::Sha256(value) => digest_file::<Sha256>(path)? == *value,
+            Checksum::Md5(value) => digest_file::<Md5>(path)? == *value,
+        })
+    }
+}
+
+impl std::fmt::Display for Checksum {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Checksum::Sha256(value) => write!(f, "sha256:{}", value),
+            Checksum::Md5(value) => write!(f, "md5:{}", value),
+        }
+    }
+}
+
+/// A file to download
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RemoteFile {
+    pub url: String,
+    /// Where the file goes: relative to the dataset directory in a listing,
+    /// relative to the project root once resolved
+    pub path: PathBuf,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<Checksum>,
+}
+
+/// The files of one version of a published dataset
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DatasetListing {
+    /// Version as the repository names it
+    pub version: String,
+    pub files: Vec<RemoteFile>,
+}
+
+/// Where a DOI is published
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Repository {
+    Dataverse,
+    Zenodo,
+}
+
+/// Strip `doi:` and resolver URL prefixes from a DOI
+pub fn normalize_doi(doi: &str) -> &str {
+    let doi = doi.trim();
+    [
+        "https://doi.org/",
+        "http://doi.org/",
+        "https://dx.doi.org/",
+        "http://dx.doi.org/",
+        "doi:",
+    ]
+    .iter()
+    .find_map(|prefix| doi.strip_prefix(prefix))
+    .unwrap_or(doi)
+}
+
+/// Zenodo DOIs all read `10.5281/zenodo.<record>`; any other DOI is looked up
+/// on Dataverse
+pub fn repository(doi: &str) -> Repository {
+    if zenodo::record_id(doi).is_some() {
+        Repository::Zenodo
+    } else {
+        Repository::Dataverse
+    }
+}
+
+/// Check that every declared file is present, without hashing or the
+/// network. A DOI source counts as present when its directory has files in
+/// it. Returns the names and paths of the missing ones.
+pub fn missing(root: &Path, data: &BTreeMap<String, DataSource>) -> Vec<(String, PathBuf)> {
+    data.iter()
+        .filter(|(_, source)| {
+            let path = root.join(&source.path);
+            if source.doi.is_some() {
+                std::fs::read_dir(&path).map_or(true, |mut entries| entries.next().is_none())
+            } else {
+                !path.is_file()
+            }
+        })
+        .map(|(name, source)| (name.clone(), source.path.clone()))
+        .collect()
+}
+
+/// How `fetch` put a file in place
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FetchOutcome {
+    /// The file was already there with the right content
+    Present,
+    /// Copied from the data cache
+    Cached,
+    /// Downloaded
+    Downloaded,
+}
+
+impl FetchOutcome {
+    /// Short label for output
+    pub fn label(&self) -> &'static str {
+        match self {
+            FetchOutcome::Present => "present",
+            FetchOutcome::Cached => "cached",
+            FetchOutcome::Downloaded => "downloaded",
+        }
+    }
+}
+
+/// Result of fetching one file
+#[derive(Debug, Clone)]
+pub struct FetchResult {
+    /// Name of the `[data]` entry
+    pub name: String,
+    /// Path relative to the project root
+    pub path: PathBuf,
+    pub outcome: FetchOutcome,
+    /// SHA-256 of the file now in place
+    pub sha256: String,
+}
+
+/// Fetches `[data]` files into a project
+pub struct DataFetcher {
+    project_root: PathBuf,
+    cache_dir: PathBuf,
+    force: bool,
+    client: reqwest::blocking::Client,
+}
+
+impl DataFetcher {
+    /// Create a fetcher using the user's data cache
+    pub fn new(project_root: &Path) -> Result<Self> {
+        let client = client_builder()
+            .connect_timeout(CONNECT_TIMEOUT)
+            .user_agent(concat!("stacy/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .map_err(|e| Error::Network(format!("Failed to create HTTP client: {}", e)))?;
+        Ok(Self {
+            project_root: project_root.to_path_buf(),
+            cache_dir: cache_dir()?,
+            force: false,
+            client,
+        })
+    }
+
+    /// Use another cache directory
+    pub fn with_cache_dir(mut self, cache_dir: PathBuf) -> Self {
+        self.cache_dir = cache_dir;
+        self
+    }
+
+    /// Download even when the file is present or cached
+    pub fn with_force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// Put the files of one `[data]` entry in place: keep each one that is
+    /// already there and matches, else copy it from the cache, else download
+    /// it. A download that doesn't match its checksum is discarded with an
+    /// error.
+    pub fn fetch(&self, name: &str, source: &DataSource) -> Result<Vec<FetchResult>> {
+        self.resolve(name, source)?
+            .iter()
+            .map(|file| self.fetch_file(name, file))
+            .collect()
+    }
+
+    /// The files a `[data]` entry stands for, with paths relative to the
+    /// project root
+    pub fn resolve(&self, name: &str, source: &DataSource) -> Result<Vec<RemoteFile>> {
+        match (&source.url, &source.doi) {
+            (Some(url), None) => {
+                if source.version.is_some() || !source.files.is_empty() || source.server.is_some() {
+                    return Err(Error::Config(format!(
+                        "data '{}': `version`, `files` and `server` only apply with `doi`",
+                        name
+                    )));
+                }
+                let checksum = match source.sha256 {
+                    Some(ref sha256) => {
+                        Some(Checksum::parse_prefixed(sha256).ok_or_else(|| {
+                            Error::Config(format!("data '{}': invalid sha256 '{}'", name, sha256))
+                        })?)
+                    }
+                    None => None,
+                };
+                Ok(vec![RemoteFile {
+                    url: url.clone(),
+                    path: source.path.clone(),
+                    checksum,
+                }])
+            }
+            (None, Some(doi)) => {
+                if source.sha256.is_some() {
+                    return Err(Error::Config(format!(
+                        "data '{}': `sha256` only applies with `url`; files from a DOI are checked against the repository's checksums",
+                        name
+                    )));
+                }
+                let listing = self.listing(doi, source)?;
+                select_files(name, doi, &listing, &source.files)?
+                    .into_iter()
+                    .map(|file| {
+                        if !is_relative_and_inside(&file.path) {
+                            return Err(Error::Parse(format!(
+                                "data '{}': the repository lists an unsafe path '{}'",
+                                name,
+                                file.path.display()
+                            )));
+                        }
+                        Ok(RemoteFile {
+                            path: source.path.join(&file.path),
+                            ..file
+                        })
+                    })
+                    .collect()
+            }
+            _ => Err(Error::Config(format!(
+                "data '{}': set either `url` or `doi`",
+                name
+            ))),
+        }
+    }
+
+    /// The repository's file listing for a DOI. A listing for a pinned
+    /// `version` never changes, so it is cached and later fetches of that
+    /// version don't need the network.
+    fn listing(&self, doi: &str, source: &DataSource) -> Result<DatasetListing> {
+        let doi = normalize_doi(doi);
+        let repository = repository(doi);
+        let server = source.server.clone().unwrap_or_else(|| match repository {
+            Repository::Dataverse => dataverse::DEFAULT_SERVER.to_string(),
+            Repository::Zenodo => zenodo::DEFAULT_SERVER.to_string(),
+        });
+        let server = server.trim_end_matches('/');
+
+        let cached = source.version.as_ref().map(|version| {
+            let key = format!("{} {} {}", server, doi, version);
+            self.cache_dir.join("listings").join(format!(
+                "{}.json",
+                hex::encode(Sha256::digest(key.as_bytes()))
+            ))
+        });
+        if let Some(ref cached) = cached {
+            if let Ok(content) = std::fs::read_to_string(cached) {
+                if let Ok(listing) = serde_json::from_str(&content) {
+                    return Ok(listing);
+                }
+            }
+        }
+
+        if crate::update_check::is_offline() {
+            return Err(Error::Network(format!(
+                "Cannot look up DOI {}: STACY_OFFLINE is set",
+                doi
+            )));
+        }
+
+        let listing = match repository {
+            Repository::Dataverse => {
+                let url = dataverse::listing_url(server, doi, source.version.as_deref());
+                dataverse::parse_listing(&self.get_json(&url)?, server)?
+            }
+            Repository::Zenodo => {
+                let url = zenodo::listing_url(server, doi)
+                    .ok_or_else(|| Error::Config(format!("Not a Zenodo DOI: {}", doi)))?;
+                let listing = zenodo::parse_listing(&self.get_json(&url)?, server)?;
+                if let Some(ref version) = source.version {
+                    if listing.version != *version {
+                        return Err(Error::Config(format!(
+                            "Zenodo record {} is version '{}', not '{}' (each Zenodo version has its own DOI)",
+                            doi, listing.version, version
+                        )));
+                    }
+                }
+                listing
+            }
+        };
+
+        if let Some(ref cached) = cached {
+            if let Some(parent) = cached.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(cached, serde_json::to_string_pretty(&listing)?)?;
+        }
+        Ok(listing)
+    }
+
+    /// Put one file in place
+    fn fetch_file(&self, name: &str, file: &RemoteFile) -> Result<FetchResult> {
+        let dest = self.project_root.join(&file.path);
+        let result = |outcome| -> Result<FetchResult> {
+            Ok(FetchResult {
+                name: name.to_string(),
+                path: file.path.clone(),
+                outcome,
+                sha256: digest_file::<Sha256>(&dest)?,
+            })
+        };
+
+        if !self.force && dest.is_file() {
+            match file.checksum {
+                Some(ref checksum) if !checksum.matches(&dest)? => {}
+                _ => return result(FetchOutcome::Present),
+            }
+        }
+
+        if !self.force {
+            if let Some(ref checksum) = file.checksum {
+                let cached = self.cache_dir.join(checksum.cache_key());
+                if cached.is_file() && checksum.matches(&cached)? {
+                    install(&cached, &dest)?;
+                    return result(FetchOutcome::Cached);
+                }
+            }
+        }
+
+        if crate::update_check::is_offline() {
+            return Err(Error::Network(format!(
+                "Cannot download data '{}': STACY_OFFLINE is set and {} is not cached",
+                name,
+                file.path.display()
+            )));
+        }
+
+        let (download, sha256) = self.download(&file.url)?;
+        let key = match file.checksum {
+            Some(Checksum::Sha256(ref expected)) if *expected != sha256 => {
+                return Err(checksum_mismatch(name, file, &sha256));
+            }
+            Some(Checksum::Md5(ref expected)) => {
+                let md5 = digest_file::<Md5>(download.path())?;
+                if *expected != md5 {
+                    return Err(checksum_mismatch(name, file, &md5));
+                }
+                format!("md5-{}", md5)
+            }
+            _ => sha256,
+        };
+
+        let cached = self.cache_dir.join(key);
+        download.persist(&cached).map_err(|e| Error::Io(e.error))?;
+        install(&cached, &dest)?;
+        result(FetchOutcome::Downloaded)
+    }
+
+    /// GET a URL, mapping transport and HTTP failures to stacy errors
+    fn get(&self, url: &str) -> Result<reqwest::blocking::Response> {
+        let response = self.client.get(url).send().map_err(|e| {
+            if e.is_connect() {
+                Error::Network(format!("Connection failed: {}", url))
+            } else {
+                Error::Network(format!("HTTP error: {}", e))
+            }
+        })?;
+        let status = response.status();
+        if status.as_u16() == 404 {
+            return Err(Error::Config(format!("Not found: {}", url)));
+        }
+        if !status.is_success() {
+            return Err(Error::Network(format!(
+                "HTTP {} for {}",
+                status.as_u16(),
+                url
+            )));
+        }
+        Ok(response)
+    }
+
+    /// GET a repository API endpoint
+    fn get_json(&self, url: &str) -> Result<serde_json::Value> {
+        self.get(url)?
+            .json()
+            .map_err(|e| Error::Parse(format!("Invalid response from {}: {}", url, e)))
+    }
+
+    /// Stream `url` into a temporary file in the cache, hashing as it goes
+    fn download(&self, url: &str) -> Result<(tempfile::NamedTempFile, String)> {
+        let mut response = self.get(url)?;
+
+        std::fs::create_dir_all(&self.cache_dir)?;
+        let mut file = tempfile::NamedTempFile::new_in(&self.cache_dir)?;
+        let mut hasher = Sha256::new();
+        let mut buf = vec![0u8; 64 * 1024];
+        loop {
+            let read = response
+                .read(&mut buf)
+                .map_err(|e| Error::Network(format!("Failed to read {}: {}", url, e)))?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buf[..read]);
+            file.write_all(&buf[..read])?;
+        }
+        file.flush()?;
+        Ok((file, hex::encode(hasher.finalize())))
+    }
+}
+
+/// The files of a listing named in `files` (all when empty), by path or
+/// file name
+fn select_files(
+    name: &str,
+    doi: &str,
+    listing: &DatasetListing,
+    files: &[String],
+) -> Result<Vec<RemoteFile>> {
+    if files.is_empty() {
+        return Ok(listing.files.clone());
+    }
+    files
+        .iter()
+        .map(|wanted| {
+            listing
+                .files
+                .iter()
+                .find(|f| {
+                    f.path == Path::new(wanted)
+                        || f.path.file_name().is_some_and(|n| n == wanted.as_str())
+                })
+                .cloned()
+                .ok_or_else(|| {
+                    Error::Config(format!(
+                        "data '{}': no file '{}' in {} (version {})",
+                        name, wanted, doi, listing.version
+                    ))
+                })
+        })
+        .collect()
+}
+
+fn checksum_mismatch(name: &str, file: &RemoteFile, actual: &str) -> Error {
+    let expected = file
+        .checksum
+        .as_ref()
+        .map(|c| c.to_string())
+        .unwrap_or_default();
+    let (algorithm, _) = expected.split_once(':').unwrap_or(("", ""));
+    Error::Integrity(format!(
+        "Checksum mismatch for data '{}' from {}\n  expected: {}\n  got:      {}:{}",
+        name, file.url, expected, algorithm, actual
+    ))
+}
+
+/// A path a repository lists must stay inside the dataset directory
+fn is_relative_and_inside(path: &Path) -> bool {
+    !path.as_os_str().is_empty() && path.components().all(|c| matches!(c, Component::Normal(_)))
+}
+
+/// Copy `from` to `to` through a temporary file, so an interrupted copy never
+/// leaves a truncated file in the project
+fn install(from: &Path, to: &Path) -> Result<()> {
+    let parent = to.parent().unwrap_or(Path::new("."));
+    std::fs::create_dir_all(parent)?;
+    let mut temp = tempfile::NamedTempFile::new_in(parent)?;
+    std::io::copy(&mut std::fs::File::open(from)?, &mut temp)?;
+    temp.persist(to).map_err(|e| Error::Io(e.error))?;
+    Ok(())
+}
+
+/// Hex digest of a file, read in chunks so large data files aren't loaded
+/// whole
+fn digest_file<D: Digest>(path: &Path) -> Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = D::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buf[..read]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    const CONTENT: &[u8] = b"id,income\n1,100\n";
+
+    fn source(sha256: Option<String>) -> DataSource {
+        DataSource {
+            // Nothing listens on port 1: any download attempt fails
+            url: Some("http://127.0.0.1:1/survey.csv".to_string()),
+            path: PathBuf::from("data/raw/survey.csv"),
+            sha256,
+            ..Default::default()
+        }
+    }
+
+    fn content_sha256() -> String {
+        hex::encode(Sha256::digest(CONTENT))
+    }
+
+    fn fetcher(project: &TempDir, cache: &TempDir) -> DataFetcher {
+        DataFetcher::new(project.path())
+            .unwrap()
+            .with_cache_dir(cache.path().to_path_buf())
+    }
+
+    #[test]
+    fn test_missing() {
+        let project = TempDir::new().unwrap();
+        let mut data = BTreeMap::new();
+        data.insert("survey".to_string(), source(None));
+        data.insert(
+            "replication".to_string(),
+            DataSource {
+                doi: Some("10.7910/DVN/ABCDEF".to_string()),
+                path: PathBuf::from("data/replication"),
+                ..Default::default()
+            },
+        );
+        assert_eq!(missing(project.path(), &data).len(), 2);
+
+        fs::create_dir_all(project.path().join("data/raw")).unwrap();
+        fs::write(project.path().join("data/raw/survey.csv"), CONTENT).unwrap();
+        fs::create_dir_all(project.path().join("data/replication")).unwrap();
+        assert_eq!(
+            missing(project.path(), &data),
+            vec![("replication".to_string(), PathBuf::from("data/replication"))]
+        );
+
+        fs::write(project.path().join("data/replication/a.dta"), "").unwrap();
+        assert!(missing(project.path(), &data).is_empty());
+    }
+
+    #[test]
+    fn test_fetch_restores_from_cache() {
+        let project = TempDir::new().unwrap();
+        let cache = TempDir::new().unwrap();
+        fs::write(cache.path().join(content_sha256()), CONTENT).unwrap();
+        let fetcher = fetcher(&project, &cache);
+        let declared = source(Some(format!("sha256:{}", content_sha256().to_uppercase())));
+
+        let results = fetcher.fetch("survey", &declared).unwrap();
+        assert_eq!(results[0].outcome, FetchOutcome::Cached);
+        assert_eq!(
+            fs::read(project.path().join("data/raw/survey.csv")).unwrap(),
+            CONTENT
+        );
+
+        let results = fetcher.fetch("survey", &declared).unwrap();
+        assert_eq!(results[0].outcome, FetchOutcome::Present);
+
+        // A modified file is replaced from the cache
+        fs::write(project.path().join("data/raw/survey.csv"), "edited").unwrap();
+        let results = fetcher.fetch("survey", &declared).unwrap();
+        assert_eq!(results[0].outcome, FetchOutcome::Cached);
+    }
+
+    #[test]
+    fn test_fetch_unpinned_present_file_is_kept() {
+        let project = TempDir::new().unwrap();
+        let cache = TempDir::new().unwrap();
+        fs::create_dir_all(project.path().join("data/raw")).unwrap();
+        fs::write(project.path().join("data/raw/survey.csv"), CONTENT).unwrap();
+        let fetcher = fetcher(&project, &cache);
+
+        let results = fetcher.fetch("survey", &source(None)).unwrap();
+        assert_eq!(results[0].outcome, FetchOutcome::Present);
+        assert_eq!(results[0].sha256, content_sha256());
+
+        // --force downloads again, which fails here
+        assert!(fetcher
+            .with_force(true)
+            .fetch("survey", &source(None))
+            .is_err());
+    }
+
+    #[test]
+    fn test_resolve_rejects_ambiguous_sources() {
+        let project = TempDir::new().unwrap();
+        let cache = TempDir::new().unwrap();
+        let fetcher = fetcher(&project, &cache);
+
+        let neither = DataSource {
+            path: PathBuf::from("data"),
+            ..Default::default()
+        };
+        assert!(fetcher.resolve("x", &neither).is_err());
+
+        let both = DataSource {
+            doi: Some("10.7910/DVN/ABCDEF".to_string()),
+            ..source(None)
+        };
+        assert!(fetcher.resolve("x", &both).is_err());
+
+        let url_with_version = DataSource {
+            version: Some("1.0".to_string()),
+            ..source(None)
+        };
+        assert!(fetcher.resolve("x", &url_with_version).is_err());
+    }
+
+    #[test]
+    fn test_resolve_doi_from_cached_listing() {
+        let project = TempDir::new().unwrap();
+        let cache = TempDir::new().unwrap();
+        let fetcher = fetcher(&project, &cache);
+        let source = DataSource {
+            doi: Some("doi:10.7910/DVN/ABCDEF".to_string()),
+            path: PathBuf::from("data/replication"),
+            version: Some("2.0".to_string()),
+            files: vec!["survey.dta".to_string()],
+            server: Some("http://127.0.0.1:1".to_string()),
+            ..Default::default()
+        };
+
+        // Pinned listings are read from the cache before any request
+        let key = "http://127.0.0.1:1 10.7910/DVN/ABCDEF 2.0";
+        let listing = DatasetListing {
+            version: "2.0".to_string(),
+            files: vec![
+                RemoteFile {
+                    url: "http://127.0.0.1:1/api/access/datafile/1".to_string(),
+                    path: PathBuf::from("raw/survey.dta"),
+                    checksum: Some(Checksum::Md5("abc".to_string())),
+                },
+                RemoteFile {
+                    url: "http://127.0.0.1:1/api/access/datafile/2".to_string(),
+                    path: PathBuf::from("readme.txt"),
+                    checksum: None,
+                },
+            ],
+        };
+        fs::create_dir_all(cache.path().join("listings")).unwrap();
+        fs::write(
+            cache.path().join("listings").join(format!(
+                "{}.json",
+                hex::encode(Sha256::digest(key.as_bytes()))
+            )),
+            serde_json::to_string(&listing).unwrap(),
+        )
+        .unwrap();
+
+        let files = fetcher.resolve("replication", &source).unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(
+            files[0].path,
+            PathBuf::from("data/replication/raw/survey.dta")
+        );
+
+        let unknown = DataSource {
+            files: vec!["codebook.pdf".to_string()],
+            ..source
+        };
+        assert!(fetcher.resolve("replication", &unknown).is_err());
+    }
+
+    #[test]
+    fn test_normalize_doi_and_repository() {
+        assert_eq!(normalize_doi("doi:10.7910/DVN/ABC"), "10.7910/DVN/ABC");
+        assert_eq!(
+            normalize_doi("https://doi.org/10.5281/zenodo.123"),
+            "10.5281/zenodo.123"
+        );
+        assert_eq!(repository("10.5281/zenodo.123"), Repository::Zenodo);
+        assert_eq!(repository("10.7910/DVN/ABC"), Repository::Dataverse);
+    }
+
+    #[test]
+    fn test_unsafe_listing_paths() {
+        assert!(is_relative_and_inside(Path::new("raw/survey.dta")));
+        assert!(!is_relative_and_inside(Path::new("../survey.dta")));
+        assert!(!is_relative_and_inside(Path::new("/etc/passwd")));
+        assert!(!is_relative_and_inside(Path::new("")));
+    }
+}
@@ -0,0 +1,133 @@
+//! Zenodo records
+//!
+//! Every Zenodo DOI names a record, `10.5281/zenodo.<id>`, and every version
+//! of a dataset is its own record; a "concept" DOI follows the latest one.
+//! The records API (`/api/records/<id>`) lists the files with their MD5.
+
+use super::{Checksum, DatasetListing, RemoteFile};
+use crate::error::{Error, Result};
+use serde_json::Value;
+use std::path::PathBuf;
+
+/// zenodo.org
+pub const DEFAULT_SERVER: &str = "https://zenodo.org";
+
+/// The record id of a Zenodo DOI, if it is one
+pub fn record_id(doi: &str) -> Option<&str> {
+    let id = doi
+        .to_ascii_lowercase()
+        .starts_with("10.5281/zenodo.")
+        .then(|| &doi[15..])?;
+    (!id.is_empty() && id.bytes().all(|b| b.is_ascii_digit())).then_some(id)
+}
+
+/// API endpoint listing a record's files
+pub fn listing_url(server: &str, doi: &str) -> Option<String> {
+    record_id(doi).map(|id| format!("{}/api/records/{}", server, id))
+}
+
+/// Parse the records API response. The version is the one the record's
+/// metadata gives, else the record id.
+pub fn parse_listing(json: &Value, server: &str) -> Result<DatasetListing> {
+    let id = match json.get("id") {
+        Some(Value::Number(n)) => n.to_string(),
+        Some(Value::String(s)) => s.clone(),
+        _ => return Err(invalid("missing 'id'")),
+    };
+    let version = json
+        .pointer("/metadata/version")
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("record {}", id));
+
+    let files = json
+        .get("files")
+        .and_then(Value::as_array)
+        .ok_or_else(|| invalid("missing 'files'"))?
+        .iter()
+        .map(|file| {
+            let name = file
+                .get("key")
+                .or_else(|| file.get("filename"))
+                .and_then(Value::as_str)
+                .ok_or_else(|| invalid("file without a name"))?;
+            let url = file
+                .pointer("/links/self")
+                .or_else(|| file.pointer("/links/download"))
+                .and_then(Value::as_str)
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("{}/records/{}/files/{}?download=1", server, id, name));
+            let checksum = file
+                .get("checksum")
+                .and_then(Value::as_str)
+                .and_then(Checksum::parse_prefixed);
+            Ok(RemoteFile {
+                url,
+                path: PathBuf::from(name),
+                checksum,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(DatasetListing { version, files })
+}
+
+fn invalid(what: &str) -> Error {
+    Error::Parse(format!("Unexpected Zenodo API response: {}", what))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_id() {
+        assert_eq!(record_id("10.5281/zenodo.1234567"), Some("1234567"));
+        assert_eq!(record_id("10.5281/ZENODO.42"), Some("42"));
+        assert_eq!(record_id("10.5281/zenodo."), None);
+        assert_eq!(record_id("10.7910/DVN/ABCDEF"), None);
+        assert_eq!(
+            listing_url(DEFAULT_SERVER, "10.5281/zenodo.42").as_deref(),
+            Some("https://zenodo.org/api/records/42")
+        );
+    }
+
+    #[test]
+    fn test_parse_listing() {
+        let json: Value = serde_json::from_str(
+            r#"{
+              "id": 1234567,
+              "doi": "10.5281/zenodo.1234567",
+              "metadata": { "title": "Replication data", "version": "v1.2" },
+              "files": [
+                {
+                  "key": "analysis.dta",
+                  "size": 2048,
+                  "checksum": "md5:0cc175b9c0f1b6a831c399e269772661",
+                  "links": { "self": "https://zenodo.org/api/records/1234567/files/analysis.dta/content" }
+                },
+                { "key": "codebook.pdf" }
+              ]
+            }"#,
+        )
+        .unwrap();
+
+        let listing = parse_listing(&json, DEFAULT_SERVER).unwrap();
+        assert_eq!(listing.version, "v1.2");
+        assert_eq!(listing.files[0].path, PathBuf::from("analysis.dta"));
+        assert_eq!(
+            listing.files[0].url,
+            "https://zenodo.org/api/records/1234567/files/analysis.dta/content"
+        );
+        assert_eq!(
+            listing.files[0].checksum,
+            Some(Checksum::Md5(
+                "0cc175b9c0f1b6a831c399e269772661".to_string()
+            ))
+        );
+        assert_eq!(
+            listing.files[1].url,
+            "https://zenodo.org/records/1234567/files/codebook.pdf?download=1"
+        );
+    }
+}
@@ -0,0 +1,239 @@
+//! Parsed dependencies kept between commands
+//!
+//! `stacy deps`, `run --cache` and the build cache parse every script they
+//! reach for `do`, `run`, `include` and `require` statements. On projects with
+//! hundreds of do-files that parse shows up in `--profile`, so the statements
+//! found in each file are kept in `.stacy/cache/deps.json`:
+//!
+//! - a file whose size and modification time are unchanged isn't read
+//! - a file that was touched but whose content hash is unchanged is read but
+//!   not parsed
+//! - anything else is parsed and its entry replaced
+//!
+//! Outside a project nothing is stored. The cache only saves time: one that
+//! can't be read or written is ignored and scripts are parsed as before.
+
+use super::parser::{parse_dependencies_from_content, Dependency};
+use crate::error::{Error, Result};
+use crate::utils::encoding::{self, Encoding};
+use crate::utils::paths;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Cache format version; a cache of another version is dropped
+const CACHE_VERSION: u32 = 3;
+
+/// Cache file within the project root
+const CACHE_FILE: &str = ".stacy/cache/deps.json";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheFile {
+    version: u32,
+    entries: HashMap<PathBuf, Entry>,
+}
+
+/// What a file held when it was last parsed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry {
+    size: u64,
+    mtime_secs: u64,
+    mtime_nanos: u32,
+    /// SHA256 of the content
+    hash: String,
+    encoding: Encoding,
+    dependencies: Vec<Dependency>,
+}
+
+/// Dependencies parsed per file, keyed by canonical path
+#[derive(Debug, Default)]
+pub struct DepsCache {
+    /// Where the cache is saved; `None` outside a project
+    path: Option<PathBuf>,
+    entries: HashMap<PathBuf, Entry>,
+    dirty: bool,
+}
+
+impl DepsCache {
+    /// The cache of the project at `project_root`
+    pub fn load(project_root: &Path) -> Self {
+        let path = project_root.join(CACHE_FILE);
+        let entries = std::fs::read(&path)
+            .ok()
+            .and_then(|content| serde_json::from_slice::<CacheFile>(&content).ok())
+            .filter(|file| file.version == CACHE_VERSION)
+            .map(|file| file.entries)
+            .unwrap_or_default();
+        Self {
+            path: Some(path),
+            entries,
+            dirty: false,
+        }
+    }
+
+    /// The cache of the project `script` belongs to, or one that is never
+    /// saved when the script is outside a project
+    pub fn for_script(script: &Path) -> Self {
+        let dir = script
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        match crate::project::root::find_project_root(dir) {
+            Ok(Some(root)) => Self::load(&root.path),
+            _ => Self::default(),
+        }
+    }
+
+    /// The dependencies of `script`, from the cache when the file hasn't
+    /// changed. Files that aren't UTF-8 (older do-files are often Latin-1)
+    /// are decoded as Latin-1, so paths with accented characters survive.
+    pub fn parse(&mut self, script: &Path) -> Result<Vec<Dependency>> {
+        let read_error = |e: std::io::Error| {
+            Error::Io(std::io::Error::new(
+                e.kind(),
+                format!("Failed to read {}: {}", script.display(), e),
+            ))
+        };
+        let key = paths::canonicalize_or_self(script);
+        let metadata = std::fs::metadata(script).map_err(read_error)?;
+        let mtime = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .unwrap_or_default();
+
+        if let Some(entry) = self.entries.get(&key) {
+            if entry.size == metadata.len()
+                && entry.mtime_secs == mtime.as_secs()
+                && entry.mtime_nanos == mtime.subsec_nanos()
+            {
+                return Ok(entry.dependencies.clone());
+            }
+        }
+
+        let content = std::fs::read(script).map_err(read_error)?;
+        let hash = hex::encode(Sha256::digest(&content));
+        let (dependencies, encoding) = match self.entries.get(&key) {
+            Some(entry) if entry.hash == hash => (entry.dependencies.clone(), entry.encoding),
+            _ => {
+                let (text, encoding) = encoding::decode(&content);
+                (parse_dependencies_from_content(&text)?, encoding)
+            }
+        };
+        self.entries.insert(
+            key,
+            Entry {
+                size: metadata.len(),
+                mtime_secs: mtime.as_secs(),
+                mtime_nanos: mtime.subsec_nanos(),
+                hash,
+                encoding,
+                dependencies: dependencies.clone(),
+            },
+        );
+        self.dirty = true;
+        Ok(dependencies)
+    }
+
+    /// How `script` was decoded when it was parsed, `None` before
+    /// [`DepsCache::parse`] has seen it
+    pub fn encoding(&self, script: &Path) -> Option<Encoding> {
+        self.entries
+            .get(&paths::canonicalize_or_self(script))
+            .map(|entry| entry.encoding)
+    }
+
+    /// Write the cache back when something was parsed. Best effort: a cache
+    /// that can't be written only costs a parse next time.
+    pub fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        if !self.dirty {
+            return;
+        }
+        let Some(dir) = path.parent() else {
+            return;
+        };
+        let file = CacheFile {
+            version: CACHE_VERSION,
+            entries: self.entries.clone(),
+        };
+        let _ = std::fs::create_dir_all(dir).and_then(|_| {
+            // Through a temporary file: scripts running side by side may save
+            // at the same time, and a reader must never see half a file
+            let mut tmp = tempfile::NamedTempFile::new_in(dir)?;
+            std::io::Write::write_all(&mut tmp, &serde_json::to_vec(&file).unwrap_or_default())?;
+            tmp.persist(path).map_err(|e| e.error)?;
+            Ok(())
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_cache_round_trip() {
+        let temp = TempDir::new().unwrap();
+        let script = temp.path().join("main.do");
+        fs::write(&script, "do \"clean.do\"\nrequire estout\n").unwrap();
+
+        let mut cache = DepsCache::load(temp.path());
+        assert_eq!(cache.parse(&script).unwrap().len(), 2);
+        cache.save();
+        assert!(temp.path().join(CACHE_FILE).exists());
+
+        // Served from the saved cache: the entry is there, nothing to save
+        let mut cache = DepsCache::load(temp.path());
+        let deps = cache.parse(&script).unwrap();
+        assert_eq!(deps[0].path, PathBuf::from("clean.do"));
+        assert!(!cache.dirty);
+    }
+
+    #[test]
+    fn test_cache_reparses_changed_files() {
+        let temp = TempDir::new().unwrap();
+        let script = temp.path().join("main.do");
+        fs::write(&script, "do \"clean.do\"\n").unwrap();
+
+        let mut cache = DepsCache::load(temp.path());
+        cache.parse(&script).unwrap();
+
+        // Same size, other content: the hash tells them apart
+        fs::write(&script, "do \"other.do\"\n").unwrap();
+        let key = paths::canonicalize(&script).unwrap();
+        cache.entries.get_mut(&key).unwrap().mtime_secs = 0;
+        let deps = cache.parse(&script).unwrap();
+        assert_eq!(deps[0].path, PathBuf::from("other.do"));
+    }
+
+    #[test]
+    fn test_cache_decodes_latin1_scripts() {
+        let temp = TempDir::new().unwrap();
+        let script = temp.path().join("main.do");
+        fs::write(&script, b"do \"r\xE9sultats.do\"\n").unwrap();
+
+        let mut cache = DepsCache::load(temp.path());
+        let deps = cache.parse(&script).unwrap();
+        assert_eq!(deps[0].path, PathBuf::from("résultats.do"));
+        assert_eq!(cache.encoding(&script), Some(Encoding::Latin1));
+    }
+
+    #[test]
+    fn test_cache_outside_project_is_not_saved() {
+        let temp = TempDir::new().unwrap();
+        let script = temp.path().join("main.do");
+        fs::write(&script, "display 1\n").unwrap();
+
+        let mut cache = DepsCache::default();
+        cache.parse(&script).unwrap();
+        cache.save();
+        assert!(!temp.path().join(".stacy").exists());
+    }
+}
@@ -0,0 +1,213 @@
+//! Find the data files a Stata script reads
+//!
+//! Recognizes `use`, `merge`, `append`, `joinby` and `cross` (which read
+//! `.dta` files) and the `import`, `insheet`, `infile` and `infix` family.
+//! Only reads are collected: a file the script writes is an output, and
+//! tracking it would make every run invalidate its own cache entry.
+
+use crate::error::{Error, Result};
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+/// A data file read by a Stata script
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataReference {
+    /// Path to the file (as written in the script, default extension added)
+    pub path: PathBuf,
+    /// The command that reads it (`use`, `merge`, `import delimited`, ...)
+    pub command: String,
+    /// Line number where the command was found (1-indexed)
+    pub line_number: usize,
+}
+
+/// A reading command, after any `capture`/`quietly`/`noisily` prefixes.
+static COMMAND_PATTERN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)^\s*(?:(?:cap(?:ture)?|qui(?:etly)?|noi(?:sily)?)\s+)*(use|merge|append|joinby|cross|insheet|infile|infix|import\s+(?:delimited|excel|sas|spss))\b(.*)$",
+    )
+    .unwrap()
+});
+
+static USING_PATTERN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\busing\s+(.*)$").unwrap());
+
+/// One filename: `"compound quoted"'`, `"quoted"` or a bare word up to a comma.
+static PATH_PATTERN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"^\s*(?:`"([^"]+)"'|"([^"]+)"|([^\s,"]+))"#).unwrap());
+
+/// Parse a Stata script file for the data files it reads
+pub fn parse_data_references(script: &Path) -> Result<Vec<DataReference>> {
+    let (content, _) = crate::utils::encoding::read_text(script).map_err(|e| {
+        Error::Io(std::io::Error::new(
+            e.kind(),
+            format!("Failed to read {}: {}", script.display(), e),
+        ))
+    })?;
+
+    Ok(parse_data_references_from_content(&content))
+}
+
+/// Parse data references from script content
+pub fn parse_data_references_from_content(content: &str) -> Vec<DataReference> {
+    let mut references = Vec::new();
+
+    for (line_number, line) in content.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('*') || trimmed.starts_with("//") {
+            continue;
+        }
+
+        let line_without_comment = match line.find("//") {
+            Some(pos) if !line[..pos].ends_with(':') => &line[..pos],
+            _ => line,
+        };
+
+        let Some(caps) = COMMAND_PATTERN.captures(line_without_comment) else {
+            continue;
+        };
+        let command = caps[1].split_whitespace().collect::<Vec<_>>().join(" ");
+        let command = command.to_lowercase();
+        let rest = &caps[2];
+
+        // `using` names the file wherever it appears; `use` and `import`
+        // also take the filename directly (`use auto, clear`).
+        let paths = if let Some(using) = USING_PATTERN.captures(rest) {
+            parse_paths(&using[1])
+        } else if command == "use" || command.starts_with("import") {
+            parse_paths(rest).into_iter().take(1).collect()
+        } else {
+            Vec::new()
+        };
+
+        for path in paths {
+            // Remote files are not on disk to hash
+            if path.contains("://") {
+                continue;
+            }
+            references.push(DataReference {
+                path: with_default_extension(&path, &command),
+                command: command.clone(),
+                line_number: line_number + 1,
+            });
+        }
+    }
+
+    references
+}
+
+/// Filenames at the start of `text`, up to the options comma
+fn parse_paths(mut text: &str) -> Vec<String> {
+    let mut paths = Vec::new();
+    while let Some(caps) = PATH_PATTERN.captures(text) {
+        let m = caps
+            .get(1)
+            .or_else(|| caps.get(2))
+            .or_else(|| caps.get(3))
+            .unwrap();
+        paths.push(m.as_str().trim().to_string());
+        text = &text[caps.get(0).unwrap().end()..];
+    }
+    paths
+}
+
+/// Add the extension Stata assumes when the script leaves it off
+fn with_default_extension(path: &str, command: &str) -> PathBuf {
+    let path = PathBuf::from(path);
+    if path.extension().is_some() {
+        return path;
+    }
+    let extension = match command {
+        "use" | "merge" | "append" | "joinby" | "cross" => "dta",
+        "import delimited" => "csv",
+        "insheet" | "infile" | "infix" => "raw",
+        _ => return path,
+    };
+    path.with_extension(extension)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(content: &str) -> Vec<PathBuf> {
+        parse_data_references_from_content(content)
+            .into_iter()
+            .map(|r| r.path)
+            .collect()
+    }
+
+    #[test]
+    fn test_use() {
+        assert_eq!(
+            paths("use data/raw, clear"),
+            vec![PathBuf::from("data/raw.dta")]
+        );
+        assert_eq!(
+            paths(r#"use "data/my file.dta", clear"#),
+            vec![PathBuf::from("data/my file.dta")]
+        );
+        assert_eq!(
+            paths("use id income using `\"data/panel.dta\"', clear"),
+            vec![PathBuf::from("data/panel.dta")]
+        );
+    }
+
+    #[test]
+    fn test_merge_append_using() {
+        let refs = parse_data_references_from_content(
+            "merge 1:1 id using data/covariates, keep(3)\nappend using a.dta \"b c.dta\"\n",
+        );
+        assert_eq!(refs.len(), 3);
+        assert_eq!(refs[0].command, "merge");
+        assert_eq!(refs[0].path, PathBuf::from("data/covariates.dta"));
+        assert_eq!(refs[0].line_number, 1);
+        assert_eq!(refs[1].path, PathBuf::from("a.dta"));
+        assert_eq!(refs[2].path, PathBuf::from("b c.dta"));
+        assert_eq!(refs[2].line_number, 2);
+    }
+
+    #[test]
+    fn test_import() {
+        let refs = parse_data_references_from_content(
+            "import delimited raw/survey, clear\nimport excel using \"raw/prices.xlsx\", firstrow\n",
+        );
+        assert_eq!(refs[0].command, "import delimited");
+        assert_eq!(refs[0].path, PathBuf::from("raw/survey.csv"));
+        assert_eq!(refs[1].command, "import excel");
+        assert_eq!(refs[1].path, PathBuf::from("raw/prices.xlsx"));
+    }
+
+    #[test]
+    fn test_prefixes_and_case() {
+        assert_eq!(
+            paths("capture noisily USE data/raw.dta"),
+            vec![PathBuf::from("data/raw.dta")]
+        );
+        assert_eq!(
+            paths("qui merge m:1 id using lookup"),
+            vec![PathBuf::from("lookup.dta")]
+        );
+    }
+
+    #[test]
+    fn test_ignores_writes_comments_and_remote() {
+        let content = r#"
+* use commented.dta
+// use also_commented.dta
+save data/clean.dta, replace
+export delimited out.csv
+sysuse auto
+use "https://example.com/data.dta", clear
+"#;
+        assert!(paths(content).is_empty());
+    }
+
+    #[test]
+    fn test_inline_comment() {
+        assert_eq!(
+            paths("use raw.dta, clear // load the survey"),
+            vec![PathBuf::from("raw.dta")]
+        );
+    }
+}
@@ -0,0 +1,309 @@
+//! Whole-project dependency graph (`stacy deps --all`)
+//!
+//! Finds every `.do` file under the project root and links them by their
+//! `do`, `run` and `include` statements into one graph, which shows what the
+//! tree of a single script can't:
+//!
+//! - entry points: scripts no other script calls that stacy.toml runs (a
+//!   task or pipeline stage), that are tests, or that call other scripts
+//! - orphans: scripts nothing calls, that call nothing and that stacy.toml
+//!   doesn't run, often leftovers
+//! - shared scripts: scripts called from more than one other script
+//!
+//! Hidden directories (`.git`, `.stacy`) and the directories passed as
+//! `skip` (the project's ado directories) are left out.
+
+use super::cache::DepsCache;
+use super::parser::{is_dynamic_path, DependencyType};
+use super::tree::AdopathChange;
+use crate::error::{Error, Result};
+use crate::utils::paths::normalize;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// A `do`, `run` or `include` statement linking two scripts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Call {
+    /// Calling script, relative to the project root
+    pub from: PathBuf,
+    /// Line of the statement (1-indexed)
+    pub line: usize,
+    pub dep_type: DependencyType,
+    /// Called script, relative to the project root when inside it
+    pub to: PathBuf,
+}
+
+/// The scripts of a project and the calls between them
+#[derive(Debug, Default)]
+pub struct ProjectGraph {
+    /// Every `.do` file, relative to the project root, sorted
+    pub scripts: Vec<PathBuf>,
+    /// Calls from one script of the project to another
+    pub calls: Vec<Call>,
+    /// Calls to files that don't exist
+    pub missing: Vec<Call>,
+    pub entry_points: Vec<PathBuf>,
+    pub orphans: Vec<PathBuf>,
+    /// Scripts with more than one caller, with their callers
+    pub shared: BTreeMap<PathBuf, Vec<PathBuf>>,
+    /// Scripts that end up calling themselves
+    pub circular: Vec<PathBuf>,
+    /// `adopath` and `sysdir set` statements, in script order
+    pub adopath: Vec<AdopathChange>,
+}
+
+impl ProjectGraph {
+    pub fn has_missing(&self) -> bool {
+        !self.missing.is_empty()
+    }
+
+    pub fn has_circular(&self) -> bool {
+        !self.circular.is_empty()
+    }
+}
+
+/// Build the graph of every `.do` file under `root`. `runs` holds the
+/// scripts stacy itself starts (tasks, pipeline stages, tests), relative to
+/// the root: those are entry points even when they call nothing. Scripts
+/// are parsed through `cache`.
+pub fn build_project_graph(
+    root: &Path,
+    runs: &HashSet<PathBuf>,
+    skip: &[PathBuf],
+    cache: &mut DepsCache,
+) -> Result<ProjectGraph> {
+    let mut scripts = Vec::new();
+    let walk = walkdir::WalkDir::new(root)
+        .min_depth(1)
+        .into_iter()
+        .filter_entry(|e| {
+            !(e.file_type().is_dir()
+                && (e.file_name().to_string_lossy().starts_with('.')
+                    || skip.iter().any(|dir| dir == e.path())))
+        });
+    for entry in walk {
+        let entry = entry.map_err(|e| {
+            Error::Io(
+                e.into_io_error()
+                    .unwrap_or_else(|| std::io::Error::other("directory walk failed")),
+            )
+        })?;
+        if entry.file_type().is_file() && entry.path().extension().is_some_and(|e| e == "do") {
+            let rel = entry.path().strip_prefix(root).unwrap_or(entry.path());
+            scripts.push(rel.to_path_buf());
+        }
+    }
+    scripts.sort();
+    let known: BTreeSet<&Path> = scripts.iter().map(PathBuf::as_path).collect();
+
+    let mut calls = Vec::new();
+    let mut missing = Vec::new();
+    let mut adopath = Vec::new();
+    for script in &scripts {
+        let path = root.join(script);
+        let base_dir = path.parent().unwrap_or(root);
+        for dep in cache.parse(&path)? {
+            if dep.dep_type == DependencyType::Adopath {
+                adopath.push(AdopathChange {
+                    script: script.clone(),
+                    line_number: dep.line_number,
+                    statement: dep.raw_statement,
+                });
+                continue;
+            }
+            if dep.dep_type == DependencyType::Require || is_dynamic_path(&dep.path) {
+                continue;
+            }
+            let resolved = normalize(&dep.resolve(base_dir));
+            let to = resolved
+                .strip_prefix(root)
+                .map(Path::to_path_buf)
+                .unwrap_or_else(|_| resolved.clone());
+            let call = Call {
+                from: script.clone(),
+                line: dep.line_number,
+                dep_type: dep.dep_type,
+                to,
+            };
+            if known.contains(call.to.as_path()) {
+                calls.push(call);
+            } else if !resolved.exists() {
+                missing.push(call);
+            }
+            // Anything else exists outside the graph (an `.ado`, a skipped
+            // directory): not a script of the project
+        }
+    }
+
+    let mut callers: BTreeMap<&Path, BTreeSet<&Path>> = BTreeMap::new();
+    for call in &calls {
+        callers
+            .entry(call.to.as_path())
+            .or_default()
+            .insert(call.from.as_path());
+    }
+    let calls_out: HashSet<&Path> = calls
+        .iter()
+        .chain(&missing)
+        .map(|call| call.from.as_path())
+        .collect();
+
+    let mut entry_points = Vec::new();
+    let mut orphans = Vec::new();
+    for script in &scripts {
+        if callers.contains_key(script.as_path()) {
+            continue;
+        }
+        if runs.contains(script) || calls_out.contains(script.as_path()) {
+            entry_points.push(script.clone());
+        } else {
+            orphans.push(script.clone());
+        }
+    }
+    let shared = callers
+        .iter()
+        .filter(|(_, from)| from.len() > 1)
+        .map(|(to, from)| {
+            (
+                to.to_path_buf(),
+                from.iter().map(|p| p.to_path_buf()).collect(),
+            )
+        })
+        .collect();
+    let circular = circular(&scripts, &calls);
+
+    Ok(ProjectGraph {
+        scripts,
+        calls,
+        missing,
+        entry_points,
+        orphans,
+        shared,
+        circular,
+        adopath,
+    })
+}
+
+/// Scripts a call leads back to, found by a depth-first search
+fn circular(scripts: &[PathBuf], calls: &[Call]) -> Vec<PathBuf> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum Mark {
+        Open,
+        Done,
+    }
+
+    fn visit<'a>(
+        script: &'a Path,
+        next: &HashMap<&'a Path, Vec<&'a Path>>,
+        marks: &mut HashMap<&'a Path, Mark>,
+        found: &mut BTreeSet<PathBuf>,
+    ) {
+        marks.insert(script, Mark::Open);
+        for &to in next.get(script).into_iter().flatten() {
+            match marks.get(to) {
+                Some(Mark::Open) => {
+                    found.insert(to.to_path_buf());
+                }
+                Some(Mark::Done) => {}
+                None => visit(to, next, marks, found),
+            }
+        }
+        marks.insert(script, Mark::Done);
+    }
+
+    let mut next: HashMap<&Path, Vec<&Path>> = HashMap::new();
+    for call in calls {
+        next.entry(call.from.as_path())
+            .or_default()
+            .push(call.to.as_path());
+    }
+    let mut marks = HashMap::new();
+    let mut found = BTreeSet::new();
+    for script in scripts {
+        if !marks.contains_key(script.as_path()) {
+            visit(script, &next, &mut marks, &mut found);
+        }
+    }
+    found.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn write(root: &Path, name: &str, content: &str) {
+        let path = root.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    fn paths(names: &[&str]) -> Vec<PathBuf> {
+        names.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn test_project_graph_classifies_scripts() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path();
+        write(root, "main.do", "do src/clean.do\ndo src/report.do\n");
+        write(root, "src/clean.do", "include \"../lib/helpers.do\"\n");
+        write(
+            root,
+            "src/report.do",
+            "run ../lib/helpers.do\ndo missing.do\n",
+        );
+        write(root, "lib/helpers.do", "display 1\n");
+        write(root, "scratch.do", "display 2\nadopath ++ mine\n");
+        write(root, "nightly.do", "display 3\n");
+        write(root, "ado/pkg/setup.do", "display 4\n");
+        write(root, ".stacy/shell/profile.do", "display 5\n");
+
+        let runs: HashSet<PathBuf> = [PathBuf::from("nightly.do")].into_iter().collect();
+        let graph =
+            build_project_graph(root, &runs, &[root.join("ado")], &mut DepsCache::default())
+                .unwrap();
+
+        assert_eq!(
+            graph.scripts,
+            paths(&[
+                "lib/helpers.do",
+                "main.do",
+                "nightly.do",
+                "scratch.do",
+                "src/clean.do",
+                "src/report.do",
+            ])
+        );
+        assert_eq!(graph.entry_points, paths(&["main.do", "nightly.do"]));
+        assert_eq!(graph.orphans, paths(&["scratch.do"]));
+        assert_eq!(
+            graph.shared[Path::new("lib/helpers.do")],
+            paths(&["src/clean.do", "src/report.do"])
+        );
+        assert_eq!(graph.missing.len(), 1);
+        assert_eq!(graph.missing[0].to, PathBuf::from("src/missing.do"));
+        assert_eq!(graph.missing[0].line, 2);
+        assert!(!graph.has_circular());
+        // An adopath change is reported, not followed: scratch.do stays an orphan
+        assert_eq!(graph.adopath.len(), 1);
+        assert_eq!(graph.adopath[0].script, PathBuf::from("scratch.do"));
+        assert_eq!(graph.adopath[0].statement, "adopath ++ mine");
+    }
+
+    #[test]
+    fn test_project_graph_finds_cycles() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path();
+        write(root, "a.do", "do b.do\n");
+        write(root, "b.do", "do a.do\n");
+        write(root, "c.do", "do c.do\n");
+
+        let graph =
+            build_project_graph(root, &HashSet::new(), &[], &mut DepsCache::default()).unwrap();
+        assert_eq!(graph.circular, paths(&["a.do", "c.do"]));
+        // Each is called by another, so none starts the project
+        assert!(graph.entry_points.is_empty());
+    }
+}
@@ -0,0 +1,349 @@
+//! Quick checks of an editor buffer (`lint` in `stacy daemon`)
+//!
+//! Runs on the text as it is in the editor, saved or not, and only reports
+//! what stacy can tell without running Stata: scripts named by `do`, `run` or
+//! `include` that don't exist, `require`d packages the project doesn't
+//! declare, and `adopath`/`sysdir set` statements that reach past the
+//! packages stacy locked. Paths that hold a macro are left alone, as in
+//! `stacy deps`.
+//!
+//! It also flags paths that only work on the machine they were written on:
+//! absolute paths (`C:\data`, `/Users/me/...`, a `cd` to one) and backslash
+//! separators, which Stata only understands on Windows. Where stacy can tell
+//! the portable form it suggests it: the path relative to the project root,
+//! or the same path with `/`.
+
+use crate::deps::parser::{is_dynamic_path, parse_dependencies_from_content, DependencyType};
+use crate::error::Result;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::LazyLock;
+
+/// How serious a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// The script will fail when it gets there
+    Error,
+    /// The script may run here but not on another machine
+    Warning,
+}
+
+/// A problem found on one line of the buffer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    /// 1-indexed line number
+    pub line: usize,
+    pub severity: Severity,
+    pub message: String,
+    /// Replacement for the offending path, when stacy can tell it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suggestion: Option<String>,
+}
+
+/// Check `text`, a script whose relative paths resolve against `base_dir`,
+/// in the project at `root`. `declared` holds the packages from stacy.toml
+/// and stacy.lock (lowercase); without a project (`None`) `require` isn't
+/// checked.
+pub fn lint_buffer(
+    text: &str,
+    base_dir: &Path,
+    root: &Path,
+    declared: Option<&HashSet<String>>,
+) -> Result<Vec<Diagnostic>> {
+    let mut diagnostics = Vec::new();
+    for dep in parse_dependencies_from_content(text)? {
+        match dep.dep_type {
+            DependencyType::Require => {
+                let name = dep.path.to_string_lossy().to_lowercase();
+                if declared.is_some_and(|declared| !declared.contains(&name)) {
+                    diagnostics.push(Diagnostic {
+                        line: dep.line_number,
+                        severity: Severity::Warning,
+                        message: format!(
+                            "Package '{}' is not in stacy.toml (stacy add {})",
+                            name, name
+                        ),
+                        suggestion: None,
+                    });
+                }
+            }
+            DependencyType::Adopath => diagnostics.push(Diagnostic {
+                line: dep.line_number,
+                severity: Severity::Warning,
+                message: format!(
+                    "'{}' changes the adopath: commands found there aren't locked. \
+                     Use `stacy add` for packages or [paths] ado in stacy.toml for local .ado files",
+                    dep.raw_statement
+                ),
+                suggestion: None,
+            }),
+            _ if is_dynamic_path(&dep.path) => {}
+            dep_type => {
+                if !dep.resolve(base_dir).exists() {
+                    diagnostics.push(Diagnostic {
+                        line: dep.line_number,
+                        severity: Severity::Error,
+                        message: format!("{}: file not found: {}", dep_type, dep.path.display()),
+                        suggestion: None,
+                    });
+                }
+            }
+        }
+    }
+    diagnostics.extend(lint_paths(text, root));
+    diagnostics.sort_by_key(|diagnostic| diagnostic.line);
+    Ok(diagnostics)
+}
+
+/// A path rooted somewhere only this machine has: a drive letter, a UNC
+/// share, a home directory or a mount point
+static MACHINE_PATH: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)^(?:[a-z]:[\\/]|\\\\[^\\]+\\|~[\\/]|/(?:users|home|volumes|mnt|media)/)")
+        .unwrap()
+});
+
+/// A backslash between two path characters (`data\raw.dta`). A backslash
+/// before `` ` `` or `$` is Stata's escape, not a separator.
+static BACKSLASH_SEPARATOR: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"[\w.~-]\\[\w.~-]").unwrap());
+
+/// A string that could be a path: no quotes, operators or parentheses
+static PATH_LIKE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^[\w .~$:{}/\\-]+$").unwrap());
+
+/// `cd`, behind any `capture`/`quietly` prefix
+static CD_PATTERN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)^\s*(?:(?:cap(?:ture)?|qui(?:etly)?|noi(?:sily)?)\s+)*cd\s").unwrap()
+});
+
+static QUOTED: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#""([^"]*)""#).unwrap());
+
+/// Paths in `text` that won't work on another machine
+fn lint_paths(text: &str, root: &Path) -> Vec<Diagnostic> {
+    let root = root.to_string_lossy().replace('\\', "/");
+    let mut diagnostics = Vec::new();
+    let mut in_block_comment = false;
+    for (index, line) in text.lines().enumerate() {
+        let code = strip_comments(line, &mut in_block_comment);
+        if code.trim_start().starts_with('*') {
+            continue;
+        }
+        let is_cd = CD_PATTERN.is_match(&code);
+        for path in path_candidates(&code) {
+            if let Some((message, suggestion)) = check_path(&path, &root, is_cd) {
+                diagnostics.push(Diagnostic {
+                    line: index + 1,
+                    severity: Severity::Warning,
+                    message,
+                    suggestion,
+                });
+            }
+        }
+    }
+    diagnostics
+}
+
+/// What a path does wrong, and its portable form when there is one
+fn check_path(path: &str, root: &str, is_cd: bool) -> Option<(String, Option<String>)> {
+    let forward = path.replace('\\', "/");
+    let relative = relative_to_root(&forward, root);
+    if MACHINE_PATH.is_match(path) || relative.is_some() {
+        let message = match (&relative, is_cd) {
+            (Some(relative), true) => format!(
+                "cd to '{}' only works on this machine: cd \"{}\" relative to the project root, \
+                 or run with stacy run -C",
+                path, relative
+            ),
+            (None, true) => format!(
+                "cd to '{}' only works on this machine: keep the location in a global set in \
+                 one place, or run with stacy run -C",
+                path
+            ),
+            (Some(relative), false) => format!(
+                "Absolute path '{}' only exists on this machine: use \"{}\", relative to the project root",
+                path, relative
+            ),
+            (None, false) => format!(
+                "Absolute path '{}' only exists on this machine: set a global to its directory \
+                 in one place and write \"$global/...\" here",
+                path
+            ),
+        };
+        return Some((message, relative));
+    }
+    if BACKSLASH_SEPARATOR.is_match(path) {
+        return Some((
+            format!(
+                "Path '{}' uses backslashes, which only work on Windows: write \"{}\"",
+                path, forward
+            ),
+            Some(forward),
+        ));
+    }
+    None
+}
+
+/// `path` relative to the project root, when it lies inside it
+fn relative_to_root(path: &str, root: &str) -> Option<String> {
+    let root = root.trim_end_matches('/');
+    if root.is_empty() {
+        return None;
+    }
+    // Windows paths differ in case as often as not
+    let (head, rest) = (path.get(..root.len())?, &path[root.len()..]);
+    let same = if head.contains(':') {
+        head.eq_ignore_ascii_case(root)
+    } else {
+        head == root
+    };
+    if !same {
+        return None;
+    }
+    match rest {
+        "" | "/" => Some(".".to_string()),
+        _ => rest.strip_prefix('/').map(str::to_string),
+    }
+}
+
+/// Quoted strings on the line, and the unquoted words that look like paths
+fn path_candidates(code: &str) -> Vec<String> {
+    let quoted = QUOTED
+        .captures_iter(code)
+        .map(|caps| caps.get(1).map_or("", |m| m.as_str()).trim().to_string());
+    let unquoted = QUOTED.replace_all(code, " ");
+    let words = unquoted
+        .split_whitespace()
+        .map(|word| word.trim_matches(['`', '\'', ',', '(', ')']).to_string());
+    quoted
+        .chain(words.collect::<Vec<_>>())
+        .filter(|candidate| !candidate.is_empty() && PATH_LIKE.is_match(candidate))
+        .collect()
+}
+
+/// The code on a line, without `//` and `/* */` comments. `in_block` carries
+/// an open `/*` over to the next line.
+pub(crate) fn strip_comments(line: &str, in_block: &mut bool) -> String {
+    let mut code = String::new();
+    let mut rest = line;
+    loop {
+        if *in_block {
+            match rest.find("*/") {
+                Some(end) => {
+                    rest = &rest[end + 2..];
+                    *in_block = false;
+                }
+                None => return code,
+            }
+        }
+        match (rest.find("/*"), rest.find("//")) {
+            (Some(open), Some(pos)) if pos < open => {
+                code.push_str(&rest[..pos]);
+                return code;
+            }
+            (Some(open), _) => {
+                code.push_str(&rest[..open]);
+                rest = &rest[open + 2..];
+                *in_block = true;
+            }
+            (None, Some(pos)) => {
+                code.push_str(&rest[..pos]);
+                return code;
+            }
+            (None, None) => {
+                code.push_str(rest);
+                return code;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_lint_flags_missing_scripts() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("clean.do"), "").unwrap();
+        let text = "do clean.do\nrun \"missing.do\"\ndo \"$root/setup.do\"\n";
+        let diagnostics = lint_buffer(text, dir.path(), dir.path(), None).unwrap();
+        assert_eq!(
+            diagnostics,
+            vec![Diagnostic {
+                line: 2,
+                severity: Severity::Error,
+                message: "run: file not found: missing.do".to_string(),
+                suggestion: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_lint_flags_undeclared_packages() {
+        let dir = TempDir::new().unwrap();
+        let declared: HashSet<String> = ["estout".to_string()].into_iter().collect();
+        let text = "require estout\ncap require reghdfe >= 6.0\n";
+        let diagnostics = lint_buffer(text, dir.path(), dir.path(), Some(&declared)).unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].line, 2);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert!(diagnostics[0].message.contains("'reghdfe'"));
+
+        // Outside a project there is nothing to check packages against
+        assert!(lint_buffer(text, dir.path(), dir.path(), None)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_lint_flags_adopath_changes() {
+        let dir = TempDir::new().unwrap();
+        let text = "display 1\nadopath ++ \"$root/ado\"\nsysdir set PLUS plus\n";
+        let diagnostics = lint_buffer(text, dir.path(), dir.path(), None).unwrap();
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].line, 2);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert!(diagnostics[1].message.starts_with("'sysdir set PLUS plus'"));
+    }
+
+    #[test]
+    fn test_lint_flags_absolute_paths() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().to_string_lossy().replace('\\', "/");
+        let text = format!(
+            "use \"{root}/data/raw.dta\", clear\n\
+             cd C:\\Users\\me\\project\n\
+             save `\"/Users/me/Dropbox/out.dta\"', replace\n\
+             use \"$data/raw.dta\"\n\
+             * use \"C:/old/raw.dta\"\n\
+             /* import delimited \"~/raw.csv\"\n\
+             */ display 4/2\n"
+        );
+        let diagnostics = lint_buffer(&text, dir.path(), dir.path(), None).unwrap();
+        let lines: Vec<usize> = diagnostics.iter().map(|d| d.line).collect();
+        assert_eq!(lines, vec![1, 2, 3]);
+        // Inside the project: the relative path is the fix
+        assert_eq!(diagnostics[0].suggestion.as_deref(), Some("data/raw.dta"));
+        assert!(diagnostics[1]
+            .message
+            .starts_with("cd to 'C:\\Users\\me\\project'"));
+        assert_eq!(diagnostics[1].suggestion, None);
+        assert!(diagnostics[2].message.contains("set a global"));
+    }
+
+    #[test]
+    fn test_lint_flags_backslash_separators() {
+        let dir = TempDir::new().unwrap();
+        let text = "use \"data\\raw.dta\"\nrun code\\clean.do // clean\ndisplay \"\\`x'\"\n";
+        let diagnostics = lint_buffer(text, dir.path(), dir.path(), None).unwrap();
+        let suggestions: Vec<_> = diagnostics
+            .iter()
+            .filter_map(|d| d.suggestion.as_deref())
+            .collect();
+        assert_eq!(suggestions, vec!["data/raw.dta", "code/clean.do"]);
+    }
+}
@@ -4,7 +4,13 @@
 //! - `do "file.do"` statements
 //! - `run "file.do"` statements
 //! - `include "file.do"` statements
+//!
+//! and the data files scripts read (`use`, `merge ... using`, `import`).
 
+pub mod cache;
+pub mod data;
+pub mod graph;
+pub mod lint;
 pub mod parser;
 pub mod tree;
 
@@ -1,14 +1,17 @@
 //! Parse Stata scripts for dependency statements
 //!
-//! Extracts `do`, `run`, and `include` statements from .do files.
+//! Extracts `do`, `run`, and `include` statements from .do files, along with
+//! `require` and the statements that change where Stata looks for commands.
 
 use crate::error::{Error, Result};
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 use std::sync::LazyLock;
 
 /// Type of dependency statement
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum DependencyType {
     /// `do "file.do"` - execute script
     Do,
@@ -18,6 +21,9 @@ pub enum DependencyType {
     Include,
     /// `require reghdfe >= 6.0` - package requirement
     Require,
+    /// `adopath ++ "dir"`, `sysdir set PLUS "dir"` - changes the search path
+    /// stacy sets up for the run
+    Adopath,
 }
 
 impl std::fmt::Display for DependencyType {
@@ -27,12 +33,13 @@ impl std::fmt::Display for DependencyType {
             DependencyType::Run => write!(f, "run"),
             DependencyType::Include => write!(f, "include"),
             DependencyType::Require => write!(f, "require"),
+            DependencyType::Adopath => write!(f, "adopath"),
         }
     }
 }
 
 /// A dependency found in a Stata script
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Dependency {
     /// Path to the dependency (as written in the script)
     pub path: PathBuf,
@@ -75,6 +82,15 @@ static INCLUDE_PATTERN: LazyLock<Regex> = LazyLock::new(|| {
 static REQUIRE_PATTERN: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r#"(?i)^\s*(?:cap(?:ture)?\s+)?require\s+(\w+)"#).unwrap());
 
+/// Matches `adopath +`, `adopath ++`, `adopath -` and `sysdir set`, behind any
+/// `capture`/`quietly`/`noisily` prefix. Captures the rest of the statement.
+static ADOPATH_PATTERN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"(?i)^\s*(?:(?:cap(?:ture)?|qui(?:etly)?|noi(?:sily)?)\s+)*(?:adopath\s*(?:\+\+|\+|-)|sysdir\s+set\s+\w+)\s*(.*)$"#,
+    )
+    .unwrap()
+});
+
 /// Matches an unexpanded Stata macro: `$name`, `${name}` (global) or `` `name' `` (local).
 static MACRO_PATTERN: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\$\{?\w|`\w").unwrap());
 
@@ -87,6 +103,16 @@ pub fn is_dynamic_path(path: &Path) -> bool {
     MACRO_PATTERN.is_match(&path.to_string_lossy())
 }
 
+/// Whether a Stata command changes the adopath (`adopath ++ dir`,
+/// `sysdir set PLUS dir`).
+///
+/// stacy puts exactly the locked packages on the adopath. A script that adds
+/// a directory of its own, or moves PLUS or PERSONAL, runs commands stacy
+/// didn't lock and that another machine may not have.
+pub fn is_adopath_change(command: &str) -> bool {
+    ADOPATH_PATTERN.is_match(command)
+}
+
 /// Parse a Stata script file for dependencies
 ///
 /// # Arguments
@@ -107,7 +133,7 @@ pub fn is_dynamic_path(path: &Path) -> bool {
 /// # Ok::<(), Box<dyn std::error::Error>>(())
 /// ```
 pub fn parse_dependencies(script: &Path) -> Result<Vec<Dependency>> {
-    let content = std::fs::read_to_string(script).map_err(|e| {
+    let (content, _) = crate::utils::encoding::read_text(script).map_err(|e| {
         Error::Io(std::io::Error::new(
             e.kind(),
             format!("Failed to read {}: {}", script.display(), e),
@@ -176,6 +202,14 @@ pub fn parse_dependencies_from_content(content: &str) -> Result<Vec<Dependency>>
                     });
                 }
             }
+        } else if let Some(caps) = ADOPATH_PATTERN.captures(line_without_comment) {
+            let dir = caps.get(1).map_or("", |m| m.as_str()).trim();
+            dependencies.push(Dependency {
+                path: PathBuf::from(unquote(dir)),
+                dep_type: DependencyType::Adopath,
+                line_number,
+                raw_statement: line.trim().to_string(),
+            });
         }
     }
 
@@ -205,6 +239,14 @@ fn try_parse_pattern(line: &str, pattern: &Regex, dep_type: DependencyType) -> O
     })
 }
 
+/// Strip Stata's `"..."` or `` `"..."' `` quotes
+fn unquote(s: &str) -> &str {
+    s.strip_prefix("`\"")
+        .and_then(|s| s.strip_suffix("\"'"))
+        .or_else(|| s.strip_prefix('"').and_then(|s| s.strip_suffix('"')))
+        .unwrap_or(s)
+}
+
 /// Normalize a path string from Stata syntax
 fn normalize_path(path_str: &str) -> PathBuf {
     let path_str = path_str.trim();
@@ -388,6 +430,34 @@ regress y x
         assert!(deps.is_empty());
     }
 
+    #[test]
+    fn test_parse_adopath_changes() {
+        let content = r#"
+adopath ++ "ado/personal"
+cap adopath + `"C:/my ado"'
+quietly sysdir set PLUS "~/ado/plus"
+adopath
+sysdir
+"#;
+        let deps = parse_dependencies_from_content(content).unwrap();
+        assert_eq!(deps.len(), 3);
+        assert!(deps.iter().all(|d| d.dep_type == DependencyType::Adopath));
+        assert_eq!(deps[0].path, PathBuf::from("ado/personal"));
+        assert_eq!(deps[1].path, PathBuf::from("C:/my ado"));
+        assert_eq!(deps[1].line_number, 3);
+        assert_eq!(deps[2].path, PathBuf::from("~/ado/plus"));
+    }
+
+    #[test]
+    fn test_is_adopath_change() {
+        assert!(is_adopath_change("adopath ++ mydir"));
+        assert!(is_adopath_change("ADOPATH - PERSONAL"));
+        assert!(is_adopath_change("capture sysdir set PERSONAL ."));
+        // Listing the paths changes nothing
+        assert!(!is_adopath_change("adopath"));
+        assert!(!is_adopath_change("sysdir list"));
+    }
+
     #[test]
     fn test_is_dynamic_path_global_macro() {
         assert!(is_dynamic_path(Path::new("$root/prep.do")));
@@ -3,8 +3,11 @@
 //! Recursively analyzes Stata scripts to build a complete dependency tree,
 //! detecting circular dependencies and missing files.
 
-use super::parser::{is_dynamic_path, parse_dependencies, DependencyType};
+use super::cache::DepsCache;
+use super::parser::{is_dynamic_path, DependencyType};
 use crate::error::Result;
+use crate::utils::encoding::Encoding;
+use crate::utils::paths;
 use std::collections::HashSet;
 use std::path::{Path, PathBuf};
 
@@ -223,8 +226,16 @@ pub struct FlatDependency {
 /// # Ok::<(), Box<dyn std::error::Error>>(())
 /// ```
 pub fn build_tree(script: &Path) -> Result<DependencyTree> {
+    // Scripts that haven't changed since the last command aren't parsed again
+    let mut cache = DepsCache::for_script(script);
+    let tree = build_tree_with_cache(script, &mut cache)?;
+    cache.save();
+    Ok(tree)
+}
+
+fn build_tree_with_cache(script: &Path, cache: &mut DepsCache) -> Result<DependencyTree> {
     let mut visited = HashSet::new();
-    build_tree_recursive(script, None, None, &mut visited)
+    build_tree_recursive(script, None, None, &mut visited, cache)
 }
 
 fn build_tree_recursive(
@@ -232,12 +243,11 @@ fn build_tree_recursive(
     dep_type: Option<DependencyType>,
     line_number: Option<usize>,
     visited: &mut HashSet<PathBuf>,
+    cache: &mut DepsCache,
 ) -> Result<DependencyTree> {
     // Canonicalize path for consistent comparison
     let canonical = if script.exists() {
-        script
-            .canonicalize()
-            .unwrap_or_else(|_| script.to_path_buf())
+        paths::canonicalize_or_self(script)
     } else {
         script.to_path_buf()
     };
@@ -272,7 +282,7 @@ fn build_tree_recursive(
     visited.insert(canonical.clone());
 
     // Parse dependencies
-    let dependencies = parse_dependencies(script)?;
+    let dependencies = cache.parse(script)?;
 
     // Get base directory for resolving relative paths
     let base_dir = script.parent().unwrap_or(Path::new("."));
@@ -294,6 +304,11 @@ fn build_tree_recursive(
             continue;
         }
 
+        // Adopath changes name directories, not scripts to follow
+        if dep.dep_type == DependencyType::Adopath {
+            continue;
+        }
+
         // A path built from a macro only exists once Stata expands it. Record
         // it as written and stop: there is nothing to look up or recurse into.
         if is_dynamic_path(&dep.path) {
@@ -315,6 +330,7 @@ fn build_tree_recursive(
             Some(dep.dep_type),
             Some(dep.line_number),
             visited,
+            cache,
         )?;
         children.push(child);
     }
@@ -344,10 +360,30 @@ pub struct DependencyAnalysis {
     pub missing_paths: Vec<PathBuf>,
     /// Paths that hold a Stata macro and only resolve when the script runs
     pub dynamic_paths: Vec<PathBuf>,
+    /// Statements in the tree's scripts that change the adopath
+    pub adopath_changes: Vec<AdopathChange>,
+    /// Scripts that aren't UTF-8 and were read as Latin-1
+    pub latin1_scripts: Vec<PathBuf>,
+}
+
+/// An `adopath` or `sysdir set` statement, which makes Stata find commands
+/// stacy didn't put on the adopath
+#[derive(Debug, Clone)]
+pub struct AdopathChange {
+    /// Script holding the statement
+    pub script: PathBuf,
+    /// Line of the statement (1-indexed)
+    pub line_number: usize,
+    /// The statement as written
+    pub statement: String,
 }
 
 pub fn analyze_dependencies(script: &Path) -> Result<DependencyAnalysis> {
-    let tree = build_tree(script)?;
+    let mut cache = DepsCache::for_script(script);
+    let tree = build_tree_with_cache(script, &mut cache)?;
+    let mut findings = ScriptFindings::default();
+    collect_findings(&tree, &mut cache, &mut HashSet::new(), &mut findings)?;
+    cache.save();
 
     let circular_paths: Vec<PathBuf> = tree
         .circular_paths()
@@ -372,10 +408,50 @@ pub fn analyze_dependencies(script: &Path) -> Result<DependencyAnalysis> {
         circular_paths,
         missing_paths,
         dynamic_paths,
+        adopath_changes: findings.adopath_changes,
+        latin1_scripts: findings.latin1_scripts,
         tree,
     })
 }
 
+/// What the scripts of a tree hold beyond their dependencies
+#[derive(Default)]
+struct ScriptFindings {
+    adopath_changes: Vec<AdopathChange>,
+    latin1_scripts: Vec<PathBuf>,
+}
+
+/// The adopath changes and encodings of every script of `tree`, each script
+/// once
+fn collect_findings(
+    tree: &DependencyTree,
+    cache: &mut DepsCache,
+    seen: &mut HashSet<PathBuf>,
+    findings: &mut ScriptFindings,
+) -> Result<()> {
+    if !tree.exists || tree.is_circular || tree.is_dynamic || !seen.insert(tree.path.clone()) {
+        return Ok(());
+    }
+    for dep in cache.parse(&tree.path)? {
+        if dep.dep_type == DependencyType::Adopath {
+            findings.adopath_changes.push(AdopathChange {
+                script: tree.path.clone(),
+                line_number: dep.line_number,
+                statement: dep.raw_statement,
+            });
+        }
+    }
+    if cache.encoding(&tree.path) == Some(Encoding::Latin1) {
+        findings.latin1_scripts.push(tree.path.clone());
+    }
+    for child in &tree.children {
+        if child.dep_type != Some(DependencyType::Require) {
+            collect_findings(child, cache, seen, findings)?;
+        }
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -519,6 +595,45 @@ mod tests {
         assert_eq!(flat[1].depth, 1);
     }
 
+    #[test]
+    fn test_analysis_reports_adopath_changes() {
+        let temp = TempDir::new().unwrap();
+        let main = create_test_file(temp.path(), "main.do", "do a.do\ndo b.do\nrequire estout\n");
+        create_test_file(temp.path(), "a.do", "do b.do\n");
+        create_test_file(temp.path(), "b.do", "adopath ++ \"~/my_ado\"\n");
+
+        let analysis = analyze_dependencies(&main).unwrap();
+        // b.do is reached twice but reported once; the directory isn't a node
+        assert_eq!(analysis.adopath_changes.len(), 1);
+        assert_eq!(analysis.adopath_changes[0].line_number, 1);
+        assert_eq!(
+            analysis.adopath_changes[0].statement,
+            "adopath ++ \"~/my_ado\""
+        );
+        assert!(analysis.adopath_changes[0].script.ends_with("b.do"));
+        assert_eq!(analysis.unique_count, 3);
+    }
+
+    #[test]
+    fn test_analysis_reports_latin1_scripts() {
+        let temp = TempDir::new().unwrap();
+        let main = create_test_file(
+            temp.path(),
+            "main.do",
+            "do legacy.do
+",
+        );
+        fs::write(
+            temp.path().join("legacy.do"),
+            b"label var city \"Z\xFCrich\"\n",
+        )
+        .unwrap();
+
+        let analysis = analyze_dependencies(&main).unwrap();
+        assert_eq!(analysis.latin1_scripts.len(), 1);
+        assert!(analysis.latin1_scripts[0].ends_with("legacy.do"));
+    }
+
     #[test]
     fn test_subdirectory_deps() {
         let temp = TempDir::new().unwrap();
@@ -0,0 +1,117 @@
+//! Internal diagnostics: what stacy itself is doing
+//!
+//! The executor, package and cache code report their steps through
+//! `tracing` (which binary was detected, which URL is downloaded, why a cache
+//! entry missed). Nothing is shown by default. `-v` and `-vv` belong to the
+//! commands (streamed logs, failed downloads); `-vvv` also shows debug-level
+//! events on stderr and `-vvvv` trace. Below that, `STACY_LOG` takes an
+//! `EnvFilter` directive such as `debug` or `stacy::packages=trace`.
+//! `--log-file <path>` also writes the events as JSON lines, at debug level
+//! unless more was asked for, to attach to a bug report.
+//!
+//! Not to be confused with the Stata logs of the scripts stacy runs.
+
+use crate::error::{Error, Result};
+use std::fs::File;
+use std::path::Path;
+use std::sync::Mutex;
+use tracing_subscriber::filter::{EnvFilter, LevelFilter};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::Layer;
+
+/// Environment variable holding the stderr filter
+pub const LOG_ENV: &str = "STACY_LOG";
+
+/// Level for `-v` repeated `verbosity` times
+pub fn verbosity_level(verbosity: u8) -> LevelFilter {
+    match verbosity {
+        0..=2 => LevelFilter::OFF,
+        3 => LevelFilter::DEBUG,
+        _ => LevelFilter::TRACE,
+    }
+}
+
+/// Filter for stderr: `-vvv` wins over `STACY_LOG`; neither shows nothing
+fn stderr_filter(verbosity: u8, env: Option<&str>) -> Result<EnvFilter> {
+    match (verbosity_level(verbosity), env) {
+        (LevelFilter::OFF, Some(directives)) if !directives.trim().is_empty() => {
+            EnvFilter::builder()
+                .parse(directives)
+                .map_err(|e| Error::Config(format!("Invalid {}: {}", LOG_ENV, e)))
+        }
+        _ => Ok(EnvFilter::default().add_directive(verbosity_level(verbosity).into())),
+    }
+}
+
+/// Install the subscriber for this process. Called once, after parsing the
+/// command line.
+pub fn init(verbosity: u8, log_file: Option<&Path>) -> Result<()> {
+    let env = std::env::var(LOG_ENV).ok();
+    let stderr = tracing_subscriber::fmt::layer()
+        .with_writer(std::io::stderr)
+        .with_target(true)
+        .without_time()
+        .with_filter(stderr_filter(verbosity, env.as_deref())?);
+
+    let file = match log_file {
+        Some(path) => {
+            let file = File::create(path).map_err(|e| {
+                Error::Config(format!(
+                    "Failed to create log file {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+            let level = verbosity_level(verbosity).max(LevelFilter::DEBUG);
+            Some(
+                tracing_subscriber::fmt::layer()
+                    .json()
+                    .with_writer(Mutex::new(file))
+                    .with_current_span(false)
+                    .with_filter(level),
+            )
+        }
+        None => None,
+    };
+
+    // Another subscriber may already be set (in tests); keep it
+    let _ = tracing_subscriber::registry()
+        .with(stderr)
+        .with(file)
+        .try_init();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verbosity_level() {
+        assert_eq!(verbosity_level(0), LevelFilter::OFF);
+        // -v and -vv are the commands' own
+        assert_eq!(verbosity_level(2), LevelFilter::OFF);
+        assert_eq!(verbosity_level(3), LevelFilter::DEBUG);
+        assert_eq!(verbosity_level(4), LevelFilter::TRACE);
+        assert_eq!(verbosity_level(9), LevelFilter::TRACE);
+    }
+
+    #[test]
+    fn test_stderr_filter() {
+        // -vvv wins over STACY_LOG
+        let filter = stderr_filter(3, Some("trace")).unwrap();
+        assert_eq!(filter.max_level_hint(), Some(LevelFilter::DEBUG));
+
+        let filter = stderr_filter(1, Some("info")).unwrap();
+        assert_eq!(filter.max_level_hint(), Some(LevelFilter::INFO));
+
+        let filter = stderr_filter(0, Some("stacy::packages=trace")).unwrap();
+        assert_eq!(filter.max_level_hint(), Some(LevelFilter::TRACE));
+
+        let filter = stderr_filter(0, None).unwrap();
+        assert_eq!(filter.max_level_hint(), Some(LevelFilter::OFF));
+
+        assert!(stderr_filter(0, Some("stacy=loud")).is_err());
+    }
+}
@@ -0,0 +1,286 @@
+//! DVC integration
+//!
+//! Many groups already version their data with [DVC](https://dvc.org). stacy
+//! doesn't replace that: it calls the `dvc` executable to pull the data a run
+//! or build reads before Stata starts, and to record and push what a build
+//! wrote. Only files DVC tracks with a `.dvc` file (`dvc add`), directly or
+//! through a parent directory, are passed on; everything else is left alone.
+//!
+//! [`dvc_yaml`] goes the other way and renders the `[pipeline]` section as a
+//! `dvc.yaml` for teams that drive their builds with `dvc repro`.
+
+use crate::error::{Error, Result};
+use crate::pipeline::Pipeline;
+use crate::utils::paths::normalize;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// The DVC executable
+const DVC: &str = "dvc";
+
+/// Is `path` (relative to `root`) tracked by a `.dvc` file, its own or one
+/// of a parent directory?
+pub fn is_tracked(root: &Path, path: &Path) -> bool {
+    tracking_output(root, path).is_some()
+}
+
+/// The DVC output that `path` (relative to `root`) belongs to: `path` itself
+/// when it has a `.dvc` file, otherwise the nearest parent directory that has
+/// one. DVC only accepts the output as a whole for `dvc add`.
+fn tracking_output(root: &Path, path: &Path) -> Option<PathBuf> {
+    path.ancestors()
+        .filter(|p| !p.as_os_str().is_empty())
+        .find(|p| {
+            let mut dvc_file = root.join(p).into_os_string();
+            dvc_file.push(".dvc");
+            Path::new(&dvc_file).is_file()
+        })
+        .map(Path::to_path_buf)
+}
+
+/// The DVC outputs that `paths` belong to, relative to `root`: a file inside
+/// a tracked directory maps to that directory. Paths outside `root`, paths
+/// DVC doesn't track and duplicates are dropped.
+pub fn tracked(root: &Path, paths: &[PathBuf]) -> Vec<PathBuf> {
+    let mut targets = Vec::new();
+    for path in paths {
+        let relative = if path.is_absolute() {
+            match normalize(path).strip_prefix(normalize(root)) {
+                Ok(rel) => rel.to_path_buf(),
+                Err(_) => continue,
+            }
+        } else {
+            normalize(path)
+        };
+        if relative.as_os_str().is_empty() || relative.starts_with("..") {
+            continue;
+        }
+        if let Some(output) = tracking_output(root, &relative) {
+            if !targets.contains(&output) {
+                targets.push(output);
+            }
+        }
+    }
+    targets
+}
+
+/// `dvc pull` the given targets in `root`
+pub fn pull(root: &Path, targets: &[PathBuf]) -> Result<()> {
+    run_dvc(root, "pull", targets)
+}
+
+/// Record the current content of the targets (`dvc add`) and push it to the
+/// DVC remote (`dvc push`)
+pub fn push(root: &Path, targets: &[PathBuf]) -> Result<()> {
+    run_dvc(root, "add", targets)?;
+    run_dvc(root, "push", targets)
+}
+
+fn run_dvc(root: &Path, subcommand: &str, targets: &[PathBuf]) -> Result<()> {
+    if targets.is_empty() {
+        return Ok(());
+    }
+    let output = Command::new(DVC)
+        .arg(subcommand)
+        .args(targets)
+        .current_dir(root)
+        .output()
+        .map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                Error::Config(
+                    "dvc not found on PATH (install it with `pip install dvc`)".to_string(),
+                )
+            } else {
+                Error::Execution(format!("Failed to run dvc {}: {}", subcommand, e))
+            }
+        })?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(Error::Execution(format!(
+            "dvc {} failed:\n{}",
+            subcommand,
+            stderr.trim_end()
+        )));
+    }
+    Ok(())
+}
+
+/// Render the pipeline as a `dvc.yaml`: one DVC stage per stage, running its
+/// script through `stacy run`. A stage's `needs` become dependencies on the
+/// outputs of the stages it needs, the only way DVC orders stages.
+pub fn dvc_yaml(pipeline: &Pipeline) -> String {
+    let mut yaml =
+        String::from("# Generated by `stacy build --export-dvc` from stacy.toml\nstages:\n");
+    let names = pipeline.select(&[]).unwrap_or_default();
+    for name in names {
+        let Some(stage) = pipeline.stage(name) else {
+            continue;
+        };
+        let script = stage.script.display().to_string();
+        yaml.push_str(&format!("  {}:\n", yaml_scalar(name)));
+        yaml.push_str(&format!(
+            "    cmd: {}\n",
+            yaml_scalar(&format!("stacy run {}", shell_word(&script)))
+        ));
+        if let Some(ref description) = stage.description {
+            yaml.push_str(&format!("    desc: {}\n", yaml_scalar(description)));
+        }
+
+        let mut deps = vec![script];
+        deps.extend(stage.inputs.iter().map(|p| p.display().to_string()));
+        for need in &stage.needs {
+            if let Some(needed) = pipeline.stage(need) {
+                deps.extend(needed.outputs.iter().map(|p| p.display().to_string()));
+            }
+        }
+        deps.dedup();
+        yaml.push_str("    deps:\n");
+        for dep in &deps {
+            yaml.push_str(&format!("      - {}\n", yaml_scalar(dep)));
+        }
+
+        if !stage.outputs.is_empty() {
+            yaml.push_str("    outs:\n");
+            for out in &stage.outputs {
+                yaml.push_str(&format!(
+                    "      - {}\n",
+                    yaml_scalar(&out.display().to_string())
+                ));
+            }
+        }
+    }
+    yaml
+}
+
+/// A YAML scalar: plain when that's unambiguous, otherwise double-quoted
+/// (a JSON string is a valid YAML double-quoted scalar)
+fn yaml_scalar(value: &str) -> String {
+    let plain = !value.is_empty()
+        && !value.starts_with(['-', '.', ' '])
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '/' | ' '))
+        && !value.ends_with(' ');
+    if plain {
+        value.to_string()
+    } else {
+        serde_json::to_string(value).unwrap_or_default()
+    }
+}
+
+/// Quote a path for the shell DVC runs `cmd` in
+fn shell_word(word: &str) -> String {
+    if word
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '/'))
+    {
+        word.to_string()
+    } else {
+        format!("\"{}\"", word.replace('"', "\\\""))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project::config::StageDef;
+    use std::collections::BTreeMap;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_tracked_by_own_or_parent_dvc_file() {
+        let temp = TempDir::new().unwrap();
+        fs::create_dir_all(temp.path().join("data/raw")).unwrap();
+        fs::write(temp.path().join("data/raw.dvc"), "outs:\n").unwrap();
+        fs::write(temp.path().join("codebook.csv.dvc"), "outs:\n").unwrap();
+
+        assert!(is_tracked(temp.path(), Path::new("data/raw/survey.csv")));
+        assert!(is_tracked(temp.path(), Path::new("codebook.csv")));
+        assert!(!is_tracked(temp.path(), Path::new("data/clean.dta")));
+
+        let targets = tracked(
+            temp.path(),
+            &[
+                temp.path().join("data/raw/survey.csv"),
+                PathBuf::from("data/raw/survey.csv"),
+                PathBuf::from("data/clean.dta"),
+                temp.path().join("src/../data/raw/other.csv"),
+                PathBuf::from("/elsewhere/codebook.csv"),
+            ],
+        );
+        assert_eq!(targets, vec![PathBuf::from("data/raw")]);
+    }
+
+    #[test]
+    fn test_tracked_maps_files_to_their_tracked_directory() {
+        let temp = TempDir::new().unwrap();
+        fs::create_dir_all(temp.path().join("data/clean")).unwrap();
+        fs::write(temp.path().join("data/clean.dvc"), "outs:\n").unwrap();
+        fs::write(temp.path().join("results.dta.dvc"), "outs:\n").unwrap();
+
+        // `dvc add data/clean/x.dta` overlaps the output of data/clean.dvc;
+        // the directory is added as a whole, once
+        let targets = tracked(
+            temp.path(),
+            &[
+                PathBuf::from("data/clean/x.dta"),
+                temp.path().join("data/clean/y.dta"),
+                PathBuf::from("data/clean"),
+                PathBuf::from("results.dta"),
+                PathBuf::from("data/scratch.dta"),
+            ],
+        );
+        assert_eq!(
+            targets,
+            vec![PathBuf::from("data/clean"), PathBuf::from("results.dta")]
+        );
+    }
+
+    #[test]
+    fn test_dvc_yaml() {
+        let mut stages = BTreeMap::new();
+        stages.insert(
+            "clean".to_string(),
+            StageDef {
+                script: PathBuf::from("src/01_clean.do"),
+                inputs: vec![PathBuf::from("data/raw.csv")],
+                outputs: vec![PathBuf::from("data/clean.dta")],
+                needs: vec![],
+                description: Some("Clean: raw survey".to_string()),
+            },
+        );
+        stages.insert(
+            "report".to_string(),
+            StageDef {
+                script: PathBuf::from("src/my report.do"),
+                inputs: vec![],
+                outputs: vec![],
+                needs: vec!["clean".to_string()],
+                description: None,
+            },
+        );
+        let pipeline = Pipeline::from_config(&stages).unwrap();
+
+        let yaml = dvc_yaml(&pipeline);
+        assert_eq!(
+            yaml,
+            "# Generated by `stacy build --export-dvc` from stacy.toml
+stages:
+  clean:
+    cmd: stacy run src/01_clean.do
+    desc: \"Clean: raw survey\"
+    deps:
+      - src/01_clean.do
+      - data/raw.csv
+    outs:
+      - data/clean.dta
+  report:
+    cmd: \"stacy run \\\"src/my report.do\\\"\"
+    deps:
+      - src/my report.do
+      - data/clean.dta
+"
+        );
+    }
+}
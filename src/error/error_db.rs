@@ -23,6 +23,9 @@ pub struct ErrorCodeEntry {
 pub struct ErrorDatabase {
     pub stata_version: Option<String>,
     pub sysdir: Option<String>,
+    /// Processor count the Stata license allows (`c(processors_lic)`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub processors_lic: Option<u32>,
     pub extracted_at: String,
     pub stacy_version: String,
     pub errors: Vec<ErrorCodeEntry>,
@@ -38,6 +41,7 @@ impl ErrorDatabase {
         Self {
             stata_version: None,
             sysdir: None,
+            processors_lic: None,
             extracted_at: String::new(),
             stacy_version: env!("CARGO_PKG_VERSION").to_string(),
             errors: Vec::new(),
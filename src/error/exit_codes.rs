@@ -0,0 +1,202 @@
+//! Process exit codes and their remapping
+//!
+//! Each kind of failure has a standard exit code (see [`super::mapper`]).
+//! Orchestrators that reserve some of those codes can give a kind another one
+//! under `[exit_codes]`, in the user config or in stacy.toml; the project's
+//! entries win. Only the process's exit status changes: `exit_code` fields in
+//! stacy's output keep the standard codes, so parsers see one contract.
+//! `stacy explain --exit-codes` prints the mapping in effect.
+
+use super::{Error, Result};
+use crate::project::config::load_config;
+use crate::project::root::find_project_root_from_cwd;
+use crate::project::user_config::{get_config_path, read_user_config};
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+/// A kind of failure and its standard exit code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExitKind {
+    /// Key under `[exit_codes]`, and the error category in telemetry
+    pub name: &'static str,
+    pub code: i32,
+    pub description: &'static str,
+}
+
+/// The kinds of failure, in order of their standard codes
+pub const EXIT_KINDS: &[ExitKind] = &[
+    ExitKind {
+        name: "stata_error",
+        code: 1,
+        description: "Stata error (r() code detected)",
+    },
+    ExitKind {
+        name: "syntax_error",
+        code: 2,
+        description: "Syntax error",
+    },
+    ExitKind {
+        name: "file_error",
+        code: 3,
+        description: "File error (not found, permission denied)",
+    },
+    ExitKind {
+        name: "memory_error",
+        code: 4,
+        description: "Memory error",
+    },
+    ExitKind {
+        name: "internal_error",
+        code: 5,
+        description: "Internal stacy error",
+    },
+    ExitKind {
+        name: "statistical_error",
+        code: 6,
+        description: "Statistical error (convergence, model problems)",
+    },
+    ExitKind {
+        name: "environment_error",
+        code: 10,
+        description: "Environment error (Stata not found)",
+    },
+];
+
+/// The kind of failure a standard exit code stands for. Other codes, such as
+/// a killed process's 130 or 143, have none.
+pub fn kind_for_code(code: i32) -> Option<&'static ExitKind> {
+    EXIT_KINDS.iter().find(|kind| kind.code == code)
+}
+
+/// Check an `[exit_codes]` table from `source`: known kinds only, and codes a
+/// shell can report as a failure
+pub fn validate(codes: &BTreeMap<String, i32>, source: &str) -> Result<()> {
+    for (name, &code) in codes {
+        if !EXIT_KINDS.iter().any(|kind| kind.name == name) {
+            let names: Vec<&str> = EXIT_KINDS.iter().map(|kind| kind.name).collect();
+            return Err(Error::Config(format!(
+                "[exit_codes] in {}: unknown kind '{}'. Valid kinds: {}",
+                source,
+                name,
+                names.join(", ")
+            )));
+        }
+        if !(1..=255).contains(&code) {
+            return Err(Error::Config(format!(
+                "[exit_codes] in {}: {} must be between 1 and 255, got {}",
+                source, name, code
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Exit codes in effect: the standard ones, with the configured overrides
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExitCodeMap {
+    overrides: BTreeMap<String, i32>,
+}
+
+impl ExitCodeMap {
+    /// The user's overrides, then the project's on top
+    pub fn new(user: &BTreeMap<String, i32>, project: &BTreeMap<String, i32>) -> Self {
+        let mut overrides = user.clone();
+        overrides.extend(project.iter().map(|(name, &code)| (name.clone(), code)));
+        Self { overrides }
+    }
+
+    /// The mapping configured for the current directory
+    pub fn load() -> Result<Self> {
+        let user = match get_config_path() {
+            Some(path) => {
+                let codes = read_user_config(&path)?
+                    .map(|config| config.exit_codes)
+                    .unwrap_or_default();
+                validate(&codes, &path.display().to_string())?;
+                codes
+            }
+            None => BTreeMap::new(),
+        };
+        let project = match find_project_root_from_cwd()? {
+            Some(root) => load_config(&root.path)?
+                .map(|config| config.exit_codes)
+                .unwrap_or_default(),
+            None => BTreeMap::new(),
+        };
+        Ok(Self::new(&user, &project))
+    }
+
+    /// Exit code for `kind`
+    pub fn code(&self, kind: &ExitKind) -> i32 {
+        self.overrides.get(kind.name).copied().unwrap_or(kind.code)
+    }
+
+    /// Whether `kind` has been given another code
+    pub fn is_remapped(&self, kind: &ExitKind) -> bool {
+        self.code(kind) != kind.code
+    }
+
+    /// The process exit status for standard exit code `code`
+    pub fn remap(&self, code: i32) -> i32 {
+        kind_for_code(code).map_or(code, |kind| self.code(kind))
+    }
+}
+
+/// The mapping for this process, loaded on first use. A config that fails to
+/// load leaves the standard codes: the command reports that error itself.
+pub fn active() -> &'static ExitCodeMap {
+    static ACTIVE: OnceLock<ExitCodeMap> = OnceLock::new();
+    ACTIVE.get_or_init(|| ExitCodeMap::load().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codes(entries: &[(&str, i32)]) -> BTreeMap<String, i32> {
+        entries
+            .iter()
+            .map(|&(name, code)| (name.to_string(), code))
+            .collect()
+    }
+
+    #[test]
+    fn test_standard_codes() {
+        let map = ExitCodeMap::default();
+        assert_eq!(map.remap(0), 0);
+        assert_eq!(map.remap(2), 2);
+        assert_eq!(map.remap(10), 10);
+        assert_eq!(kind_for_code(6).unwrap().name, "statistical_error");
+        assert!(kind_for_code(143).is_none());
+    }
+
+    #[test]
+    fn test_project_overrides_user() {
+        let map = ExitCodeMap::new(
+            &codes(&[("syntax_error", 64), ("file_error", 66)]),
+            &codes(&[("syntax_error", 65)]),
+        );
+        assert_eq!(map.remap(2), 65);
+        assert_eq!(map.remap(3), 66);
+        assert_eq!(map.remap(1), 1);
+        // Success and signals pass through
+        assert_eq!(map.remap(0), 0);
+        assert_eq!(map.remap(130), 130);
+        assert!(map.is_remapped(kind_for_code(2).unwrap()));
+        assert!(!map.is_remapped(kind_for_code(1).unwrap()));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(validate(&codes(&[("memory_error", 70)]), "stacy.toml").is_ok());
+
+        let err = validate(&codes(&[("timeout", 70)]), "stacy.toml")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("unknown kind 'timeout'"), "{}", err);
+
+        for code in [0, 256, -1] {
+            assert!(validate(&codes(&[("stata_error", code)]), "stacy.toml").is_err());
+        }
+    }
+}
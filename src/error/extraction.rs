@@ -21,6 +21,7 @@ pub fn extract_error_codes(stata_binary: &str) -> Result<ErrorDatabase> {
     let do_file_content = r#"display "STACY_EXTRACTION_START"
 display "STATA_VERSION:" c(stata_version)
 display "SYSDIR:" c(sysdir_stata)
+display "PROCESSORS_LIC:" c(processors_lic)
 forvalues i = 1/9999 {
     capture noisily error `i'
     if _rc != 0 {
@@ -33,8 +34,8 @@ display "STACY_EXTRACTION_END"
 
     // Run Stata in batch mode
     let mut cmd = std::process::Command::new(stata_binary);
-    cmd.args(["-b", "-q", "do"]);
-    cmd.arg(&script_path);
+    cmd.args(crate::executor::binary::BatchStyle::for_binary(stata_binary).args());
+    cmd.arg(crate::executor::run_paths::stata_path(&script_path));
     cmd.current_dir(tmp_dir.path());
     cmd.stdout(std::process::Stdio::null());
     cmd.stderr(std::process::Stdio::null());
@@ -87,6 +88,7 @@ display "STACY_EXTRACTION_END"
 /// STACY_EXTRACTION_START
 /// STATA_VERSION:19.5
 /// SYSDIR:/usr/local/stata19
+/// PROCESSORS_LIC:8
 /// ...error message text...
 /// r(199);
 /// STACY_CODE:199
@@ -118,6 +120,7 @@ pub fn parse_extraction_log(log_content: &str) -> Result<ErrorDatabase> {
     // Extract metadata
     let mut stata_version = None;
     let mut sysdir = None;
+    let mut processors_lic = None;
     let mut entries = Vec::new();
 
     // Collect message lines between STACY_CODE markers
@@ -132,6 +135,10 @@ pub fn parse_extraction_log(log_content: &str) -> Result<ErrorDatabase> {
             sysdir = Some(dir.trim().to_string());
             continue;
         }
+        if let Some(lic) = line.strip_prefix("PROCESSORS_LIC:") {
+            processors_lic = lic.trim().parse().ok();
+            continue;
+        }
 
         if let Some(code_str) = line.strip_prefix("STACY_CODE:") {
             if let Ok(code) = code_str.trim().parse::<u32>() {
@@ -170,6 +177,7 @@ pub fn parse_extraction_log(log_content: &str) -> Result<ErrorDatabase> {
     let mut db = ErrorDatabase::empty();
     db.stata_version = stata_version;
     db.sysdir = sysdir;
+    db.processors_lic = processors_lic;
     db.extracted_at = now;
     db.errors = entries;
     db.build_index();
@@ -239,6 +247,9 @@ STATA_VERSION:19.5
 . display "SYSDIR:" c(sysdir_stata)
 SYSDIR:/usr/local/stata19
 
+. display "PROCESSORS_LIC:" c(processors_lic)
+PROCESSORS_LIC:8
+
 . forvalues i = 1/9999 {
 You pressed Break.  This is not considered an error.
 r(1);
@@ -273,6 +284,7 @@ STACY_EXTRACTION_END
 
         assert_eq!(db.stata_version, Some("19.5".to_string()));
         assert_eq!(db.sysdir, Some("/usr/local/stata19".to_string()));
+        assert_eq!(db.processors_lic, Some(8));
         assert_eq!(db.errors.len(), 5);
 
         // Check specific entries
@@ -0,0 +1,444 @@
+//! Where in the do-files an error happened
+//!
+//! Stata reports an error against its log, not the do-file. The location is
+//! found by matching the commands Stata echoed to the log against the
+//! do-files that ran them: `do` and `include` echoes open the nested file,
+//! `end of do-file` closes it, so an error inside a helper three `do`s deep
+//! is reported as `helpers/reshape.do:14` rather than against the top-level
+//! script. A nested file stacy can't read (a path built from a macro, say)
+//! ends the chain, which leaves the error on the line that ran it.
+//!
+//! The do-files still open when the error happened make up its stack trace,
+//! `main.do:3 → clean/01_merge.do:12 → helpers/labels.do:42`: the line of
+//! each file is that of the `do` that opened the next one.
+
+use crate::utils::encoding::decode_text;
+use crate::utils::paths::canonicalize_or_self;
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+/// The `r(N);` line Stata writes right after an error
+static R_CODE_LINE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\s*r\(\d+\);\s*$").unwrap());
+
+/// A command Stata echoed to the log: `. cmd`, or `2. cmd` inside a loop or
+/// program body
+static ECHO: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\s*(?:\d+)?\.\s+(.*)$").unwrap());
+
+/// A command that runs another do-file with its commands echoed. `run` and
+/// `quietly do` echo nothing, so they open no file.
+static NESTED: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(?:noi(?:sily)?\s+)?(?:do|include)\s+(.+)$").unwrap());
+
+/// A change of Stata's working directory, which relative `do` paths follow
+static CD: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(?:cd|chdir)\s+(.+)$").unwrap());
+
+/// A do-file and line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLocation {
+    pub file: PathBuf,
+    /// 1-indexed
+    pub line: usize,
+}
+
+impl std::fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.file.display(), self.line)
+    }
+}
+
+/// A do-file open at some point of the log, with the commands echoed from it
+struct Frame {
+    file: PathBuf,
+    /// `None` when the file couldn't be read
+    lines: Option<Vec<String>>,
+    echoes: Vec<String>,
+}
+
+impl Frame {
+    fn open(path: PathBuf) -> Self {
+        let lines = std::fs::read(&path).ok().map(|bytes| {
+            decode_text(&bytes)
+                .lines()
+                .map(|l| l.trim().to_string())
+                .collect()
+        });
+        Frame {
+            file: path,
+            lines,
+            echoes: Vec::new(),
+        }
+    }
+
+    fn contains(&self, command: &str) -> bool {
+        self.lines.iter().flatten().any(|line| line == command)
+    }
+}
+
+/// The chain of do-files and lines that led to the first error in the log of
+/// a run of `script`, outermost first: the last one is where it happened.
+/// Empty when the error can't be placed. `working_dir` is the directory Stata
+/// started in; relative paths are shown relative to it.
+pub fn trace(script: &Path, log_text: &str, working_dir: &Path) -> Vec<SourceLocation> {
+    let log: Vec<&str> = log_text.lines().collect();
+    let Some(end) = log.iter().position(|line| R_CODE_LINE.is_match(line)) else {
+        return Vec::new();
+    };
+    let script_path = canonicalize_or_self(script);
+
+    let mut cwd = working_dir.to_path_buf();
+    let mut stack = vec![Frame::open(script.to_path_buf())];
+    for line in &log[..end] {
+        if line.trim() == "end of do-file" {
+            if stack.len() > 1 {
+                stack.pop();
+            }
+            continue;
+        }
+        let Some(command) = echoed_command(line) else {
+            continue;
+        };
+        // Not in the file on top but in one below: an included file ended
+        // without an `end of do-file` of its own
+        let top = stack.last().expect("stack starts with the script");
+        if top.lines.is_some() && !top.contains(command) {
+            if let Some(depth) = stack.iter().rposition(|f| f.contains(command)) {
+                stack.truncate(depth + 1);
+            }
+        }
+        stack
+            .last_mut()
+            .expect("stack starts with the script")
+            .echoes
+            .push(command.to_string());
+
+        if let Some(target) = NESTED
+            .captures(command)
+            .and_then(|c| path_argument(c.get(1)?.as_str()))
+        {
+            let mut path = cwd.join(target);
+            if path.extension().is_none() {
+                path.set_extension("do");
+            }
+            stack.push(Frame::open(path));
+        } else if let Some(target) = CD
+            .captures(command)
+            .and_then(|c| path_argument(c.get(1)?.as_str()))
+        {
+            cwd = cwd.join(target);
+        }
+    }
+
+    // The chain ends at a file that can't be read, which leaves the error on
+    // the line that ran it: the files it opened in turn can't be placed under
+    // a line of it. A file none of whose echoes match (the script as opened
+    // by stacy's wrapper) is left out.
+    let mut chain = Vec::new();
+    for frame in &stack {
+        let Some(lines) = &frame.lines else {
+            tracing::debug!(
+                file = %frame.file.display(),
+                "could not read nested do-file; the trace ends at the line that ran it"
+            );
+            break;
+        };
+        let echoes: Vec<&str> = frame.echoes.iter().map(String::as_str).collect();
+        let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+        let Some(line) = matching_line(&lines, &echoes) else {
+            continue;
+        };
+        let file = if canonicalize_or_self(&frame.file) == script_path {
+            script.to_path_buf()
+        } else {
+            let file = &frame.file;
+            file.strip_prefix(working_dir).unwrap_or(file).to_path_buf()
+        };
+        chain.push(SourceLocation { file, line });
+    }
+    chain
+}
+
+/// A stack trace as one line: `main.do:3 → helpers/labels.do:42`
+pub fn format_trace(trace: &[SourceLocation]) -> String {
+    trace
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(" → ")
+}
+
+fn echoed_command(line: &str) -> Option<&str> {
+    let command = ECHO.captures(line)?.get(1)?.as_str().trim();
+    (!command.is_empty()).then_some(command)
+}
+
+/// The file a `do`/`cd` names: quoted (`"a b.do"`, `` `"a b.do"' ``) or up to
+/// the first space or comma
+fn path_argument(rest: &str) -> Option<&str> {
+    let rest = rest.trim();
+    let path = if let Some(quoted) = rest.strip_prefix("`\"") {
+        quoted.split("\"'").next()?
+    } else if let Some(quoted) = rest.strip_prefix('"') {
+        quoted.split('"').next()?
+    } else {
+        rest.split(|c: char| c.is_whitespace() || c == ',').next()?
+    };
+    (!path.is_empty()).then_some(path)
+}
+
+/// The line (1-indexed) of the last of `echoes` that is in `lines`.
+/// Commands can repeat, so the n-th echo of a command maps to its n-th
+/// occurrence in the file.
+fn matching_line(lines: &[&str], echoes: &[&str]) -> Option<usize> {
+    for (k, command) in echoes.iter().enumerate().rev() {
+        let matches: Vec<usize> = lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| *line == command)
+            .map(|(i, _)| i)
+            .collect();
+        if let Some(&last) = matches.last() {
+            let nth = echoes[..=k].iter().filter(|c| *c == command).count();
+            return Some(matches.get(nth - 1).copied().unwrap_or(last) + 1);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn location(file: &str, line: usize) -> Option<SourceLocation> {
+        Some(SourceLocation {
+            file: PathBuf::from(file),
+            line,
+        })
+    }
+
+    fn locate(script: &Path, log: &str, working_dir: &Path) -> Option<SourceLocation> {
+        trace(script, log, working_dir).pop()
+    }
+
+    #[test]
+    fn test_locate_maps_repeated_commands() {
+        let temp = TempDir::new().unwrap();
+        let script = temp.path().join("main.do");
+        std::fs::write(
+            &script,
+            "sysuse auto, clear\nsummarize price\ndrop if foreign\nsummarize price\nregress price mpgx\n",
+        )
+        .unwrap();
+        let log = "\
+. sysuse auto, clear
+(1978 automobile data)
+
+. summarize price
+(output)
+
+. drop if foreign
+(22 observations deleted)
+
+. summarize price
+(output)
+
+. regress price mpgx
+variable mpgx not found
+r(111);
+
+end of do-file
+r(111);
+";
+        let found = locate(&script, log, temp.path()).unwrap();
+        assert_eq!((found.file, found.line), (script.clone(), 5));
+
+        let log = log.replace(". regress price mpgx\nvariable mpgx not found\n", "");
+        assert_eq!(locate(&script, &log, temp.path()).unwrap().line, 4);
+        assert_eq!(locate(&script, "no errors here\n", temp.path()), None);
+    }
+
+    #[test]
+    fn test_locate_follows_nested_do_files() {
+        let temp = TempDir::new().unwrap();
+        std::fs::create_dir(temp.path().join("helpers")).unwrap();
+        std::fs::write(
+            temp.path().join("main.do"),
+            "sysuse auto, clear\ndo helpers/prep\ninclude \"helpers/common.do\"\ndisplay 1\n",
+        )
+        .unwrap();
+        std::fs::write(
+            temp.path().join("helpers/prep.do"),
+            "generate x = 1\ndo helpers/deep.do\n",
+        )
+        .unwrap();
+        std::fs::write(temp.path().join("helpers/deep.do"), "generate y = 2\n").unwrap();
+        std::fs::write(
+            temp.path().join("helpers/common.do"),
+            "local n = 3\nbadcmd\n",
+        )
+        .unwrap();
+        let script = temp.path().join("main.do");
+
+        // The wrapper stacy runs the script through, then two levels down
+        let deep = "\
+. do `\"SCRIPT\"'
+
+. sysuse auto, clear
+
+. do helpers/prep
+
+. generate x = 1
+
+. do helpers/deep.do
+
+. generate y = 2
+variable y already defined
+r(110);
+
+end of do-file
+r(110);
+"
+        .replace("SCRIPT", &script.display().to_string());
+        let chain = trace(&script, &deep, temp.path());
+        assert_eq!(
+            format_trace(&chain),
+            format!(
+                "{}:2 → helpers/prep.do:2 → helpers/deep.do:1",
+                script.display()
+            )
+        );
+
+        // Back out of both, then into the included file
+        let included = deep.replace(
+            ". generate y = 2\nvariable y already defined\nr(110);\n\nend of do-file\nr(110);\n",
+            ". generate y = 2\n\nend of do-file\n\nend of do-file\n\n\
+             . include \"helpers/common.do\"\n\n. local n = 3\n\n. badcmd\n\
+             command badcmd is unrecognized\nr(199);\n",
+        );
+        assert_eq!(
+            locate(&script, &included, temp.path()),
+            location("helpers/common.do", 2)
+        );
+        assert_eq!(trace(&script, &included, temp.path()).len(), 2);
+    }
+
+    #[test]
+    fn test_locate_falls_back_to_calling_line() {
+        let temp = TempDir::new().unwrap();
+        let script = temp.path().join("main.do");
+        std::fs::write(&script, "sysuse auto, clear\ndo helper.do\ndisplay 1\n").unwrap();
+        let log = "\
+. sysuse auto, clear
+
+. do helper.do
+
+. badcmd
+command badcmd is unrecognized
+r(199);
+
+end of do-file
+r(199);
+";
+        // helper.do isn't there to match against
+        assert_eq!(locate(&script, log, temp.path()).unwrap().line, 2);
+    }
+
+    #[test]
+    fn test_trace_through_every_level() {
+        let temp = TempDir::new().unwrap();
+        let code = temp.path().join("code");
+        std::fs::create_dir_all(code.join("sub")).unwrap();
+        let script = temp.path().join("main.do");
+        std::fs::write(&script, "cd code\ndo a\n").unwrap();
+        std::fs::write(code.join("a.do"), "do sub/b.do\n").unwrap();
+        std::fs::write(code.join("sub/b.do"), "local k = 1\ndo sub/c.do\n").unwrap();
+        std::fs::write(
+            code.join("sub/c.do"),
+            "sysuse auto, clear\nregress price mpgx\n",
+        )
+        .unwrap();
+        // `do` paths are relative to Stata's working directory after `cd`,
+        // not to the file that runs them
+        let log = "\
+. cd code
+
+. do a
+
+. do sub/b.do
+
+. local k = 1
+
+. do sub/c.do
+
+. sysuse auto, clear
+
+. regress price mpgx
+variable mpgx not found
+r(111);
+
+end of do-file
+r(111);
+";
+        let chain = trace(&script, log, temp.path());
+        assert_eq!(
+            format_trace(&chain),
+            format!(
+                "{}:2 → code/a.do:1 → code/sub/b.do:2 → code/sub/c.do:2",
+                script.display()
+            )
+        );
+    }
+
+    #[test]
+    fn test_trace_ends_at_unreadable_file() {
+        let temp = TempDir::new().unwrap();
+        let script = temp.path().join("main.do");
+        std::fs::write(&script, "sysuse auto, clear\ndo helper.do\n").unwrap();
+        std::fs::write(
+            temp.path().join("helper.do"),
+            "local n = 1\ndo missing.do\n",
+        )
+        .unwrap();
+        std::fs::write(temp.path().join("deep.do"), "badcmd\n").unwrap();
+        // missing.do isn't there: the chain ends at the line that ran it
+        // rather than leaving it out and putting deep.do under helper.do
+        let log = "\
+. sysuse auto, clear
+
+. do helper.do
+
+. local n = 1
+
+. do missing.do
+
+. do deep.do
+
+. badcmd
+command badcmd is unrecognized
+r(199);
+
+end of do-file
+r(199);
+";
+        let chain = trace(&script, log, temp.path());
+        assert_eq!(
+            format_trace(&chain),
+            format!("{}:2 → helper.do:2", script.display())
+        );
+    }
+
+    #[test]
+    fn test_path_argument() {
+        assert_eq!(path_argument("helpers/a.do arg1"), Some("helpers/a.do"));
+        assert_eq!(
+            path_argument("\"my dir/a.do\", nostop"),
+            Some("my dir/a.do")
+        );
+        assert_eq!(
+            path_argument("`\"C:/my dir/a.do\"'"),
+            Some("C:/my dir/a.do")
+        );
+        assert_eq!(path_argument("a.do, nostop"), Some("a.do"));
+    }
+}
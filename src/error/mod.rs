@@ -1,7 +1,9 @@
 pub mod categories;
 pub mod codes;
 pub mod error_db;
+pub mod exit_codes;
 pub mod extraction;
+pub mod location;
 pub mod mapper;
 pub mod parser;
 
@@ -34,10 +36,50 @@ pub enum Error {
     #[error("{0}")]
     Integrity(String),
 
+    /// Another stacy process is changing the same project or cache, and
+    /// `--wait` wasn't given
+    #[error(
+        "Another stacy process holds the lock on {} (rerun with --wait to wait for it)",
+        .0.display()
+    )]
+    Locked(std::path::PathBuf),
+
     #[error("Project not found. Run `stacy init` to create a project.")]
     ProjectNotFound,
 }
 
+impl Error {
+    /// Short, stable name for the kind of error (no message, no paths)
+    pub fn category(&self) -> &'static str {
+        match self {
+            Error::Io(_) => "io",
+            Error::Json(_) => "json",
+            Error::Parse(_) => "parse",
+            Error::Execution(_) => "execution",
+            Error::Config(_) => "config",
+            Error::Network(_) => "network",
+            Error::Integrity(_) => "integrity",
+            Error::Locked(_) => "locked",
+            Error::ProjectNotFound => "project_not_found",
+        }
+    }
+
+    /// What to try next, for the kinds of error that have a usual remedy
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            Error::ProjectNotFound => Some("Run `stacy init` to create a project"),
+            Error::Network(_) => Some("Check the network connection and try again"),
+            Error::Integrity(_) => Some(
+                "Check where the change comes from; if it is expected, run `stacy lock` to record it",
+            ),
+            Error::Locked(_) => {
+                Some("Wait for it to finish, or rerun with --wait to wait for it")
+            }
+            _ => None,
+        }
+    }
+}
+
 pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -2,7 +2,8 @@
 ///
 /// # Precedence Order
 ///
-/// 1. CLI flag `--engine` (highest priority)
+/// 1. CLI flag `--engine` (highest priority): a path, or a binary name such
+///    as `stata-se` found in the platform locations or on PATH
 /// 2. Environment variable `$STATA_BINARY` (machine-specific)
 /// 3. User config `~/.config/stacy/config.toml` (stata_binary field)
 /// 4. Auto-detection via PATH search (lowest priority)
@@ -38,15 +39,110 @@
 /// - `C:\Program Files\Stata16\StataMP-64.exe` (and SE, IC)
 /// - `C:\Program Files\Stata\StataMP-64.exe` (version-agnostic)
 /// - `C:\Program Files (x86)\Stata*\StataSE.exe` (32-bit installations)
-/// - PATH search
+/// - PATH search (`StataMP-64.exe`, ...)
+///
+/// # Batch Conventions
+///
+/// Unix console binaries run a do-file with `-b -q do script.do`. Windows
+/// has no console Stata: the GUI executable takes `/e /q do script.do` and
+/// exits when the script finishes. See [`BatchStyle`].
 use crate::error::{Error, Result};
 use std::env;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
 /// Stata binary preference order for auto-detection
 const BINARY_NAMES: &[&str] = &["stata-mp", "stata-se", "stata-be", "stata"];
 
+/// Windows executable names for PATH search, in preference order
+#[cfg(target_os = "windows")]
+const WINDOWS_BINARY_NAMES: &[&str] = &[
+    "StataMP-64.exe",
+    "StataSE-64.exe",
+    "StataBE-64.exe",
+    "StataIC-64.exe",
+    "StataMP.exe",
+    "StataSE.exe",
+];
+
+/// How a Stata binary is told to run a do-file non-interactively
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatchStyle {
+    /// Unix console binaries: `stata-mp -b -q do script.do`
+    Unix,
+    /// Windows executables: `StataMP-64.exe /e /q do script.do`
+    Windows,
+}
+
+impl BatchStyle {
+    /// Style for `binary`. Any `.exe` is a Windows Stata, so a Windows
+    /// binary under Wine or WSL interop gets Windows flags too.
+    pub fn for_binary(binary: &str) -> Self {
+        if cfg!(target_os = "windows") || binary.to_ascii_lowercase().ends_with(".exe") {
+            BatchStyle::Windows
+        } else {
+            BatchStyle::Unix
+        }
+    }
+
+    /// Flags preceding the script path
+    pub fn args(self) -> &'static [&'static str] {
+        match self {
+            // -b: batch mode (no GUI), -q: quiet startup (suppress banner)
+            BatchStyle::Unix => &["-b", "-q", "do"],
+            // /e: batch mode without the completion dialog /b would show
+            BatchStyle::Windows => &["/e", "/q", "do"],
+        }
+    }
+}
+
+/// The console binary next to a GUI Stata (`xstata-mp` on Linux, `StataMP`
+/// inside the macOS app bundle), which can't run batch jobs from a terminal.
+///
+/// Returns `None` for binaries that aren't a GUI Stata. Windows has only
+/// the GUI executable, which runs batch jobs itself.
+pub fn console_counterpart(binary: &str) -> Option<PathBuf> {
+    let path = Path::new(binary);
+    let name = path.file_name()?.to_str()?;
+    let console = if let Some(rest) = name.strip_prefix("xstata") {
+        format!("stata{}", rest)
+    } else if let Some(edition) = name.strip_prefix("Stata") {
+        let in_app_bundle = path.parent().is_some_and(|p| p.ends_with("Contents/MacOS"));
+        if !in_app_bundle || edition.is_empty() || edition.contains('.') {
+            return None;
+        }
+        format!("stata-{}", edition.to_ascii_lowercase())
+    } else {
+        return None;
+    };
+    Some(path.with_file_name(console))
+}
+
+/// The GUI Stata next to a console binary (`xstata-mp` on Linux, `StataMP`
+/// inside the macOS app bundle), the inverse of [`console_counterpart`].
+///
+/// Windows executables are the GUI already and come back unchanged.
+pub fn gui_counterpart(binary: &str) -> Option<PathBuf> {
+    let path = Path::new(binary);
+    if binary.to_ascii_lowercase().ends_with(".exe") {
+        return Some(path.to_path_buf());
+    }
+    let name = path.file_name()?.to_str()?;
+    let edition = name.strip_prefix("stata")?;
+    let in_app_bundle = path.parent().is_some_and(|p| p.ends_with("Contents/MacOS"));
+    let gui = if in_app_bundle {
+        // stata-mp -> StataMP
+        let edition = edition.strip_prefix('-')?;
+        if edition.is_empty() {
+            return None;
+        }
+        format!("Stata{}", edition.to_ascii_uppercase())
+    } else {
+        format!("xstata{}", edition)
+    };
+    Some(path.with_file_name(gui))
+}
+
 /// macOS-specific Stata application locations
 #[cfg(target_os = "macos")]
 const MACOS_APP_LOCATIONS: &[&str] = &[
@@ -67,7 +163,11 @@ const LINUX_LOCATIONS: &[&str] = &[
 /// Windows-specific Stata installation locations
 #[cfg(target_os = "windows")]
 const WINDOWS_LOCATIONS: &[&str] = &[
-    // StataNow (newest, 64-bit)
+    // Stata 19 (64-bit)
+    r"C:\Program Files\Stata19\StataMP-64.exe",
+    r"C:\Program Files\Stata19\StataSE-64.exe",
+    r"C:\Program Files\Stata19\StataBE-64.exe",
+    // StataNow (64-bit)
     r"C:\Program Files\StataNow\StataMP-64.exe",
     r"C:\Program Files\StataNow\StataSE-64.exe",
     r"C:\Program Files\StataNow\StataIC-64.exe",
@@ -118,7 +218,11 @@ pub fn detect_stata_binary(cli_engine: Option<&str>) -> Result<String> {
     // 1. CLI flag (highest priority)
     if let Some(binary) = cli_engine {
         if verify_binary(binary)? {
+            tracing::debug!(binary, "Stata binary from --engine");
             return Ok(binary.to_string());
+        } else if let Some(found) = find_named_binary(binary) {
+            tracing::debug!(binary = %found, engine = binary, "Stata binary found by name");
+            return Ok(found);
         } else {
             return Err(Error::Execution(format!(
                 "Stata binary specified via --engine not found or not executable: {}",
@@ -130,6 +234,7 @@ pub fn detect_stata_binary(cli_engine: Option<&str>) -> Result<String> {
     // 2. Environment variable ($STATA_BINARY)
     if let Ok(binary) = env::var("STATA_BINARY") {
         if verify_binary(&binary)? {
+            tracing::debug!(%binary, "Stata binary from $STATA_BINARY");
             return Ok(binary);
         } else {
             return Err(Error::Execution(format!(
@@ -142,6 +247,7 @@ pub fn detect_stata_binary(cli_engine: Option<&str>) -> Result<String> {
     // 3. User config (~/.config/stacy/config.toml)
     if let Some(binary) = get_user_config_binary()? {
         if verify_binary(&binary)? {
+            tracing::debug!(%binary, "Stata binary from the user config");
             return Ok(binary);
         } else {
             return Err(Error::Execution(format!(
@@ -170,14 +276,18 @@ fn get_user_config_binary() -> Result<Option<String>> {
 fn auto_detect_binary() -> Result<String> {
     // Try platform-specific locations first
     if let Some(binary) = try_platform_locations() {
+        tracing::debug!(%binary, "Stata binary found in a standard location");
         return Ok(binary);
     }
 
     // Try PATH search
     if let Some(binary) = try_path_search() {
+        tracing::debug!(%binary, "Stata binary found on PATH");
         return Ok(binary);
     }
 
+    tracing::debug!("no Stata binary in the standard locations or on PATH");
+
     // No Stata binary found
     Err(Error::Execution(
         "Stata binary not found. Tried:\n\
@@ -220,8 +330,39 @@ fn try_platform_locations() -> Option<String> {
     None
 }
 
+/// Find a binary given by bare name (`--engine stata-se`) where
+/// auto-detection looks: the platform locations, then PATH
+fn find_named_binary(name: &str) -> Option<String> {
+    if Path::new(name).components().count() != 1 {
+        return None;
+    }
+    #[cfg(target_os = "macos")]
+    let locations = MACOS_APP_LOCATIONS;
+    #[cfg(target_os = "linux")]
+    let locations = LINUX_LOCATIONS;
+    #[cfg(target_os = "windows")]
+    let locations = WINDOWS_LOCATIONS;
+    #[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
+    let locations: &[&str] = &[];
+
+    locations
+        .iter()
+        .find(|location| {
+            Path::new(location).file_name().is_some_and(|f| f == name) && is_executable(location)
+        })
+        .map(|location| location.to_string())
+        .or_else(|| find_in_path(name))
+}
+
 /// Search PATH for Stata binaries
 fn try_path_search() -> Option<String> {
+    #[cfg(target_os = "windows")]
+    for binary_name in WINDOWS_BINARY_NAMES {
+        if let Some(path) = find_in_path(binary_name) {
+            return Some(path);
+        }
+    }
+
     for binary_name in BINARY_NAMES {
         if let Some(path) = find_in_path(binary_name) {
             return Some(path);
@@ -241,7 +382,8 @@ fn find_in_path(binary_name: &str) -> Option<String> {
     if let Ok(output) = Command::new(which_cmd).arg(binary_name).output() {
         if output.status.success() {
             if let Ok(path) = String::from_utf8(output.stdout) {
-                let path = path.trim();
+                // `where` lists every match, one per line
+                let path = path.lines().next().unwrap_or("").trim();
                 if !path.is_empty() && Path::new(path).is_file() {
                     return Some(path.to_string());
                 }
@@ -332,6 +474,60 @@ mod tests {
         assert!(WINDOWS_LOCATIONS.iter().any(|p| p.contains("Stata17")));
     }
 
+    #[test]
+    fn test_batch_style() {
+        assert_eq!(
+            BatchStyle::for_binary(r"C:\Program Files\Stata18\StataMP-64.EXE"),
+            BatchStyle::Windows
+        );
+        assert_eq!(BatchStyle::Windows.args(), &["/e", "/q", "do"]);
+        if !cfg!(target_os = "windows") {
+            assert_eq!(
+                BatchStyle::for_binary("/usr/local/stata18/stata-mp"),
+                BatchStyle::Unix
+            );
+        }
+        assert_eq!(BatchStyle::Unix.args(), &["-b", "-q", "do"]);
+    }
+
+    #[test]
+    fn test_console_counterpart() {
+        assert_eq!(
+            console_counterpart("/usr/local/stata18/xstata-mp"),
+            Some(PathBuf::from("/usr/local/stata18/stata-mp"))
+        );
+        assert_eq!(
+            console_counterpart("/Applications/Stata/StataMP.app/Contents/MacOS/StataMP"),
+            Some(PathBuf::from(
+                "/Applications/Stata/StataMP.app/Contents/MacOS/stata-mp"
+            ))
+        );
+        assert_eq!(console_counterpart("/usr/local/stata18/stata-mp"), None);
+        assert_eq!(
+            console_counterpart(r"C:\Program Files\Stata18\StataMP-64.exe"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_gui_counterpart() {
+        assert_eq!(
+            gui_counterpart("/usr/local/stata18/stata-mp"),
+            Some(PathBuf::from("/usr/local/stata18/xstata-mp"))
+        );
+        assert_eq!(
+            gui_counterpart("/Applications/Stata/StataMP.app/Contents/MacOS/stata-mp"),
+            Some(PathBuf::from(
+                "/Applications/Stata/StataMP.app/Contents/MacOS/StataMP"
+            ))
+        );
+        assert_eq!(
+            gui_counterpart(r"C:\Program Files\Stata18\StataMP-64.exe"),
+            Some(PathBuf::from(r"C:\Program Files\Stata18\StataMP-64.exe"))
+        );
+        assert_eq!(gui_counterpart("/usr/local/stata18/xstata-mp"), None);
+    }
+
     #[test]
     fn test_verify_nonexistent_binary() {
         let result = verify_binary("/nonexistent/stata-mp");
@@ -339,6 +535,13 @@ mod tests {
         assert!(!result.unwrap());
     }
 
+    #[test]
+    fn test_find_named_binary() {
+        // Paths are taken as given, never searched for
+        assert_eq!(find_named_binary("/fake/stata-mp"), None);
+        assert_eq!(find_named_binary("no-such-stata-edition"), None);
+    }
+
     #[test]
     fn test_cli_precedence() {
         // CLI flag should override everything
@@ -0,0 +1,340 @@
+//! Console-mode execution over a pseudo-terminal
+//!
+//! The batch runner starts `stata -b` and polls the log Stata writes. Stata
+//! flushes that log in blocks, so streamed output lags, and whatever is still
+//! buffered when Stata dies never reaches the log. The console backend
+//! instead starts Stata's interactive console on a pty, types
+//! `do "<script>"` at its prompt, and reads the output as Stata prints it.
+//!
+//! Each line is appended (and flushed) to the run's log file as it arrives,
+//! so streaming, error detection and log retention work exactly as they do
+//! for batch runs. The transcript has the shape of a batch log: the console
+//! echoes the typed `do` command after its `. ` prompt just as the log does.
+//!
+//! Unix only; Windows has no console Stata.
+
+use super::runner::{RunOptions, RunResult};
+use crate::error::{Error, Result};
+use std::path::Path;
+
+/// Run `script` in Stata's console mode (see module docs)
+#[cfg(unix)]
+pub fn run_console(script: &Path, options: &RunOptions) -> Result<RunResult> {
+    unix::run_console(script, options)
+}
+
+/// Run `script` in Stata's console mode (see module docs)
+#[cfg(not(unix))]
+pub fn run_console(_script: &Path, _options: &RunOptions) -> Result<RunResult> {
+    Err(Error::Execution(
+        "The console backend needs a Unix pseudo-terminal; use --backend batch".to_string(),
+    ))
+}
+
+#[cfg(unix)]
+mod unix {
+    use super::*;
+    use crate::executor::run_paths::stata_path;
+    use crate::executor::runner::{
+        capture_stderr, configure_environment, exit_code_from_status, resolve_log_file,
+        signaled_from_status, wait_with_timeout,
+    };
+    use std::fs::File;
+    use std::io::{Read, Write};
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::os::unix::process::CommandExt;
+    use std::process::{Command, Stdio};
+    use std::time::{Duration, Instant};
+
+    /// How long output must pause before a trailing `. ` counts as the
+    /// prompt rather than the start of a line still arriving
+    const PROMPT_SETTLE: Duration = Duration::from_millis(50);
+
+    /// Stata's console prompt
+    const PROMPT: &[u8] = b". ";
+
+    /// Shown when `set more on` pauses output; a space continues
+    const MORE: &[u8] = b"--more--";
+
+    /// Terminal size reported to Stata. 80 columns makes the console wrap
+    /// lines where a batch log does, which the log parser relies on.
+    const COLUMNS: u16 = 80;
+    const ROWS: u16 = 24;
+
+    pub(super) fn run_console(script: &Path, options: &RunOptions) -> Result<RunResult> {
+        let start = Instant::now();
+        let log_file = resolve_log_file(script, options);
+        let (master, slave) = open_pty()?;
+
+        let mut cmd = Command::new(options.stata_binary);
+        cmd.arg("-q");
+        cmd.env("TERM", "dumb");
+        cmd.stdin(Stdio::from(slave.try_clone()?));
+        cmd.stdout(Stdio::from(slave));
+        // stderr stays a pipe: startup failures (license, init) land there,
+        // where `parse_or_explain` looks for them.
+        cmd.stderr(Stdio::piped());
+        configure_environment(&mut cmd, options)?;
+        // SAFETY: setsid and ioctl are async-signal-safe. Stata gets its own
+        // session with the pty as controlling terminal, so it sees a console.
+        unsafe {
+            cmd.pre_exec(|| {
+                if libc::setsid() == -1 || libc::ioctl(0, libc::TIOCSCTTY as _, 0) == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+
+        let mut child = cmd.spawn()?;
+        // The command holds our copies of the slave; the master only sees
+        // end-of-file once every copy outside Stata is closed.
+        drop(cmd);
+
+        let stderr_handle = capture_stderr(&mut child);
+        let commands = vec![
+            format!("do `\"{}\"'", stata_path(script)),
+            "exit, clear".to_string(),
+        ];
+        let transcript = {
+            let log_file = log_file.clone();
+            std::thread::spawn(move || drive(File::from(master), &log_file, commands))
+        };
+
+        let (exit_status, timed_out) = match options.timeout {
+            Some(timeout) => wait_with_timeout(&mut child, timeout)?,
+            None => (child.wait()?, false),
+        };
+        let duration = start.elapsed();
+
+        transcript
+            .join()
+            .map_err(|_| Error::Execution("Console reader thread panicked".to_string()))?
+            .map_err(|e| {
+                Error::Io(std::io::Error::new(
+                    e.kind(),
+                    format!("Failed to write {}: {}", log_file.display(), e),
+                ))
+            })?;
+
+        let stderr = stderr_handle
+            .and_then(|h| h.join().ok())
+            .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+            .unwrap_or_default();
+
+        let exit_code = exit_code_from_status(&exit_status);
+        Ok(RunResult {
+            exit_code,
+            log_file,
+            duration,
+            completed: exit_status.success() || exit_code == 0,
+            signaled: signaled_from_status(&exit_status) || timed_out,
+            stderr,
+        })
+    }
+
+    /// Open a pty pair sized like a batch log
+    fn open_pty() -> Result<(OwnedFd, OwnedFd)> {
+        let mut master = -1;
+        let mut slave = -1;
+        let size = libc::winsize {
+            ws_row: ROWS,
+            ws_col: COLUMNS,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        // SAFETY: openpty writes two fds into the provided ints; a null name
+        // and termios request the defaults.
+        let rc = unsafe {
+            libc::openpty(
+                &mut master,
+                &mut slave,
+                std::ptr::null_mut(),
+                std::ptr::null(),
+                &size as *const _ as *mut _,
+            )
+        };
+        if rc != 0 {
+            return Err(Error::Execution(format!(
+                "Failed to open a pseudo-terminal: {}",
+                std::io::Error::last_os_error()
+            )));
+        }
+        // SAFETY: openpty succeeded, so both fds are open and owned by us.
+        Ok(unsafe { (OwnedFd::from_raw_fd(master), OwnedFd::from_raw_fd(slave)) })
+    }
+
+    /// What the pty has for us within a wait
+    enum Ready {
+        Data,
+        Quiet,
+        Closed,
+    }
+
+    fn wait_readable(master: &File, timeout: Duration) -> Ready {
+        let mut fd = libc::pollfd {
+            fd: master.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // SAFETY: one valid pollfd
+        match unsafe { libc::poll(&mut fd, 1, timeout.as_millis() as libc::c_int) } {
+            0 => Ready::Quiet,
+            n if n > 0 && fd.revents & libc::POLLIN != 0 => Ready::Data,
+            n if n > 0 => Ready::Closed,
+            _ if std::io::Error::last_os_error().kind() == std::io::ErrorKind::Interrupted => {
+                Ready::Quiet
+            }
+            _ => Ready::Closed,
+        }
+    }
+
+    /// Read Stata's console output into `log_file`, typing each of
+    /// `commands` at a prompt. The last command (`exit`) and anything after
+    /// it stay out of the log.
+    pub(super) fn drive(
+        mut master: File,
+        log_file: &Path,
+        commands: Vec<String>,
+    ) -> std::io::Result<()> {
+        let mut log = File::create(log_file)?;
+        let mut input = master.try_clone()?;
+        let total = commands.len();
+        let mut commands = commands.into_iter().enumerate();
+        let mut pending: Vec<u8> = Vec::new();
+        let mut recording = true;
+        let mut chunk = [0u8; 4096];
+
+        loop {
+            match wait_readable(&master, PROMPT_SETTLE) {
+                Ready::Data => {
+                    let n = match master.read(&mut chunk) {
+                        Ok(0) => break,
+                        Ok(n) => n,
+                        Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                        // EIO: Stata exited and the pty closed
+                        Err(_) => break,
+                    };
+                    pending.extend_from_slice(&chunk[..n]);
+                    while let Some(end) = pending.iter().position(|&b| b == b'\n') {
+                        let line: Vec<u8> = pending.drain(..=end).collect();
+                        if recording {
+                            log.write_all(&strip_cr(&line))?;
+                            log.write_all(b"\n")?;
+                            log.flush()?;
+                        }
+                    }
+                    if strip_cr(&pending).ends_with(MORE) {
+                        input.write_all(b" ")?;
+                        pending.clear();
+                    }
+                }
+                Ready::Quiet => {
+                    if strip_cr(&pending) != PROMPT {
+                        continue;
+                    }
+                    let Some((i, command)) = commands.next() else {
+                        continue;
+                    };
+                    if i + 1 == total {
+                        recording = false;
+                        pending.clear();
+                    }
+                    input.write_all(command.as_bytes())?;
+                    input.write_all(b"\n")?;
+                }
+                Ready::Closed => break,
+            }
+        }
+
+        if recording && !pending.is_empty() {
+            log.write_all(&strip_cr(&pending))?;
+            log.write_all(b"\n")?;
+        }
+        log.flush()
+    }
+
+    /// Drop the carriage returns the pty adds to every line ending
+    fn strip_cr(bytes: &[u8]) -> Vec<u8> {
+        let end = bytes
+            .iter()
+            .rposition(|&b| b != b'\r' && b != b'\n')
+            .map_or(0, |i| i + 1);
+        bytes[..end]
+            .iter()
+            .copied()
+            .filter(|&b| b != b'\r')
+            .collect()
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::executor::runner::{Backend, RunOptions};
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    /// A stand-in for console Stata: prompts, runs `do` by printing a
+    /// result and the trailer, and exits on `exit`
+    const FAKE_CONSOLE: &str = r#"#!/bin/sh
+while true; do
+    printf '. '
+    IFS= read -r line || exit 0
+    case "$line" in
+        exit*) exit 0 ;;
+        do*) printf '\nhello from the console\n\nend of do-file\n\n' ;;
+    esac
+done
+"#;
+
+    fn fake_stata(dir: &Path) -> std::path::PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+        let path = dir.join("stata-console");
+        std::fs::write(&path, FAKE_CONSOLE).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    /// Freshly written executables can briefly fail with ETXTBSY under the
+    /// parallel test runner (see `runner::tests`); retry a few times.
+    fn run_with_retry(script: &Path, binary: &str, log: &Path) -> RunResult {
+        for attempt in 0..3 {
+            let options = RunOptions::new(binary)
+                .with_log_file(log.to_path_buf())
+                .with_backend(Backend::Console)
+                .with_timeout(Duration::from_secs(20));
+            match run_console(script, &options) {
+                Ok(result) => return result,
+                Err(Error::Io(e)) if e.kind() == std::io::ErrorKind::ExecutableFileBusy => {
+                    std::thread::sleep(Duration::from_millis(50 * (attempt + 1)));
+                }
+                Err(e) => panic!("console run failed: {e:?}"),
+            }
+        }
+        panic!("spawn after ETXTBSY retries");
+    }
+
+    #[test]
+    fn test_console_transcript_becomes_log() {
+        let temp = TempDir::new().unwrap();
+        let binary = fake_stata(temp.path());
+        let log = temp.path().join("run.log");
+        let script = temp.path().join("analysis.do");
+
+        let result = run_with_retry(&script, binary.to_str().unwrap(), &log);
+
+        assert!(result.completed);
+        assert!(!result.signaled);
+        let content = std::fs::read_to_string(&log).unwrap();
+        assert!(
+            content.starts_with(&format!(". do `\"{}\"'\n", script.display())),
+            "transcript should open with the echoed do command: {:?}",
+            content
+        );
+        assert!(content.contains("hello from the console\n"));
+        assert!(content.contains("end of do-file"));
+        assert!(!content.contains('\r'));
+        assert!(!content.contains("exit, clear"));
+    }
+}
@@ -0,0 +1,223 @@
+//! Newline-delimited JSON events (`--format json-lines`)
+//!
+//! `--format json` prints one document once everything is over. Editor
+//! plugins and orchestration tools want to follow a run while it happens, so
+//! with `--format json-lines` the executor writes one JSON object per line to
+//! stdout as things happen instead, for every script it runs:
+//!
+//! - `started` — Stata was started on the script
+//! - `log-chunk` — a line Stata wrote to the log, as it was written
+//! - `error-detected` — an error found in the log once Stata is done
+//! - `regression` — a passing script took much longer than its last passing
+//!   runs (see `history::regression`), just before its `finished`
+//! - `finished` — the script's outcome (also for a build-cache hit, with
+//!   `"cached": true` and no events before it)
+//!
+//! Each event names its script, so the events of scripts running side by side
+//! (`--parallel`) can be told apart. Lines are written whole under the stdout
+//! lock and flushed, so they never interleave mid-line.
+
+use crate::cache::CachedResult;
+use crate::error::location::SourceLocation;
+use crate::error::StataError;
+use serde::Serialize;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// One line of the event stream
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum Event<'a> {
+    Started {
+        script: &'a Path,
+    },
+    LogChunk {
+        script: &'a Path,
+        text: &'a str,
+    },
+    ErrorDetected {
+        script: &'a Path,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        r_code: Option<u32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        line_number: Option<usize>,
+        /// `file.do:line` of the failing command, in nested do-files too
+        #[serde(skip_serializing_if = "Option::is_none")]
+        location: Option<String>,
+        /// The do-files that led there, outermost first (`main.do:3`, ...,
+        /// then `location`)
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        trace: Vec<String>,
+        message: String,
+    },
+    Regression {
+        script: &'a Path,
+        duration_secs: f64,
+        /// Median duration of the script's last passing runs
+        baseline_secs: f64,
+        baseline_runs: usize,
+        slower_percent: f64,
+    },
+    Finished {
+        script: &'a Path,
+        success: bool,
+        exit_code: i32,
+        duration_secs: f64,
+        error_count: usize,
+        /// The result came from the build cache; Stata didn't run
+        #[serde(skip_serializing_if = "std::ops::Not::not")]
+        cached: bool,
+    },
+}
+
+impl<'a> Event<'a> {
+    /// `finished` for a result taken from the build cache
+    pub fn cached(script: &'a Path, result: &CachedResult) -> Self {
+        Event::Finished {
+            script,
+            success: result.success,
+            exit_code: result.exit_code,
+            duration_secs: result.duration_secs,
+            error_count: result.errors.len(),
+            cached: true,
+        }
+    }
+
+    pub fn error_detected(script: &'a Path, error: &StataError, trace: &[SourceLocation]) -> Self {
+        match error {
+            StataError::StataCode {
+                r_code,
+                message,
+                line_number,
+                ..
+            } => Event::ErrorDetected {
+                script,
+                r_code: Some(*r_code),
+                line_number: *line_number,
+                location: trace.last().map(ToString::to_string),
+                trace: trace.iter().map(ToString::to_string).collect(),
+                message: message.clone(),
+            },
+            StataError::ProcessKilled { exit_code } => Event::ErrorDetected {
+                script,
+                r_code: None,
+                line_number: None,
+                location: None,
+                trace: Vec::new(),
+                message: format!("Process killed (exit code {})", exit_code),
+            },
+        }
+    }
+}
+
+/// Write `event` to stdout as one line. A closed stdout is ignored, as for
+/// streamed logs.
+pub fn emit(event: &Event) {
+    if let Ok(line) = serde_json::to_string(event) {
+        let mut out = std::io::stdout().lock();
+        let _ = writeln!(out, "{}", line);
+        let _ = out.flush();
+    }
+}
+
+/// Writer that turns a streamed log into `log-chunk` events, one per line
+pub struct LogChunks {
+    script: PathBuf,
+    line: Vec<u8>,
+}
+
+impl LogChunks {
+    pub fn new(script: impl Into<PathBuf>) -> Self {
+        Self {
+            script: script.into(),
+            line: Vec::new(),
+        }
+    }
+
+    fn emit_line(&mut self) {
+        let text = crate::utils::encoding::decode_text(&self.line);
+        let text = text.strip_suffix('\n').unwrap_or(&text);
+        let text = text.strip_suffix('\r').unwrap_or(text);
+        emit(&Event::LogChunk {
+            script: &self.script,
+            text,
+        });
+        self.line.clear();
+    }
+}
+
+impl Write for LogChunks {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        for &byte in buf {
+            self.line.push(byte);
+            if byte == b'\n' {
+                self.emit_line();
+            }
+        }
+        Ok(buf.len())
+    }
+
+    /// A partial line is its own chunk: the stream is done
+    fn flush(&mut self) -> std::io::Result<()> {
+        if !self.line.is_empty() {
+            self.emit_line();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorType;
+
+    #[test]
+    fn test_events_serialize_tagged() {
+        let script = Path::new("analysis.do");
+        let started = serde_json::to_string(&Event::Started { script }).unwrap();
+        assert_eq!(started, r#"{"event":"started","script":"analysis.do"}"#);
+
+        let chunk = serde_json::to_string(&Event::LogChunk {
+            script,
+            text: ". sysuse auto",
+        })
+        .unwrap();
+        assert_eq!(
+            chunk,
+            r#"{"event":"log-chunk","script":"analysis.do","text":". sysuse auto"}"#
+        );
+    }
+
+    #[test]
+    fn test_error_detected_from_stata_error() {
+        let script = Path::new("analysis.do");
+        let error = StataError::new(
+            ErrorType::StataError,
+            "variable price not found".to_string(),
+            111,
+        )
+        .with_line_number(4);
+        let trace = [
+            SourceLocation {
+                file: PathBuf::from("analysis.do"),
+                line: 3,
+            },
+            SourceLocation {
+                file: PathBuf::from("helpers/prep.do"),
+                line: 12,
+            },
+        ];
+        let json = serde_json::to_value(Event::error_detected(script, &error, &trace)).unwrap();
+        assert_eq!(json["event"], "error-detected");
+        assert_eq!(json["r_code"], 111);
+        assert_eq!(json["line_number"], 4);
+        assert_eq!(json["location"], "helpers/prep.do:12");
+        assert_eq!(json["trace"][0], "analysis.do:3");
+        assert_eq!(json["message"], "variable price not found");
+
+        let killed = StataError::ProcessKilled { exit_code: 137 };
+        let json = serde_json::to_value(Event::error_detected(script, &killed, &[])).unwrap();
+        assert!(json.get("r_code").is_none());
+        assert_eq!(json["message"], "Process killed (exit code 137)");
+    }
+}
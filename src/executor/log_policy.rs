@@ -6,6 +6,10 @@
 //!
 //! - `--log FILE` — the log is a durable artifact: move it to FILE, keep it
 //!   whether the run passed or failed.
+//! - `--log-dir DIR` and/or `--log-name PATTERN` — also an artifact, kept pass
+//!   or fail under a name that doesn't change from run to run
+//!   (`<script>.log` unless a pattern says otherwise), so downstream tooling
+//!   knows where to look. The directory defaults to `[run] log_dir`.
 //! - otherwise the log is internal: removed when the run succeeded, kept when it
 //!   failed.
 //!
@@ -19,9 +23,14 @@
 //! Kept logs land in `[run] log_dir` from `stacy.toml` when the run happened
 //! inside a project — without that they piled up in the working directory (#98).
 
+use crate::error::{Error, Result};
 use crate::project::Project;
+use crate::utils::date::now_utc;
 use std::path::{Path, PathBuf};
 
+/// Placeholders a `--log-name` pattern may contain, each in braces
+const NAME_PLACEHOLDERS: &[&str] = &["script", "date", "time", "status"];
+
 /// What to do with a log file once the run is over.
 #[derive(Debug, Clone, Default)]
 pub struct LogPolicy {
@@ -29,6 +38,10 @@ pub struct LogPolicy {
     keep_dir: Option<PathBuf>,
     /// Explicit destination from `--log`. Wins over everything else.
     dest: Option<PathBuf>,
+    /// Directory from `--log-dir`: every log is kept there, pass or fail.
+    artifact_dir: Option<PathBuf>,
+    /// File name pattern from `--log-name`. Keeps every log, like `--log-dir`.
+    name: Option<String>,
 }
 
 impl LogPolicy {
@@ -52,6 +65,23 @@ impl LogPolicy {
         self
     }
 
+    /// Keep every log in this directory (`--log-dir DIR`), pass or fail.
+    pub fn with_dir(mut self, dir: Option<PathBuf>) -> Self {
+        self.artifact_dir = dir;
+        self
+    }
+
+    /// Name every kept log after `pattern` (`--log-name`), pass or fail.
+    /// Fails when the pattern is not a plain file name or uses an unknown
+    /// placeholder.
+    pub fn with_name(mut self, pattern: Option<String>) -> Result<Self> {
+        if let Some(pattern) = &pattern {
+            check_name_pattern(pattern)?;
+        }
+        self.name = pattern;
+        Ok(self)
+    }
+
     /// Directory kept logs are moved into, if any.
     pub fn keep_dir(&self) -> Option<&Path> {
         self.keep_dir.as_deref()
@@ -63,32 +93,105 @@ impl LogPolicy {
     /// Call this only after everything that reads the log (streaming, error
     /// context, printed excerpts) is done.
     pub fn finalize(&self, log: &Path, success: bool) -> Option<PathBuf> {
+        self.apply(log, None, success)
+    }
+
+    /// [`finalize`](Self::finalize) for the log of `script`, whose stem is
+    /// what `--log-dir` and `{script}` name the log after.
+    pub fn finalize_for(&self, script: &Path, log: &Path, success: bool) -> Option<PathBuf> {
+        self.apply(log, Some(script), success)
+    }
+
+    fn apply(&self, log: &Path, script: Option<&Path>, success: bool) -> Option<PathBuf> {
+        let kept = self.keep(log, script, success);
+        crate::history::note_log(log, kept.as_deref());
+        kept
+    }
+
+    fn keep(&self, log: &Path, script: Option<&Path>, success: bool) -> Option<PathBuf> {
         if let Some(dest) = &self.dest {
             return Some(move_log(log, dest));
         }
 
+        if self.artifact_dir.is_some() || self.name.is_some() {
+            let dir = self
+                .artifact_dir
+                .as_deref()
+                .or(self.keep_dir.as_deref())
+                .or_else(|| log.parent())
+                .unwrap_or(Path::new(""));
+            let stem = script
+                .and_then(Path::file_stem)
+                .or_else(|| log.file_stem())
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let name = match &self.name {
+                Some(pattern) => render_name(pattern, &stem, success),
+                None => format!("{}.log", stem),
+            };
+            return Some(move_into(log, dir, &name));
+        }
+
         if success {
             let _ = std::fs::remove_file(log);
             return None;
         }
 
         match (&self.keep_dir, log.file_name()) {
-            (Some(dir), Some(name)) => {
-                if let Err(e) = std::fs::create_dir_all(dir) {
-                    eprintln!(
-                        "Warning: could not create log directory {}: {}",
-                        dir.display(),
-                        e
-                    );
-                    return Some(log.to_path_buf());
-                }
-                Some(move_log(log, &dir.join(name)))
-            }
+            (Some(dir), Some(name)) => Some(move_into(log, dir, &name.to_string_lossy())),
             _ => Some(log.to_path_buf()),
         }
     }
 }
 
+/// Check that a `--log-name` pattern is a file name with known placeholders
+fn check_name_pattern(pattern: &str) -> Result<()> {
+    if pattern.is_empty() || pattern.contains(['/', '\\']) {
+        return Err(Error::Config(format!(
+            "--log-name must be a file name, not a path: '{}' (use --log-dir for the directory)",
+            pattern
+        )));
+    }
+    let mut rest = pattern;
+    while let Some(open) = rest.find('{') {
+        let after = &rest[open + 1..];
+        let close = after
+            .find('}')
+            .ok_or_else(|| Error::Config(format!("Unclosed '{{' in --log-name '{}'", pattern)))?;
+        let placeholder = &after[..close];
+        if !NAME_PLACEHOLDERS.contains(&placeholder) {
+            return Err(Error::Config(format!(
+                "Unknown placeholder {{{}}} in --log-name '{}' (available: {})",
+                placeholder,
+                pattern,
+                NAME_PLACEHOLDERS
+                    .iter()
+                    .map(|p| format!("{{{}}}", p))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )));
+        }
+        rest = &after[close + 1..];
+    }
+    Ok(())
+}
+
+/// Fill in a `--log-name` pattern. `{date}` and `{time}` are UTC (YYYYMMDD,
+/// HHMMSS); a name without an extension gets `.log`.
+fn render_name(pattern: &str, script_stem: &str, success: bool) -> String {
+    let (date, time) = now_utc();
+    let name = pattern
+        .replace("{script}", script_stem)
+        .replace("{date}", &date)
+        .replace("{time}", &time)
+        .replace("{status}", if success { "passed" } else { "failed" });
+    if Path::new(&name).extension().is_none() {
+        format!("{}.log", name)
+    } else {
+        name
+    }
+}
+
 /// Absolute `[run] log_dir` for a project, if the log file has somewhere to go.
 fn log_dir_for(project: &Project) -> Option<PathBuf> {
     let config = project.config.as_ref()?;
@@ -99,6 +202,20 @@ fn log_dir_for(project: &Project) -> Option<PathBuf> {
     Some(project.root.join(dir))
 }
 
+/// Move `log` into `dir` as `name`, creating the directory. If it can't be
+/// created the log stays where it is.
+fn move_into(log: &Path, dir: &Path, name: &str) -> PathBuf {
+    if let Err(e) = std::fs::create_dir_all(dir) {
+        eprintln!(
+            "Warning: could not create log directory {}: {}",
+            dir.display(),
+            e
+        );
+        return log.to_path_buf();
+    }
+    move_log(log, &dir.join(name))
+}
+
 /// Move `log` to `dest`, falling back to copy+remove across filesystems.
 /// On failure the log is left where it is and its original path is returned.
 fn move_log(log: &Path, dest: &Path) -> PathBuf {
@@ -222,4 +339,63 @@ mod tests {
         assert_eq!(final_path, Some(dest.clone()));
         assert_eq!(fs::read_to_string(&dest).unwrap(), "log body\n");
     }
+
+    #[test]
+    fn test_log_dir_keeps_passing_log_under_script_name() {
+        let temp = TempDir::new().unwrap();
+        let log = write_log(temp.path());
+        let ci = temp.path().join("logs").join("ci");
+
+        let policy = LogPolicy::new().with_dir(Some(ci.clone()));
+        let final_path = policy.finalize_for(Path::new("code/analysis.do"), &log, true);
+
+        assert_eq!(final_path, Some(ci.join("analysis.log")));
+        assert_eq!(
+            fs::read_to_string(ci.join("analysis.log")).unwrap(),
+            "log body\n"
+        );
+        assert!(!log.exists());
+    }
+
+    #[test]
+    fn test_log_name_renames_within_log_dir() {
+        let temp = TempDir::new().unwrap();
+        let log = write_log(temp.path());
+        let log_dir = temp.path().join("logs");
+
+        let policy = LogPolicy {
+            keep_dir: Some(log_dir.clone()),
+            ..LogPolicy::new()
+        }
+        .with_name(Some("{script}-{status}".to_string()))
+        .unwrap();
+
+        let final_path = policy.finalize_for(Path::new("analysis.do"), &log, false);
+        assert_eq!(final_path, Some(log_dir.join("analysis-failed.log")));
+        assert!(log_dir.join("analysis-failed.log").exists());
+    }
+
+    #[test]
+    fn test_render_name_fills_date_and_time() {
+        let name = render_name("{script}_{date}_{time}.txt", "analysis", true);
+        let parts: Vec<&str> = name.trim_end_matches(".txt").split('_').collect();
+        assert_eq!(parts[0], "analysis");
+        assert_eq!(parts[1].len(), 8);
+        assert_eq!(parts[2].len(), 6);
+        assert!(name.ends_with(".txt"), "an explicit extension is kept");
+    }
+
+    #[test]
+    fn test_with_name_rejects_bad_patterns() {
+        for bad in ["", "ci/{script}", "{script", "{user}.log"] {
+            assert!(
+                LogPolicy::new().with_name(Some(bad.to_string())).is_err(),
+                "{} should be rejected",
+                bad
+            );
+        }
+        assert!(LogPolicy::new()
+            .with_name(Some("{script}_{date}.log".to_string()))
+            .is_ok());
+    }
 }
@@ -6,6 +6,7 @@
 //! - Missing (if Stata crashed before writing)
 
 use crate::error::Result;
+use crate::utils::encoding::decode_text;
 use std::fs::File;
 use std::io::{BufReader, Read as _, Seek, SeekFrom};
 use std::path::Path;
@@ -40,7 +41,7 @@ pub fn read_last_lines(log_file: &Path, n: usize) -> Result<Vec<String>> {
         reader.seek(SeekFrom::Start(0))?;
         let mut buf = Vec::new();
         reader.read_to_end(&mut buf)?;
-        let text = String::from_utf8_lossy(&buf);
+        let text = decode_text(&buf);
         let lines: Vec<String> = text.lines().map(String::from).collect();
         return Ok(lines.into_iter().rev().take(n).rev().collect());
     }
@@ -52,7 +53,14 @@ pub fn read_last_lines(log_file: &Path, n: usize) -> Result<Vec<String>> {
 
     let mut buf = Vec::new();
     reader.read_to_end(&mut buf)?;
-    let text = String::from_utf8_lossy(&buf);
+    // The read may start inside a UTF-8 character; skip its continuation
+    // bytes so the rest isn't mistaken for Latin-1
+    let start = buf
+        .iter()
+        .take(3)
+        .take_while(|&&b| (0x80..0xC0).contains(&b))
+        .count();
+    let text = decode_text(&buf[start..]);
     let lines: Vec<String> = text.lines().map(String::from).collect();
 
     // Take last n lines
@@ -77,7 +85,7 @@ pub fn read_last_lines(log_file: &Path, n: usize) -> Result<Vec<String>> {
 /// ```
 pub fn read_full_log(log_file: &Path) -> Result<String> {
     let bytes = std::fs::read(log_file)?;
-    Ok(String::from_utf8_lossy(&bytes).into_owned())
+    Ok(decode_text(&bytes))
 }
 
 /// Count lines without loading the file into memory.
@@ -267,8 +275,10 @@ pub fn stream_log_to(
     loop {
         reader.seek(SeekFrom::Start(position))?;
 
-        let mut buffer = String::new();
-        let bytes_read = reader.read_line(&mut buffer)?;
+        // Read as bytes: a log of a Latin-1 do-file echoes Latin-1 lines
+        let mut bytes = Vec::new();
+        let bytes_read = reader.read_until(b'\n', &mut bytes)?;
+        let buffer = decode_text(&bytes);
 
         if bytes_read > 0 && (buffer.ends_with('\n') || final_pass) {
             position += bytes_read as u64;
@@ -326,6 +336,52 @@ pub fn stream_log_to(
     Ok(())
 }
 
+/// Writer that puts a label in front of every line, for streaming several
+/// logs into one terminal (`stacy run --parallel -v`). Each line reaches the
+/// inner writer in a single write, so lines of concurrent streams to stdout
+/// interleave whole.
+pub struct PrefixedLines<W: std::io::Write> {
+    prefix: String,
+    out: W,
+    line: Vec<u8>,
+}
+
+impl<W: std::io::Write> PrefixedLines<W> {
+    pub fn new(prefix: impl Into<String>, out: W) -> Self {
+        Self {
+            prefix: prefix.into(),
+            out,
+            line: Vec::new(),
+        }
+    }
+}
+
+impl<W: std::io::Write> std::io::Write for PrefixedLines<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        for &byte in buf {
+            if self.line.is_empty() {
+                self.line.extend_from_slice(self.prefix.as_bytes());
+            }
+            self.line.push(byte);
+            if byte == b'\n' {
+                self.out.write_all(&self.line)?;
+                self.line.clear();
+            }
+        }
+        Ok(buf.len())
+    }
+
+    /// A partial line is written out with a newline: the stream is done
+    fn flush(&mut self) -> std::io::Result<()> {
+        if !self.line.is_empty() {
+            self.line.push(b'\n');
+            self.out.write_all(&self.line)?;
+            self.line.clear();
+        }
+        self.out.flush()
+    }
+}
+
 /// Width at which Stata wraps a log line (default `linesize`). A line this
 /// long was cut off, so the `> ` line after it continues it.
 const WRAP_WIDTH: usize = 79;
@@ -646,6 +702,22 @@ pub fn strip_boilerplate(log_content: &str) -> String {
     result
 }
 
+/// Commands in the log that changed the adopath (`adopath ++ dir`,
+/// `sysdir set PLUS dir`), as echoed.
+///
+/// Only what runs after the wrapper's `do` counts: the wrapper itself points
+/// PLUS and PERSONAL at the strict-mode sandbox before handing over.
+pub fn adopath_changes(log_content: &str) -> Vec<String> {
+    log_content
+        .lines()
+        .skip_while(|line| !line.starts_with(". do "))
+        .skip(1)
+        .filter_map(|line| line.strip_prefix(". "))
+        .filter(|command| crate::deps::parser::is_adopath_change(command))
+        .map(|command| command.trim().to_string())
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1312,6 +1384,20 @@ in prog
         assert_eq!(out, "hello world\n");
     }
 
+    #[test]
+    fn test_prefixed_lines_label_each_line() {
+        let mut out = PrefixedLines::new("[clean] ", Vec::new());
+        out.write_all(b". display 1\n1\npart").unwrap();
+        assert_eq!(out.out, b"[clean] . display 1\n[clean] 1\n");
+        out.write_all(b"ial\n").unwrap();
+        out.write_all(b"tail").unwrap();
+        out.flush().unwrap();
+        assert_eq!(
+            String::from_utf8(out.out).unwrap(),
+            "[clean] . display 1\n[clean] 1\n[clean] partial\n[clean] tail\n"
+        );
+    }
+
     #[test]
     fn test_stream_survives_closed_writer() {
         struct BrokenPipe;
@@ -1397,6 +1483,29 @@ in prog
         Ok(())
     }
 
+    #[test]
+    fn test_adopath_changes_skip_the_wrapper() {
+        let log = "\
+. quietly sysdir set PLUS `\"/tmp/stacy-ado-1/plus\"'
+
+. do `\"/project/main.do\"'
+
+. adopath ++ \"~/my_ado\"
+  [1]  (BASE)      \"/usr/local/stata/ado/base/\"
+
+. display \"adopath ++ x\"
+adopath ++ x
+
+. cap sysdir set PERSONAL .
+
+end of do-file
+";
+        assert_eq!(
+            adopath_changes(log),
+            vec!["adopath ++ \"~/my_ado\"", "cap sysdir set PERSONAL ."]
+        );
+    }
+
     #[test]
     fn test_get_error_context_with_non_utf8() -> Result<()> {
         let mut temp = NamedTempFile::new()?;
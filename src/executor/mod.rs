@@ -1,14 +1,25 @@
 pub mod binary;
+pub mod console;
+pub mod events;
 pub mod log_policy;
 pub mod log_reader;
+pub mod open_log;
+pub mod post_mortem;
+pub mod previous_log;
+pub mod problems;
 pub mod progress;
+pub mod redact;
 pub mod run_paths;
 pub mod runner;
+pub mod sandbox;
+pub mod trace;
 pub mod verbosity;
 pub mod wrapper;
 
+use crate::error::location::SourceLocation;
 use crate::error::{Result, StataError};
 use crate::metrics::Metrics;
+use crate::project::config::ReproducibilitySection;
 use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
 
@@ -20,8 +31,30 @@ pub struct ExecutionResult {
     pub success: bool,
     pub parse_duration: Duration,
     pub metrics: Option<Metrics>,
+    /// Stata commands run before the script (`set seed 12345`, ...)
+    pub preamble: Vec<String>,
+    /// Packages the script installed into its throwaway PLUS/PERSONAL
+    /// (strict mode), deleted after the run
+    pub discarded_installs: Vec<String>,
+    /// `adopath`/`sysdir set` commands the script ran, which let Stata find
+    /// commands outside the locked packages
+    pub adopath_changes: Vec<String>,
+    /// Do-files and lines that led to the first error, outermost first, when
+    /// stacy could match them (following nested `do`/`include`)
+    pub error_trace: Vec<SourceLocation>,
+    /// The data a failed run left behind, described in a fresh Stata
+    /// (`--post-mortem`)
+    pub post_mortem: Option<post_mortem::PostMortem>,
 }
 
+impl ExecutionResult {
+    /// Do-file and line of the command that raised the first error
+    pub fn error_location(&self) -> Option<&SourceLocation> {
+        self.error_trace.last()
+    }
+}
+
+#[derive(Clone)]
 pub struct StataExecutor {
     stata_binary: String,
     verbosity: verbosity::Verbosity,
@@ -36,6 +69,24 @@ pub struct StataExecutor {
     /// Check the locked packages against the package cache before starting Stata.
     /// Default is true; `stacy run --no-verify` turns it off.
     verify_packages: bool,
+    /// Batch mode (default) or console mode over a pty
+    backend: runner::Backend,
+    /// `set processors N` before the script (Stata/MP core count)
+    processors: Option<u32>,
+    /// `[reproducibility]` settings applied before the script
+    reproducibility: ReproducibilitySection,
+    /// Put this label before every streamed log line, for runs sharing a
+    /// terminal (`stacy run --parallel -v`)
+    stream_label: Option<String>,
+    /// Report the run as JSON events on stdout (`--format json-lines`)
+    events: bool,
+    /// Print each error as a `file:line:` problem line on stdout
+    /// (`--format vscode`)
+    problems: bool,
+    /// Filtering and destination of `--trace` output
+    trace: Option<trace::TraceView>,
+    /// After a failure, describe the data the script left behind
+    post_mortem: bool,
 }
 
 impl Default for StataExecutor {
@@ -81,6 +132,14 @@ impl StataExecutor {
             local_ado_paths: Vec::new(),
             timeout: None,
             verify_packages: true,
+            backend: runner::Backend::Batch,
+            processors: None,
+            reproducibility: ReproducibilitySection::default(),
+            stream_label: None,
+            events: false,
+            problems: false,
+            trace: None,
+            post_mortem: false,
         })
     }
 
@@ -94,6 +153,14 @@ impl StataExecutor {
             local_ado_paths: Vec::new(),
             timeout: None,
             verify_packages: true,
+            backend: runner::Backend::Batch,
+            processors: None,
+            reproducibility: ReproducibilitySection::default(),
+            stream_label: None,
+            events: false,
+            problems: false,
+            trace: None,
+            post_mortem: false,
         }
     }
 
@@ -127,6 +194,100 @@ impl StataExecutor {
         self
     }
 
+    /// Drive Stata in batch mode (default) or console mode over a pty
+    pub fn with_backend(mut self, backend: runner::Backend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Run every script with `set processors N`, so timings don't depend on
+    /// how many cores the machine (or its Stata/MP default) happens to use
+    pub fn with_processors(mut self, processors: Option<u32>) -> Self {
+        self.processors = processors;
+        self
+    }
+
+    /// Apply `[reproducibility]` (version, seeds, RNG stream) to every script
+    pub fn with_reproducibility(mut self, reproducibility: ReproducibilitySection) -> Self {
+        self.reproducibility = reproducibility;
+        self
+    }
+
+    /// Prefix every streamed log line with `label`, so the logs of scripts
+    /// running side by side can be told apart
+    pub fn with_stream_label(mut self, label: impl Into<String>) -> Self {
+        self.stream_label = Some(label.into());
+        self
+    }
+
+    /// Write `started`, `log-chunk`, `error-detected` and `finished` events
+    /// to stdout while running, instead of streaming the log
+    pub fn with_events(mut self, events: bool) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// Print each error of a run as a `file:line:` problem line on stdout
+    pub fn with_problems(mut self, problems: bool) -> Self {
+        self.problems = problems;
+        self
+    }
+
+    /// Filter the `set trace on` output of traced scripts, or send it to a
+    /// file of its own
+    pub fn with_trace(mut self, view: Option<trace::TraceView>) -> Self {
+        self.trace = view.map(|mut view| {
+            if view.filter == trace::TraceFilter::User {
+                view.base_dir = trace::base_dir(&self.stata_binary);
+                if view.base_dir.is_none() && !self.verbosity.is_quiet() {
+                    eprintln!(
+                        "warning: Stata's ado/base not found next to {}, so --trace-filter user can't leave out its programs",
+                        self.stata_binary
+                    );
+                }
+            }
+            view
+        });
+        self
+    }
+
+    /// After a failed run, load the script's checkpoint (or the last file it
+    /// saved) in a fresh Stata and describe it
+    pub fn with_post_mortem(mut self, post_mortem: bool) -> Self {
+        self.post_mortem = post_mortem;
+        self
+    }
+
+    /// How `--trace` output is filtered, when it is
+    pub fn trace(&self) -> Option<&trace::TraceView> {
+        self.trace.as_ref()
+    }
+
+    /// Report a result taken from the build cache instead of running
+    /// `script`: with events on, its `finished` event; with problem lines
+    /// on, its errors
+    pub fn report_cached(&self, script: &Path, result: &crate::cache::CachedResult) {
+        if self.events {
+            events::emit(&events::Event::cached(script, result));
+        }
+        if self.problems {
+            problems::emit(&problems::cached_problems(script, result));
+        }
+    }
+
+    /// Stata commands the wrapper runs before the user's script
+    fn preamble(&self) -> Result<Vec<String>> {
+        let mut lines = self.reproducibility.preamble();
+        if let Some(n) = self.processors {
+            crate::cache::engine::check_processors(
+                n,
+                crate::cache::engine::licensed_processors(&self.stata_binary),
+            )?;
+            lines.push(format!("quietly set processors {}", n));
+        }
+        Ok(lines)
+    }
+
     /// Run a Stata script with optional arguments
     pub fn run_with_args(
         &self,
@@ -134,11 +295,17 @@ impl StataExecutor {
         project_root: Option<&Path>,
         args: &std::collections::HashMap<String, String>,
     ) -> Result<ExecutionResult> {
-        self.run_internal(script, project_root, args.clone(), None)
+        self.run_internal(script, project_root, args.clone(), None, None)
     }
 
     pub fn run(&self, script: &Path, project_root: Option<&Path>) -> Result<ExecutionResult> {
-        self.run_internal(script, project_root, std::collections::HashMap::new(), None)
+        self.run_internal(
+            script,
+            project_root,
+            std::collections::HashMap::new(),
+            None,
+            None,
+        )
     }
 
     /// Run a Stata script in a specific working directory
@@ -153,6 +320,28 @@ impl StataExecutor {
             project_root,
             std::collections::HashMap::new(),
             Some(working_dir),
+            None,
+        )
+    }
+
+    /// [`run_in_dir`](Self::run_in_dir) with a scratch directory of the run's
+    /// own: Stata's temporary files (`STATATMP`, `TMPDIR`), the
+    /// `$STACY_TEST_TMP` global and, in strict mode, the throwaway PLUS and
+    /// PERSONAL all live under `scratch`. Tests running side by side then
+    /// can't clobber each other's files.
+    pub fn run_isolated(
+        &self,
+        script: &Path,
+        project_root: Option<&Path>,
+        working_dir: &Path,
+        scratch: &Path,
+    ) -> Result<ExecutionResult> {
+        self.run_internal(
+            script,
+            project_root,
+            std::collections::HashMap::new(),
+            Some(working_dir),
+            Some(scratch),
         )
     }
 
@@ -162,6 +351,7 @@ impl StataExecutor {
         project_root: Option<&Path>,
         args: std::collections::HashMap<String, String>,
         working_dir: Option<&Path>,
+        scratch: Option<&Path>,
     ) -> Result<ExecutionResult> {
         use runner::{run_stata, RunOptions};
         use std::thread;
@@ -180,11 +370,68 @@ impl StataExecutor {
             None => std::env::current_dir()?,
         };
 
-        // Per-invocation wrapper + unique log path. `_paths` is bound for the
+        // Per-invocation wrapper + unique log path. `paths` is bound for the
         // full function scope so the wrapper file outlives every read of the
         // log (parse_log_for_errors, get_error_context, streaming threads).
         // See src/executor/run_paths.rs and #20 for rationale.
-        let _paths = run_paths::RunPaths::prepare(&abs_script, &effective_working_dir)?;
+        //
+        // In strict mode the wrapper first points PLUS and PERSONAL at a
+        // throwaway directory, so an `ssc install` in the script can't touch
+        // the user's global ado tree.
+        let preamble = self.preamble()?;
+        let sandbox = match scratch {
+            _ if self.allow_global => None,
+            Some(dir) => Some(sandbox::AdoSandbox::new_in(dir)?),
+            None => Some(sandbox::AdoSandbox::new()?),
+        };
+        let mut wrapper_lines = sandbox.as_ref().map(|s| s.commands()).unwrap_or_default();
+        let scratch_tmp = match scratch {
+            Some(dir) => {
+                let tmp = dir.join("tmp");
+                std::fs::create_dir_all(&tmp)?;
+                wrapper_lines.push(format!(
+                    "global STACY_TEST_TMP `\"{}\"'",
+                    run_paths::stata_path(&tmp)
+                ));
+                Some(tmp)
+            }
+            None => None,
+        };
+        wrapper_lines.extend(preamble.iter().cloned());
+        // Always defined, so a script saving to it runs the same either way
+        let checkpoint = post_mortem::Checkpoint::new()?;
+        wrapper_lines.push(checkpoint.command());
+        let paths = run_paths::RunPaths::prepare_with_preamble(
+            &abs_script,
+            &effective_working_dir,
+            &wrapper_lines,
+        )?;
+
+        // Stata doesn't open paths past Windows' MAX_PATH, whatever stacy does
+        if cfg!(target_os = "windows") {
+            for path in [&abs_script, &paths.log] {
+                if crate::utils::paths::exceeds_max_path(path) {
+                    self.verbosity.warn(
+                        &format!(
+                            "{} is longer than {} characters, which Stata may fail to open",
+                            path.display(),
+                            crate::utils::paths::MAX_PATH
+                        ),
+                        "move the project to a shorter path",
+                    );
+                }
+            }
+        }
+
+        if !self.verbosity.is_quiet() && !crate::utils::encoding::is_utf8_file(&abs_script) {
+            self.verbosity.warn(
+                &format!(
+                    "{} is not UTF-8 (read as Latin-1), so Stata 14 and later show its accented characters garbled",
+                    script.display()
+                ),
+                &crate::utils::encoding::translate_hint(&abs_script),
+            );
+        }
 
         // Build run options
         let mut options = RunOptions::new(&self.stata_binary);
@@ -205,7 +452,13 @@ impl StataExecutor {
         if let Some(timeout) = self.timeout {
             options = options.with_timeout(timeout);
         }
-        options = options.with_log_file(_paths.log.clone());
+        options = options.with_log_file(paths.log.clone());
+        options = options.with_backend(self.backend);
+        if let Some(tmp) = &scratch_tmp {
+            for key in ["STATATMP", "TMPDIR", "TMP", "TEMP"] {
+                options = options.with_env(key, tmp.clone());
+            }
+        }
 
         // Show execution details if VeryVerbose
         if self.verbosity.should_show_execution_details() {
@@ -255,25 +508,51 @@ impl StataExecutor {
             if let Some(timeout) = self.timeout {
                 eprintln!("  Timeout: {}s", timeout.as_secs());
             }
+            if !preamble.is_empty() {
+                eprintln!("  Preamble: {}", preamble.join("; "));
+            }
+            if let Some(ref sandbox) = sandbox {
+                eprintln!("  PLUS/PERSONAL: {} (discarded)", sandbox.path().display());
+            }
             // The runner spawns Stata against a wrapper that delegates to the
             // user's script; show both so `-vv` reflects what actually runs.
-            eprintln!("  Command: stata-mp -b -q do {}", _paths.wrapper.display());
+            match self.backend {
+                runner::Backend::Batch => eprintln!(
+                    "  Command: {} {} {}",
+                    self.stata_binary,
+                    binary::BatchStyle::for_binary(&self.stata_binary)
+                        .args()
+                        .join(" "),
+                    paths.wrapper.display()
+                ),
+                runner::Backend::Console => eprintln!(
+                    "  Command: {} -q (console), typing: do {}",
+                    self.stata_binary,
+                    paths.wrapper.display()
+                ),
+            }
             eprintln!("  Wraps: {}", abs_script.display());
             eprintln!();
         }
 
         // Log file path was computed by RunPaths above; reuse it for streaming.
-        let log_file = _paths.log.clone();
+        let log_file = paths.log.clone();
 
         // Start log streaming thread if verbose or interactive. The thread
         // terminates when `stop` is set after the Stata process exits — the
         // log alone can't signal completion (a killed Stata writes no
         // trailer, and scripts can print marker-lookalike output).
-        let stream_mode = if self.verbosity.should_stream_raw() {
-            // Print header to separate stacy output from Stata log
-            eprintln!("─────────────────────────────────────────────────────────────");
-            eprintln!("Stata log ({})", log_file.display());
-            eprintln!("─────────────────────────────────────────────────────────────");
+        let stream_mode = if self.events {
+            events::emit(&events::Event::Started { script });
+            Some(log_reader::StreamMode::Raw)
+        } else if self.verbosity.should_stream_raw() {
+            // Print header to separate stacy output from Stata log. Labeled
+            // lines need none, and the header would repeat for every script.
+            if self.stream_label.is_none() {
+                eprintln!("─────────────────────────────────────────────────────────────");
+                eprintln!("Stata log ({})", log_file.display());
+                eprintln!("─────────────────────────────────────────────────────────────");
+            }
             Some(log_reader::StreamMode::Raw)
         } else if self.verbosity.should_stream_clean() {
             Some(log_reader::StreamMode::Clean)
@@ -286,8 +565,22 @@ impl StataExecutor {
             let log_path = log_file.clone();
             let poll_interval = self.progress_interval;
             let stop = std::sync::Arc::clone(&stop);
+            let label = self.stream_label.clone();
+            let chunks = self.events.then(|| events::LogChunks::new(script));
+            let trace = self.trace.clone();
             thread::spawn(move || {
-                let _ = log_reader::stream_log(&log_path, poll_interval, mode, &stop);
+                let mut out: Box<dyn std::io::Write> = match (chunks, label) {
+                    (Some(chunks), _) => Box::new(chunks),
+                    (None, Some(label)) => {
+                        Box::new(log_reader::PrefixedLines::new(label, std::io::stdout()))
+                    }
+                    (None, None) => Box::new(std::io::stdout()),
+                };
+                if let Some(view) = trace {
+                    out = Box::new(trace::TraceWriter::new(view, out));
+                }
+                let mut out = redact::RedactingWriter::new(out);
+                let _ = log_reader::stream_log_to(&log_path, poll_interval, mode, &stop, &mut out);
             })
         });
 
@@ -296,7 +589,7 @@ impl StataExecutor {
         // the wrapper has a unique stem so concurrent runs cannot collide.
         // Don't propagate a spawn error until the streamer is released, or
         // its thread spins unjoined forever.
-        let run_result = run_stata(&_paths.wrapper, options);
+        let run_result = run_stata(&paths.wrapper, options);
 
         // Stata is done (or never started) — release and join the streamer.
         stop.store(true, std::sync::atomic::Ordering::Release);
@@ -304,7 +597,18 @@ impl StataExecutor {
             let _ = handle.join();
         }
 
-        let run_result = run_result?;
+        let mut run_result = run_result?;
+
+        // Mask secrets before anything reads the log (see redact.rs)
+        let redactor = redact::current();
+        if let Err(e) = redactor.redact_file(&run_result.log_file) {
+            eprintln!(
+                "warning: could not mask secrets in {}: {}",
+                run_result.log_file.display(),
+                e
+            );
+        }
+        run_result.stderr = redactor.redact(&run_result.stderr).into_owned();
 
         // Parse log file for errors (with timing).
         //
@@ -323,6 +627,66 @@ impl StataExecutor {
         };
         let parse_duration = parse_start.elapsed();
 
+        let discarded_installs = sandbox.map(|s| s.installed()).unwrap_or_default();
+        if !discarded_installs.is_empty() {
+            self.verbosity.warn(
+                &format!(
+                    "{} installed {} into a throwaway PLUS directory, discarded after the run",
+                    script.display(),
+                    discarded_installs.join(", ")
+                ),
+                &format!(
+                    "declare them with `stacy add {}`",
+                    discarded_installs.join(" ")
+                ),
+            );
+        }
+
+        let log_text = log_reader::read_full_log(&run_result.log_file).ok();
+        let adopath_changes = log_text
+            .as_deref()
+            .map(log_reader::adopath_changes)
+            .unwrap_or_default();
+        if let (Some(path), Some(view), Some(log)) = (
+            self.trace.as_ref().and_then(|v| v.output.as_ref()),
+            &self.trace,
+            &log_text,
+        ) {
+            // Appended: a sequential run traces one script after another
+            let written = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .and_then(|mut file| {
+                    std::io::Write::write_all(&mut file, trace::split(log, view).1.as_bytes())
+                });
+            if let Err(e) = written {
+                eprintln!(
+                    "warning: could not write the trace to {}: {}",
+                    path.display(),
+                    e
+                );
+            }
+        }
+        let error_trace = match (errors.first(), &log_text) {
+            (Some(StataError::StataCode { .. }), Some(log)) => {
+                crate::error::location::trace(script, log, &effective_working_dir)
+            }
+            _ => Vec::new(),
+        };
+        if !adopath_changes.is_empty() && !self.verbosity.is_quiet() {
+            eprintln!(
+                "warning: {} changed the adopath, so it may have run commands stacy didn't lock:",
+                script.display()
+            );
+            for command in &adopath_changes {
+                eprintln!("  {}", command);
+            }
+            eprintln!(
+                "  hint: use `stacy add` for packages and [paths] ado in stacy.toml for project .ado files"
+            );
+        }
+
         // Determine success and exit code
         let success = errors.is_empty();
         let exit_code = if success {
@@ -330,6 +694,12 @@ impl StataExecutor {
         } else {
             // Map first error to exit code
             let exit_code = crate::error::mapper::error_to_exit_code(&errors[0]);
+            tracing::debug!(
+                errors = errors.len(),
+                r_code = errors[0].r_code(),
+                exit_code,
+                "errors found in the log"
+            );
 
             // Show error context if Default verbosity and error occurred
             if self.verbosity.should_show_error_context() {
@@ -341,6 +711,60 @@ impl StataExecutor {
             exit_code
         };
 
+        let regression = success
+            .then(|| crate::history::regression::check(script, run_result.duration))
+            .flatten();
+        if let Some(regression) = &regression {
+            if self.events {
+                events::emit(&events::Event::Regression {
+                    script,
+                    duration_secs: regression.duration_secs,
+                    baseline_secs: regression.baseline_secs,
+                    baseline_runs: regression.baseline_runs,
+                    slower_percent: regression.slower_percent(),
+                });
+            } else if self.problems {
+                problems::emit(&[problems::format_warning(script, &regression.describe())]);
+            } else if !self.verbosity.is_quiet() {
+                eprintln!("warning: {} {}", script.display(), regression.describe());
+            }
+        }
+
+        if self.events {
+            for error in &errors {
+                events::emit(&events::Event::error_detected(script, error, &error_trace));
+            }
+            events::emit(&events::Event::Finished {
+                script,
+                success,
+                exit_code,
+                duration_secs: run_result.duration.as_secs_f64(),
+                error_count: errors.len(),
+                cached: false,
+            });
+        }
+        if self.problems {
+            problems::emit(&problems::problems(script, error_trace.last(), &errors));
+        }
+        crate::metrics::counters::note_script(success, run_result.duration);
+        crate::history::note_run(
+            script,
+            &run_result.log_file,
+            success,
+            exit_code,
+            run_result.duration,
+            errors.first(),
+        );
+
+        let post_mortem = match (&log_text, errors.first()) {
+            (Some(log), Some(StataError::StataCode { .. })) if self.post_mortem => {
+                post_mortem::dataset(checkpoint.path(), log, &effective_working_dir).and_then(
+                    |(dataset, is_checkpoint)| post_mortem::capture(self, &dataset, is_checkpoint),
+                )
+            }
+            _ => None,
+        };
+
         Ok(ExecutionResult {
             exit_code,
             log_file: run_result.log_file,
@@ -349,6 +773,11 @@ impl StataExecutor {
             success,
             parse_duration,
             metrics: None, // Metrics collection happens in CLI layer
+            preamble,
+            discarded_installs,
+            adopath_changes,
+            error_trace,
+            post_mortem,
         })
     }
 }
@@ -0,0 +1,168 @@
+//! Opening a failed run's log (`stacy run --open-log`)
+//!
+//! After a failure the next step is nearly always to open the kept log and
+//! jump to the error. `--open-log` (or `[ui] open_log_on_failure` in the user
+//! config) does that: in `$VISUAL`/`$EDITOR` at the line with Stata's `r()`
+//! code, or in `less` at that line. Only in a terminal: a run in CI or piped
+//! into another program never waits on an editor.
+
+use crate::error::parser::parse_log_file;
+use crate::error::StataError;
+use serde::{Deserialize, Serialize};
+use std::ffi::OsString;
+use std::io::IsTerminal;
+use std::path::Path;
+use std::process::Command;
+
+/// Where a failed run's log is opened
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LogViewer {
+    /// `$VISUAL` or `$EDITOR`, else the pager
+    Editor,
+    /// `less`, at the error line
+    Pager,
+}
+
+impl std::fmt::Display for LogViewer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LogViewer::Editor => write!(f, "editor"),
+            LogViewer::Pager => write!(f, "pager"),
+        }
+    }
+}
+
+/// Open `log` in `viewer` at the line of its `r()` code and wait for the
+/// viewer to close. Does nothing outside a terminal or when the log is gone.
+pub fn open(log: &Path, viewer: LogViewer) {
+    if log.as_os_str().is_empty()
+        || !log.is_file()
+        || !std::io::stdin().is_terminal()
+        || !std::io::stderr().is_terminal()
+    {
+        return;
+    }
+    let line = error_line(log);
+    let editor = std::env::var("VISUAL")
+        .ok()
+        .or_else(|| std::env::var("EDITOR").ok());
+    let (program, args) = viewer_command(viewer, log, line, editor.as_deref());
+
+    if let Err(e) = Command::new(&program).args(&args).status() {
+        eprintln!(
+            "\x1b[33mwarning\x1b[0m: could not open the log with {}: {}",
+            program.to_string_lossy(),
+            e
+        );
+    }
+}
+
+/// Line of the log with the `r()` code of the failure
+fn error_line(log: &Path) -> Option<usize> {
+    parse_log_file(log)
+        .ok()?
+        .into_iter()
+        .find_map(|error| match error {
+            StataError::StataCode { line_number, .. } => line_number,
+            StataError::ProcessKilled { .. } => None,
+        })
+}
+
+/// Program and arguments that show `log` at `line`. `editor` is the command
+/// in `$VISUAL`/`$EDITOR`, which may carry arguments of its own
+/// (`code --wait`). Editors take `+N`, except those that take `file:N`.
+fn viewer_command(
+    viewer: LogViewer,
+    log: &Path,
+    line: Option<usize>,
+    editor: Option<&str>,
+) -> (OsString, Vec<OsString>) {
+    let mut words = editor
+        .filter(|_| viewer == LogViewer::Editor)
+        .map(|e| e.split_whitespace().map(OsString::from).collect::<Vec<_>>())
+        .unwrap_or_default();
+    if words.is_empty() {
+        let jump = match line {
+            Some(n) => format!("+{}G", n),
+            None => "+G".to_string(),
+        };
+        return ("less".into(), vec![jump.into(), log.into()]);
+    }
+
+    let program = words.remove(0);
+    let name = Path::new(&program)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let mut args = words;
+    match (line, name.as_str()) {
+        (Some(n), "code" | "codium" | "cursor") => {
+            args.push("--goto".into());
+            args.push(format!("{}:{}", log.display(), n).into());
+        }
+        (Some(n), "subl" | "zed") => args.push(format!("{}:{}", log.display(), n).into()),
+        (Some(n), _) => {
+            args.push(format!("+{}", n).into());
+            args.push(log.into());
+        }
+        (None, _) => args.push(log.into()),
+    }
+    (program, args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(command: (OsString, Vec<OsString>)) -> Vec<String> {
+        std::iter::once(command.0)
+            .chain(command.1)
+            .map(|s| s.to_string_lossy().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_viewer_command() {
+        let log = Path::new("logs/clean.log");
+        assert_eq!(
+            strings(viewer_command(
+                LogViewer::Editor,
+                log,
+                Some(42),
+                Some("vim")
+            )),
+            ["vim", "+42", "logs/clean.log"]
+        );
+        assert_eq!(
+            strings(viewer_command(
+                LogViewer::Editor,
+                log,
+                Some(42),
+                Some("code --wait")
+            )),
+            ["code", "--wait", "--goto", "logs/clean.log:42"]
+        );
+        // No editor set, or the pager asked for
+        assert_eq!(
+            strings(viewer_command(LogViewer::Editor, log, Some(42), None)),
+            ["less", "+42G", "logs/clean.log"]
+        );
+        assert_eq!(
+            strings(viewer_command(LogViewer::Pager, log, None, Some("vim"))),
+            ["less", "+G", "logs/clean.log"]
+        );
+    }
+
+    #[test]
+    fn test_error_line() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let log = temp.path().join("run.log");
+        std::fs::write(
+            &log,
+            ". use missing\nfile missing.dta not found\n\nend of do-file\nr(601);\n",
+        )
+        .unwrap();
+        assert_eq!(error_line(&log), Some(5));
+    }
+}
@@ -0,0 +1,163 @@
+//! State of a failed run (`stacy run --post-mortem`)
+//!
+//! A batch Stata that fails exits and takes its data with it. With
+//! `--post-mortem`, stacy starts a fresh Stata after the failure, loads the
+//! last dataset the script left behind and `describe`s it, and attaches what
+//! that prints to the failure report. The dataset is the checkpoint, when the
+//! script saved one to the path stacy puts in `$STACY_CHECKPOINT`:
+//!
+//! ```stata
+//! if "$STACY_CHECKPOINT" != "" save "$STACY_CHECKPOINT", replace
+//! ```
+//!
+//! else the last file the log shows the script saving, if it is still there.
+
+use super::run_paths::stata_path;
+use super::{log_reader, verbosity::Verbosity, StataExecutor};
+use crate::error::Result;
+use crate::utils::temp::TempScript;
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use tempfile::TempDir;
+
+/// Global holding the checkpoint path in every run
+pub const CHECKPOINT_GLOBAL: &str = "STACY_CHECKPOINT";
+
+/// What Stata prints after `save`: `file data/clean.dta saved`, or `saved as
+/// .dta format` for a tempfile
+static SAVED: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\(?file (.+?) saved(?: as .+)?\)?\s*$").unwrap());
+
+/// Where a run's script can save its data for the post-mortem, in a directory
+/// that lives as long as the run
+pub struct Checkpoint {
+    _dir: TempDir,
+    path: PathBuf,
+}
+
+impl Checkpoint {
+    pub fn new() -> Result<Self> {
+        let dir = TempDir::with_prefix("stacy-checkpoint-")?;
+        let path = dir.path().join("checkpoint.dta");
+        Ok(Self { _dir: dir, path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The wrapper line defining `$STACY_CHECKPOINT`
+    pub fn command(&self) -> String {
+        format!(
+            "global {} `\"{}\"'",
+            CHECKPOINT_GLOBAL,
+            stata_path(&self.path)
+        )
+    }
+}
+
+/// The dataset of a failed run and what `describe` says about it
+#[derive(Debug, Clone)]
+pub struct PostMortem {
+    /// The checkpoint, or the file the script last saved
+    pub dataset: PathBuf,
+    /// Whether `dataset` is the checkpoint
+    pub checkpoint: bool,
+    /// Output of the epilogue
+    pub output: String,
+}
+
+/// The dataset to look at after a failure: the checkpoint, else the last
+/// file the log shows being saved that still exists
+pub fn dataset(checkpoint: &Path, log: &str, working_dir: &Path) -> Option<(PathBuf, bool)> {
+    if checkpoint.is_file() {
+        return Some((checkpoint.to_path_buf(), true));
+    }
+    log.lines()
+        .rev()
+        .filter_map(|line| SAVED.captures(line.trim()))
+        .map(|c| working_dir.join(c[1].trim_matches('"')))
+        .find(|path| path.is_file())
+        .map(|path| (path, false))
+}
+
+/// Load `dataset` in a fresh Stata and describe it. `None` when Stata
+/// couldn't be run at all.
+pub fn capture(executor: &StataExecutor, dataset: &Path, checkpoint: bool) -> Option<PostMortem> {
+    let dir = TempDir::with_prefix("stacy-post-mortem-").ok()?;
+    let script = TempScript::new(&epilogue(dataset), dir.path()).ok()?;
+    let epilogue_run = executor.for_epilogue();
+    let result = epilogue_run
+        .run_in_dir(script.path(), None, dir.path())
+        .ok()?;
+    let log = log_reader::read_full_log(&result.log_file).ok()?;
+    Some(PostMortem {
+        dataset: dataset.to_path_buf(),
+        checkpoint,
+        output: log_reader::strip_boilerplate(&log),
+    })
+}
+
+/// Stata code of the post-mortem
+fn epilogue(dataset: &Path) -> String {
+    format!("use `\"{}\"', clear\ndescribe\n", stata_path(dataset))
+}
+
+impl StataExecutor {
+    /// This executor, reporting nothing of its own: for the epilogue run
+    fn for_epilogue(&self) -> Self {
+        let mut executor = self.clone().with_verbosity(Verbosity::Quiet);
+        executor.events = false;
+        executor.problems = false;
+        executor.trace = None;
+        executor.post_mortem = false;
+        executor.stream_label = None;
+        executor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dataset_prefers_checkpoint() {
+        let temp = TempDir::new().unwrap();
+        std::fs::create_dir(temp.path().join("data")).unwrap();
+        std::fs::write(temp.path().join("data/clean.dta"), "").unwrap();
+        let log = "\
+. save data/clean.dta, replace
+file data/clean.dta saved
+
+. save \"data/gone.dta\"
+file data/gone.dta saved
+
+. merge 1:1 id using data/other
+file data/other.dta not found
+r(601);
+";
+        let checkpoint = temp.path().join("checkpoint.dta");
+        // gone.dta was saved last but isn't there any more
+        assert_eq!(
+            dataset(&checkpoint, log, temp.path()),
+            Some((temp.path().join("data/clean.dta"), false))
+        );
+
+        std::fs::write(&checkpoint, "").unwrap();
+        assert_eq!(
+            dataset(&checkpoint, log, temp.path()),
+            Some((checkpoint.clone(), true))
+        );
+        assert_eq!(dataset(&temp.path().join("x.dta"), "", temp.path()), None);
+    }
+
+    #[test]
+    fn test_checkpoint_command() {
+        let checkpoint = Checkpoint::new().unwrap();
+        let command = checkpoint.command();
+        assert!(command.starts_with("global STACY_CHECKPOINT `\""));
+        assert!(command.ends_with("checkpoint.dta\"'"));
+        assert!(!checkpoint.path().exists());
+    }
+}
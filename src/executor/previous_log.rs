@@ -0,0 +1,233 @@
+//! The last successful log of each script (`stacy run --diff-previous`)
+//!
+//! When a script succeeds inside a project, its cleaned log is kept in
+//! `.stacy/logs/previous/`, replacing the one from the run before. When a later
+//! run of the same script fails, `--diff-previous` diffs the end of the failed
+//! log against it: after a red pipeline the first question is what changed
+//! since it last passed.
+
+use crate::error::Result;
+use crate::executor::log_reader::{read_full_log, strip_boilerplate};
+use crate::utils::paths;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+/// Lines at the end of the failed log the diff covers: the failure and what
+/// led up to it
+pub const DIFF_WINDOW: usize = 40;
+
+/// Unchanged lines shown around each change
+const CONTEXT: usize = 3;
+
+/// Above this many line pairs left after trimming the common start and end,
+/// the logs are shown as replaced rather than aligned line by line
+const MAX_ALIGN: usize = 4_000_000;
+
+/// Where the last successful log of `script` is kept. Scripts are told apart
+/// by their path, so two `clean.do` in different directories don't collide.
+pub fn path_for(project_root: &Path, script: &Path) -> PathBuf {
+    let script = paths::canonicalize_or_self(script);
+    let root = paths::canonicalize_or_self(project_root);
+    let relative = script.strip_prefix(&root).unwrap_or(&script);
+    let key = hex::encode(Sha256::digest(relative.to_string_lossy().as_bytes()));
+    let stem = script
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    project_root
+        .join(".stacy")
+        .join("logs")
+        .join("previous")
+        .join(format!("{}-{}.log", stem, &key[..8]))
+}
+
+/// Keep the cleaned content of a successful run's log
+pub fn save(project_root: &Path, script: &Path, log: &Path) -> Result<()> {
+    let clean = strip_boilerplate(&read_full_log(log)?);
+    let path = path_for(project_root, script);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, clean)?;
+    Ok(())
+}
+
+/// The last successful log of `script`, if there is one
+pub fn load(project_root: &Path, script: &Path) -> Option<String> {
+    std::fs::read_to_string(path_for(project_root, script)).ok()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    Same,
+    Removed,
+    Added,
+}
+
+/// Line edits turning `old` into `new`, each with its position in both
+fn edits(old: &[&str], new: &[&str]) -> Vec<(Edit, usize, usize)> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let a = &old[prefix..old.len() - suffix];
+    let b = &new[prefix..new.len() - suffix];
+
+    let mut out: Vec<(Edit, usize, usize)> = (0..prefix).map(|i| (Edit::Same, i, i)).collect();
+    if a.len() * b.len() > MAX_ALIGN {
+        out.extend((0..a.len()).map(|i| (Edit::Removed, prefix + i, prefix)));
+        out.extend((0..b.len()).map(|j| (Edit::Added, prefix + a.len(), prefix + j)));
+    } else {
+        // Longest common subsequence, filled from the end
+        let mut lcs = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+        for i in (0..a.len()).rev() {
+            for j in (0..b.len()).rev() {
+                lcs[i][j] = if a[i] == b[j] {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    lcs[i + 1][j].max(lcs[i][j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() || j < b.len() {
+            if i < a.len() && j < b.len() && a[i] == b[j] {
+                out.push((Edit::Same, prefix + i, prefix + j));
+                i += 1;
+                j += 1;
+            } else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+                // Removals first on a tie, as diff(1) prints them
+                out.push((Edit::Removed, prefix + i, prefix + j));
+                i += 1;
+            } else {
+                out.push((Edit::Added, prefix + i, prefix + j));
+                j += 1;
+            }
+        }
+    }
+    let (old_end, new_end) = (old.len() - suffix, new.len() - suffix);
+    out.extend((0..suffix).map(|k| (Edit::Same, old_end + k, new_end + k)));
+    out
+}
+
+/// Unified diff of the last `window` lines of `current` against `previous`,
+/// or `None` when nothing in that stretch changed
+pub fn diff_tail(previous: &str, current: &str, window: usize) -> Option<String> {
+    unified_diff(
+        previous,
+        current,
+        ("last successful run", "this run"),
+        window,
+    )
+}
+
+/// Unified diff of `current` against `previous` under the given (old, new)
+/// labels, covering changes in the last `window` lines of `current`; `None`
+/// when nothing there changed
+pub fn unified_diff(
+    previous: &str,
+    current: &str,
+    labels: (&str, &str),
+    window: usize,
+) -> Option<String> {
+    let old: Vec<&str> = previous.lines().collect();
+    let new: Vec<&str> = current.lines().collect();
+    let edits = edits(&old, &new);
+    let window_start = new.len().saturating_sub(window);
+
+    // Changes in the window, and the unchanged lines around them
+    let mut keep = vec![false; edits.len()];
+    for (k, (edit, _, j)) in edits.iter().enumerate() {
+        if *edit != Edit::Same && *j >= window_start {
+            let from = k.saturating_sub(CONTEXT);
+            let to = (k + CONTEXT + 1).min(edits.len());
+            keep[from..to].iter_mut().for_each(|kept| *kept = true);
+        }
+    }
+    if !keep.iter().any(|&kept| kept) {
+        return None;
+    }
+
+    let mut out = format!("--- {}\n+++ {}\n", labels.0, labels.1);
+    let mut k = 0;
+    while k < edits.len() {
+        if !keep[k] {
+            k += 1;
+            continue;
+        }
+        let end = (k..edits.len()).find(|&e| !keep[e]).unwrap_or(edits.len());
+        let hunk = &edits[k..end];
+        let old_len = hunk.iter().filter(|(e, _, _)| *e != Edit::Added).count();
+        let new_len = hunk.iter().filter(|(e, _, _)| *e != Edit::Removed).count();
+        // An empty range starts at the line before it, as in diff(1)
+        let start = |pos: usize, len: usize| if len == 0 { pos } else { pos + 1 };
+        out.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            start(hunk[0].1, old_len),
+            old_len,
+            start(hunk[0].2, new_len),
+            new_len
+        ));
+        for &(edit, i, j) in hunk {
+            let (mark, line) = match edit {
+                Edit::Same => (' ', new[j]),
+                Edit::Removed => ('-', old[i]),
+                Edit::Added => ('+', new[j]),
+            };
+            out.push(mark);
+            out.push_str(line);
+            out.push('\n');
+        }
+        k = end;
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_tail_shows_change_before_failure() {
+        let previous = "obs: 74\nmean 6165\nsaved table\n";
+        let current = "obs: 72\nmean 6165\nvariable price not found\nr(111);\n";
+        let diff = diff_tail(previous, current, DIFF_WINDOW).unwrap();
+        assert_eq!(
+            diff,
+            "--- last successful run\n+++ this run\n\
+             @@ -1,3 +1,4 @@\n\
+             -obs: 74\n\
+             +obs: 72\n\
+             \x20mean 6165\n\
+             -saved table\n\
+             +variable price not found\n\
+             +r(111);\n"
+        );
+    }
+
+    #[test]
+    fn test_diff_tail_ignores_changes_before_window() {
+        let mut previous: Vec<String> = (0..100).map(|i| format!("line {}", i)).collect();
+        let current = previous.join("\n");
+        previous[0] = "changed".to_string();
+        assert!(diff_tail(&previous.join("\n"), &current, 10).is_none());
+        assert!(diff_tail(&previous.join("\n"), &current, 100).is_some());
+    }
+
+    #[test]
+    fn test_path_for_tells_scripts_apart_by_path() {
+        let root = Path::new("/project");
+        let a = path_for(root, Path::new("/project/a/clean.do"));
+        let b = path_for(root, Path::new("/project/b/clean.do"));
+        assert_ne!(a, b);
+        assert!(a.starts_with("/project/.stacy/logs/previous"));
+        assert!(a
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with("clean-"));
+    }
+}
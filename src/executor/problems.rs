@@ -0,0 +1,111 @@
+//! Problem-matcher lines (`--format vscode`)
+//!
+//! With `--format vscode` every error is written to stdout as one line in
+//! the `file:line: message` form compilers use, which VS Code problem
+//! matchers, Vim's quickfix list and Emacs' compilation mode all read:
+//!
+//! ```text
+//! analysis/clean.do:12: error r(111): variable price not found
+//! analysis/clean.do: error: Process killed (exit code 137)
+//! ```
+//!
+//! Stata reports an error against its log, not the script. The location is
+//! the do-file and line of the command that failed, following `do` and
+//! `include` into nested do-files (see [`crate::error::location`]). When no
+//! echoed command is in a do-file stacy can read, the line is left out.
+//! Multi-line messages are joined into one.
+
+use crate::cache::CachedResult;
+use crate::error::location::SourceLocation;
+use crate::error::StataError;
+use std::io::Write;
+use std::path::Path;
+
+/// One problem line
+pub fn format_problem(
+    script: &Path,
+    line: Option<usize>,
+    r_code: Option<u32>,
+    message: &str,
+) -> String {
+    let location = match line {
+        Some(line) => format!("{}:{}", script.display(), line),
+        None => script.display().to_string(),
+    };
+    let kind = match r_code {
+        Some(code) => format!("error r({})", code),
+        None => "error".to_string(),
+    };
+    let message = message.split_whitespace().collect::<Vec<_>>().join(" ");
+    format!("{}: {}: {}", location, kind, message)
+}
+
+/// The problem lines for the errors of a run of `script`, the first of which
+/// happened at `location`
+pub fn problems(
+    script: &Path,
+    location: Option<&SourceLocation>,
+    errors: &[StataError],
+) -> Vec<String> {
+    let (file, line) = match location {
+        Some(location) => (location.file.as_path(), Some(location.line)),
+        None => (script, None),
+    };
+    errors
+        .iter()
+        .map(|error| match error {
+            StataError::StataCode {
+                r_code, message, ..
+            } => format_problem(file, line, Some(*r_code), message),
+            StataError::ProcessKilled { exit_code } => format_problem(
+                script,
+                None,
+                None,
+                &format!("Process killed (exit code {})", exit_code),
+            ),
+        })
+        .collect()
+}
+
+/// A warning line (`analysis/clean.do: warning: took 12.3s, ...`)
+pub fn format_warning(script: &Path, message: &str) -> String {
+    format!("{}: warning: {}", script.display(), message)
+}
+
+/// The problem lines of a result taken from the build cache. Its log is
+/// gone, so there are no line numbers.
+pub fn cached_problems(script: &Path, result: &CachedResult) -> Vec<String> {
+    result
+        .errors
+        .iter()
+        .map(|error| format_problem(script, None, error.r_code, &error.message))
+        .collect()
+}
+
+/// Write problem lines to stdout. A closed stdout is ignored, as for
+/// streamed logs.
+pub fn emit(lines: &[String]) {
+    let mut out = std::io::stdout().lock();
+    for line in lines {
+        let _ = writeln!(out, "{}", line);
+    }
+    let _ = out.flush();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_problem() {
+        let script = Path::new("analysis/clean.do");
+        assert_eq!(
+            format_problem(script, Some(12), Some(111), "variable price\nnot found"),
+            "analysis/clean.do:12: error r(111): variable price not found"
+        );
+        assert_eq!(
+            format_problem(script, None, None, "Process killed (exit code 137)"),
+            "analysis/clean.do: error: Process killed (exit code 137)"
+        );
+    }
+}
@@ -1,4 +1,132 @@
-//! Progress indicator
+//! Progress display
 //!
-//! Reserved for progress bar support using indicatif.
-//! Will show progress for long-running Stata jobs.
+//! Shows work that runs several items at once, such as package downloads.
+//! On a terminal every running item gets a spinner above an overall bar;
+//! elsewhere (CI logs, pipes) each finished item prints one compact
+//! `[done/total]` line to stderr. Nothing is drawn for machine-readable
+//! output formats.
+
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// Spinner redraw interval
+const TICK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Progress of a fixed number of items processed concurrently
+pub struct MultiProgressDisplay {
+    mode: Mode,
+    total: usize,
+    done: AtomicUsize,
+}
+
+enum Mode {
+    /// Interactive terminal: live spinners and an overall bar
+    Bars {
+        multi: MultiProgress,
+        overall: ProgressBar,
+    },
+    /// Not a terminal: one line per finished item
+    Lines,
+    /// Machine-readable output: draw nothing
+    Hidden,
+}
+
+/// One item in flight, returned by [`MultiProgressDisplay::start`]
+pub struct ItemProgress {
+    label: String,
+    spinner: Option<ProgressBar>,
+}
+
+impl MultiProgressDisplay {
+    /// Progress for `total` items, labelled `action` ("Downloading").
+    /// With `visible` false nothing is drawn.
+    pub fn new(total: usize, action: &str, visible: bool) -> Self {
+        let mode = if !visible {
+            Mode::Hidden
+        } else if std::io::stderr().is_terminal() {
+            let multi = MultiProgress::with_draw_target(ProgressDrawTarget::stderr());
+            let overall = multi.add(ProgressBar::new(total as u64));
+            overall.set_style(
+                ProgressStyle::with_template("{prefix:>12.cyan.bold} [{bar:30}] {pos}/{len}")
+                    .expect("valid progress template")
+                    .progress_chars("=> "),
+            );
+            overall.set_prefix(action.to_string());
+            Mode::Bars { multi, overall }
+        } else {
+            Mode::Lines
+        };
+
+        Self {
+            mode,
+            total,
+            done: AtomicUsize::new(0),
+        }
+    }
+
+    /// Show `label` as running until it is passed to [`Self::finish`]
+    pub fn start(&self, label: &str) -> ItemProgress {
+        let spinner = match &self.mode {
+            Mode::Bars { multi, overall } => {
+                let spinner = multi.insert_before(overall, ProgressBar::new_spinner());
+                spinner.set_style(
+                    ProgressStyle::with_template("  {spinner:.cyan} {msg}")
+                        .expect("valid progress template"),
+                );
+                spinner.set_message(label.to_string());
+                spinner.enable_steady_tick(TICK_INTERVAL);
+                Some(spinner)
+            }
+            Mode::Lines | Mode::Hidden => None,
+        };
+
+        ItemProgress {
+            label: label.to_string(),
+            spinner,
+        }
+    }
+
+    /// Mark an item done, with a one-word `outcome` ("installed", "failed")
+    pub fn finish(&self, item: ItemProgress, outcome: &str) {
+        let done = self.done.fetch_add(1, Ordering::SeqCst) + 1;
+        match &self.mode {
+            Mode::Bars { multi, overall } => {
+                if let Some(spinner) = item.spinner {
+                    spinner.finish_and_clear();
+                    multi.remove(&spinner);
+                }
+                overall.inc(1);
+            }
+            Mode::Lines => {
+                eprintln!("  [{}/{}] {} {}", done, self.total, item.label, outcome);
+            }
+            Mode::Hidden => {}
+        }
+    }
+
+    /// Remove the display, leaving the terminal as it was
+    pub fn clear(self) {
+        if let Mode::Bars { multi, overall } = self.mode {
+            overall.finish_and_clear();
+            let _ = multi.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hidden_display_counts_items() {
+        let progress = MultiProgressDisplay::new(2, "Downloading", false);
+        let a = progress.start("a 1.0");
+        let b = progress.start("b 2.0");
+        progress.finish(b, "installed");
+        progress.finish(a, "installed");
+        assert_eq!(progress.done.load(Ordering::SeqCst), 2);
+        progress.clear();
+    }
+}
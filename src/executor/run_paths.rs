@@ -45,6 +45,16 @@ impl RunPaths {
     /// absolute (its existence is the caller's responsibility — Stata's
     /// spawn would fail anyway).
     pub fn prepare(user_script: &Path, working_dir: &Path) -> Result<Self> {
+        Self::prepare_with_preamble(user_script, working_dir, &[])
+    }
+
+    /// Like [`RunPaths::prepare`], with Stata commands the wrapper runs
+    /// before handing over to the user's script (e.g. `set processors 4`).
+    pub fn prepare_with_preamble(
+        user_script: &Path,
+        working_dir: &Path,
+        preamble: &[String],
+    ) -> Result<Self> {
         debug_assert!(
             user_script.is_absolute(),
             "RunPaths::prepare: user_script must be absolute, got {}",
@@ -74,7 +84,12 @@ impl RunPaths {
 
         // Stata compound double-quotes (`"..."') tolerate spaces and embedded
         // single/double quotes inside the absolute path.
-        let body = format!("do `\"{}\"'\n", user_script.display());
+        let mut body = String::new();
+        for line in preamble {
+            body.push_str(line);
+            body.push('\n');
+        }
+        body.push_str(&format!("do `\"{}\"'\n", stata_path(user_script)));
 
         let mut f = File::create(&wrapper)?;
         f.write_all(body.as_bytes())?;
@@ -88,6 +103,25 @@ impl RunPaths {
     }
 }
 
+/// Render a path for use inside Stata code.
+///
+/// Strips the `\\?\` verbatim prefix `canonicalize` adds on Windows, which
+/// Stata can't open, and writes Windows separators as `/`: Stata reads a
+/// backslash before `` ` `` or `$` as an escape, so `C:\data\$raw` would
+/// otherwise expand a global. Unix paths pass through unchanged.
+pub fn stata_path(path: &Path) -> String {
+    let path = path.display().to_string();
+    if cfg!(target_os = "windows") {
+        windows_stata_path(&path)
+    } else {
+        path
+    }
+}
+
+fn windows_stata_path(path: &str) -> String {
+    crate::utils::paths::strip_verbatim_str(path).replace('\\', "/")
+}
+
 /// Build a unique stem for the wrapper/log filenames.
 ///
 /// Format: `<sanitized_original>_<pid>_<nanos>_<counter>`. The original stem
@@ -151,7 +185,7 @@ mod tests {
         // Wrapper file exists and contains a single `do` line referencing the abs script.
         let body = fs::read_to_string(&paths.wrapper).unwrap();
         assert!(
-            body.contains(&format!("do `\"{}\"'", script.display())),
+            body.contains(&format!("do `\"{}\"'", stata_path(&script))),
             "wrapper body unexpected: {:?}",
             body
         );
@@ -171,6 +205,25 @@ mod tests {
         assert_eq!(log_stem, wrapper_stem);
     }
 
+    #[test]
+    fn test_prepare_with_preamble_runs_it_before_script() {
+        let temp = TempDir::new().unwrap();
+        let script = temp.path().join("build.do");
+        fs::write(&script, "display 1\n").unwrap();
+
+        let preamble = vec!["quietly set processors 2".to_string()];
+        let paths = RunPaths::prepare_with_preamble(&script, temp.path(), &preamble).unwrap();
+
+        let body = fs::read_to_string(&paths.wrapper).unwrap();
+        assert_eq!(
+            body,
+            format!(
+                "quietly set processors 2\ndo `\"{}\"'\n",
+                stata_path(&script)
+            )
+        );
+    }
+
     #[test]
     fn test_prepare_log_path_in_working_dir_not_tempdir() {
         let working = TempDir::new().unwrap();
@@ -210,6 +263,19 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_windows_stata_path() {
+        assert_eq!(
+            windows_stata_path(r"\\?\C:\Users\me\$proj\build.do"),
+            "C:/Users/me/$proj/build.do"
+        );
+        assert_eq!(
+            windows_stata_path(r"\\?\UNC\server\share\a.do"),
+            "//server/share/a.do"
+        );
+        assert_eq!(windows_stata_path(r"D:\data\a.do"), "D:/data/a.do");
+    }
+
     #[test]
     fn test_prepare_handles_path_with_spaces() {
         let temp = TempDir::new().unwrap();
@@ -223,6 +289,6 @@ mod tests {
         // Compound quoting must wrap the path so spaces don't split it.
         assert!(body.contains("`\""), "compound-quote opener missing");
         assert!(body.contains("\"'"), "compound-quote closer missing");
-        assert!(body.contains(&script.display().to_string()));
+        assert!(body.contains(&stata_path(&script)));
     }
 }
@@ -1,13 +1,15 @@
 //! Stata subprocess management
 //!
 //! This module handles:
-//! - Spawning Stata in batch mode
+//! - Spawning Stata in batch mode (Unix `-b` or Windows `/e` conventions)
 //! - Setting environment variables (S_ADO from lockfile for package isolation)
 //! - Waiting for completion
 //! - Handling signals (SIGTERM, SIGINT)
 //! - Collecting exit codes
 
-use crate::error::Result;
+use crate::error::{Error, Result};
+use crate::executor::binary::{console_counterpart, BatchStyle};
+use crate::executor::run_paths::stata_path;
 use crate::packages::global_cache;
 use crate::packages::lockfile::load_lockfile;
 use std::io::Read;
@@ -70,6 +72,21 @@ pub struct RunOptions<'a> {
     /// this so the log path reflects the wrapper's basename, not the user's
     /// script.
     pub log_file: Option<PathBuf>,
+    /// How Stata is driven: batch mode (default) or console mode over a pty
+    pub backend: Backend,
+    /// Further environment variables for the Stata process
+    pub env: Vec<(String, PathBuf)>,
+}
+
+/// How stacy drives the Stata process
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Backend {
+    /// Batch mode (`-b`): Stata writes the log, stacy polls it
+    #[default]
+    Batch,
+    /// Console mode over a pseudo-terminal: stacy reads Stata's output as it
+    /// is printed and writes the log itself (Unix only)
+    Console,
 }
 
 impl<'a> RunOptions<'a> {
@@ -84,6 +101,8 @@ impl<'a> RunOptions<'a> {
             local_ado_paths: Vec::new(),
             verify_packages: true,
             log_file: None,
+            backend: Backend::Batch,
+            env: Vec::new(),
         }
     }
 
@@ -126,13 +145,24 @@ impl<'a> RunOptions<'a> {
         self.log_file = Some(path);
         self
     }
+
+    pub fn with_backend(mut self, backend: Backend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    pub fn with_env(mut self, key: &str, value: PathBuf) -> Self {
+        self.env.push((key.to_string(), value));
+        self
+    }
 }
 
 /// Run a Stata script in batch mode
 ///
 /// # Implementation Notes
 ///
-/// - Uses `-b -q` flags (batch mode, quiet startup)
+/// - Uses `-b -q` flags (batch mode, quiet startup), `/e /q` for Windows Stata
+/// - Refuses a GUI binary (`xstata-mp`) whose console sibling exists
 /// - Sets S_ADO environment variable if project_root provided
 /// - Log file: `{script_name}.log` in current directory
 /// - Returns actual exit code from Stata (always 0 for Stata errors!)
@@ -154,14 +184,27 @@ impl<'a> RunOptions<'a> {
 pub fn run_stata(script: &Path, options: RunOptions) -> Result<RunResult> {
     let start = Instant::now();
 
+    // A GUI Stata given `-b` opens a window (or fails without a display)
+    // instead of running the job.
+    if let Some(console) = console_counterpart(options.stata_binary) {
+        if console.is_file() {
+            return Err(Error::Execution(format!(
+                "{} is Stata's GUI application, which can't run batch jobs from a terminal.\n\
+                 Use the console binary instead: --engine {}",
+                options.stata_binary,
+                console.display()
+            )));
+        }
+    }
+
+    if options.backend == Backend::Console {
+        return super::console::run_console(script, &options);
+    }
+
     // Build Stata command
     let mut cmd = Command::new(options.stata_binary);
-
-    // Batch mode flags:
-    // -b: batch mode (no GUI)
-    // -q: quiet startup (suppress banner)
-    cmd.args(["-b", "-q", "do"]);
-    cmd.arg(script);
+    cmd.args(BatchStyle::for_binary(options.stata_binary).args());
+    cmd.arg(stata_path(script));
 
     // Stata writes results to the log file, so stdout is uninteresting.
     // stderr is normally empty, but carries real diagnostics on startup
@@ -170,6 +213,71 @@ pub fn run_stata(script: &Path, options: RunOptions) -> Result<RunResult> {
     cmd.stdout(Stdio::null());
     cmd.stderr(Stdio::piped());
 
+    configure_environment(&mut cmd, &options)?;
+
+    tracing::info!(script = %script.display(), binary = options.stata_binary, "starting Stata");
+    tracing::debug!(command = ?cmd, "Stata command line");
+
+    // Spawn process
+    let mut child = cmd.spawn()?;
+
+    let stderr_handle = capture_stderr(&mut child);
+
+    // Wait for completion (with optional timeout)
+    let (exit_status, timed_out) = if let Some(timeout) = options.timeout {
+        wait_with_timeout(&mut child, timeout)?
+    } else {
+        (child.wait()?, false)
+    };
+
+    let duration = start.elapsed();
+
+    // Collect captured stderr after the child has exited.
+    let stderr = stderr_handle
+        .and_then(|h| h.join().ok())
+        .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+        .unwrap_or_default();
+
+    let log_file = resolve_log_file(script, &options);
+
+    // Extract exit code
+    let exit_code = exit_code_from_status(&exit_status);
+
+    // Check if process completed normally
+    let completed = exit_status.success() || exit_code == 0;
+
+    // Was the process killed by a signal? On Unix this is the only way to
+    // distinguish stacy's own watchdog (or an external SIGKILL) from a
+    // binary that simply exited non-zero (a launch failure with no log).
+    // Windows has no signals, so the watchdog reports its own kill.
+    let signaled = signaled_from_status(&exit_status) || timed_out;
+
+    tracing::info!(
+        exit_code,
+        signaled,
+        timed_out,
+        duration_secs = duration.as_secs_f64(),
+        log = %log_file.display(),
+        "Stata exited"
+    );
+    if !stderr.is_empty() {
+        tracing::debug!(%stderr, "Stata wrote to stderr");
+    }
+
+    Ok(RunResult {
+        exit_code,
+        log_file,
+        duration,
+        completed,
+        signaled,
+        stderr,
+    })
+}
+
+/// Apply the options that shape Stata's environment: working directory,
+/// S_ADO package isolation, `STACY_ARG_*` arguments and further variables.
+/// Shared by the batch runner and the console backend.
+pub(crate) fn configure_environment(cmd: &mut Command, options: &RunOptions) -> Result<()> {
     // Set working directory if specified
     if let Some(dir) = options.working_dir {
         cmd.current_dir(dir);
@@ -200,6 +308,7 @@ pub fn run_stata(script: &Path, options: RunOptions) -> Result<RunResult> {
                 options.allow_global,
                 &options.local_ado_paths,
             )?;
+            tracing::debug!(%s_ado, "S_ADO from the lockfile");
             cmd.env("S_ADO", s_ado);
         } else if has_local_paths {
             // No lockfile but local paths configured — still set S_ADO
@@ -224,13 +333,20 @@ pub fn run_stata(script: &Path, options: RunOptions) -> Result<RunResult> {
         cmd.env(&env_key, value);
     }
 
-    // Spawn process
-    let mut child = cmd.spawn()?;
+    for (key, value) in &options.env {
+        cmd.env(key, value);
+    }
 
-    // Drain stderr on a background thread so the kernel pipe buffer can't
-    // deadlock the child if it writes more than ~64 KiB. The reader caps the
-    // captured bytes at STDERR_CAPTURE_LIMIT.
-    let stderr_handle = child.stderr.take().map(|mut pipe| {
+    Ok(())
+}
+
+/// Drain stderr on a background thread so the kernel pipe buffer can't
+/// deadlock the child if it writes more than ~64 KiB. The reader caps the
+/// captured bytes at STDERR_CAPTURE_LIMIT.
+pub(crate) fn capture_stderr(
+    child: &mut std::process::Child,
+) -> Option<std::thread::JoinHandle<Vec<u8>>> {
+    child.stderr.take().map(|mut pipe| {
         std::thread::spawn(move || -> Vec<u8> {
             let mut buf = Vec::with_capacity(512);
             let mut chunk = [0u8; 1024];
@@ -249,28 +365,16 @@ pub fn run_stata(script: &Path, options: RunOptions) -> Result<RunResult> {
             }
             buf
         })
-    });
-
-    // Wait for completion (with optional timeout)
-    let exit_status = if let Some(timeout) = options.timeout {
-        wait_with_timeout(&mut child, timeout)?
-    } else {
-        child.wait()?
-    };
-
-    let duration = start.elapsed();
-
-    // Collect captured stderr after the child has exited.
-    let stderr = stderr_handle
-        .and_then(|h| h.join().ok())
-        .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
-        .unwrap_or_default();
+    })
+}
 
+/// Where Stata writes the log for `script`
+pub(crate) fn resolve_log_file(script: &Path, options: &RunOptions) -> PathBuf {
     // Determine log file path. Callers that want collision-safe parallel runs
     // pass a precomputed path via `with_log_file` (see `executor::run_paths`).
     // The fallback below preserves the legacy "{stem}.log in cwd" behavior for
     // callers that don't (notably the unit tests in this module).
-    let log_file = options.log_file.clone().unwrap_or_else(|| {
+    options.log_file.clone().unwrap_or_else(|| {
         let log_basename = script
             .file_stem()
             .map(|s| PathBuf::from(s).with_extension("log"))
@@ -279,71 +383,63 @@ pub fn run_stata(script: &Path, options: RunOptions) -> Result<RunResult> {
             Some(dir) => dir.join(&log_basename),
             None => log_basename,
         }
-    });
-
-    // Extract exit code
-    let exit_code = exit_code_from_status(&exit_status);
-
-    // Check if process completed normally
-    let completed = exit_status.success() || exit_code == 0;
-
-    // Was the process killed by a signal? On Unix this is the only way to
-    // distinguish stacy's own watchdog (or an external SIGKILL) from a
-    // binary that simply exited non-zero (a launch failure with no log).
-    let signaled = signaled_from_status(&exit_status);
-
-    Ok(RunResult {
-        exit_code,
-        log_file,
-        duration,
-        completed,
-        signaled,
-        stderr,
     })
 }
 
 /// Wait for process with timeout
 ///
 /// If timeout expires, kills the process with SIGTERM, then SIGKILL after 5s.
-/// Uses channel-based cancellation so the watchdog is cleanly stopped when
-/// the process exits before the timeout.
-fn wait_with_timeout(child: &mut std::process::Child, timeout: Duration) -> Result<ExitStatus> {
+/// On Windows, `taskkill /T /F` ends Stata and anything it started. Uses
+/// channel-based cancellation so the watchdog is cleanly stopped when the
+/// process exits before the timeout. Returns whether the watchdog fired.
+pub(crate) fn wait_with_timeout(
+    child: &mut std::process::Child,
+    timeout: Duration,
+) -> Result<(ExitStatus, bool)> {
     use std::sync::mpsc;
     use std::thread;
 
-    #[cfg(unix)]
     let pid = child.id();
 
     let (tx, rx) = mpsc::channel();
 
-    let watchdog = thread::spawn(move || {
+    let watchdog = thread::spawn(move || -> bool {
         // Wait for timeout OR cancellation signal
-        if rx.recv_timeout(timeout).is_err() {
-            // Timeout expired, no cancel received — kill process
-            #[cfg(unix)]
-            unsafe {
-                libc::kill(pid as i32, libc::SIGTERM);
-
-                // SIGKILL escalation — wait 5s, then force kill if still alive
-                thread::sleep(Duration::from_secs(5));
-                if libc::kill(pid as i32, 0) == 0 {
-                    libc::kill(pid as i32, libc::SIGKILL);
-                }
+        if rx.recv_timeout(timeout).is_ok() {
+            return false;
+        }
+        // Timeout expired, no cancel received — kill process
+        #[cfg(unix)]
+        unsafe {
+            libc::kill(pid as i32, libc::SIGTERM);
+
+            // SIGKILL escalation — wait 5s, then force kill if still alive
+            thread::sleep(Duration::from_secs(5));
+            if libc::kill(pid as i32, 0) == 0 {
+                libc::kill(pid as i32, libc::SIGKILL);
             }
         }
-        // If Ok(_) received, process exited normally — do nothing
+        #[cfg(not(unix))]
+        {
+            let _ = Command::new("taskkill")
+                .args(["/PID", &pid.to_string(), "/T", "/F"])
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status();
+        }
+        true
     });
 
     let status = child.wait()?;
     let _ = tx.send(()); // Cancel watchdog (ignore error if thread already exited)
-    let _ = watchdog.join(); // Wait for clean thread shutdown
+    let timed_out = watchdog.join().unwrap_or(false); // Wait for clean thread shutdown
 
-    Ok(status)
+    Ok((status, timed_out))
 }
 
 /// True iff the process was terminated by a signal (Unix). Always false on
 /// non-Unix platforms (Windows has no equivalent concept).
-fn signaled_from_status(status: &ExitStatus) -> bool {
+pub(crate) fn signaled_from_status(status: &ExitStatus) -> bool {
     #[cfg(unix)]
     {
         use std::os::unix::process::ExitStatusExt;
@@ -361,7 +457,7 @@ fn signaled_from_status(status: &ExitStatus) -> bool {
 /// On Unix, handles both normal exits and signals:
 /// - Normal exit: return code
 /// - Signal: 128 + signal number
-fn exit_code_from_status(status: &ExitStatus) -> i32 {
+pub(crate) fn exit_code_from_status(status: &ExitStatus) -> i32 {
     #[cfg(unix)]
     {
         use std::os::unix::process::ExitStatusExt;
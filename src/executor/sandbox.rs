@@ -0,0 +1,128 @@
+//! Throwaway PLUS and PERSONAL directories for strict-mode runs.
+//!
+//! Strict mode keeps PLUS and PERSONAL off the adopath, but a script can
+//! still `ssc install` or `net install`, which writes into the user's global
+//! PLUS. Each strict run points both system directories at a fresh temporary
+//! directory instead, so such an install lands somewhere that is deleted
+//! after the run, and stacy reports what was installed.
+
+use crate::error::Result;
+use crate::executor::run_paths::stata_path;
+use std::path::Path;
+use tempfile::TempDir;
+
+/// Temporary PLUS/PERSONAL for one Stata invocation, deleted on drop
+pub struct AdoSandbox {
+    dir: TempDir,
+}
+
+impl AdoSandbox {
+    pub fn new() -> Result<Self> {
+        Self::with_dir(TempDir::with_prefix("stacy-ado-")?)
+    }
+
+    /// A sandbox inside `parent`, such as a test's scratch directory
+    pub fn new_in(parent: &Path) -> Result<Self> {
+        Self::with_dir(TempDir::with_prefix_in("stacy-ado-", parent)?)
+    }
+
+    fn with_dir(dir: TempDir) -> Result<Self> {
+        std::fs::create_dir(dir.path().join("plus"))?;
+        std::fs::create_dir(dir.path().join("personal"))?;
+        Ok(Self { dir })
+    }
+
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// Stata commands pointing PLUS and PERSONAL into the sandbox
+    pub fn commands(&self) -> Vec<String> {
+        ["PLUS", "PERSONAL"]
+            .iter()
+            .map(|name| {
+                let dir = self.dir.path().join(name.to_lowercase());
+                format!("quietly sysdir set {} `\"{}\"'", name, stata_path(&dir))
+            })
+            .collect()
+    }
+
+    /// Packages the script installed, sorted. Package names come from the
+    /// `stata.trk` install log `net install` keeps in PLUS; `.ado` files put
+    /// there any other way are reported by file name.
+    pub fn installed(&self) -> Vec<String> {
+        let mut names: Vec<String> =
+            std::fs::read_to_string(self.dir.path().join("plus").join("stata.trk"))
+                .map(|trk| tracked_packages(&trk))
+                .unwrap_or_default();
+        if names.is_empty() {
+            collect_ado_stems(self.dir.path(), &mut names);
+        }
+        names.sort();
+        names.dedup();
+        names
+    }
+}
+
+/// Package names in a `stata.trk`: its `N name.pkg` lines
+fn tracked_packages(trk: &str) -> Vec<String> {
+    trk.lines()
+        .filter_map(|line| line.strip_prefix("N "))
+        .map(|pkg| pkg.trim().trim_end_matches(".pkg").to_string())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+fn collect_ado_stems(dir: &Path, names: &mut Vec<String>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_ado_stems(&path, names);
+        } else if path.extension().is_some_and(|e| e == "ado") {
+            if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+                names.push(stem.to_string());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_commands_point_plus_and_personal_into_sandbox() {
+        let sandbox = AdoSandbox::new().unwrap();
+        let commands = sandbox.commands();
+        assert_eq!(commands.len(), 2);
+        assert!(commands[0].starts_with("quietly sysdir set PLUS `\""));
+        assert!(commands[1].starts_with("quietly sysdir set PERSONAL `\""));
+        assert!(sandbox.path().join("plus").is_dir());
+    }
+
+    #[test]
+    fn test_installed_reads_stata_trk() {
+        let sandbox = AdoSandbox::new().unwrap();
+        let plus = sandbox.path().join("plus");
+        fs::create_dir_all(plus.join("e")).unwrap();
+        fs::write(plus.join("e").join("estout.ado"), "").unwrap();
+        fs::write(
+            plus.join("stata.trk"),
+            "* 00000001\nS http://fmwww.bc.edu/repec/bocode/e\nN estout.pkg\nD 1 Jan 2026\ne\n",
+        )
+        .unwrap();
+        assert_eq!(sandbox.installed(), vec!["estout".to_string()]);
+    }
+
+    #[test]
+    fn test_installed_falls_back_to_ado_files() {
+        let sandbox = AdoSandbox::new().unwrap();
+        assert!(sandbox.installed().is_empty());
+        fs::write(sandbox.path().join("personal").join("myhelper.ado"), "").unwrap();
+        assert_eq!(sandbox.installed(), vec!["myhelper".to_string()]);
+    }
+}
@@ -0,0 +1,298 @@
+//! Filtering `stacy run --trace` output
+//!
+//! `set trace on` writes out every line of every program Stata runs, its own
+//! ado-files included: a single `regress` is hundreds of lines.
+//! `--trace-filter user` leaves out the programs that come with Stata (those
+//! with an ado-file in its `ado/base` directory), and `--trace-output FILE`
+//! sends the trace to a file of its own so the console keeps the script's
+//! output only.
+//!
+//! A traced program shows up between banners, its lines indented by depth and
+//! starting with `- ` (the command) or `= ` (the command with macros
+//! expanded):
+//!
+//! ```text
+//!   ---------------------------------------------------- begin myprog ---
+//!   - syntax varlist
+//!   - summarize `varlist'
+//!   = summarize price
+//!   ------------------------------------------------------ end myprog ---
+//! ```
+//!
+//! Output the program prints isn't indented and always stays on the console.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+/// `--- begin name ---` and `--- end name ---`
+static BANNER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\s*-{3,}\s+(begin|end)\s+(\S+)\s+---\s*$").unwrap());
+
+/// A traced command, or the continuation of a long one
+static TRACE_LINE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\s*[-=>]\s").unwrap());
+
+/// Which programs `--trace` shows
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum TraceFilter {
+    /// Every program, Stata's own included
+    #[default]
+    All,
+    /// Programs of the project and its packages, not those that come with
+    /// Stata
+    User,
+}
+
+/// How the trace of a run is shown
+#[derive(Debug, Clone, Default)]
+pub struct TraceView {
+    pub filter: TraceFilter,
+    /// Stata's `ado/base`, whose programs `TraceFilter::User` leaves out
+    pub base_dir: Option<PathBuf>,
+    /// File the trace goes to instead of the console
+    pub output: Option<PathBuf>,
+}
+
+/// What a log line is to the trace
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    /// Not part of the trace: command echoes and output
+    Output,
+    Trace,
+    /// Trace of a program the filter leaves out
+    Hidden,
+}
+
+/// Sorts the lines of a traced log, one at a time and in order
+struct TraceLines {
+    view: TraceView,
+    /// Programs being traced, innermost last: whether each is hidden
+    open: Vec<bool>,
+    is_base: HashMap<String, bool>,
+}
+
+impl TraceLines {
+    fn new(view: TraceView) -> Self {
+        Self {
+            view,
+            open: Vec::new(),
+            is_base: HashMap::new(),
+        }
+    }
+
+    fn kind(&mut self, line: &str) -> Kind {
+        let hidden_kind = |hidden: bool| if hidden { Kind::Hidden } else { Kind::Trace };
+        if let Some(caps) = BANNER.captures(line) {
+            return if &caps[1] == "begin" {
+                let hidden = self.open.last().copied().unwrap_or(false)
+                    || (self.view.filter == TraceFilter::User && self.comes_with_stata(&caps[2]));
+                self.open.push(hidden);
+                hidden_kind(hidden)
+            } else {
+                hidden_kind(self.open.pop().unwrap_or(false))
+            };
+        }
+        match self.open.last() {
+            Some(&hidden) if TRACE_LINE.is_match(line) => hidden_kind(hidden),
+            _ => Kind::Output,
+        }
+    }
+
+    /// Whether Stata's `ado/base` has the program. A subroutine
+    /// (`regress.Display`) goes with its ado-file.
+    fn comes_with_stata(&mut self, program: &str) -> bool {
+        let Some(base) = &self.view.base_dir else {
+            return false;
+        };
+        let name = program.split('.').next().unwrap_or(program);
+        *self.is_base.entry(name.to_string()).or_insert_with(|| {
+            name.chars().next().is_some_and(|first| {
+                base.join(first.to_ascii_lowercase().to_string())
+                    .join(format!("{}.ado", name))
+                    .is_file()
+            })
+        })
+    }
+}
+
+/// Writer that applies a [`TraceView`] to a streamed log: hidden programs
+/// are dropped, and with `--trace-output` the whole trace is, the file
+/// getting it after the run. Like `PrefixedLines`, it works on whole lines.
+pub struct TraceWriter<W: Write> {
+    lines: TraceLines,
+    out: W,
+    line: Vec<u8>,
+}
+
+impl<W: Write> TraceWriter<W> {
+    pub fn new(view: TraceView, out: W) -> Self {
+        Self {
+            lines: TraceLines::new(view),
+            out,
+            line: Vec::new(),
+        }
+    }
+
+    fn emit(&mut self) -> std::io::Result<()> {
+        let text = String::from_utf8_lossy(&self.line);
+        let keep = match self.lines.kind(&text) {
+            Kind::Output => true,
+            Kind::Trace => self.lines.view.output.is_none(),
+            Kind::Hidden => false,
+        };
+        if keep {
+            self.out.write_all(&self.line)?;
+        }
+        self.line.clear();
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for TraceWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        for &byte in buf {
+            self.line.push(byte);
+            if byte == b'\n' {
+                self.emit()?;
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        if !self.line.is_empty() {
+            self.emit()?;
+        }
+        self.out.flush()
+    }
+}
+
+/// A traced log split into what the console shows and the trace, each
+/// filtered by `view`
+pub fn split(log: &str, view: &TraceView) -> (String, String) {
+    let mut lines = TraceLines::new(view.clone());
+    let (mut console, mut trace) = (String::new(), String::new());
+    for line in log.lines() {
+        let kind = lines.kind(line);
+        let to = match kind {
+            Kind::Output => &mut console,
+            Kind::Trace if view.output.is_none() => &mut console,
+            Kind::Trace => &mut trace,
+            Kind::Hidden => continue,
+        };
+        to.push_str(line);
+        to.push('\n');
+    }
+    (console, trace)
+}
+
+/// Stata's `ado/base`, found going up from the binary: `<dir>/stata-mp`,
+/// `<dir>/StataMP.app/Contents/MacOS/stata-mp` and `<dir>\StataMP-64.exe`
+/// all have it in `<dir>/ado/base`
+pub fn base_dir(stata_binary: &str) -> Option<PathBuf> {
+    let binary = crate::utils::paths::canonicalize_or_self(Path::new(stata_binary));
+    binary
+        .ancestors()
+        .skip(1)
+        .map(|dir| dir.join("ado").join("base"))
+        .find(|base| base.is_dir())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const LOG: &str = "\
+. myprog price
+  ---------------------------------------------------- begin myprog ---
+  - syntax varlist
+  - summarize `varlist'
+  = summarize price
+    ------------------------------------------------- begin summarize ---
+    - version 18
+    ------------------------------------------------- end summarize ---
+    Variable |        Obs        Mean
+  ------------------------------------------------------ end myprog ---
+
+. display 1
+1
+";
+
+    fn stata_with_base() -> TempDir {
+        let temp = TempDir::new().unwrap();
+        std::fs::create_dir_all(temp.path().join("ado/base/s")).unwrap();
+        std::fs::write(temp.path().join("ado/base/s/summarize.ado"), "").unwrap();
+        std::fs::write(temp.path().join("stata-mp"), "").unwrap();
+        temp
+    }
+
+    #[test]
+    fn test_split_filters_stata_programs() {
+        let stata = stata_with_base();
+        let binary = stata.path().join("stata-mp").display().to_string();
+        let view = TraceView {
+            filter: TraceFilter::User,
+            base_dir: base_dir(&binary),
+            output: None,
+        };
+        assert!(view.base_dir.is_some());
+        let (console, trace) = split(LOG, &view);
+        assert!(trace.is_empty());
+        assert!(console.contains("- syntax varlist"));
+        assert!(!console.contains("version 18"));
+        assert!(!console.contains("summarize ---"));
+        // Output of a program is never filtered
+        assert!(console.contains("Variable |"));
+
+        let all = TraceView {
+            base_dir: view.base_dir.clone(),
+            ..TraceView::default()
+        };
+        assert_eq!(split(LOG, &all).0, LOG);
+    }
+
+    #[test]
+    fn test_split_to_trace_output() {
+        let view = TraceView {
+            output: Some(PathBuf::from("trace.log")),
+            ..TraceView::default()
+        };
+        let (console, trace) = split(LOG, &view);
+        assert_eq!(
+            console,
+            ". myprog price\n    Variable |        Obs        Mean\n\n. display 1\n1\n"
+        );
+        assert_eq!(trace.lines().count(), 8);
+    }
+
+    #[test]
+    fn test_trace_writer_streams_whole_lines() {
+        let view = TraceView {
+            output: Some(PathBuf::from("trace.log")),
+            ..TraceView::default()
+        };
+        let mut out = Vec::new();
+        {
+            let mut writer = TraceWriter::new(view, &mut out);
+            for chunk in LOG.as_bytes().chunks(7) {
+                writer.write_all(chunk).unwrap();
+            }
+            writer.flush().unwrap();
+        }
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            split(
+                LOG,
+                &TraceView {
+                    output: Some(PathBuf::from("trace.log")),
+                    ..TraceView::default()
+                }
+            )
+            .0
+        );
+    }
+}
@@ -97,6 +97,14 @@ impl Verbosity {
     pub fn is_quiet(&self) -> bool {
         matches!(self, Verbosity::Quiet)
     }
+
+    /// Print a `warning:` line and its `hint:` on stderr, unless quiet
+    pub fn warn(&self, message: &str, hint: &str) {
+        if !self.is_quiet() {
+            eprintln!("warning: {}", message);
+            eprintln!("  hint: {}", hint);
+        }
+    }
 }
 
 impl fmt::Display for Verbosity {
@@ -0,0 +1,311 @@
+//! Run history: what ran, when, and how it went
+//!
+//! Every script `stacy run`, `task`, `build` and `test` run inside a project
+//! is added to `.stacy/history.json` as the command exits: the command line,
+//! the script, when it started, how long it took, its exit code and first
+//! error, the log it kept and the commit checked out. `stacy history` lists
+//! and filters the runs, `stacy history show <id>` shows one, and `stacy
+//! rerun` replays the last command. The newest [`MAX_RUNS`] are kept.
+//!
+//! Like the metrics totals, the history only informs: failing to read or
+//! write it never fails a command.
+
+pub mod regression;
+
+use crate::error::{Error, Result, StataError};
+use crate::project::root::find_project_root_from_cwd;
+use crate::utils::lock::with_exclusive_lock;
+use crate::utils::paths::{canonicalize_or_self, write_atomically};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// History file within the project root
+const HISTORY_FILE: &str = ".stacy/history.json";
+
+/// Lock serializing concurrent stacy processes' updates
+const HISTORY_LOCK: &str = ".stacy/history.lock";
+
+/// History format version; a history of another version starts over
+const HISTORY_VERSION: u32 = 1;
+
+/// Runs kept; older ones are dropped
+pub const MAX_RUNS: usize = 1000;
+
+/// Commands whose scripts are recorded
+const RECORDED_COMMANDS: [&str; 4] = ["run", "task", "build", "test"];
+
+/// One script run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunRecord {
+    /// Number of the run in the project, counting up
+    pub id: u64,
+    /// Number of the stacy command it ran in; the runs of one command share it
+    pub invocation: u64,
+    /// The stacy command it ran in (`task`)
+    pub command: String,
+    /// Arguments of that command, global options included
+    /// (`["task", "all", "--resume"]`)
+    pub args: Vec<String>,
+    /// Directory the command ran in, relative to the project root
+    pub dir: PathBuf,
+    /// Script, relative to the project root when inside it
+    pub script: PathBuf,
+    /// When it started, in seconds since the Unix epoch
+    pub started_at: u64,
+    pub duration_secs: f64,
+    pub exit_code: i32,
+    pub success: bool,
+    /// First error (`r(601): file data.dta not found`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Log kept after the run, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_file: Option<PathBuf>,
+    /// Abbreviated commit checked out, when the project is in git
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct HistoryFile {
+    version: u32,
+    /// Oldest first
+    runs: Vec<RunRecord>,
+}
+
+/// A run noted by this process, not yet in the history
+struct PendingRun {
+    script: PathBuf,
+    started_at: u64,
+    duration: Duration,
+    exit_code: i32,
+    success: bool,
+    error: Option<String>,
+    /// Stata's log; replaced by where it was kept, or `None` once removed
+    log_file: Option<PathBuf>,
+}
+
+static PENDING: Mutex<Vec<PendingRun>> = Mutex::new(Vec::new());
+
+/// Note a script Stata ran
+pub fn note_run(
+    script: &Path,
+    log_file: &Path,
+    success: bool,
+    exit_code: i32,
+    duration: Duration,
+    error: Option<&StataError>,
+) {
+    if let Ok(mut pending) = PENDING.lock() {
+        pending.push(PendingRun {
+            script: script.to_path_buf(),
+            started_at: unix_now().saturating_sub(duration.as_secs()),
+            duration,
+            exit_code,
+            success,
+            error: error.map(describe),
+            log_file: Some(log_file.to_path_buf()),
+        });
+    }
+}
+
+fn describe(error: &StataError) -> String {
+    match error {
+        // The message echoes the failing command first; keep the error line
+        StataError::StataCode {
+            message, r_code, ..
+        } => format!(
+            "r({}): {}",
+            r_code,
+            message.lines().last().unwrap_or_default().trim()
+        ),
+        StataError::ProcessKilled { exit_code } => {
+            format!("Stata was killed (exit code {})", exit_code)
+        }
+    }
+}
+
+/// Note where a run's log went once it ran: `kept`, or nowhere
+pub fn note_log(log_file: &Path, kept: Option<&Path>) {
+    if let Ok(mut pending) = PENDING.lock() {
+        if let Some(run) = pending
+            .iter_mut()
+            .rev()
+            .find(|run| run.log_file.as_deref() == Some(log_file))
+        {
+            run.log_file = kept.map(Path::to_path_buf);
+        }
+    }
+}
+
+/// The runs recorded for the project at `project_root`, oldest first
+pub fn load(project_root: &Path) -> Vec<RunRecord> {
+    std::fs::read(project_root.join(HISTORY_FILE))
+        .ok()
+        .and_then(|content| serde_json::from_slice::<HistoryFile>(&content).ok())
+        .filter(|file| file.version == HISTORY_VERSION)
+        .map(|file| file.runs)
+        .unwrap_or_default()
+}
+
+/// Add the runs of this invocation of `command` to the project's history.
+/// Called once, as the command exits.
+pub fn finish(command: &str) {
+    if !RECORDED_COMMANDS.contains(&command) {
+        return;
+    }
+    let Ok(mut pending) = PENDING.lock() else {
+        return;
+    };
+    if pending.is_empty() {
+        return;
+    }
+    let Ok(Some(root)) = find_project_root_from_cwd() else {
+        return;
+    };
+    let root = root.path;
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let dir = std::env::current_dir()
+        .map(|dir| relative_to(&root, &dir))
+        .unwrap_or_default();
+    let commit = current_commit(&root);
+
+    let _ = with_exclusive_lock(&root.join(HISTORY_LOCK), || {
+        let mut runs = load(&root);
+        let next_id = runs.last().map_or(0, |run| run.id) + 1;
+        let invocation = runs.last().map_or(0, |run| run.invocation) + 1;
+        for (id, run) in (next_id..).zip(pending.drain(..)) {
+            runs.push(RunRecord {
+                id,
+                invocation,
+                command: command.to_string(),
+                args: args.clone(),
+                dir: dir.clone(),
+                script: relative_to(&root, &run.script),
+                started_at: run.started_at,
+                duration_secs: run.duration.as_secs_f64(),
+                exit_code: run.exit_code,
+                success: run.success,
+                error: run.error,
+                log_file: run.log_file.map(|log| relative_to(&root, &log)),
+                commit: commit.clone(),
+            });
+        }
+        let excess = runs.len().saturating_sub(MAX_RUNS);
+        runs.drain(..excess);
+        if let Ok(content) = serde_json::to_vec_pretty(&HistoryFile {
+            version: HISTORY_VERSION,
+            runs,
+        }) {
+            let _ = write_atomically(&root.join(HISTORY_FILE), &content);
+        }
+    });
+}
+
+/// The runs of the last command recorded, oldest first
+pub fn last_invocation(runs: &[RunRecord]) -> Vec<&RunRecord> {
+    let Some(last) = runs.last() else {
+        return Vec::new();
+    };
+    runs.iter()
+        .filter(|run| run.invocation == last.invocation)
+        .collect()
+}
+
+/// `path` relative to `root` if inside it, as the history records scripts
+pub fn relative_to(root: &Path, path: &Path) -> PathBuf {
+    let path = canonicalize_or_self(path);
+    path.strip_prefix(canonicalize_or_self(root))
+        .map(Path::to_path_buf)
+        .unwrap_or(path)
+}
+
+/// The abbreviated commit checked out in `dir`, if it is in git
+fn current_commit(dir: &Path) -> Option<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .current_dir(dir)
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .filter(|commit| !commit.is_empty())
+}
+
+/// Parse a `--since` value into seconds since the Unix epoch: an age
+/// (`30m`, `12h`, `7d`, `2w`) or a UTC date (`2026-03-01`)
+pub fn parse_since(value: &str, now: u64) -> Result<u64> {
+    let invalid = || {
+        Error::Config(format!(
+            "Invalid --since '{}': use an age such as 12h, 7d or 2w, or a date (YYYY-MM-DD)",
+            value
+        ))
+    };
+    if let Some((year, rest)) = value.split_once('-') {
+        let (month, day) = rest.split_once('-').ok_or_else(invalid)?;
+        let (year, month, day): (i64, i64, i64) = (
+            year.parse().map_err(|_| invalid())?,
+            month.parse().map_err(|_| invalid())?,
+            day.parse().map_err(|_| invalid())?,
+        );
+        if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+            return Err(invalid());
+        }
+        let days = days_from_civil(year, month, day);
+        return u64::try_from(days * 86_400).map_err(|_| invalid());
+    }
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(invalid)?;
+    let (amount, unit) = value.split_at(split);
+    let amount: u64 = amount.parse().map_err(|_| invalid())?;
+    let unit_secs = match unit {
+        "m" => 60,
+        "h" => 3_600,
+        "d" => 86_400,
+        "w" => 604_800,
+        _ => return Err(invalid()),
+    };
+    Ok(now.saturating_sub(amount * unit_secs))
+}
+
+/// Days since 1970-01-01 of a civil date (Howard Hinnant's algorithm)
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_since() {
+        let now = 1_700_000_000;
+        assert_eq!(parse_since("7d", now).unwrap(), now - 7 * 86_400);
+        assert_eq!(parse_since("12h", now).unwrap(), now - 12 * 3_600);
+        assert_eq!(parse_since("2w", now).unwrap(), now - 14 * 86_400);
+        assert_eq!(parse_since("1970-01-02", now).unwrap(), 86_400);
+        assert_eq!(parse_since("2024-03-01", now).unwrap(), 1_709_251_200);
+        for bad in ["7", "d", "7y", "2024-13-01", "yesterday"] {
+            assert!(parse_since(bad, now).is_err(), "{}", bad);
+        }
+    }
+}
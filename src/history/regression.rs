@@ -0,0 +1,242 @@
+//! Slowdowns against the run history
+//!
+//! Each script that passes is compared with its last passing runs in the
+//! history. When it took much longer than their median, stacy warns: on
+//! stderr, as a `regression` event under `--format json-lines` and as a
+//! warning line under `--format vscode`. With `stacy run --fail-on-regression
+//! 20%` a slowdown past 20% also fails the command.
+//!
+//! Scripts with fewer than [`MIN_BASELINE_RUNS`] passing runs have no
+//! baseline yet, and slowdowns under a second are ignored as noise.
+
+use super::RunRecord;
+use crate::project::root::find_project_root_from_cwd;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+/// Slowdown warned about, in percent over the baseline, unless
+/// `--fail-on-regression` sets another
+pub const DEFAULT_THRESHOLD: f64 = 50.0;
+
+/// Passing runs the baseline is the median of
+const BASELINE_RUNS: usize = 10;
+
+/// Passing runs needed before a script has a baseline
+pub const MIN_BASELINE_RUNS: usize = 3;
+
+/// Slowdowns shorter than this are never reported
+const MIN_SLOWDOWN_SECS: f64 = 1.0;
+
+/// `--fail-on-regression`, in percent
+static FAIL_THRESHOLD: Mutex<Option<f64>> = Mutex::new(None);
+
+/// Slowdowns past `--fail-on-regression` found by this process
+static FAILURES: Mutex<Vec<Regression>> = Mutex::new(Vec::new());
+
+/// The project root and its history as the command started
+static HISTORY: OnceLock<Option<(PathBuf, Vec<RunRecord>)>> = OnceLock::new();
+
+/// A script that ran slower than its baseline
+#[derive(Debug, Clone, PartialEq)]
+pub struct Regression {
+    /// Script, relative to the project root when inside it
+    pub script: PathBuf,
+    pub duration_secs: f64,
+    /// Median duration of the script's last passing runs
+    pub baseline_secs: f64,
+    /// Number of runs the baseline is the median of
+    pub baseline_runs: usize,
+}
+
+impl Regression {
+    /// How much slower than the baseline, in percent
+    pub fn slower_percent(&self) -> f64 {
+        (self.duration_secs / self.baseline_secs - 1.0) * 100.0
+    }
+
+    /// `took 12.3s, 45% longer than its usual 8.5s (...)`
+    pub fn describe(&self) -> String {
+        format!(
+            "took {:.1}s, {:.0}% longer than its usual {:.1}s (median of its last {} passing runs)",
+            self.duration_secs,
+            self.slower_percent(),
+            self.baseline_secs,
+            self.baseline_runs
+        )
+    }
+}
+
+/// Fail the command on slowdowns past `percent` (`--fail-on-regression`)
+pub fn set_fail_threshold(percent: f64) {
+    if let Ok(mut threshold) = FAIL_THRESHOLD.lock() {
+        *threshold = Some(percent);
+    }
+}
+
+fn fail_threshold() -> Option<f64> {
+    FAIL_THRESHOLD.lock().ok().and_then(|threshold| *threshold)
+}
+
+/// Parse a percentage (`20%` or `20`)
+pub fn parse_percent(value: &str) -> std::result::Result<f64, String> {
+    value
+        .trim()
+        .trim_end_matches('%')
+        .parse::<f64>()
+        .ok()
+        .filter(|percent| percent.is_finite() && *percent > 0.0)
+        .ok_or_else(|| format!("'{}' is not a percentage above 0 (such as 20%)", value))
+}
+
+/// Compare a passing run of `script` that took `duration` with its last
+/// passing runs in `runs` (oldest first)
+pub fn compare(
+    script: &Path,
+    duration: Duration,
+    runs: &[RunRecord],
+    threshold: f64,
+) -> Option<Regression> {
+    let mut baseline: Vec<f64> = runs
+        .iter()
+        .rev()
+        .filter(|run| run.success && run.script == script)
+        .take(BASELINE_RUNS)
+        .map(|run| run.duration_secs)
+        .collect();
+    if baseline.len() < MIN_BASELINE_RUNS {
+        return None;
+    }
+    baseline.sort_by(f64::total_cmp);
+    let mid = baseline.len() / 2;
+    let median = if baseline.len().is_multiple_of(2) {
+        (baseline[mid - 1] + baseline[mid]) / 2.0
+    } else {
+        baseline[mid]
+    };
+
+    let secs = duration.as_secs_f64();
+    let slower = secs - median >= MIN_SLOWDOWN_SECS && secs >= median * (1.0 + threshold / 100.0);
+    slower.then(|| Regression {
+        script: script.to_path_buf(),
+        duration_secs: secs,
+        baseline_secs: median,
+        baseline_runs: baseline.len(),
+    })
+}
+
+/// Compare a passing run of `script` with the project's history. A slowdown
+/// past `--fail-on-regression` is kept for [`gate`].
+pub fn check(script: &Path, duration: Duration) -> Option<Regression> {
+    let (root, runs) = HISTORY
+        .get_or_init(|| {
+            let root = find_project_root_from_cwd().ok()??.path;
+            let runs = super::load(&root);
+            Some((root, runs))
+        })
+        .as_ref()?;
+    let fail = fail_threshold();
+    let regression = compare(
+        &super::relative_to(root, script),
+        duration,
+        runs,
+        fail.unwrap_or(DEFAULT_THRESHOLD),
+    )?;
+    if fail.is_some() {
+        if let Ok(mut failures) = FAILURES.lock() {
+            failures.push(regression.clone());
+        }
+    }
+    Some(regression)
+}
+
+/// The exit code of a command that would exit with `code`: 1 instead of 0
+/// when scripts slowed down past `--fail-on-regression`
+pub fn gate(code: i32) -> i32 {
+    let Some(threshold) = fail_threshold() else {
+        return code;
+    };
+    let Ok(failures) = FAILURES.lock() else {
+        return code;
+    };
+    if code != 0 || failures.is_empty() {
+        return code;
+    }
+    eprintln!(
+        "Error: {} script(s) slowed down by more than {}% (--fail-on-regression):",
+        failures.len(),
+        threshold
+    );
+    for regression in failures.iter() {
+        eprintln!(
+            "  {} {}",
+            regression.script.display(),
+            regression.describe()
+        );
+    }
+    1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(script: &str, duration_secs: f64, success: bool) -> RunRecord {
+        RunRecord {
+            id: 1,
+            invocation: 1,
+            command: "run".to_string(),
+            args: Vec::new(),
+            dir: PathBuf::new(),
+            script: PathBuf::from(script),
+            started_at: 0,
+            duration_secs,
+            exit_code: if success { 0 } else { 1 },
+            success,
+            error: None,
+            log_file: None,
+            commit: None,
+        }
+    }
+
+    #[test]
+    fn test_compare_with_median_of_passing_runs() {
+        let runs = vec![
+            run("a.do", 10.0, true),
+            run("a.do", 1.0, false),
+            run("b.do", 50.0, true),
+            run("a.do", 12.0, true),
+            run("a.do", 8.0, true),
+        ];
+        let script = Path::new("a.do");
+
+        let regression = compare(script, Duration::from_secs(16), &runs, 50.0).unwrap();
+        assert_eq!(regression.baseline_secs, 10.0);
+        assert_eq!(regression.baseline_runs, 3);
+        assert!((regression.slower_percent() - 60.0).abs() < 1e-9);
+        assert!(compare(script, Duration::from_secs(14), &runs, 50.0).is_none());
+        assert!(compare(script, Duration::from_secs(14), &runs, 20.0).is_some());
+        // Too few passing runs for a baseline
+        assert!(compare(script, Duration::from_secs(60), &runs[..4], 20.0).is_none());
+    }
+
+    #[test]
+    fn test_small_slowdowns_are_noise() {
+        let runs = vec![
+            run("a.do", 0.2, true),
+            run("a.do", 0.2, true),
+            run("a.do", 0.2, true),
+        ];
+        let script = Path::new("a.do");
+        assert!(compare(script, Duration::from_millis(900), &runs, 50.0).is_none());
+        assert!(compare(script, Duration::from_millis(1500), &runs, 50.0).is_some());
+    }
+
+    #[test]
+    fn test_parse_percent() {
+        assert_eq!(parse_percent("20%"), Ok(20.0));
+        assert_eq!(parse_percent("12.5"), Ok(12.5));
+        assert!(parse_percent("0%").is_err());
+        assert!(parse_percent("fast").is_err());
+    }
+}
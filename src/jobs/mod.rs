@@ -0,0 +1,122 @@
+//! Batch jobs submitted with `stacy submit`
+//!
+//! `stacy submit` writes a batch script that runs `stacy run` or `stacy task`
+//! in the project, hands it to the cluster's scheduler (SLURM's `sbatch` or
+//! PBS's `qsub`), and records the job in `.stacy/jobs.json`. `stacy jobs`
+//! asks the scheduler how each recorded job is doing and shows the output of
+//! a finished one. Scripts and job output live in `.stacy/jobs/`.
+
+pub mod scheduler;
+
+use crate::error::{Error, Result};
+use crate::project::config::Scheduler;
+use crate::utils::lock::{project_lock_path, with_state_write_lock};
+use crate::utils::paths::write_atomically;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Registry format version; a file with another version is started afresh
+const JOBS_VERSION: u32 = 1;
+
+/// Registry path within the project root
+const JOBS_FILE: &str = ".stacy/jobs.json";
+
+/// Directory of batch scripts and job output, within the project root
+pub const JOBS_DIR: &str = ".stacy/jobs";
+
+/// A submitted job
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobRecord {
+    /// Job ID the scheduler gave it
+    pub id: String,
+    pub scheduler: Scheduler,
+    /// Job name given to the scheduler
+    pub name: String,
+    /// The stacy command the job runs (`run analysis.do`)
+    pub command: String,
+    /// Batch script, relative to the project root
+    pub script: PathBuf,
+    /// File the scheduler writes the job's output to, relative to the
+    /// project root
+    pub output: PathBuf,
+    /// When it was submitted, in seconds since the Unix epoch
+    pub submitted_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct JobsFile {
+    version: u32,
+    jobs: Vec<JobRecord>,
+}
+
+impl Default for JobsFile {
+    fn default() -> Self {
+        Self {
+            version: JOBS_VERSION,
+            jobs: Vec::new(),
+        }
+    }
+}
+
+/// The project's submitted jobs, oldest first (none if nothing was
+/// submitted yet)
+pub fn load(project_root: &Path) -> Result<Vec<JobRecord>> {
+    Ok(read(project_root)?.jobs)
+}
+
+/// Add a job to the project's registry
+pub fn record(project_root: &Path, job: JobRecord) -> Result<()> {
+    with_state_write_lock(&project_lock_path(project_root), || {
+        let mut file = read(project_root)?;
+        file.jobs.push(job);
+        write_atomically(
+            &project_root.join(JOBS_FILE),
+            serde_json::to_string_pretty(&file)?.as_bytes(),
+        )?;
+        Ok(())
+    })?
+}
+
+fn read(project_root: &Path) -> Result<JobsFile> {
+    let path = project_root.join(JOBS_FILE);
+    if !path.exists() {
+        return Ok(JobsFile::default());
+    }
+    let content = std::fs::read_to_string(&path)?;
+    let file: JobsFile = serde_json::from_str(&content)
+        .map_err(|e| Error::Parse(format!("Failed to parse {}: {}", path.display(), e)))?;
+    Ok(if file.version == JOBS_VERSION {
+        file
+    } else {
+        JobsFile::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn job(id: &str) -> JobRecord {
+        JobRecord {
+            id: id.to_string(),
+            scheduler: Scheduler::Slurm,
+            name: "stacy-analysis".to_string(),
+            command: "run analysis.do".to_string(),
+            script: PathBuf::from(".stacy/jobs/analysis.sh"),
+            output: PathBuf::from(".stacy/jobs/analysis.out"),
+            submitted_at: 1_700_000_000,
+        }
+    }
+
+    #[test]
+    fn test_record_and_load() {
+        let temp = TempDir::new().unwrap();
+        assert!(load(temp.path()).unwrap().is_empty());
+
+        record(temp.path(), job("101")).unwrap();
+        record(temp.path(), job("102")).unwrap();
+        let jobs = load(temp.path()).unwrap();
+        assert_eq!(jobs, vec![job("101"), job("102")]);
+    }
+}
@@ -0,0 +1,388 @@
+//! Talking to SLURM and PBS
+//!
+//! A job is a bash script whose directives (`#SBATCH`, `#PBS`) carry the
+//! `[submit]` resources, loading the configured modules and then `exec`ing
+//! stacy in the project, so the job's exit status is stacy's and the
+//! scheduler reports a failed run as a failed job.
+//!
+//! | | SLURM | PBS |
+//! |--|-------|-----|
+//! | submit | `sbatch --parsable` | `qsub` |
+//! | queued or running | `squeue -j` | `qstat -fx` (`qstat -f` on Torque) |
+//! | finished | `sacct -j` | same |
+
+use crate::error::{Error, Result};
+use crate::project::config::{Scheduler, SubmitSection};
+use crate::utils::shell_quote;
+use std::path::Path;
+use std::process::Command;
+
+/// Where a job is, as far as stacy cares
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobState {
+    Pending,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+    /// The scheduler doesn't know the job (any more)
+    Unknown,
+}
+
+impl JobState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobState::Pending => "pending",
+            JobState::Running => "running",
+            JobState::Completed => "completed",
+            JobState::Failed => "failed",
+            JobState::Cancelled => "cancelled",
+            JobState::Unknown => "unknown",
+        }
+    }
+
+    /// Whether the job is over, and its output complete
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            JobState::Completed | JobState::Failed | JobState::Cancelled
+        )
+    }
+}
+
+/// A job's state, and the scheduler's own word for it (`TIMEOUT`,
+/// `OUT_OF_MEMORY`, `exit status 1`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JobStatus {
+    pub state: JobState,
+    pub detail: String,
+}
+
+impl JobStatus {
+    fn unknown() -> Self {
+        Self {
+            state: JobState::Unknown,
+            detail: "not known to the scheduler".to_string(),
+        }
+    }
+}
+
+/// The batch script of a job named `name` that runs `command` (the stacy
+/// binary and its arguments) in `project_root`, writing its output to
+/// `output`
+pub fn batch_script(
+    scheduler: Scheduler,
+    name: &str,
+    resources: &SubmitSection,
+    project_root: &Path,
+    output: &Path,
+    command: &[String],
+) -> String {
+    let mut lines = vec!["#!/bin/bash -l".to_string()];
+    let directive = |line: String| match scheduler {
+        Scheduler::Slurm => format!("#SBATCH {}", line),
+        Scheduler::Pbs => format!("#PBS {}", line),
+    };
+    match scheduler {
+        Scheduler::Slurm => {
+            lines.push(directive(format!("--job-name={}", name)));
+            lines.push(directive(format!("--output={}", output.display())));
+            if let Some(partition) = &resources.partition {
+                lines.push(directive(format!("--partition={}", partition)));
+            }
+            if let Some(time) = &resources.time {
+                lines.push(directive(format!("--time={}", time)));
+            }
+            if let Some(memory) = &resources.memory {
+                lines.push(directive(format!("--mem={}", memory)));
+            }
+            if let Some(cpus) = resources.cpus {
+                lines.push(directive(format!("--cpus-per-task={}", cpus)));
+            }
+            if let Some(account) = &resources.account {
+                lines.push(directive(format!("--account={}", account)));
+            }
+        }
+        Scheduler::Pbs => {
+            lines.push(directive(format!("-N {}", name)));
+            lines.push(directive(format!("-o {}", output.display())));
+            lines.push(directive("-j oe".to_string()));
+            if let Some(queue) = &resources.partition {
+                lines.push(directive(format!("-q {}", queue)));
+            }
+            if let Some(time) = &resources.time {
+                lines.push(directive(format!("-l walltime={}", time)));
+            }
+            if let Some(memory) = &resources.memory {
+                lines.push(directive(format!("-l mem={}", memory)));
+            }
+            if let Some(cpus) = resources.cpus {
+                lines.push(directive(format!("-l ncpus={}", cpus)));
+            }
+            if let Some(account) = &resources.account {
+                lines.push(directive(format!("-A {}", account)));
+            }
+        }
+    }
+    for extra in &resources.directives {
+        lines.push(directive(extra.clone()));
+    }
+
+    lines.push(String::new());
+    lines.push("# Written by stacy submit".to_string());
+    lines.push("set -e".to_string());
+    for module in &resources.modules {
+        lines.push(format!("module load {}", module));
+    }
+    lines.push(format!(
+        "cd {}",
+        shell_quote(&project_root.display().to_string())
+    ));
+    let quoted: Vec<String> = command.iter().map(|arg| shell_quote(arg)).collect();
+    lines.push(format!("exec {}", quoted.join(" ")));
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+/// Submit the batch script at `script` from `project_root`; the job ID
+pub fn submit(scheduler: Scheduler, script: &Path, project_root: &Path) -> Result<String> {
+    let (program, args): (&str, &[&str]) = match scheduler {
+        Scheduler::Slurm => ("sbatch", &["--parsable"]),
+        Scheduler::Pbs => ("qsub", &[]),
+    };
+    let output = Command::new(program)
+        .args(args)
+        .arg(script)
+        .current_dir(project_root)
+        .output()
+        .map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                Error::Config(format!(
+                    "{} not found: stacy submit needs the scheduler's tools, as on the cluster's login node",
+                    program
+                ))
+            } else {
+                Error::Execution(format!("Failed to run {}: {}", program, e))
+            }
+        })?;
+    if !output.status.success() {
+        return Err(Error::Execution(format!(
+            "{} failed: {}",
+            program,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    parse_job_id(&String::from_utf8_lossy(&output.stdout)).ok_or_else(|| {
+        Error::Execution(format!(
+            "{} printed no job ID: {}",
+            program,
+            String::from_utf8_lossy(&output.stdout).trim()
+        ))
+    })
+}
+
+/// The job ID `sbatch --parsable` (`12345` or `12345;cluster`) or `qsub`
+/// (`12345.server`) printed
+fn parse_job_id(stdout: &str) -> Option<String> {
+    let line = stdout.lines().map(str::trim).find(|l| !l.is_empty())?;
+    Some(line.split(';').next().unwrap_or(line).to_string())
+}
+
+/// Ask the scheduler about job `id`. A scheduler that can't be asked
+/// reports it unknown.
+pub fn status(scheduler: Scheduler, id: &str) -> JobStatus {
+    match scheduler {
+        Scheduler::Slurm => {
+            let word = query("squeue", &["-h", "-j", id, "-o", "%T"])
+                .filter(|s| !s.trim().is_empty())
+                .or_else(|| query("sacct", &["-n", "-X", "-P", "-j", id, "-o", "State"]));
+            word.and_then(|w| {
+                // `CANCELLED by 1234`
+                let word = w.split_whitespace().next()?.to_string();
+                Some(JobStatus {
+                    state: slurm_state(&word),
+                    detail: word,
+                })
+            })
+            .unwrap_or_else(JobStatus::unknown)
+        }
+        Scheduler::Pbs => query("qstat", &["-fx", id])
+            .or_else(|| query("qstat", &["-f", id]))
+            .and_then(|text| pbs_status(&text))
+            .unwrap_or_else(JobStatus::unknown),
+    }
+}
+
+/// Stdout of a successful scheduler command
+fn query(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// A SLURM job state (`squeue %T`, `sacct State`)
+fn slurm_state(word: &str) -> JobState {
+    match word {
+        "PENDING" | "CONFIGURING" | "REQUEUED" | "RESV_DEL_HOLD" | "SUSPENDED" => JobState::Pending,
+        "RUNNING" | "COMPLETING" | "STAGE_OUT" => JobState::Running,
+        "COMPLETED" => JobState::Completed,
+        "CANCELLED" => JobState::Cancelled,
+        "FAILED" | "TIMEOUT" | "OUT_OF_MEMORY" | "NODE_FAIL" | "PREEMPTED" | "BOOT_FAIL"
+        | "DEADLINE" => JobState::Failed,
+        _ => JobState::Unknown,
+    }
+}
+
+/// The status in `qstat -f` output: `job_state`, and for a finished job its
+/// exit status (`Exit_status`, `exit_status` on Torque)
+fn pbs_status(text: &str) -> Option<JobStatus> {
+    let field = |key: &str| {
+        text.lines().find_map(|line| {
+            let (k, v) = line.split_once('=')?;
+            k.trim()
+                .eq_ignore_ascii_case(key)
+                .then(|| v.trim().to_string())
+        })
+    };
+    let state = field("job_state")?;
+    let status = match state.as_str() {
+        "Q" | "H" | "W" | "T" | "S" => JobStatus {
+            state: JobState::Pending,
+            detail: state,
+        },
+        "R" | "E" | "B" => JobStatus {
+            state: JobState::Running,
+            detail: state,
+        },
+        "F" | "C" | "X" => {
+            let exit = field("exit_status").and_then(|s| s.parse::<i32>().ok());
+            JobStatus {
+                state: match exit {
+                    Some(0) => JobState::Completed,
+                    Some(_) => JobState::Failed,
+                    None => JobState::Unknown,
+                },
+                detail: exit
+                    .map(|code| format!("exit status {}", code))
+                    .unwrap_or(state),
+            }
+        }
+        _ => JobStatus {
+            state: JobState::Unknown,
+            detail: state,
+        },
+    };
+    Some(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn resources() -> SubmitSection {
+        SubmitSection {
+            partition: Some("short".to_string()),
+            time: Some("02:00:00".to_string()),
+            memory: Some("16G".to_string()),
+            cpus: Some(4),
+            modules: vec!["stata/18".to_string()],
+            directives: vec!["--mail-type=END".to_string()],
+            ..SubmitSection::default()
+        }
+    }
+
+    #[test]
+    fn test_slurm_batch_script() {
+        let script = batch_script(
+            Scheduler::Slurm,
+            "stacy-analysis",
+            &resources(),
+            &PathBuf::from("/home/me/my project"),
+            &PathBuf::from(".stacy/jobs/analysis.out"),
+            &[
+                "/opt/bin/stacy".to_string(),
+                "run".to_string(),
+                "analysis.do".to_string(),
+            ],
+        );
+        assert_eq!(
+            script,
+            "#!/bin/bash -l
+#SBATCH --job-name=stacy-analysis
+#SBATCH --output=.stacy/jobs/analysis.out
+#SBATCH --partition=short
+#SBATCH --time=02:00:00
+#SBATCH --mem=16G
+#SBATCH --cpus-per-task=4
+#SBATCH --mail-type=END
+
+# Written by stacy submit
+set -e
+module load stata/18
+cd '/home/me/my project'
+exec '/opt/bin/stacy' 'run' 'analysis.do'
+"
+        );
+    }
+
+    #[test]
+    fn test_pbs_batch_script() {
+        let script = batch_script(
+            Scheduler::Pbs,
+            "stacy-clean",
+            &resources(),
+            &PathBuf::from("/proj"),
+            &PathBuf::from(".stacy/jobs/clean.out"),
+            &["stacy".to_string(), "task".to_string(), "clean".to_string()],
+        );
+        assert!(script.contains("#PBS -N stacy-clean\n#PBS -o .stacy/jobs/clean.out\n#PBS -j oe\n"));
+        assert!(script.contains("#PBS -q short\n"));
+        assert!(script.contains("#PBS -l walltime=02:00:00\n#PBS -l mem=16G\n#PBS -l ncpus=4\n"));
+        assert!(script.ends_with("exec 'stacy' 'task' 'clean'\n"));
+    }
+
+    #[test]
+    fn test_parse_job_id() {
+        assert_eq!(parse_job_id("12345\n"), Some("12345".to_string()));
+        assert_eq!(parse_job_id("12345;cluster\n"), Some("12345".to_string()));
+        assert_eq!(
+            parse_job_id("\n4242.pbs-server\n"),
+            Some("4242.pbs-server".to_string())
+        );
+        assert_eq!(parse_job_id("  \n"), None);
+    }
+
+    #[test]
+    fn test_slurm_state() {
+        assert_eq!(slurm_state("PENDING"), JobState::Pending);
+        assert_eq!(slurm_state("RUNNING"), JobState::Running);
+        assert_eq!(slurm_state("COMPLETED"), JobState::Completed);
+        assert_eq!(slurm_state("OUT_OF_MEMORY"), JobState::Failed);
+        assert_eq!(slurm_state("CANCELLED"), JobState::Cancelled);
+        assert!(!slurm_state("RUNNING").is_finished());
+        assert!(slurm_state("TIMEOUT").is_finished());
+    }
+
+    #[test]
+    fn test_pbs_status() {
+        let running = "Job Id: 4242.server\n    Job_Name = stacy-clean\n    job_state = R\n";
+        assert_eq!(pbs_status(running).unwrap().state, JobState::Running);
+
+        let failed = "Job Id: 4242.server\n    job_state = F\n    Exit_status = 1\n";
+        assert_eq!(
+            pbs_status(failed).unwrap(),
+            JobStatus {
+                state: JobState::Failed,
+                detail: "exit status 1".to_string()
+            }
+        );
+
+        // Torque
+        let done = "Job Id: 4242.server\n    job_state = C\n    exit_status = 0\n";
+        assert_eq!(pbs_status(done).unwrap().state, JobState::Completed);
+        assert_eq!(pbs_status("qstat: Unknown Job Id"), None);
+    }
+}
@@ -0,0 +1,147 @@
+//! Jupyter kernel that runs Stata cells through stacy
+//!
+//! `stacy kernel install` writes a kernelspec whose kernel is a small Python
+//! program (`stacy_kernel.py`, built on ipykernel). Each cell runs either in
+//! an embedded PyStata session, isolated to the project's locked packages, or
+//! as `stacy run -c` with stacy's error detection.
+
+use crate::error::{Error, Result};
+use std::path::{Path, PathBuf};
+
+/// The kernel program, copied into the kernelspec directory
+pub const KERNEL_SCRIPT: &str = include_str!("stacy_kernel.py");
+
+const KERNEL_SCRIPT_NAME: &str = "stacy_kernel.py";
+
+/// How the kernel runs cells
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum KernelBackend {
+    /// PyStata if it can be loaded, otherwise batch
+    #[default]
+    Auto,
+    /// One embedded Stata session; state carries over between cells
+    Pystata,
+    /// A fresh `stacy run -c` per cell; cells share no state
+    Batch,
+}
+
+impl std::fmt::Display for KernelBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KernelBackend::Auto => write!(f, "auto"),
+            KernelBackend::Pystata => write!(f, "pystata"),
+            KernelBackend::Batch => write!(f, "batch"),
+        }
+    }
+}
+
+/// What to install
+pub struct KernelSpec<'a> {
+    /// Kernel directory name (`jupyter kernelspec list` shows it)
+    pub name: &'a str,
+    /// Name shown in Jupyter's kernel picker
+    pub display_name: &'a str,
+    /// Python interpreter that has ipykernel
+    pub python: &'a str,
+    /// stacy binary the kernel calls
+    pub stacy_bin: &'a Path,
+    pub backend: KernelBackend,
+}
+
+/// Jupyter's per-user data directory: `$JUPYTER_DATA_DIR`, else the
+/// platform default (`~/.local/share/jupyter`, `~/Library/Jupyter`,
+/// `%APPDATA%\jupyter`)
+pub fn user_data_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("JUPYTER_DATA_DIR").filter(|d| !d.is_empty()) {
+        return Some(PathBuf::from(dir));
+    }
+    if cfg!(target_os = "macos") {
+        dirs::home_dir().map(|h| h.join("Library").join("Jupyter"))
+    } else {
+        dirs::data_dir().map(|d| d.join("jupyter"))
+    }
+}
+
+/// `kernel.json` for a kernel installed in `kernel_dir`
+pub fn kernel_json(spec: &KernelSpec, kernel_dir: &Path) -> serde_json::Value {
+    serde_json::json!({
+        "argv": [
+            spec.python,
+            kernel_dir.join(KERNEL_SCRIPT_NAME).display().to_string(),
+            "-f",
+            "{connection_file}",
+        ],
+        "display_name": spec.display_name,
+        "language": "stata",
+        "env": {
+            "STACY_BIN": spec.stacy_bin.display().to_string(),
+            "STACY_KERNEL_BACKEND": spec.backend.to_string(),
+        },
+    })
+}
+
+/// Write the kernelspec into `<data_dir>/kernels/<name>`, replacing an
+/// earlier install. Returns the kernel directory.
+pub fn install(spec: &KernelSpec, data_dir: &Path) -> Result<PathBuf> {
+    if spec.name.is_empty()
+        || !spec
+            .name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        return Err(Error::Config(format!(
+            "Invalid kernel name '{}': use letters, digits, '-', '_' and '.'",
+            spec.name
+        )));
+    }
+
+    let kernel_dir = data_dir.join("kernels").join(spec.name);
+    std::fs::create_dir_all(&kernel_dir)?;
+    std::fs::write(kernel_dir.join(KERNEL_SCRIPT_NAME), KERNEL_SCRIPT)?;
+    let json = serde_json::to_string_pretty(&kernel_json(spec, &kernel_dir))?;
+    std::fs::write(kernel_dir.join("kernel.json"), json + "\n")?;
+    Ok(kernel_dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn spec(name: &str) -> KernelSpec<'_> {
+        KernelSpec {
+            name,
+            display_name: "Stata (stacy)",
+            python: "python3",
+            stacy_bin: Path::new("/usr/local/bin/stacy"),
+            backend: KernelBackend::Batch,
+        }
+    }
+
+    #[test]
+    fn test_install_writes_kernelspec() {
+        let temp = TempDir::new().unwrap();
+        let dir = install(&spec("stacy"), temp.path()).unwrap();
+
+        assert_eq!(dir, temp.path().join("kernels").join("stacy"));
+        assert_eq!(
+            std::fs::read_to_string(dir.join(KERNEL_SCRIPT_NAME)).unwrap(),
+            KERNEL_SCRIPT
+        );
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(dir.join("kernel.json")).unwrap())
+                .unwrap();
+        assert_eq!(json["language"], "stata");
+        assert_eq!(json["argv"][0], "python3");
+        assert_eq!(json["argv"][3], "{connection_file}");
+        assert_eq!(json["env"]["STACY_KERNEL_BACKEND"], "batch");
+        assert_eq!(json["env"]["STACY_BIN"], "/usr/local/bin/stacy");
+    }
+
+    #[test]
+    fn test_install_rejects_bad_name() {
+        let temp = TempDir::new().unwrap();
+        assert!(install(&spec("../escape"), temp.path()).is_err());
+        assert!(install(&spec(""), temp.path()).is_err());
+    }
+}
@@ -0,0 +1,159 @@
+"""Jupyter kernel that runs Stata cells through stacy.
+
+Installed by `stacy kernel install`. Two backends:
+
+- pystata: cells run in one embedded Stata session (state carries over
+  between cells). S_ADO is set from `stacy env` before Stata starts, so the
+  session sees exactly the project's locked packages.
+- batch: each cell runs as `stacy run -c <cell>` in a fresh Stata process,
+  with stacy's error detection and exit codes. Cells share no state.
+
+STACY_KERNEL_BACKEND picks one (`auto` tries pystata, then falls back to
+batch). STACY_BIN is the stacy binary to call.
+"""
+
+import json
+import os
+import re
+import subprocess
+import sys
+
+from ipykernel.kernelbase import Kernel
+
+STACY = os.environ.get("STACY_BIN", "stacy")
+BACKEND = os.environ.get("STACY_KERNEL_BACKEND", "auto")
+RC_PATTERN = re.compile(r"r\((\d+)\);")
+
+
+def stacy_env():
+    out = subprocess.run(
+        [STACY, "env", "--format", "json"], capture_output=True, text=True
+    )
+    if out.returncode != 0:
+        return {}
+    try:
+        return json.loads(out.stdout)
+    except ValueError:
+        return {}
+
+
+class PyStataSession:
+    """One embedded Stata, isolated to the project's locked packages."""
+
+    def __init__(self):
+        env = stacy_env()
+        binary = (env.get("stata") or {}).get("binary")
+        if not binary:
+            raise RuntimeError("stacy found no Stata binary")
+        adopath = [entry["path"] for entry in env.get("s_ado", [])]
+        if adopath:
+            os.environ["S_ADO"] = ";".join(adopath)
+
+        stata_dir = os.path.dirname(binary)
+        if stata_dir.endswith(os.path.join("Contents", "MacOS")):
+            stata_dir = os.path.dirname(os.path.dirname(os.path.dirname(stata_dir)))
+        sys.path.append(os.path.join(stata_dir, "utilities"))
+        from pystata import config
+
+        name = os.path.basename(binary).lower()
+        edition = next((e for e in ("mp", "se", "be") if e in name), "be")
+        config.init(edition, splash=False)
+        from pystata import stata
+
+        self.stata = stata
+
+    def run(self, code):
+        import contextlib
+        import io
+
+        buffer = io.StringIO()
+        with contextlib.redirect_stdout(buffer):
+            try:
+                self.stata.run(code, echo=False)
+                error = None
+            except SystemError as e:
+                error = str(e)
+        return buffer.getvalue(), error
+
+
+class BatchSession:
+    """A fresh `stacy run -c` per cell."""
+
+    def run(self, code):
+        out = subprocess.run(
+            [STACY, "run", "-c", code], capture_output=True, text=True
+        )
+        if out.returncode == 0:
+            return out.stdout, None
+        return out.stdout, out.stderr.strip() or "stacy run exited %d" % out.returncode
+
+
+class StacyKernel(Kernel):
+    implementation = "stacy"
+    implementation_version = "1.0"
+    language = "stata"
+    language_version = ""
+    language_info = {
+        "name": "stata",
+        "mimetype": "text/x-stata",
+        "file_extension": ".do",
+    }
+    banner = "Stata through stacy"
+
+    def __init__(self, **kwargs):
+        super().__init__(**kwargs)
+        self.session = None
+
+    def start_session(self):
+        if BACKEND in ("auto", "pystata"):
+            try:
+                return PyStataSession()
+            except Exception as e:
+                if BACKEND == "pystata":
+                    raise
+                self.log.info("pystata unavailable (%s); using stacy run", e)
+        return BatchSession()
+
+    def do_execute(
+        self, code, silent, store_history=True, user_expressions=None, allow_stdin=False
+    ):
+        if not code.strip():
+            return self.ok()
+        if self.session is None:
+            self.session = self.start_session()
+
+        output, error = self.session.run(code)
+        if output and not silent:
+            self.send_response(
+                self.iopub_socket, "stream", {"name": "stdout", "text": output}
+            )
+        if error is None:
+            return self.ok()
+
+        match = RC_PATTERN.search(error)
+        ename = "r(%s)" % match.group(1) if match else "StataError"
+        if not silent:
+            self.send_response(
+                self.iopub_socket, "stream", {"name": "stderr", "text": error + "\n"}
+            )
+        return {
+            "status": "error",
+            "execution_count": self.execution_count,
+            "ename": ename,
+            "evalue": error,
+            "traceback": [],
+        }
+
+    def ok(self):
+        return {
+            "status": "ok",
+            "execution_count": self.execution_count,
+            "payload": [],
+            "user_expressions": {},
+        }
+
+
+if __name__ == "__main__":
+    from ipykernel.kernelapp import IPKernelApp
+
+    IPKernelApp.launch_instance(kernel_class=StacyKernel)
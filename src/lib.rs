@@ -4,15 +4,29 @@
 #![allow(clippy::enum_variant_names)] // Error types have Error suffix intentionally
 #![allow(clippy::upper_case_acronyms)] // SSC, JSON, etc. are standard acronyms
 
+pub mod artifacts;
 pub mod cache;
 pub mod cli;
+pub mod data;
 pub mod deps;
+pub mod diagnostics;
+pub mod dvc;
 pub mod error;
 pub mod executor;
+pub mod history;
+pub mod jobs;
+pub mod kernel;
 pub mod metrics;
 pub mod packages;
+pub mod pipeline;
+pub mod pristine;
 pub mod project;
+pub mod reproducible;
+pub mod schedule;
+pub mod self_update;
+pub mod session;
 pub mod task;
+pub mod telemetry;
 pub mod test;
 pub mod update_check;
 pub mod utils;
@@ -4,17 +4,33 @@
 
 use clap::error::{ContextKind, ContextValue, ErrorKind};
 use clap::{Parser, Subcommand};
+use cli::output_format::OutputFormat;
+use cli::output_types::{CommandOutput, ErrorOutput};
 use std::process;
 
+mod artifacts;
 mod cache;
 mod cli;
+mod data;
 mod deps;
+mod diagnostics;
+mod dvc;
 mod error;
 mod executor;
+mod history;
+mod jobs;
+mod kernel;
 mod metrics;
 mod packages;
+mod pipeline;
+mod pristine;
 mod project;
+mod reproducible;
+mod schedule;
+mod self_update;
+mod session;
 mod task;
+mod telemetry;
 mod test;
 mod update_check;
 mod utils;
@@ -37,6 +53,41 @@ Getting started:
 
 Docs: https://stacy.janfasnacht.com")]
 struct Cli {
+    /// Print less: no progress or summary lines, only failures
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Print more: failed downloads, streamed logs, full failure context.
+    /// -vvv adds stacy's internal diagnostics (debug), -vvvv trace
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Also write stacy's internal diagnostics to this file as JSON lines,
+    /// for bug reports
+    #[arg(long, global = true, value_name = "PATH")]
+    log_file: Option<std::path::PathBuf>,
+
+    /// Use the project at this directory instead of searching up from the
+    /// current directory. A file must be the project's own stacy.toml (or
+    /// stacy.lock): other config files are not accepted
+    #[arg(long, global = true, value_name = "PATH")]
+    project: Option<std::path::PathBuf>,
+
+    /// Use the packages of this `[profiles.<name>]` in stacy.toml, locked in
+    /// stacy.<name>.lock. STACY_PROFILE does the same
+    #[arg(long, global = true, value_name = "NAME")]
+    env_profile: Option<String>,
+
+    /// Which project wins when one lives inside another: the nearest
+    /// (default) or the outermost
+    #[arg(long, global = true, value_enum, value_name = "POLICY")]
+    nested_projects: Option<project::root::NestedPolicy>,
+
+    /// When another stacy process is changing the project or the package
+    /// cache, wait for it instead of failing
+    #[arg(long, global = true)]
+    wait: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -53,6 +104,24 @@ enum Commands {
     /// Run tests by convention
     #[command(display_order = 3)]
     Test(cli::test::TestArgs),
+    /// Build out-of-date pipeline stages from stacy.toml
+    #[command(display_order = 4)]
+    Build(cli::build::BuildArgs),
+    /// Run any command with the project's package environment
+    #[command(display_order = 5)]
+    Exec(cli::exec::ExecArgs),
+    /// Start interactive Stata with the project's locked packages
+    #[command(display_order = 6)]
+    Shell(cli::shell::ShellArgs),
+    /// Watch a package under development and re-run a smoke script or tests
+    #[command(display_order = 7)]
+    Dev(cli::dev::DevArgs),
+    /// Submit a script or task to a SLURM or PBS cluster as a batch job
+    #[command(display_order = 8)]
+    Submit(cli::submit::SubmitArgs),
+    /// Show jobs submitted with `stacy submit`, and their output
+    #[command(display_order = 9)]
+    Jobs(cli::jobs::JobsArgs),
     // === Project (10-19) ===
     /// Initialize a new stacy project
     #[command(display_order = 10)]
@@ -60,6 +129,24 @@ enum Commands {
     /// Show dependency tree for a script
     #[command(display_order = 11)]
     Deps(cli::deps::DepsArgs),
+    /// Show and remove files produced by tasks and pipeline stages
+    #[command(display_order = 12)]
+    Outputs(cli::outputs::OutputsArgs),
+    /// Download the input data declared in stacy.toml
+    #[command(display_order = 13)]
+    Data(cli::data::DataArgs),
+    /// Run tasks on their schedule with cron, launchd or systemd
+    #[command(display_order = 14)]
+    Schedule(cli::schedule::ScheduleArgs),
+    /// Show the scripts run, task, build and test ran, and how each went
+    #[command(display_order = 15)]
+    History(cli::history::HistoryArgs),
+    /// Run the last run, task, build or test command again
+    #[command(display_order = 16)]
+    Rerun(cli::rerun::RerunArgs),
+    /// Update stacy.toml and stacy.lock written for an older stacy
+    #[command(display_order = 17)]
+    Migrate(cli::migrate::MigrateArgs),
 
     // === Packages (20-29) ===
     /// Add packages to stacy.toml and install them
@@ -83,6 +170,12 @@ enum Commands {
     /// Generate or verify lockfile from stacy.toml
     #[command(display_order = 26)]
     Lock(cli::lock::LockArgs),
+    /// Write a do-file that installs the locked packages without stacy
+    #[command(display_order = 27)]
+    Export(cli::export::ExportArgs),
+    /// Search SSC packages by name and description
+    #[command(display_order = 28)]
+    Search(cli::search::SearchArgs),
 
     // === Info (30-39) ===
     /// Show current environment configuration
@@ -94,6 +187,9 @@ enum Commands {
     /// Look up Stata error code details
     #[command(display_order = 32)]
     Explain(cli::explain::ExplainArgs),
+    /// Bundle environment, diagnostics, config and the failing log for a bug report
+    #[command(display_order = 33)]
+    Report(cli::report::ReportArgs),
 
     // === Advanced (40-49) ===
     /// Manage the build cache
@@ -102,6 +198,165 @@ enum Commands {
     /// Benchmark script execution
     #[command(display_order = 41)]
     Bench(cli::bench::BenchArgs),
+    /// Manage the stacy installation
+    #[command(name = "self", display_order = 42)]
+    SelfCmd(cli::self_update::SelfArgs),
+    /// Read or change user settings
+    #[command(display_order = 43)]
+    Config(cli::config::ConfigArgs),
+    /// Show anonymous usage metrics settings
+    #[command(display_order = 44)]
+    Telemetry(cli::telemetry::TelemetryArgs),
+    /// Run Stata in Jupyter notebooks through stacy
+    #[command(display_order = 45)]
+    Kernel(cli::kernel::KernelArgs),
+    /// Serve run, deps, lint and task requests to editor plugins
+    #[command(display_order = 46)]
+    Daemon(cli::daemon::DaemonArgs),
+    /// Export run, test, cache and install totals
+    #[command(display_order = 47)]
+    Metrics(cli::metrics::MetricsArgs),
+}
+
+impl Commands {
+    /// Command name as recorded by telemetry
+    fn name(&self) -> &'static str {
+        match self {
+            Commands::Run(_) => "run",
+            Commands::Task(_) => "task",
+            Commands::Test(_) => "test",
+            Commands::Build(_) => "build",
+            Commands::Exec(_) => "exec",
+            Commands::Shell(_) => "shell",
+            Commands::Dev(_) => "dev",
+            Commands::Submit(_) => "submit",
+            Commands::Jobs(_) => "jobs",
+            Commands::Init(_) => "init",
+            Commands::Deps(_) => "deps",
+            Commands::Add(_) => "add",
+            Commands::Remove(_) => "remove",
+            Commands::Install(_) => "install",
+            Commands::Update(_) => "update",
+            Commands::List(_) => "list",
+            Commands::Outdated(_) => "outdated",
+            Commands::Lock(_) => "lock",
+            Commands::Export(_) => "export",
+            Commands::Search(_) => "search",
+            Commands::Env(_) => "env",
+            Commands::Doctor(_) => "doctor",
+            Commands::Explain(_) => "explain",
+            Commands::Report(_) => "report",
+            Commands::Cache(_) => "cache",
+            Commands::Bench(_) => "bench",
+            Commands::SelfCmd(_) => "self",
+            Commands::Config(_) => "config",
+            Commands::Telemetry(_) => "telemetry",
+            Commands::Kernel(_) => "kernel",
+            Commands::Daemon(_) => "daemon",
+            Commands::Metrics(_) => "metrics",
+            Commands::Outputs(_) => "outputs",
+            Commands::Data(_) => "data",
+            Commands::Schedule(_) => "schedule",
+            Commands::History(_) => "history",
+            Commands::Rerun(_) => "rerun",
+            Commands::Migrate(_) => "migrate",
+        }
+    }
+
+    /// Hand the global `-q` and `-v` to the commands that print less or more
+    fn apply_global(&mut self, quiet: bool, verbose: u8) {
+        let verbose_flag = verbose > 0;
+        match self {
+            Commands::Run(args) => {
+                args.quiet = quiet;
+                args.verbose = verbose;
+            }
+            Commands::Test(args) => {
+                args.quiet = quiet;
+                args.verbose |= verbose_flag;
+            }
+            Commands::Add(args) => {
+                args.quiet = quiet;
+                args.verbose = verbose_flag;
+            }
+            Commands::Install(args) => {
+                args.quiet = quiet;
+                args.verbose = verbose_flag;
+            }
+            Commands::Update(args) => {
+                args.quiet = quiet;
+                args.verbose = verbose_flag;
+            }
+            Commands::Lock(args) => {
+                args.quiet = quiet;
+                args.verbose = verbose_flag;
+            }
+            Commands::Remove(args) => args.quiet = quiet,
+            Commands::Outdated(args) => args.verbose = verbose_flag,
+            Commands::Bench(args) => args.quiet = quiet,
+            Commands::Cache(args) => {
+                if let cli::cache::CacheCommand::Clean(clean) = &mut args.command {
+                    clean.quiet = quiet;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The `--format` asked for, as far as reporting an error goes
+    fn format(&self) -> OutputFormat {
+        match self {
+            Commands::Run(args) => args.format.output(),
+            Commands::Task(args) => args.format.output(),
+            Commands::Test(args) => args.format.output(),
+            Commands::Build(args) => args.format,
+            Commands::Init(args) => args.format,
+            Commands::Deps(args) => args.format,
+            Commands::Add(args) => args.format,
+            Commands::Remove(args) => args.format,
+            Commands::Install(args) => args.format,
+            Commands::Update(args) => args.format,
+            Commands::List(args) => args.format.output(),
+            Commands::Outdated(args) => args.format.output(),
+            Commands::Lock(args) => args.format,
+            Commands::Search(args) => args.format,
+            Commands::Env(args) => args.format,
+            Commands::Doctor(args) => args.format,
+            Commands::Explain(args) => args.format,
+            Commands::Cache(args) => args.format(),
+            Commands::Bench(args) => args.format.output(),
+            Commands::Metrics(args) => args.format(),
+            Commands::Outputs(args) => args.format(),
+            Commands::Data(args) => args.format(),
+            Commands::Submit(args) => args.format,
+            Commands::Jobs(args) => args.format,
+            Commands::History(args) => args.format(),
+            Commands::Config(args) => args.format(),
+            Commands::Exec(_)
+            | Commands::Shell(_)
+            | Commands::Dev(_)
+            | Commands::Export(_)
+            | Commands::Report(_)
+            | Commands::SelfCmd(_)
+            | Commands::Telemetry(_)
+            | Commands::Kernel(_)
+            | Commands::Daemon(_)
+            | Commands::Schedule(_)
+            | Commands::Rerun(_)
+            | Commands::Migrate(_) => OutputFormat::Human,
+        }
+    }
+}
+
+/// Report a failed command: a JSON error object under `--format json` (and
+/// the formats built on it), else `Error: ...`. Either way on stderr, so it
+/// never mixes with a result document a command already wrote to stdout.
+fn report_error(error: &error::Error, format: OutputFormat, exit_code: i32) {
+    if format == OutputFormat::Json {
+        eprintln!("{}", ErrorOutput::new(error, exit_code).to_json());
+    } else {
+        eprintln!("Error: {}", error);
+    }
 }
 
 /// Handle clap parse errors with custom suggestions for common mistakes
@@ -160,10 +415,37 @@ fn handle_parse_error(mut err: clap::Error) -> ! {
 fn main() {
     update_check::maybe_notify_and_spawn();
 
-    let cli = match Cli::try_parse() {
+    let mut cli = match Cli::try_parse() {
         Ok(cli) => cli,
         Err(e) => handle_parse_error(e),
     };
+    cli.command.apply_global(cli.quiet, cli.verbose);
+    if let Err(e) = diagnostics::init(cli.verbose, cli.log_file.as_deref()) {
+        report_error(&e, cli.command.format(), 1);
+        process::exit(1);
+    }
+    let env_profile = cli.env_profile.clone().or_else(|| {
+        std::env::var("STACY_PROFILE")
+            .ok()
+            .filter(|v| !v.is_empty())
+    });
+    if let Some(name) = &env_profile {
+        if let Err(e) = project::profile::set_active(name) {
+            report_error(&e, cli.command.format(), 1);
+            process::exit(1);
+        }
+    }
+    if let Some(policy) = cli.nested_projects {
+        project::root::set_nested_policy(policy);
+    }
+    if let Some(path) = &cli.project {
+        if let Err(e) = project::root::set_project_override(path) {
+            report_error(&e, cli.command.format(), 1);
+            process::exit(1);
+        }
+    }
+    utils::lock::set_wait(cli.wait);
+    session::begin(cli.command.name());
 
     let result = match &cli.command {
         Commands::Run(args) => cli::run::execute(args),
@@ -176,18 +458,43 @@ fn main() {
         Commands::List(args) => cli::list::execute(args),
         Commands::Outdated(args) => cli::outdated::execute(args),
         Commands::Lock(args) => cli::lock::execute(args),
+        Commands::Export(args) => cli::export::execute(args),
+        Commands::Search(args) => cli::search::execute(args),
         Commands::Deps(args) => cli::deps::execute(args),
         Commands::Env(args) => cli::env::execute(args),
         Commands::Doctor(args) => cli::doctor::execute(args),
+        Commands::Report(args) => cli::report::execute(args),
         Commands::Explain(args) => cli::explain::execute(args),
         Commands::Task(args) => cli::task::execute(args),
         Commands::Test(args) => cli::test::execute(args),
+        Commands::Build(args) => cli::build::execute(args),
+        Commands::Exec(args) => cli::exec::execute(args),
+        Commands::Shell(args) => cli::shell::execute(args),
+        Commands::Dev(args) => cli::dev::execute(args),
+        Commands::Submit(args) => cli::submit::execute(args),
+        Commands::Jobs(args) => cli::jobs::execute(args),
         Commands::Cache(args) => cli::cache::execute(args),
         Commands::Bench(args) => cli::bench::execute(args),
+        Commands::SelfCmd(args) => cli::self_update::execute(args),
+        Commands::Config(args) => cli::config::execute(args),
+        Commands::Telemetry(args) => cli::telemetry::execute(args),
+        Commands::Kernel(args) => cli::kernel::execute(args),
+        Commands::Daemon(args) => cli::daemon::execute(args),
+        Commands::Metrics(args) => cli::metrics::execute(args),
+        Commands::Outputs(args) => cli::outputs::execute(args),
+        Commands::Data(args) => cli::data::execute(args),
+        Commands::Schedule(args) => cli::schedule::execute(args),
+        Commands::History(args) => cli::history::execute(args),
+        Commands::Rerun(args) => cli::rerun::execute(args),
+        Commands::Migrate(args) => cli::migrate::execute(args),
     };
 
-    if let Err(e) = result {
-        eprintln!("Error: {}", e);
-        process::exit(1);
+    match result {
+        Ok(()) => session::finish(None),
+        Err(e) => {
+            report_error(&e, cli.command.format(), 1);
+            session::finish(Some(e.category()));
+            process::exit(error::exit_codes::active().remap(1));
+        }
     }
 }
@@ -0,0 +1,305 @@
+//! Running totals of a project's runs, tests, cache lookups and installs
+//!
+//! Every invocation inside a project adds what it did to
+//! `.stacy/metrics.json`: the scripts `stacy run`, `task` and `build` ran, the
+//! tests `stacy test` ran, build-cache hits and misses, and package installs,
+//! each with its outcome and duration. `stacy metrics export` turns the totals
+//! into Prometheus metrics; with `metrics.textfile_dir` set in the user config
+//! they are also rewritten there after every invocation, for node_exporter's
+//! textfile collector.
+//!
+//! Samples gather in memory while the command runs and are written once, as
+//! it exits. Like the test history, the totals only inform: failing to read or
+//! write them never fails a command.
+
+use crate::history::unix_now;
+use crate::project::root::find_project_root_from_cwd;
+use crate::project::user_config::{get_config_path, read_user_config};
+use crate::utils::lock::with_exclusive_lock;
+use crate::utils::paths::write_atomically;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Totals file within the project root
+const METRICS_FILE: &str = ".stacy/metrics.json";
+
+/// Lock serializing concurrent stacy processes' updates
+const METRICS_LOCK: &str = ".stacy/metrics.lock";
+
+/// Totals format version; totals of another version start over
+const METRICS_VERSION: u32 = 1;
+
+/// Outcomes noted by this process, not yet added to the totals
+static PENDING: Mutex<Pending> = Mutex::new(Pending {
+    scripts: Vec::new(),
+    cache_hits: 0,
+    cache_misses: 0,
+});
+
+struct Pending {
+    scripts: Vec<(bool, Duration)>,
+    cache_hits: u64,
+    cache_misses: u64,
+}
+
+/// Successes, failures and time spent
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Tally {
+    pub succeeded: u64,
+    pub failed: u64,
+    pub seconds: f64,
+}
+
+impl Tally {
+    fn add(&mut self, success: bool, duration: Duration) {
+        if success {
+            self.succeeded += 1;
+        } else {
+            self.failed += 1;
+        }
+        self.seconds += duration.as_secs_f64();
+    }
+}
+
+/// A project's totals since they were first recorded
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Totals {
+    version: u32,
+    /// Scripts run by `stacy run`, `task` and `build`
+    pub runs: Tally,
+    /// Tests run by `stacy test`
+    pub tests: Tally,
+    /// `stacy install`, `add` and `update` invocations
+    pub installs: Tally,
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+    /// When a script or test last ran, in seconds since the Unix epoch
+    pub last_run: Option<u64>,
+}
+
+impl Totals {
+    /// The totals of the project at `project_root`; zero if none are recorded
+    pub fn load(project_root: &Path) -> Self {
+        std::fs::read(project_root.join(METRICS_FILE))
+            .ok()
+            .and_then(|content| serde_json::from_slice::<Totals>(&content).ok())
+            .filter(|totals| totals.version == METRICS_VERSION)
+            .unwrap_or_default()
+    }
+
+    fn save(&self, project_root: &Path) -> std::io::Result<()> {
+        let content = serde_json::to_vec_pretty(&Totals {
+            version: METRICS_VERSION,
+            ..self.clone()
+        })?;
+        write_atomically(&project_root.join(METRICS_FILE), &content)
+    }
+
+    /// Add what `command` did: its scripts, cache lookups and, for the
+    /// package commands, the install itself
+    fn add(&mut self, command: &str, success: bool, duration: Duration, pending: &Pending) {
+        let scripts = match command {
+            "run" | "task" | "build" => Some(&mut self.runs),
+            "test" => Some(&mut self.tests),
+            _ => None,
+        };
+        if let Some(tally) = scripts {
+            for &(success, duration) in &pending.scripts {
+                tally.add(success, duration);
+            }
+            if !pending.scripts.is_empty() {
+                self.last_run = Some(unix_now());
+            }
+        }
+        if matches!(command, "install" | "add" | "update") {
+            self.installs.add(success, duration);
+        }
+        self.cache_hits += pending.cache_hits;
+        self.cache_misses += pending.cache_misses;
+    }
+}
+
+/// Note a script Stata ran
+pub fn note_script(success: bool, duration: Duration) {
+    if let Ok(mut pending) = PENDING.lock() {
+        pending.scripts.push((success, duration));
+    }
+}
+
+/// Note a build-cache lookup
+pub fn note_cache(hit: bool) {
+    if let Ok(mut pending) = PENDING.lock() {
+        if hit {
+            pending.cache_hits += 1;
+        } else {
+            pending.cache_misses += 1;
+        }
+    }
+}
+
+/// Add this invocation of `command` to the project's totals. Called once, as
+/// the command exits.
+pub fn finish(command: &str, success: bool, duration: Duration) {
+    let Ok(pending) = PENDING.lock() else {
+        return;
+    };
+    let installs = matches!(command, "install" | "add" | "update");
+    if pending.scripts.is_empty()
+        && pending.cache_hits == 0
+        && pending.cache_misses == 0
+        && !installs
+    {
+        return;
+    }
+    let Ok(Some(root)) = find_project_root_from_cwd() else {
+        return;
+    };
+    let root = root.path;
+
+    let _ = with_exclusive_lock(&root.join(METRICS_LOCK), || {
+        let mut totals = Totals::load(&root);
+        totals.add(command, success, duration, &pending);
+        if totals.save(&root).is_ok() {
+            if let Some(dir) = textfile_dir() {
+                let _ = write_textfile(&dir, &root, &totals);
+            }
+        }
+    });
+}
+
+/// Name of the project in metric labels: `[project] name`, or else the name
+/// of its directory
+pub fn project_name(project_root: &Path) -> String {
+    crate::project::config::load_config(project_root)
+        .ok()
+        .flatten()
+        .and_then(|config| config.project.name)
+        .or_else(|| {
+            project_root
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+        })
+        .unwrap_or_default()
+}
+
+/// `metrics.textfile_dir` from the user config
+fn textfile_dir() -> Option<PathBuf> {
+    get_config_path()
+        .and_then(|path| read_user_config(&path).ok().flatten())
+        .and_then(|config| config.metrics.textfile_dir)
+}
+
+/// Write the totals as `stacy-<project>.prom` in a textfile collector's
+/// directory. node_exporter may read the directory at any moment, so the file
+/// is replaced whole.
+pub fn write_textfile(
+    dir: &Path,
+    project_root: &Path,
+    totals: &Totals,
+) -> std::io::Result<PathBuf> {
+    let project = project_name(project_root);
+    let file_stem: String = project
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let path = dir.join(format!("stacy-{}.prom", file_stem));
+    write_atomically(
+        &path,
+        super::prometheus::render(&project, totals).as_bytes(),
+    )?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn pending(scripts: &[(bool, u64)], hits: u64, misses: u64) -> Pending {
+        Pending {
+            scripts: scripts
+                .iter()
+                .map(|&(ok, secs)| (ok, Duration::from_secs(secs)))
+                .collect(),
+            cache_hits: hits,
+            cache_misses: misses,
+        }
+    }
+
+    #[test]
+    fn test_scripts_counted_by_command() {
+        let mut totals = Totals::default();
+        totals.add(
+            "run",
+            false,
+            Duration::from_secs(9),
+            &pending(&[(true, 2), (false, 3)], 1, 2),
+        );
+        totals.add(
+            "test",
+            true,
+            Duration::from_secs(1),
+            &pending(&[(true, 1)], 0, 0),
+        );
+        // Bench runs are neither runs nor tests
+        totals.add(
+            "bench",
+            true,
+            Duration::from_secs(5),
+            &pending(&[(true, 5)], 0, 0),
+        );
+        totals.add(
+            "install",
+            false,
+            Duration::from_secs(4),
+            &pending(&[], 0, 0),
+        );
+
+        assert_eq!((totals.runs.succeeded, totals.runs.failed), (1, 1));
+        assert_eq!(totals.runs.seconds, 5.0);
+        assert_eq!((totals.tests.succeeded, totals.tests.failed), (1, 0));
+        assert_eq!((totals.installs.succeeded, totals.installs.failed), (0, 1));
+        assert_eq!((totals.cache_hits, totals.cache_misses), (1, 2));
+        assert!(totals.last_run.is_some());
+    }
+
+    #[test]
+    fn test_totals_round_trip() {
+        let temp = TempDir::new().unwrap();
+        let mut totals = Totals::default();
+        totals.add("run", true, Duration::ZERO, &pending(&[(true, 2)], 0, 1));
+        // A sum of durations that only survives with exact float parsing
+        totals.tests.seconds = 0.0038831910000000002;
+        totals.save(temp.path()).unwrap();
+
+        assert_eq!(
+            Totals::load(temp.path()),
+            Totals {
+                version: METRICS_VERSION,
+                ..totals
+            }
+        );
+    }
+
+    #[test]
+    fn test_textfile_named_after_project() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path().join("proj");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("stacy.toml"), "[project]\nname = \"wage gap\"\n").unwrap();
+
+        let path =
+            write_textfile(&temp.path().join("collector"), &root, &Totals::default()).unwrap();
+        assert!(path.ends_with("collector/stacy-wage_gap.prom"));
+        let content = std::fs::read_to_string(path).unwrap();
+        assert!(content.contains("project=\"wage gap\""));
+    }
+}
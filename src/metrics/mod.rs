@@ -14,6 +14,12 @@
 //! // ... spawn process
 //! metrics.end_phase("spawn");
 //! ```
+//!
+//! [`counters`] keeps a project's running totals across invocations, which
+//! [`prometheus`] renders for dashboards.
+
+pub mod counters;
+pub mod prometheus;
 
 use std::collections::HashMap;
 use std::time::{Duration, Instant};
@@ -0,0 +1,127 @@
+//! Prometheus text exposition of a project's totals
+//!
+//! See <https://prometheus.io/docs/instrumenting/exposition_formats/>. Every
+//! metric carries a `project` label, so the files of several projects can sit
+//! side by side in one textfile collector directory.
+
+use super::counters::Totals;
+use std::fmt::Write;
+
+/// The totals of `project` in the Prometheus text format
+pub fn render(project: &str, totals: &Totals) -> String {
+    let project = format!("project=\"{}\"", escape_label(project));
+    let mut out = String::new();
+
+    let mut metric = |name: &str, kind: &str, help: &str, samples: &[(&str, String)]| {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        for (labels, value) in samples {
+            let _ = writeln!(out, "{}{{{}{}}} {}", name, project, labels, value);
+        }
+    };
+
+    metric(
+        "stacy_runs_total",
+        "counter",
+        "Scripts run by stacy run, task and build.",
+        &[
+            (",status=\"success\"", totals.runs.succeeded.to_string()),
+            (",status=\"failure\"", totals.runs.failed.to_string()),
+        ],
+    );
+    metric(
+        "stacy_run_duration_seconds_total",
+        "counter",
+        "Time Stata spent on scripts run by stacy run, task and build.",
+        &[("", totals.runs.seconds.to_string())],
+    );
+    metric(
+        "stacy_tests_total",
+        "counter",
+        "Tests run by stacy test.",
+        &[
+            (",status=\"passed\"", totals.tests.succeeded.to_string()),
+            (",status=\"failed\"", totals.tests.failed.to_string()),
+        ],
+    );
+    metric(
+        "stacy_test_duration_seconds_total",
+        "counter",
+        "Time Stata spent on tests.",
+        &[("", totals.tests.seconds.to_string())],
+    );
+    metric(
+        "stacy_cache_hits_total",
+        "counter",
+        "Build-cache lookups that skipped a script.",
+        &[("", totals.cache_hits.to_string())],
+    );
+    metric(
+        "stacy_cache_misses_total",
+        "counter",
+        "Build-cache lookups that ran the script.",
+        &[("", totals.cache_misses.to_string())],
+    );
+    metric(
+        "stacy_installs_total",
+        "counter",
+        "Package installs by stacy install, add and update.",
+        &[
+            (",status=\"success\"", totals.installs.succeeded.to_string()),
+            (",status=\"failure\"", totals.installs.failed.to_string()),
+        ],
+    );
+    metric(
+        "stacy_install_duration_seconds_total",
+        "counter",
+        "Time spent installing packages.",
+        &[("", totals.installs.seconds.to_string())],
+    );
+    if let Some(last_run) = totals.last_run {
+        metric(
+            "stacy_last_run_timestamp_seconds",
+            "gauge",
+            "When a script or test last ran, in seconds since the Unix epoch.",
+            &[("", last_run.to_string())],
+        );
+    }
+
+    out
+}
+
+/// Label values escape backslash, double quote and newline
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let mut totals = Totals::default();
+        totals.runs.succeeded = 3;
+        totals.runs.failed = 1;
+        totals.runs.seconds = 12.5;
+        totals.cache_hits = 2;
+
+        let text = render("wages", &totals);
+        assert!(text.contains("# TYPE stacy_runs_total counter\n"));
+        assert!(text.contains("stacy_runs_total{project=\"wages\",status=\"success\"} 3\n"));
+        assert!(text.contains("stacy_runs_total{project=\"wages\",status=\"failure\"} 1\n"));
+        assert!(text.contains("stacy_run_duration_seconds_total{project=\"wages\"} 12.5\n"));
+        assert!(text.contains("stacy_cache_hits_total{project=\"wages\"} 2\n"));
+        // Nothing ran yet: no timestamp
+        assert!(!text.contains("stacy_last_run_timestamp_seconds"));
+    }
+
+    #[test]
+    fn test_escape_label() {
+        assert_eq!(escape_label(r#"a "b"\c"#), r#"a \"b\"\\c"#);
+        assert_eq!(escape_label("a\nb"), "a\\nb");
+    }
+}
@@ -0,0 +1,296 @@
+//! Layout versions of the global package cache
+//!
+//! The cache root records the layout it is written in, in a `.layout` file.
+//! A cache without one is new, or was written by a stacy from before the
+//! marker: layout 1. When the layout changes, `stacy cache packages migrate`
+//! brings an existing cache up to date in place, one version at a time,
+//! instead of users wiping it and downloading everything again.
+//!
+//! | Layout | Changes |
+//! |--------|---------|
+//! | 1 | `{name}/{version}/`, names as the package was first installed |
+//! | 2 | names lowercase, a `{name}/{version}.json` metadata file per package recording its checksum, no leftovers of interrupted installs |
+//!
+//! A cache in an older layout still works; a cache in a newer one is left
+//! alone, as this stacy doesn't know what changed.
+
+use crate::error::{Error, Result};
+use crate::packages::global_cache::{cache_lock_path, hash_package_dir};
+use crate::utils::lock::with_state_lock;
+use crate::utils::paths::write_atomically;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Layout this stacy writes
+pub const LAYOUT_VERSION: u32 = 2;
+
+/// File at the cache root holding the layout version
+const LAYOUT_FILE: &str = ".layout";
+
+/// What the cache records about a package version, next to its directory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PackageMetadata {
+    pub name: String,
+    pub version: String,
+    /// `sha256:<hex>` of the package's files, as stacy.lock records it
+    pub checksum: String,
+}
+
+/// What a migration changed
+#[derive(Debug, Clone, Default)]
+pub struct MigrationReport {
+    pub from: u32,
+    pub to: u32,
+    /// Package directories renamed, as `old -> new`
+    pub renamed: Vec<String>,
+    /// Leftovers and duplicates removed
+    pub removed: Vec<PathBuf>,
+    /// Package versions hashed and given a metadata file
+    pub recorded: usize,
+}
+
+impl MigrationReport {
+    pub fn changed(&self) -> bool {
+        self.from != self.to
+    }
+}
+
+/// Layout of the cache at `cache`: `None` for a cache without packages yet
+pub fn detect(cache: &Path) -> Result<Option<u32>> {
+    let marker = cache.join(LAYOUT_FILE);
+    if let Ok(text) = std::fs::read_to_string(&marker) {
+        let version = text.trim().parse::<u32>().map_err(|_| {
+            Error::Config(format!(
+                "Unreadable cache layout marker {}: {:?}",
+                marker.display(),
+                text.trim()
+            ))
+        })?;
+        return Ok(Some(version));
+    }
+    Ok((!package_dirs(cache).is_empty()).then_some(1))
+}
+
+/// Check the cache can be written before installing into it, and mark a new
+/// cache with the current layout. Call with the cache lock held.
+pub fn prepare(cache: &Path) -> Result<()> {
+    match detect(cache)? {
+        None => write_marker(cache),
+        Some(version) if version > LAYOUT_VERSION => Err(newer_layout(cache, version)),
+        Some(_) => Ok(()),
+    }
+}
+
+/// Write the metadata file of the package in `dir`
+pub fn record(dir: &Path, name: &str, version: &str) -> Result<()> {
+    let checksum = hash_package_dir(dir)
+        .ok_or_else(|| Error::Config(format!("Cannot read cached package {}", dir.display())))?;
+    let metadata = PackageMetadata {
+        name: name.to_string(),
+        version: version.to_string(),
+        checksum: format!("sha256:{}", checksum),
+    };
+    write_atomically(
+        &metadata_path(dir),
+        serde_json::to_string_pretty(&metadata)?.as_bytes(),
+    )?;
+    Ok(())
+}
+
+/// The metadata file of the package version in `dir`: `{name}/{version}.json`
+pub fn metadata_path(dir: &Path) -> PathBuf {
+    let version = dir
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    dir.with_file_name(format!("{}.json", version))
+}
+
+/// Bring the cache at `cache` up to [`LAYOUT_VERSION`], holding its lock
+pub fn migrate(cache: &Path) -> Result<MigrationReport> {
+    with_state_lock(&cache_lock_path(cache), || {
+        let from = detect(cache)?.unwrap_or(LAYOUT_VERSION);
+        if from > LAYOUT_VERSION {
+            return Err(newer_layout(cache, from));
+        }
+        let mut report = MigrationReport {
+            from,
+            to: LAYOUT_VERSION,
+            ..MigrationReport::default()
+        };
+        for version in from..LAYOUT_VERSION {
+            match version {
+                1 => migrate_1_to_2(cache, &mut report)?,
+                _ => unreachable!("no layout between 1 and {}", LAYOUT_VERSION),
+            }
+        }
+        if cache.is_dir() {
+            write_marker(cache)?;
+        }
+        Ok(report)
+    })?
+}
+
+/// Lowercase package names, drop interrupted installs, record checksums
+fn migrate_1_to_2(cache: &Path, report: &mut MigrationReport) -> Result<()> {
+    let existing = package_dirs(cache);
+    for dir in &existing {
+        let name = file_name(dir);
+        let lower = name.to_lowercase();
+        if name == lower {
+            continue;
+        }
+        if existing.iter().any(|other| file_name(other) == lower) {
+            // Both spellings: move over the versions the lowercase one lacks
+            for version_dir in subdirs(dir) {
+                let target = cache.join(&lower).join(file_name(&version_dir));
+                if target.exists() {
+                    std::fs::remove_dir_all(&version_dir)?;
+                    report.removed.push(version_dir);
+                } else {
+                    std::fs::rename(&version_dir, &target)?;
+                }
+            }
+            std::fs::remove_dir_all(dir)?;
+        } else {
+            // Through another name, for file systems that ignore case
+            let moving = cache.join(format!(".{}.migrating", lower));
+            std::fs::rename(dir, &moving)?;
+            std::fs::rename(&moving, cache.join(&lower))?;
+        }
+        report.renamed.push(format!("{} -> {}", name, lower));
+    }
+
+    for dir in package_dirs(cache) {
+        let name = file_name(&dir);
+        for version_dir in subdirs(&dir) {
+            let version = file_name(&version_dir);
+            if version.contains(".downloading") {
+                std::fs::remove_dir_all(&version_dir)?;
+                report.removed.push(version_dir);
+                continue;
+            }
+            record(&version_dir, &name, &version)?;
+            report.recorded += 1;
+        }
+    }
+    Ok(())
+}
+
+fn write_marker(cache: &Path) -> Result<()> {
+    write_atomically(
+        &cache.join(LAYOUT_FILE),
+        format!("{}\n", LAYOUT_VERSION).as_bytes(),
+    )?;
+    Ok(())
+}
+
+fn newer_layout(cache: &Path, version: u32) -> Error {
+    Error::Config(format!(
+        "The package cache at {} is in layout {}, newer than this stacy knows ({}). Upgrade stacy to use it.",
+        cache.display(),
+        version,
+        LAYOUT_VERSION
+    ))
+}
+
+/// Package name directories, leaving out stacy's own (`.staging`, `.locks`)
+fn package_dirs(cache: &Path) -> Vec<PathBuf> {
+    subdirs(cache)
+        .into_iter()
+        .filter(|dir| !file_name(dir).starts_with('.'))
+        .collect()
+}
+
+fn subdirs(dir: &Path) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok().map(|e| e.path()))
+                .filter(|p| p.is_dir())
+                .collect()
+        })
+        .unwrap_or_default();
+    dirs.sort();
+    dirs
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn package(cache: &Path, name: &str, version: &str, content: &str) {
+        let dir = cache.join(name).join(version);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(format!("{}.ado", name.to_lowercase())), content).unwrap();
+    }
+
+    #[test]
+    fn test_detect() {
+        let temp = TempDir::new().unwrap();
+        assert_eq!(detect(temp.path()).unwrap(), None);
+        assert_eq!(detect(&temp.path().join("missing")).unwrap(), None);
+
+        fs::create_dir_all(temp.path().join(".staging")).unwrap();
+        assert_eq!(detect(temp.path()).unwrap(), None);
+
+        package(temp.path(), "estout", "1.0", "*! estout");
+        assert_eq!(detect(temp.path()).unwrap(), Some(1));
+
+        prepare(temp.path()).unwrap();
+        assert_eq!(detect(temp.path()).unwrap(), Some(1), "old caches stay put");
+
+        fs::write(temp.path().join(LAYOUT_FILE), "9\n").unwrap();
+        assert!(prepare(temp.path()).is_err());
+        assert!(migrate(temp.path()).is_err());
+    }
+
+    #[test]
+    fn test_migrate_1_to_2() {
+        let temp = TempDir::new().unwrap();
+        let cache = temp.path();
+        package(cache, "MyPkg", "1.0", "*! mypkg 1.0");
+        package(cache, "Estout", "2.0", "*! estout 2.0, mixed case");
+        package(cache, "estout", "1.0", "*! estout 1.0");
+        package(cache, "estout", "2.0", "*! estout 2.0");
+        fs::create_dir_all(cache.join("estout/3.0.downloading-x1")).unwrap();
+
+        let report = migrate(cache).unwrap();
+        assert_eq!((report.from, report.to), (1, 2));
+        assert_eq!(report.renamed, vec!["Estout -> estout", "MyPkg -> mypkg"]);
+        assert_eq!(report.removed.len(), 2);
+        assert_eq!(report.recorded, 3);
+
+        assert_eq!(detect(cache).unwrap(), Some(2));
+        assert!(cache.join("mypkg/1.0/mypkg.ado").is_file());
+        assert!(!cache.join("estout/3.0.downloading-x1").exists());
+        assert_eq!(
+            fs::read_to_string(cache.join("estout/2.0/estout.ado")).unwrap(),
+            "*! estout 2.0"
+        );
+        let metadata: PackageMetadata =
+            serde_json::from_str(&fs::read_to_string(cache.join("mypkg/1.0.json")).unwrap())
+                .unwrap();
+        assert_eq!(metadata.name, "mypkg");
+        assert_eq!(
+            metadata.checksum,
+            format!(
+                "sha256:{}",
+                hash_package_dir(&cache.join("mypkg/1.0")).unwrap()
+            )
+        );
+
+        // Nothing left to do
+        let again = migrate(cache).unwrap();
+        assert!(!again.changed());
+        assert_eq!(again.recorded, 0);
+    }
+}
@@ -0,0 +1,222 @@
+//! Ado name collisions between packages
+//!
+//! Stata finds a command by searching the adopath for `<command>.ado` and runs
+//! the first match. When two locked packages ship the same `.ado` file, the
+//! package that sorts first on S_ADO silently shadows the other, and a script
+//! runs a command from a package it never asked for. `lock`, `install`, `add`
+//! and `doctor` look for such files; `[packages] on_collision` decides whether
+//! one is an error (the default) or a warning.
+//!
+//! Copies that are byte-identical are not collisions: packages often bundle
+//! the same helper, and whichever copy wins, the same code runs.
+
+use crate::error::{Error, Result};
+use crate::packages::global_cache;
+use crate::packages::ssc::DownloadedFile;
+use crate::project::config::CollisionPolicy;
+use crate::project::Lockfile;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// The `.ado` files one package provides: lowercase file name -> SHA256
+pub type AdoFiles = BTreeMap<String, String>;
+
+/// An `.ado` file shipped, with different contents, by more than one package
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Collision {
+    /// The file name, e.g. `esttab.ado`
+    pub file: String,
+    /// The packages providing it, in S_ADO order: the first one wins
+    pub packages: Vec<String>,
+}
+
+impl Collision {
+    /// The package whose copy Stata runs
+    pub fn winner(&self) -> &str {
+        &self.packages[0]
+    }
+
+    /// One-line description, naming the copy that wins
+    pub fn describe(&self) -> String {
+        format!(
+            "{} is provided by {}; {} shadows the others",
+            self.file,
+            self.packages.join(", "),
+            self.winner()
+        )
+    }
+}
+
+/// The `.ado` files among downloaded package files
+pub fn ado_files_from_download(files: &[DownloadedFile]) -> AdoFiles {
+    files
+        .iter()
+        .filter_map(|file| {
+            let name = Path::new(&file.name).file_name()?.to_str()?.to_lowercase();
+            is_ado(&name).then(|| (name, hex::encode(Sha256::digest(&file.content))))
+        })
+        .collect()
+}
+
+/// The `.ado` files in a package's cache directory
+pub fn ado_files_in_dir(dir: &Path) -> AdoFiles {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return AdoFiles::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_str()?.to_lowercase();
+            if !is_ado(&name) {
+                return None;
+            }
+            let content = std::fs::read(entry.path()).ok()?;
+            Some((name, hex::encode(Sha256::digest(&content))))
+        })
+        .collect()
+}
+
+fn is_ado(file_name: &str) -> bool {
+    file_name.ends_with(".ado")
+}
+
+/// Find the `.ado` files more than one package provides with different
+/// contents. Packages are taken in name order, the order of S_ADO.
+pub fn find_collisions(packages: &BTreeMap<String, AdoFiles>) -> Vec<Collision> {
+    let mut providers: BTreeMap<&str, Vec<(&str, &str)>> = BTreeMap::new();
+    for (package, files) in packages {
+        for (file, hash) in files {
+            providers
+                .entry(file.as_str())
+                .or_default()
+                .push((package.as_str(), hash.as_str()));
+        }
+    }
+
+    providers
+        .into_iter()
+        .filter(|(_, copies)| copies.iter().any(|(_, hash)| *hash != copies[0].1))
+        .map(|(file, copies)| Collision {
+            file: file.to_string(),
+            packages: copies.iter().map(|(pkg, _)| pkg.to_string()).collect(),
+        })
+        .collect()
+}
+
+/// The `.ado` files of every locked package present in the cache, optionally
+/// only those in the given dependency groups
+pub fn locked_ado_files(
+    lockfile: &Lockfile,
+    groups: Option<&[&str]>,
+) -> BTreeMap<String, AdoFiles> {
+    lockfile
+        .packages
+        .iter()
+        .filter(|(_, entry)| groups.is_none_or(|g| g.contains(&entry.group.as_str())))
+        .filter_map(|(name, entry)| {
+            let dir = global_cache::locate_package(name, &entry.version).ok()??;
+            Some((name.clone(), ado_files_in_dir(&dir)))
+        })
+        .collect()
+}
+
+/// Apply the collision policy: an error listing every collision under
+/// `error`, a warning on stderr under `warn`
+pub fn enforce(collisions: &[Collision], policy: CollisionPolicy) -> Result<()> {
+    if collisions.is_empty() {
+        return Ok(());
+    }
+    match policy {
+        CollisionPolicy::Error => Err(Error::Config(format!(
+            "{} ado file(s) provided by more than one package:\n{}\n\n  \
+             Stata would run whichever copy comes first on the adopath.\n  \
+             hint: remove one of the packages, or set `on_collision = \"warn\"` under \
+             [packages] in stacy.toml to accept the first copy",
+            collisions.len(),
+            collisions
+                .iter()
+                .map(|c| format!("  - {}", c.describe()))
+                .collect::<Vec<_>>()
+                .join("\n")
+        ))),
+        CollisionPolicy::Warn => {
+            use colored::Colorize;
+            for collision in collisions {
+                eprintln!("{} {}", "warning:".yellow().bold(), collision.describe());
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(entries: &[(&str, &str)]) -> AdoFiles {
+        entries
+            .iter()
+            .map(|(f, h)| (f.to_string(), h.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_find_collisions_orders_by_package() {
+        let mut packages = BTreeMap::new();
+        packages.insert(
+            "estout".to_string(),
+            files(&[("esttab.ado", "a"), ("estout.ado", "b")]),
+        );
+        packages.insert("myestout".to_string(), files(&[("esttab.ado", "c")]));
+        packages.insert("other".to_string(), files(&[("other.ado", "d")]));
+
+        let collisions = find_collisions(&packages);
+        assert_eq!(
+            collisions,
+            vec![Collision {
+                file: "esttab.ado".to_string(),
+                packages: vec!["estout".to_string(), "myestout".to_string()],
+            }]
+        );
+        assert_eq!(collisions[0].winner(), "estout");
+    }
+
+    #[test]
+    fn test_identical_copies_do_not_collide() {
+        let mut packages = BTreeMap::new();
+        packages.insert("a".to_string(), files(&[("helper.ado", "same")]));
+        packages.insert("b".to_string(), files(&[("helper.ado", "same")]));
+        assert!(find_collisions(&packages).is_empty());
+    }
+
+    #[test]
+    fn test_ado_files_from_download() {
+        let downloaded = vec![
+            DownloadedFile {
+                name: "../e/EstTab.ado".to_string(),
+                content: b"program esttab\nend\n".to_vec(),
+                checksum: String::new(),
+            },
+            DownloadedFile {
+                name: "esttab.sthlp".to_string(),
+                content: b"help".to_vec(),
+                checksum: String::new(),
+            },
+        ];
+        let ado = ado_files_from_download(&downloaded);
+        assert_eq!(ado.keys().collect::<Vec<_>>(), vec!["esttab.ado"]);
+    }
+
+    #[test]
+    fn test_enforce_policy() {
+        let collisions = vec![Collision {
+            file: "esttab.ado".to_string(),
+            packages: vec!["a".to_string(), "b".to_string()],
+        }];
+        let err = enforce(&collisions, CollisionPolicy::Error).unwrap_err();
+        assert!(err.to_string().contains("esttab.ado is provided by a, b"));
+        assert!(enforce(&collisions, CollisionPolicy::Warn).is_ok());
+        assert!(enforce(&[], CollisionPolicy::Error).is_ok());
+    }
+}
@@ -8,6 +8,7 @@
 //! References to files the package ships itself are internal, not dependencies,
 //! so they are excluded — see [`provided_names`].
 
+use crate::packages::ssc::DownloadedFile;
 use regex::Regex;
 use std::collections::HashSet;
 use std::path::Path;
@@ -179,6 +180,25 @@ pub fn scan_package_deps(package_dir: &Path) -> Vec<String> {
     result
 }
 
+/// [`scan_package_deps`] for files fetched but not saved anywhere yet
+fn scan_file_deps(files: &[DownloadedFile]) -> Vec<String> {
+    let builtins: HashSet<&str> = BUILTINS.iter().copied().collect();
+    let mut result: Vec<String> = files
+        .iter()
+        .filter(|file| {
+            Path::new(&file.name)
+                .extension()
+                .is_some_and(|ext| ext == "ado")
+        })
+        .flat_map(|file| scan_content(&String::from_utf8_lossy(&file.content)))
+        .filter(|name| !builtins.contains(name.as_str()))
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    result.sort();
+    result
+}
+
 /// Names the package provides itself: its own name plus the stem of every
 /// shipped file that can define a name.
 ///
@@ -197,33 +217,45 @@ pub fn provided_names(package_name: &str, package_dir: &Path) -> HashSet<String>
     };
 
     for entry in entries.flatten() {
-        let path = entry.path();
-        let provides_name = path
-            .extension()
-            .and_then(|ext| ext.to_str())
-            .is_some_and(|ext| PROVIDED_EXTENSIONS.contains(&ext.to_lowercase().as_str()));
-        if !provides_name {
-            continue;
+        if let Some(file_name) = entry.path().file_name().and_then(|n| n.to_str()) {
+            insert_provided(file_name, &mut names);
         }
+    }
 
-        let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else {
-            continue;
-        };
-        let name = file_name.to_lowercase();
+    names
+}
 
-        // Both the full stem and the leading component, so a file shipped as
-        // `parallel_map_template.do.ado` is provided under either spelling.
-        if let Some(stem) = name.rsplit_once('.').map(|(stem, _)| stem) {
-            names.insert(stem.to_string());
-        }
-        if let Some((head, _)) = name.split_once('.') {
-            names.insert(head.to_string());
-        }
+/// [`provided_names`] for files fetched but not saved anywhere yet
+pub fn provided_names_in_files(package_name: &str, files: &[DownloadedFile]) -> HashSet<String> {
+    let mut names = HashSet::new();
+    names.insert(package_name.to_lowercase());
+    for file in files {
+        insert_provided(&file.name, &mut names);
     }
-
     names
 }
 
+/// Add the names a shipped file provides, if its kind can define one
+fn insert_provided(file_name: &str, names: &mut HashSet<String>) {
+    let provides_name = Path::new(file_name)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| PROVIDED_EXTENSIONS.contains(&ext.to_lowercase().as_str()));
+    if !provides_name {
+        return;
+    }
+    let name = file_name.to_lowercase();
+
+    // Both the full stem and the leading component, so a file shipped as
+    // `parallel_map_template.do.ado` is provided under either spelling.
+    if let Some(stem) = name.rsplit_once('.').map(|(stem, _)| stem) {
+        names.insert(stem.to_string());
+    }
+    if let Some((head, _)) = name.split_once('.') {
+        names.insert(head.to_string());
+    }
+}
+
 /// Compare scanned deps against the names the package provides itself and the
 /// packages already installed. Returns names that are detected but missing.
 pub fn find_missing_deps(
@@ -239,6 +271,20 @@ pub fn find_missing_deps(
         .collect()
 }
 
+/// [`find_missing_deps`] for files fetched but not saved anywhere yet
+/// (`stacy add --dry-run`)
+pub fn find_missing_deps_in_files(
+    package_name: &str,
+    files: &[DownloadedFile],
+    installed: &HashSet<String>,
+) -> Vec<String> {
+    let provided = provided_names_in_files(package_name, files);
+    scan_file_deps(files)
+        .into_iter()
+        .filter(|dep| !provided.contains(dep) && !installed.contains(dep))
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -470,6 +516,29 @@ mod tests {
         assert!(provided.contains("mypkg"));
     }
 
+    /// Files fetched but not saved yet are scanned like an installed package
+    #[test]
+    fn test_find_missing_deps_in_files() {
+        let file = |name: &str, content: &str| DownloadedFile {
+            name: name.to_string(),
+            content: content.as_bytes().to_vec(),
+            checksum: String::new(),
+        };
+        let files = [
+            file(
+                "pkg.ado",
+                "require moremata\nrequire ftools\nwhich pkg_sub\n",
+            ),
+            file("pkg_sub.ado", "// sub-command"),
+            file("notes.txt", "require gtools"),
+        ];
+        let installed: HashSet<String> = ["ftools".to_string()].into();
+        assert_eq!(
+            find_missing_deps_in_files("pkg", &files, &installed),
+            vec!["moremata".to_string()]
+        );
+    }
+
     #[test]
     fn test_empty_dir() {
         let dir = tempfile::tempdir().unwrap();
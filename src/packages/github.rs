@@ -63,6 +63,11 @@ pub struct GitHubPackageDownload {
     pub files: Vec<DownloadedFile>,
     /// Combined checksum of all files
     pub package_checksum: String,
+    /// Repository directory holding the `.pkg` manifest; `None` for the root
+    pub pkg_dir: Option<String>,
+    /// Whether the repository has no `.pkg` and the manifest was inferred
+    /// from its files
+    pub synthesized: bool,
 }
 
 /// GitHub package downloader
@@ -141,10 +146,13 @@ impl GitHubDownloader {
     ) -> Result<GitHubPackageDownload> {
         // Try to find .pkg file - check multiple locations
         match self.find_and_download_pkg(name, user, repo, git_ref) {
-            Ok(pkg_content) => {
+            Ok((pkg_dir, pkg_content)) => {
                 // Parse manifest
                 let manifest = parse_pkg_file(&pkg_content, name)?;
-                self.download_manifest_files(name, user, repo, git_ref, manifest)
+                let mut download =
+                    self.download_manifest_files(name, user, repo, git_ref, manifest)?;
+                download.pkg_dir = pkg_dir;
+                Ok(download)
             }
             Err(_pkg_err) => {
                 // No .pkg found — try to synthesize manifest from repo tree
@@ -153,7 +161,10 @@ impl GitHubDownloader {
                         eprintln!(
                             "  note: No .pkg manifest found, inferred package contents from repository files"
                         );
-                        self.download_manifest_files(name, user, repo, git_ref, manifest)
+                        let mut download =
+                            self.download_manifest_files(name, user, repo, git_ref, manifest)?;
+                        download.synthesized = true;
+                        Ok(download)
                     }
                     Err(synth_err) => Err(synth_err),
                 }
@@ -208,6 +219,8 @@ impl GitHubDownloader {
             manifest,
             files,
             package_checksum,
+            pkg_dir: None,
+            synthesized: false,
         })
     }
 
@@ -318,26 +331,36 @@ impl GitHubDownloader {
     }
 
     /// Find and download the .pkg file, checking multiple locations
+    ///
+    /// Returns the directory it was found in (`None` for the repo root) with
+    /// its contents.
     fn find_and_download_pkg(
         &self,
         name: &str,
         user: &str,
         repo: &str,
         git_ref: &str,
-    ) -> Result<String> {
+    ) -> Result<(Option<String>, String)> {
         // Try common locations for .pkg files
-        let locations = vec![
-            format!("{}.pkg", name),          // repo root
-            format!("src/{}.pkg", name),      // src/ directory
-            format!("pkg/{}.pkg", name),      // pkg/ directory
-            format!("ado/{}.pkg", name),      // ado/ directory
-            format!("{}/{}.pkg", name, name), // package subdirectory
+        let dirs = [
+            None,                    // repo root
+            Some("src".to_string()), // src/ directory
+            Some("pkg".to_string()), // pkg/ directory
+            Some("ado".to_string()), // ado/ directory
+            Some(name.to_string()),  // package subdirectory
         ];
+        let locations: Vec<String> = dirs
+            .iter()
+            .map(|dir| match dir {
+                Some(dir) => format!("{}/{}.pkg", dir, name),
+                None => format!("{}.pkg", name),
+            })
+            .collect();
 
-        for location in &locations {
+        for (dir, location) in dirs.iter().zip(&locations) {
             let url = Self::get_raw_url(user, repo, git_ref, location);
             match self.download_text(&url) {
-                Ok(content) => return Ok(content),
+                Ok(content) => return Ok((dir.clone(), content)),
                 Err(_) => continue,
             }
         }
@@ -605,6 +628,8 @@ mod tests {
                 },
             ],
             package_checksum: "combined".to_string(),
+            pkg_dir: None,
+            synthesized: false,
         };
 
         let saved = save_github_package_files(&download, &ado_dir).unwrap();
@@ -5,10 +5,25 @@
 //!
 //! At runtime, S_ADO is constructed dynamically from the lockfile to point
 //! to the cached packages.
+//!
+//! A secondary, read-only cache with the same layout can be shared between
+//! users (`[cache] shared_path` in the user config, or `STACY_SHARED_CACHE`).
+//! A package missing from the local cache is looked up there before anything
+//! is downloaded; stacy never writes to it.
+//!
+//! A project can keep its packages itself instead (`[packages] install_mode =
+//! "local"`, or `stacy install --target DIR`), in the same layout under a
+//! directory of its own. Where a project has such a store, a package found
+//! there is used ahead of both caches.
 
 use crate::error::{Error, Result};
 use crate::project::{Lockfile, PackageEntry};
-use std::path::PathBuf;
+use crate::utils::lock::{with_shared_lock, with_state_lock, LOCK_FILE_NAME};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// The current project's own package store, if it has one
+static PROJECT_STORE: OnceLock<Option<PathBuf>> = OnceLock::new();
 
 /// Get the global package cache directory.
 ///
@@ -45,6 +60,84 @@ pub fn cache_dir() -> Result<PathBuf> {
     Ok(cache_base.join("packages"))
 }
 
+/// Get the shared, read-only package cache directory, if one is configured.
+///
+/// Precedence: `STACY_SHARED_CACHE` environment variable, then
+/// `[cache] shared_path` in the user config. An empty variable disables the
+/// shared cache even when the user config sets one.
+pub fn shared_cache_dir() -> Option<PathBuf> {
+    if let Ok(value) = std::env::var("STACY_SHARED_CACHE") {
+        return (!value.is_empty()).then(|| PathBuf::from(value));
+    }
+
+    crate::project::user_config::load_user_config()
+        .ok()
+        .flatten()
+        .and_then(|config| config.cache.shared_path)
+}
+
+/// Whether the global cache is read-only: provisioned centrally (a managed
+/// Stata server), so stacy installs nothing into it and only checks that the
+/// locked packages are there.
+///
+/// `STACY_CACHE_READ_ONLY` (any value but empty or `0`) overrides `[cache]
+/// read_only` in the user config either way.
+pub fn cache_read_only() -> bool {
+    match std::env::var("STACY_CACHE_READ_ONLY") {
+        Ok(value) => !value.is_empty() && value != "0",
+        Err(_) => crate::project::user_config::load_user_config()
+            .ok()
+            .flatten()
+            .and_then(|config| config.cache.read_only)
+            .unwrap_or(false),
+    }
+}
+
+/// The error for packages a read-only cache doesn't have, `missing` as
+/// `name version`
+pub fn read_only_cache_error(missing: &[String]) -> Error {
+    let cache = cache_dir()
+        .map(|dir| dir.display().to_string())
+        .unwrap_or_else(|_| "the package cache".to_string());
+    Error::Config(format!(
+        "The package cache is read-only and {} package(s) are not in it:\n{}\n\n  \
+         hint: ask the administrators of {} to install them there, or install into \
+         the project with `stacy install --target ado/`",
+        missing.len(),
+        missing
+            .iter()
+            .map(|package| format!("  {}", package))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        cache
+    ))
+}
+
+/// Path of the lock file guarding a cache directory.
+pub fn cache_lock_path(cache: &Path) -> PathBuf {
+    cache.join(LOCK_FILE_NAME)
+}
+
+/// Directory of the per-package install locks, skipped like `.staging` when
+/// the cache is listed.
+const PACKAGE_LOCKS_DIR: &str = ".locks";
+
+/// Path of the lock file held while a package version is installed into
+/// `store` (the cache or a project's store): `{store}/.locks/{name}@{version}.lock`
+pub fn package_lock_path(store: &Path, name: &str, version: &str) -> PathBuf {
+    let file_name: String = format!("{}@{}.lock", name.to_lowercase(), version)
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || "@.-_".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    store.join(PACKAGE_LOCKS_DIR).join(file_name)
+}
+
 /// Get the path to a specific package version in the cache.
 ///
 /// Returns: `{cache_dir}/{name}/{version}/`
@@ -58,13 +151,16 @@ pub fn package_path(name: &str, version: &str) -> Result<PathBuf> {
 /// Returns true if the package directory exists and contains at least one file.
 pub fn is_cached(name: &str, version: &str) -> Result<bool> {
     let path = package_path(name, version)?;
+    dir_has_entries(&path)
+}
 
+fn dir_has_entries(path: &Path) -> Result<bool> {
     if !path.exists() {
         return Ok(false);
     }
 
     // Check if there are any files in the directory
-    let entries = std::fs::read_dir(&path).map_err(|e| {
+    let entries = std::fs::read_dir(path).map_err(|e| {
         Error::Io(std::io::Error::new(
             e.kind(),
             format!("Failed to read cache directory {}: {}", path.display(), e),
@@ -74,6 +170,77 @@ pub fn is_cached(name: &str, version: &str) -> Result<bool> {
     Ok(entries.count() > 0)
 }
 
+/// Get the path to a package version in the shared cache, if it is there.
+pub fn shared_package_path(name: &str, version: &str) -> Option<PathBuf> {
+    let shared = shared_cache_dir()?;
+    let path = shared.join(name.to_lowercase()).join(version);
+    with_shared_lock(&cache_lock_path(&shared), || dir_has_entries(&path))
+        .ok()?
+        .unwrap_or(false)
+        .then_some(path)
+}
+
+/// Use `dir` as the project's package store for the rest of the process
+/// (`stacy install --target`). No effect once the store has been looked up.
+pub fn set_project_store(dir: PathBuf) {
+    let _ = PROJECT_STORE.set(Some(dir));
+}
+
+/// The current project's package store: the directory `[packages]
+/// install_mode = "local"` installs to, or the one set with
+/// [`set_project_store`]
+pub fn project_store() -> Option<&'static Path> {
+    PROJECT_STORE
+        .get_or_init(|| {
+            let project = crate::project::Project::find().ok().flatten()?;
+            let dir = project.config.as_ref()?.packages.local_install_dir()?;
+            Some(project.root.join(dir))
+        })
+        .as_deref()
+}
+
+/// Get the path to a package version in a store laid out like the cache, if
+/// it is there.
+pub fn store_package_path(store: &Path, name: &str, version: &str) -> Option<PathBuf> {
+    let path = store.join(name.to_lowercase()).join(version);
+    dir_has_entries(&path).unwrap_or(false).then_some(path)
+}
+
+/// Where a package version is available: the project's own store if it has
+/// one holding it, else the local cache if it is there, else the shared cache
+/// if it is there.
+///
+/// Returns None if none of them holds it.
+pub fn locate_package(name: &str, version: &str) -> Result<Option<PathBuf>> {
+    if let Some(path) = project_store().and_then(|store| store_package_path(store, name, version)) {
+        tracing::trace!(package = name, version, "in the project's package store");
+        return Ok(Some(path));
+    }
+    if is_cached(name, version)? {
+        tracing::trace!(package = name, version, "in the local cache");
+        return package_path(name, version).map(Some);
+    }
+    let shared = shared_package_path(name, version);
+    tracing::trace!(
+        package = name,
+        version,
+        shared = shared.is_some(),
+        "not in the local cache"
+    );
+    Ok(shared)
+}
+
+/// Directory to put on S_ADO for a package version.
+///
+/// The located copy (local first, then shared); falls back to the local path
+/// so a missing package still gets a deterministic, nonexistent entry.
+pub fn resolve_package_path(name: &str, version: &str) -> Result<PathBuf> {
+    match locate_package(name, version)? {
+        Some(path) => Ok(path),
+        None => package_path(name, version),
+    }
+}
+
 /// Ensure the cache directory for a package exists.
 pub fn ensure_package_cache_dir(name: &str, version: &str) -> Result<PathBuf> {
     let path = package_path(name, version)?;
@@ -90,6 +257,14 @@ pub fn ensure_package_cache_dir(name: &str, version: &str) -> Result<PathBuf> {
     Ok(path)
 }
 
+/// A directory as Stata reads it on S_ADO: without the `\\?\` prefix a
+/// canonicalized Windows path carries (a UNC share stays `\\server\share`)
+fn s_ado_entry(dir: &Path) -> String {
+    crate::utils::paths::strip_verbatim(dir)
+        .display()
+        .to_string()
+}
+
 /// Build the S_ADO environment variable from a lockfile.
 ///
 /// By default (strict mode), only locked packages and BASE are included.
@@ -110,16 +285,21 @@ pub fn build_s_ado(
 
     // Prepend local ado paths in declared order
     for local_path in local_ado_paths {
-        paths.push(local_path.display().to_string());
+        paths.push(s_ado_entry(local_path));
     }
 
     // Sort packages alphabetically for deterministic S_ADO order
     let mut sorted_packages: Vec<_> = lockfile.packages.iter().collect();
     sorted_packages.sort_by_key(|(a, _)| *a);
 
-    for (name, entry) in sorted_packages {
-        let pkg_path = package_path(name, &entry.version)?;
-        paths.push(pkg_path.display().to_string());
+    // Editable packages are on the local ado paths already, as their own
+    // directories
+    for (name, entry) in sorted_packages
+        .into_iter()
+        .filter(|(_, e)| !e.is_editable())
+    {
+        let pkg_path = resolve_package_path(name, &entry.version)?;
+        paths.push(s_ado_entry(&pkg_path));
     }
 
     // Always include Stata's built-in commands
@@ -152,7 +332,7 @@ pub fn build_s_ado_for_groups(
 
     // Prepend local ado paths in declared order
     for local_path in local_ado_paths {
-        paths.push(local_path.display().to_string());
+        paths.push(s_ado_entry(local_path));
     }
 
     // Sort packages alphabetically for deterministic S_ADO order
@@ -160,9 +340,9 @@ pub fn build_s_ado_for_groups(
     sorted_packages.sort_by_key(|(a, _)| *a);
 
     for (name, entry) in sorted_packages {
-        if groups.contains(&entry.group.as_str()) {
-            let pkg_path = package_path(name, &entry.version)?;
-            paths.push(pkg_path.display().to_string());
+        if groups.contains(&entry.group.as_str()) && !entry.is_editable() {
+            let pkg_path = resolve_package_path(name, &entry.version)?;
+            paths.push(s_ado_entry(&pkg_path));
         }
     }
 
@@ -210,7 +390,8 @@ pub fn list_cached_packages() -> Result<Vec<(String, String, PathBuf)>> {
         let pkg_name = entry.file_name().to_string_lossy().to_string();
         let pkg_path = entry.path();
 
-        if !pkg_path.is_dir() {
+        // Skip non-packages, including `.staging` (installs in progress)
+        if !pkg_path.is_dir() || pkg_name.starts_with('.') {
             continue;
         }
 
@@ -237,7 +418,8 @@ pub fn list_cached_packages() -> Result<Vec<(String, String, PathBuf)>> {
             let version = version_entry.file_name().to_string_lossy().to_string();
             let version_path = version_entry.path();
 
-            if version_path.is_dir() {
+            // Staging dirs of interrupted installs by older stacy releases
+            if version_path.is_dir() && !version.contains(".downloading") {
                 packages.push((pkg_name.clone(), version, version_path));
             }
         }
@@ -299,13 +481,16 @@ pub fn remove_cached_package(name: &str, version: &str) -> Result<()> {
     let path = package_path(name, version)?;
 
     if path.exists() {
-        std::fs::remove_dir_all(&path).map_err(|e| {
+        let lock_path = cache_lock_path(&cache_dir()?);
+        with_state_lock(&lock_path, || std::fs::remove_dir_all(&path))?.map_err(|e| {
             Error::Io(std::io::Error::new(
                 e.kind(),
                 format!("Failed to remove cached package {}: {}", path.display(), e),
             ))
         })?;
 
+        let _ = std::fs::remove_file(super::cache_layout::metadata_path(&path));
+
         // Try to remove the parent directory if empty
         if let Some(parent) = path.parent() {
             if parent.exists() {
@@ -405,21 +590,31 @@ pub enum CacheState {
 }
 
 /// Compare one locked package against the global cache.
+///
+/// Checks the copy `stacy run` would put on S_ADO: the local cache if it
+/// holds the package, else the shared cache.
 pub fn check_cached_package(name: &str, entry: &PackageEntry) -> CacheState {
-    if !is_cached(name, &entry.version).unwrap_or(false) {
+    let Ok(Some(dir)) = locate_package(name, &entry.version) else {
         return CacheState::Missing;
-    }
+    };
 
     let Some(expected) = entry.checksum.as_deref() else {
         return CacheState::Unverifiable;
     };
     let expected = expected.strip_prefix("sha256:").unwrap_or(expected);
 
-    let Ok(dir) = package_path(name, &entry.version) else {
-        return CacheState::Missing;
+    // Hash a shared copy under the shared cache's lock, so a package being
+    // replaced there is not read half-written.
+    let hash = match shared_cache_dir() {
+        Some(shared) if dir.starts_with(&shared) => {
+            with_shared_lock(&cache_lock_path(&shared), || hash_package_dir(&dir))
+                .ok()
+                .flatten()
+        }
+        _ => hash_package_dir(&dir),
     };
 
-    match hash_package_dir(&dir) {
+    match hash {
         Some(actual) if actual == expected => CacheState::Verified,
         Some(_) => CacheState::Modified,
         None => CacheState::Missing,
@@ -450,7 +645,8 @@ pub fn verify_lockfile_against_cache(lockfile: &Lockfile) -> Result<()> {
     let mut missing: Vec<&str> = Vec::new();
     let mut modified: Vec<&str> = Vec::new();
 
-    for (name, entry) in &lockfile.packages {
+    // An editable package is its directory, meant to change: nothing to check
+    for (name, entry) in lockfile.packages.iter().filter(|(_, e)| !e.is_editable()) {
         match check_cached_package(name, entry) {
             CacheState::Verified | CacheState::Unverifiable => {}
             CacheState::Missing => {
@@ -492,6 +688,27 @@ pub fn verify_lockfile_against_cache(lockfile: &Lockfile) -> Result<()> {
 
 #[cfg(test)]
 mod tests {
+    #[test]
+    fn test_store_package_path() {
+        let store = tempfile::TempDir::new().unwrap();
+        let dir = store.path().join("estout").join("1.0");
+        assert_eq!(store_package_path(store.path(), "estout", "1.0"), None);
+
+        std::fs::create_dir_all(&dir).unwrap();
+        // An interrupted install leaves an empty directory
+        assert_eq!(store_package_path(store.path(), "estout", "1.0"), None);
+
+        std::fs::write(dir.join("estout.ado"), "").unwrap();
+        assert_eq!(
+            store_package_path(store.path(), "Estout", "1.0"),
+            Some(dir.clone())
+        );
+        assert_eq!(
+            package_lock_path(store.path(), "Estout", "1.0"),
+            store.path().join(".locks").join("estout@1.0.lock")
+        );
+    }
+
     #[test]
     fn test_hash_package_dir_matches_download_checksum() {
         use crate::packages::ssc::{calculate_combined_checksum, calculate_sha256};
@@ -533,10 +750,13 @@ mod tests {
         // On Unix, cache_dir() reads XDG_CACHE_HOME.
         // On Windows, cache_dir() reads LOCALAPPDATA.
         // Set both so tests are isolated on all platforms.
+        // An empty STACY_SHARED_CACHE keeps a user-config shared cache out.
         let prev_xdg = std::env::var("XDG_CACHE_HOME").ok();
         let prev_localappdata = std::env::var("LOCALAPPDATA").ok();
+        let prev_shared = std::env::var("STACY_SHARED_CACHE").ok();
         std::env::set_var("XDG_CACHE_HOME", temp.path());
         std::env::set_var("LOCALAPPDATA", temp.path());
+        std::env::set_var("STACY_SHARED_CACHE", "");
         let result = f(&temp);
         // Restore previous values
         match prev_xdg {
@@ -547,9 +767,43 @@ mod tests {
             Some(v) => std::env::set_var("LOCALAPPDATA", v),
             None => std::env::remove_var("LOCALAPPDATA"),
         }
+        match prev_shared {
+            Some(v) => std::env::set_var("STACY_SHARED_CACHE", v),
+            None => std::env::remove_var("STACY_SHARED_CACHE"),
+        }
         result
     }
 
+    /// Point STACY_SHARED_CACHE at a fresh directory for the duration of `f`.
+    /// Must run inside `with_test_cache`, which restores the variable.
+    fn with_shared_cache<F, R>(f: F) -> R
+    where
+        F: FnOnce(&TempDir) -> R,
+    {
+        let shared = TempDir::new().unwrap();
+        std::env::set_var("STACY_SHARED_CACHE", shared.path());
+        f(&shared)
+    }
+
+    fn write_package(root: &Path, name: &str, version: &str, content: &[u8]) -> PathBuf {
+        let dir = root.join(name).join(version);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(format!("{}.ado", name)), content).unwrap();
+        dir
+    }
+
+    fn locked_entry(version: &str, dir: &Path) -> PackageEntry {
+        use crate::project::PackageSource;
+        PackageEntry {
+            version: version.to_string(),
+            source: PackageSource::SSC {
+                name: "pkg".to_string(),
+            },
+            checksum: hash_package_dir(dir).map(|h| format!("sha256:{}", h)),
+            group: "production".to_string(),
+        }
+    }
+
     #[test]
     #[serial]
     fn test_cache_dir_xdg_compliant() {
@@ -1246,6 +1500,61 @@ mod tests {
         });
     }
 
+    #[test]
+    #[serial]
+    fn test_build_s_ado_leaves_out_editable_packages() {
+        with_test_cache(|_temp| {
+            use crate::project::{PackageEntry, PackageSource};
+
+            let mut packages = HashMap::new();
+            packages.insert(
+                "myutils".to_string(),
+                PackageEntry {
+                    version: "editable".to_string(),
+                    source: PackageSource::Local {
+                        path: "../myutils".to_string(),
+                        editable: true,
+                    },
+                    checksum: None,
+                    group: "dev".to_string(),
+                },
+            );
+            let lockfile = Lockfile {
+                version: "1".to_string(),
+                stacy_version: None,
+                packages,
+            };
+
+            // The directory itself comes in with the local ado paths
+            let local_paths = vec![PathBuf::from("/project/../myutils")];
+            let s_ado = build_s_ado(&lockfile, false, &local_paths).unwrap();
+            assert_eq!(s_ado, "/project/../myutils;BASE");
+            let s_ado = build_s_ado_for_groups(&lockfile, &["dev"], false, &[]).unwrap();
+            assert_eq!(s_ado, "BASE");
+            assert!(verify_lockfile_against_cache(&lockfile).is_ok());
+        });
+    }
+
+    #[test]
+    #[serial]
+    fn test_build_s_ado_strips_verbatim_prefix() {
+        with_test_cache(|_temp| {
+            let lockfile = Lockfile {
+                version: "1".to_string(),
+                stacy_version: None,
+                packages: HashMap::new(),
+            };
+
+            // What canonicalize returns on Windows; Stata can't open it
+            let local_paths = vec![
+                PathBuf::from(r"\\?\C:\project\ado"),
+                PathBuf::from(r"\\?\UNC\server\share\ado"),
+            ];
+            let s_ado = build_s_ado(&lockfile, false, &local_paths).unwrap();
+            assert_eq!(s_ado, r"C:\project\ado;\\server\share\ado;BASE");
+        });
+    }
+
     #[test]
     #[serial]
     fn test_build_s_ado_for_groups_with_local_paths() {
@@ -1281,4 +1590,83 @@ mod tests {
             assert!(s_ado.ends_with(";BASE"));
         });
     }
+
+    #[test]
+    #[serial]
+    fn test_shared_cache_disabled_by_empty_env() {
+        with_test_cache(|_temp| {
+            assert_eq!(shared_cache_dir(), None);
+            assert_eq!(shared_package_path("estout", "1.0.0"), None);
+        });
+    }
+
+    #[test]
+    #[serial]
+    fn test_locate_package_falls_back_to_shared_cache() {
+        with_test_cache(|_temp| {
+            with_shared_cache(|shared| {
+                let shared_dir = write_package(shared.path(), "estout", "1.0.0", b"shared");
+
+                assert!(!is_cached("estout", "1.0.0").unwrap());
+                assert_eq!(
+                    locate_package("estout", "1.0.0").unwrap(),
+                    Some(shared_dir.clone())
+                );
+                assert_eq!(locate_package("estout", "2.0.0").unwrap(), None);
+            });
+        });
+    }
+
+    #[test]
+    #[serial]
+    fn test_locate_package_prefers_local_cache() {
+        with_test_cache(|_temp| {
+            with_shared_cache(|shared| {
+                write_package(shared.path(), "estout", "1.0.0", b"shared");
+                let local_dir = ensure_package_cache_dir("estout", "1.0.0").unwrap();
+                std::fs::write(local_dir.join("estout.ado"), b"local").unwrap();
+
+                assert_eq!(locate_package("estout", "1.0.0").unwrap(), Some(local_dir));
+            });
+        });
+    }
+
+    #[test]
+    #[serial]
+    fn test_build_s_ado_uses_shared_copy() {
+        with_test_cache(|_temp| {
+            with_shared_cache(|shared| {
+                let shared_dir = write_package(shared.path(), "estout", "1.0.0", b"shared");
+
+                let mut packages = HashMap::new();
+                packages.insert("estout".to_string(), locked_entry("1.0.0", &shared_dir));
+                let lockfile = Lockfile {
+                    version: "1".to_string(),
+                    stacy_version: None,
+                    packages,
+                };
+
+                let s_ado = build_s_ado(&lockfile, false, &[]).unwrap();
+                assert_eq!(s_ado, format!("{};BASE", shared_dir.display()));
+            });
+        });
+    }
+
+    #[test]
+    #[serial]
+    fn test_check_cached_package_verifies_shared_copy() {
+        with_test_cache(|_temp| {
+            with_shared_cache(|shared| {
+                let shared_dir = write_package(shared.path(), "estout", "1.0.0", b"shared");
+                // Readers take the shared lock when the share has a lock file
+                std::fs::write(cache_lock_path(shared.path()), "").unwrap();
+
+                let entry = locked_entry("1.0.0", &shared_dir);
+                assert_eq!(check_cached_package("estout", &entry), CacheState::Verified);
+
+                std::fs::write(shared_dir.join("estout.ado"), b"tampered").unwrap();
+                assert_eq!(check_cached_package("estout", &entry), CacheState::Modified);
+            });
+        });
+    }
 }
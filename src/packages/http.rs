@@ -2,17 +2,173 @@
 //!
 //! Provides a common HTTP client with consistent error handling,
 //! used by SSC, GitHub, and Net downloaders.
+//!
+//! Every client stacy builds honors the configured proxy (`STACY_PROXY`, else
+//! `proxy` in the user config), on top of reqwest's own `HTTPS_PROXY` and
+//! `HTTP_PROXY` handling.
+//!
+//! Downloads go through an on-disk response cache (`~/.cache/stacy/http/`):
+//! a response carrying an `ETag` or `Last-Modified` is kept, and the next
+//! request for the same URL is conditional, so a package file that hasn't
+//! changed comes back as a bodyless `304 Not Modified`.
+//!
+//! Failed downloads are retried (`--network-retries`, default 3) with
+//! exponential backoff, and a transfer that breaks off is resumed from the
+//! last byte received rather than started over.
 
 use crate::error::{Error, Result};
 use reqwest::blocking::Client;
-use std::time::Duration;
+use reqwest::header::{
+    CONTENT_RANGE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_RANGE, LAST_MODIFIED, RANGE,
+};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::PathBuf;
+use std::sync::{Condvar, Mutex, OnceLock};
+use std::time::{Duration, Instant};
 
 /// HTTP client timeout for all package operations
 const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
 
+/// Retries after a failed download attempt, unless `--network-retries` or
+/// `STACY_NETWORK_RETRIES` says otherwise
+pub const DEFAULT_NETWORK_RETRIES: u32 = 3;
+
+/// Delay before the first retry; doubled for every retry after it
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+
+/// Longest delay between two attempts
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(8);
+
+/// Network options from the command line, for every client built afterwards
+#[derive(Debug, Clone, Copy)]
+struct NetworkOptions {
+    retries: Option<u32>,
+    verbose: bool,
+}
+
+static NETWORK_OPTIONS: OnceLock<NetworkOptions> = OnceLock::new();
+
+/// Apply `--network-retries` and `--verbose` to the clients built after this
+/// call. Only the first call takes effect.
+pub fn configure(retries: Option<u32>, verbose: bool) {
+    let _ = NETWORK_OPTIONS.set(NetworkOptions { retries, verbose });
+}
+
+/// Retries per download: `--network-retries`, else `STACY_NETWORK_RETRIES`,
+/// else [`DEFAULT_NETWORK_RETRIES`]
+pub fn network_retries() -> u32 {
+    NETWORK_OPTIONS
+        .get()
+        .and_then(|o| o.retries)
+        .or_else(|| {
+            std::env::var("STACY_NETWORK_RETRIES")
+                .ok()
+                .and_then(|v| v.trim().parse().ok())
+        })
+        .unwrap_or(DEFAULT_NETWORK_RETRIES)
+}
+
+/// Proxy URL from `STACY_PROXY` or the user config, if any
+pub fn configured_proxy_url() -> Option<String> {
+    match std::env::var("STACY_PROXY") {
+        Ok(url) if !url.is_empty() => Some(url),
+        _ => crate::project::user_config::load_user_config()
+            .ok()
+            .flatten()
+            .and_then(|c| c.proxy),
+    }
+}
+
+/// A reqwest client builder with the configured proxy applied.
+///
+/// An unparseable `STACY_PROXY` is ignored here; the user config's `proxy` is
+/// validated when the config is loaded.
+pub fn client_builder() -> reqwest::blocking::ClientBuilder {
+    let builder = Client::builder();
+    match configured_proxy_url().and_then(|url| reqwest::Proxy::all(url).ok()) {
+        Some(proxy) => builder.proxy(proxy),
+        None => builder,
+    }
+}
+
+/// Default location of the response cache, next to the package cache
+pub fn response_cache_dir() -> Result<PathBuf> {
+    Ok(crate::packages::global_cache::cache_dir()?.with_file_name("http"))
+}
+
+/// Remove every cached response
+pub fn clear_response_cache() -> Result<()> {
+    let dir = response_cache_dir()?;
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir)?;
+    }
+    Ok(())
+}
+
+/// Validators of a cached response, stored next to its body
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct CachedResponse {
+    url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    etag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_modified: Option<String>,
+}
+
+/// On-disk cache of responses, keyed by URL
+#[derive(Debug, Clone)]
+struct ResponseCache {
+    dir: PathBuf,
+}
+
+impl ResponseCache {
+    fn paths(&self, url: &str) -> (PathBuf, PathBuf) {
+        let key = hex::encode(Sha256::digest(url.as_bytes()));
+        (
+            self.dir.join(format!("{}.json", key)),
+            self.dir.join(format!("{}.body", key)),
+        )
+    }
+
+    /// The cached validators and body for a URL, if both are readable
+    fn lookup(&self, url: &str) -> Option<(CachedResponse, Vec<u8>)> {
+        let (meta_path, body_path) = self.paths(url);
+        let meta: CachedResponse = serde_json::from_slice(&std::fs::read(meta_path).ok()?).ok()?;
+        if meta.url != url {
+            return None;
+        }
+        Some((meta, std::fs::read(body_path).ok()?))
+    }
+
+    /// Store a response. Best effort: a cache that can't be written only
+    /// costs a download next time.
+    fn store(&self, meta: &CachedResponse, body: &[u8]) {
+        let (meta_path, body_path) = self.paths(&meta.url);
+        let write = |path: &PathBuf, content: &[u8]| -> std::io::Result<()> {
+            let mut tmp = tempfile::NamedTempFile::new_in(&self.dir)?;
+            std::io::Write::write_all(&mut tmp, content)?;
+            tmp.persist(path).map_err(|e| e.error)?;
+            Ok(())
+        };
+        let _ = std::fs::create_dir_all(&self.dir).and_then(|_| {
+            // Body first: a validator must never point at a stale body
+            write(&body_path, body)?;
+            write(&meta_path, &serde_json::to_vec(meta).unwrap_or_default())
+        });
+    }
+}
+
 /// Shared HTTP client for stacy package operations
 pub struct StacyHttpClient {
     client: Client,
+    cache: Option<ResponseCache>,
+    retries: u32,
+    retry_delay: Duration,
+    verbose: bool,
 }
 
 impl Default for StacyHttpClient {
@@ -24,13 +180,38 @@ impl Default for StacyHttpClient {
 impl StacyHttpClient {
     /// Create a new HTTP client with stacy's default settings
     pub fn new() -> Self {
-        let client = Client::builder()
+        let client = client_builder()
             .timeout(REQUEST_TIMEOUT)
             .user_agent(concat!("stacy/", env!("CARGO_PKG_VERSION")))
             .build()
             .expect("Failed to create HTTP client");
 
-        Self { client }
+        Self {
+            client,
+            cache: response_cache_dir().ok().map(|dir| ResponseCache { dir }),
+            retries: network_retries(),
+            retry_delay: RETRY_BASE_DELAY,
+            verbose: NETWORK_OPTIONS.get().is_some_and(|o| o.verbose),
+        }
+    }
+
+    /// Retry a failed download up to `retries` times
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Wait `delay` before the first retry (doubling after that)
+    pub fn with_retry_delay(mut self, delay: Duration) -> Self {
+        self.retry_delay = delay;
+        self
+    }
+
+    /// Keep responses in `dir` instead of the default response cache, or
+    /// (with `None`) don't cache them at all
+    pub fn with_response_cache(mut self, dir: Option<PathBuf>) -> Self {
+        self.cache = dir.map(|dir| ResponseCache { dir });
+        self
     }
 
     /// Get the underlying reqwest client for custom requests (e.g., JSON API calls)
@@ -43,56 +224,306 @@ impl StacyHttpClient {
     /// Returns appropriate errors for timeouts, connection failures,
     /// 404s, and other HTTP errors.
     pub fn download_text(&self, url: &str) -> Result<String> {
-        let response = self.send_request(url)?;
-        self.check_status(&response, url)?;
-
-        response
-            .text()
-            .map_err(|e| Error::Network(format!("Failed to read response: {}", e)))
+        let bytes = self.download_bytes(url)?;
+        Ok(String::from_utf8(bytes)
+            .unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned()))
     }
 
     /// Download a URL and return its content as bytes
     ///
-    /// Returns appropriate errors for timeouts, connection failures,
-    /// 404s, and other HTTP errors.
+    /// A cached copy is revalidated with `If-None-Match`/`If-Modified-Since`
+    /// and reused on `304 Not Modified`. Timeouts, dropped connections and
+    /// 5xx responses are retried with exponential backoff; a body cut off
+    /// mid-transfer is resumed with a `Range` request when the server sent a
+    /// validator to pin the file with. Returns appropriate errors for
+    /// timeouts, connection failures, 404s, and other HTTP errors.
     pub fn download_bytes(&self, url: &str) -> Result<Vec<u8>> {
-        let response = self.send_request(url)?;
-        self.check_status(&response, url)?;
-
-        response
-            .bytes()
-            .map(|b| b.to_vec())
-            .map_err(|e| Error::Network(format!("Failed to read response: {}", e)))
-    }
-
-    /// Send a GET request with standardized error handling
-    fn send_request(&self, url: &str) -> Result<reqwest::blocking::Response> {
-        self.client.get(url).send().map_err(|e| {
-            if e.is_timeout() {
-                Error::Network(format!("Request timed out: {}", url))
-            } else if e.is_connect() {
-                Error::Network(format!("Connection failed: {}", url))
-            } else {
-                Error::Network(format!("HTTP error: {}", e))
+        let cached = self.cache.as_ref().and_then(|c| c.lookup(url));
+        let mut partial = Partial::default();
+        let attempts = self.retries + 1;
+
+        for attempt in 1..=attempts {
+            let failure = match self.attempt(url, cached.as_ref(), &mut partial) {
+                Ok(body) => return Ok(body),
+                Err(failure) => failure,
+            };
+            if !failure.retryable || attempt == attempts {
+                return Err(match failure.error {
+                    Error::Network(msg) if attempt > 1 => {
+                        Error::Network(format!("{} (gave up after {} attempts)", msg, attempt))
+                    }
+                    error => error,
+                });
             }
-        })
+            let delay = self.backoff(attempt);
+            tracing::warn!(
+                url,
+                attempt,
+                attempts,
+                error = %failure.error,
+                delay_secs = delay.as_secs_f64(),
+                "download attempt failed, retrying"
+            );
+            if self.verbose {
+                eprintln!(
+                    "  attempt {}/{} for {} failed: {}; retrying in {:.1}s",
+                    attempt,
+                    attempts,
+                    url,
+                    failure.error,
+                    delay.as_secs_f64()
+                );
+            }
+            std::thread::sleep(delay);
+        }
+        unreachable!("the last attempt always returns")
     }
 
-    /// Check HTTP response status and return appropriate errors
-    fn check_status(&self, response: &reqwest::blocking::Response, url: &str) -> Result<()> {
-        if !response.status().is_success() {
-            let status = response.status();
-            if status.as_u16() == 404 {
-                return Err(Error::Config(format!("Not found: {}", url)));
+    /// One request for `url`, resuming `partial` when it holds the start of
+    /// the body
+    fn attempt(
+        &self,
+        url: &str,
+        cached: Option<&(CachedResponse, Vec<u8>)>,
+        partial: &mut Partial,
+    ) -> std::result::Result<Vec<u8>, Failure> {
+        let mut request = self.client.get(url);
+        let resuming = match partial.resume_validator() {
+            Some(validator) => {
+                if self.verbose {
+                    eprintln!("  resuming {} at byte {}", url, partial.body.len());
+                }
+                request = request
+                    .header(RANGE, format!("bytes={}-", partial.body.len()))
+                    .header(IF_RANGE, validator);
+                true
+            }
+            None => {
+                partial.body.clear();
+                if let Some((meta, _)) = cached {
+                    if let Some(etag) = &meta.etag {
+                        request = request.header(IF_NONE_MATCH, etag);
+                    }
+                    if let Some(last_modified) = &meta.last_modified {
+                        request = request.header(IF_MODIFIED_SINCE, last_modified);
+                    }
+                }
+                false
+            }
+        };
+
+        tracing::debug!(url, resuming, revalidating = cached.is_some(), "GET");
+        let mut response = request.send().map_err(|e| Failure {
+            retryable: !e.is_builder() && !e.is_redirect(),
+            error: request_error(&e, url),
+        })?;
+
+        let status = response.status();
+        tracing::debug!(url, status = status.as_u16(), "response");
+        if status == StatusCode::NOT_MODIFIED {
+            if let Some((_, body)) = cached {
+                tracing::debug!(url, "not modified, using the cached response");
+                return Ok(body.clone());
+            }
+        }
+        if status == StatusCode::PARTIAL_CONTENT {
+            let expected = format!("bytes {}-", partial.body.len());
+            let content_range = response
+                .headers()
+                .get(CONTENT_RANGE)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or("");
+            if !resuming || !content_range.starts_with(&expected) {
+                *partial = Partial::default();
+                return Err(Failure {
+                    retryable: true,
+                    error: Error::Network(format!("Unexpected partial response for {}", url)),
+                });
+            }
+        } else {
+            check_status(&response, url).map_err(|error| Failure {
+                retryable: status.is_server_error()
+                    || status == StatusCode::REQUEST_TIMEOUT
+                    || status == StatusCode::TOO_MANY_REQUESTS,
+                error,
+            })?;
+            // A full response: the file is (re)sent from the start
+            let header = |name| {
+                response
+                    .headers()
+                    .get(name)
+                    .and_then(|v: &reqwest::header::HeaderValue| v.to_str().ok())
+                    .map(str::to_string)
+            };
+            *partial = Partial {
+                body: Vec::new(),
+                meta: CachedResponse {
+                    url: url.to_string(),
+                    etag: header(ETAG),
+                    last_modified: header(LAST_MODIFIED),
+                },
+            };
+        }
+
+        let mut buf = [0u8; 64 * 1024];
+        loop {
+            match response.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => partial.body.extend_from_slice(&buf[..n]),
+                Err(e) => {
+                    return Err(Failure {
+                        retryable: true,
+                        error: Error::Network(format!(
+                            "Download of {} interrupted after {} bytes: {}",
+                            url,
+                            partial.body.len(),
+                            e
+                        )),
+                    })
+                }
             }
-            return Err(Error::Network(format!(
-                "HTTP {} for {}",
-                status.as_u16(),
-                url
-            )));
         }
-        Ok(())
+
+        let body = std::mem::take(&mut partial.body);
+        tracing::trace!(url, bytes = body.len(), "downloaded");
+        if let Some(cache) = &self.cache {
+            if partial.meta.etag.is_some() || partial.meta.last_modified.is_some() {
+                cache.store(&partial.meta, &body);
+            }
+        }
+        Ok(body)
+    }
+
+    /// Delay before the attempt after `attempt`: doubling from the base,
+    /// capped at [`RETRY_MAX_DELAY`]
+    fn backoff(&self, attempt: u32) -> Duration {
+        self.retry_delay
+            .saturating_mul(1 << (attempt - 1).min(16))
+            .min(RETRY_MAX_DELAY)
+    }
+}
+
+/// A failed download attempt, and whether trying again could help
+struct Failure {
+    error: Error,
+    retryable: bool,
+}
+
+/// The part of a response body received before the transfer broke off
+#[derive(Debug, Default)]
+struct Partial {
+    body: Vec<u8>,
+    meta: CachedResponse,
+}
+
+impl Partial {
+    /// The `If-Range` validator to resume with, if there is anything to
+    /// resume. Only strong ETags pin the bytes; else `Last-Modified` does.
+    fn resume_validator(&self) -> Option<String> {
+        if self.body.is_empty() {
+            return None;
+        }
+        self.meta
+            .etag
+            .clone()
+            .filter(|etag| !etag.starts_with("W/"))
+            .or_else(|| self.meta.last_modified.clone())
+    }
+}
+
+/// Paces the requests threads make to each host: at most `max_in_flight`
+/// at a time, started at least `spacing` apart, so work can run in parallel
+/// without hammering one server
+pub struct HostLimiter {
+    max_in_flight: usize,
+    spacing: Duration,
+    hosts: Mutex<HashMap<String, HostSlot>>,
+    freed: Condvar,
+}
+
+/// Requests in flight to a host, and when the next one may start
+#[derive(Default)]
+struct HostSlot {
+    in_flight: usize,
+    next_start: Option<Instant>,
+}
+
+/// A turn at a host, given back when dropped
+pub struct HostPermit<'a> {
+    limiter: &'a HostLimiter,
+    host: String,
+}
+
+impl HostLimiter {
+    pub fn new(max_in_flight: usize, spacing: Duration) -> Self {
+        Self {
+            max_in_flight: max_in_flight.max(1),
+            spacing,
+            hosts: Mutex::new(HashMap::new()),
+            freed: Condvar::new(),
+        }
+    }
+
+    /// Wait for a turn at `host`: until fewer than `max_in_flight` requests
+    /// to it are in flight, then until `spacing` after the last one started
+    pub fn acquire(&self, host: &str) -> HostPermit<'_> {
+        let mut hosts = self.hosts.lock().unwrap_or_else(|e| e.into_inner());
+        while hosts
+            .get(host)
+            .is_some_and(|slot| slot.in_flight >= self.max_in_flight)
+        {
+            hosts = self.freed.wait(hosts).unwrap_or_else(|e| e.into_inner());
+        }
+        let slot = hosts.entry(host.to_string()).or_default();
+        slot.in_flight += 1;
+        let now = Instant::now();
+        let start = slot.next_start.map_or(now, |next| next.max(now));
+        slot.next_start = Some(start + self.spacing);
+        drop(hosts);
+
+        std::thread::sleep(start - now);
+        HostPermit {
+            limiter: self,
+            host: host.to_string(),
+        }
+    }
+}
+
+impl Drop for HostPermit<'_> {
+    fn drop(&mut self) {
+        let mut hosts = self.limiter.hosts.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(slot) = hosts.get_mut(&self.host) {
+            slot.in_flight -= 1;
+        }
+        self.limiter.freed.notify_all();
+    }
+}
+
+/// Map a request error to stacy's error
+fn request_error(e: &reqwest::Error, url: &str) -> Error {
+    if e.is_timeout() {
+        Error::Network(format!("Request timed out: {}", url))
+    } else if e.is_connect() {
+        Error::Network(format!("Connection failed: {}", url))
+    } else {
+        Error::Network(format!("HTTP error: {}", e))
+    }
+}
+
+/// Check HTTP response status and return appropriate errors
+fn check_status(response: &reqwest::blocking::Response, url: &str) -> Result<()> {
+    if !response.status().is_success() {
+        let status = response.status();
+        if status.as_u16() == 404 {
+            return Err(Error::Config(format!("Not found: {}", url)));
+        }
+        return Err(Error::Network(format!(
+            "HTTP {} for {}",
+            status.as_u16(),
+            url
+        )));
     }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -105,6 +536,178 @@ mod tests {
         let _ = client.inner();
     }
 
+    #[test]
+    fn test_host_limiter_paces_each_host() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let limiter = HostLimiter::new(2, Duration::from_millis(20));
+        let in_flight = AtomicUsize::new(0);
+        let most = AtomicUsize::new(0);
+        let started = Instant::now();
+        std::thread::scope(|scope| {
+            for _ in 0..6 {
+                scope.spawn(|| {
+                    let _permit = limiter.acquire("fmwww.bc.edu");
+                    let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                    most.fetch_max(now, Ordering::SeqCst);
+                    std::thread::sleep(Duration::from_millis(10));
+                    in_flight.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+        });
+        assert!(most.load(Ordering::SeqCst) <= 2);
+        // Six starts, each at least 20ms after the one before
+        assert!(started.elapsed() >= Duration::from_millis(100));
+
+        // Another host is not held up by the first
+        let _busy = limiter.acquire("fmwww.bc.edu");
+        let _also_busy = limiter.acquire("fmwww.bc.edu");
+        let other = Instant::now();
+        let _permit = limiter.acquire("api.github.com");
+        assert!(other.elapsed() < Duration::from_millis(20));
+    }
+
+    /// Serve `body` with an ETag; answer 304 to a matching If-None-Match.
+    /// Returns the base URL and the number of full (200) responses sent.
+    fn serve_with_etag(
+        body: &'static [u8],
+    ) -> (String, std::sync::Arc<std::sync::atomic::AtomicUsize>) {
+        use std::io::{Read, Write};
+        use std::sync::atomic::Ordering;
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let full = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = full.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { continue };
+                let mut buf = [0u8; 4096];
+                let read = stream.read(&mut buf).unwrap_or(0);
+                let request = String::from_utf8_lossy(&buf[..read]).to_lowercase();
+                let response = if request.contains("if-none-match: \"v1\"") {
+                    b"HTTP/1.1 304 Not Modified\r\nETag: \"v1\"\r\nConnection: close\r\n\r\n"
+                        .to_vec()
+                } else {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    let mut head = format!(
+                        "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                        body.len()
+                    )
+                    .into_bytes();
+                    head.extend_from_slice(body);
+                    head
+                };
+                let _ = stream.write_all(&response);
+            }
+        });
+        (base, full)
+    }
+
+    #[test]
+    fn test_unchanged_file_is_revalidated_not_downloaded() {
+        use std::sync::atomic::Ordering;
+
+        let (base, full) = serve_with_etag(b"v 3\nd test package\n");
+        let cache = tempfile::TempDir::new().unwrap();
+        let client = StacyHttpClient::new().with_response_cache(Some(cache.path().to_path_buf()));
+        let url = format!("{}/test.pkg", base);
+
+        assert_eq!(client.download_text(&url).unwrap(), "v 3\nd test package\n");
+        assert_eq!(client.download_text(&url).unwrap(), "v 3\nd test package\n");
+        assert_eq!(full.load(Ordering::SeqCst), 1);
+
+        // Without a cache every request is a full download
+        let uncached = StacyHttpClient::new().with_response_cache(None);
+        uncached.download_bytes(&url).unwrap();
+        assert_eq!(full.load(Ordering::SeqCst), 2);
+    }
+
+    /// Serve `body` badly: a 503, then a transfer cut off halfway, then
+    /// (given a matching `Range`) the rest. Returns the base URL and the
+    /// requests received.
+    fn serve_flaky(body: &'static [u8]) -> (String, std::sync::Arc<std::sync::Mutex<Vec<String>>>) {
+        use std::io::{Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let requests = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = requests.clone();
+        std::thread::spawn(move || {
+            let half = body.len() / 2;
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { continue };
+                let mut buf = [0u8; 4096];
+                let read = stream.read(&mut buf).unwrap_or(0);
+                let request = String::from_utf8_lossy(&buf[..read]).to_lowercase();
+                let mut log = log.lock().unwrap();
+                log.push(request.clone());
+                let response = if request.contains("/missing") {
+                    b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                        .to_vec()
+                } else if log.len() == 1 {
+                    b"HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                        .to_vec()
+                } else if request.contains(&format!("range: bytes={}-", half)) {
+                    let mut head = format!(
+                        "HTTP/1.1 206 Partial Content\r\nETag: \"v1\"\r\nContent-Range: bytes {}-{}/{}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                        half,
+                        body.len() - 1,
+                        body.len(),
+                        body.len() - half
+                    )
+                    .into_bytes();
+                    head.extend_from_slice(&body[half..]);
+                    head
+                } else {
+                    let mut head = format!(
+                        "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                        body.len()
+                    )
+                    .into_bytes();
+                    head.extend_from_slice(&body[..half]);
+                    head
+                };
+                let _ = stream.write_all(&response);
+            }
+        });
+        (base, requests)
+    }
+
+    #[test]
+    fn test_retries_and_resumes_interrupted_download() {
+        let body: &[u8] = b"a package archive that arrives in two pieces";
+        let (base, requests) = serve_flaky(body);
+        let client = StacyHttpClient::new()
+            .with_response_cache(None)
+            .with_retries(3)
+            .with_retry_delay(Duration::from_millis(1));
+
+        let downloaded = client.download_bytes(&format!("{}/big.zip", base)).unwrap();
+        assert_eq!(downloaded, body);
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 3);
+        assert!(!requests[1].contains("range:"));
+        assert!(requests[2].contains(&format!("range: bytes={}-", body.len() / 2)));
+        assert!(requests[2].contains("if-range: \"v1\""));
+    }
+
+    #[test]
+    fn test_not_found_is_not_retried() {
+        let (base, requests) = serve_flaky(b"unused");
+        let client = StacyHttpClient::new()
+            .with_response_cache(None)
+            .with_retries(3)
+            .with_retry_delay(Duration::from_millis(1));
+
+        let err = client
+            .download_bytes(&format!("{}/missing", base))
+            .unwrap_err();
+        assert!(err.to_string().contains("Not found"));
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+
     #[test]
     fn test_connection_refused_returns_error() {
         let client = StacyHttpClient::new();
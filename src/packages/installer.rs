@@ -4,15 +4,15 @@
 //! and updating the lockfile.
 
 use crate::error::{Error, Result};
+use crate::packages::cache_layout;
 use crate::packages::github::GitHubDownloader;
 use crate::packages::global_cache;
 use crate::packages::local;
-use crate::packages::lockfile::{
-    add_package, create_lockfile, create_package_entry, load_lockfile, save_lockfile,
-};
+use crate::packages::lockfile::{add_package, create_package_entry, update_lockfile};
 use crate::packages::net::NetDownloader;
 use crate::packages::ssc::{DownloadedFile, SscDownloader};
 use crate::project::{PackageEntry, PackageSource, Project};
+use crate::utils::lock::with_exclusive_lock;
 use std::path::{Path, PathBuf};
 
 /// Result of installing a package
@@ -65,6 +65,10 @@ struct ResolvedPackage {
     required_stata_version: Option<String>,
     /// Commit SHA, for GitHub sources where it could be resolved
     commit: Option<String>,
+    /// Repository directory holding a GitHub package's `.pkg`, when not the root
+    pkg_dir: Option<String>,
+    /// Whether a GitHub package's manifest was inferred, having no `.pkg`
+    synthesized: bool,
 }
 
 impl ResolvedPackage {
@@ -74,10 +78,22 @@ impl ResolvedPackage {
             .clone()
             .unwrap_or_else(|| self.fallback_version.clone())
     }
+
+    /// The lockfile source for a package fetched from GitHub `repo` at `tag`
+    fn github_source(&self, repo: String, tag: String) -> PackageSource {
+        PackageSource::GitHub {
+            repo,
+            tag,
+            commit: self.commit.clone(),
+            pkg_dir: self.pkg_dir.clone(),
+            synthesized: self.synthesized,
+        }
+    }
 }
 
 /// Fetch a package from SSC.
 fn resolve_ssc(name: &str) -> Result<ResolvedPackage> {
+    tracing::info!(package = name, "fetching package from SSC");
     let download = SscDownloader::new().download_package(name)?;
 
     Ok(ResolvedPackage {
@@ -89,6 +105,8 @@ fn resolve_ssc(name: &str) -> Result<ResolvedPackage> {
         declared_deps: download.manifest.requires,
         required_stata_version: download.manifest.stata_version,
         commit: None,
+        pkg_dir: None,
+        synthesized: false,
     })
 }
 
@@ -100,6 +118,13 @@ fn resolve_github(
     repo: &str,
     git_ref: Option<&str>,
 ) -> Result<ResolvedPackage> {
+    tracing::info!(
+        package = name,
+        user,
+        repo,
+        git_ref,
+        "fetching package from GitHub"
+    );
     let downloader = GitHubDownloader::new();
     let download = downloader.download_package(name, user, repo, git_ref)?;
 
@@ -123,11 +148,14 @@ fn resolve_github(
         declared_deps: download.manifest.requires,
         required_stata_version: download.manifest.stata_version,
         commit,
+        pkg_dir: download.pkg_dir,
+        synthesized: download.synthesized,
     })
 }
 
 /// Fetch a package from a net URL.
 fn resolve_net(name: &str, url: &str) -> Result<ResolvedPackage> {
+    tracing::info!(package = name, url, "fetching package from a net URL");
     let download = NetDownloader::new().download_package(name, url)?;
 
     Ok(ResolvedPackage {
@@ -139,6 +167,8 @@ fn resolve_net(name: &str, url: &str) -> Result<ResolvedPackage> {
         declared_deps: download.manifest.requires,
         required_stata_version: download.manifest.stata_version,
         commit: None,
+        pkg_dir: None,
+        synthesized: false,
     })
 }
 
@@ -150,6 +180,7 @@ fn resolve_local(name: &str, path: &str, project_root: &Path) -> Result<Resolved
         project_root.join(path)
     };
 
+    tracing::info!(package = name, dir = %dir.display(), "reading local package");
     let download = local::scan_local_directory(name, &dir)?;
 
     // Local packages have no manifest: the version is the short content hash,
@@ -165,6 +196,8 @@ fn resolve_local(name: &str, path: &str, project_root: &Path) -> Result<Resolved
         declared_deps: Vec::new(),
         required_stata_version: None,
         commit: None,
+        pkg_dir: None,
+        synthesized: false,
     })
 }
 
@@ -176,7 +209,9 @@ fn resolve_from_source(
 ) -> Result<ResolvedPackage> {
     match source {
         PackageSource::SSC { name: ssc_name } => resolve_ssc(ssc_name),
-        PackageSource::GitHub { repo, tag, commit } => {
+        PackageSource::GitHub {
+            repo, tag, commit, ..
+        } => {
             let (user, repo_name) = repo.split_once('/').ok_or_else(|| {
                 Error::Config(format!(
                     "Invalid GitHub repo format: {} (want owner/repo)",
@@ -188,7 +223,7 @@ fn resolve_from_source(
             resolve_github(name, user, repo_name, Some(git_ref))
         }
         PackageSource::Net { url } => resolve_net(name, url),
-        PackageSource::Local { path } => resolve_local(name, path, project_root),
+        PackageSource::Local { path, .. } => resolve_local(name, path, project_root),
     }
 }
 
@@ -203,14 +238,16 @@ fn cache_and_lock(
     group: &str,
 ) -> Result<InstallResult> {
     let version = resolved.pin_version();
-    let (_cache_dir, saved_files) = atomic_save_to_cache(&resolved.files, name, &version)?;
-
-    let mut lockfile = load_lockfile(project_root)?.unwrap_or_else(create_lockfile);
-    let was_update = lockfile.packages.contains_key(name);
+    let (_cache_dir, saved_files) = with_package_lock(name, &version, || {
+        atomic_save_to_cache(&resolved.files, name, &version)
+    })?;
 
     let entry = create_package_entry(&version, source, &resolved.package_checksum, group);
-    add_package(&mut lockfile, name, entry);
-    save_lockfile(project_root, &lockfile)?;
+    let was_update = update_lockfile(project_root, |lockfile| {
+        let was_update = lockfile.packages.contains_key(name);
+        add_package(lockfile, name, entry);
+        was_update
+    })?;
 
     Ok(InstallResult {
         name: name.to_string(),
@@ -224,6 +261,26 @@ fn cache_and_lock(
     })
 }
 
+/// Fetch a package into the cache and return the entry to lock, leaving the
+/// lockfile alone.
+///
+/// This is how `stacy lock` resolves an `[overrides]` source it has no
+/// resolver of its own for (`local:`, `net:`); it writes the lockfile itself.
+pub fn fetch_for_lock(
+    name: &str,
+    source: PackageSource,
+    project_root: &Path,
+    group: &str,
+) -> Result<(PackageEntry, Vec<DownloadedFile>)> {
+    let resolved = resolve_from_source(name, &source, project_root)?;
+    let version = resolved.pin_version();
+    with_package_lock(name, &version, || {
+        atomic_save_to_cache(&resolved.files, name, &version)
+    })?;
+    let entry = create_package_entry(&version, source, &resolved.package_checksum, group);
+    Ok((entry, resolved.files))
+}
+
 /// Install a package from SSC, recording it in the lockfile.
 ///
 /// # Arguments
@@ -272,11 +329,10 @@ pub fn install_package_github(
 ) -> Result<InstallResult> {
     let name = name.to_lowercase();
     let resolved = resolve_github(&name, user, repo, git_ref)?;
-    let source = PackageSource::GitHub {
-        repo: format!("{}/{}", user, repo),
-        tag: git_ref.unwrap_or("main").to_string(),
-        commit: resolved.commit.clone(),
-    };
+    let source = resolved.github_source(
+        format!("{}/{}", user, repo),
+        git_ref.unwrap_or("main").to_string(),
+    );
     cache_and_lock(&name, resolved, source, project_root, group)
 }
 
@@ -318,10 +374,137 @@ pub fn install_from_local(
     let resolved = resolve_local(&name, path, project_root)?;
     let source = PackageSource::Local {
         path: path.to_string(),
+        editable: false,
     };
     cache_and_lock(&name, resolved, source, project_root, group)
 }
 
+/// Version recorded for an editable package: its files change with every edit
+pub const EDITABLE_VERSION: &str = "editable";
+
+/// The lockfile entry of an editable local package (`dev = true`).
+///
+/// Nothing is copied: the directory itself goes on S_ADO, so an edit to an
+/// .ado file shows in the next run. It is read once to check it holds the
+/// package, and the entry carries no checksum, since its contents are
+/// expected to change.
+pub fn editable_entry(
+    name: &str,
+    path: &str,
+    project_root: &Path,
+    group: &str,
+) -> Result<(PackageEntry, Vec<DownloadedFile>)> {
+    let resolved = resolve_local(name, path, project_root)?;
+    let source = PackageSource::Local {
+        path: path.to_string(),
+        editable: true,
+    };
+    let entry = create_package_entry(EDITABLE_VERSION, source, "", group);
+    Ok((entry, resolved.files))
+}
+
+/// Link a local directory as an editable package, recording it in the
+/// lockfile (`stacy add --editable`).
+pub fn link_local(
+    name: &str,
+    path: &str,
+    project_root: &Path,
+    group: &str,
+) -> Result<InstallResult> {
+    let name = name.to_lowercase();
+    let (entry, _) = editable_entry(&name, path, project_root, group)?;
+
+    let was_update = update_lockfile(project_root, |lockfile| {
+        let was_update = lockfile.packages.contains_key(&name);
+        add_package(lockfile, &name, entry);
+        was_update
+    })?;
+
+    Ok(InstallResult {
+        name,
+        version: EDITABLE_VERSION.to_string(),
+        files_installed: Vec::new(),
+        was_update,
+        from_mirror: false,
+        package_checksum: String::new(),
+        declared_deps: Vec::new(),
+        required_stata_version: None,
+    })
+}
+
+/// A package fetched the way `stacy add` would fetch it, written nowhere: not
+/// to the cache, not to the lockfile (`stacy add --dry-run`)
+#[derive(Debug)]
+pub struct PackagePreview {
+    /// The entry `add` would record in the lockfile
+    pub entry: PackageEntry,
+    /// Files that would be installed
+    pub files: Vec<DownloadedFile>,
+    /// Whether the download came from an SSC mirror (not the primary server)
+    pub from_mirror: bool,
+    /// Package names declared on the manifest's `Requires:` line
+    pub declared_deps: Vec<String>,
+    /// Minimum Stata version declared on the manifest's `Requires:` line
+    pub required_stata_version: Option<String>,
+}
+
+/// Fetch a package from `source` and return what `add` would record and
+/// install, without writing anything.
+///
+/// A GitHub source tagged `main` is fetched the way `add` without a ref
+/// fetches it: from `main`, then `master`.
+pub fn preview_package(
+    name: &str,
+    source: PackageSource,
+    project_root: &Path,
+    group: &str,
+) -> Result<PackagePreview> {
+    let name = name.to_lowercase();
+    if let PackageSource::Local {
+        path,
+        editable: true,
+    } = &source
+    {
+        let (entry, files) = editable_entry(&name, path, project_root, group)?;
+        return Ok(PackagePreview {
+            entry,
+            files,
+            from_mirror: false,
+            declared_deps: Vec::new(),
+            required_stata_version: None,
+        });
+    }
+
+    let (resolved, source) = match source {
+        PackageSource::GitHub { repo, tag, .. } => {
+            let (user, repo_name) = repo.split_once('/').ok_or_else(|| {
+                Error::Config(format!(
+                    "Invalid GitHub repo format: {} (want owner/repo)",
+                    repo
+                ))
+            })?;
+            let git_ref = (tag != "main").then_some(tag.as_str());
+            let resolved = resolve_github(&name, user, repo_name, git_ref)?;
+            let source = resolved.github_source(repo, tag);
+            (resolved, source)
+        }
+        source => (resolve_from_source(&name, &source, project_root)?, source),
+    };
+    let entry = create_package_entry(
+        &resolved.pin_version(),
+        source,
+        &resolved.package_checksum,
+        group,
+    );
+    Ok(PackagePreview {
+        entry,
+        files: resolved.files,
+        from_mirror: resolved.from_mirror,
+        declared_deps: resolved.declared_deps,
+        required_stata_version: resolved.required_stata_version,
+    })
+}
+
 /// Install a package exactly as `stacy.lock` pins it.
 ///
 /// This is the `stacy install` path, and it never writes `stacy.lock`: the
@@ -342,6 +525,10 @@ pub fn install_from_local(
 /// A package whose source names no version and whose lockfile entry carries no
 /// checksum (lockfiles written before checksums) has nothing to check against,
 /// and installs as it did before.
+///
+/// Installs of the same package version by other stacy processes are waited
+/// for, not raced: the one that gets the package's lock first downloads it,
+/// and the others find it in the cache once they get it.
 pub fn install_locked(
     name: &str,
     entry: &PackageEntry,
@@ -349,6 +536,53 @@ pub fn install_locked(
     verify: bool,
 ) -> Result<InstallResult> {
     let name = name.to_lowercase();
+    with_package_lock(&name, &entry.version, || {
+        if let Some(cached) = installed_meanwhile(&name, entry, verify) {
+            return Ok(cached);
+        }
+        fetch_locked(&name, entry, project_root, verify)
+    })
+}
+
+/// The package as another process installed it while `install_locked` waited
+/// for its lock, if its contents match the lockfile (or aren't checked)
+fn installed_meanwhile(name: &str, entry: &PackageEntry, verify: bool) -> Option<InstallResult> {
+    let dir = global_cache::store_package_path(&install_store().ok()?, name, &entry.version)?;
+    let package_checksum = global_cache::hash_package_dir(&dir)?;
+    if verify {
+        if let Some(expected) = entry.checksum.as_deref() {
+            let expected = expected.strip_prefix("sha256:").unwrap_or(expected);
+            if package_checksum != expected {
+                return None;
+            }
+        }
+    }
+    let mut files_installed: Vec<PathBuf> = std::fs::read_dir(&dir)
+        .ok()?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.is_file())
+        .collect();
+    files_installed.sort();
+    Some(InstallResult {
+        name: name.to_string(),
+        version: entry.version.clone(),
+        files_installed,
+        was_update: false,
+        from_mirror: false,
+        package_checksum,
+        declared_deps: Vec::new(),
+        required_stata_version: None,
+    })
+}
+
+/// Fetch the package from its source and cache it under the pinned version
+fn fetch_locked(
+    name: &str,
+    entry: &PackageEntry,
+    project_root: &Path,
+    verify: bool,
+) -> Result<InstallResult> {
+    let name = name.to_string();
     let resolved = resolve_from_source(&name, &entry.source, project_root)?;
 
     if let Some(served) = resolved.declared_version.as_deref() {
@@ -391,7 +625,7 @@ fn source_origin(source: &PackageSource) -> String {
         PackageSource::SSC { .. } => "SSC".to_string(),
         PackageSource::GitHub { repo, .. } => format!("GitHub ({})", repo),
         PackageSource::Net { url } => url.clone(),
-        PackageSource::Local { path } => format!("the local directory {}", path),
+        PackageSource::Local { path, .. } => format!("the local directory {}", path),
     }
 }
 
@@ -453,7 +687,9 @@ fn checksum_mismatch_message(
 
 /// Check if a package version is installed in the global cache
 pub fn is_package_installed(name: &str, version: &str) -> bool {
-    global_cache::is_cached(name, version).unwrap_or(false)
+    global_cache::locate_package(name, version)
+        .map(|found| found.is_some())
+        .unwrap_or(false)
 }
 
 /// Check if a package is installed in a local ado directory (legacy check)
@@ -584,110 +820,228 @@ pub fn get_or_create_project(path: Option<&Path>) -> Result<PathBuf> {
     Ok(current_dir)
 }
 
-/// Atomically save downloaded files to the global cache using a staging directory.
+/// Directory under the cache root holding in-progress installs. It sits on
+/// the cache's filesystem so a finished install moves into place with a rename.
+const STAGING_DIR: &str = ".staging";
+
+/// Staging directories older than this belong to an interrupted install.
+const STALE_STAGING_AGE: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+/// Run `f` holding the install lock of a package version, so concurrent
+/// installs of it by other stacy processes (projects sharing a CI runner's
+/// cache) take turns instead of writing the same directory at once.
 ///
-/// Writes files to a temporary `.downloading` directory, then renames it to the
-/// final location. This prevents partial packages from being visible to `is_cached()`.
+/// A read-only global cache takes no installs: that fails here, before
+/// anything is written.
+fn with_package_lock<T>(name: &str, version: &str, f: impl FnOnce() -> Result<T>) -> Result<T> {
+    if global_cache::project_store().is_none() && global_cache::cache_read_only() {
+        return Err(global_cache::read_only_cache_error(&[format!(
+            "{} {}",
+            name, version
+        )]));
+    }
+    let lock_path = global_cache::package_lock_path(&install_store()?, name, version);
+    tracing::debug!(package = name, version, "taking the package's install lock");
+    with_exclusive_lock(&lock_path, f)?
+}
+
+/// Where packages are installed: the project's own store if it has one
+/// (`[packages] install_mode = "local"`, `stacy install --target`), else the
+/// global cache
+fn install_store() -> Result<PathBuf> {
+    match global_cache::project_store() {
+        Some(store) => Ok(store.to_path_buf()),
+        None => global_cache::cache_dir(),
+    }
+}
+
+/// Atomically save downloaded files to the global cache (or the project's
+/// store, see [`install_store`]).
+///
+/// The files are written to a temporary directory under `<cache>/.staging/`
+/// and read back; only when every file on disk matches what was downloaded is
+/// the directory renamed to `{cache}/{name}/{version}/`. Any failure before
+/// that drops the temporary directory, so a half-written package is never
+/// visible to `is_cached()` or `stacy run`.
+///
+/// A package version already in the cache is replaced only if its contents
+/// differ (a repair of a damaged copy): the old directory is moved aside,
+/// the new one moved in, and the old one restored if that move fails.
 fn atomic_save_to_cache(
     files: &[crate::packages::ssc::DownloadedFile],
     name: &str,
     version: &str,
 ) -> Result<(PathBuf, Vec<PathBuf>)> {
-    let final_dir = global_cache::package_path(name, version)?;
+    let cache = install_store()?;
+    let final_dir = cache.join(name.to_lowercase()).join(version);
+    let staging_root = cache.join(STAGING_DIR);
+
+    std::fs::create_dir_all(&staging_root).map_err(|e| {
+        Error::Io(std::io::Error::new(
+            e.kind(),
+            format!(
+                "Failed to create staging dir {}: {}",
+                staging_root.display(),
+                e
+            ),
+        ))
+    })?;
+    remove_stale_staging(&staging_root, final_dir.parent());
+
+    // Removed on drop: every early return below rolls the install back
+    let staging = tempfile::Builder::new()
+        .prefix(&format!("{}-{}.", name, version))
+        .tempdir_in(&staging_root)?;
+
+    let staged_files = save_package_files_to_cache(files, staging.path())?;
+    verify_staged_files(files, staging.path())?;
+
+    let final_files: Vec<PathBuf> = staged_files
+        .iter()
+        .map(|p| final_dir.join(p.file_name().unwrap()))
+        .collect();
+
+    // The move holds the cache's exclusive lock, so a cache other users read
+    // as their shared cache is never swapped under them. It waits rather than
+    // fails: the move is quick, and installs of other packages take it too.
+    let lock_path = global_cache::cache_lock_path(&cache);
+    with_exclusive_lock(&lock_path, || {
+        cache_layout::prepare(&cache)?;
+        move_into_place(staging.path(), &final_dir, &staging_root)?;
+        // The package is in place either way; the metadata only describes it
+        if let Err(e) = cache_layout::record(&final_dir, &name.to_lowercase(), version) {
+            tracing::warn!(package = name, version, error = %e, "could not record package metadata");
+        }
+        Ok::<_, Error>(())
+    })??;
 
-    // Use a unique staging dir per attempt (PID + thread ID) to avoid races
-    let unique_id = format!("{}.{:?}", std::process::id(), std::thread::current().id());
-    let staging_dir = final_dir.with_file_name(format!("{}.downloading.{}", version, unique_id));
+    Ok((final_dir, final_files))
+}
 
-    // Ensure parent exists
-    if let Some(parent) = staging_dir.parent() {
-        std::fs::create_dir_all(parent).map_err(|e| {
+/// Read the staged files back and check them against the downloaded bytes.
+fn verify_staged_files(
+    files: &[crate::packages::ssc::DownloadedFile],
+    staging_dir: &Path,
+) -> Result<()> {
+    use crate::packages::ssc::calculate_sha256;
+
+    // Later files overwrite earlier ones with the same name, as on disk
+    let mut expected = std::collections::HashMap::new();
+    for file in files {
+        expected.insert(
+            staged_file_name(&file.name),
+            calculate_sha256(&file.content),
+        );
+    }
+
+    for (filename, checksum) in expected {
+        let path = staging_dir.join(filename);
+        let written = std::fs::read(&path).map_err(|e| {
             Error::Io(std::io::Error::new(
                 e.kind(),
-                format!("Failed to create parent dir {}: {}", parent.display(), e),
+                format!("Failed to read back {}: {}", path.display(), e),
             ))
         })?;
-
-        // Clean up stale staging dirs from interrupted installs (different PIDs).
-        // Skip dirs from our own PID — those are concurrent threads, not stale.
-        let our_pid_marker = format!(".{}", std::process::id());
-        let prefix = format!("{}.downloading", version);
-        if let Ok(entries) = std::fs::read_dir(parent) {
-            for entry in entries.flatten() {
-                if let Some(name) = entry.file_name().to_str() {
-                    if name.starts_with(&prefix) && !name.contains(&our_pid_marker) {
-                        let _ = std::fs::remove_dir_all(entry.path());
-                    }
-                }
-            }
+        if calculate_sha256(&written) != checksum {
+            return Err(Error::Integrity(format!(
+                "{} was not written correctly; the install was rolled back",
+                filename
+            )));
         }
     }
+    Ok(())
+}
 
-    // Clean up our own staging dir if it exists (shouldn't happen, but be safe)
-    if staging_dir.exists() {
-        let _ = std::fs::remove_dir_all(&staging_dir);
+/// Rename the staged package to its final place, replacing a copy with
+/// different contents and restoring it if the replacement fails.
+fn move_into_place(staged: &Path, final_dir: &Path, staging_root: &Path) -> Result<()> {
+    let io_error = |e: std::io::Error, what: String| {
+        Error::Io(std::io::Error::new(e.kind(), format!("{}: {}", what, e)))
+    };
+
+    if let Some(parent) = final_dir.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| io_error(e, format!("Failed to create {}", parent.display())))?;
     }
 
-    std::fs::create_dir(&staging_dir).map_err(|e| {
-        Error::Io(std::io::Error::new(
-            e.kind(),
-            format!(
-                "Failed to create staging dir {}: {}",
-                staging_dir.display(),
-                e
-            ),
-        ))
-    })?;
+    let previous = if final_dir.exists() {
+        // Another process (or an earlier run) placed the same files: keep them
+        if global_cache::hash_package_dir(final_dir) == global_cache::hash_package_dir(staged) {
+            return Ok(());
+        }
+        let aside = tempfile::Builder::new()
+            .prefix("previous.")
+            .tempdir_in(staging_root)?
+            .keep()
+            .join("package");
+        std::fs::rename(final_dir, &aside)
+            .map_err(|e| io_error(e, format!("Failed to move aside {}", final_dir.display())))?;
+        Some(aside)
+    } else {
+        None
+    };
 
-    // Write to staging
-    let result = save_package_files_to_cache(files, &staging_dir);
-
-    match result {
-        Ok(staged_files) => {
-            // Atomic rename: try to move staging → final
-            // If final_dir already exists (another process won the race), that's fine —
-            // just clean up our staging dir and use the existing final dir.
-            match std::fs::rename(&staging_dir, &final_dir) {
-                Ok(()) => {
-                    // We won: remap paths from staging to final dir
-                    let final_files = staged_files
-                        .iter()
-                        .map(|p| final_dir.join(p.file_name().unwrap()))
-                        .collect();
-                    Ok((final_dir, final_files))
-                }
-                Err(_) if final_dir.exists() => {
-                    // Another process/thread already placed the final dir — that's OK.
-                    // Clean up our staging dir and return the existing final dir.
-                    let _ = std::fs::remove_dir_all(&staging_dir);
-                    let final_files = staged_files
-                        .iter()
-                        .map(|p| final_dir.join(p.file_name().unwrap()))
-                        .collect();
-                    Ok((final_dir, final_files))
-                }
-                Err(e) => {
-                    let _ = std::fs::remove_dir_all(&staging_dir);
-                    Err(Error::Io(std::io::Error::new(
-                        e.kind(),
-                        format!(
-                            "Failed to rename staging dir {} to {}: {}",
-                            staging_dir.display(),
-                            final_dir.display(),
-                            e
-                        ),
-                    )))
-                }
+    match std::fs::rename(staged, final_dir) {
+        Ok(()) => {
+            if let Some(aside) = previous.as_ref().and_then(|p| p.parent()) {
+                let _ = std::fs::remove_dir_all(aside);
             }
+            Ok(())
         }
         Err(e) => {
-            // Clean up staging on failure
-            let _ = std::fs::remove_dir_all(&staging_dir);
-            Err(e)
+            if let Some(aside) = &previous {
+                let _ = std::fs::rename(aside, final_dir);
+                if let Some(parent) = aside.parent() {
+                    let _ = std::fs::remove_dir_all(parent);
+                }
+            }
+            Err(io_error(
+                e,
+                format!(
+                    "Failed to move {} into {}",
+                    staged.display(),
+                    final_dir.display()
+                ),
+            ))
+        }
+    }
+}
+
+/// Remove what interrupted installs left behind: staging directories older
+/// than an hour, and `<version>.downloading*` directories written next to
+/// the versions by older stacy releases.
+fn remove_stale_staging(staging_root: &Path, package_dir: Option<&Path>) {
+    if let Ok(entries) = std::fs::read_dir(staging_root) {
+        for entry in entries.flatten() {
+            let stale = entry
+                .metadata()
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|modified| modified.elapsed().ok())
+                .is_some_and(|age| age > STALE_STAGING_AGE);
+            if stale {
+                let _ = std::fs::remove_dir_all(entry.path());
+            }
+        }
+    }
+
+    if let Some(Ok(entries)) = package_dir.map(std::fs::read_dir) {
+        for entry in entries.flatten() {
+            if entry.file_name().to_string_lossy().contains(".downloading") {
+                let _ = std::fs::remove_dir_all(entry.path());
+            }
         }
     }
 }
 
+/// The name a downloaded file is saved under: its path components stripped
+fn staged_file_name(name: &str) -> &str {
+    std::path::Path::new(name)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or(name)
+}
+
 /// Save downloaded files to a cache directory (flat structure)
 ///
 /// Unlike the old save_package_files which organized by first letter,
@@ -700,10 +1054,7 @@ fn save_package_files_to_cache(
 
     for file in files {
         // Get the actual filename (strip any path components like "../e/")
-        let filename = std::path::Path::new(&file.name)
-            .file_name()
-            .and_then(|n| n.to_str())
-            .unwrap_or(&file.name);
+        let filename = staged_file_name(&file.name);
 
         let target_path = cache_dir.join(filename);
         std::fs::write(&target_path, &file.content).map_err(|e| {
@@ -1133,6 +1484,54 @@ mod tests {
     }
 
     // C5: Test concurrent atomic_save_to_cache calls don't corrupt the final state
+    #[test]
+    #[serial]
+    fn test_install_locked_waits_for_concurrent_install() {
+        with_test_cache(|temp| {
+            use crate::packages::ssc::DownloadedFile;
+            use std::sync::mpsc;
+            use std::thread;
+
+            // The package's source directory is missing: fetching would fail
+            let entry = create_package_entry(
+                "1.0.0",
+                PackageSource::Local {
+                    path: "vendor/waitpkg".to_string(),
+                    editable: false,
+                },
+                "",
+                "production",
+            );
+
+            // Another process is installing it
+            let lock_path = global_cache::package_lock_path(
+                &global_cache::cache_dir().unwrap(),
+                "waitpkg",
+                "1.0.0",
+            );
+            let (locked, is_locked) = mpsc::channel();
+            let other = thread::spawn(move || {
+                with_exclusive_lock(&lock_path, || {
+                    locked.send(()).unwrap();
+                    thread::sleep(std::time::Duration::from_millis(200));
+                    let files = vec![DownloadedFile {
+                        name: "waitpkg.ado".to_string(),
+                        content: b"program waitpkg\nend\n".to_vec(),
+                        checksum: String::new(),
+                    }];
+                    atomic_save_to_cache(&files, "waitpkg", "1.0.0")
+                })
+            });
+            is_locked.recv().unwrap();
+
+            let result = install_locked("waitpkg", &entry, temp.path(), true).unwrap();
+            other.join().unwrap().unwrap().unwrap();
+            assert_eq!(result.version, "1.0.0");
+            assert_eq!(result.files_installed.len(), 1);
+            assert!(result.files_installed[0].ends_with("waitpkg.ado"));
+        });
+    }
+
     #[test]
     #[serial]
     fn test_concurrent_atomic_save_no_corruption() {
@@ -1225,4 +1624,68 @@ mod tests {
             assert!(!staging_dir2.exists());
         });
     }
+
+    #[test]
+    #[serial]
+    fn test_atomic_save_replaces_damaged_copy() {
+        with_test_cache(|_temp| {
+            use crate::packages::ssc::DownloadedFile;
+
+            // A half-written copy left by an interrupted install
+            let final_dir = global_cache::ensure_package_cache_dir("fixpkg", "1.0.0").unwrap();
+            fs::write(final_dir.join("fixpkg.ado"), "prog").unwrap();
+
+            let files = vec![DownloadedFile {
+                name: "fixpkg.ado".to_string(),
+                content: b"program define fixpkg\nend\n".to_vec(),
+                checksum: String::new(),
+            }];
+            atomic_save_to_cache(&files, "fixpkg", "1.0.0").unwrap();
+
+            assert_eq!(
+                fs::read(final_dir.join("fixpkg.ado")).unwrap(),
+                b"program define fixpkg\nend\n"
+            );
+            let staging = global_cache::cache_dir().unwrap().join(STAGING_DIR);
+            assert_eq!(fs::read_dir(&staging).unwrap().count(), 0);
+        });
+    }
+
+    #[test]
+    #[serial]
+    fn test_atomic_save_failure_leaves_previous_copy() {
+        with_test_cache(|_temp| {
+            use crate::packages::ssc::DownloadedFile;
+
+            let final_dir = global_cache::ensure_package_cache_dir("keeppkg", "1.0.0").unwrap();
+            fs::write(final_dir.join("keeppkg.ado"), "previous").unwrap();
+
+            // The second file cannot be written: the install must roll back
+            let files = vec![
+                DownloadedFile {
+                    name: "keeppkg.ado".to_string(),
+                    content: b"new".to_vec(),
+                    checksum: String::new(),
+                },
+                DownloadedFile {
+                    name: String::new(),
+                    content: b"unwritable".to_vec(),
+                    checksum: String::new(),
+                },
+            ];
+            assert!(atomic_save_to_cache(&files, "keeppkg", "1.0.0").is_err());
+
+            assert_eq!(
+                fs::read(final_dir.join("keeppkg.ado")).unwrap(),
+                b"previous"
+            );
+            let staging = global_cache::cache_dir().unwrap().join(STAGING_DIR);
+            assert_eq!(fs::read_dir(&staging).unwrap().count(), 0);
+            assert_eq!(
+                global_cache::list_cached_packages().unwrap().len(),
+                1,
+                "staging must not be listed as a package"
+            );
+        });
+    }
 }
@@ -4,20 +4,34 @@
 //! and checksums for reproducible environments.
 
 use crate::error::{Error, Result};
+use crate::project::config::Config;
+use crate::project::profile;
 use crate::project::{Lockfile, PackageEntry, PackageSource};
+use crate::utils::lock::{project_lock_path, with_state_lock};
+use crate::utils::paths::write_atomically;
 use std::collections::HashMap;
 use std::path::Path;
 
+/// Format of stacy.lock this stacy writes. `stacy migrate` brings older
+/// lockfiles up to it; newer ones are refused.
+pub const LOCKFILE_VERSION: u32 = 1;
+
 /// Header comment for stacy.lock
-const LOCKFILE_HEADER: &str = r#"# Auto-generated by stacy - do not edit manually
-# Use `stacy install` to manage packages
-"#;
+fn lockfile_header() -> String {
+    format!(
+        "# Auto-generated by stacy {} - do not edit manually\n\
+         # Use `stacy install` to install these packages, `stacy lock` to update them.\n\
+         # Packages are sorted by name and no timestamps are recorded, so re-locking\n\
+         # unchanged packages leaves this file byte-identical.\n",
+        env!("CARGO_PKG_VERSION")
+    )
+}
 
-/// Load lockfile from project root
+/// Load lockfile from project root (`stacy.<profile>.lock` under `--env-profile`)
 ///
 /// Returns None if lockfile doesn't exist
 pub fn load_lockfile(project_root: &Path) -> Result<Option<Lockfile>> {
-    let lockfile_path = project_root.join("stacy.lock");
+    let lockfile_path = project_root.join(profile::lockfile_name());
 
     if !lockfile_path.exists() {
         return Ok(None);
@@ -30,42 +44,71 @@ pub fn load_lockfile(project_root: &Path) -> Result<Option<Lockfile>> {
         ))
     })?;
 
-    let lockfile: Lockfile = toml::from_str(&content)
-        .map_err(|e| Error::Config(format!("Invalid stacy.lock format: {}", e)))?;
+    if has_conflict_markers(&content) {
+        return Err(Error::Config(
+            "stacy.lock has unresolved merge conflicts. \
+             Run `stacy lock --resolve-conflicts` to merge both sides."
+                .to_string(),
+        ));
+    }
+
+    let lockfile: Lockfile = toml::from_str(&content).map_err(|e| {
+        let hint = match toml::from_str::<toml::Table>(&content) {
+            Ok(table) if !table.contains_key("version") => {
+                "\nhint: this looks like a stacy.lock in an older format; `stacy migrate` updates it"
+            }
+            _ => "",
+        };
+        Error::Config(format!("Invalid stacy.lock format: {}{}", e, hint))
+    })?;
+    check_format_version(&lockfile.version)?;
 
     Ok(Some(lockfile))
 }
 
-/// Save lockfile to project root
-pub fn save_lockfile(project_root: &Path, lockfile: &Lockfile) -> Result<()> {
-    let lockfile_path = project_root.join("stacy.lock");
+/// Refuse a lockfile written in a newer format than this stacy knows
+pub fn check_format_version(version: &str) -> Result<()> {
+    match version.trim().parse::<u32>() {
+        Ok(version) if version <= LOCKFILE_VERSION => Ok(()),
+        Ok(version) => Err(Error::Config(format!(
+            "stacy.lock is in format {}, newer than the format {} this stacy {} reads. \
+             Upgrade stacy (stacy self update) to use it.",
+            version,
+            LOCKFILE_VERSION,
+            env!("CARGO_PKG_VERSION")
+        ))),
+        Err(_) => Err(Error::Config(format!(
+            "Invalid stacy.lock format: unknown format version '{}'",
+            version
+        ))),
+    }
+}
 
+/// The content [`save_lockfile`] writes for `lockfile`
+pub fn saved_content(lockfile: &Lockfile) -> Result<String> {
     // Always update stacy_version to current version when saving
     let mut lockfile_to_save = lockfile.clone();
     lockfile_to_save.stacy_version = Some(env!("CARGO_PKG_VERSION").to_string());
+    render_lockfile(&lockfile_to_save)
+}
 
-    // Serialize to TOML Value, then sort the packages table alphabetically
-    // for deterministic lockfile output (M1).
-    let mut value = toml::Value::try_from(&lockfile_to_save)
-        .map_err(|e| Error::Config(format!("Failed to serialize lockfile: {}", e)))?;
-
-    if let Some(packages_table) = value.get("packages").and_then(|v| v.as_table()) {
-        let mut sorted = toml::map::Map::new();
-        let mut keys: Vec<_> = packages_table.keys().collect();
-        keys.sort();
-        for key in keys {
-            sorted.insert(key.clone(), packages_table[key].clone());
-        }
-        value["packages"] = toml::Value::Table(sorted);
+/// Save lockfile to project root
+///
+/// The file is written in canonical form (see [`render_lockfile`]) and only
+/// when its content changes, so saving an unchanged lockfile never touches it.
+/// It is replaced whole under the project lock, so two stacy processes
+/// saving at once never leave a mix of both.
+pub fn save_lockfile(project_root: &Path, lockfile: &Lockfile) -> Result<()> {
+    let lockfile_path = project_root.join(profile::lockfile_name());
+    let full_content = saved_content(lockfile)?;
+    if std::fs::read_to_string(&lockfile_path).ok().as_deref() == Some(full_content.as_str()) {
+        return Ok(());
     }
 
-    let content = toml::to_string_pretty(&value)
-        .map_err(|e| Error::Config(format!("Failed to serialize lockfile: {}", e)))?;
-
-    // Add header and write
-    let full_content = format!("{}\n{}", LOCKFILE_HEADER, content);
-
-    std::fs::write(&lockfile_path, full_content).map_err(|e| {
+    with_state_lock(&project_lock_path(project_root), || {
+        write_atomically(&lockfile_path, full_content.as_bytes())
+    })?
+    .map_err(|e| {
         Error::Io(std::io::Error::new(
             e.kind(),
             format!("Failed to write stacy.lock: {}", e),
@@ -75,10 +118,113 @@ pub fn save_lockfile(project_root: &Path, lockfile: &Lockfile) -> Result<()> {
     Ok(())
 }
 
+/// Load the lockfile (or start one), change it and save it, all under the
+/// project lock (see [`with_state_lock`])
+pub fn update_lockfile<T>(project_root: &Path, f: impl FnOnce(&mut Lockfile) -> T) -> Result<T> {
+    with_state_lock(&project_lock_path(project_root), || {
+        let mut lockfile = load_lockfile(project_root)?.unwrap_or_else(create_lockfile);
+        let value = f(&mut lockfile);
+        save_lockfile(project_root, &lockfile)?;
+        Ok(value)
+    })?
+}
+
+/// Serialize a lockfile in canonical form.
+///
+/// The same lockfile always renders to the same bytes: packages are sorted
+/// by name, each entry lists `version`, `checksum` and `group` in that order,
+/// its source table starts with `type` followed by the remaining keys sorted,
+/// and checksums are normalized to lowercase `sha256:<hex>`. Lines end in
+/// `\n` on every platform.
+pub fn render_lockfile(lockfile: &Lockfile) -> Result<String> {
+    let mut out = lockfile_header();
+    out.push('\n');
+    out.push_str(&format!("version = {}\n", toml_string(&lockfile.version)));
+    if let Some(stacy_version) = &lockfile.stacy_version {
+        out.push_str(&format!("stacy_version = {}\n", toml_string(stacy_version)));
+    }
+
+    let mut names: Vec<&String> = lockfile.packages.keys().collect();
+    names.sort();
+    if names.is_empty() {
+        out.push_str("\n[packages]\n");
+    }
+
+    for name in names {
+        let entry = &lockfile.packages[name];
+        let key = format!("packages.{}", toml_key(name));
+
+        out.push_str(&format!("\n[{}]\n", key));
+        out.push_str(&format!("version = {}\n", toml_string(&entry.version)));
+        if let Some(checksum) = &entry.checksum {
+            out.push_str(&format!(
+                "checksum = {}\n",
+                toml_string(&normalize_checksum(checksum))
+            ));
+        }
+        out.push_str(&format!("group = {}\n", toml_string(&entry.group)));
+
+        let source = toml::Value::try_from(&entry.source)
+            .map_err(|e| Error::Config(format!("Failed to serialize lockfile: {}", e)))?;
+        let Some(source) = source.as_table() else {
+            return Err(Error::Config(format!(
+                "Failed to serialize lockfile: source of {} is not a table",
+                name
+            )));
+        };
+        out.push_str(&format!("\n[{}.source]\n", key));
+        if let Some(source_type) = source.get("type") {
+            out.push_str(&format!("type = {}\n", source_type));
+        }
+        let mut fields: Vec<(&String, &toml::Value)> = source
+            .iter()
+            .filter(|(k, _)| k.as_str() != "type")
+            .collect();
+        fields.sort_by(|a, b| a.0.cmp(b.0));
+        for (field, value) in fields {
+            out.push_str(&format!("{} = {}\n", toml_key(field), value));
+        }
+    }
+
+    Ok(out)
+}
+
+/// Normalize a checksum to lowercase `sha256:<hex>`, adding the algorithm
+/// prefix when it is missing
+pub fn normalize_checksum(checksum: &str) -> String {
+    let checksum = checksum.trim();
+    match checksum.split_once(':') {
+        Some((algorithm, hex)) => format!(
+            "{}:{}",
+            algorithm.trim().to_lowercase(),
+            hex.trim().to_lowercase()
+        ),
+        None => format!("sha256:{}", checksum.to_lowercase()),
+    }
+}
+
+/// A TOML basic string
+fn toml_string(s: &str) -> String {
+    toml::Value::String(s.to_string()).to_string()
+}
+
+/// A TOML key: bare when it can be, quoted otherwise
+fn toml_key(key: &str) -> String {
+    let bare = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if bare {
+        key.to_string()
+    } else {
+        toml_string(key)
+    }
+}
+
 /// Create a new empty lockfile
 pub fn create_lockfile() -> Lockfile {
     Lockfile {
-        version: "1".to_string(),
+        version: LOCKFILE_VERSION.to_string(),
         stacy_version: Some(env!("CARGO_PKG_VERSION").to_string()),
         packages: HashMap::new(),
     }
@@ -124,7 +270,7 @@ pub struct LockfileSyncResult {
 
 /// Verify that lockfile is in sync with the manifest (stacy.toml).
 ///
-/// This is used by `--frozen` mode to ensure reproducible builds in CI.
+/// This is used by frozen mode to ensure reproducible builds in CI.
 /// Returns details about any differences found.
 pub fn verify_lockfile_sync(
     lockfile: &Lockfile,
@@ -158,6 +304,176 @@ pub fn verify_lockfile_sync(
     }
 }
 
+/// Whether frozen mode is on: `--frozen`, else `STACY_FROZEN` (any value but
+/// empty or `0`), else `[install] frozen` in stacy.toml.
+///
+/// A set `STACY_FROZEN` overrides the config either way, so `STACY_FROZEN=0`
+/// turns off a project's `frozen = true` on a developer machine.
+pub fn frozen_mode(flag: bool, config: Option<&Config>) -> bool {
+    if flag {
+        return true;
+    }
+    match std::env::var("STACY_FROZEN") {
+        Ok(value) => !value.is_empty() && value != "0",
+        Err(_) => config.is_some_and(|c| c.install.frozen),
+    }
+}
+
+/// Frozen mode: fail unless stacy.lock lists exactly the packages stacy.toml
+/// declares. A missing lockfile passes only when no packages are declared.
+pub fn check_frozen(lockfile: Option<&Lockfile>, config: &Config) -> Result<()> {
+    let config_package_names: Vec<&str> = config
+        .packages
+        .all_packages()
+        .map(|(name, _, _)| name.as_str())
+        .collect();
+
+    let Some(lockfile) = lockfile else {
+        if config_package_names.is_empty() {
+            return Ok(());
+        }
+        return Err(Error::Config(
+            "No stacy.lock found but stacy.toml has packages (frozen mode)\n\n  hint: run `stacy lock` then commit stacy.lock".to_string(),
+        ));
+    };
+
+    let sync_result = verify_lockfile_sync(lockfile, &config_package_names);
+    if sync_result.in_sync {
+        return Ok(());
+    }
+
+    let mut msg = String::from("Lockfile out of sync with stacy.toml (frozen mode)\n");
+    if !sync_result.missing_in_lock.is_empty() {
+        msg.push_str("\n  Missing from stacy.lock:\n");
+        for name in &sync_result.missing_in_lock {
+            msg.push_str(&format!("    - {}\n", name));
+        }
+    }
+    if !sync_result.extra_in_lock.is_empty() {
+        msg.push_str("\n  Extra in stacy.lock (not in stacy.toml):\n");
+        for name in &sync_result.extra_in_lock {
+            msg.push_str(&format!("    - {}\n", name));
+        }
+    }
+    msg.push_str("\n  hint: run `stacy lock` then commit stacy.lock");
+    Err(Error::Config(msg))
+}
+
+/// Whether `content` still has git merge conflict markers
+pub fn has_conflict_markers(content: &str) -> bool {
+    content
+        .lines()
+        .any(|line| line.starts_with("<<<<<<<") || line.starts_with(">>>>>>>"))
+}
+
+/// Both sides of a conflicted stacy.lock, merged
+#[derive(Debug)]
+pub struct ConflictMerge {
+    /// Entries both sides agree on, plus those only one side has
+    pub lockfile: Lockfile,
+    /// Packages the two sides lock differently, left out to be re-resolved
+    pub conflicting: Vec<String>,
+}
+
+/// Merge the two sides of a lockfile git left conflict markers in.
+///
+/// Each side (`<<<<<<<` to `=======`, `=======` to `>>>>>>>`, plus everything
+/// outside the markers) is parsed as a lockfile of its own; a diff3 base
+/// section (`|||||||`) is ignored. A package locked identically on both
+/// sides, or on only one, is kept. A package the sides disagree on is
+/// dropped and reported, so `stacy lock` resolves it again from stacy.toml.
+/// If either side does not parse (a conflict cutting through a table), the
+/// merge fails: leaving that side out would lose its packages and hide its
+/// conflicts.
+pub fn merge_conflicted(content: &str) -> Result<ConflictMerge> {
+    enum Section {
+        Both,
+        Ours,
+        Base,
+        Theirs,
+    }
+
+    let mut ours = String::new();
+    let mut theirs = String::new();
+    let mut section = Section::Both;
+    for line in content.lines() {
+        if line.starts_with("<<<<<<<") {
+            section = Section::Ours;
+            continue;
+        } else if line.starts_with("|||||||") {
+            section = Section::Base;
+            continue;
+        } else if line.starts_with("=======") && !matches!(section, Section::Both) {
+            section = Section::Theirs;
+            continue;
+        } else if line.starts_with(">>>>>>>") {
+            section = Section::Both;
+            continue;
+        }
+        match section {
+            Section::Both => {
+                ours.push_str(line);
+                ours.push('\n');
+                theirs.push_str(line);
+                theirs.push('\n');
+            }
+            Section::Ours => {
+                ours.push_str(line);
+                ours.push('\n');
+            }
+            Section::Theirs => {
+                theirs.push_str(line);
+                theirs.push('\n');
+            }
+            Section::Base => {}
+        }
+    }
+
+    let sides = [("ours", ours), ("theirs", theirs)]
+        .iter()
+        .map(|(label, side)| {
+            toml::from_str::<Lockfile>(side).map_err(|e| {
+                Error::Config(format!(
+                    "Could not parse {} side of the conflicted stacy.lock: {}\n\
+                     Check out one side (`git checkout --ours stacy.lock`) and run `stacy lock`.",
+                    label, e
+                ))
+            })
+        })
+        .collect::<Result<Vec<Lockfile>>>()?;
+    let first = &sides[0];
+
+    let mut lockfile = Lockfile {
+        version: first.version.clone(),
+        stacy_version: first.stacy_version.clone(),
+        packages: HashMap::new(),
+    };
+    let mut conflicting = Vec::new();
+    for side in &sides {
+        for (name, entry) in &side.packages {
+            if conflicting.contains(name) {
+                continue;
+            }
+            match lockfile.packages.get(name) {
+                Some(existing) if existing != entry => {
+                    lockfile.packages.remove(name);
+                    conflicting.push(name.clone());
+                }
+                Some(_) => {}
+                None => {
+                    lockfile.packages.insert(name.clone(), entry.clone());
+                }
+            }
+        }
+    }
+    conflicting.sort();
+
+    Ok(ConflictMerge {
+        lockfile,
+        conflicting,
+    })
+}
+
 /// Get a package entry from the lockfile
 pub fn get_package<'a>(lockfile: &'a Lockfile, name: &str) -> Option<&'a PackageEntry> {
     lockfile.packages.get(name)
@@ -645,4 +961,204 @@ version = "1"
             zebra_pos
         );
     }
+
+    /// A stacy.lock after two coauthors each added a package and updated
+    /// estout on different days
+    const CONFLICTED_LOCKFILE: &str = r#"version = "1"
+stacy_version = "1.5.0"
+
+[packages.estout]
+<<<<<<< HEAD
+version = "20240115"
+checksum = "sha256:aaa"
+=======
+version = "20240301"
+checksum = "sha256:bbb"
+>>>>>>> coauthor
+group = "production"
+
+[packages.estout.source]
+type = "SSC"
+name = "estout"
+
+<<<<<<< HEAD
+[packages.ftools]
+version = "20230820"
+checksum = "sha256:ccc"
+group = "production"
+
+[packages.ftools.source]
+type = "SSC"
+name = "ftools"
+=======
+[packages.reghdfe]
+version = "20231001"
+checksum = "sha256:ddd"
+group = "production"
+
+[packages.reghdfe.source]
+type = "SSC"
+name = "reghdfe"
+>>>>>>> coauthor
+"#;
+
+    #[test]
+    fn test_load_lockfile_rejects_conflict_markers() {
+        let temp = TempDir::new().unwrap();
+        fs::write(temp.path().join("stacy.lock"), CONFLICTED_LOCKFILE).unwrap();
+
+        let err = load_lockfile(temp.path()).unwrap_err();
+        assert!(err.to_string().contains("--resolve-conflicts"));
+    }
+
+    #[test]
+    fn test_merge_conflicted_keeps_agreed_and_one_sided_entries() {
+        assert!(has_conflict_markers(CONFLICTED_LOCKFILE));
+
+        let merge = merge_conflicted(CONFLICTED_LOCKFILE).unwrap();
+        let mut names: Vec<_> = merge.lockfile.packages.keys().cloned().collect();
+        names.sort();
+        assert_eq!(names, vec!["ftools", "reghdfe"]);
+        assert_eq!(merge.conflicting, vec!["estout"]);
+    }
+
+    #[test]
+    fn test_merge_conflicted_fails_when_no_side_parses() {
+        let content = "<<<<<<< HEAD\nnot toml [\n=======\nalso = not [\n>>>>>>> other\n";
+        assert!(merge_conflicted(content).is_err());
+    }
+
+    #[test]
+    fn test_merge_conflicted_fails_when_one_side_does_not_parse() {
+        // Their side cuts through the ftools table: skipping it would drop
+        // reghdfe and hide the conflict on estout
+        let content = CONFLICTED_LOCKFILE.replacen("[packages.reghdfe]", "[packages.reghdfe", 1);
+        let err = merge_conflicted(&content).unwrap_err();
+        assert!(err.to_string().contains("theirs side"));
+    }
+
+    #[test]
+    fn test_render_lockfile_is_canonical() {
+        let ssc = |name: &str, checksum: &str| PackageEntry {
+            version: "1.0.0".to_string(),
+            source: PackageSource::SSC {
+                name: name.to_string(),
+            },
+            checksum: Some(checksum.to_string()),
+            group: "production".to_string(),
+        };
+        let github = PackageEntry {
+            version: "6.12.3".to_string(),
+            source: PackageSource::GitHub {
+                repo: "sergiocorreia/reghdfe".to_string(),
+                tag: "v6.12.3".to_string(),
+                commit: Some("abc123".to_string()),
+                pkg_dir: None,
+                synthesized: false,
+            },
+            checksum: None,
+            group: "dev".to_string(),
+        };
+
+        // Same packages, inserted in different orders
+        let mut first = create_lockfile();
+        add_package(&mut first, "zebra", ssc("zebra", "sha256:abc"));
+        add_package(&mut first, "reghdfe", github.clone());
+        add_package(&mut first, "alpha", ssc("alpha", "SHA256:DEF"));
+        let mut second = create_lockfile();
+        add_package(&mut second, "alpha", ssc("alpha", "def"));
+        add_package(&mut second, "reghdfe", github);
+        add_package(&mut second, "zebra", ssc("zebra", "sha256:abc"));
+
+        let rendered = render_lockfile(&first).unwrap();
+        assert_eq!(rendered, render_lockfile(&second).unwrap());
+        assert!(!rendered.contains('\r'));
+
+        let expected_alpha = "\n[packages.alpha]\nversion = \"1.0.0\"\nchecksum = \"sha256:def\"\ngroup = \"production\"\n\n[packages.alpha.source]\ntype = \"SSC\"\nname = \"alpha\"\n";
+        assert!(rendered.contains(expected_alpha));
+        let expected_source = "[packages.reghdfe.source]\ntype = \"GitHub\"\ncommit = \"abc123\"\nrepo = \"sergiocorreia/reghdfe\"\ntag = \"v6.12.3\"\n";
+        assert!(rendered.contains(expected_source));
+
+        // And it parses back to the same lockfile
+        let parsed: Lockfile = toml::from_str(&rendered).unwrap();
+        assert_eq!(parsed.packages["reghdfe"], first.packages["reghdfe"]);
+        assert_eq!(
+            parsed.packages["alpha"].checksum.as_deref(),
+            Some("sha256:def")
+        );
+    }
+
+    #[test]
+    fn test_relocking_one_package_leaves_other_entries_byte_identical() {
+        let entry = |version: &str, checksum: &str| {
+            create_package_entry(
+                version,
+                PackageSource::SSC {
+                    name: "x".to_string(),
+                },
+                checksum,
+                "production",
+            )
+        };
+        let mut lockfile = create_lockfile();
+        add_package(&mut lockfile, "estout", entry("20240315", "aaa"));
+        add_package(&mut lockfile, "reghdfe", entry("6.12.3", "bbb"));
+        add_package(&mut lockfile, "winsor2", entry("20200101", "ccc"));
+        let before = render_lockfile(&lockfile).unwrap();
+
+        // What `stacy lock --package reghdfe` does to the lockfile
+        remove_package(&mut lockfile, "reghdfe");
+        add_package(&mut lockfile, "reghdfe", entry("6.12.4", "ddd"));
+        let after = render_lockfile(&lockfile).unwrap();
+
+        // Only the two lines of the relocked entry differ
+        assert_eq!(before.lines().count(), after.lines().count());
+        let changed: Vec<_> = before
+            .lines()
+            .zip(after.lines())
+            .filter(|(a, b)| a != b)
+            .collect();
+        assert_eq!(
+            changed,
+            vec![
+                ("version = \"6.12.3\"", "version = \"6.12.4\""),
+                ("checksum = \"sha256:bbb\"", "checksum = \"sha256:ddd\""),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render_empty_lockfile_parses() {
+        let rendered = render_lockfile(&create_lockfile()).unwrap();
+        let parsed: Lockfile = toml::from_str(&rendered).unwrap();
+        assert!(parsed.packages.is_empty());
+    }
+
+    #[test]
+    fn test_render_lockfile_quotes_unusual_names() {
+        let mut lockfile = create_lockfile();
+        add_package(
+            &mut lockfile,
+            "my.pkg",
+            create_package_entry(
+                "1.0",
+                PackageSource::SSC {
+                    name: "my.pkg".into(),
+                },
+                "",
+                "production",
+            ),
+        );
+        let rendered = render_lockfile(&lockfile).unwrap();
+        assert!(rendered.contains("[packages.\"my.pkg\"]"));
+        let parsed: Lockfile = toml::from_str(&rendered).unwrap();
+        assert!(parsed.packages.contains_key("my.pkg"));
+    }
+
+    #[test]
+    fn test_normalize_checksum() {
+        assert_eq!(normalize_checksum("sha256:ABC"), "sha256:abc");
+        assert_eq!(normalize_checksum(" abc123 "), "sha256:abc123");
+        assert_eq!(normalize_checksum("SHA256: ff"), "sha256:ff");
+    }
 }
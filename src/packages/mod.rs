@@ -1,4 +1,6 @@
 pub mod cache;
+pub mod cache_layout;
+pub mod collisions;
 pub mod dep_scan;
 pub mod github;
 pub mod global_cache;
@@ -10,7 +12,10 @@ pub mod lockfile;
 pub mod naming;
 pub mod net;
 pub mod pkg_parser;
+pub mod requirements;
 pub mod ssc;
+pub mod ssc_index;
+pub mod usage;
 
 // Package types are defined in project/mod.rs
 // Re-export them here for convenience (currently unused during development)
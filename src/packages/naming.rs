@@ -1,8 +1,9 @@
 //! Package naming hints for SSC
 //!
 //! Many Stata commands are provided by packages with different names.
-//! When a user tries to install a command name that isn't a package,
-//! this module suggests the correct package.
+//! When a user tries to install a command name that isn't a package, or a
+//! script stops on a command Stata doesn't know, this module suggests the
+//! correct package.
 
 /// Look up which package provides a given command name.
 ///
@@ -18,6 +19,22 @@ pub fn find_provider(name: &str) -> Option<(&'static str, &'static [&'static str
     None
 }
 
+/// How to get `command`, for a script Stata stopped on because it doesn't
+/// know it: the package the curated map says provides it, else the package
+/// of that name if the local SSC index lists one. Never goes to the network.
+pub fn command_hint(command: &str) -> Option<String> {
+    let command = command.to_lowercase();
+    if let Some((package, _)) = find_provider(&command) {
+        return Some(format!(
+            "`{}` is provided by the SSC package {}: stacy add {}",
+            command, package, package
+        ));
+    }
+    super::ssc_index::load()
+        .filter(|index| index.contains(&command))
+        .map(|_| format!("`{}` is an SSC package: stacy add {}", command, command))
+}
+
 /// Curated map: (package_name, &[command_names_it_provides])
 ///
 /// Only includes cases where the command name differs from the package name.
@@ -96,6 +113,12 @@ mod tests {
         assert_eq!(pkg, "estout");
     }
 
+    #[test]
+    fn test_command_hint_from_curated_map() {
+        let hint = command_hint("EstTab").unwrap();
+        assert!(hint.contains("stacy add estout"));
+    }
+
     #[test]
     fn test_find_provider_unknown() {
         assert!(find_provider("notarealcommand").is_none());
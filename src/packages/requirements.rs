@@ -0,0 +1,277 @@
+//! Package lists for `stacy add --from-file`
+//!
+//! Two formats are read:
+//!
+//! - A plain list (`packages.txt`): one package per line, optionally followed
+//!   by its source (`rdrobust github:rdpackages/rdrobust`). `#` starts a
+//!   comment.
+//! - A do-file (`requirements.do`, any `.do` or `.ado`): the `ssc install`,
+//!   `net install` and `github install` lines old projects use to set up
+//!   their packages. Everything else in the file is ignored.
+
+use crate::error::{Error, Result};
+use std::path::Path;
+
+/// A package named in a requirements file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Requirement {
+    pub name: String,
+    /// Source in `stacy add --source` syntax; `None` means the default
+    pub source: Option<String>,
+    /// 1-based line the package appears on
+    pub line: usize,
+}
+
+/// Read the packages listed in `path`, choosing the format by extension
+pub fn read_requirements(path: &Path) -> Result<Vec<Requirement>> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| Error::Config(format!("Failed to read {}: {}", path.display(), e)))?;
+
+    let is_do_file = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("do") || e.eq_ignore_ascii_case("ado"));
+
+    let parsed = if is_do_file {
+        parse_do_file(&content)
+    } else {
+        parse_package_list(&content)
+    };
+    parsed.map_err(|e| match e {
+        Error::Config(msg) => Error::Config(format!("{}:{}", path.display(), msg)),
+        other => other,
+    })
+}
+
+/// Parse a plain package list: `name [source]` per line
+pub fn parse_package_list(content: &str) -> Result<Vec<Requirement>> {
+    let mut requirements = Vec::new();
+
+    for (i, line) in content.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let mut words = line.split_whitespace();
+        let name = words.next().unwrap_or_default();
+        let source = words.next().map(str::to_string);
+        if words.next().is_some() {
+            return Err(Error::Config(format!(
+                "{}: expected `name [source]`, found `{}`",
+                i + 1,
+                line
+            )));
+        }
+
+        requirements.push(Requirement {
+            name: name.to_lowercase(),
+            source,
+            line: i + 1,
+        });
+    }
+
+    Ok(requirements)
+}
+
+/// Parse the package installs out of a do-file
+pub fn parse_do_file(content: &str) -> Result<Vec<Requirement>> {
+    let mut requirements = Vec::new();
+    // `net from URL` sets the location for the `net install`s after it
+    let mut net_from: Option<String> = None;
+
+    for (i, line) in content.lines().enumerate() {
+        let line = strip_comment(line);
+        let words: Vec<&str> = strip_prefixes(&line).split_whitespace().collect();
+        let (command, options) = split_options(&words.join(" "));
+        let args: Vec<&str> = command.split_whitespace().collect();
+
+        match args.as_slice() {
+            ["ssc", "install", name, ..] => requirements.push(Requirement {
+                name: name.to_lowercase(),
+                source: Some("ssc".to_string()),
+                line: i + 1,
+            }),
+            ["net", "from", url, ..] => net_from = Some(unquote(url).to_string()),
+            ["net", "install", name, ..] => {
+                let url = option_value(&options, "from").or_else(|| net_from.clone());
+                let Some(url) = url else {
+                    return Err(Error::Config(format!(
+                        "{}: `net install {}` without from() or a preceding `net from`",
+                        i + 1,
+                        name
+                    )));
+                };
+                requirements.push(Requirement {
+                    name: name.to_lowercase(),
+                    source: Some(format!("net:{}", url)),
+                    line: i + 1,
+                });
+            }
+            ["github", "install", repo, ..] => {
+                let Some((_, name)) = repo.split_once('/') else {
+                    return Err(Error::Config(format!(
+                        "{}: `github install {}` is not of the form user/repo",
+                        i + 1,
+                        repo
+                    )));
+                };
+                let source = match option_value(&options, "version") {
+                    Some(tag) => format!("github:{}@{}", repo, tag),
+                    None => format!("github:{}", repo),
+                };
+                requirements.push(Requirement {
+                    name: name.to_lowercase(),
+                    source: Some(source),
+                    line: i + 1,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    Ok(requirements)
+}
+
+/// Drop `*` comment lines and `//` trailing comments
+fn strip_comment(line: &str) -> String {
+    let line = line.trim();
+    if line.starts_with('*') {
+        return String::new();
+    }
+    match line.find("//") {
+        // `//` inside a URL (`http://`) is not a comment
+        Some(pos) if pos == 0 || line[..pos].ends_with(char::is_whitespace) => {
+            line[..pos].trim().to_string()
+        }
+        _ => line.to_string(),
+    }
+}
+
+/// Drop `capture`, `quietly` and `noisily` prefixes (and their abbreviations)
+fn strip_prefixes(line: &str) -> &str {
+    const PREFIXES: [(&str, usize); 3] = [("capture", 3), ("quietly", 3), ("noisily", 1)];
+
+    let mut rest = line.trim_start();
+    loop {
+        let word = rest.split_whitespace().next().unwrap_or("");
+        let word = word.trim_end_matches(':');
+        let is_prefix = !word.is_empty()
+            && PREFIXES
+                .iter()
+                .any(|(full, min)| word.len() >= *min && full.starts_with(word));
+        if !is_prefix {
+            return rest;
+        }
+        rest = rest[rest.find(word).unwrap_or(0) + word.len()..]
+            .trim_start_matches(':')
+            .trim_start();
+    }
+}
+
+/// Split `command, options` at the first comma
+fn split_options(line: &str) -> (String, String) {
+    match line.split_once(',') {
+        Some((command, options)) => (command.trim().to_string(), options.trim().to_string()),
+        None => (line.trim().to_string(), String::new()),
+    }
+}
+
+/// The value of `name(value)` in a Stata options string
+fn option_value(options: &str, name: &str) -> Option<String> {
+    let lower = options.to_lowercase();
+    let start = lower.find(&format!("{}(", name))? + name.len() + 1;
+    let end = start + options[start..].find(')')?;
+    Some(unquote(options[start..end].trim()).to_string())
+}
+
+fn unquote(s: &str) -> &str {
+    s.trim_matches('"')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_package_list_with_sources_and_comments() {
+        let content = "\
+# Course packages
+estout
+reghdfe ssc
+rdrobust github:rdpackages/rdrobust   # regression discontinuity
+
+grc1leg net:http://www.stata.com/users/vwiggins/
+";
+        let reqs = parse_package_list(content).unwrap();
+        assert_eq!(reqs.len(), 4);
+        assert_eq!(reqs[0].name, "estout");
+        assert_eq!(reqs[0].source, None);
+        assert_eq!(reqs[1].source.as_deref(), Some("ssc"));
+        assert_eq!(
+            reqs[2].source.as_deref(),
+            Some("github:rdpackages/rdrobust")
+        );
+        assert_eq!(reqs[2].line, 4);
+        assert_eq!(
+            reqs[3].source.as_deref(),
+            Some("net:http://www.stata.com/users/vwiggins/")
+        );
+    }
+
+    #[test]
+    fn test_package_list_rejects_extra_words() {
+        let err = parse_package_list("estout ssc extra\n").unwrap_err();
+        assert!(err.to_string().contains("1: expected `name [source]`"));
+    }
+
+    #[test]
+    fn test_do_file_install_lines() {
+        let content = r#"
+* Install required packages
+ssc install estout, replace
+cap ssc install reghdfe
+quietly: ssc install ftools   // needed by reghdfe
+net install grc1leg, from("http://www.stata.com/users/vwiggins/") replace
+net from https://example.org/stata
+net install myado
+github install rdpackages/rdrobust, version(v9.1.0)
+sysuse auto, clear
+"#;
+        let reqs = parse_do_file(content).unwrap();
+        let got: Vec<(&str, &str)> = reqs
+            .iter()
+            .map(|r| (r.name.as_str(), r.source.as_deref().unwrap()))
+            .collect();
+        assert_eq!(
+            got,
+            vec![
+                ("estout", "ssc"),
+                ("reghdfe", "ssc"),
+                ("ftools", "ssc"),
+                ("grc1leg", "net:http://www.stata.com/users/vwiggins/"),
+                ("myado", "net:https://example.org/stata"),
+                ("rdrobust", "github:rdpackages/rdrobust@v9.1.0"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_do_file_net_install_needs_location() {
+        let err = parse_do_file("net install myado\n").unwrap_err();
+        assert!(err.to_string().contains("without from()"));
+    }
+
+    #[test]
+    fn test_read_requirements_picks_format_by_extension() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let do_file = temp.path().join("requirements.do");
+        std::fs::write(&do_file, "ssc install estout\nestout\n").unwrap();
+        let reqs = read_requirements(&do_file).unwrap();
+        assert_eq!(reqs.len(), 1);
+
+        let txt = temp.path().join("packages.txt");
+        std::fs::write(&txt, "estout\nreghdfe\n").unwrap();
+        assert_eq!(read_requirements(&txt).unwrap().len(), 2);
+    }
+}
@@ -6,8 +6,8 @@
 
 use crate::error::{Error, Result};
 use crate::packages::http::StacyHttpClient;
-use crate::packages::naming;
 use crate::packages::pkg_parser::{parse_pkg_file, PackageManifest};
+use crate::packages::{naming, ssc_index};
 use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::path::Path;
@@ -118,6 +118,7 @@ impl SscDownloader {
                 Ok(download)
             }
             Err(primary_err) => {
+                tracing::debug!(package = %name, error = %primary_err, "primary SSC server failed");
                 if is_connection_error(&primary_err) {
                     // Primary connection error → try mirror as fallback
                     eprintln!("Primary SSC server unreachable, trying GitHub mirror...");
@@ -127,7 +128,28 @@ impl SscDownloader {
                             Ok(download)
                         }
                         Err(mirror_err) => {
-                            if is_not_found_error(&mirror_err) {
+                            // Only the cached index can tell a mirror gap
+                            // from a typo while SSC is down
+                            let index = ssc_index::load();
+                            let similar = index
+                                .as_ref()
+                                .map(|index| index.similar(&name))
+                                .unwrap_or_default();
+                            if is_not_found_error(&mirror_err)
+                                && index.as_ref().is_some_and(|index| index.contains(&name))
+                            {
+                                Err(Error::Network(format!(
+                                    "SSC server is unreachable and the GitHub mirror does not have '{}' yet, \
+                                     though SSC lists it. Try again once SSC is reachable.",
+                                    name
+                                )))
+                            } else if is_not_found_error(&mirror_err) && !similar.is_empty() {
+                                Err(Error::Network(format!(
+                                    "SSC server is unreachable and the GitHub mirror does not have '{}'. {}",
+                                    name,
+                                    did_you_mean(&similar)
+                                )))
+                            } else if is_not_found_error(&mirror_err) {
                                 Err(Error::Network(format!(
                                     "SSC server is unreachable and the GitHub mirror does not have '{}'. \
                                      This may be a recently published package not yet mirrored, \
@@ -160,11 +182,17 @@ impl SscDownloader {
                         }
                         Err(_) => {
                             let mut msg = format!("Package '{}' not found on SSC.", name);
+                            let similar = ssc_index::ensure_fresh(|| {})
+                                .map(|index| index.similar(&name))
+                                .unwrap_or_default();
                             if let Some((provider, _)) = naming::find_provider(&name) {
                                 msg.push_str(&format!(
                                     " '{}' is provided by '{}'. Run: stacy add {}",
                                     name, provider, provider
                                 ));
+                            } else if !similar.is_empty() {
+                                msg.push(' ');
+                                msg.push_str(&did_you_mean(&similar));
                             } else {
                                 msg.push_str(
                                     " Check spelling or verify the package exists at \
@@ -189,6 +217,9 @@ impl SscDownloader {
 
         // Parse manifest
         let manifest = parse_pkg_file(&pkg_content, name)?;
+        if let Some(version) = &manifest.distribution_date {
+            ssc_index::record_version(name, version);
+        }
 
         // Download all files
         let mut files = Vec::new();
@@ -244,7 +275,25 @@ impl SscDownloader {
         let pkg_url = format!("{}{}.pkg", base_url, name);
 
         let pkg_content = self.download_text(&pkg_url)?;
-        parse_pkg_file(&pkg_content, &name)
+        let manifest = parse_pkg_file(&pkg_content, &name)?;
+        if let Some(version) = &manifest.distribution_date {
+            ssc_index::record_version(&name, version);
+        }
+        Ok(manifest)
+    }
+
+    /// Download the `stata.toc` listing the SSC packages whose names start
+    /// with `letter` (`_` for the rest), from the mirror if the primary
+    /// server is unreachable
+    pub fn download_toc(&self, letter: char) -> Result<String> {
+        let letter = letter.to_string();
+        let primary = format!("{}stata.toc", Self::get_package_url(&letter));
+        match self.download_text(&primary) {
+            Err(e) if is_connection_error(&e) => {
+                self.download_text(&format!("{}stata.toc", Self::get_mirror_url(&letter)))
+            }
+            result => result,
+        }
     }
 
     fn download_text(&self, url: &str) -> Result<String> {
@@ -290,6 +339,16 @@ fn is_connection_error(err: &Error) -> bool {
     }
 }
 
+/// `Did you mean `estout`?` for the SSC packages a misspelled name is close to
+fn did_you_mean(similar: &[String]) -> String {
+    let quoted: Vec<String> = similar.iter().map(|name| format!("`{}`", name)).collect();
+    let names = match quoted.split_last() {
+        Some((last, rest)) if !rest.is_empty() => format!("{} or {}", rest.join(", "), last),
+        _ => quoted.join(""),
+    };
+    format!("Did you mean {}? Run: stacy add {}", names, similar[0])
+}
+
 /// Check if an error indicates a 404 / package not found
 fn is_not_found_error(err: &Error) -> bool {
     match err {
@@ -483,6 +542,22 @@ mod tests {
     }
 
     // Tests for is_not_found_error
+    #[test]
+    fn test_did_you_mean() {
+        assert_eq!(
+            did_you_mean(&["estout".to_string()]),
+            "Did you mean `estout`? Run: stacy add estout"
+        );
+        assert_eq!(
+            did_you_mean(&[
+                "estout".to_string(),
+                "estadd".to_string(),
+                "esttab".to_string()
+            ]),
+            "Did you mean `estout`, `estadd` or `esttab`? Run: stacy add estout"
+        );
+    }
+
     #[test]
     fn test_is_not_found_error() {
         assert!(is_not_found_error(&Error::Config(
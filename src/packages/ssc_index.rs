@@ -0,0 +1,316 @@
+//! Local index of the SSC archive
+//!
+//! SSC lists its packages in one `stata.toc` per first letter. stacy keeps
+//! those listings, each package's name and description, in
+//! `~/.cache/stacy/ssc-index.json`, so `stacy search`, the "did you mean" for
+//! a misspelled package and the hint for an unrecognized command don't go to
+//! the network each time.
+//!
+//! `stacy search`, and `stacy add` of a package SSC doesn't have, first
+//! download an index that is missing or older than [`MAX_AGE_SECS`];
+//! `stacy cache packages refresh-index` downloads it at once. Everything else
+//! reads the index as it is. With `STACY_OFFLINE=1` it is never downloaded.
+//!
+//! The listings carry no versions. The latest version stacy sees for an SSC
+//! package when it downloads one or checks it with `stacy outdated` is
+//! recorded in the index, and `stacy outdated` compares with those under
+//! `STACY_OFFLINE=1`.
+
+use crate::error::{Error, Result};
+use crate::history::unix_now;
+use crate::packages::ssc::SscDownloader;
+use crate::task::levenshtein_distance;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// An index older than a week is downloaded again before it is searched
+pub const MAX_AGE_SECS: u64 = 7 * 24 * 60 * 60;
+
+/// The SSC directories, one per first letter of a package name
+const LETTERS: &str = "_abcdefghijklmnopqrstuvwxyz";
+
+/// Names suggested for a misspelled package, at most
+const MAX_SIMILAR: usize = 3;
+
+/// One SSC package in the index
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct IndexEntry {
+    /// One-line description from the listing
+    #[serde(default)]
+    pub description: String,
+    /// Latest version (Distribution-Date) stacy has seen SSC serve
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// When that version was seen (Unix seconds)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version_seen_at_unix: Option<u64>,
+}
+
+/// The SSC package listing, serialized to `~/.cache/stacy/ssc-index.json`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SscIndex {
+    /// When the listing was last downloaded (Unix seconds); 0 if it never
+    /// was and the index only holds versions seen since
+    pub fetched_at_unix: u64,
+    /// Packages by lowercase name
+    pub packages: BTreeMap<String, IndexEntry>,
+}
+
+impl SscIndex {
+    /// Whether the listing was downloaded less than [`MAX_AGE_SECS`] ago
+    pub fn is_fresh(&self) -> bool {
+        self.fetched_at_unix > 0 && unix_now().saturating_sub(self.fetched_at_unix) < MAX_AGE_SECS
+    }
+
+    /// Packages whose name or description holds every word of `query`, best
+    /// matches first: the exact name, then names starting with the query,
+    /// then names holding it, then descriptions
+    pub fn search(&self, query: &str) -> Vec<(&str, &IndexEntry)> {
+        let query = query.trim().to_lowercase();
+        let words: Vec<&str> = query.split_whitespace().collect();
+        if words.is_empty() {
+            return Vec::new();
+        }
+        let mut matches: Vec<(u8, &str, &IndexEntry)> = self
+            .packages
+            .iter()
+            .filter_map(|(name, entry)| {
+                let description = entry.description.to_lowercase();
+                let all_words = words
+                    .iter()
+                    .all(|word| name.contains(word) || description.contains(word));
+                let rank = if *name == query {
+                    0
+                } else if name.starts_with(&query) {
+                    1
+                } else if name.contains(&query) {
+                    2
+                } else if all_words {
+                    3
+                } else {
+                    return None;
+                };
+                Some((rank, name.as_str(), entry))
+            })
+            .collect();
+        matches.sort_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+        matches
+            .into_iter()
+            .map(|(_, name, entry)| (name, entry))
+            .collect()
+    }
+
+    /// Listed packages close enough to `name` to be what a typo meant,
+    /// closest first
+    pub fn similar(&self, name: &str) -> Vec<String> {
+        let name = name.to_lowercase();
+        let max_distance = match name.chars().count() {
+            0..=3 => 1,
+            len => (len / 4).max(2),
+        };
+        let mut similar: Vec<(usize, &String)> = self
+            .packages
+            .iter()
+            .filter(|(candidate, entry)| *candidate != &name && !entry.description.is_empty())
+            .map(|(candidate, _)| (levenshtein_distance(&name, candidate), candidate))
+            .filter(|(distance, _)| *distance <= max_distance)
+            .collect();
+        similar.sort();
+        similar
+            .into_iter()
+            .take(MAX_SIMILAR)
+            .map(|(_, candidate)| candidate.clone())
+            .collect()
+    }
+
+    /// Whether `name` is a package SSC lists
+    pub fn contains(&self, name: &str) -> bool {
+        self.packages
+            .get(&name.to_lowercase())
+            .is_some_and(|entry| !entry.description.is_empty())
+    }
+}
+
+/// The `p name description` lines of a `stata.toc`
+pub fn parse_toc(content: &str) -> Vec<(String, String)> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let rest = line.strip_prefix("p ")?.trim_start();
+            let (name, description) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            (!name.is_empty()).then(|| (name.to_lowercase(), description.trim().to_string()))
+        })
+        .collect()
+}
+
+/// Where the index is kept, next to the package cache
+pub fn index_path() -> Result<PathBuf> {
+    Ok(crate::packages::global_cache::cache_dir()?.with_file_name("ssc-index.json"))
+}
+
+/// The index as it is on disk, if there is one
+pub fn load() -> Option<SscIndex> {
+    let content = std::fs::read_to_string(index_path().ok()?).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+fn save(index: &SscIndex) -> Result<()> {
+    let json = serde_json::to_string_pretty(index)
+        .map_err(|e| Error::Config(format!("Failed to serialize the SSC index: {}", e)))?;
+    crate::utils::paths::write_atomically(&index_path()?, json.as_bytes())?;
+    Ok(())
+}
+
+/// Download every listing and save the index. Versions seen before are kept
+/// for the packages still listed.
+pub fn refresh() -> Result<SscIndex> {
+    let downloader = SscDownloader::new();
+    let previous = load().unwrap_or_default();
+    let mut index = SscIndex {
+        fetched_at_unix: unix_now(),
+        packages: BTreeMap::new(),
+    };
+    for letter in LETTERS.chars() {
+        let toc = downloader.download_toc(letter)?;
+        for (name, description) in parse_toc(&toc) {
+            let entry = IndexEntry {
+                description,
+                ..previous.packages.get(&name).cloned().unwrap_or_default()
+            };
+            index.packages.insert(name, entry);
+        }
+    }
+    if index.packages.is_empty() {
+        return Err(Error::Network(
+            "The SSC listings hold no packages; the index was left as it was".to_string(),
+        ));
+    }
+    save(&index)?;
+    Ok(index)
+}
+
+/// The index, refreshed first when it is missing or older than
+/// [`MAX_AGE_SECS`]. A stale index is still returned when the refresh fails
+/// or `STACY_OFFLINE` forbids it. `on_refresh` is called before a download.
+pub fn ensure_fresh(on_refresh: impl FnOnce()) -> Result<SscIndex> {
+    let current = load();
+    if current.as_ref().is_some_and(SscIndex::is_fresh) || crate::update_check::is_offline() {
+        return current.ok_or_else(|| {
+            Error::Network(
+                "No SSC index is cached and STACY_OFFLINE is set; \
+                 run `stacy cache packages refresh-index` while online"
+                    .to_string(),
+            )
+        });
+    }
+    on_refresh();
+    match refresh() {
+        Ok(index) => Ok(index),
+        Err(e) => match current {
+            Some(index) if !index.packages.is_empty() => {
+                tracing::debug!(error = %e, "could not refresh the SSC index");
+                Ok(index)
+            }
+            _ => Err(e),
+        },
+    }
+}
+
+/// Record `version` as the latest SSC serves of `name`. Best effort: the
+/// index is a cache, so a failure to write it is ignored.
+pub fn record_version(name: &str, version: &str) {
+    // Packages are checked on several threads: one read-modify-write at a time
+    static RECORDING: Mutex<()> = Mutex::new(());
+    let _recording = RECORDING.lock().unwrap_or_else(|e| e.into_inner());
+    let mut index = load().unwrap_or_default();
+    let entry = index.packages.entry(name.to_lowercase()).or_default();
+    if entry.version.as_deref() == Some(version) {
+        return;
+    }
+    entry.version = Some(version.to_string());
+    entry.version_seen_at_unix = Some(unix_now());
+    let _ = save(&index);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index(packages: &[(&str, &str)]) -> SscIndex {
+        SscIndex {
+            fetched_at_unix: unix_now(),
+            packages: packages
+                .iter()
+                .map(|(name, description)| {
+                    (
+                        name.to_string(),
+                        IndexEntry {
+                            description: description.to_string(),
+                            ..Default::default()
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_parse_toc() {
+        let toc = "v 3\n\
+                   d Materials by various authors\n\
+                   p estout module to make regression tables\n\
+                   p   EstAdd  module to add results to stored estimates\n\
+                   p ftools\n";
+        assert_eq!(
+            parse_toc(toc),
+            vec![
+                (
+                    "estout".to_string(),
+                    "module to make regression tables".to_string()
+                ),
+                (
+                    "estadd".to_string(),
+                    "module to add results to stored estimates".to_string()
+                ),
+                ("ftools".to_string(), String::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_search_ranks_names_before_descriptions() {
+        let index = index(&[
+            ("esttab2", "module for tables"),
+            ("estout", "module to make regression tables"),
+            ("outreg2", "module to arrange regression outputs"),
+            ("reghdfe", "module for linear regression with fixed effects"),
+            ("regtab", "module to tabulate regressions"),
+        ]);
+        let names = |query| {
+            index
+                .search(query)
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names("reg"), ["reghdfe", "regtab", "outreg2", "estout"]);
+        assert_eq!(names("regression tables"), ["estout"]);
+        assert!(names("  ").is_empty());
+    }
+
+    #[test]
+    fn test_similar_names() {
+        let index = index(&[
+            ("estout", "module to make regression tables"),
+            ("estadd", "module to add results"),
+            ("reghdfe", "module for fixed effects"),
+        ]);
+        assert_eq!(index.similar("estuot"), ["estout"]);
+        assert_eq!(index.similar("reghdef"), ["reghdfe"]);
+        assert!(index.similar("xyz").is_empty());
+        assert!(index.contains("EstOut"));
+        assert!(!index.contains("estuot"));
+    }
+}
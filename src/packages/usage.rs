@@ -0,0 +1,401 @@
+//! Which packages the project's scripts use (`stacy remove --unused`)
+//!
+//! A package is used when a project script names one of the commands it
+//! ships (the stems of its `.ado` files) or the package itself, say in a
+//! `require`. Names are matched as words anywhere outside comments, so a
+//! command after a prefix (`quietly`, `bysort id:`) or inside a macro still
+//! counts: the analysis errs towards keeping a package. A package that a used
+//! package needs (`require`, `which` or `findfile` in its `.ado` files) is
+//! used too.
+//!
+//! Scripts are the `.do` and `.ado` files under the project root, outside
+//! hidden directories, the directories installed packages live in and the
+//! sources of `local:` packages. A package whose commands stacy can't see,
+//! because it isn't installed or ships no `.ado` files (a Mata library), is
+//! never reported unused.
+
+use crate::deps::lint::strip_comments;
+use crate::error::Result;
+use crate::packages::{dep_scan, global_cache};
+use crate::project::{profile, Lockfile, PackageSource, Project};
+use regex::Regex;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+/// A word that can name a command or package
+static WORD: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"[A-Za-z_][A-Za-z0-9_]*").unwrap());
+
+/// Commands listed when reporting a package unused
+const LISTED_COMMANDS: usize = 5;
+
+/// What the files of an installed package show about it
+#[derive(Debug, Clone, Default)]
+pub struct Provided {
+    /// The commands it ships: the stems of its `.ado` files, lowercase
+    pub commands: BTreeSet<String>,
+    /// Names its `.ado` files `require`, `which` or `findfile`
+    pub needs: Vec<String>,
+}
+
+/// How a package is used
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Usage {
+    /// A script names `command`, first at `script:line`
+    Used {
+        command: String,
+        script: PathBuf,
+        line: usize,
+    },
+    /// A used package needs it
+    NeededBy(String),
+    /// stacy can't see its commands, so it is kept
+    Unknown(String),
+    /// No script names any of `commands`
+    Unused { commands: Vec<String> },
+}
+
+/// A package and how the project uses it
+#[derive(Debug, Clone)]
+pub struct PackageUsage {
+    pub name: String,
+    /// Dependency group: "production", "dev", or "test"
+    pub group: String,
+    pub usage: Usage,
+}
+
+impl PackageUsage {
+    /// `used`, `needed`, `unknown` or `unused`
+    pub fn status(&self) -> &'static str {
+        match self.usage {
+            Usage::Used { .. } => "used",
+            Usage::NeededBy(_) => "needed",
+            Usage::Unknown(_) => "unknown",
+            Usage::Unused { .. } => "unused",
+        }
+    }
+
+    /// The evidence, one line: where it is used, or that it is not
+    pub fn evidence(&self, scripts: usize) -> String {
+        match &self.usage {
+            Usage::Used {
+                command,
+                script,
+                line,
+            } => format!("{} at {}:{}", command, script.display(), line),
+            Usage::NeededBy(package) => format!("needed by {}", package),
+            Usage::Unknown(reason) => reason.clone(),
+            Usage::Unused { commands } => {
+                let mut listed = commands
+                    .iter()
+                    .take(LISTED_COMMANDS)
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                if commands.len() > LISTED_COMMANDS {
+                    listed.push_str(&format!(" and {} more", commands.len() - LISTED_COMMANDS));
+                }
+                format!("none of {} appears in the {} script(s)", listed, scripts)
+            }
+        }
+    }
+}
+
+/// The packages of a project and how its scripts use them
+#[derive(Debug, Clone)]
+pub struct UsageReport {
+    /// Number of scripts scanned
+    pub scripts: usize,
+    /// Every package stacy.toml declares or the lockfile locks, by name
+    pub packages: Vec<PackageUsage>,
+}
+
+impl UsageReport {
+    /// The packages no script uses
+    pub fn unused(&self) -> impl Iterator<Item = &PackageUsage> {
+        self.packages
+            .iter()
+            .filter(|p| matches!(p.usage, Usage::Unused { .. }))
+    }
+}
+
+/// How the scripts of `project` use the packages its stacy.toml declares
+/// (those of the profile in use) and `lockfile` locks
+pub fn analyze(project: &Project, lockfile: Option<&Lockfile>) -> Result<UsageReport> {
+    let root = &project.root;
+    let mut groups: BTreeMap<String, String> = BTreeMap::new();
+    let mut skip = vec![root.join("ado")];
+    if let Some(config) = &project.config {
+        let packages = profile::active_packages(config)?;
+        for (name, spec, group) in packages.all_packages() {
+            groups.insert(name.to_lowercase(), group.as_str().to_string());
+            let source = spec.source();
+            if source.to_lowercase().starts_with("local:") {
+                skip.push(root.join(&source["local:".len()..]));
+            }
+        }
+        skip.extend(config.paths.ado.iter().map(|dir| root.join(dir)));
+        skip.extend(packages.local_install_dir().map(|dir| root.join(dir)));
+    }
+
+    let mut provided = BTreeMap::new();
+    for (name, entry) in lockfile.iter().flat_map(|l| &l.packages) {
+        groups
+            .entry(name.clone())
+            .or_insert_with(|| entry.group.clone());
+        let dir = match &entry.source {
+            PackageSource::Local {
+                path,
+                editable: true,
+            } => Some(root.join(path)),
+            _ => global_cache::locate_package(name, &entry.version)
+                .ok()
+                .flatten(),
+        };
+        if let Some(dir) = dir.filter(|dir| dir.is_dir()) {
+            provided.insert(name.clone(), provided_by(&dir));
+        }
+    }
+
+    let scripts = project_scripts(root, &skip);
+    let mut words = BTreeMap::new();
+    for script in &scripts {
+        if let Ok(bytes) = std::fs::read(root.join(script)) {
+            let content = crate::utils::encoding::decode_text(&bytes);
+            collect_words(script, &content, &mut words);
+        }
+    }
+
+    let packages = groups
+        .into_iter()
+        .map(|(name, group)| {
+            let files = provided.remove(&name);
+            (name, group, files)
+        })
+        .collect();
+    Ok(UsageReport {
+        scripts: scripts.len(),
+        packages: classify(packages, &words),
+    })
+}
+
+/// The commands and needs of the package installed in `dir`
+fn provided_by(dir: &Path) -> Provided {
+    let commands = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_str()?.to_lowercase();
+            name.strip_suffix(".ado").map(str::to_string)
+        })
+        .collect();
+    Provided {
+        commands,
+        needs: dep_scan::scan_package_deps(dir),
+    }
+}
+
+/// The `.do` and `.ado` files under `root`, relative to it, outside hidden
+/// directories and `skip`
+fn project_scripts(root: &Path, skip: &[PathBuf]) -> Vec<PathBuf> {
+    let mut scripts: Vec<PathBuf> = walkdir::WalkDir::new(root)
+        .min_depth(1)
+        .into_iter()
+        .filter_entry(|e| {
+            !(e.file_type().is_dir()
+                && (e.file_name().to_string_lossy().starts_with('.')
+                    || skip.iter().any(|dir| dir == e.path())))
+        })
+        .flatten()
+        .filter(|e| {
+            e.file_type().is_file()
+                && e.path()
+                    .extension()
+                    .is_some_and(|ext| ext == "do" || ext == "ado")
+        })
+        .filter_map(|e| e.path().strip_prefix(root).ok().map(Path::to_path_buf))
+        .collect();
+    scripts.sort();
+    scripts
+}
+
+/// Record where each word of `content` outside comments first appears,
+/// lowercase, unless an earlier script already has it
+fn collect_words(script: &Path, content: &str, words: &mut BTreeMap<String, (PathBuf, usize)>) {
+    let mut in_block = false;
+    for (index, line) in content.lines().enumerate() {
+        if !in_block && line.trim_start().starts_with('*') {
+            continue;
+        }
+        let code = strip_comments(line, &mut in_block);
+        for word in WORD.find_iter(&code) {
+            words
+                .entry(word.as_str().to_lowercase())
+                .or_insert_with(|| (script.to_path_buf(), index + 1));
+        }
+    }
+}
+
+/// Decide how each package (name, group, files when installed) is used,
+/// given where the scripts' words first appear
+fn classify(
+    packages: Vec<(String, String, Option<Provided>)>,
+    words: &BTreeMap<String, (PathBuf, usize)>,
+) -> Vec<PackageUsage> {
+    let mut result: Vec<PackageUsage> = packages
+        .iter()
+        .map(|(name, group, provided)| {
+            let commands = provided.as_ref().map(|p| &p.commands);
+            let named = std::iter::once(name)
+                .chain(commands.into_iter().flatten())
+                .filter_map(|word| words.get(word).map(|at| (word, at)))
+                .min_by(|a, b| a.1.cmp(b.1));
+            let usage = match (named, commands) {
+                (Some((command, (script, line))), _) => Usage::Used {
+                    command: command.clone(),
+                    script: script.clone(),
+                    line: *line,
+                },
+                (None, None) => {
+                    Usage::Unknown("not installed, so its commands are unknown".to_string())
+                }
+                (None, Some(commands)) if commands.is_empty() => Usage::Unknown(
+                    "ships no commands (.ado files), such as a Mata library".to_string(),
+                ),
+                (None, Some(commands)) => Usage::Unused {
+                    commands: commands.iter().cloned().collect(),
+                },
+            };
+            PackageUsage {
+                name: name.clone(),
+                group: group.clone(),
+                usage,
+            }
+        })
+        .collect();
+
+    // What used packages need is used too, and so on down
+    loop {
+        let mut changed = false;
+        for i in 0..result.len() {
+            if matches!(result[i].usage, Usage::Used { .. } | Usage::NeededBy(_)) {
+                continue;
+            }
+            let (name, _, provided) = &packages[i];
+            let commands = provided.iter().flat_map(|p| &p.commands);
+            let names: BTreeSet<&String> = std::iter::once(name).chain(commands).collect();
+            let needed_by = result
+                .iter()
+                .zip(&packages)
+                .find_map(|(user, (_, _, files))| {
+                    let used = matches!(user.usage, Usage::Used { .. } | Usage::NeededBy(_));
+                    let needs = files.iter().flat_map(|p| &p.needs);
+                    (used && needs.into_iter().any(|need| names.contains(need)))
+                        .then(|| user.name.clone())
+                });
+            if let Some(user) = needed_by {
+                result[i].usage = Usage::NeededBy(user);
+                changed = true;
+            }
+        }
+        if !changed {
+            return result;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn provided(commands: &[&str], needs: &[&str]) -> Option<Provided> {
+        Some(Provided {
+            commands: commands.iter().map(|c| c.to_string()).collect(),
+            needs: needs.iter().map(|n| n.to_string()).collect(),
+        })
+    }
+
+    #[test]
+    fn test_classify_packages() {
+        let mut words = BTreeMap::new();
+        collect_words(
+            Path::new("analysis.do"),
+            "* esttab is only mentioned in a comment\n\
+             use data, clear\n\
+             quietly reghdfe y x, absorb(id) // not eststo\n\
+             /* estpost\n   estadd */\n",
+            &mut words,
+        );
+        let packages = vec![
+            (
+                "estout".into(),
+                "production".into(),
+                provided(&["esttab", "eststo", "estpost", "estadd"], &[]),
+            ),
+            (
+                "ftools".into(),
+                "production".into(),
+                provided(&["fcollapse", "ftools"], &[]),
+            ),
+            ("moremata".into(), "production".into(), provided(&[], &[])),
+            (
+                "reghdfe".into(),
+                "production".into(),
+                provided(&["reghdfe"], &["ftools"]),
+            ),
+            ("texdoc".into(), "dev".into(), None),
+        ];
+        let usage: Vec<(String, Usage)> = classify(packages, &words)
+            .into_iter()
+            .map(|p| (p.name, p.usage))
+            .collect();
+        assert_eq!(
+            usage,
+            [
+                (
+                    "estout".to_string(),
+                    Usage::Unused {
+                        commands: ["estadd", "estpost", "eststo", "esttab"]
+                            .map(String::from)
+                            .into()
+                    }
+                ),
+                ("ftools".to_string(), Usage::NeededBy("reghdfe".to_string())),
+                (
+                    "moremata".to_string(),
+                    Usage::Unknown(
+                        "ships no commands (.ado files), such as a Mata library".to_string()
+                    )
+                ),
+                (
+                    "reghdfe".to_string(),
+                    Usage::Used {
+                        command: "reghdfe".to_string(),
+                        script: PathBuf::from("analysis.do"),
+                        line: 3,
+                    }
+                ),
+                (
+                    "texdoc".to_string(),
+                    Usage::Unknown("not installed, so its commands are unknown".to_string())
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unused_evidence_lists_commands() {
+        let package = PackageUsage {
+            name: "estout".to_string(),
+            group: "production".to_string(),
+            usage: Usage::Unused {
+                commands: ["a", "b", "c", "d", "e", "f", "g"].map(String::from).into(),
+            },
+        };
+        assert_eq!(
+            package.evidence(12),
+            "none of a, b, c, d, e and 2 more appears in the 12 script(s)"
+        );
+    }
+}
@@ -0,0 +1,356 @@
+//! Pipeline execution engine
+//!
+//! Runs the selected stages in order. A stage runs when its build-cache entry
+//! is stale, one of its outputs is missing, or a stage upstream of it ran;
+//! otherwise it is fresh and skipped. A failed stage stops everything
+//! downstream of it.
+
+use crate::artifacts::{Artifacts, ProducerKind};
+use crate::cache::detect::CacheStatus;
+use crate::cache::step::{StepCache, StepKey};
+use crate::error::{Error, Result};
+use crate::executor::log_policy::LogPolicy;
+use crate::executor::StataExecutor;
+use crate::pipeline::Pipeline;
+use crate::project::config::StageDef;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// What happened to a stage
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StageStatus {
+    /// Up to date; not run
+    Fresh,
+    /// Out of date; not run because of `--dry-run`
+    Stale,
+    /// Ran and succeeded
+    Ran,
+    /// Ran and failed, or didn't write a declared output
+    Failed,
+    /// Not run because a stage upstream of it failed
+    Skipped,
+}
+
+impl StageStatus {
+    /// Label used in human output
+    pub fn label(&self) -> &'static str {
+        match self {
+            StageStatus::Fresh => "fresh",
+            StageStatus::Stale => "stale",
+            StageStatus::Ran => "ran",
+            StageStatus::Failed => "FAIL",
+            StageStatus::Skipped => "skip",
+        }
+    }
+}
+
+/// Result of one stage
+#[derive(Debug, Clone)]
+pub struct StageResult {
+    /// Stage name
+    pub name: String,
+    /// Path to the stage's script
+    pub script: PathBuf,
+    /// What happened to the stage
+    pub status: StageStatus,
+    /// Why the stage ran, failed or was skipped
+    pub reason: Option<String>,
+    /// Exit code from execution (0 unless the stage failed)
+    pub exit_code: i32,
+    /// How long the stage took (zero unless it ran)
+    pub duration: Duration,
+    /// Path to the retained log file, if any
+    pub log_file: PathBuf,
+}
+
+/// Result of a build
+#[derive(Debug)]
+pub struct BuildResult {
+    /// Whether every stage that ran succeeded
+    pub success: bool,
+    /// Exit code (0 if all succeeded, first failure code otherwise)
+    pub exit_code: i32,
+    /// Total duration of the build
+    pub duration: Duration,
+    /// Results in execution order
+    pub stages: Vec<StageResult>,
+}
+
+impl BuildResult {
+    /// Number of stages with `status`
+    pub fn count(&self, status: StageStatus) -> usize {
+        self.stages.iter().filter(|s| s.status == status).count()
+    }
+}
+
+/// Pipeline build context
+pub struct PipelineExecutor<'a> {
+    pipeline: &'a Pipeline,
+    stata: &'a StataExecutor,
+    project_root: &'a Path,
+    cache: &'a StepCache,
+    log_policy: LogPolicy,
+    dry_run: bool,
+    artifacts: Option<&'a Artifacts>,
+}
+
+impl<'a> PipelineExecutor<'a> {
+    /// Create a new pipeline executor. The caller saves the cache once the
+    /// build is done.
+    pub fn new(
+        pipeline: &'a Pipeline,
+        stata: &'a StataExecutor,
+        project_root: &'a Path,
+        cache: &'a StepCache,
+    ) -> Self {
+        Self {
+            pipeline,
+            stata,
+            project_root,
+            cache,
+            log_policy: LogPolicy::new(),
+            dry_run: false,
+            artifacts: None,
+        }
+    }
+
+    /// Set the log-retention policy applied after each stage
+    pub fn with_log_policy(mut self, policy: LogPolicy) -> Self {
+        self.log_policy = policy;
+        self
+    }
+
+    /// Report which stages are stale without running them
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Register the outputs of each stage that succeeds. The caller saves
+    /// the registry once the build is done.
+    pub fn with_artifacts(mut self, artifacts: &'a Artifacts) -> Self {
+        self.artifacts = Some(artifacts);
+        self
+    }
+
+    /// Build `targets` and everything upstream of them (all stages when empty)
+    pub fn build(&self, targets: &[String]) -> Result<BuildResult> {
+        let start = Instant::now();
+        let mut result = BuildResult {
+            success: true,
+            exit_code: 0,
+            duration: Duration::ZERO,
+            stages: Vec::new(),
+        };
+        // Stages that ran (or would run): everything downstream is stale too
+        let mut rebuilt: HashSet<&str> = HashSet::new();
+        // Stages that failed or were skipped: everything downstream is skipped
+        let mut blocked: HashSet<&str> = HashSet::new();
+
+        for name in self.pipeline.select(targets)? {
+            let stage = self
+                .pipeline
+                .stage(name)
+                .ok_or_else(|| Error::Config(format!("Unknown stage '{}'", name)))?;
+            let script = self.project_root.join(&stage.script);
+            let upstream = self.pipeline.upstream(name);
+
+            if let Some(failed) = upstream.iter().find(|u| blocked.contains(u.as_str())) {
+                blocked.insert(name);
+                result.stages.push(StageResult {
+                    name: name.to_string(),
+                    script,
+                    status: StageStatus::Skipped,
+                    reason: Some(format!("upstream stage '{}' did not build", failed)),
+                    exit_code: 0,
+                    duration: Duration::ZERO,
+                    log_file: PathBuf::new(),
+                });
+                continue;
+            }
+
+            if !script.exists() {
+                return Err(Error::Config(format!(
+                    "Stage '{}': Script not found: {}",
+                    name,
+                    script.display()
+                )));
+            }
+
+            let key = step_key(name, stage);
+            let reason = match self.cache.check(&script, None, &key)? {
+                CacheStatus::Miss(reason) => Some(reason.to_string()),
+                CacheStatus::Hit(_) => self.other_stale_reason(name, upstream, &rebuilt),
+            };
+            let Some(reason) = reason else {
+                result.stages.push(StageResult {
+                    name: name.to_string(),
+                    script,
+                    status: StageStatus::Fresh,
+                    reason: None,
+                    exit_code: 0,
+                    duration: Duration::ZERO,
+                    log_file: PathBuf::new(),
+                });
+                continue;
+            };
+
+            rebuilt.insert(name);
+            if self.dry_run {
+                result.stages.push(StageResult {
+                    name: name.to_string(),
+                    script,
+                    status: StageStatus::Stale,
+                    reason: Some(reason),
+                    exit_code: 0,
+                    duration: Duration::ZERO,
+                    log_file: PathBuf::new(),
+                });
+                continue;
+            }
+
+            let stage_result = self.run_stage(name, stage, script, &key, reason)?;
+            if stage_result.status == StageStatus::Failed {
+                blocked.insert(name);
+                if result.success {
+                    result.success = false;
+                    result.exit_code = stage_result.exit_code;
+                }
+            }
+            result.stages.push(stage_result);
+        }
+
+        result.duration = start.elapsed();
+        Ok(result)
+    }
+
+    /// Why a stage with a valid cache entry must still run, if it must
+    fn other_stale_reason(
+        &self,
+        name: &str,
+        upstream: &[String],
+        rebuilt: &HashSet<&str>,
+    ) -> Option<String> {
+        if let Some(missing) = self
+            .pipeline
+            .missing_outputs(self.project_root, name)
+            .first()
+        {
+            return Some(format!("output missing: {}", missing.display()));
+        }
+        upstream
+            .iter()
+            .find(|u| rebuilt.contains(u.as_str()))
+            .map(|u| format!("upstream stage '{}' rebuilt", u))
+    }
+
+    /// Run a stale stage and check that it wrote its outputs
+    fn run_stage(
+        &self,
+        name: &str,
+        stage: &StageDef,
+        script: PathBuf,
+        key: &StepKey,
+        reason: String,
+    ) -> Result<StageResult> {
+        let start = Instant::now();
+        let run = self.stata.run(&script, Some(self.project_root))?;
+
+        let missing = if run.success {
+            self.pipeline.missing_outputs(self.project_root, name)
+        } else {
+            Vec::new()
+        };
+        let (status, exit_code, reason) = if !run.success {
+            (StageStatus::Failed, run.exit_code, Some(reason))
+        } else if let Some(output) = missing.first() {
+            let reason = format!("script succeeded but did not write {}", output.display());
+            (StageStatus::Failed, 1, Some(reason))
+        } else {
+            self.cache.record(&script, None, key, &run)?;
+            if let Some(artifacts) = self.artifacts {
+                artifacts.record(ProducerKind::Stage, name, &stage.outputs)?;
+            }
+            (StageStatus::Ran, 0, Some(reason))
+        };
+
+        let log_file = self
+            .log_policy
+            .finalize(&run.log_file, status == StageStatus::Ran)
+            .unwrap_or_default();
+
+        Ok(StageResult {
+            name: name.to_string(),
+            script,
+            status,
+            reason,
+            exit_code,
+            duration: start.elapsed(),
+            log_file,
+        })
+    }
+}
+
+/// Cache key for a stage: its name, script and declared files. Inputs are
+/// hashed by content.
+fn step_key(name: &str, stage: &StageDef) -> StepKey {
+    let definition = format!(
+        "stage:{}\nscript:{}\ninputs:{:?}\noutputs:{:?}",
+        name,
+        stage.script.display(),
+        stage.inputs,
+        stage.outputs
+    );
+    StepKey::new(&definition).with_inputs(stage.inputs.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stage_result(name: &str, status: StageStatus) -> StageResult {
+        StageResult {
+            name: name.to_string(),
+            script: PathBuf::from(format!("{}.do", name)),
+            status,
+            reason: None,
+            exit_code: 0,
+            duration: Duration::ZERO,
+            log_file: PathBuf::new(),
+        }
+    }
+
+    #[test]
+    fn test_build_result_counts() {
+        let result = BuildResult {
+            success: false,
+            exit_code: 198,
+            duration: Duration::ZERO,
+            stages: vec![
+                stage_result("clean", StageStatus::Fresh),
+                stage_result("analyze", StageStatus::Failed),
+                stage_result("report", StageStatus::Skipped),
+            ],
+        };
+        assert_eq!(result.count(StageStatus::Fresh), 1);
+        assert_eq!(result.count(StageStatus::Failed), 1);
+        assert_eq!(result.count(StageStatus::Ran), 0);
+    }
+
+    #[test]
+    fn test_step_key_changes_with_outputs() {
+        let mut stage = StageDef {
+            script: PathBuf::from("clean.do"),
+            inputs: vec![],
+            outputs: vec![PathBuf::from("a.dta")],
+            needs: vec![],
+            description: None,
+        };
+        let before = format!("{:?}", step_key("clean", &stage));
+        stage.outputs.push(PathBuf::from("b.dta"));
+        assert_ne!(before, format!("{:?}", step_key("clean", &stage)));
+    }
+}
@@ -0,0 +1,304 @@
+//! Declarative build pipeline
+//!
+//! Stages in stacy.toml's `[pipeline]` section name a script and the files it
+//! reads and writes. A stage that reads another stage's output runs after it;
+//! `needs` adds ordering where no file links two stages. `stacy build` runs
+//! the stages in that order, skipping the ones that are up to date.
+
+pub mod executor;
+
+use crate::error::{Error, Result};
+use crate::project::config::StageDef;
+use crate::utils::paths::normalize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+/// The validated stage graph of a project
+#[derive(Debug)]
+pub struct Pipeline {
+    stages: BTreeMap<String, StageDef>,
+    /// Stages each stage depends on, by name
+    upstream: BTreeMap<String, Vec<String>>,
+    /// Every stage, each after all of its upstream stages
+    order: Vec<String>,
+}
+
+impl Pipeline {
+    /// Build and validate the graph from the `[pipeline]` section
+    pub fn from_config(stages: &BTreeMap<String, StageDef>) -> Result<Self> {
+        validate_stages(stages)?;
+
+        let mut upstream = BTreeMap::new();
+        for (name, stage) in stages {
+            let mut deps: BTreeSet<String> = stage.needs.iter().cloned().collect();
+            for (other, other_stage) in stages {
+                if other != name && reads_output_of(stage, other_stage) {
+                    deps.insert(other.clone());
+                }
+            }
+            upstream.insert(name.clone(), deps.into_iter().collect());
+        }
+
+        let order = topological_order(&upstream)?;
+        Ok(Self {
+            stages: stages.clone(),
+            upstream,
+            order,
+        })
+    }
+
+    /// Get a stage by name
+    pub fn stage(&self, name: &str) -> Option<&StageDef> {
+        self.stages.get(name)
+    }
+
+    /// Stages that must run before `name`
+    pub fn upstream(&self, name: &str) -> &[String] {
+        self.upstream.get(name).map_or(&[], Vec::as_slice)
+    }
+
+    /// Get the number of stages
+    pub fn len(&self) -> usize {
+        self.stages.len()
+    }
+
+    /// Check if the pipeline has no stages
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+
+    /// The stages to build for `targets` (all stages when empty): the targets
+    /// and everything upstream of them, in execution order
+    pub fn select(&self, targets: &[String]) -> Result<Vec<&str>> {
+        if targets.is_empty() {
+            return Ok(self.order.iter().map(String::as_str).collect());
+        }
+
+        let mut wanted = BTreeSet::new();
+        let mut pending: Vec<&str> = Vec::new();
+        for target in targets {
+            if !self.stages.contains_key(target) {
+                return Err(Error::Config(format!(
+                    "Unknown stage '{}'\n\nAvailable stages: {}",
+                    target,
+                    self.order.join(", ")
+                )));
+            }
+            pending.push(target);
+        }
+        while let Some(name) = pending.pop() {
+            if wanted.insert(name) {
+                pending.extend(self.upstream(name).iter().map(String::as_str));
+            }
+        }
+
+        Ok(self
+            .order
+            .iter()
+            .map(String::as_str)
+            .filter(|name| wanted.contains(name))
+            .collect())
+    }
+
+    /// Declared outputs of `name` that don't exist under `project_root`
+    pub fn missing_outputs(&self, project_root: &Path, name: &str) -> Vec<PathBuf> {
+        self.stage(name)
+            .map(|stage| {
+                stage
+                    .outputs
+                    .iter()
+                    .filter(|output| !project_root.join(output).exists())
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Reject stages that name no script, share an output, or need a stage that
+/// doesn't exist
+fn validate_stages(stages: &BTreeMap<String, StageDef>) -> Result<()> {
+    let mut producers: BTreeMap<PathBuf, &str> = BTreeMap::new();
+    for (name, stage) in stages {
+        if stage.script.as_os_str().is_empty() {
+            return Err(Error::Config(format!("Stage '{}' has no script", name)));
+        }
+        for output in &stage.outputs {
+            if let Some(other) = producers.insert(normalize(output), name) {
+                return Err(Error::Config(format!(
+                    "Stages '{}' and '{}' both declare output '{}'",
+                    other,
+                    name,
+                    output.display()
+                )));
+            }
+        }
+        for need in &stage.needs {
+            if !stages.contains_key(need) {
+                return Err(Error::Config(format!(
+                    "Stage '{}' needs unknown stage '{}'",
+                    name, need
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Does `stage` read anything `producer` writes? An input directory covers
+/// the outputs inside it, and an input inside an output directory is that
+/// directory's.
+fn reads_output_of(stage: &StageDef, producer: &StageDef) -> bool {
+    stage.inputs.iter().any(|input| {
+        let input = normalize(input);
+        producer.outputs.iter().any(|output| {
+            let output = normalize(output);
+            input.starts_with(&output) || output.starts_with(&input)
+        })
+    })
+}
+
+/// Order stages so each comes after its upstream stages (Kahn's algorithm,
+/// ties broken by name so the order is stable)
+fn topological_order(upstream: &BTreeMap<String, Vec<String>>) -> Result<Vec<String>> {
+    let mut remaining: BTreeMap<&str, usize> = upstream
+        .iter()
+        .map(|(name, deps)| (name.as_str(), deps.len()))
+        .collect();
+    let mut ready: BTreeSet<&str> = remaining
+        .iter()
+        .filter(|(_, &count)| count == 0)
+        .map(|(&name, _)| name)
+        .collect();
+    let mut order = Vec::with_capacity(upstream.len());
+
+    while let Some(name) = ready.pop_first() {
+        remaining.remove(name);
+        order.push(name.to_string());
+        for (downstream, deps) in upstream {
+            if deps.iter().any(|d| d == name) {
+                if let Some(count) = remaining.get_mut(downstream.as_str()) {
+                    *count -= 1;
+                    if *count == 0 {
+                        ready.insert(downstream);
+                    }
+                }
+            }
+        }
+    }
+
+    if !remaining.is_empty() {
+        let cycle: Vec<_> = remaining.keys().copied().collect();
+        return Err(Error::Config(format!(
+            "Circular dependency among stages: {}",
+            cycle.join(", ")
+        )));
+    }
+    Ok(order)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stage(script: &str, inputs: &[&str], outputs: &[&str]) -> StageDef {
+        StageDef {
+            script: PathBuf::from(script),
+            inputs: inputs.iter().map(PathBuf::from).collect(),
+            outputs: outputs.iter().map(PathBuf::from).collect(),
+            needs: vec![],
+            description: None,
+        }
+    }
+
+    fn pipeline(stages: Vec<(&str, StageDef)>) -> Result<Pipeline> {
+        Pipeline::from_config(
+            &stages
+                .into_iter()
+                .map(|(name, stage)| (name.to_string(), stage))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_order_follows_inputs_and_outputs() {
+        let pipeline = pipeline(vec![
+            ("report", stage("report.do", &["output/tables"], &[])),
+            (
+                "analyze",
+                stage(
+                    "analyze.do",
+                    &["./data/clean.dta"],
+                    &["output/tables/t1.tex"],
+                ),
+            ),
+            (
+                "clean",
+                stage("clean.do", &["data/raw.csv"], &["data/clean.dta"]),
+            ),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            pipeline.select(&[]).unwrap(),
+            vec!["clean", "analyze", "report"]
+        );
+        assert_eq!(pipeline.upstream("analyze"), ["clean"]);
+        assert_eq!(pipeline.upstream("report"), ["analyze"]);
+    }
+
+    #[test]
+    fn test_select_includes_upstream_only() {
+        let mut figures = stage("figures.do", &[], &["output/fig.pdf"]);
+        figures.needs = vec!["clean".to_string()];
+        let pipeline = pipeline(vec![
+            ("clean", stage("clean.do", &[], &["data/clean.dta"])),
+            ("analyze", stage("analyze.do", &["data/clean.dta"], &[])),
+            ("figures", figures),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            pipeline.select(&["figures".to_string()]).unwrap(),
+            vec!["clean", "figures"]
+        );
+        let err = pipeline.select(&["figs".to_string()]).unwrap_err();
+        assert!(err.to_string().contains("Unknown stage 'figs'"));
+    }
+
+    #[test]
+    fn test_duplicate_output_errors() {
+        let err = pipeline(vec![
+            ("a", stage("a.do", &[], &["data/x.dta"])),
+            ("b", stage("b.do", &[], &["./data/x.dta"])),
+        ])
+        .unwrap_err();
+        assert!(err.to_string().contains("both declare output"));
+    }
+
+    #[test]
+    fn test_unknown_need_errors() {
+        let mut a = stage("a.do", &[], &[]);
+        a.needs = vec!["missing".to_string()];
+        let err = pipeline(vec![("a", a)]).unwrap_err();
+        assert!(err.to_string().contains("needs unknown stage 'missing'"));
+    }
+
+    #[test]
+    fn test_cycle_errors() {
+        let err = pipeline(vec![
+            ("a", stage("a.do", &["b.dta"], &["a.dta"])),
+            ("b", stage("b.do", &["a.dta"], &["b.dta"])),
+        ])
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Circular dependency among stages: a, b"));
+    }
+
+    #[test]
+    fn test_stage_reading_its_own_output_is_not_a_cycle() {
+        let pipeline = pipeline(vec![("a", stage("a.do", &["data"], &["data/a.dta"]))]);
+        assert!(pipeline.is_ok());
+    }
+}
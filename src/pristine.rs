@@ -0,0 +1,374 @@
+//! Clean-checkout execution (`stacy run --pristine`)
+//!
+//! Runs against a temporary `git worktree` of a commit (HEAD by default)
+//! instead of the working directory, so edits that were never committed
+//! can't leak into the results. The worktree shares the repository's object
+//! store, so creating one is cheap, and it is removed after the run.
+//!
+//! Untracked and ignored files are not in the worktree. Data usually is
+//! ignored, so the files declared in `[data]` are linked in from the working
+//! directory; everything else a script reads has to be committed.
+
+use crate::error::{Error, Result};
+use crate::project::config::DataSource;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tempfile::TempDir;
+
+/// The git executable
+const GIT: &str = "git";
+
+/// One line of `git status --porcelain`: a change the worktree won't have
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    /// The two-letter status code (`" M"`, `"A "`, `"??"`, ...)
+    pub status: String,
+    /// Path relative to the repository root (the new path for a rename)
+    pub path: String,
+}
+
+impl Change {
+    /// Short description for output
+    pub fn label(&self) -> &'static str {
+        match self.status.trim() {
+            "??" => "untracked",
+            s if s.contains('D') => "deleted",
+            s if s.contains('A') => "added",
+            s if s.contains('R') => "renamed",
+            _ => "modified",
+        }
+    }
+}
+
+/// A detached `git worktree`, removed (with its registration) on drop
+pub struct Worktree {
+    repo: PathBuf,
+    path: PathBuf,
+    parent: Option<TempDir>,
+}
+
+impl Worktree {
+    /// Check out `rev` into a fresh temporary worktree of `repo`
+    pub fn add(repo: &Path, rev: &str) -> Result<Self> {
+        let parent = TempDir::with_prefix("stacy-pristine-")?;
+        let path = parent.path().join("tree");
+        git(
+            repo,
+            &[
+                "worktree",
+                "add",
+                "--detach",
+                "--quiet",
+                &path.to_string_lossy(),
+                rev,
+            ],
+        )?;
+        Ok(Self {
+            repo: repo.to_path_buf(),
+            path,
+            parent: Some(parent),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Leave the worktree on disk, e.g. to inspect a failed run. It stays
+    /// registered until `git worktree remove` (or `prune`) is run.
+    pub fn keep(&mut self) -> PathBuf {
+        if let Some(parent) = self.parent.take() {
+            let _ = parent.keep();
+        }
+        self.path.clone()
+    }
+}
+
+impl Drop for Worktree {
+    fn drop(&mut self) {
+        if self.parent.is_some() {
+            let _ = git(
+                &self.repo,
+                &[
+                    "worktree",
+                    "remove",
+                    "--force",
+                    &self.path.to_string_lossy(),
+                ],
+            );
+        }
+    }
+}
+
+/// The root of the git repository containing `dir`
+pub fn repo_root(dir: &Path) -> Result<PathBuf> {
+    let out = git(dir, &["rev-parse", "--show-toplevel"]).map_err(|_| {
+        Error::Config(format!(
+            "{} is not in a git repository; --pristine runs a committed snapshot",
+            dir.display()
+        ))
+    })?;
+    Ok(PathBuf::from(out.trim()))
+}
+
+/// The abbreviated commit hash `rev` names
+pub fn resolve_commit(repo: &Path, rev: &str) -> Result<String> {
+    let spec = format!("{}^{{commit}}", rev);
+    git(repo, &["rev-parse", "--verify", "--short", &spec])
+        .map(|out| out.trim().to_string())
+        .map_err(|_| Error::Config(format!("Not a commit in this repository: {}", rev)))
+}
+
+/// Uncommitted changes in the working directory, untracked files included
+pub fn uncommitted_changes(repo: &Path) -> Result<Vec<Change>> {
+    let out = git(repo, &["status", "--porcelain", "--untracked-files=all"])?;
+    Ok(parse_porcelain(&out))
+}
+
+/// Parse `git status --porcelain` (v1) output
+pub fn parse_porcelain(out: &str) -> Vec<Change> {
+    out.lines()
+        .filter(|line| line.len() > 3)
+        .map(|line| {
+            let (status, path) = line.split_at(2);
+            let path = path.trim_start();
+            // `R  old -> new`: the worktree lacks the new path
+            let path = path.rsplit(" -> ").next().unwrap_or(path);
+            Change {
+                status: status.to_string(),
+                path: path.trim_matches('"').to_string(),
+            }
+        })
+        .collect()
+}
+
+/// Link the files declared in `[data]` that the worktree lacks from the
+/// working directory's project root into the worktree's. Returns the paths
+/// linked, relative to the project root.
+pub fn link_data(
+    from_root: &Path,
+    to_root: &Path,
+    data: &BTreeMap<String, DataSource>,
+) -> Result<Vec<PathBuf>> {
+    let mut linked = Vec::new();
+    for source in data.values() {
+        let src = from_root.join(&source.path);
+        let dest = to_root.join(&source.path);
+        if dest.exists() || !src.exists() {
+            continue;
+        }
+        if let Some(parent) = dest.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        link(&src, &dest)?;
+        linked.push(source.path.clone());
+    }
+    Ok(linked)
+}
+
+#[cfg(unix)]
+fn link(src: &Path, dest: &Path) -> Result<()> {
+    std::os::unix::fs::symlink(src, dest)?;
+    Ok(())
+}
+
+/// Without symlink rights on Windows, files are copied
+#[cfg(not(unix))]
+fn link(src: &Path, dest: &Path) -> Result<()> {
+    if src.is_dir() {
+        crate::reproducible::copy_project(src, dest)
+    } else {
+        std::fs::copy(src, dest)?;
+        Ok(())
+    }
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new(GIT)
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                Error::Config("git not found on PATH".to_string())
+            } else {
+                Error::Execution(format!("Failed to run git: {}", e))
+            }
+        })?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(Error::Execution(format!(
+            "git {} failed:\n{}",
+            args.first().copied().unwrap_or_default(),
+            stderr.trim_end()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// A repository with `main.do` committed
+    fn init_repo() -> TempDir {
+        let repo = TempDir::new().unwrap();
+        git(repo.path(), &["init", "--quiet"]).unwrap();
+        fs::write(repo.path().join("main.do"), "display 1\n").unwrap();
+        git(repo.path(), &["add", "main.do"]).unwrap();
+        git(
+            repo.path(),
+            &[
+                "-c",
+                "user.name=stacy",
+                "-c",
+                "user.email=stacy@example.com",
+                "-c",
+                "commit.gpgsign=false",
+                "commit",
+                "--quiet",
+                "-m",
+                "init",
+            ],
+        )
+        .unwrap();
+        repo
+    }
+
+    fn worktree_count(repo: &Path) -> usize {
+        git(repo, &["worktree", "list", "--porcelain"])
+            .unwrap()
+            .lines()
+            .filter(|line| line.starts_with("worktree "))
+            .count()
+    }
+
+    #[test]
+    fn test_worktree_has_committed_state_and_is_removed_on_drop() {
+        let repo = init_repo();
+        fs::write(repo.path().join("main.do"), "display 2\n").unwrap();
+        fs::write(repo.path().join("scratch.do"), "display 3\n").unwrap();
+
+        let worktree = Worktree::add(repo.path(), "HEAD").unwrap();
+        let path = worktree.path().to_path_buf();
+        assert_eq!(
+            fs::read_to_string(path.join("main.do")).unwrap(),
+            "display 1\n"
+        );
+        assert!(!path.join("scratch.do").exists());
+        assert_eq!(worktree_count(repo.path()), 2);
+
+        drop(worktree);
+        assert!(!path.exists());
+        assert_eq!(worktree_count(repo.path()), 1);
+    }
+
+    #[test]
+    fn test_kept_worktree_survives_drop() {
+        let repo = init_repo();
+        let mut worktree = Worktree::add(repo.path(), "HEAD").unwrap();
+        let kept = worktree.keep();
+        drop(worktree);
+
+        assert!(kept.join("main.do").is_file());
+        assert_eq!(worktree_count(repo.path()), 2);
+        git(
+            repo.path(),
+            &["worktree", "remove", "--force", &kept.to_string_lossy()],
+        )
+        .unwrap();
+        let _ = fs::remove_dir_all(kept.parent().unwrap());
+    }
+
+    #[test]
+    fn test_worktree_of_unknown_rev_fails() {
+        let repo = init_repo();
+        assert!(Worktree::add(repo.path(), "no-such-branch").is_err());
+        assert!(resolve_commit(repo.path(), "no-such-branch").is_err());
+        assert!(resolve_commit(repo.path(), "HEAD").unwrap().len() >= 7);
+    }
+
+    #[test]
+    fn test_uncommitted_changes_lists_what_the_worktree_leaves_out() {
+        let repo = init_repo();
+        assert!(uncommitted_changes(repo.path()).unwrap().is_empty());
+
+        fs::write(repo.path().join("main.do"), "display 2\n").unwrap();
+        fs::create_dir(repo.path().join("wip")).unwrap();
+        fs::write(repo.path().join("wip/new.do"), "display 3\n").unwrap();
+        let changes: Vec<(&str, String)> = uncommitted_changes(repo.path())
+            .unwrap()
+            .iter()
+            .map(|c| (c.label(), c.path.clone()))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                ("modified", "main.do".to_string()),
+                ("untracked", "wip/new.do".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_link_data() {
+        let from = TempDir::new().unwrap();
+        let to = TempDir::new().unwrap();
+        fs::create_dir_all(from.path().join("data")).unwrap();
+        fs::write(from.path().join("data/raw.csv"), "a,b\n").unwrap();
+        fs::write(from.path().join("data/codebook.csv"), "mine\n").unwrap();
+        fs::create_dir_all(to.path().join("data")).unwrap();
+        fs::write(to.path().join("data/codebook.csv"), "committed\n").unwrap();
+
+        let source = |path: &str| DataSource {
+            path: PathBuf::from(path),
+            ..Default::default()
+        };
+        let data = BTreeMap::from([
+            ("raw".to_string(), source("data/raw.csv")),
+            ("codebook".to_string(), source("data/codebook.csv")),
+            ("missing".to_string(), source("data/missing.dta")),
+        ]);
+
+        let linked = link_data(from.path(), to.path(), &data).unwrap();
+        assert_eq!(linked, vec![PathBuf::from("data/raw.csv")]);
+        assert_eq!(
+            fs::read_to_string(to.path().join("data/raw.csv")).unwrap(),
+            "a,b\n"
+        );
+        // What the worktree has already is left alone
+        assert_eq!(
+            fs::read_to_string(to.path().join("data/codebook.csv")).unwrap(),
+            "committed\n"
+        );
+        assert!(!to.path().join("data/missing.dta").exists());
+    }
+
+    #[test]
+    fn test_parse_porcelain() {
+        let changes = parse_porcelain(
+            " M analysis/clean.do\nA  analysis/new.do\nR  old.do -> renamed.do\n?? scratch.do\n D gone.do\n",
+        );
+        let summary: Vec<(&str, &str)> = changes
+            .iter()
+            .map(|c| (c.label(), c.path.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("modified", "analysis/clean.do"),
+                ("added", "analysis/new.do"),
+                ("renamed", "renamed.do"),
+                ("untracked", "scratch.do"),
+                ("deleted", "gone.do"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_porcelain_empty() {
+        assert!(parse_porcelain("").is_empty());
+    }
+}
@@ -8,6 +8,10 @@ use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, HashMap};
 use std::path::{Path, PathBuf};
 
+/// JSON Schema of stacy.toml, for editors to complete and check it against
+/// (`stacy config schema`). Kept in step with the structs below by a test.
+pub const JSON_SCHEMA: &str = include_str!("../../schema/stacy.schema.json");
+
 /// Project configuration loaded from stacy.toml
 ///
 /// Unknown keys are rejected (#100). A misplaced key — `[dependencies]` instead
@@ -20,12 +24,65 @@ pub struct Config {
     pub project: ProjectSection,
     /// Execution settings (for `stacy run`)
     pub run: RunSection,
+    /// How Stata itself is set up for each run
+    #[serde(skip_serializing_if = "ExecutionSection::is_empty")]
+    pub execution: ExecutionSection,
+    /// Version and random-number settings applied to every run
+    #[serde(skip_serializing_if = "ReproducibilitySection::is_empty")]
+    pub reproducibility: ReproducibilitySection,
     /// Path settings (local ado directories, etc.)
     pub paths: PathsSection,
     /// Package management settings
     pub packages: PackagesSection,
+    /// How the lockfile is enforced
+    #[serde(skip_serializing_if = "InstallSection::is_empty")]
+    pub install: InstallSection,
     /// Task definitions (for `stacy task`)
     pub scripts: ScriptsSection,
+    /// Where `stacy test` finds tests
+    #[serde(skip_serializing_if = "TestSection::is_empty")]
+    pub test: TestSection,
+    /// Build stages keyed by name (for `stacy build`)
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub pipeline: BTreeMap<String, StageDef>,
+    /// Downloadable input data keyed by name (for `stacy data fetch`)
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub data: BTreeMap<String, DataSource>,
+    /// Batch scheduler resources for `stacy submit`
+    #[serde(skip_serializing_if = "SubmitSection::is_empty")]
+    pub submit: SubmitSection,
+    /// Exit codes for kinds of failure, overriding the standard ones
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub exit_codes: BTreeMap<String, i32>,
+    /// Named package sets with lockfiles of their own (for `--env-profile`)
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, ProfileSection>,
+    /// Sources replacing those packages are declared with (`[overrides]`)
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub overrides: BTreeMap<String, String>,
+}
+
+/// A package set selected with `--env-profile <name>` and locked in
+/// `stacy.<name>.lock`
+///
+/// ```toml
+/// [profiles.replication]
+/// groups = ["dependencies"]
+///
+/// [profiles.replication.packages]
+/// reghdfe = "github:sergiocorreia/reghdfe@6.12.3"
+/// ```
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProfileSection {
+    /// Groups of `[packages]` the profile takes (`dependencies`, `dev`,
+    /// `test`); unset takes all of them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub groups: Option<Vec<String>>,
+    /// Packages of the profile only, winning over a group's entry of the
+    /// same name
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub packages: BTreeMap<String, PackageSpec>,
 }
 
 /// Path settings for local ado directories
@@ -49,6 +106,13 @@ pub struct ProjectSection {
     pub description: Option<String>,
     /// Project URL (e.g., GitHub repository)
     pub url: Option<String>,
+    /// With `--nested-projects outermost`, stop looking for an enclosing
+    /// project here
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub root: bool,
+    /// Directory of the enclosing project this one belongs to (relative to
+    /// this one), whose stacy.toml and stacy.lock are used instead
+    pub parent: Option<PathBuf>,
 }
 
 /// Execution settings for `stacy run`
@@ -63,6 +127,12 @@ pub struct RunSection {
     pub progress_interval_seconds: u64,
     /// Maximum log file size in MB before warning
     pub max_log_size_mb: u64,
+    /// Refuse to run while a file declared in `[data]` is missing
+    pub verify_data: bool,
+    /// Environment variables whose values are masked in logs and output,
+    /// besides those with secret-looking names (`CENSUS_*` takes a prefix)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub secrets: Vec<String>,
 }
 
 impl Default for RunSection {
@@ -72,15 +142,247 @@ impl Default for RunSection {
             show_progress: true,
             progress_interval_seconds: 10,
             max_log_size_mb: 50,
+            verify_data: false,
+            secrets: Vec::new(),
+        }
+    }
+}
+
+/// Stata settings applied to every script stacy runs
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExecutionSection {
+    /// Cores Stata/MP may use (`set processors N`); unset leaves Stata's
+    /// default, which is every licensed core on the machine
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub processors: Option<u32>,
+}
+
+impl ExecutionSection {
+    fn is_empty(&self) -> bool {
+        self.processors.is_none()
+    }
+}
+
+/// Lockfile enforcement for `stacy install`, `task`, `test` and `run`
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct InstallSection {
+    /// Always run in frozen mode, as if `--frozen` were passed: fail when
+    /// stacy.lock is out of sync with stacy.toml
+    pub frozen: bool,
+}
+
+impl InstallSection {
+    fn is_empty(&self) -> bool {
+        !self.frozen
+    }
+}
+
+/// Resources `stacy submit` asks the batch scheduler for. Each can be
+/// overridden on the command line; unset ones are left to the cluster's
+/// defaults.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SubmitSection {
+    /// Scheduler to submit to (default slurm)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scheduler: Option<Scheduler>,
+    /// SLURM partition, or PBS queue
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub partition: Option<String>,
+    /// Wall time limit, as the scheduler takes it (`02:00:00`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time: Option<String>,
+    /// Memory, as the scheduler takes it (`16G`, `16gb`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory: Option<String>,
+    /// CPU cores for the job; set Stata/MP's `[execution] processors` to match
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpus: Option<u32>,
+    /// Account the job is charged to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub account: Option<String>,
+    /// Environment modules to load before stacy runs (`module load ...`)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub modules: Vec<String>,
+    /// Further scheduler directives, written as given (`--mail-type=END`)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub directives: Vec<String>,
+}
+
+impl SubmitSection {
+    fn is_empty(&self) -> bool {
+        *self == SubmitSection::default()
+    }
+}
+
+/// Batch scheduler of a cluster
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Scheduler {
+    #[default]
+    Slurm,
+    Pbs,
+}
+
+impl std::fmt::Display for Scheduler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Scheduler::Slurm => write!(f, "slurm"),
+            Scheduler::Pbs => write!(f, "pbs"),
         }
     }
 }
 
+/// Test discovery for `stacy test`. Unset, tests are found by convention:
+/// `test_*.do` and `*_test.do` anywhere, and every `.do` file in `tests/` or
+/// `test/`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TestSection {
+    /// Directories holding tests (relative to project root); unset searches
+    /// the whole project for `pattern`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub dirs: Vec<PathBuf>,
+    /// File name patterns of tests (`check_*.do`), one or a list; unset takes
+    /// every `.do` file in `dirs`
+    #[serde(
+        deserialize_with = "deserialize_one_or_many",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub pattern: Vec<String>,
+    /// Paths that are never tests, as globs relative to project root
+    /// (`tests/fixtures`, `**/helpers_*.do`)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+    /// Share of runs on the same code that must disagree with the rest
+    /// before a test counts as flaky (default 0.1)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flaky_threshold: Option<f64>,
+}
+
+impl TestSection {
+    /// Default for `flaky_threshold`: one run in ten
+    pub const DEFAULT_FLAKY_THRESHOLD: f64 = 0.1;
+
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Whether discovery falls back to the naming convention
+    pub fn is_conventional(&self) -> bool {
+        self.dirs.is_empty() && self.pattern.is_empty()
+    }
+
+    /// `flaky_threshold`, or its default
+    pub fn flaky_threshold(&self) -> f64 {
+        self.flaky_threshold
+            .unwrap_or(Self::DEFAULT_FLAKY_THRESHOLD)
+    }
+}
+
+/// A string or a list of strings
+fn deserialize_one_or_many<'de, D>(deserializer: D) -> std::result::Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(one) => vec![one],
+        OneOrMany::Many(many) => many,
+    })
+}
+
+/// Settings that pin a script's results, applied before every script stacy
+/// runs. An unset seed is the most common reason a rerun doesn't reproduce.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReproducibilitySection {
+    /// Stata version to interpret scripts under (`version 17`)
+    #[serde(
+        deserialize_with = "deserialize_stata_version",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub version: Option<String>,
+    /// `set seed N`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u32>,
+    /// `set sortseed N`, which fixes how `sort` breaks ties
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sortseed: Option<u32>,
+    /// `set rngstream N` (switches the generator to `mt64s`, the only one
+    /// with streams)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rngstream: Option<u32>,
+}
+
+impl ReproducibilitySection {
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The Stata commands that apply these settings, in the order Stata needs
+    /// them: `version` first, the generator before its seed
+    pub fn preamble(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        if let Some(version) = &self.version {
+            lines.push(format!("version {}", version));
+        }
+        if let Some(stream) = self.rngstream {
+            lines.push("set rng mt64s".to_string());
+            lines.push(format!("set rngstream {}", stream));
+        }
+        if let Some(seed) = self.seed {
+            lines.push(format!("set seed {}", seed));
+        }
+        if let Some(sortseed) = self.sortseed {
+            lines.push(format!("set sortseed {}", sortseed));
+        }
+        lines
+    }
+}
+
+/// `version = 17` and `version = "17"` both mean Stata 17
+fn deserialize_stata_version<'de, D>(
+    deserializer: D,
+) -> std::result::Result<Option<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let version = match toml::Value::deserialize(deserializer)? {
+        toml::Value::String(s) => s,
+        toml::Value::Integer(i) => i.to_string(),
+        toml::Value::Float(f) => f.to_string(),
+        other => {
+            return Err(serde::de::Error::custom(format!(
+                "expected a Stata version such as 17 or \"16.1\", found {}",
+                other.type_str()
+            )))
+        }
+    };
+    if version.parse::<f64>().map_or(true, |v| v <= 0.0) {
+        return Err(serde::de::Error::custom(format!(
+            "'{}' is not a Stata version (expected e.g. 17 or \"16.1\")",
+            version
+        )));
+    }
+    Ok(Some(version))
+}
+
 /// Package specification in stacy.toml
 ///
 /// Supports two formats:
 /// - Simple: just the source string, e.g., `estout = "ssc"` or `ftools = "github:sergiocorreia/ftools"`
 /// - Detailed: object with source and optional version, e.g., `{ source = "ssc", version = "1.0.0" }`
+///
+/// `dev = true` on a `local:` source makes the package editable: its
+/// directory goes on the adopath as it is instead of a copy in the cache.
 #[derive(Debug, Clone, Serialize, PartialEq)]
 #[serde(untagged)]
 pub enum PackageSpec {
@@ -91,6 +393,8 @@ pub enum PackageSpec {
         source: String,
         #[serde(skip_serializing_if = "Option::is_none")]
         version: Option<String>,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        dev: bool,
     },
 }
 
@@ -102,6 +406,8 @@ struct DetailedSpec {
     source: String,
     #[serde(default)]
     version: Option<String>,
+    #[serde(default)]
+    dev: bool,
 }
 
 // Hand-written so a bad key inside the table is an error naming the key.
@@ -118,6 +424,7 @@ impl<'de> Deserialize<'de> for PackageSpec {
                 Ok(PackageSpec::Detailed {
                     source: spec.source,
                     version: spec.version,
+                    dev: spec.dev,
                 })
             }
             other => Err(serde::de::Error::custom(format!(
@@ -149,6 +456,20 @@ impl PackageSpec {
     pub fn simple(source: impl Into<String>) -> Self {
         PackageSpec::Simple(source.into())
     }
+
+    /// An editable spec (`dev = true`) for a `local:` source
+    pub fn editable(source: impl Into<String>) -> Self {
+        PackageSpec::Detailed {
+            source: source.into(),
+            version: None,
+            dev: true,
+        }
+    }
+
+    /// Whether `dev = true` is set
+    pub fn is_editable(&self) -> bool {
+        matches!(self, PackageSpec::Detailed { dev: true, .. })
+    }
 }
 
 /// Dependency group for categorizing packages
@@ -192,6 +513,67 @@ pub struct PackagesSection {
     /// Test dependencies: package_name -> source spec
     #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
     pub test: BTreeMap<String, PackageSpec>,
+    /// What to do when two packages ship the same `.ado` file
+    #[serde(default, skip_serializing_if = "CollisionPolicy::is_default")]
+    pub on_collision: CollisionPolicy,
+    /// Where `stacy install` puts packages
+    #[serde(default, skip_serializing_if = "InstallMode::is_default")]
+    pub install_mode: InstallMode,
+    /// Project directory packages go to with `install_mode = "local"`
+    /// (default `ado`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub install_dir: Option<PathBuf>,
+}
+
+impl PackagesSection {
+    /// The project directory packages are installed to, relative to the
+    /// root, when they aren't installed to the global cache
+    pub fn local_install_dir(&self) -> Option<PathBuf> {
+        (self.install_mode == InstallMode::Local).then(|| {
+            self.install_dir
+                .clone()
+                .unwrap_or_else(|| PathBuf::from(DEFAULT_INSTALL_DIR))
+        })
+    }
+}
+
+/// Project directory of `install_mode = "local"` unless `install_dir` says
+/// otherwise
+pub const DEFAULT_INSTALL_DIR: &str = "ado";
+
+/// Where installed packages live
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InstallMode {
+    /// The global package cache, shared by every project of the user
+    #[default]
+    Global,
+    /// A directory of the project, for machines where a shared cache is
+    /// not an option (home quotas, policies)
+    Local,
+}
+
+impl InstallMode {
+    fn is_default(&self) -> bool {
+        *self == InstallMode::Global
+    }
+}
+
+/// Policy for `.ado` files provided by more than one package
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CollisionPolicy {
+    /// Refuse to lock or install
+    #[default]
+    Error,
+    /// Report the collision; the package first on the adopath wins
+    Warn,
+}
+
+impl CollisionPolicy {
+    fn is_default(&self) -> bool {
+        *self == CollisionPolicy::Error
+    }
 }
 
 impl PackagesSection {
@@ -361,9 +743,98 @@ pub struct ComplexTask {
     /// Human-readable description of the task
     #[serde(default)]
     pub description: Option<String>,
+    /// Files or directories the script reads, relative to the project root.
+    /// Their content is part of the build-cache key (`stacy task --cache`).
+    #[serde(default)]
+    pub inputs: Option<Vec<PathBuf>>,
+    /// Files or directories the script writes, relative to the project root.
+    /// Registered as artifacts each time the script succeeds (`stacy outputs`).
+    #[serde(default)]
+    pub outputs: Option<Vec<PathBuf>>,
+    /// Cron expression to run the task on (`"0 3 * * *"`), once `stacy
+    /// schedule install` has set it up
+    #[serde(default)]
+    pub schedule: Option<String>,
+}
+
+/// A stage of the `[pipeline]` built by `stacy build`
+///
+/// ```toml
+/// [pipeline.clean]
+/// script = "src/01_clean.do"
+/// inputs = ["data/raw/survey.csv"]
+/// outputs = ["data/clean/survey.dta"]
+/// ```
+///
+/// A stage depends on every stage whose outputs it lists among its inputs,
+/// plus the stages named in `needs`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct StageDef {
+    /// Script the stage runs
+    pub script: PathBuf,
+    /// Files or directories the script reads, relative to the project root
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub inputs: Vec<PathBuf>,
+    /// Files or directories the script writes, relative to the project root
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub outputs: Vec<PathBuf>,
+    /// Stages that must run first although no file links them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub needs: Vec<String>,
+    /// Human-readable description of the stage
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+/// Data the project downloads (`[data]`), fetched by `stacy data fetch`
+///
+/// Either a single file from a URL, or the files of a dataset published on
+/// Dataverse or Zenodo, found by its DOI:
+///
+/// ```toml
+/// [data.survey]
+/// url = "https://example.org/files/survey-2024.csv"
+/// path = "data/raw/survey.csv"
+/// sha256 = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"
+///
+/// [data.replication]
+/// doi = "10.7910/DVN/ABCDEF"
+/// path = "data/replication"
+/// version = "2.0"
+/// ```
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct DataSource {
+    /// Where to download the file from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// DOI of a Dataverse dataset or Zenodo record whose files to download
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub doi: Option<String>,
+    /// Where the file goes (with `url`) or the directory the dataset's files
+    /// go into (with `doi`), relative to the project root
+    pub path: PathBuf,
+    /// Expected SHA-256 of the file; a download that doesn't match is rejected
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// Dataset version to download (with `doi`; default: the latest)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// Only these files of the dataset (with `doi`; default: all)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<String>,
+    /// Repository to query (with `doi`; default: Harvard Dataverse, or
+    /// zenodo.org for Zenodo DOIs)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server: Option<String>,
+    /// Human-readable description of the data
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
 }
 
-/// Load configuration from stacy.toml in the project root.
+/// Load configuration from stacy.toml in the project root. Under
+/// `--env-profile`, `packages` holds the profile's packages.
 ///
 /// Returns `None` if the config file doesn't exist.
 /// Returns an error if the file exists but is invalid TOML.
@@ -379,6 +850,16 @@ pub struct ComplexTask {
 /// }
 /// ```
 pub fn load_config(project_root: &Path) -> Result<Option<Config>> {
+    let mut config = read_config(project_root)?;
+    if let Some(config) = &mut config {
+        super::profile::apply(config)?;
+        super::overrides::apply(config);
+    }
+    Ok(config)
+}
+
+/// stacy.toml as written, whatever the profile: for commands that edit it
+pub fn read_config(project_root: &Path) -> Result<Option<Config>> {
     let config_path = project_root.join("stacy.toml");
 
     if !config_path.exists() {
@@ -393,12 +874,8 @@ pub fn load_config(project_root: &Path) -> Result<Option<Config>> {
         ))
     })?;
 
-    let config: Config = toml::from_str(&content).map_err(|e| {
-        Error::Config(format!(
-            "Failed to parse stacy.toml: {}",
-            format_toml_error(&e)
-        ))
-    })?;
+    let config = super::validate::parse(&content)
+        .map_err(|diagnostics| Error::Config(super::validate::describe(&diagnostics)))?;
 
     // Validate the loaded config
     validate_config(&config, project_root)?;
@@ -406,23 +883,20 @@ pub fn load_config(project_root: &Path) -> Result<Option<Config>> {
     Ok(Some(config))
 }
 
-/// Format a TOML parse error: the toml crate renders line, column and the
-/// offending snippet. Unknown keys get an extra line saying where the key
-/// actually belongs, when we can tell.
-fn format_toml_error(err: &toml::de::Error) -> String {
-    let mut msg = err.to_string();
-    if let Some(hint) = unknown_key_hint(err.message()) {
-        msg.push('\n');
-        msg.push_str(&hint);
-    }
-    msg
-}
-
 /// Point a misplaced top-level dependency key at the section it belongs in.
 ///
 /// serde's message ("unknown field `dependencies`, expected one of `project`,
 /// ...") names the key; this adds the fix.
-fn unknown_key_hint(message: &str) -> Option<String> {
+pub(super) fn unknown_key_hint(message: &str) -> Option<String> {
+    // A package under `[test]` instead of `[packages.test]`
+    if message.contains("expected one of `dirs`") {
+        return Some(
+            "hint: [test] sets where tests are found; declare test packages under [packages.test] \
+             (`stacy migrate` moves them)"
+                .into(),
+        );
+    }
+
     let key = message
         .split("unknown field `")
         .nth(1)?
@@ -438,17 +912,19 @@ fn unknown_key_hint(message: &str) -> Option<String> {
     let section = match key.as_str() {
         "dependencies" => "[packages.dependencies]",
         "dev" => "[packages.dev]",
-        "test" => "[packages.test]",
         _ => return None,
     };
-    Some(format!("hint: declare these under {}", section))
+    Some(format!(
+        "hint: declare these under {} (`stacy migrate` moves them)",
+        section
+    ))
 }
 
 /// Validate configuration values.
 ///
 /// Checks that specified paths exist and are valid.
 /// Note: log_dir is not validated for existence - it will be created at runtime.
-fn validate_config(_config: &Config, _project_root: &Path) -> Result<()> {
+pub(super) fn validate_config(config: &Config, _project_root: &Path) -> Result<()> {
     // Note: We don't validate the log_dir path here because:
     // 1. It is a relative path that will be created at runtime
     // 2. The project might be shared and paths may not exist on all systems yet
@@ -457,6 +933,74 @@ fn validate_config(_config: &Config, _project_root: &Path) -> Result<()> {
     // Stata binary is NOT in project config - it's in user config (~/.config/stacy/config.toml)
     // or set via STATA_BINARY environment variable.
 
+    if config.project.root && config.project.parent.is_some() {
+        return Err(Error::Config(
+            "[project] root = true and parent can't both be set".to_string(),
+        ));
+    }
+
+    // At most half the runs can disagree with the most common outcome
+    if let Some(threshold) = config.test.flaky_threshold {
+        if !(threshold > 0.0 && threshold <= 0.5) {
+            return Err(Error::Config(format!(
+                "[test] flaky_threshold must be above 0 and at most 0.5, got {}",
+                threshold
+            )));
+        }
+    }
+
+    // Patterns match file names; directories go in `dirs`
+    if let Some(pattern) = config
+        .test
+        .pattern
+        .iter()
+        .find(|p| p.contains('/') || p.contains('\\'))
+    {
+        return Err(Error::Config(format!(
+            "[test] pattern \"{}\" matches file names and can't hold a directory\n\
+             hint: put the directory in [test] dirs",
+            pattern
+        )));
+    }
+
+    crate::error::exit_codes::validate(&config.exit_codes, "stacy.toml")?;
+
+    for (name, profile) in &config.profiles {
+        super::profile::validate_name(name)?;
+        if let Some(group) = profile
+            .groups
+            .iter()
+            .flatten()
+            .find(|g| !super::profile::GROUPS.contains(&g.as_str()))
+        {
+            return Err(Error::Config(format!(
+                "[profiles.{}] groups: unknown group '{}'. Valid groups: {}",
+                name,
+                group,
+                super::profile::GROUPS.join(", ")
+            )));
+        }
+    }
+
+    // Only a local directory can be linked instead of copied
+    let profile_packages = config.profiles.values().flat_map(|p| p.packages.iter());
+    let all_packages = config
+        .packages
+        .all_packages()
+        .map(|(name, spec, _)| (name, spec))
+        .chain(profile_packages);
+    for (name, spec) in all_packages {
+        if spec.is_editable() && !spec.source().to_lowercase().starts_with("local:") {
+            return Err(Error::Config(format!(
+                "Package '{}': dev = true needs a local: source, not '{}'",
+                name,
+                spec.source()
+            )));
+        }
+    }
+
+    super::overrides::validate(config)?;
+
     Ok(())
 }
 
@@ -467,8 +1011,7 @@ fn validate_config(_config: &Config, _project_root: &Path) -> Result<()> {
 /// * `project_root` - Path to the project root directory
 pub fn write_config(config: &Config, project_root: &Path) -> Result<()> {
     let config_path = project_root.join("stacy.toml");
-    let content = toml::to_string_pretty(config)
-        .map_err(|e| Error::Config(format!("Failed to serialize config: {}", e)))?;
+    let content = render_config(config)?;
 
     std::fs::write(&config_path, content).map_err(|e| {
         Error::Config(format!(
@@ -481,6 +1024,12 @@ pub fn write_config(config: &Config, project_root: &Path) -> Result<()> {
     Ok(())
 }
 
+/// The stacy.toml [`write_config`] writes for `config`
+pub fn render_config(config: &Config) -> Result<String> {
+    toml::to_string_pretty(config)
+        .map_err(|e| Error::Config(format!("Failed to serialize config: {}", e)))
+}
+
 /// Generate a template stacy.toml with commented defaults.
 ///
 /// This is used by `stacy init` to create a discoverable config file.
@@ -603,6 +1152,116 @@ show_progress = false
         assert!(err_msg.contains("Failed to parse stacy.toml"));
     }
 
+    /// The keys serde takes in the table at `path` (dotted), from the error
+    /// an unknown key there gives
+    fn serde_keys(path: &str) -> Vec<String> {
+        let header = if path.is_empty() {
+            String::new()
+        } else {
+            format!("[{}]\n", path)
+        };
+        let err = toml::from_str::<Config>(&format!("{}not_a_key = 1\n", header)).unwrap_err();
+        let (_, expected) = err.message().split_once("expected").unwrap();
+        let mut keys: Vec<String> = expected
+            .split('`')
+            .skip(1)
+            .step_by(2)
+            .map(String::from)
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    /// The keys the JSON Schema lists for the table at `path` (dotted)
+    fn schema_keys(schema: &serde_json::Value, path: &str) -> Vec<String> {
+        let resolve = |node: &serde_json::Value| -> serde_json::Value {
+            let node = match node["$ref"].as_str() {
+                Some(reference) => {
+                    let name = reference.trim_start_matches("#/definitions/");
+                    schema["definitions"][name].clone()
+                }
+                None => node.clone(),
+            };
+            match node["oneOf"].as_array() {
+                Some(variants) => variants
+                    .iter()
+                    .find(|variant| variant["type"] == "object")
+                    .cloned()
+                    .unwrap(),
+                None => node,
+            }
+        };
+        let mut node = schema.clone();
+        for part in path.split('.').filter(|part| !part.is_empty()) {
+            node = match node["properties"].get(part) {
+                Some(property) => resolve(property),
+                None => resolve(&node["additionalProperties"]),
+            };
+        }
+        let mut keys: Vec<String> = node["properties"]
+            .as_object()
+            .unwrap_or_else(|| panic!("no properties for [{}] in the schema", path))
+            .keys()
+            .cloned()
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    #[test]
+    fn test_json_schema_matches_config() {
+        let schema: serde_json::Value = serde_json::from_str(JSON_SCHEMA).unwrap();
+        for path in [
+            "",
+            "project",
+            "run",
+            "execution",
+            "reproducibility",
+            "paths",
+            "packages",
+            "packages.dependencies.estout",
+            "install",
+            "scripts.build",
+            "test",
+            "pipeline.clean",
+            "data.survey",
+            "submit",
+            "profiles.replication",
+        ] {
+            assert_eq!(
+                schema_keys(&schema, path),
+                serde_keys(path),
+                "keys of [{}] in schema/stacy.schema.json",
+                path
+            );
+        }
+
+        let mut kinds: Vec<String> = crate::error::exit_codes::EXIT_KINDS
+            .iter()
+            .map(|kind| kind.name.to_string())
+            .collect();
+        kinds.sort();
+        assert_eq!(schema_keys(&schema, "exit_codes"), kinds);
+
+        for (section, key) in [
+            ("packages", "on_collision"),
+            ("packages", "install_mode"),
+            ("submit", "scheduler"),
+        ] {
+            let err =
+                toml::from_str::<Config>(&format!("[{}]\n{} = \"x\"\n", section, key)).unwrap_err();
+            let (_, expected) = err.message().split_once("expected").unwrap();
+            let variants: Vec<&str> = expected.split('`').skip(1).step_by(2).collect();
+            assert_eq!(
+                schema["properties"][section]["properties"][key]["enum"],
+                serde_json::json!(variants),
+                "values of {}.{}",
+                section,
+                key
+            );
+        }
+    }
+
     #[test]
     fn test_misplaced_dependencies_section_is_rejected() {
         // #100: `[dependencies]` instead of `[packages.dependencies]` used to be
@@ -676,6 +1335,81 @@ show_progress = false
         assert!(err.contains("naem"), "error must name the key: {}", err);
     }
 
+    #[test]
+    fn test_exit_codes_section() {
+        let temp = TempDir::new().unwrap();
+        fs::write(
+            temp.path().join("stacy.toml"),
+            "[exit_codes]\nsyntax_error = 65\n",
+        )
+        .unwrap();
+        let config = load_config(temp.path()).unwrap().unwrap();
+        assert_eq!(config.exit_codes.get("syntax_error"), Some(&65));
+
+        fs::write(
+            temp.path().join("stacy.toml"),
+            "[exit_codes]\nsyntax_error = 0\n",
+        )
+        .unwrap();
+        let err = load_config(temp.path()).unwrap_err().to_string();
+        assert!(err.contains("between 1 and 255"), "{}", err);
+    }
+
+    #[test]
+    fn test_project_root_and_parent_exclusive() {
+        let temp = TempDir::new().unwrap();
+        fs::write(
+            temp.path().join("stacy.toml"),
+            "[project]\nroot = true\nparent = \"..\"\n",
+        )
+        .unwrap();
+        let err = load_config(temp.path()).unwrap_err().to_string();
+        assert!(err.contains("can't both be set"), "{}", err);
+    }
+
+    #[test]
+    fn test_test_section() {
+        let temp = TempDir::new().unwrap();
+        fs::write(
+            temp.path().join("stacy.toml"),
+            "[test]\ndirs = [\"tests\", \"validation\"]\npattern = \"check_*.do\"\nexclude = [\"tests/fixtures\"]\n",
+        )
+        .unwrap();
+
+        let config = load_config(temp.path()).unwrap().unwrap();
+        assert_eq!(
+            config.test.dirs,
+            vec![PathBuf::from("tests"), PathBuf::from("validation")]
+        );
+        assert_eq!(config.test.pattern, vec!["check_*.do"]);
+        assert!(!config.test.is_conventional());
+        assert_eq!(
+            config.test.flaky_threshold(),
+            TestSection::DEFAULT_FLAKY_THRESHOLD
+        );
+
+        fs::write(
+            temp.path().join("stacy.toml"),
+            "[test]\nflaky_threshold = 0.8\n",
+        )
+        .unwrap();
+        let err = load_config(temp.path()).unwrap_err().to_string();
+        assert!(err.contains("flaky_threshold"), "{}", err);
+
+        // Packages in the wrong section point at [packages.test]
+        fs::write(temp.path().join("stacy.toml"), "[test]\nassert = \"ssc\"\n").unwrap();
+        let err = load_config(temp.path()).unwrap_err().to_string();
+        assert!(err.contains("[packages.test]"), "{}", err);
+
+        fs::write(
+            temp.path().join("stacy.toml"),
+            "[test]\npattern = [\"tests/check_*.do\"]\n",
+        )
+        .unwrap();
+        let err = load_config(temp.path()).unwrap_err().to_string();
+        assert!(err.contains("dirs"), "{}", err);
+    }
+
     #[test]
     fn test_task_names_are_not_unknown_keys() {
         // [scripts] is open by design — task names are user-chosen.
@@ -828,6 +1562,31 @@ analyze = { script = "src/02_analyze.do", description = "Run main analysis" }
         }
     }
 
+    #[test]
+    fn test_load_config_with_task_inputs() {
+        let temp = TempDir::new().unwrap();
+        let config_content = r#"
+[scripts]
+clean = { script = "src/01_clean.do", inputs = ["data/raw", "codebook.csv"] }
+"#;
+        fs::write(temp.path().join("stacy.toml"), config_content).unwrap();
+
+        let result = load_config(temp.path()).unwrap().unwrap();
+
+        match &result.scripts.tasks["clean"] {
+            TaskDef::Complex(complex) => {
+                assert_eq!(
+                    complex.inputs,
+                    Some(vec![
+                        PathBuf::from("data/raw"),
+                        PathBuf::from("codebook.csv")
+                    ])
+                );
+            }
+            _ => panic!("Expected Complex task"),
+        }
+    }
+
     #[test]
     fn test_load_config_with_mixed_tasks() {
         let temp = TempDir::new().unwrap();
@@ -877,11 +1636,30 @@ pipeline = ["clean", "analyze", "outputs"]
         let spec = PackageSpec::Detailed {
             source: "ssc".to_string(),
             version: Some("1.0.0".to_string()),
+            dev: false,
         };
         assert_eq!(spec.source(), "ssc");
         assert_eq!(spec.version(), Some("1.0.0"));
     }
 
+    #[test]
+    fn test_editable_spec_needs_local_source() {
+        let config: Config = toml::from_str(
+            "[packages.dev]\nmyutils = { source = \"local:../myutils\", dev = true }\n",
+        )
+        .unwrap();
+        assert!(config.packages.dev["myutils"].is_editable());
+        assert!(validate_config(&config, Path::new(".")).is_ok());
+
+        let config: Config =
+            toml::from_str("[packages.dependencies]\nestout = { source = \"ssc\", dev = true }\n")
+                .unwrap();
+        let err = validate_config(&config, Path::new("."))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("dev = true needs a local: source"), "{}", err);
+    }
+
     #[test]
     fn test_package_spec_detailed_parses_with_its_version_pin() {
         let config: Config = toml::from_str(
@@ -1122,6 +1900,66 @@ mdesc = "ssc"
         assert!(!result.packages.is_dev_package("estout"));
     }
 
+    #[test]
+    fn test_load_config_with_install_mode() {
+        let temp = TempDir::new().unwrap();
+        fs::write(temp.path().join("stacy.toml"), "[project]\n").unwrap();
+        let result = load_config(temp.path()).unwrap().unwrap();
+        assert_eq!(result.packages.install_mode, InstallMode::Global);
+        assert_eq!(result.packages.local_install_dir(), None);
+
+        fs::write(
+            temp.path().join("stacy.toml"),
+            "[packages]\ninstall_mode = \"local\"\n",
+        )
+        .unwrap();
+        let result = load_config(temp.path()).unwrap().unwrap();
+        assert_eq!(
+            result.packages.local_install_dir(),
+            Some(PathBuf::from("ado"))
+        );
+
+        fs::write(
+            temp.path().join("stacy.toml"),
+            "[packages]\ninstall_mode = \"local\"\ninstall_dir = \"vendor/ado\"\n",
+        )
+        .unwrap();
+        let result = load_config(temp.path()).unwrap().unwrap();
+        assert_eq!(
+            result.packages.local_install_dir(),
+            Some(PathBuf::from("vendor/ado"))
+        );
+    }
+
+    #[test]
+    fn test_load_config_with_submit_section() {
+        let temp = TempDir::new().unwrap();
+        fs::write(
+            temp.path().join("stacy.toml"),
+            r#"
+[submit]
+scheduler = "pbs"
+partition = "long"
+cpus = 8
+modules = ["stata/18"]
+"#,
+        )
+        .unwrap();
+        let result = load_config(temp.path()).unwrap().unwrap();
+        assert_eq!(result.submit.scheduler, Some(Scheduler::Pbs));
+        assert_eq!(result.submit.partition.as_deref(), Some("long"));
+        assert_eq!(result.submit.cpus, Some(8));
+        assert_eq!(result.submit.modules, vec!["stata/18"]);
+        assert_eq!(result.submit.time, None);
+
+        fs::write(
+            temp.path().join("stacy.toml"),
+            "[submit]\nqueue = \"long\"\n",
+        )
+        .unwrap();
+        assert!(load_config(temp.path()).is_err());
+    }
+
     #[test]
     fn test_package_spec_net_roundtrip() {
         // PackageSpec must be serialized within a map (TOML doesn't support bare strings)
@@ -1281,6 +2119,129 @@ ado = ["ado", "lib/custom"]
         assert_eq!(result.paths.ado[1], PathBuf::from("lib/custom"));
     }
 
+    #[test]
+    fn test_load_config_with_execution_section() {
+        let temp = TempDir::new().unwrap();
+        fs::write(
+            temp.path().join("stacy.toml"),
+            "[execution]\nprocessors = 4\n",
+        )
+        .unwrap();
+
+        let result = load_config(temp.path()).unwrap().unwrap();
+        assert_eq!(result.execution.processors, Some(4));
+
+        // An unset section stays out of a rewritten stacy.toml
+        let written = toml::to_string_pretty(&Config::default()).unwrap();
+        assert!(!written.contains("[execution]"));
+    }
+
+    #[test]
+    fn test_load_config_with_reproducibility_section() {
+        let temp = TempDir::new().unwrap();
+        fs::write(
+            temp.path().join("stacy.toml"),
+            "[reproducibility]\nversion = 17\nseed = 12345\nsortseed = 1\nrngstream = 3\n",
+        )
+        .unwrap();
+
+        let result = load_config(temp.path()).unwrap().unwrap();
+        assert_eq!(result.reproducibility.version.as_deref(), Some("17"));
+        assert_eq!(
+            result.reproducibility.preamble(),
+            vec![
+                "version 17",
+                "set rng mt64s",
+                "set rngstream 3",
+                "set seed 12345",
+                "set sortseed 1"
+            ]
+        );
+
+        fs::write(
+            temp.path().join("stacy.toml"),
+            "[reproducibility]\nversion = \"latest\"\n",
+        )
+        .unwrap();
+        let err = load_config(temp.path()).unwrap_err().to_string();
+        assert!(err.contains("not a Stata version"), "{}", err);
+    }
+
+    #[test]
+    fn test_load_config_with_pipeline_section() {
+        let temp = TempDir::new().unwrap();
+        fs::write(
+            temp.path().join("stacy.toml"),
+            r#"
+[pipeline.clean]
+script = "src/clean.do"
+inputs = ["data/raw.csv"]
+outputs = ["data/clean.dta"]
+
+[pipeline.report]
+script = "src/report.do"
+needs = ["clean"]
+"#,
+        )
+        .unwrap();
+
+        let result = load_config(temp.path()).unwrap().unwrap();
+        assert_eq!(result.pipeline.len(), 2);
+        assert_eq!(
+            result.pipeline["clean"].outputs,
+            vec![PathBuf::from("data/clean.dta")]
+        );
+        assert_eq!(result.pipeline["report"].needs, vec!["clean"]);
+
+        fs::write(
+            temp.path().join("stacy.toml"),
+            "[pipeline.clean]\nscript = \"src/clean.do\"\noutput = [\"x.dta\"]\n",
+        )
+        .unwrap();
+        assert!(load_config(temp.path()).is_err());
+    }
+
+    #[test]
+    fn test_load_config_with_data_section() {
+        let temp = TempDir::new().unwrap();
+        fs::write(
+            temp.path().join("stacy.toml"),
+            r#"
+[run]
+verify_data = true
+
+[data.survey]
+url = "https://example.org/survey.csv"
+path = "data/raw/survey.csv"
+sha256 = "abc123"
+
+[data]
+replication = { doi = "10.7910/DVN/ABCDEF", path = "data/replication", version = "2.0" }
+"#,
+        )
+        .unwrap();
+
+        let result = load_config(temp.path()).unwrap().unwrap();
+        assert!(result.run.verify_data);
+        assert_eq!(
+            result.data["survey"].path,
+            PathBuf::from("data/raw/survey.csv")
+        );
+        assert_eq!(result.data["survey"].sha256.as_deref(), Some("abc123"));
+        assert_eq!(
+            result.data["replication"].doi.as_deref(),
+            Some("10.7910/DVN/ABCDEF")
+        );
+        assert_eq!(result.data["replication"].version.as_deref(), Some("2.0"));
+
+        fs::write(
+            temp.path().join("stacy.toml"),
+            "[data.survey]\nurl = \"https://example.org/survey.csv\"\npath = \"data/raw/survey.csv\"\nchecksum = \"abc\"\n",
+        )
+        .unwrap();
+        assert!(load_config(temp.path()).is_err());
+    }
+
     #[test]
     fn test_load_config_with_empty_paths() {
         let temp = TempDir::new().unwrap();
@@ -0,0 +1,600 @@
+//! Migration of stacy.toml and stacy.lock from older formats
+//!
+//! Files written for an older stacy are either rejected (a stacy.toml layout
+//! that moved) or loaded with gaps filled in silently (a lockfile from before
+//! a field existed). `stacy migrate` rewrites them in the current format
+//! instead, keeping a backup of each file it changes:
+//!
+//! | File | Older format | Now |
+//! |------|--------------|-----|
+//! | stacy.toml | `[dependencies]` and `[dev]` at the top level | `[packages.dependencies]`, `[packages.dev]` |
+//! | stacy.toml | test packages in `[test]` | `[packages.test]`; `[test]` sets where tests are found |
+//! | stacy.lock | no `version` | `version = "1"` |
+//! | stacy.lock | no `stacy_version` | the stacy that wrote it |
+//! | stacy.lock | entries without `group` | `group = "production"` |
+//! | stacy.lock | bare or uppercase checksums | lowercase `sha256:<hex>` |
+//! | stacy.lock | keys stacy no longer reads | dropped |
+//!
+//! stacy.toml is rewritten line by line, so comments and formatting survive;
+//! the result must load, or nothing is written. Profile lockfiles
+//! (`stacy.<name>.lock`) are migrated like stacy.lock. A lockfile in a newer
+//! format than this stacy knows is left alone.
+
+use super::validate::{describe, parse};
+use crate::error::{Error, Result};
+use crate::packages::lockfile::{
+    check_format_version, normalize_checksum, render_lockfile, LOCKFILE_VERSION,
+};
+use crate::project::Lockfile;
+use crate::utils::lock::{project_lock_path, with_state_lock};
+use crate::utils::paths::write_atomically;
+use std::path::{Path, PathBuf};
+
+/// Top-level tables of the older stacy.toml layout, and where they are now
+const MOVED_TABLES: [(&str, &str); 2] = [
+    ("dependencies", "packages.dependencies"),
+    ("dev", "packages.dev"),
+];
+
+/// Keys of `[test]`; any other entry in it is a test package of the older
+/// layout
+const TEST_KEYS: [&str; 4] = ["dirs", "pattern", "exclude", "flaky_threshold"];
+
+/// Keys of a stacy.lock, and of its package entries
+const LOCKFILE_KEYS: [&str; 3] = ["version", "stacy_version", "packages"];
+const ENTRY_KEYS: [&str; 4] = ["version", "source", "checksum", "group"];
+
+/// A file to rewrite in the current format
+#[derive(Debug, Clone)]
+pub struct FileMigration {
+    /// File name, relative to the project root
+    pub file: PathBuf,
+    /// What changes, one line each
+    pub changes: Vec<String>,
+    content: String,
+}
+
+/// The files of the project at `root` in an older format, and what migrating
+/// them changes. Nothing is written.
+pub fn plan(root: &Path) -> Result<Vec<FileMigration>> {
+    let mut migrations = Vec::new();
+
+    let config = root.join("stacy.toml");
+    if config.is_file() {
+        let content = std::fs::read_to_string(&config)?;
+        if let Some((content, changes)) = migrate_config(&content)? {
+            migrations.push(FileMigration {
+                file: PathBuf::from("stacy.toml"),
+                changes,
+                content,
+            });
+        }
+    }
+
+    for file in lockfiles(root)? {
+        let content = std::fs::read_to_string(root.join(&file))?;
+        let (content, changes) = migrate_lockfile(&content)
+            .map_err(|e| Error::Config(format!("{}: {}", file.display(), message(e))))?;
+        if !changes.is_empty() {
+            migrations.push(FileMigration {
+                file,
+                changes,
+                content,
+            });
+        }
+    }
+
+    Ok(migrations)
+}
+
+/// Write the migrated files, each after copying it to a backup. Returns the
+/// backups, relative to the project root.
+pub fn apply(root: &Path, migrations: &[FileMigration]) -> Result<Vec<PathBuf>> {
+    with_state_lock(&project_lock_path(root), || -> Result<Vec<PathBuf>> {
+        let mut backups = Vec::new();
+        for migration in migrations {
+            let path = root.join(&migration.file);
+            let backup = backup_path(&path);
+            std::fs::copy(&path, &backup)?;
+            write_atomically(&path, migration.content.as_bytes())?;
+            backups.push(backup.strip_prefix(root).unwrap_or(&backup).to_path_buf());
+        }
+        Ok(backups)
+    })?
+}
+
+/// `stacy.lock` and the lockfiles of profiles (`stacy.<name>.lock`)
+fn lockfiles(root: &Path) -> Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(root)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| PathBuf::from(entry.file_name()))
+        .filter(|name| {
+            let name = name.to_string_lossy();
+            name.starts_with("stacy.") && name.ends_with(".lock")
+        })
+        .filter(|name| root.join(name).is_file())
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// `stacy.toml.bak`, or the first of `stacy.toml.bak.2`, `.3`, ... not taken
+fn backup_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let first = path.with_file_name(format!("{}.bak", name));
+    if !first.exists() {
+        return first;
+    }
+    (2..)
+        .map(|n| path.with_file_name(format!("{}.bak.{}", name, n)))
+        .find(|candidate| !candidate.exists())
+        .unwrap_or(first)
+}
+
+fn message(err: Error) -> String {
+    match err {
+        Error::Config(message) => message,
+        other => other.to_string(),
+    }
+}
+
+// =============================================================================
+// stacy.toml
+// =============================================================================
+
+/// A table of stacy.toml: its header line and the lines up to the next one
+struct Block {
+    /// Dotted name of the table; empty for the lines before the first header
+    path: Vec<String>,
+    header: Option<String>,
+    lines: Vec<String>,
+}
+
+impl Block {
+    fn rename(&mut self, path: Vec<String>) {
+        self.header = Some(format!("[{}]", path.join(".")));
+        self.path = path;
+    }
+}
+
+/// `content` in the current layout and what changed, or `None` when it
+/// needs no migration
+fn migrate_config(content: &str) -> Result<Option<(String, Vec<String>)>> {
+    if parse(content).is_ok() {
+        return Ok(None);
+    }
+
+    let mut blocks = blocks(content);
+    let mut changes = Vec::new();
+
+    for (old, new) in MOVED_TABLES {
+        let mut moved = false;
+        for block in blocks
+            .iter_mut()
+            .filter(|b| b.path.first().map(String::as_str) == Some(old))
+        {
+            let path = new
+                .split('.')
+                .map(String::from)
+                .chain(block.path[1..].iter().cloned())
+                .collect();
+            block.rename(path);
+            moved = true;
+        }
+        if moved {
+            changes.push(format!("moved [{}] to [{}]", old, new));
+        }
+    }
+
+    let mut test_packages = Vec::new();
+    let mut moved_lines = Vec::new();
+    for block in &mut blocks {
+        match block.path.as_slice() {
+            [test] if test == "test" => {
+                let (packages, settings): (Vec<String>, Vec<String>) =
+                    block.lines.drain(..).partition(|line| {
+                        entry_key(line).is_some_and(|key| !TEST_KEYS.contains(&key.as_str()))
+                    });
+                block.lines = settings;
+                test_packages.extend(packages.iter().filter_map(|line| entry_key(line)));
+                moved_lines.extend(packages);
+            }
+            [test, name, ..] if test == "test" && !TEST_KEYS.contains(&name.as_str()) => {
+                test_packages.push(name.clone());
+                let path = ["packages", "test"]
+                    .into_iter()
+                    .map(String::from)
+                    .chain(block.path[1..].iter().cloned())
+                    .collect();
+                block.rename(path);
+            }
+            _ => {}
+        }
+    }
+    if !moved_lines.is_empty() {
+        let target = ["packages".to_string(), "test".to_string()];
+        match blocks.iter_mut().find(|block| block.path == target) {
+            Some(block) => block.lines.extend(moved_lines),
+            None => {
+                if let Some(last) = blocks.last_mut() {
+                    if last
+                        .lines
+                        .last()
+                        .is_some_and(|line| !line.trim().is_empty())
+                    {
+                        last.lines.push(String::new());
+                    }
+                }
+                let mut block = Block {
+                    path: Vec::new(),
+                    header: None,
+                    lines: moved_lines,
+                };
+                block.rename(target.to_vec());
+                blocks.push(block);
+            }
+        }
+    }
+    if !test_packages.is_empty() {
+        test_packages.dedup();
+        changes.push(format!(
+            "moved test packages {} from [test] to [packages.test]",
+            test_packages.join(", ")
+        ));
+    }
+
+    if changes.is_empty() {
+        return Err(Error::Config(format!(
+            "{}\n\nThese are not an older layout stacy migrate can update: fix them by hand \
+             (stacy config validate lists them)",
+            describe(&parse(content).err().unwrap_or_default())
+        )));
+    }
+
+    let mut migrated = render(merge_duplicates(blocks));
+    if content.ends_with('\n') && !migrated.ends_with('\n') {
+        migrated.push('\n');
+    }
+    if let Err(diagnostics) = parse(&migrated) {
+        return Err(Error::Config(format!(
+            "stacy.toml still has errors once moved to the current layout:\n\n{}\n\n\
+             Fix them by hand, then run stacy migrate again",
+            describe(&diagnostics)
+        )));
+    }
+    Ok(Some((migrated, changes)))
+}
+
+fn blocks(content: &str) -> Vec<Block> {
+    let mut blocks = vec![Block {
+        path: Vec::new(),
+        header: None,
+        lines: Vec::new(),
+    }];
+    for line in content.lines() {
+        match header_path(line) {
+            Some(path) => blocks.push(Block {
+                path,
+                header: Some(line.to_string()),
+                lines: Vec::new(),
+            }),
+            None => blocks
+                .last_mut()
+                .expect("at least one block")
+                .lines
+                .push(line.to_string()),
+        }
+    }
+    blocks
+}
+
+/// The dotted name of a `[table]` header line
+fn header_path(line: &str) -> Option<Vec<String>> {
+    let line = line.trim();
+    if line.starts_with("[[") {
+        return None;
+    }
+    let (name, rest) = line.strip_prefix('[')?.split_once(']')?;
+    let rest = rest.trim();
+    if !(rest.is_empty() || rest.starts_with('#')) {
+        return None;
+    }
+    Some(
+        name.split('.')
+            .map(|part| part.trim().to_string())
+            .collect(),
+    )
+}
+
+/// The key of a `key = value` line
+fn entry_key(line: &str) -> Option<String> {
+    let line = line.trim();
+    if line.starts_with('#') {
+        return None;
+    }
+    let (key, _) = line.split_once('=')?;
+    let key = key.trim().trim_matches('"');
+    (!key.is_empty()).then(|| key.to_string())
+}
+
+/// Tables declared twice once moved (`[dependencies]` next to an existing
+/// `[packages.dependencies]`): the later one's entries join the first
+fn merge_duplicates(blocks: Vec<Block>) -> Vec<Block> {
+    let mut merged: Vec<Block> = Vec::new();
+    for block in blocks {
+        let existing = merged
+            .iter_mut()
+            .find(|b| b.header.is_some() && b.path == block.path);
+        match existing {
+            Some(first) => {
+                let trailing = first
+                    .lines
+                    .iter()
+                    .rev()
+                    .take_while(|line| line.trim().is_empty())
+                    .count();
+                let at = first.lines.len() - trailing;
+                let entries: Vec<String> = block
+                    .lines
+                    .into_iter()
+                    .filter(|line| !line.trim().is_empty())
+                    .collect();
+                first.lines.splice(at..at, entries);
+            }
+            None => merged.push(block),
+        }
+    }
+    merged
+}
+
+fn render(blocks: Vec<Block>) -> String {
+    let mut lines = Vec::new();
+    for block in blocks {
+        lines.extend(block.header);
+        lines.extend(block.lines);
+    }
+    lines.join("\n")
+}
+
+// =============================================================================
+// stacy.lock
+// =============================================================================
+
+/// `content` in the current lockfile format and what changed (nothing when
+/// it already is)
+fn migrate_lockfile(content: &str) -> Result<(String, Vec<String>)> {
+    if crate::packages::lockfile::has_conflict_markers(content) {
+        return Err(Error::Config(
+            "unresolved merge conflicts: run `stacy lock --resolve-conflicts` first".to_string(),
+        ));
+    }
+    let mut raw: toml::Table = toml::from_str(content)
+        .map_err(|e| Error::Config(format!("not a readable lockfile: {}", e)))?;
+    let mut changes = Vec::new();
+
+    match raw.get("version") {
+        None => {
+            raw.insert(
+                "version".to_string(),
+                toml::Value::String(LOCKFILE_VERSION.to_string()),
+            );
+            changes.push(format!(
+                "recorded the format version ({})",
+                LOCKFILE_VERSION
+            ));
+        }
+        Some(toml::Value::Integer(version)) => {
+            let version = version.to_string();
+            check_format_version(&version)?;
+            raw.insert("version".to_string(), toml::Value::String(version));
+            changes.push("wrote the format version as a string".to_string());
+        }
+        Some(version) => check_format_version(version.as_str().unwrap_or_default())?,
+    }
+    if !raw.contains_key("stacy_version") {
+        changes.push("recorded the stacy version that wrote it".to_string());
+    }
+    let unused: Vec<String> = raw
+        .keys()
+        .filter(|key| !LOCKFILE_KEYS.contains(&key.as_str()))
+        .cloned()
+        .collect();
+    for key in &unused {
+        raw.remove(key);
+    }
+
+    let mut without_group = 0;
+    let mut checksums = 0;
+    let mut unused_entry_keys: Vec<String> = Vec::new();
+    if let Some(packages) = raw.get_mut("packages").and_then(|p| p.as_table_mut()) {
+        for entry in packages.iter_mut().filter_map(|(_, e)| e.as_table_mut()) {
+            if !entry.contains_key("group") {
+                without_group += 1;
+            }
+            if let Some(checksum) = entry.get("checksum").and_then(|c| c.as_str()) {
+                if normalize_checksum(checksum) != checksum {
+                    checksums += 1;
+                }
+            }
+            let keys: Vec<String> = entry
+                .keys()
+                .filter(|key| !ENTRY_KEYS.contains(&key.as_str()))
+                .cloned()
+                .collect();
+            for key in keys {
+                entry.remove(&key);
+                if !unused_entry_keys.contains(&key) {
+                    unused_entry_keys.push(key);
+                }
+            }
+        }
+    } else {
+        raw.insert(
+            "packages".to_string(),
+            toml::Value::Table(toml::Table::new()),
+        );
+    }
+
+    if without_group > 0 {
+        changes.push(format!(
+            "recorded the group of {} package(s) as production",
+            without_group
+        ));
+    }
+    if checksums > 0 {
+        changes.push(format!(
+            "wrote {} checksum(s) as lowercase sha256:<hex>",
+            checksums
+        ));
+    }
+    let dropped: Vec<String> = unused
+        .iter()
+        .map(|key| format!("`{}`", key))
+        .chain(
+            unused_entry_keys
+                .iter()
+                .map(|key| format!("`{}` of packages", key)),
+        )
+        .collect();
+    if !dropped.is_empty() {
+        changes.push(format!(
+            "dropped {}, which stacy doesn't read",
+            dropped.join(", ")
+        ));
+    }
+
+    let mut lockfile: Lockfile = toml::Value::Table(raw)
+        .try_into()
+        .map_err(|e| Error::Config(format!("not a readable lockfile: {}", e)))?;
+    lockfile.stacy_version = Some(env!("CARGO_PKG_VERSION").to_string());
+    Ok((render_lockfile(&lockfile)?, changes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_migrate_config_moves_old_tables() {
+        let content = "\
+# Analysis of the 2024 survey
+[project]
+name = \"survey\"
+
+# Packages every script needs
+[dependencies]
+estout = \"ssc\"   # tables
+
+[packages.dependencies]
+ftools = \"ssc\"
+
+[dev]
+assert_all = \"ssc\"
+
+[test]
+dirs = [\"tests\"]
+testit = \"github:user/testit\"
+";
+        let (migrated, changes) = migrate_config(content).unwrap().unwrap();
+        assert_eq!(
+            changes,
+            [
+                "moved [dependencies] to [packages.dependencies]",
+                "moved [dev] to [packages.dev]",
+                "moved test packages testit from [test] to [packages.test]",
+            ]
+        );
+        assert_eq!(
+            migrated,
+            "\
+# Analysis of the 2024 survey
+[project]
+name = \"survey\"
+
+# Packages every script needs
+[packages.dependencies]
+estout = \"ssc\"   # tables
+ftools = \"ssc\"
+
+[packages.dev]
+assert_all = \"ssc\"
+
+[test]
+dirs = [\"tests\"]
+
+[packages.test]
+testit = \"github:user/testit\"
+"
+        );
+        let config = parse(&migrated).unwrap();
+        assert_eq!(config.packages.dependencies.len(), 2);
+
+        assert!(migrate_config(&migrated).unwrap().is_none());
+        assert!(migrate_config("[project]\nnmae = \"x\"\n").is_err());
+    }
+
+    #[test]
+    fn test_migrate_lockfile() {
+        let content = "\
+generated_at = \"2024-01-15T10:00:00Z\"
+
+[packages.estout]
+version = \"20240115\"
+checksum = \"ABC123\"
+installed_at = \"2024-01-15T10:00:00Z\"
+
+[packages.estout.source]
+type = \"SSC\"
+name = \"estout\"
+";
+        let (migrated, changes) = migrate_lockfile(content).unwrap();
+        assert_eq!(
+            changes,
+            [
+                "recorded the format version (1)",
+                "recorded the stacy version that wrote it",
+                "recorded the group of 1 package(s) as production",
+                "wrote 1 checksum(s) as lowercase sha256:<hex>",
+                "dropped `generated_at`, `installed_at` of packages, which stacy doesn't read",
+            ]
+        );
+        let lockfile: Lockfile = toml::from_str(&migrated).unwrap();
+        assert_eq!(lockfile.version, "1");
+        let entry = &lockfile.packages["estout"];
+        assert_eq!(entry.group, "production");
+        assert_eq!(entry.checksum.as_deref(), Some("sha256:abc123"));
+
+        let (again, changes) = migrate_lockfile(&migrated).unwrap();
+        assert!(changes.is_empty());
+        assert_eq!(again, migrated);
+
+        assert!(migrate_lockfile("version = \"2\"\n[packages]\n").is_err());
+    }
+
+    #[test]
+    fn test_apply_keeps_backups() {
+        let temp = TempDir::new().unwrap();
+        fs::write(
+            temp.path().join("stacy.toml"),
+            "[dependencies]\nestout = \"ssc\"\n",
+        )
+        .unwrap();
+        fs::write(temp.path().join("stacy.toml.bak"), "older backup").unwrap();
+
+        let migrations = plan(temp.path()).unwrap();
+        assert_eq!(migrations.len(), 1);
+        let backups = apply(temp.path(), &migrations).unwrap();
+        assert_eq!(backups, [PathBuf::from("stacy.toml.bak.2")]);
+        assert_eq!(
+            fs::read_to_string(temp.path().join("stacy.toml.bak.2")).unwrap(),
+            "[dependencies]\nestout = \"ssc\"\n"
+        );
+        assert_eq!(
+            fs::read_to_string(temp.path().join("stacy.toml")).unwrap(),
+            "[packages.dependencies]\nestout = \"ssc\"\n"
+        );
+        assert!(plan(temp.path()).unwrap().is_empty());
+    }
+}
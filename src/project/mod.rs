@@ -1,12 +1,17 @@
 pub mod config;
+pub mod migrate;
+pub mod overrides;
+pub mod profile;
 pub mod root;
 pub mod structure;
 pub mod user_config;
+pub mod validate;
 
 // Re-export main types
 pub use config::Config;
 
 use crate::error::Result;
+use config::ReproducibilitySection;
 use std::path::PathBuf;
 
 /// Represents a stacy project with its root directory, configuration, and lockfile.
@@ -28,7 +33,7 @@ pub struct Lockfile {
     pub packages: std::collections::HashMap<String, PackageEntry>,
 }
 
-#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
 pub struct PackageEntry {
     pub version: String,
     pub source: PackageSource,
@@ -42,7 +47,15 @@ fn default_group() -> String {
     "production".to_string()
 }
 
-#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+impl PackageEntry {
+    /// Whether the package is an editable local directory, on the adopath in
+    /// place of a cached copy
+    pub fn is_editable(&self) -> bool {
+        matches!(self.source, PackageSource::Local { editable: true, .. })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
 #[serde(tag = "type")]
 pub enum PackageSource {
     SSC {
@@ -54,9 +67,18 @@ pub enum PackageSource {
         #[serde(skip_serializing_if = "Option::is_none")]
         #[serde(default)]
         commit: Option<String>,
+        /// Repository directory holding the `.pkg` manifest, when not the root
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pkg_dir: Option<String>,
+        /// The repository has no `.pkg`; its files were picked by name
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        synthesized: bool,
     },
     Local {
         path: String,
+        /// Linked from `path` rather than copied into the cache (`dev = true`)
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        editable: bool,
     },
     Net {
         url: String,
@@ -156,12 +178,36 @@ impl Project {
         &self.root
     }
 
-    /// Resolve `[paths].ado` entries to absolute paths relative to project root.
+    /// Resolve `[paths].ado` entries to absolute paths relative to project root,
+    /// followed by the directories of editable packages (`dev = true`).
     pub fn resolve_local_ado_paths(&self) -> Vec<PathBuf> {
-        match &self.config {
-            Some(c) => c.paths.ado.iter().map(|rel| self.root.join(rel)).collect(),
-            None => Vec::new(),
-        }
+        let Some(c) = &self.config else {
+            return Vec::new();
+        };
+        let editable = c
+            .packages
+            .all_packages()
+            .filter(|(_, spec, _)| spec.is_editable())
+            .map(|(_, spec, _)| &spec.source()["local:".len()..]);
+        c.paths
+            .ado
+            .iter()
+            .map(|rel| self.root.join(rel))
+            .chain(editable.map(|dir| self.root.join(dir)))
+            .collect()
+    }
+
+    /// `[execution] processors` from stacy.toml, if set
+    pub fn processors(&self) -> Option<u32> {
+        self.config.as_ref().and_then(|c| c.execution.processors)
+    }
+
+    /// `[reproducibility]` from stacy.toml (empty without a config)
+    pub fn reproducibility(&self) -> ReproducibilitySection {
+        self.config
+            .as_ref()
+            .map(|c| c.reproducibility.clone())
+            .unwrap_or_default()
     }
 
     /// Check if the project has a configuration file
@@ -185,6 +231,8 @@ mod tests {
             repo: "sergiocorreia/reghdfe".to_string(),
             tag: "main".to_string(),
             commit: Some("abc123def456abc123def456abc123def456abc1".to_string()),
+            pkg_dir: None,
+            synthesized: false,
         };
 
         let toml_str = toml::to_string(&source).unwrap();
@@ -245,6 +293,8 @@ mod tests {
             repo: "user/repo".to_string(),
             tag: "v1.0".to_string(),
             commit: None,
+            pkg_dir: None,
+            synthesized: false,
         };
 
         let toml_str = toml::to_string(&source).unwrap();
@@ -0,0 +1,189 @@
+//! Dependency overrides: another source for a declared package
+//!
+//! `[overrides]` in stacy.toml maps a package name to a source that replaces
+//! the declared one wherever the package is declared, e.g. a fork carrying a
+//! fix that is not on SSC yet:
+//!
+//! ```toml
+//! [packages.dependencies]
+//! reghdfe = "ssc"
+//!
+//! [overrides]
+//! reghdfe = "github:me/reghdfe@fix-absorb"
+//! ```
+//!
+//! The declaration itself stays as it is, so dropping the override is all it
+//! takes to go back. `stacy lock` re-resolves a package whose locked source no
+//! longer matches, and `stacy list` marks overridden packages.
+
+use super::config::{Config, PackageSpec};
+use super::PackageSource;
+use crate::error::{Error, Result};
+
+/// Replace the source of every overridden package the config declares
+pub fn apply(config: &mut Config) {
+    for (name, source) in &config.overrides {
+        let packages = &mut config.packages;
+        for group in [
+            &mut packages.dependencies,
+            &mut packages.dev,
+            &mut packages.test,
+        ] {
+            if let Some(spec) = group.get_mut(name) {
+                *spec = PackageSpec::simple(source.clone());
+            }
+        }
+    }
+}
+
+/// Every override names a package declared in `[packages]` or a profile, and
+/// a source `stacy lock` understands
+pub fn validate(config: &Config) -> Result<()> {
+    for (name, source) in &config.overrides {
+        if !is_declared(config, name) {
+            return Err(Error::Config(format!(
+                "[overrides] {}: no package '{}' is declared in stacy.toml",
+                name, name
+            )));
+        }
+        if !valid_source(source) {
+            return Err(Error::Config(format!(
+                "[overrides] {}: unknown source '{}'. Use 'ssc', 'github:user/repo[@ref]', 'net:URL', or 'local:path'",
+                name, source
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Whether stacy.toml declares package `name`, in `[packages]` or a profile
+pub fn is_declared(config: &Config, name: &str) -> bool {
+    config.packages.has_package(name)
+        || config
+            .profiles
+            .values()
+            .any(|p| p.packages.contains_key(name))
+}
+
+fn valid_source(source: &str) -> bool {
+    if source == "ssc" {
+        return true;
+    }
+    if let Some(rest) = source.strip_prefix("github:") {
+        let repo = rest.split('@').next().unwrap_or_default();
+        return repo
+            .split_once('/')
+            .is_some_and(|(user, repo)| !user.is_empty() && !repo.is_empty());
+    }
+    ["net:", "local:"]
+        .iter()
+        .any(|prefix| source.strip_prefix(prefix).is_some_and(|s| !s.is_empty()))
+}
+
+/// Whether `locked` is the source stacy.toml's `spec` names. A GitHub source
+/// without `@ref` means `main`.
+pub fn source_matches(spec: &str, locked: &PackageSource) -> bool {
+    match locked {
+        PackageSource::SSC { .. } => spec.eq_ignore_ascii_case("ssc"),
+        PackageSource::GitHub { repo, tag, .. } => {
+            let Some(rest) = strip_scheme(spec, "github:") else {
+                return false;
+            };
+            let (spec_repo, spec_tag) = rest.split_once('@').unwrap_or((rest, "main"));
+            spec_repo == repo && spec_tag == tag
+        }
+        PackageSource::Local { path, .. } => strip_scheme(spec, "local:") == Some(path.as_str()),
+        PackageSource::Net { url } => strip_scheme(spec, "net:") == Some(url.as_str()),
+    }
+}
+
+/// `spec` without `scheme`, which `stacy add` takes in any case
+fn strip_scheme<'a>(spec: &'a str, scheme: &str) -> Option<&'a str> {
+    spec.get(..scheme.len())
+        .filter(|s| s.eq_ignore_ascii_case(scheme))
+        .map(|_| &spec[scheme.len()..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(toml: &str) -> Config {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn test_apply_replaces_declared_source() {
+        let mut config = config(
+            r#"
+[packages.dependencies]
+reghdfe = "ssc"
+
+[packages.dev]
+fre = { source = "ssc", version = "2020" }
+
+[overrides]
+reghdfe = "github:me/reghdfe@fix"
+fre = "local:../fre"
+"#,
+        );
+        validate(&config).unwrap();
+        apply(&mut config);
+        assert_eq!(
+            config.packages.dependencies["reghdfe"].source(),
+            "github:me/reghdfe@fix"
+        );
+        assert_eq!(config.packages.dev["fre"].source(), "local:../fre");
+        assert_eq!(config.packages.dev["fre"].version(), None);
+    }
+
+    #[test]
+    fn test_validate_overrides() {
+        let undeclared = config("[overrides]\nestout = \"ssc\"\n");
+        let err = validate(&undeclared).unwrap_err().to_string();
+        assert!(err.contains("no package 'estout'"), "{}", err);
+
+        // A profile's own package can be overridden too
+        let profile = config(
+            "[profiles.ci.packages]\nestout = \"ssc\"\n\n[overrides]\nestout = \"local:x\"\n",
+        );
+        assert!(validate(&profile).is_ok());
+
+        for bad in ["github:reghdfe", "local:", "cran", "github:/r"] {
+            let bad = config(&format!(
+                "[packages.dependencies]\nestout = \"ssc\"\n\n[overrides]\nestout = \"{}\"\n",
+                bad
+            ));
+            assert!(validate(&bad).is_err());
+        }
+    }
+
+    #[test]
+    fn test_source_matches() {
+        let github = PackageSource::GitHub {
+            repo: "me/reghdfe".to_string(),
+            tag: "main".to_string(),
+            commit: Some("abc".to_string()),
+            pkg_dir: None,
+            synthesized: false,
+        };
+        assert!(source_matches("github:me/reghdfe", &github));
+        assert!(source_matches("github:me/reghdfe@main", &github));
+        assert!(!source_matches("github:me/reghdfe@fix", &github));
+        assert!(!source_matches("ssc", &github));
+
+        let ssc = PackageSource::SSC {
+            name: "reghdfe".to_string(),
+        };
+        assert!(source_matches("ssc", &ssc));
+        assert!(source_matches("SSC", &ssc));
+        assert!(!source_matches("local:./lib/reghdfe", &ssc));
+
+        let local = PackageSource::Local {
+            path: "./lib/reghdfe".to_string(),
+            editable: false,
+        };
+        assert!(source_matches("local:./lib/reghdfe", &local));
+        assert!(!source_matches("local:../reghdfe", &local));
+    }
+}
@@ -0,0 +1,207 @@
+//! Environment profiles: named package sets with lockfiles of their own
+//!
+//! A profile under `[profiles.<name>]` in stacy.toml takes some of the
+//! dependency groups of `[packages]` and adds packages of its own. The global
+//! `--env-profile <name>` (or `STACY_PROFILE`) makes every command use that
+//! set, locked in `stacy.<name>.lock` instead of `stacy.lock`, so a published
+//! replication environment stays frozen while day-to-day dependencies move on.
+
+use super::config::{Config, DependencyGroup, PackageSpec, PackagesSection};
+use crate::error::{Error, Result};
+use std::sync::OnceLock;
+
+/// Lockfile without a profile
+pub const DEFAULT_LOCKFILE: &str = "stacy.lock";
+
+/// Group names a profile can take, as in `[packages.<group>]`
+pub const GROUPS: &[&str] = &["dependencies", "dev", "test"];
+
+/// Profile given with `--env-profile` or `STACY_PROFILE`
+static ACTIVE: OnceLock<String> = OnceLock::new();
+
+/// Use profile `name` for the rest of this process. Called once, after
+/// parsing the command line.
+pub fn set_active(name: &str) -> Result<()> {
+    validate_name(name)?;
+    let _ = ACTIVE.set(name.to_string());
+    Ok(())
+}
+
+/// The profile in use, if any
+pub fn active() -> Option<&'static str> {
+    ACTIVE.get().map(String::as_str)
+}
+
+/// Lockfile of the profile in use, relative to the project root
+pub fn lockfile_name() -> String {
+    match active() {
+        Some(name) => format!("stacy.{}.lock", name),
+        None => DEFAULT_LOCKFILE.to_string(),
+    }
+}
+
+/// Profile names end up in file names: letters, digits, `-` and `_`
+pub fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(Error::Config(format!(
+            "Invalid profile name '{}': use letters, digits, '-' and '_'",
+            name
+        )));
+    }
+    Ok(())
+}
+
+/// Replace the config's packages with those of the profile in use
+pub fn apply(config: &mut Config) -> Result<()> {
+    if let Some(name) = active() {
+        config.packages = packages_for(config, name)?;
+    }
+    Ok(())
+}
+
+/// The packages of profile `name`: the groups it takes, then its own
+/// packages, which win over a group's entry of the same name
+pub fn packages_for(config: &Config, name: &str) -> Result<PackagesSection> {
+    let profile = config.profiles.get(name).ok_or_else(|| {
+        let names: Vec<&str> = config.profiles.keys().map(String::as_str).collect();
+        Error::Config(format!(
+            "Unknown profile '{}'. Profiles in stacy.toml: {}",
+            name,
+            if names.is_empty() {
+                "none".to_string()
+            } else {
+                names.join(", ")
+            }
+        ))
+    })?;
+
+    let takes = |group: &str| {
+        profile
+            .groups
+            .as_ref()
+            .is_none_or(|g| g.iter().any(|g| g == group))
+    };
+    let all = &config.packages;
+    let mut packages = PackagesSection {
+        on_collision: all.on_collision,
+        install_mode: all.install_mode,
+        install_dir: all.install_dir.clone(),
+        ..Default::default()
+    };
+    if takes("dependencies") {
+        packages.dependencies = all.dependencies.clone();
+    }
+    if takes("dev") {
+        packages.dev = all.dev.clone();
+    }
+    if takes("test") {
+        packages.test = all.test.clone();
+    }
+    for (package, spec) in &profile.packages {
+        packages.remove_dependency(package);
+        packages.dependencies.insert(package.clone(), spec.clone());
+    }
+    Ok(packages)
+}
+
+/// The packages of the profile in use, or all of `[packages]`
+pub fn active_packages(config: &Config) -> Result<PackagesSection> {
+    match active() {
+        Some(name) => packages_for(config, name),
+        None => Ok(config.packages.clone()),
+    }
+}
+
+/// Record a package `stacy add` installed: among the profile's own packages
+/// under `--env-profile`, else in `group` of `[packages]`
+pub fn add_package(config: &mut Config, name: String, spec: PackageSpec, group: DependencyGroup) {
+    match active() {
+        Some(profile) => {
+            config
+                .profiles
+                .entry(profile.to_string())
+                .or_default()
+                .packages
+                .insert(name, spec);
+        }
+        None => config.packages.add_dependency(name, spec, group),
+    }
+}
+
+/// Take out a package for `stacy remove`: from the profile's own packages
+/// under `--env-profile` (one it takes from a group stays), else from `[packages]`
+pub fn remove_package(config: &mut Config, name: &str) -> Option<PackageSpec> {
+    match active() {
+        Some(profile) => config
+            .profiles
+            .get_mut(profile)
+            .and_then(|p| p.packages.remove(name)),
+        None => config.packages.remove_dependency(name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(toml: &str) -> Config {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn test_packages_for_profile() {
+        let config = config(
+            r#"
+[packages.dependencies]
+estout = "ssc"
+reghdfe = "ssc"
+
+[packages.dev]
+fre = "ssc"
+
+[profiles.replication]
+groups = ["dependencies"]
+
+[profiles.replication.packages]
+reghdfe = "github:sergiocorreia/reghdfe@6.12.3"
+ftools = "ssc"
+"#,
+        );
+        let packages = packages_for(&config, "replication").unwrap();
+        let names: Vec<&String> = packages.dependencies.keys().collect();
+        assert_eq!(names, ["estout", "ftools", "reghdfe"]);
+        assert_eq!(
+            packages.dependencies["reghdfe"].source(),
+            "github:sergiocorreia/reghdfe@6.12.3"
+        );
+        assert!(packages.dev.is_empty());
+
+        let err = packages_for(&config, "dev").unwrap_err().to_string();
+        assert!(
+            err.contains("Profiles in stacy.toml: replication"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_profile_takes_all_groups_by_default() {
+        let config =
+            config("[packages.dev]\nfre = \"ssc\"\n\n[profiles.ci.packages]\nestout = \"ssc\"\n");
+        let packages = packages_for(&config, "ci").unwrap();
+        assert!(packages.dev.contains_key("fre"));
+        assert!(packages.dependencies.contains_key("estout"));
+    }
+
+    #[test]
+    fn test_validate_name() {
+        assert!(validate_name("replication-2024_v1").is_ok());
+        for bad in ["", "../x", "a.b", "a b"] {
+            assert!(validate_name(bad).is_err(), "{}", bad);
+        }
+    }
+}
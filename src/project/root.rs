@@ -3,9 +3,19 @@
 //! Walks up the directory tree looking for project markers:
 //! 1. `stacy.toml` (explicit config - highest precedence)
 //! 2. `stacy.lock` (lockfile marker)
+//!
+//! The global `--project <path>` names the root instead, so one process can
+//! drive many projects without changing directories.
+//!
+//! When projects nest, the nearest marker wins unless its stacy.toml says
+//! otherwise: `[project] parent = "../.."` hands the directory to the
+//! project above. `--nested-projects outermost` keeps walking up instead,
+//! stopping at a stacy.toml with `[project] root = true`.
 
-use crate::error::Result;
+use crate::error::{Error, Result};
+use crate::utils::paths;
 use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 
 /// Markers that indicate a project root, in precedence order.
 /// The first marker found determines the project root.
@@ -14,6 +24,22 @@ use std::path::{Path, PathBuf};
 /// in a global cache at `~/.cache/stacy/packages/`.
 const PROJECT_MARKERS: &[&str] = &["stacy.toml", "stacy.lock"];
 
+/// Root given with `--project`, used in place of the walk up from the cwd
+static PROJECT_OVERRIDE: OnceLock<ProjectRoot> = OnceLock::new();
+
+/// Policy given with `--nested-projects`
+static NESTED_POLICY: OnceLock<NestedPolicy> = OnceLock::new();
+
+/// Which project wins when one lives inside another
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum NestedPolicy {
+    /// The nearest marker, unless its stacy.toml names a `parent`
+    #[default]
+    Nearest,
+    /// The outermost marker, or the nearest stacy.toml with `root = true`
+    Outermost,
+}
+
 /// Information about a detected project root
 #[derive(Debug, Clone)]
 pub struct ProjectRoot {
@@ -29,7 +55,9 @@ pub struct ProjectRoot {
 /// 1. `stacy.toml` - explicit project configuration
 /// 2. `stacy.lock` - package lockfile
 ///
-/// Returns the first directory containing any marker, or None if no project found.
+/// Returns the first directory containing any marker, or None if no project
+/// found. `[project] parent` and `--nested-projects outermost` pick a project
+/// further up instead.
 ///
 /// # Arguments
 /// * `start_dir` - The directory to start searching from
@@ -45,18 +73,28 @@ pub struct ProjectRoot {
 /// }
 /// ```
 pub fn find_project_root(start_dir: &Path) -> Result<Option<ProjectRoot>> {
+    find_project_root_with(start_dir, NESTED_POLICY.get().copied().unwrap_or_default())
+}
+
+fn find_project_root_with(start_dir: &Path, policy: NestedPolicy) -> Result<Option<ProjectRoot>> {
     // Canonicalize the start directory to resolve symlinks and get absolute path
-    let mut current = start_dir
-        .canonicalize()
-        .unwrap_or_else(|_| start_dir.to_path_buf());
+    let mut current = paths::canonicalize_or_self(start_dir);
+    let mut outermost = None;
 
     loop {
         // Check for project markers in precedence order
-        if let Some(marker) = find_marker_in_dir(&current) {
-            return Ok(Some(ProjectRoot {
-                path: current,
-                marker: marker.to_string(),
-            }));
+        if let Some(root) = marked_root(&current) {
+            let section = nesting(&root);
+            match policy {
+                NestedPolicy::Nearest => {
+                    return match section.parent {
+                        Some(parent) => parent_root(&root, &parent).map(Some),
+                        None => Ok(Some(root)),
+                    };
+                }
+                NestedPolicy::Outermost if section.root => return Ok(Some(root)),
+                NestedPolicy::Outermost => outermost = Some(root),
+            }
         }
 
         // Move up to parent directory
@@ -72,17 +110,141 @@ pub fn find_project_root(start_dir: &Path) -> Result<Option<ProjectRoot>> {
         }
     }
 
-    Ok(None)
+    Ok(outermost)
+}
+
+/// `root` and `parent` of `[project]` in a stacy.toml
+#[derive(Default)]
+struct Nesting {
+    root: bool,
+    parent: Option<PathBuf>,
+}
+
+/// The nesting keys of the root's stacy.toml; the defaults without one.
+/// Read leniently: the walk passes stacy.toml files of projects it doesn't
+/// end up in, and one that is invalid or written for a newer stacy mustn't
+/// stop it. The chosen project's config is checked when it's loaded.
+fn nesting(root: &ProjectRoot) -> Nesting {
+    let project = std::fs::read_to_string(root.path.join("stacy.toml"))
+        .ok()
+        .and_then(|text| text.parse::<toml::Table>().ok())
+        .and_then(|mut table| match table.remove("project") {
+            Some(toml::Value::Table(project)) => Some(project),
+            _ => None,
+        })
+        .unwrap_or_default();
+    Nesting {
+        root: project
+            .get("root")
+            .and_then(toml::Value::as_bool)
+            .unwrap_or(false),
+        parent: project
+            .get("parent")
+            .and_then(toml::Value::as_str)
+            .map(PathBuf::from),
+    }
+}
+
+/// The project `parent` (relative to `child`) names. It must be a project
+/// above `child`; its own `parent` is followed in turn.
+fn parent_root(child: &ProjectRoot, parent: &Path) -> Result<ProjectRoot> {
+    let target = paths::canonicalize_or_self(&child.path.join(parent));
+    let root = match marked_root(&target) {
+        Some(root) if child.path.starts_with(&root.path) && child.path != root.path => root,
+        _ => {
+            return Err(Error::Config(format!(
+                "[project] parent = \"{}\" in {}: {} is not a project above it",
+                parent.display(),
+                child.path.join("stacy.toml").display(),
+                target.display()
+            )))
+        }
+    };
+    match nesting(&root).parent {
+        Some(next) => parent_root(&root, &next),
+        None => Ok(root),
+    }
+}
+
+/// `dir` as a project root, if it holds a marker
+fn marked_root(dir: &Path) -> Option<ProjectRoot> {
+    find_marker_in_dir(dir).map(|marker| ProjectRoot {
+        path: dir.to_path_buf(),
+        marker: marker.to_string(),
+    })
+}
+
+/// Resolve nested projects by `policy` for the rest of this process. Called
+/// once, after parsing the command line.
+pub fn set_nested_policy(policy: NestedPolicy) {
+    let _ = NESTED_POLICY.set(policy);
 }
 
 /// Find project root starting from the current working directory.
 ///
 /// Convenience wrapper around `find_project_root` that uses `std::env::current_dir()`.
+/// With `--project`, returns that root instead.
 pub fn find_project_root_from_cwd() -> Result<Option<ProjectRoot>> {
+    if let Some(root) = PROJECT_OVERRIDE.get() {
+        return Ok(Some(root.clone()));
+    }
     let cwd = std::env::current_dir()?;
     find_project_root(&cwd)
 }
 
+/// The project root for commands that must work while stacy.toml is invalid
+/// (`stacy config validate`, `stacy migrate`): the `--project` override, or
+/// the nearest directory from the current one up with a project marker.
+/// Unlike [`find_project_root_from_cwd`], no stacy.toml is read, so
+/// `[project] parent` is not followed.
+pub fn find_project_root_unchecked() -> Result<Option<PathBuf>> {
+    if let Some(root) = PROJECT_OVERRIDE.get() {
+        return Ok(Some(root.path.clone()));
+    }
+    let cwd = std::env::current_dir()?;
+    Ok(cwd
+        .ancestors()
+        .find(|dir| find_marker_in_dir(dir).is_some())
+        .map(Path::to_path_buf))
+}
+
+/// The project root `path` names: a directory with a project marker, or the
+/// `stacy.toml` or `stacy.lock` in one. Nothing is searched above it. Any
+/// other file is refused: a project's config is always its root's
+/// stacy.toml, so a config file under another name would not be read.
+pub fn project_root_at(path: &Path) -> Result<ProjectRoot> {
+    let path = paths::canonicalize(path)
+        .map_err(|_| Error::Config(format!("--project {}: no such path", path.display())))?;
+    let dir = if path.is_file() {
+        match (path.file_name().and_then(|n| n.to_str()), path.parent()) {
+            (Some(name), Some(parent)) if PROJECT_MARKERS.contains(&name) => parent.to_path_buf(),
+            _ => {
+                return Err(Error::Config(format!(
+                    "--project {}: expected a project directory or its stacy.toml \
+                     (stacy reads a project's config only from stacy.toml at its root)",
+                    path.display()
+                )))
+            }
+        }
+    } else {
+        path
+    };
+    marked_root(&dir).ok_or_else(|| {
+        Error::Config(format!(
+            "--project {}: no stacy.toml or stacy.lock there",
+            dir.display()
+        ))
+    })
+}
+
+/// Use the project at `path` for the rest of this process. Called once, after
+/// parsing the command line.
+pub fn set_project_override(path: &Path) -> Result<()> {
+    let root = project_root_at(path)?;
+    let _ = PROJECT_OVERRIDE.set(root);
+    Ok(())
+}
+
 /// Check if a directory contains any project marker.
 /// Returns the first marker found (in precedence order), or None.
 fn find_marker_in_dir(dir: &Path) -> Option<&'static str> {
@@ -190,4 +352,87 @@ mod tests {
         fs::write(temp.path().join("stacy.toml"), "").unwrap();
         assert!(is_project_root(temp.path()));
     }
+
+    #[test]
+    fn test_project_root_at() {
+        let temp = TempDir::new().unwrap();
+        let project = temp.path().join("proj");
+        fs::create_dir_all(project.join("sub")).unwrap();
+        fs::write(project.join("stacy.toml"), "").unwrap();
+        let expected = project.canonicalize().unwrap();
+
+        assert_eq!(project_root_at(&project).unwrap().path, expected);
+        assert_eq!(
+            project_root_at(&project.join("stacy.toml")).unwrap().path,
+            expected
+        );
+        // No walking up from a subdirectory
+        assert!(project_root_at(&project.join("sub")).is_err());
+        assert!(project_root_at(&temp.path().join("missing")).is_err());
+
+        fs::write(project.join("notes.txt"), "").unwrap();
+        assert!(project_root_at(&project.join("notes.txt")).is_err());
+        // A config file under another name is not the project's config
+        fs::write(project.join("ci.toml"), "[project]\n").unwrap();
+        let err = project_root_at(&project.join("ci.toml")).unwrap_err();
+        assert!(err.to_string().contains("only from stacy.toml"));
+    }
+
+    #[test]
+    fn test_nested_parent_hands_over() {
+        let temp = TempDir::new().unwrap();
+        let study = temp.path().join("studies/wages");
+        fs::create_dir_all(study.join("code")).unwrap();
+        fs::write(temp.path().join("stacy.toml"), "").unwrap();
+        fs::write(study.join("stacy.toml"), "[project]\nparent = \"../..\"\n").unwrap();
+
+        let root = find_project_root_with(&study.join("code"), NestedPolicy::Nearest)
+            .unwrap()
+            .unwrap();
+        assert_eq!(root.path, temp.path().canonicalize().unwrap());
+
+        // A parent that isn't a project above is an error
+        fs::write(study.join("stacy.toml"), "[project]\nparent = \"code\"\n").unwrap();
+        let err = find_project_root_with(&study, NestedPolicy::Nearest).unwrap_err();
+        assert!(
+            err.to_string().contains("not a project above it"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_nested_outermost() {
+        let temp = TempDir::new().unwrap();
+        let repo = temp.path().join("repo");
+        let inner = repo.join("studies/wages");
+        fs::create_dir_all(&inner).unwrap();
+        fs::write(temp.path().join("stacy.lock"), "").unwrap();
+        fs::write(repo.join("stacy.toml"), "").unwrap();
+        fs::write(inner.join("stacy.toml"), "").unwrap();
+
+        let nearest = find_project_root_with(&inner, NestedPolicy::Nearest).unwrap();
+        assert_eq!(nearest.unwrap().path, inner.canonicalize().unwrap());
+
+        let outermost = find_project_root_with(&inner, NestedPolicy::Outermost).unwrap();
+        assert_eq!(outermost.unwrap().path, temp.path().canonicalize().unwrap());
+
+        // root = true stops the walk
+        fs::write(repo.join("stacy.toml"), "[project]\nroot = true\n").unwrap();
+        let outermost = find_project_root_with(&inner, NestedPolicy::Outermost).unwrap();
+        assert_eq!(outermost.unwrap().path, repo.canonicalize().unwrap());
+
+        // A stacy.toml the walk passes needn't be valid, or known to this
+        // stacy, beyond the keys it reads
+        fs::write(
+            repo.join("stacy.toml"),
+            "[project]\nroot = true\nfuture_key = 1\n\n[run]\nlog_dir = 3\n",
+        )
+        .unwrap();
+        let outermost = find_project_root_with(&inner, NestedPolicy::Outermost).unwrap();
+        assert_eq!(outermost.unwrap().path, repo.canonicalize().unwrap());
+        fs::write(repo.join("stacy.toml"), "[project\n").unwrap();
+        let outermost = find_project_root_with(&inner, NestedPolicy::Outermost).unwrap();
+        assert_eq!(outermost.unwrap().path, temp.path().canonicalize().unwrap());
+    }
 }
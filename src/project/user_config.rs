@@ -4,7 +4,9 @@
 //! This includes paths like stata_binary that vary between machines.
 
 use crate::error::{Error, Result};
+use crate::executor::open_log::LogViewer;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 
 /// User configuration loaded from ~/.config/stacy/config.toml
@@ -15,6 +17,199 @@ pub struct UserConfig {
     pub stata_binary: Option<String>,
     /// Whether to check for updates on startup (default: true)
     pub update_check: Option<bool>,
+    /// Whether the update check also looks for updates of the current
+    /// project's locked packages (default: false)
+    pub update_check_packages: Option<bool>,
+    /// Release channel for update checks and `stacy self update`
+    pub update_channel: Option<UpdateChannel>,
+    /// HTTP(S) proxy for stacy's network requests (e.g. `http://proxy:3128`)
+    pub proxy: Option<String>,
+    /// Package cache settings
+    pub cache: UserCacheConfig,
+    /// Anonymous usage metrics (opt-in)
+    pub telemetry: UserTelemetryConfig,
+    /// Export of projects' run and test totals
+    pub metrics: UserMetricsConfig,
+    /// Terminal behavior
+    pub ui: UserUiConfig,
+    /// Exit codes for kinds of failure, overriding the standard ones (a
+    /// project's `[exit_codes]` wins over these)
+    pub exit_codes: BTreeMap<String, i32>,
+}
+
+/// Which releases count as updates
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdateChannel {
+    /// Full releases only
+    #[default]
+    Stable,
+    /// Pre-releases (`v1.3.0-beta.1`) as well
+    Beta,
+}
+
+impl std::fmt::Display for UpdateChannel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UpdateChannel::Stable => write!(f, "stable"),
+            UpdateChannel::Beta => write!(f, "beta"),
+        }
+    }
+}
+
+/// `[cache]` section of the user config
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct UserCacheConfig {
+    /// Secondary, read-only package cache (e.g. a lab's network share),
+    /// consulted before downloading. Laid out like the global cache:
+    /// `{shared_path}/{name}/{version}/`.
+    pub shared_path: Option<PathBuf>,
+    /// The global cache is provisioned centrally and read-only: stacy
+    /// installs nothing into it, only checks that locked packages are there
+    pub read_only: Option<bool>,
+}
+
+/// `[telemetry]` section of the user config
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct UserTelemetryConfig {
+    /// Record command names, durations and error categories (default: off)
+    pub enabled: Option<bool>,
+    /// Where batches of events are sent (HTTP POST, JSON)
+    pub endpoint: Option<String>,
+}
+
+/// `[metrics]` section of the user config
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct UserMetricsConfig {
+    /// node_exporter textfile collector directory: each project's totals are
+    /// written there as `stacy-<project>.prom` after every invocation
+    pub textfile_dir: Option<PathBuf>,
+}
+
+/// `[ui]` section of the user config
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct UserUiConfig {
+    /// Open a failed `stacy run`'s log at the error line, in the editor or
+    /// the pager (default: don't)
+    pub open_log_on_failure: Option<LogViewer>,
+}
+
+/// Keys accepted by `stacy config get/set`
+pub const CONFIG_KEYS: &[&str] = &[
+    "stata_binary",
+    "update_check",
+    "update_check_packages",
+    "update_channel",
+    "proxy",
+    "cache.shared_path",
+    "cache.read_only",
+    "telemetry.enabled",
+    "telemetry.endpoint",
+    "metrics.textfile_dir",
+    "ui.open_log_on_failure",
+];
+
+impl UserConfig {
+    /// Current value of `key` (see [`CONFIG_KEYS`]), `None` if unset
+    pub fn get(&self, key: &str) -> Result<Option<String>> {
+        Ok(match key {
+            "stata_binary" => self.stata_binary.clone(),
+            "update_check" => self.update_check.map(|v| v.to_string()),
+            "update_check_packages" => self.update_check_packages.map(|v| v.to_string()),
+            "update_channel" => self.update_channel.map(|v| v.to_string()),
+            "proxy" => self.proxy.clone(),
+            "cache.shared_path" => self
+                .cache
+                .shared_path
+                .as_ref()
+                .map(|p| p.display().to_string()),
+            "cache.read_only" => self.cache.read_only.map(|v| v.to_string()),
+            "telemetry.enabled" => self.telemetry.enabled.map(|v| v.to_string()),
+            "telemetry.endpoint" => self.telemetry.endpoint.clone(),
+            "metrics.textfile_dir" => self
+                .metrics
+                .textfile_dir
+                .as_ref()
+                .map(|p| p.display().to_string()),
+            "ui.open_log_on_failure" => self.ui.open_log_on_failure.map(|v| v.to_string()),
+            _ => return Err(unknown_key(key)),
+        })
+    }
+
+    /// Set `key` (see [`CONFIG_KEYS`]) from its string form
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        match key {
+            "stata_binary" => self.stata_binary = Some(value.to_string()),
+            "update_check" => self.update_check = Some(parse_bool(key, value)?),
+            "update_check_packages" => self.update_check_packages = Some(parse_bool(key, value)?),
+            "update_channel" => {
+                self.update_channel = Some(match value {
+                    "stable" => UpdateChannel::Stable,
+                    "beta" => UpdateChannel::Beta,
+                    _ => {
+                        return Err(Error::Config(format!(
+                            "Invalid value for update_channel: '{}' (expected stable or beta)",
+                            value
+                        )))
+                    }
+                })
+            }
+            "proxy" => {
+                if reqwest::Proxy::all(value).is_err() {
+                    return Err(Error::Config(format!("Invalid proxy URL: {}", value)));
+                }
+                self.proxy = Some(value.to_string())
+            }
+            "cache.shared_path" => self.cache.shared_path = Some(PathBuf::from(value)),
+            "cache.read_only" => self.cache.read_only = Some(parse_bool(key, value)?),
+            "telemetry.enabled" => self.telemetry.enabled = Some(parse_bool(key, value)?),
+            "telemetry.endpoint" => {
+                if reqwest::Url::parse(value).is_err() {
+                    return Err(Error::Config(format!(
+                        "Invalid telemetry endpoint URL: {}",
+                        value
+                    )));
+                }
+                self.telemetry.endpoint = Some(value.to_string())
+            }
+            "metrics.textfile_dir" => self.metrics.textfile_dir = Some(PathBuf::from(value)),
+            "ui.open_log_on_failure" => {
+                self.ui.open_log_on_failure = Some(match value {
+                    "editor" => LogViewer::Editor,
+                    "pager" => LogViewer::Pager,
+                    _ => {
+                        return Err(Error::Config(format!(
+                        "Invalid value for ui.open_log_on_failure: '{}' (expected editor or pager)",
+                        value
+                    )))
+                    }
+                })
+            }
+            _ => return Err(unknown_key(key)),
+        }
+        Ok(())
+    }
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool> {
+    value.parse().map_err(|_| {
+        Error::Config(format!(
+            "Invalid value for {}: '{}' (expected true or false)",
+            key, value
+        ))
+    })
+}
+
+fn unknown_key(key: &str) -> Error {
+    Error::Config(format!(
+        "Unknown config key '{}'. Valid keys: {}",
+        key,
+        CONFIG_KEYS.join(", ")
+    ))
 }
 
 /// Get the user config directory path.
@@ -41,25 +236,9 @@ pub fn load_user_config() -> Result<Option<UserConfig>> {
         None => return Ok(None),
     };
 
-    if !config_path.exists() {
+    let Some(config) = read_user_config(&config_path)? else {
         return Ok(None);
-    }
-
-    let content = std::fs::read_to_string(&config_path).map_err(|e| {
-        Error::Config(format!(
-            "Failed to read user config at {}: {}",
-            config_path.display(),
-            e
-        ))
-    })?;
-
-    let config: UserConfig = toml::from_str(&content).map_err(|e| {
-        Error::Config(format!(
-            "Failed to parse user config at {}: {}",
-            config_path.display(),
-            e
-        ))
-    })?;
+    };
 
     // Validate stata_binary if specified
     if let Some(ref binary) = config.stata_binary {
@@ -74,9 +253,45 @@ pub fn load_user_config() -> Result<Option<UserConfig>> {
         }
     }
 
+    if let Some(ref proxy) = config.proxy {
+        if reqwest::Proxy::all(proxy).is_err() {
+            return Err(Error::Config(format!(
+                "Invalid proxy URL in {}: {}",
+                config_path.display(),
+                proxy
+            )));
+        }
+    }
+
+    crate::error::exit_codes::validate(&config.exit_codes, &config_path.display().to_string())?;
+
     Ok(Some(config))
 }
 
+/// Parse the user config file without validating its values, so a stale
+/// setting can still be fixed with `stacy config set`
+pub fn read_user_config(config_path: &Path) -> Result<Option<UserConfig>> {
+    if !config_path.exists() {
+        return Ok(None);
+    }
+
+    let content = std::fs::read_to_string(config_path).map_err(|e| {
+        Error::Config(format!(
+            "Failed to read user config at {}: {}",
+            config_path.display(),
+            e
+        ))
+    })?;
+
+    toml::from_str(&content).map(Some).map_err(|e| {
+        Error::Config(format!(
+            "Failed to parse user config at {}: {}",
+            config_path.display(),
+            e
+        ))
+    })
+}
+
 /// Save user configuration to ~/.config/stacy/config.toml
 ///
 /// Creates the config directory if it doesn't exist.
@@ -130,6 +345,92 @@ fn generate_user_config_content(config: &UserConfig) -> String {
         content.push_str("# update_check = false\n");
     }
 
+    content.push_str("# Also nudge, at most weekly, when a project's packages have updates\n");
+    if let Some(packages) = config.update_check_packages {
+        content.push_str(&format!("update_check_packages = {}\n", packages));
+    } else {
+        content.push_str("# update_check_packages = true\n");
+    }
+
+    content.push_str("# Release channel for updates: \"stable\" or \"beta\" (pre-releases)\n");
+    if let Some(channel) = config.update_channel {
+        content.push_str(&format!("update_channel = \"{}\"\n", channel));
+    } else {
+        content.push_str("# update_channel = \"beta\"\n");
+    }
+
+    content.push('\n');
+    content.push_str("# HTTP(S) proxy for downloads and update checks\n");
+    if let Some(ref proxy) = config.proxy {
+        content.push_str(&format!("proxy = \"{}\"\n", proxy));
+    } else {
+        content.push_str("# proxy = \"http://proxy.example.com:3128\"\n");
+    }
+
+    content.push('\n');
+    content.push_str("# Read-only package cache shared with other users (e.g. a network share)\n");
+    content.push_str("[cache]\n");
+    if let Some(ref shared) = config.cache.shared_path {
+        content.push_str(&format!(
+            "shared_path = \"{}\"\n",
+            shared.display().to_string().replace('\\', "\\\\")
+        ));
+    } else {
+        content.push_str("# shared_path = \"/shared/stacy/packages\"\n");
+    }
+    content
+        .push_str("# Global cache provisioned by administrators: check packages, never install\n");
+    if let Some(read_only) = config.cache.read_only {
+        content.push_str(&format!("read_only = {}\n", read_only));
+    } else {
+        content.push_str("# read_only = true\n");
+    }
+
+    content.push('\n');
+    content.push_str("# Anonymous usage metrics: command names, durations, error categories\n");
+    content.push_str("[telemetry]\n");
+    if let Some(enabled) = config.telemetry.enabled {
+        content.push_str(&format!("enabled = {}\n", enabled));
+    } else {
+        content.push_str("# enabled = true\n");
+    }
+    if let Some(ref endpoint) = config.telemetry.endpoint {
+        content.push_str(&format!("endpoint = \"{}\"\n", endpoint));
+    } else {
+        content.push_str("# endpoint = \"https://telemetry.example.com/v1/events\"\n");
+    }
+
+    content.push('\n');
+    content.push_str("# Prometheus textfile collector directory for run and test totals\n");
+    content.push_str("[metrics]\n");
+    if let Some(ref dir) = config.metrics.textfile_dir {
+        content.push_str(&format!(
+            "textfile_dir = \"{}\"\n",
+            dir.display().to_string().replace('\\', "\\\\")
+        ));
+    } else {
+        content.push_str("# textfile_dir = \"/var/lib/node_exporter/textfile_collector\"\n");
+    }
+
+    content.push('\n');
+    content.push_str("# Open a failed run's log at the error line: \"editor\" or \"pager\"\n");
+    content.push_str("[ui]\n");
+    if let Some(viewer) = config.ui.open_log_on_failure {
+        content.push_str(&format!("open_log_on_failure = \"{}\"\n", viewer));
+    } else {
+        content.push_str("# open_log_on_failure = \"editor\"\n");
+    }
+
+    content.push('\n');
+    content.push_str("# Exit codes for kinds of failure, for orchestrators that reserve some\n");
+    content.push_str("[exit_codes]\n");
+    if config.exit_codes.is_empty() {
+        content.push_str("# syntax_error = 65\n");
+    }
+    for (kind, code) in &config.exit_codes {
+        content.push_str(&format!("{} = {}\n", kind, code));
+    }
+
     content
 }
 
@@ -145,6 +446,38 @@ pub fn generate_user_config_template() -> &'static str {
 
 # Check for updates on startup (set to false to disable)
 # update_check = false
+
+# Also nudge, at most weekly, when a project's packages have updates
+# update_check_packages = true
+
+# Release channel for updates: "stable" or "beta" (pre-releases)
+# update_channel = "beta"
+
+# HTTP(S) proxy for downloads and update checks
+# proxy = "http://proxy.example.com:3128"
+
+# Read-only package cache shared with other users (e.g. a network share)
+# [cache]
+# shared_path = "/shared/stacy/packages"
+# Global cache provisioned by administrators: check packages, never install
+# read_only = true
+
+# Anonymous usage metrics: command names, durations, error categories
+# [telemetry]
+# enabled = true
+# endpoint = "https://telemetry.example.com/v1/events"
+
+# Prometheus textfile collector directory for run and test totals
+# [metrics]
+# textfile_dir = "/var/lib/node_exporter/textfile_collector"
+
+# Open a failed run's log at the error line: "editor" or "pager"
+# [ui]
+# open_log_on_failure = "editor"
+
+# Exit codes for kinds of failure, for orchestrators that reserve some
+# [exit_codes]
+# syntax_error = 65
 "#
 }
 
@@ -182,6 +515,7 @@ mod tests {
         let config = UserConfig {
             stata_binary: Some("/usr/local/stata/stata-mp".to_string()),
             update_check: None,
+            ..Default::default()
         };
         let content = generate_user_config_content(&config);
         assert!(content.contains("stata_binary = \"/usr/local/stata/stata-mp\""));
@@ -192,6 +526,7 @@ mod tests {
         let config = UserConfig {
             stata_binary: None,
             update_check: Some(false),
+            ..Default::default()
         };
         let content = generate_user_config_content(&config);
         assert!(content.contains("update_check = false"));
@@ -204,6 +539,106 @@ mod tests {
         assert_eq!(config.update_check, Some(false));
     }
 
+    #[test]
+    fn test_update_channel_and_proxy_round_trip() {
+        let config: UserConfig = toml::from_str(
+            "update_channel = \"beta\"\nproxy = \"http://proxy.example.com:3128\"\n",
+        )
+        .unwrap();
+        assert_eq!(config.update_channel, Some(UpdateChannel::Beta));
+
+        let parsed: UserConfig = toml::from_str(&generate_user_config_content(&config)).unwrap();
+        assert_eq!(parsed.update_channel, Some(UpdateChannel::Beta));
+        assert_eq!(
+            parsed.proxy.as_deref(),
+            Some("http://proxy.example.com:3128")
+        );
+
+        assert!(toml::from_str::<UserConfig>("update_channel = \"nightly\"").is_err());
+    }
+
+    #[test]
+    fn test_parse_cache_shared_path() {
+        let toml_str = r#"
+[cache]
+shared_path = "/mnt/lab/stacy/packages"
+"#;
+        let config: UserConfig = toml::from_str(toml_str).unwrap();
+        assert_eq!(
+            config.cache.shared_path,
+            Some(PathBuf::from("/mnt/lab/stacy/packages"))
+        );
+    }
+
+    #[test]
+    fn test_generate_user_config_content_round_trips_shared_path() {
+        let config = UserConfig {
+            cache: UserCacheConfig {
+                shared_path: Some(PathBuf::from("/mnt/lab/stacy/packages")),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let content = generate_user_config_content(&config);
+        let parsed: UserConfig = toml::from_str(&content).unwrap();
+        assert_eq!(parsed.cache.shared_path, config.cache.shared_path);
+    }
+
+    #[test]
+    fn test_generate_user_config_content_keeps_exit_codes() {
+        let config = UserConfig {
+            exit_codes: BTreeMap::from([("syntax_error".to_string(), 65)]),
+            ..Default::default()
+        };
+        let parsed: UserConfig = toml::from_str(&generate_user_config_content(&config)).unwrap();
+        assert_eq!(parsed.exit_codes, config.exit_codes);
+    }
+
+    #[test]
+    fn test_get_and_set_keys() {
+        let mut config = UserConfig::default();
+        assert_eq!(config.get("telemetry.enabled").unwrap(), None);
+
+        config.set("telemetry.enabled", "true").unwrap();
+        config.set("update_channel", "beta").unwrap();
+        config
+            .set("telemetry.endpoint", "https://example.com/events")
+            .unwrap();
+        config
+            .set("metrics.textfile_dir", "/var/lib/node_exporter/textfile")
+            .unwrap();
+        config.set("ui.open_log_on_failure", "pager").unwrap();
+        config.set("cache.read_only", "true").unwrap();
+        assert_eq!(
+            config.get("telemetry.enabled").unwrap().as_deref(),
+            Some("true")
+        );
+        assert_eq!(
+            config.get("update_channel").unwrap().as_deref(),
+            Some("beta")
+        );
+
+        let parsed: UserConfig = toml::from_str(&generate_user_config_content(&config)).unwrap();
+        assert_eq!(parsed.telemetry.enabled, Some(true));
+        assert_eq!(
+            parsed.telemetry.endpoint.as_deref(),
+            Some("https://example.com/events")
+        );
+        assert_eq!(
+            parsed.metrics.textfile_dir,
+            Some(PathBuf::from("/var/lib/node_exporter/textfile"))
+        );
+        assert_eq!(parsed.ui.open_log_on_failure, Some(LogViewer::Pager));
+        assert_eq!(parsed.cache.read_only, Some(true));
+
+        assert!(config.set("telemetry.enabled", "yes").is_err());
+        assert!(config.set("update_channel", "nightly").is_err());
+        assert!(config.set("ui.open_log_on_failure", "vim").is_err());
+        assert!(config.set("telemetry.endpoint", "not a url").is_err());
+        assert!(config.set("no_such_key", "1").is_err());
+        assert!(config.get("no_such_key").is_err());
+    }
+
     #[test]
     fn test_template_is_valid_toml() {
         let template = generate_user_config_template();
@@ -0,0 +1,507 @@
+//! stacy.toml validation
+//!
+//! Loading stacy.toml reports every unknown key and wrongly typed value at
+//! once, each with its line and column and, for a misspelled key, the key
+//! that was probably meant. After each error the entry at fault is dropped
+//! and the rest of the file deserialized again, so one typo doesn't hide the
+//! next.
+//!
+//! `stacy config validate` adds checks across fields on top: the scripts that
+//! tasks and pipeline stages run must exist, and the tasks and stages they
+//! refer to must be defined.
+
+use super::config::{unknown_key_hint, validate_config, Config, TaskDef};
+use crate::error::{Error, Result};
+use crate::pipeline::Pipeline;
+use crate::task::{is_script_ref, levenshtein_distance, task_script, TaskGraph};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::Range;
+use std::path::Path;
+use toml::de::{DeTable, DeValue, Deserializer};
+
+/// Errors reported at most, so a file that isn't a stacy.toml at all still
+/// gives a readable answer
+const MAX_ERRORS: usize = 20;
+
+/// A problem in stacy.toml
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostic {
+    pub message: String,
+    /// Line of stacy.toml, from 1
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    /// Column of the line, from 1
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column: Option<usize>,
+    /// How to fix it, when we can tell
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+    /// The line at fault with the offending part underlined
+    #[serde(skip)]
+    snippet: Option<String>,
+}
+
+impl Diagnostic {
+    fn new(
+        content: &str,
+        span: Option<Range<usize>>,
+        message: String,
+        hint: Option<String>,
+    ) -> Self {
+        let span = span.filter(|span| span.start <= content.len());
+        let (line, column, snippet) = match span {
+            Some(span) => {
+                let (line, column) = line_col(content, span.start);
+                (
+                    Some(line),
+                    Some(column),
+                    Some(snippet(content, span, line, column)),
+                )
+            }
+            None => (None, None, None),
+        };
+        Self {
+            message,
+            line,
+            column,
+            hint,
+            snippet,
+        }
+    }
+
+    fn from_toml(content: &str, err: &toml::de::Error, span: Option<Range<usize>>) -> Self {
+        let message = err.message().trim_end().to_string();
+        let hint = unknown_key_hint(&message)
+            .map(|hint| hint.trim_start_matches("hint: ").to_string())
+            .or_else(|| did_you_mean(&message));
+        Self::new(content, span, message, hint)
+    }
+}
+
+/// ```text
+/// unknown field `parralel`, expected one of `parallel`, `script`, ...
+///  --> stacy.toml:8:1
+///   |
+/// 8 | parralel = ["clean", "analyze"]
+///   | ^^^^^^^^
+///   = hint: did you mean `parallel`?
+/// ```
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let Some(snippet) = &self.snippet {
+            write!(f, "\n{}", snippet)?;
+        }
+        if let Some(hint) = &self.hint {
+            let indent = match (&self.snippet, self.line) {
+                (Some(_), Some(line)) => " ".repeat(line.to_string().len() + 1),
+                _ => String::new(),
+            };
+            write!(f, "\n{}= hint: {}", indent, hint)?;
+        }
+        Ok(())
+    }
+}
+
+/// All `diagnostics` as one error message
+pub fn describe(diagnostics: &[Diagnostic]) -> String {
+    let shown: Vec<String> = diagnostics.iter().map(ToString::to_string).collect();
+    match shown.as_slice() {
+        [one] => format!("Failed to parse stacy.toml: {}", one),
+        _ => format!(
+            "Failed to parse stacy.toml ({} errors):\n\n{}",
+            shown.len(),
+            shown.join("\n\n")
+        ),
+    }
+}
+
+/// Parse stacy.toml, collecting every unknown key, wrong type and syntax
+/// error instead of stopping at the first
+pub fn parse(content: &str) -> std::result::Result<Config, Vec<Diagnostic>> {
+    let (mut document, errors) = DeTable::parse_recoverable(content);
+    if !errors.is_empty() {
+        return Err(errors
+            .iter()
+            .take(MAX_ERRORS)
+            .map(|err| Diagnostic::from_toml(content, err, err.span()))
+            .collect());
+    }
+
+    let mut diagnostics = Vec::new();
+    loop {
+        let err = match Config::deserialize(Deserializer::from(document.clone())) {
+            Ok(config) if diagnostics.is_empty() => return Ok(config),
+            Ok(_) => return Err(diagnostics),
+            Err(err) => err,
+        };
+        let span = err
+            .span()
+            .map(|span| narrow(document.get_ref(), err.message(), span));
+        diagnostics.push(Diagnostic::from_toml(content, &err, span.clone()));
+        let dropped = span.is_some_and(|span| drop_entry(document.get_mut(), span.start));
+        if !dropped || diagnostics.len() >= MAX_ERRORS {
+            return Err(diagnostics);
+        }
+    }
+}
+
+/// Everything wrong with the stacy.toml at `project_root`: the errors
+/// [`parse`] finds, or when there are none, the checks across fields
+pub fn validate(project_root: &Path) -> Result<Vec<Diagnostic>> {
+    let path = project_root.join("stacy.toml");
+    let content = std::fs::read_to_string(&path).map_err(|e| {
+        Error::Config(format!(
+            "Failed to read stacy.toml at {}: {}",
+            path.display(),
+            e
+        ))
+    })?;
+    match parse(&content) {
+        Ok(config) => Ok(check(&config, project_root, &content)),
+        Err(diagnostics) => Ok(diagnostics),
+    }
+}
+
+/// Checks across fields of a config that parsed
+fn check(config: &Config, project_root: &Path, content: &str) -> Vec<Diagnostic> {
+    let (document, _) = DeTable::parse_recoverable(content);
+    let mut checker = Checker {
+        content,
+        document: document.get_ref(),
+        found: Vec::new(),
+    };
+
+    if let Err(e) = validate_config(config, project_root) {
+        checker.report(&[], config_message(e), None);
+    }
+
+    let tasks = &config.scripts.tasks;
+    let mut names: Vec<&String> = tasks.keys().collect();
+    names.sort();
+    let mut dangling = false;
+    for name in names {
+        let task = &tasks[name];
+        let at = ["scripts", name.as_str()];
+        if let Some(script) = task_script(task) {
+            if !project_root.join(script).is_file() {
+                checker.report(
+                    &at,
+                    format!(
+                        "task `{}` runs {}, which does not exist",
+                        name,
+                        script.display()
+                    ),
+                    None,
+                );
+            }
+        }
+        let refs = match task {
+            TaskDef::Sequential(refs) => refs.as_slice(),
+            TaskDef::Complex(complex) => complex.parallel.as_deref().unwrap_or_default(),
+            TaskDef::Simple(_) => &[],
+        };
+        for reference in refs {
+            if tasks.contains_key(reference) {
+                continue;
+            }
+            if is_script_ref(reference) {
+                if !project_root.join(reference).is_file() {
+                    checker.report(
+                        &at,
+                        format!("task `{}` runs {}, which does not exist", name, reference),
+                        None,
+                    );
+                }
+            } else {
+                dangling = true;
+                checker.report(
+                    &at,
+                    format!("task `{}` references unknown task `{}`", name, reference),
+                    closest(reference, tasks.keys().map(String::as_str))
+                        .map(|similar| format!("did you mean `{}`?", similar)),
+                );
+            }
+        }
+    }
+    if !dangling {
+        if let Err(e) = TaskGraph::from_config(&config.scripts) {
+            checker.report(&["scripts"], config_message(e), None);
+        }
+    }
+
+    dangling = false;
+    for (name, stage) in &config.pipeline {
+        let at = ["pipeline", name.as_str()];
+        if !project_root.join(&stage.script).is_file() {
+            checker.report(
+                &at,
+                format!(
+                    "stage `{}` runs {}, which does not exist",
+                    name,
+                    stage.script.display()
+                ),
+                None,
+            );
+        }
+        for need in stage
+            .needs
+            .iter()
+            .filter(|n| !config.pipeline.contains_key(*n))
+        {
+            dangling = true;
+            checker.report(
+                &at,
+                format!("stage `{}` needs unknown stage `{}`", name, need),
+                closest(need, config.pipeline.keys().map(String::as_str))
+                    .map(|similar| format!("did you mean `{}`?", similar)),
+            );
+        }
+    }
+    if !dangling {
+        if let Err(e) = Pipeline::from_config(&config.pipeline) {
+            checker.report(&["pipeline"], config_message(e), None);
+        }
+    }
+
+    checker.found
+}
+
+struct Checker<'a, 'i> {
+    content: &'a str,
+    document: &'a DeTable<'i>,
+    found: Vec<Diagnostic>,
+}
+
+impl Checker<'_, '_> {
+    /// Report a problem with the entry at `path` (`["scripts", "build"]`)
+    fn report(&mut self, path: &[&str], message: String, hint: Option<String>) {
+        let span = locate(self.document, path);
+        self.found
+            .push(Diagnostic::new(self.content, span, message, hint));
+    }
+}
+
+/// The message of a config error, without the "Configuration error" prefix
+fn config_message(err: Error) -> String {
+    match err {
+        Error::Config(message) => message,
+        other => other.to_string(),
+    }
+}
+
+/// Span of the key at `path` in the document
+fn locate(document: &DeTable<'_>, path: &[&str]) -> Option<Range<usize>> {
+    let (first, rest) = path.split_first()?;
+    let (key, value) = document
+        .iter()
+        .find(|(key, _)| key.get_ref().as_ref() == *first)?;
+    match (rest.is_empty(), value.get_ref()) {
+        (true, _) => Some(key.span()),
+        (false, DeValue::Table(table)) => locate(table, rest).or_else(|| Some(key.span())),
+        (false, _) => Some(key.span()),
+    }
+}
+
+/// The span of the unknown key an "unknown field" error at `span` is about.
+/// Tasks are deserialized in two steps, so their errors only point at the
+/// table holding them: the key is looked up inside it.
+fn narrow(document: &DeTable<'_>, message: &str, span: Range<usize>) -> Range<usize> {
+    let key = message
+        .strip_prefix("unknown field `")
+        .and_then(|rest| rest.split('`').next());
+    match (key, value_at(document, span.start)) {
+        (Some(key), Some(DeValue::Table(table))) => find_key(table, key).unwrap_or(span),
+        _ => span,
+    }
+}
+
+/// The value of the innermost entry whose key or value covers `at`
+fn value_at<'a, 'i>(table: &'a DeTable<'i>, at: usize) -> Option<&'a DeValue<'i>> {
+    table.iter().find_map(|(key, value)| {
+        let nested = match value.get_ref() {
+            DeValue::Table(table) => value_at(table, at),
+            _ => None,
+        };
+        let covers = key.span().contains(&at) || value.span().contains(&at);
+        nested.or(covers.then(|| value.get_ref()))
+    })
+}
+
+/// Span of the first key called `name` in `table` or the tables in it
+fn find_key(table: &DeTable<'_>, name: &str) -> Option<Range<usize>> {
+    table.iter().find_map(|(key, value)| {
+        if key.get_ref().as_ref() == name {
+            return Some(key.span());
+        }
+        match value.get_ref() {
+            DeValue::Table(table) => find_key(table, name),
+            _ => None,
+        }
+    })
+}
+
+/// Drop the innermost entry whose key or value covers the byte offset `at`
+fn drop_entry(table: &mut DeTable<'_>, at: usize) -> bool {
+    let keys: Vec<_> = table.keys().cloned().collect();
+    for key in keys {
+        let Some(value) = table.get_mut(&key) else {
+            continue;
+        };
+        let covers = key.span().contains(&at) || value.span().contains(&at);
+        if drop_nested(value.get_mut(), at) {
+            return true;
+        }
+        if covers {
+            table.remove(&key);
+            return true;
+        }
+    }
+    false
+}
+
+fn drop_nested(value: &mut DeValue<'_>, at: usize) -> bool {
+    match value {
+        DeValue::Table(table) => drop_entry(table, at),
+        DeValue::Array(array) => array.iter_mut().any(|item| drop_nested(item.get_mut(), at)),
+        _ => false,
+    }
+}
+
+/// "did you mean" for serde's "unknown field `x`, expected one of `a`, `b`"
+/// (and the same for unknown variants)
+fn did_you_mean(message: &str) -> Option<String> {
+    let rest = message
+        .strip_prefix("unknown field `")
+        .or_else(|| message.strip_prefix("unknown variant `"))?;
+    let (unknown, expected) = rest.split_once('`')?;
+    let candidates = expected.split('`').skip(1).step_by(2);
+    closest(unknown, candidates).map(|similar| format!("did you mean `{}`?", similar))
+}
+
+/// The candidate closest to `name`, if it is close enough to be a typo
+fn closest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let name = name.to_lowercase();
+    let max_distance = match name.chars().count() {
+        0..=3 => 1,
+        len => (len / 3).max(2),
+    };
+    candidates
+        .into_iter()
+        .map(|candidate| {
+            (
+                levenshtein_distance(&name, &candidate.to_lowercase()),
+                candidate,
+            )
+        })
+        .filter(|(distance, _)| *distance <= max_distance && *distance < name.len())
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Line and column (both from 1) of the byte offset `at`
+fn line_col(content: &str, at: usize) -> (usize, usize) {
+    let before = content.get(..at).unwrap_or(content);
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+    (line, column)
+}
+
+/// ```text
+///  --> stacy.toml:8:1
+///   |
+/// 8 | parralel = ["clean", "analyze"]
+///   | ^^^^^^^^
+/// ```
+fn snippet(content: &str, span: Range<usize>, line: usize, column: usize) -> String {
+    let text = content.lines().nth(line - 1).unwrap_or("").trim_end();
+    let width = content
+        .get(span)
+        .and_then(|spanned| spanned.lines().next())
+        .map(|first| first.chars().count())
+        .unwrap_or(0)
+        .clamp(1, (text.chars().count() + 1).saturating_sub(column).max(1));
+    let gutter = " ".repeat(line.to_string().len());
+    format!(
+        "{gutter}--> stacy.toml:{line}:{column}\n{gutter} |\n{line} | {text}\n{gutter} | {}{}",
+        " ".repeat(column - 1),
+        "^".repeat(width)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_reports_every_unknown_key() {
+        let content =
+            "[project]\nnmae = \"study\"\n\n[run]\nlog_dirs = \"logs\"\nshow_progress = \"yes\"\n";
+        let diagnostics = parse(content).unwrap_err();
+        assert_eq!(diagnostics.len(), 3, "{:?}", diagnostics);
+
+        assert!(diagnostics[0].message.contains("unknown field `nmae`"));
+        assert_eq!(
+            (diagnostics[0].line, diagnostics[0].column),
+            (Some(2), Some(1))
+        );
+        assert_eq!(diagnostics[0].hint.as_deref(), Some("did you mean `name`?"));
+
+        assert!(diagnostics[1].message.contains("unknown field `log_dirs`"));
+        assert_eq!(diagnostics[1].line, Some(5));
+        assert_eq!(
+            diagnostics[1].hint.as_deref(),
+            Some("did you mean `log_dir`?")
+        );
+
+        assert!(diagnostics[2].message.contains("invalid type"));
+        assert_eq!(
+            (diagnostics[2].line, diagnostics[2].column),
+            (Some(6), Some(17))
+        );
+        assert!(diagnostics[2].to_string().contains(
+            " --> stacy.toml:6:17\n  |\n6 | show_progress = \"yes\"\n  |                 ^^^^^"
+        ));
+    }
+
+    #[test]
+    fn test_parse_accepts_valid_config() {
+        let config = parse("[project]\nname = \"study\"\n").unwrap();
+        assert_eq!(config.project.name.as_deref(), Some("study"));
+    }
+
+    #[test]
+    fn test_closest() {
+        let keys = ["parallel", "script", "args"];
+        assert_eq!(closest("parralel", keys), Some("parallel"));
+        assert_eq!(closest("scripts", keys), Some("script"));
+        assert_eq!(closest("timeout", keys), None);
+    }
+
+    #[test]
+    fn test_check_finds_missing_scripts_and_tasks() {
+        let temp = TempDir::new().unwrap();
+        fs::write(temp.path().join("clean.do"), "").unwrap();
+        let content = "[scripts]\nclean = \"clean.do\"\nanalyze = \"analyze.do\"\nall = [\"clen\", \"analyze\"]\n";
+        fs::write(temp.path().join("stacy.toml"), content).unwrap();
+
+        let diagnostics = validate(temp.path()).unwrap();
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "task `all` references unknown task `clen`",
+                "task `analyze` runs analyze.do, which does not exist",
+            ]
+        );
+        assert_eq!(diagnostics[0].line, Some(4));
+        assert_eq!(
+            diagnostics[0].hint.as_deref(),
+            Some("did you mean `clean`?")
+        );
+        assert_eq!(diagnostics[1].line, Some(3));
+    }
+}
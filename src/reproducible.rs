@@ -0,0 +1,459 @@
+//! Output determinism check (`stacy run --check-reproducible`)
+//!
+//! Runs a script twice, each time in a fresh copy of the project, and compares
+//! the files the two runs wrote. Identical outputs don't prove a script is
+//! deterministic, but different outputs prove it isn't.
+//!
+//! Some differences are noise rather than nondeterminism and are normalized
+//! away before hashing: the save timestamp in a `.dta` header, and the
+//! `opened on:` / `closed on:` lines of a Stata log. When outputs still
+//! differ, the script and the do-files it calls are scanned for the usual
+//! culprits (random numbers without a seed, `sort` without `stable`, the
+//! current date and time, `merge m:m`).
+
+use crate::deps::tree::build_tree;
+use crate::error::{Error, Result};
+use crate::project::config::ReproducibilitySection;
+use regex::Regex;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// How many times the check runs the script
+pub const RUNS: usize = 2;
+
+/// Directories left out of a run's copy of the project: version control and
+/// stacy's own state are not inputs to the script
+const SKIP_DIRS: &[&str] = &[".git", ".stacy"];
+
+/// Hash of every file under a directory, keyed by relative path
+pub type Snapshot = BTreeMap<PathBuf, String>;
+
+/// What one run left behind
+#[derive(Debug, Clone, Default)]
+pub struct RunOutputs {
+    /// Every file in the run's copy after the run
+    pub after: Snapshot,
+    /// Files the run created or changed
+    pub written: BTreeSet<PathBuf>,
+}
+
+impl RunOutputs {
+    /// Compare the copy before and after a run
+    pub fn between(before: &Snapshot, after: Snapshot) -> Self {
+        let written = after
+            .iter()
+            .filter(|(path, hash)| before.get(*path) != Some(*hash))
+            .map(|(path, _)| path.clone())
+            .collect();
+        Self { after, written }
+    }
+}
+
+/// How an output differs between the two runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffKind {
+    /// Both runs wrote it, with different content
+    Differs,
+    /// Only the first run wrote it
+    OnlyFirst,
+    /// Only the second run wrote it
+    OnlySecond,
+    /// A declared output that neither run produced
+    NotWritten,
+}
+
+impl std::fmt::Display for DiffKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DiffKind::Differs => write!(f, "differs"),
+            DiffKind::OnlyFirst => write!(f, "only run 1"),
+            DiffKind::OnlySecond => write!(f, "only run 2"),
+            DiffKind::NotWritten => write!(f, "not written"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OutputDiff {
+    pub path: PathBuf,
+    pub kind: DiffKind,
+}
+
+/// A line that commonly makes results differ between runs
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Source {
+    pub file: PathBuf,
+    pub line: usize,
+    pub reason: String,
+}
+
+/// Result of comparing two runs
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckReport {
+    /// Outputs compared (declared, or every file either run wrote)
+    pub outputs: Vec<PathBuf>,
+    pub differences: Vec<OutputDiff>,
+    /// Likely causes, when outputs differ
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<Source>,
+}
+
+impl CheckReport {
+    pub fn is_reproducible(&self) -> bool {
+        self.differences.is_empty()
+    }
+}
+
+/// Compare the outputs of two runs.
+///
+/// With `declared` outputs, exactly those paths (relative to the project
+/// root) are compared, whether or not a run changed them. Otherwise every
+/// file either run wrote is.
+pub fn compare(first: &RunOutputs, second: &RunOutputs, declared: &[PathBuf]) -> CheckReport {
+    let outputs: Vec<PathBuf> = if declared.is_empty() {
+        first.written.union(&second.written).cloned().collect()
+    } else {
+        declared.to_vec()
+    };
+
+    let differences = outputs
+        .iter()
+        .filter_map(|path| {
+            let kind = match (first.after.get(path), second.after.get(path)) {
+                (Some(a), Some(b)) if a == b => return None,
+                (Some(_), Some(_)) => DiffKind::Differs,
+                (Some(_), None) => DiffKind::OnlyFirst,
+                (None, Some(_)) => DiffKind::OnlySecond,
+                (None, None) => DiffKind::NotWritten,
+            };
+            Some(OutputDiff {
+                path: path.clone(),
+                kind,
+            })
+        })
+        .collect();
+
+    CheckReport {
+        outputs,
+        differences,
+        sources: Vec::new(),
+    }
+}
+
+/// Copy the project at `root` into `dest`, leaving out [`SKIP_DIRS`]
+pub fn copy_project(root: &Path, dest: &Path) -> Result<()> {
+    for entry in walk(root) {
+        let entry = entry.map_err(walk_error)?;
+        let rel = entry.path().strip_prefix(root).unwrap_or(entry.path());
+        let target = dest.join(rel);
+        if entry.file_type().is_dir() {
+            std::fs::create_dir_all(&target)?;
+        } else {
+            std::fs::copy(entry.path(), &target).map_err(|e| {
+                Error::Io(std::io::Error::new(
+                    e.kind(),
+                    format!("Failed to copy {}: {}", entry.path().display(), e),
+                ))
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// Normalized hash of every file under `root`
+pub fn snapshot(root: &Path) -> Result<Snapshot> {
+    let mut snapshot = Snapshot::new();
+    for entry in walk(root) {
+        let entry = entry.map_err(walk_error)?;
+        if entry.file_type().is_file() {
+            let rel = entry.path().strip_prefix(root).unwrap_or(entry.path());
+            snapshot.insert(rel.to_path_buf(), normalized_hash(entry.path())?);
+        }
+    }
+    Ok(snapshot)
+}
+
+fn walk(root: &Path) -> impl Iterator<Item = walkdir::Result<walkdir::DirEntry>> {
+    walkdir::WalkDir::new(root)
+        .min_depth(1)
+        .into_iter()
+        .filter_entry(|e| {
+            !(e.file_type().is_dir()
+                && e.file_name()
+                    .to_str()
+                    .is_some_and(|name| SKIP_DIRS.contains(&name)))
+        })
+}
+
+fn walk_error(e: walkdir::Error) -> Error {
+    Error::Io(
+        e.into_io_error()
+            .unwrap_or_else(|| std::io::Error::other("directory walk failed")),
+    )
+}
+
+/// SHA256 of a file, ignoring content that changes on every run without
+/// being a result: `.dta` save timestamps and log open/close times
+pub fn normalized_hash(path: &Path) -> Result<String> {
+    let mut content = std::fs::read(path)?;
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("dta") => blank_dta_timestamp(&mut content),
+        Some("log") | Some("smcl") => content = strip_log_times(&content),
+        _ => {}
+    }
+    Ok(hex::encode(Sha256::digest(&content)))
+}
+
+/// Zero the `time_stamp` field of a `.dta` header.
+///
+/// Formats 117+ wrap it in `<timestamp>` tags behind a length byte; formats
+/// 113–115 keep 18 bytes at a fixed offset after the data label.
+fn blank_dta_timestamp(content: &mut [u8]) {
+    const TAG: &[u8] = b"<timestamp>";
+    if content.starts_with(b"<stata_dta>") {
+        let header = &content[..content.len().min(1024)];
+        if let Some(pos) = header.windows(TAG.len()).position(|w| w == TAG) {
+            let len_at = pos + TAG.len();
+            if let Some(&len) = content.get(len_at) {
+                let end = (len_at + 1 + len as usize).min(content.len());
+                content[len_at + 1..end].fill(0);
+            }
+        }
+    } else if matches!(content.first(), Some(113..=115)) && content.len() >= 109 {
+        content[91..109].fill(0);
+    }
+}
+
+fn strip_log_times(content: &[u8]) -> Vec<u8> {
+    String::from_utf8_lossy(content)
+        .lines()
+        .filter(|line| {
+            let line = line.trim_start();
+            !(line.contains("opened on:") || line.contains("closed on:"))
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+        .into_bytes()
+}
+
+/// Scan `script` and the do-files it runs for common nondeterminism
+pub fn likely_sources(script: &Path, settings: &ReproducibilitySection) -> Vec<Source> {
+    let mut files = vec![script.to_path_buf()];
+    if let Ok(tree) = build_tree(script) {
+        files.extend(
+            tree.flatten()
+                .into_iter()
+                .filter(|d| d.exists && !d.is_circular)
+                .map(|d| d.path),
+        );
+    }
+    let mut seen = BTreeSet::new();
+    files.retain(|f| seen.insert(f.clone()));
+
+    let scanned: Vec<(PathBuf, String)> = files
+        .into_iter()
+        .filter_map(|f| std::fs::read_to_string(&f).ok().map(|text| (f, text)))
+        .collect();
+    scan_sources(&scanned, settings)
+}
+
+fn scan_sources(files: &[(PathBuf, String)], settings: &ReproducibilitySection) -> Vec<Source> {
+    static RANDOM: OnceLock<Regex> = OnceLock::new();
+    static SEED: OnceLock<Regex> = OnceLock::new();
+    static SORT: OnceLock<Regex> = OnceLock::new();
+    static SORTSEED: OnceLock<Regex> = OnceLock::new();
+    static CLOCK: OnceLock<Regex> = OnceLock::new();
+    static MERGE_MM: OnceLock<Regex> = OnceLock::new();
+    let random = RANDOM.get_or_init(|| {
+        Regex::new(
+            r"\b(runiform(int)?|rnormal|rbinomial|rpoisson|rbeta|rgamma|rchi2|rexponential|rlogistic|rweibull|rhypergeometric|rnbinomial|rigaussian|rt)\s*\(|^\s*(sample|bsample|bootstrap|simulate|permute|splitsample)\b",
+        )
+        .unwrap()
+    });
+    let seed = SEED.get_or_init(|| Regex::new(r"^\s*set\s+seed\b").unwrap());
+    let sort = SORT.get_or_init(|| Regex::new(r"^\s*(by\w*\s+[^:]*:\s*)?sort\b").unwrap());
+    let sortseed = SORTSEED.get_or_init(|| Regex::new(r"^\s*set\s+sortseed\b").unwrap());
+    let clock =
+        CLOCK.get_or_init(|| Regex::new(r#"c\(current_(date|time)\)|\$S_(DATE|TIME)\b"#).unwrap());
+    let merge_mm = MERGE_MM.get_or_init(|| Regex::new(r"^\s*merge\s+m:m\b").unwrap());
+
+    let lines = || {
+        files.iter().flat_map(|(file, text)| {
+            text.lines()
+                .enumerate()
+                .map(move |(i, line)| (file, i + 1, line))
+        })
+    };
+    let seeded = settings.seed.is_some() || lines().any(|(_, _, l)| seed.is_match(l));
+    let sort_seeded = settings.sortseed.is_some() || lines().any(|(_, _, l)| sortseed.is_match(l));
+
+    let mut sources = Vec::new();
+    let mut push = |file: &PathBuf, line: usize, reason: &str| {
+        sources.push(Source {
+            file: file.clone(),
+            line,
+            reason: reason.to_string(),
+        })
+    };
+    for (file, n, line) in lines() {
+        let code = line.split("//").next().unwrap_or(line);
+        if code.trim_start().starts_with('*') {
+            continue;
+        }
+        if !seeded && random.is_match(code) {
+            push(
+                file,
+                n,
+                "random numbers without a seed (add `set seed` or [reproducibility] seed)",
+            );
+        }
+        if !sort_seeded && sort.is_match(code) && !code.contains("stable") {
+            push(
+                file,
+                n,
+                "`sort` breaks ties randomly (add `, stable` or [reproducibility] sortseed)",
+            );
+        }
+        if clock.is_match(code) {
+            push(file, n, "uses the current date or time");
+        }
+        if merge_mm.is_match(code) {
+            push(file, n, "`merge m:m` pairs observations in sort order");
+        }
+    }
+    sources
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn outputs(files: &[(&str, &str)], written: &[&str]) -> RunOutputs {
+        RunOutputs {
+            after: files
+                .iter()
+                .map(|(p, h)| (PathBuf::from(p), h.to_string()))
+                .collect(),
+            written: written.iter().map(PathBuf::from).collect(),
+        }
+    }
+
+    #[test]
+    fn test_compare_written_files() {
+        let first = outputs(
+            &[("in.csv", "0"), ("out.dta", "a"), ("tmp1.csv", "x")],
+            &["out.dta", "tmp1.csv"],
+        );
+        let second = outputs(
+            &[("in.csv", "0"), ("out.dta", "a"), ("tmp2.csv", "x")],
+            &["out.dta", "tmp2.csv"],
+        );
+
+        let report = compare(&first, &second, &[]);
+        assert_eq!(report.outputs.len(), 3);
+        let kinds: Vec<_> = report
+            .differences
+            .iter()
+            .map(|d| (d.path.to_str().unwrap(), d.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("tmp1.csv", DiffKind::OnlyFirst),
+                ("tmp2.csv", DiffKind::OnlySecond)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compare_declared_outputs() {
+        let first = outputs(&[("out.dta", "a"), ("table.tex", "t1")], &["table.tex"]);
+        let second = outputs(&[("out.dta", "a"), ("table.tex", "t2")], &["table.tex"]);
+
+        let declared = vec![PathBuf::from("out.dta"), PathBuf::from("table.tex")];
+        let report = compare(&first, &second, &declared);
+        assert_eq!(report.differences.len(), 1);
+        assert_eq!(report.differences[0].kind, DiffKind::Differs);
+
+        let report = compare(&first, &second, &[PathBuf::from("figure.pdf")]);
+        assert_eq!(report.differences[0].kind, DiffKind::NotWritten);
+    }
+
+    #[test]
+    fn test_snapshot_skips_state_dirs_and_tracks_writes() {
+        let temp = TempDir::new().unwrap();
+        std::fs::create_dir_all(temp.path().join(".stacy/cache")).unwrap();
+        std::fs::write(temp.path().join(".stacy/cache/build.json"), "{}").unwrap();
+        std::fs::write(temp.path().join("analysis.do"), "display 1").unwrap();
+        let before = snapshot(temp.path()).unwrap();
+        assert_eq!(before.len(), 1);
+
+        std::fs::write(temp.path().join("out.csv"), "1,2").unwrap();
+        let run = RunOutputs::between(&before, snapshot(temp.path()).unwrap());
+        assert_eq!(run.written, BTreeSet::from([PathBuf::from("out.csv")]));
+
+        let copy = TempDir::new().unwrap();
+        copy_project(temp.path(), copy.path()).unwrap();
+        assert!(copy.path().join("out.csv").is_file());
+        assert!(!copy.path().join(".stacy").exists());
+    }
+
+    #[test]
+    fn test_normalized_hash_ignores_timestamps() {
+        let temp = TempDir::new().unwrap();
+        let dta = |stamp: &str| {
+            format!(
+                "<stata_dta><header><release>118</release><timestamp>\x11{}</timestamp></header>data",
+                stamp
+            )
+        };
+        let a = temp.path().join("a.dta");
+        let b = temp.path().join("b.dta");
+        std::fs::write(&a, dta(" 1 Jan 2026 10:00")).unwrap();
+        std::fs::write(&b, dta(" 2 Jan 2026 11:30")).unwrap();
+        assert_eq!(normalized_hash(&a).unwrap(), normalized_hash(&b).unwrap());
+
+        let log_a = temp.path().join("a.log");
+        let log_b = temp.path().join("b.log");
+        std::fs::write(&log_a, "  opened on:  1 Jan 2026\nresult 42\n").unwrap();
+        std::fs::write(&log_b, "  opened on:  2 Jan 2026\nresult 42\n").unwrap();
+        assert_eq!(
+            normalized_hash(&log_a).unwrap(),
+            normalized_hash(&log_b).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_scan_sources() {
+        let script = PathBuf::from("analysis.do");
+        let text = "\
+gen u = runiform()
+sort id
+sort id, stable
+* sort commented out
+local today = c(current_date)
+merge m:m id using other
+";
+        let files = vec![(script.clone(), text.to_string())];
+
+        let sources = scan_sources(&files, &ReproducibilitySection::default());
+        let lines: Vec<_> = sources.iter().map(|s| s.line).collect();
+        assert_eq!(lines, vec![1, 2, 5, 6]);
+
+        let seeded = ReproducibilitySection {
+            seed: Some(1),
+            sortseed: Some(1),
+            ..Default::default()
+        };
+        let lines: Vec<_> = scan_sources(&files, &seeded)
+            .iter()
+            .map(|s| s.line)
+            .collect();
+        assert_eq!(lines, vec![5, 6]);
+    }
+}
@@ -0,0 +1,228 @@
+//! Cron expressions, as tasks declare them in `schedule`
+//!
+//! The five standard fields (minute, hour, day of month, month, day of
+//! week) with `*`, lists, ranges and steps, month and weekday names, and the
+//! `@hourly`-style shorthands. Each field is expanded to the values it
+//! matches, which is what launchd and systemd timers need.
+
+use crate::error::{Error, Result};
+
+/// A parsed cron expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    /// The expression as written
+    pub expression: String,
+    pub minutes: Field,
+    pub hours: Field,
+    pub days: Field,
+    pub months: Field,
+    /// 0 (Sunday) to 6
+    pub weekdays: Field,
+}
+
+/// The values a field matches: every value (`*`) or those listed, sorted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Field {
+    Every,
+    Values(Vec<u32>),
+}
+
+impl Field {
+    pub fn is_every(&self) -> bool {
+        matches!(self, Field::Every)
+    }
+}
+
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+impl CronSchedule {
+    /// Parse a cron expression (`0 3 * * *`, `30 6 * * mon-fri`, `@daily`)
+    pub fn parse(expression: &str) -> Result<Self> {
+        let invalid = |reason: String| {
+            Error::Config(format!("Invalid schedule '{}': {}", expression, reason))
+        };
+        let expanded = match expression.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            other if other.starts_with('@') => {
+                return Err(invalid(format!(
+                    "unknown shorthand {} (use @hourly, @daily, @weekly, @monthly or @yearly)",
+                    other
+                )))
+            }
+            other => other,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(invalid(format!(
+                "expected 5 fields (minute hour day month weekday), found {}",
+                fields.len()
+            )));
+        }
+        let field = |text: &str, name: &str, min: u32, max: u32, names: &[&str]| {
+            parse_field(text, min, max, names)
+                .map_err(|reason| invalid(format!("{}: {}", name, reason)))
+        };
+        let mut weekdays = field(fields[4], "day of week", 0, 7, &WEEKDAYS)?;
+        // 7 is Sunday too
+        if let Field::Values(values) = &mut weekdays {
+            for value in values.iter_mut() {
+                *value %= 7;
+            }
+            values.sort_unstable();
+            values.dedup();
+        }
+        Ok(Self {
+            expression: expression.trim().to_string(),
+            minutes: field(fields[0], "minute", 0, 59, &[])?,
+            hours: field(fields[1], "hour", 0, 23, &[])?,
+            days: field(fields[2], "day of month", 1, 31, &[])?,
+            months: field(fields[3], "month", 1, 12, &MONTHS)?,
+            weekdays,
+        })
+    }
+
+    /// The schedule as one or two schedules that each match every field they
+    /// restrict. Cron runs a job restricted by both day of month and day of
+    /// week on days matching either; schedulers that require all fields to
+    /// match need the two halves separately.
+    pub fn alternatives(&self) -> Vec<CronSchedule> {
+        if self.days.is_every() || self.weekdays.is_every() {
+            return vec![self.clone()];
+        }
+        vec![
+            CronSchedule {
+                weekdays: Field::Every,
+                ..self.clone()
+            },
+            CronSchedule {
+                days: Field::Every,
+                ..self.clone()
+            },
+        ]
+    }
+}
+
+/// Parse one field: `*`, or a comma list of values, `a-b` ranges and steps
+/// (`*/15`, `1-10/2`)
+fn parse_field(
+    text: &str,
+    min: u32,
+    max: u32,
+    names: &[&str],
+) -> std::result::Result<Field, String> {
+    let value = |s: &str| -> std::result::Result<u32, String> {
+        let lower = s.to_lowercase();
+        if let Some(i) = names.iter().position(|name| *name == lower) {
+            // Months count from 1, weekdays from 0
+            return Ok(i as u32 + min);
+        }
+        let n: u32 = s.parse().map_err(|_| format!("'{}' is not a number", s))?;
+        if n < min || n > max {
+            return Err(format!("{} is out of range {}-{}", n, min, max));
+        }
+        Ok(n)
+    };
+
+    if text == "*" {
+        return Ok(Field::Every);
+    }
+    let mut values = Vec::new();
+    for part in text.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step
+                    .parse()
+                    .ok()
+                    .filter(|s| *s > 0)
+                    .ok_or_else(|| format!("'{}' is not a valid step", step))?;
+                (range, step)
+            }
+            None => (part, 1),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((a, b)) = range.split_once('-') {
+            (value(a)?, value(b)?)
+        } else if step > 1 {
+            // `5/15`: from 5 to the end
+            (value(range)?, max)
+        } else {
+            let n = value(range)?;
+            (n, n)
+        };
+        if start > end {
+            return Err(format!("range {} is backwards", range));
+        }
+        values.extend((start..=end).step_by(step as usize));
+    }
+    values.sort_unstable();
+    values.dedup();
+    Ok(Field::Values(values))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cron_expressions() {
+        let nightly = CronSchedule::parse("0 3 * * *").unwrap();
+        assert_eq!(nightly.minutes, Field::Values(vec![0]));
+        assert_eq!(nightly.hours, Field::Values(vec![3]));
+        assert!(
+            nightly.days.is_every() && nightly.months.is_every() && nightly.weekdays.is_every()
+        );
+
+        let weekdays = CronSchedule::parse("*/20 6-8 * jan,JUL mon-fri").unwrap();
+        assert_eq!(weekdays.minutes, Field::Values(vec![0, 20, 40]));
+        assert_eq!(weekdays.hours, Field::Values(vec![6, 7, 8]));
+        assert_eq!(weekdays.months, Field::Values(vec![1, 7]));
+        assert_eq!(weekdays.weekdays, Field::Values(vec![1, 2, 3, 4, 5]));
+
+        let sunday = CronSchedule::parse("@weekly").unwrap();
+        assert_eq!(sunday.weekdays, Field::Values(vec![0]));
+        assert_eq!(sunday.expression, "@weekly");
+        assert_eq!(
+            CronSchedule::parse("0 0 * * 7").unwrap().weekdays,
+            Field::Values(vec![0])
+        );
+    }
+
+    #[test]
+    fn test_invalid_cron_expressions() {
+        for (expression, reason) in [
+            ("0 3 * *", "expected 5 fields"),
+            ("60 3 * * *", "minute: 60 is out of range 0-59"),
+            ("0 3 * * funday", "day of week: 'funday' is not a number"),
+            ("*/0 * * * *", "not a valid step"),
+            ("0 5-2 * * *", "backwards"),
+            ("@sometimes", "unknown shorthand"),
+        ] {
+            let err = CronSchedule::parse(expression).unwrap_err().to_string();
+            assert!(err.contains(reason), "{}: {}", expression, err);
+        }
+    }
+
+    #[test]
+    fn test_day_and_weekday_split_into_alternatives() {
+        let schedule = CronSchedule::parse("0 3 1 * mon").unwrap();
+        let alternatives = schedule.alternatives();
+        assert_eq!(alternatives.len(), 2);
+        assert!(alternatives[0].weekdays.is_every());
+        assert!(alternatives[1].days.is_every());
+        assert_eq!(
+            CronSchedule::parse("0 3 1 * *")
+                .unwrap()
+                .alternatives()
+                .len(),
+            1
+        );
+    }
+}
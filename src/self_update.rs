@@ -0,0 +1,422 @@
+//! Replace the running stacy binary with a release from GitHub
+//!
+//! Reads the same GitHub Releases feed as [`crate::update_check`], downloads
+//! the archive for this platform, checks it against the release's
+//! `checksums.txt`, and swaps the binary in place. The new binary is written
+//! next to the old one and renamed over it, so an interrupted update leaves
+//! the old binary intact.
+//!
+//! `checksums.txt` itself is trusted only when `checksums.txt.sig` carries a
+//! valid Ed25519 signature of it by one of the [`RELEASE_KEYS`] built into
+//! this binary, so a compromised release page can't swap both an archive and
+//! its checksum. The signature file has one `<key id> <signature>` line (hex)
+//! per signing key; while keys rotate, releases are signed with the old and
+//! the new key, and binaries that know either accept them.
+
+use crate::error::{Error, Result};
+use crate::packages::http::StacyHttpClient;
+use crate::project::user_config::UpdateChannel;
+use crate::update_check::{compare_versions, RELEASES_API};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Checksum manifest published with every release
+const CHECKSUMS_ASSET: &str = "checksums.txt";
+
+/// Signatures of the checksum manifest
+const SIGNATURE_ASSET: &str = "checksums.txt.sig";
+
+/// Public keys (hex) whose signatures on a release are trusted. A key's id is
+/// its first 16 hex digits. To rotate, add the new key here, sign releases
+/// with both keys until binaries without the new one are out of use, then
+/// drop the old key.
+///
+/// Each entry is the public half of an Ed25519 key made with
+/// `openssl genpkey -algorithm ed25519`, whose PEM private key exists only as
+/// the release workflow's `STACY_SIGNING_KEY` secret. The hex is what
+/// `openssl pkey -in key.pem -pubout -outform DER | tail -c 32 | xxd -p -c 32`
+/// prints; the release workflow stops before signing if the secret's public
+/// half is not listed here.
+pub const RELEASE_KEYS: &[&str] =
+    &["f0257074ac7cf66b63620b3aa6d81adf528cb77a9736e2de5f85c89d5cfe5053"];
+
+/// A published release and its downloadable files
+#[derive(Debug, Clone, Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    #[serde(default)]
+    pub draft: bool,
+    #[serde(default)]
+    pub assets: Vec<ReleaseAsset>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReleaseAsset {
+    pub name: String,
+    pub browser_download_url: String,
+}
+
+impl Release {
+    /// Version without the leading `v`
+    pub fn version(&self) -> &str {
+        self.tag_name.strip_prefix('v').unwrap_or(&self.tag_name)
+    }
+
+    fn asset(&self, name: &str) -> Result<&ReleaseAsset> {
+        self.assets.iter().find(|a| a.name == name).ok_or_else(|| {
+            Error::Config(format!(
+                "Release {} has no asset {} (no build for this platform?)",
+                self.tag_name, name
+            ))
+        })
+    }
+
+    /// Archive holding the binary for this platform
+    pub fn platform_asset_name(&self) -> Result<String> {
+        let target = platform_target().ok_or_else(|| {
+            Error::Config(format!(
+                "No prebuilt stacy binaries for {}-{}; build from source instead",
+                std::env::consts::ARCH,
+                std::env::consts::OS
+            ))
+        })?;
+        let extension = if cfg!(windows) { "zip" } else { "tar.gz" };
+        Ok(format!("stacy-{}-{}.{}", self.tag_name, target, extension))
+    }
+}
+
+/// The release target triple for this build, as used in asset names
+pub fn platform_target() -> Option<&'static str> {
+    match (std::env::consts::ARCH, std::env::consts::OS) {
+        ("x86_64", "linux") => Some("x86_64-unknown-linux-gnu"),
+        ("aarch64", "linux") => Some("aarch64-unknown-linux-gnu"),
+        ("x86_64", "macos") => Some("x86_64-apple-darwin"),
+        ("aarch64", "macos") => Some("aarch64-apple-darwin"),
+        ("x86_64", "windows") => Some("x86_64-pc-windows-msvc"),
+        _ => None,
+    }
+}
+
+/// Fetch the release tagged `version` (with or without `v`), or else the
+/// latest one on `channel`
+pub fn fetch_release(
+    client: &StacyHttpClient,
+    version: Option<&str>,
+    channel: UpdateChannel,
+) -> Result<Release> {
+    let url = match (version, channel) {
+        (Some(v), _) => format!("{}/tags/v{}", RELEASES_API, v.trim_start_matches('v')),
+        (None, UpdateChannel::Stable) => format!("{}/latest", RELEASES_API),
+        (None, UpdateChannel::Beta) => format!("{}?per_page=30", RELEASES_API),
+    };
+    let body = client.download_text(&url).map_err(|e| match (version, e) {
+        (Some(v), Error::Config(_)) => Error::Config(format!("No stacy release v{}", v)),
+        (_, e) => e,
+    })?;
+    let parse_error =
+        |e: serde_json::Error| Error::Parse(format!("Unexpected response from {}: {}", url, e));
+
+    if version.is_some() || channel == UpdateChannel::Stable {
+        return serde_json::from_str(&body).map_err(parse_error);
+    }
+
+    let releases: Vec<Release> = serde_json::from_str(&body).map_err(parse_error)?;
+    releases
+        .into_iter()
+        .filter(|r| !r.draft)
+        .reduce(|newest, r| {
+            if compare_versions(&newest.tag_name, &r.tag_name) {
+                r
+            } else {
+                newest
+            }
+        })
+        .ok_or_else(|| Error::Config("No stacy releases published".to_string()))
+}
+
+/// Download this platform's binary from `release`, verified against the
+/// signed release checksums, and extract it into `dest_dir`. Returns its path.
+pub fn download_binary(
+    client: &StacyHttpClient,
+    release: &Release,
+    dest_dir: &Path,
+) -> Result<PathBuf> {
+    let asset_name = release.platform_asset_name()?;
+    let asset = release.asset(&asset_name)?;
+    let checksums = client.download_text(&release.asset(CHECKSUMS_ASSET)?.browser_download_url)?;
+    let signature_asset = release.asset(SIGNATURE_ASSET).map_err(|_| {
+        Error::Integrity(format!(
+            "Release {} is not signed (no {})",
+            release.tag_name, SIGNATURE_ASSET
+        ))
+    })?;
+    let signatures = client.download_text(&signature_asset.browser_download_url)?;
+    verify_signature(&checksums, &signatures, RELEASE_KEYS).map_err(|e| match e {
+        Error::Integrity(msg) => Error::Integrity(format!(
+            "{} of {}: {}",
+            CHECKSUMS_ASSET, release.tag_name, msg
+        )),
+        e => e,
+    })?;
+    let expected = find_checksum(&checksums, &asset_name).ok_or_else(|| {
+        Error::Integrity(format!(
+            "{} of {} lists no checksum for {}",
+            CHECKSUMS_ASSET, release.tag_name, asset_name
+        ))
+    })?;
+
+    let archive = client.download_bytes(&asset.browser_download_url)?;
+    let actual = hex::encode(Sha256::digest(&archive));
+    if !actual.eq_ignore_ascii_case(expected) {
+        return Err(Error::Integrity(format!(
+            "Checksum mismatch for {}: expected {}, got {}",
+            asset_name, expected, actual
+        )));
+    }
+
+    let archive_path = dest_dir.join(&asset_name);
+    std::fs::write(&archive_path, &archive)?;
+    extract_archive(&archive_path, dest_dir)?;
+
+    let binary = dest_dir.join(binary_name());
+    if !binary.is_file() {
+        return Err(Error::Integrity(format!(
+            "{} does not contain {}",
+            asset_name,
+            binary_name()
+        )));
+    }
+    Ok(binary)
+}
+
+/// Check `message` against a signature file (`<key id> <signature>` per line,
+/// hex). Succeeds with the id of the first key in `keys` that signed it;
+/// signatures by keys not in `keys` are ignored.
+pub fn verify_signature<'k>(message: &str, signatures: &str, keys: &[&'k str]) -> Result<&'k str> {
+    for line in signatures.lines() {
+        let Some((id, signature)) = line.trim().split_once(char::is_whitespace) else {
+            continue;
+        };
+        let Some(key) = keys.iter().find(|key| key.get(..16) == Some(id)) else {
+            continue;
+        };
+        let public_key = hex::decode(key)
+            .ok()
+            .and_then(|bytes| ed25519_compact::PublicKey::from_slice(&bytes).ok())
+            .ok_or_else(|| Error::Integrity(format!("Invalid release key {}", id)))?;
+        let valid = hex::decode(signature.trim())
+            .ok()
+            .and_then(|bytes| ed25519_compact::Signature::from_slice(&bytes).ok())
+            .is_some_and(|signature| public_key.verify(message, &signature).is_ok());
+        if !valid {
+            return Err(Error::Integrity(format!(
+                "signature by key {} does not match",
+                id
+            )));
+        }
+        return Ok(key.get(..16).unwrap_or(key));
+    }
+    Err(Error::Integrity(
+        "not signed by any key this stacy trusts".to_string(),
+    ))
+}
+
+/// Look up a file's hash in `sha256sum` output (`<hash>  <name>` per line)
+pub fn find_checksum<'a>(checksums: &'a str, file_name: &str) -> Option<&'a str> {
+    checksums.lines().find_map(|line| {
+        let (hash, name) = line.trim().split_once(char::is_whitespace)?;
+        let name = name.trim_start().trim_start_matches('*');
+        (name == file_name).then_some(hash)
+    })
+}
+
+/// Unpack a release archive with the system `tar` (bsdtar on Windows reads
+/// zip archives too), so stacy needs no archive code of its own.
+fn extract_archive(archive: &Path, dest_dir: &Path) -> Result<()> {
+    let flags = if cfg!(windows) { "-xf" } else { "-xzf" };
+    let status = Command::new("tar")
+        .arg(flags)
+        .arg(archive)
+        .arg("-C")
+        .arg(dest_dir)
+        .status()
+        .map_err(|e| Error::Execution(format!("Failed to run tar: {}", e)))?;
+    if !status.success() {
+        return Err(Error::Execution(format!(
+            "tar could not extract {}",
+            archive.display()
+        )));
+    }
+    Ok(())
+}
+
+fn binary_name() -> &'static str {
+    if cfg!(windows) {
+        "stacy.exe"
+    } else {
+        "stacy"
+    }
+}
+
+/// Atomically replace the binary at `target` with `new_binary`.
+///
+/// The new binary is first copied into `target`'s directory, so the final
+/// rename never crosses filesystems. Windows won't overwrite a running
+/// executable, but it will rename one: the old binary moves aside to
+/// `stacy.old.exe` and is removed on the next update.
+pub fn replace_binary(new_binary: &Path, target: &Path) -> Result<()> {
+    let dir = target.parent().ok_or_else(|| {
+        Error::Config(format!(
+            "Cannot determine directory of {}",
+            target.display()
+        ))
+    })?;
+    let staged = dir.join(format!(".{}.new", binary_name()));
+    std::fs::copy(new_binary, &staged).map_err(|e| replace_error(target, e))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o755))
+            .map_err(|e| replace_error(target, e))?;
+    }
+
+    if cfg!(windows) {
+        let old = dir.join("stacy.old.exe");
+        let _ = std::fs::remove_file(&old);
+        std::fs::rename(target, &old).map_err(|e| replace_error(target, e))?;
+    }
+
+    std::fs::rename(&staged, target).map_err(|e| {
+        let _ = std::fs::remove_file(&staged);
+        replace_error(target, e)
+    })
+}
+
+fn replace_error(target: &Path, e: std::io::Error) -> Error {
+    Error::Io(std::io::Error::new(
+        e.kind(),
+        format!("Failed to replace {}: {}", target.display(), e),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_find_checksum() {
+        let checksums = "\
+abc123  stacy-v1.2.0-x86_64-unknown-linux-gnu.tar.gz
+def456 *stacy-v1.2.0-x86_64-pc-windows-msvc.zip
+";
+        assert_eq!(
+            find_checksum(checksums, "stacy-v1.2.0-x86_64-unknown-linux-gnu.tar.gz"),
+            Some("abc123")
+        );
+        assert_eq!(
+            find_checksum(checksums, "stacy-v1.2.0-x86_64-pc-windows-msvc.zip"),
+            Some("def456")
+        );
+        assert_eq!(find_checksum(checksums, "stacy-v1.2.0.tar.gz"), None);
+    }
+
+    /// Key pair from a fixed seed, with its public key in hex
+    fn test_key(seed: u8) -> (ed25519_compact::KeyPair, String) {
+        let pair = ed25519_compact::KeyPair::from_seed(ed25519_compact::Seed::new([seed; 32]));
+        let public = hex::encode(pair.pk.as_ref());
+        (pair, public)
+    }
+
+    fn signature_line(pair: &ed25519_compact::KeyPair, public: &str, message: &str) -> String {
+        format!(
+            "{} {}\n",
+            &public[..16],
+            hex::encode(pair.sk.sign(message, None).as_ref())
+        )
+    }
+
+    #[test]
+    fn test_verify_signature() {
+        let checksums = "abc123  stacy-v1.2.0-x86_64-unknown-linux-gnu.tar.gz\n";
+        let (pair, public) = test_key(1);
+        let signatures = signature_line(&pair, &public, checksums);
+
+        let id = verify_signature(checksums, &signatures, &[public.as_str()]).unwrap();
+        assert_eq!(id, &public[..16]);
+
+        // Another archive hash, the same signature
+        let tampered = "fff999  stacy-v1.2.0-x86_64-unknown-linux-gnu.tar.gz\n";
+        assert!(verify_signature(tampered, &signatures, &[public.as_str()]).is_err());
+
+        // Signed by a key this binary doesn't know
+        let (_, other) = test_key(2);
+        assert!(verify_signature(checksums, &signatures, &[other.as_str()]).is_err());
+        assert!(verify_signature(checksums, "", &[public.as_str()]).is_err());
+    }
+
+    #[test]
+    fn test_verify_signature_during_rotation() {
+        let checksums = "abc123  stacy-v1.3.0-aarch64-apple-darwin.tar.gz\n";
+        let (old_pair, old) = test_key(1);
+        let (new_pair, new) = test_key(2);
+        let signatures = format!(
+            "{}{}",
+            signature_line(&old_pair, &old, checksums),
+            signature_line(&new_pair, &new, checksums)
+        );
+
+        // Binaries that know only the old key, only the new one, or both
+        assert!(verify_signature(checksums, &signatures, &[old.as_str()]).is_ok());
+        assert!(verify_signature(checksums, &signatures, &[new.as_str()]).is_ok());
+        assert!(verify_signature(checksums, &signatures, &[new.as_str(), old.as_str()]).is_ok());
+    }
+
+    #[test]
+    fn test_release_keys_valid() {
+        for key in RELEASE_KEYS {
+            let bytes = hex::decode(key).unwrap();
+            assert!(ed25519_compact::PublicKey::from_slice(&bytes).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_release_asset_names() {
+        let release: Release = serde_json::from_str(
+            r#"{"tag_name": "v1.2.0", "assets": [{"name": "checksums.txt", "browser_download_url": "https://example.com/checksums.txt"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(release.version(), "1.2.0");
+        assert!(release.asset(CHECKSUMS_ASSET).is_ok());
+        assert!(release.asset("missing").is_err());
+
+        if let Some(target) = platform_target() {
+            let name = release.platform_asset_name().unwrap();
+            assert!(name.starts_with("stacy-v1.2.0-"));
+            assert!(name.contains(target));
+        }
+    }
+
+    #[test]
+    fn test_replace_binary() {
+        let temp = TempDir::new().unwrap();
+        let target = temp.path().join(binary_name());
+        let new = temp.path().join("download");
+        std::fs::write(&target, "old").unwrap();
+        std::fs::write(&new, "new").unwrap();
+
+        replace_binary(&new, &target).unwrap();
+
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "new");
+        assert!(!temp.path().join(format!(".{}.new", binary_name())).exists());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&target).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o755);
+        }
+    }
+}
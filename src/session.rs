@@ -0,0 +1,60 @@
+//! The command this process runs, from start to exit
+//!
+//! `main` starts the session before dispatching the command and finishes it
+//! when the command returns; commands that end the process themselves go
+//! through [`exit`]. Finishing records the invocation with each feature that
+//! keeps track of it: the project's metrics counters, the run history and
+//! (opt-in) telemetry.
+
+use std::sync::OnceLock;
+use std::time::Instant;
+
+/// The command running in this process, and when it started
+static SESSION: OnceLock<(&'static str, Instant)> = OnceLock::new();
+
+/// Start timing `command`. Called once, before the command runs.
+pub fn begin(command: &'static str) {
+    let _ = SESSION.set((command, Instant::now()));
+}
+
+/// Record the end of the command started with [`begin`]
+pub fn finish(error_category: Option<&str>) {
+    let Some(&(command, started)) = SESSION.get() else {
+        return;
+    };
+    let elapsed = started.elapsed();
+    crate::metrics::counters::finish(command, error_category.is_none(), elapsed);
+    crate::history::finish(command);
+    crate::telemetry::record(command, elapsed, error_category);
+}
+
+/// Record the command's outcome from its exit code, then exit.
+///
+/// Commands that end the process themselves go through here so their
+/// invocation is recorded like any other.
+pub fn exit(code: i32) -> ! {
+    let code = crate::history::regression::gate(code);
+    finish(exit_category(code));
+    std::process::exit(crate::error::exit_codes::active().remap(code))
+}
+
+/// Error category for a process exit code (see `[exit_codes]` in the schema)
+pub fn exit_category(code: i32) -> Option<&'static str> {
+    match code {
+        0 => None,
+        _ => Some(crate::error::exit_codes::kind_for_code(code).map_or("other", |kind| kind.name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_category() {
+        assert_eq!(exit_category(0), None);
+        assert_eq!(exit_category(2), Some("syntax_error"));
+        assert_eq!(exit_category(10), Some("environment_error"));
+        assert_eq!(exit_category(42), Some("other"));
+    }
+}
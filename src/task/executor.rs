@@ -2,13 +2,18 @@
 //!
 //! Handles sequential and parallel execution of tasks defined in the task graph.
 
+use crate::artifacts::{Artifacts, ProducerKind};
+use crate::cache::detect::CacheStatus;
+use crate::cache::step::{StepCache, StepKey};
 use crate::error::{Error, Result};
 use crate::executor::log_policy::LogPolicy;
 use crate::executor::StataExecutor;
 use crate::project::config::TaskDef;
+use crate::task::run_state::RunState;
+use crate::task::selection::Selection;
 use crate::task::TaskGraph;
 use std::collections::HashMap;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
@@ -27,6 +32,8 @@ pub struct ScriptResult {
     pub duration: Duration,
     /// Path to the log file
     pub log_file: std::path::PathBuf,
+    /// Whether the result came from the build cache instead of a run
+    pub cached: bool,
 }
 
 /// Result of running a task (which may include multiple scripts)
@@ -36,7 +43,7 @@ pub struct TaskResult {
     pub name: String,
     /// Whether all scripts succeeded
     pub success: bool,
-    /// Exit code (0 if all succeeded, first failure code otherwise)
+    /// Exit code (0 if all succeeded, the highest failure code otherwise)
     pub exit_code: i32,
     /// Total duration for all scripts
     pub duration: Duration,
@@ -58,9 +65,9 @@ impl TaskResult {
 
     /// Add a script result
     pub fn add_result(&mut self, result: ScriptResult) {
-        if !result.success && self.success {
+        if !result.success {
             self.success = false;
-            self.exit_code = result.exit_code;
+            self.exit_code = self.exit_code.max(result.exit_code);
         }
         self.duration += result.duration;
         self.script_results.push(result);
@@ -96,6 +103,16 @@ pub struct TaskExecutor<'a> {
     args: HashMap<String, String>,
     /// What happens to each script's log once it has run
     log_policy: LogPolicy,
+    /// Build cache consulted before each script (`--cache`)
+    cache: Option<&'a StepCache>,
+    /// Registry the declared outputs of successful scripts are recorded in
+    artifacts: Option<&'a Artifacts>,
+    /// Run the rest of a sequential task after a script fails
+    keep_going: bool,
+    /// Scripts completed so far, and those a resumed run skips
+    run_state: Option<&'a RunState>,
+    /// The scripts to run, when `--from` or `--only` pick some
+    selection: Option<&'a Selection>,
 }
 
 impl<'a> TaskExecutor<'a> {
@@ -107,6 +124,11 @@ impl<'a> TaskExecutor<'a> {
             project_root,
             args: HashMap::new(),
             log_policy: LogPolicy::new(),
+            cache: None,
+            artifacts: None,
+            keep_going: false,
+            run_state: None,
+            selection: None,
         }
     }
 
@@ -122,6 +144,40 @@ impl<'a> TaskExecutor<'a> {
         self
     }
 
+    /// Skip scripts whose cache entry is still valid, and record the ones that
+    /// run. The caller saves the cache once the task is done.
+    pub fn with_cache(mut self, cache: &'a StepCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Register the declared outputs of each script that succeeds. The caller
+    /// saves the registry once the task is done.
+    pub fn with_artifacts(mut self, artifacts: &'a Artifacts) -> Self {
+        self.artifacts = Some(artifacts);
+        self
+    }
+
+    /// Keep running the remaining entries of a sequential task after one
+    /// fails (`--keep-going`); the result still fails
+    pub fn with_keep_going(mut self, keep_going: bool) -> Self {
+        self.keep_going = keep_going;
+        self
+    }
+
+    /// Record each script that succeeds, and skip those a resumed run
+    /// completed already (`--resume`)
+    pub fn with_run_state(mut self, run_state: &'a RunState) -> Self {
+        self.run_state = Some(run_state);
+        self
+    }
+
+    /// Run only the scripts `--from` and `--only` select
+    pub fn with_selection(mut self, selection: &'a Selection) -> Self {
+        self.selection = Some(selection);
+        self
+    }
+
     /// Execute a task by name
     pub fn execute(&self, task_name: &str) -> Result<TaskResult> {
         let task = self.graph.get_task(task_name).ok_or_else(|| {
@@ -138,19 +194,28 @@ impl<'a> TaskExecutor<'a> {
             Error::Config(msg)
         })?;
 
-        self.execute_task(task_name, task)
+        self.execute_task(task_name, task, &[task_name.to_string()])
     }
 
-    /// Execute a task definition
-    fn execute_task(&self, name: &str, task: &TaskDef) -> Result<TaskResult> {
+    /// Execute a task definition. `path` names the steps from the task run
+    /// down to this one.
+    fn execute_task(&self, name: &str, task: &TaskDef, path: &[String]) -> Result<TaskResult> {
+        let selected = self
+            .selection
+            .is_none_or(|selection| selection.includes(path));
         match task {
-            TaskDef::Simple(script) => self.execute_script(name, script),
-            TaskDef::Sequential(tasks) => self.execute_sequential(name, tasks),
+            TaskDef::Simple(_) if !selected => Ok(TaskResult::empty(name)),
+            TaskDef::Simple(script) => self.execute_script(name, script, &[], &[]),
+            TaskDef::Sequential(tasks) => self.execute_sequential(name, tasks, path),
             TaskDef::Complex(complex) => {
                 if let Some(ref parallel) = complex.parallel {
-                    self.execute_parallel(name, parallel)
+                    self.execute_parallel(name, parallel, path)
+                } else if !selected {
+                    Ok(TaskResult::empty(name))
                 } else if let Some(ref script) = complex.script {
-                    self.execute_script(name, script)
+                    let inputs = complex.inputs.as_deref().unwrap_or_default();
+                    let outputs = complex.outputs.as_deref().unwrap_or_default();
+                    self.execute_script(name, script, inputs, outputs)
                 } else {
                     // Rejected at TaskGraph construction; guard against
                     // graphs built another way (#92).
@@ -163,8 +228,29 @@ impl<'a> TaskExecutor<'a> {
         }
     }
 
+    /// Cache key for a script run by a task: the task, the script, the
+    /// arguments it gets and the inputs it declares.
+    fn step_key(&self, name: &str, script: &Path, inputs: &[PathBuf]) -> StepKey {
+        let mut args: Vec<_> = self.args.iter().collect();
+        args.sort();
+        let definition = format!(
+            "task:{}\nscript:{}\nargs:{:?}\ninputs:{:?}",
+            name,
+            script.display(),
+            args,
+            inputs
+        );
+        StepKey::new(&definition).with_inputs(inputs.to_vec())
+    }
+
     /// Execute a single script
-    fn execute_script(&self, name: &str, script: &Path) -> Result<TaskResult> {
+    fn execute_script(
+        &self,
+        name: &str,
+        script: &Path,
+        inputs: &[PathBuf],
+        outputs: &[PathBuf],
+    ) -> Result<TaskResult> {
         let start = Instant::now();
 
         // Resolve script path relative to project root
@@ -183,18 +269,63 @@ impl<'a> TaskExecutor<'a> {
             )));
         }
 
+        if self
+            .run_state
+            .is_some_and(|state| state.is_done(name, &script_path))
+        {
+            return Ok(TaskResult::empty(name));
+        }
+
+        let key = self.step_key(name, script, inputs);
+        // A script whose outputs were removed (`stacy outputs clean`) runs
+        // again, whatever the cache says.
+        let outputs_present = outputs.iter().all(|o| self.project_root.join(o).exists());
+        if let Some(cache) = self.cache.filter(|_| outputs_present) {
+            if let CacheStatus::Hit(entry) = cache.check(&script_path, None, &key)? {
+                self.stata.report_cached(&script_path, &entry.result);
+                if let Some(state) = self.run_state.filter(|_| entry.result.success) {
+                    state.complete(name, &script_path)?;
+                }
+                let mut task_result = TaskResult::empty(name);
+                task_result.add_result(ScriptResult {
+                    name: name.to_string(),
+                    script: script_path,
+                    success: entry.result.success,
+                    exit_code: entry.result.exit_code,
+                    duration: Duration::from_secs_f64(entry.result.duration_secs),
+                    log_file: std::path::PathBuf::new(),
+                    cached: true,
+                });
+                return Ok(task_result);
+            }
+        }
+
         // Run the script with Stata executor
         let result = self
             .stata
             .run_with_args(&script_path, Some(self.project_root), &self.args)?;
 
+        if let Some(cache) = self.cache {
+            cache.record(&script_path, None, &key, &result)?;
+        }
+        if let Some(state) = self.run_state.filter(|_| result.success) {
+            state.complete(name, &script_path)?;
+        }
+        if let Some(artifacts) = self
+            .artifacts
+            .filter(|_| result.success && !outputs.is_empty())
+        {
+            artifacts.record(ProducerKind::Task, name, outputs)?;
+        }
+
         let duration = start.elapsed();
 
         // Same contract as `stacy run`: the log is internal unless the run
-        // failed. Without this every task left its log in the working directory.
+        // failed (or --log-dir/--log-name keep it). Without this every task
+        // left its log in the working directory.
         let log_file = self
             .log_policy
-            .finalize(&result.log_file, result.success)
+            .finalize_for(&script_path, &result.log_file, result.success)
             .unwrap_or_default();
 
         let script_result = ScriptResult {
@@ -204,6 +335,7 @@ impl<'a> TaskExecutor<'a> {
             exit_code: result.exit_code,
             duration,
             log_file,
+            cached: false,
         };
 
         let mut task_result = TaskResult::empty(name);
@@ -227,20 +359,25 @@ impl<'a> TaskExecutor<'a> {
     }
 
     /// Execute tasks sequentially
-    fn execute_sequential(&self, name: &str, tasks: &[String]) -> Result<TaskResult> {
+    fn execute_sequential(
+        &self,
+        name: &str,
+        tasks: &[String],
+        path: &[String],
+    ) -> Result<TaskResult> {
         let mut result = TaskResult::empty(name);
 
         for task_name in tasks {
             let task = self.resolve_entry(name, task_name)?;
 
-            let task_result = self.execute_task(task_name, &task)?;
+            let task_result = self.execute_task(task_name, &task, &step_path(path, task_name))?;
 
             // Merge results
             let failed = !task_result.success;
             result.merge(task_result);
 
-            // Stop on first failure
-            if failed {
+            // Stop on first failure, unless told to keep going
+            if failed && !self.keep_going {
                 break;
             }
         }
@@ -249,7 +386,12 @@ impl<'a> TaskExecutor<'a> {
     }
 
     /// Execute tasks in parallel using scoped threads
-    fn execute_parallel(&self, name: &str, tasks: &[String]) -> Result<TaskResult> {
+    fn execute_parallel(
+        &self,
+        name: &str,
+        tasks: &[String],
+        path: &[String],
+    ) -> Result<TaskResult> {
         if tasks.is_empty() {
             return Ok(TaskResult::empty(name));
         }
@@ -272,14 +414,17 @@ impl<'a> TaskExecutor<'a> {
                 let results = Arc::clone(&results);
                 let errors = Arc::clone(&errors);
 
-                s.spawn(move || match self.execute_task(&task_name, &task_def) {
-                    Ok(result) => {
-                        results.lock().unwrap().push(result);
-                    }
-                    Err(e) => {
-                        errors.lock().unwrap().push(e);
-                    }
-                });
+                let task_path = step_path(path, &task_name);
+                s.spawn(
+                    move || match self.execute_task(&task_name, &task_def, &task_path) {
+                        Ok(result) => {
+                            results.lock().unwrap().push(result);
+                        }
+                        Err(e) => {
+                            errors.lock().unwrap().push(e);
+                        }
+                    },
+                );
             }
         });
 
@@ -300,6 +445,13 @@ impl<'a> TaskExecutor<'a> {
     }
 }
 
+/// Path of the step `entry` of the step at `path`
+fn step_path(path: &[String], entry: &str) -> Vec<String> {
+    let mut path = path.to_vec();
+    path.push(entry.to_string());
+    path
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -324,6 +476,7 @@ mod tests {
             exit_code: 0,
             duration: Duration::from_secs(1),
             log_file: std::path::PathBuf::from("test.log"),
+            cached: false,
         });
 
         assert!(result.success);
@@ -342,6 +495,7 @@ mod tests {
             exit_code: 0,
             duration: Duration::from_secs(1),
             log_file: std::path::PathBuf::from("test.log"),
+            cached: false,
         });
         result.add_result(ScriptResult {
             name: "script2".to_string(),
@@ -350,6 +504,7 @@ mod tests {
             exit_code: 1,
             duration: Duration::from_secs(2),
             log_file: std::path::PathBuf::from("test2.log"),
+            cached: false,
         });
 
         assert!(!result.success);
@@ -359,6 +514,26 @@ mod tests {
         assert_eq!(result.duration, Duration::from_secs(3));
     }
 
+    #[test]
+    fn test_task_result_exit_code_is_worst_failure() {
+        let mut result = TaskResult::empty("test");
+        for (script, exit_code) in [("a.do", 1), ("b.do", 4), ("c.do", 2)] {
+            result.add_result(ScriptResult {
+                name: script.to_string(),
+                script: std::path::PathBuf::from(script),
+                success: false,
+                exit_code,
+                duration: Duration::from_secs(1),
+                log_file: std::path::PathBuf::new(),
+                cached: false,
+            });
+        }
+
+        assert!(!result.success);
+        assert_eq!(result.exit_code, 4);
+        assert_eq!(result.failed_count(), 3);
+    }
+
     #[test]
     fn test_task_result_merge() {
         let mut result1 = TaskResult::empty("test1");
@@ -369,6 +544,7 @@ mod tests {
             exit_code: 0,
             duration: Duration::from_secs(1),
             log_file: std::path::PathBuf::from("test1.log"),
+            cached: false,
         });
 
         let mut result2 = TaskResult::empty("test2");
@@ -379,6 +555,7 @@ mod tests {
             exit_code: 0,
             duration: Duration::from_secs(2),
             log_file: std::path::PathBuf::from("test2.log"),
+            cached: false,
         });
 
         result1.merge(result2);
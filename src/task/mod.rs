@@ -3,6 +3,8 @@
 //! Provides task graph construction, validation, and execution for the `stacy task` command.
 
 pub mod executor;
+pub mod run_state;
+pub mod selection;
 
 use crate::error::{Error, Result};
 use crate::project::config::{ScriptsSection, TaskDef};
@@ -26,6 +28,7 @@ impl TaskGraph {
         graph.validate_definitions()?;
         graph.validate_references()?;
         graph.validate_no_cycles()?;
+        crate::schedule::scheduled_tasks(scripts)?;
 
         Ok(graph)
     }
@@ -79,6 +82,22 @@ impl TaskGraph {
                     name
                 )));
             }
+            // Inputs key the cache entry of the script they feed; a parallel
+            // group has no script of its own to attach them to.
+            if let TaskDef::Complex(complex) = task {
+                if complex.inputs.is_some() && complex.script.is_none() {
+                    return Err(Error::Config(format!(
+                        "Task '{}': 'inputs' requires 'script' (declare inputs on the tasks that read them)",
+                        name
+                    )));
+                }
+                if complex.outputs.is_some() && complex.script.is_none() {
+                    return Err(Error::Config(format!(
+                        "Task '{}': 'outputs' requires 'script' (declare outputs on the tasks that write them)",
+                        name
+                    )));
+                }
+            }
         }
         Ok(())
     }
@@ -198,7 +217,7 @@ impl TaskGraph {
 
 /// Simple Levenshtein distance implementation
 #[allow(clippy::needless_range_loop)]
-fn levenshtein_distance(a: &str, b: &str) -> usize {
+pub(crate) fn levenshtein_distance(a: &str, b: &str) -> usize {
     let a_chars: Vec<char> = a.chars().collect();
     let b_chars: Vec<char> = b.chars().collect();
     let m = a_chars.len();
@@ -339,6 +358,9 @@ mod tests {
                     script: None,
                     args: None,
                     description: None,
+                    inputs: None,
+                    outputs: None,
+                    schedule: None,
                 }),
             ),
         ]);
@@ -358,6 +380,9 @@ mod tests {
                 script: None,
                 args: None,
                 description: Some("Build everything".to_string()),
+                inputs: None,
+                outputs: None,
+                schedule: None,
             }),
         )]);
 
@@ -385,6 +410,9 @@ mod tests {
                 script: None,
                 args: None,
                 description: None,
+                inputs: None,
+                outputs: None,
+                schedule: None,
             }),
         )]);
 
@@ -393,6 +421,28 @@ mod tests {
         assert!(result.unwrap_err().to_string().contains("defines no work"));
     }
 
+    #[test]
+    fn test_inputs_without_script_errors() {
+        let scripts = make_scripts(vec![
+            ("a", TaskDef::Simple(PathBuf::from("a.do"))),
+            (
+                "outputs",
+                TaskDef::Complex(ComplexTask {
+                    parallel: Some(vec!["a".to_string()]),
+                    script: None,
+                    args: None,
+                    description: None,
+                    inputs: Some(vec![PathBuf::from("data")]),
+                    outputs: None,
+                    schedule: None,
+                }),
+            ),
+        ]);
+
+        let err = TaskGraph::from_config(&scripts).unwrap_err().to_string();
+        assert!(err.contains("'inputs' requires 'script'"));
+    }
+
     #[test]
     fn test_missing_reference() {
         let scripts = make_scripts(vec![(
@@ -546,6 +596,9 @@ mod tests {
                 script: None,
                 args: None,
                 description: None,
+                inputs: None,
+                outputs: None,
+                schedule: None,
             })),
             "Run 2 tasks in parallel"
         );
@@ -556,6 +609,9 @@ mod tests {
                 script: None,
                 args: None,
                 description: Some("My custom task".to_string()),
+                inputs: None,
+                outputs: None,
+                schedule: None,
             })),
             "My custom task"
         );
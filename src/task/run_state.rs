@@ -0,0 +1,183 @@
+//! Progress of a task run, for `stacy task --resume`
+//!
+//! Every script a task completes is recorded in `.stacy/runs/<task>.json`
+//! as soon as it succeeds, with the hash of the script. A run that fails (or
+//! is killed) leaves the file behind; `stacy task <task> --resume` then skips
+//! the scripts recorded there and carries on from the failure. A script that
+//! changed since it completed runs again, and so does everything when the
+//! task is resumed with other arguments. A run that succeeds removes the
+//! file; a run without `--resume` starts it afresh.
+
+use crate::cache::hash::hash_file;
+use crate::error::{Error, Result};
+use crate::utils::paths::write_atomically;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// Run-state format version; a file with another version is not resumed
+const RUN_STATE_VERSION: u32 = 1;
+
+/// Directory of the run-state files, within the project root
+const RUNS_DIR: &str = ".stacy/runs";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct RunStateFile {
+    version: u32,
+    /// Arguments the task was run with, sorted
+    args: Vec<(String, String)>,
+    /// SHA-256 of each completed script, by step (`<name>:<script>`)
+    completed: BTreeMap<String, String>,
+}
+
+/// What a run of a task has completed so far
+pub struct RunState {
+    project_root: PathBuf,
+    path: PathBuf,
+    file: Mutex<RunStateFile>,
+    skipped: AtomicUsize,
+}
+
+impl RunState {
+    /// Start a run of `task`. With `resume`, it carries on from the state a
+    /// failed run with the same arguments left; `None` when there is none.
+    pub fn start(
+        project_root: &Path,
+        task: &str,
+        args: &HashMap<String, String>,
+        resume: bool,
+    ) -> Result<(Self, Option<usize>)> {
+        let path = project_root.join(RUNS_DIR).join(format!("{}.json", task));
+        let mut args: Vec<(String, String)> =
+            args.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+        args.sort();
+
+        let previous = if resume { Self::read(&path)? } else { None };
+        let resumed = previous.filter(|file| file.args == args);
+        let count = resumed.as_ref().map(|file| file.completed.len());
+        let file = resumed.unwrap_or(RunStateFile {
+            version: RUN_STATE_VERSION,
+            args,
+            completed: BTreeMap::new(),
+        });
+        let state = Self {
+            project_root: project_root.to_path_buf(),
+            path,
+            file: Mutex::new(file),
+            skipped: AtomicUsize::new(0),
+        };
+        state.write()?;
+        Ok((state, count))
+    }
+
+    fn read(path: &Path) -> Result<Option<RunStateFile>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = std::fs::read_to_string(path)?;
+        let file: RunStateFile = serde_json::from_str(&content)
+            .map_err(|e| Error::Parse(format!("Failed to parse {}: {}", path.display(), e)))?;
+        Ok((file.version == RUN_STATE_VERSION).then_some(file))
+    }
+
+    fn write(&self) -> Result<()> {
+        let file = self.file.lock().unwrap();
+        write_atomically(&self.path, serde_json::to_string_pretty(&*file)?.as_bytes())?;
+        Ok(())
+    }
+
+    /// Whether step `name` completed `script` in the run being resumed, and
+    /// the script is unchanged since. Counts the steps it says yes to.
+    pub fn is_done(&self, name: &str, script: &Path) -> bool {
+        let done = {
+            let file = self.file.lock().unwrap();
+            file.completed
+                .get(&self.step(name, script))
+                .is_some_and(|hash| hash_file(script).ok().as_ref() == Some(hash))
+        };
+        if done {
+            self.skipped.fetch_add(1, Ordering::Relaxed);
+        }
+        done
+    }
+
+    /// Record that step `name` completed `script`
+    pub fn complete(&self, name: &str, script: &Path) -> Result<()> {
+        let hash = hash_file(script)?;
+        self.file
+            .lock()
+            .unwrap()
+            .completed
+            .insert(self.step(name, script), hash);
+        self.write()
+    }
+
+    /// Key of a step: the task or entry name and the script, relative to
+    /// the project root
+    fn step(&self, name: &str, script: &Path) -> String {
+        let script = script.strip_prefix(&self.project_root).unwrap_or(script);
+        format!("{}:{}", name, script.display())
+    }
+
+    /// Scripts skipped as completed in the resumed run
+    pub fn skipped(&self) -> usize {
+        self.skipped.load(Ordering::Relaxed)
+    }
+
+    /// The task succeeded: nothing is left to resume
+    pub fn finish(self) -> Result<()> {
+        match std::fs::remove_file(&self.path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_resume_skips_completed_unchanged_scripts() {
+        let temp = TempDir::new().unwrap();
+        let clean = temp.path().join("clean.do");
+        let analyze = temp.path().join("analyze.do");
+        std::fs::write(&clean, "display 1\n").unwrap();
+        std::fs::write(&analyze, "display 2\n").unwrap();
+        let args = HashMap::new();
+
+        let (state, resumed) = RunState::start(temp.path(), "all", &args, false).unwrap();
+        assert_eq!(resumed, None);
+        state.complete("clean", &clean).unwrap();
+        state.complete("analyze", &analyze).unwrap();
+        drop(state);
+
+        std::fs::write(&analyze, "display 3\n").unwrap();
+        let (state, resumed) = RunState::start(temp.path(), "all", &args, true).unwrap();
+        assert_eq!(resumed, Some(2));
+        assert!(state.is_done("clean", &clean));
+        assert!(!state.is_done("analyze", &analyze), "changed since");
+        assert_eq!(state.skipped(), 1);
+        drop(state);
+
+        // Other arguments: start afresh
+        let other = HashMap::from([("year".to_string(), "2020".to_string())]);
+        let (state, resumed) = RunState::start(temp.path(), "all", &other, true).unwrap();
+        assert_eq!(resumed, None);
+        assert!(!state.is_done("clean", &clean));
+        drop(state);
+
+        // Without --resume: from scratch
+        let (state, resumed) = RunState::start(temp.path(), "all", &other, false).unwrap();
+        assert_eq!(resumed, None);
+        assert!(!state.is_done("clean", &clean));
+
+        let path = temp.path().join(RUNS_DIR).join("all.json");
+        assert!(path.exists());
+        state.finish().unwrap();
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,212 @@
+//! Running part of a task: `stacy task <task> --from <step>` and `--only`
+//!
+//! A task that lists other tasks is a tree of steps, its scripts the
+//! leaves. `--from analyze` runs the scripts from the first under `analyze`
+//! on, in the order the task lists them; `--only tables,figures` runs just
+//! the scripts under those steps. Both name steps anywhere in the tree, and
+//! together run the scripts both select. A step is known by the path of
+//! names from the task down to it, so a task listed twice is two steps.
+
+use crate::error::{Error, Result};
+use crate::project::config::{ComplexTask, TaskDef};
+use crate::task::{is_script_ref, TaskGraph};
+use std::collections::HashSet;
+
+/// The scripts of a task that `--from` and `--only` select
+#[derive(Debug)]
+pub struct Selection {
+    /// Paths of the selected scripts, from the task down
+    scripts: HashSet<Vec<String>>,
+}
+
+impl Selection {
+    /// Select the scripts of `task` from step `from` on, under the steps in
+    /// `only` (any, when empty). Fails on a step the task does not have.
+    pub fn new(graph: &TaskGraph, task: &str, from: Option<&str>, only: &[String]) -> Result<Self> {
+        let def = graph
+            .get_task(task)
+            .ok_or_else(|| Error::Config(format!("Unknown task '{}'", task)))?;
+        let mut steps = Vec::new();
+        walk(graph, &mut vec![task.to_string()], def, &mut steps);
+        if steps.is_empty() {
+            return Err(Error::Config(format!(
+                "Task '{}' runs a single script: --from and --only pick steps of a task that lists others",
+                task
+            )));
+        }
+
+        for name in from.into_iter().chain(only.iter().map(String::as_str)) {
+            if !steps.iter().any(|(path, _)| path.last().unwrap() == name) {
+                let mut names: Vec<&str> = Vec::new();
+                for (path, _) in &steps {
+                    let step = path.last().unwrap().as_str();
+                    if !names.contains(&step) {
+                        names.push(step);
+                    }
+                }
+                return Err(Error::Config(format!(
+                    "Task '{}' has no step '{}'\n\nIts steps: {}",
+                    task,
+                    name,
+                    names.join(", ")
+                )));
+            }
+        }
+
+        let start = from
+            .and_then(|from| {
+                steps
+                    .iter()
+                    .position(|(path, _)| path.last().unwrap() == from)
+            })
+            .unwrap_or(0);
+        let scripts: HashSet<Vec<String>> = steps
+            .into_iter()
+            .skip(start)
+            .filter(|(path, script)| {
+                *script && (only.is_empty() || path[1..].iter().any(|step| only.contains(step)))
+            })
+            .map(|(path, _)| path)
+            .collect();
+        if scripts.is_empty() {
+            return Err(Error::Config(format!(
+                "--from {} and --only {} select no script of task '{}'",
+                from.unwrap_or_default(),
+                only.join(","),
+                task
+            )));
+        }
+        Ok(Self { scripts })
+    }
+
+    /// Whether the script at `path` (from the task down) is selected
+    pub fn includes(&self, path: &[String]) -> bool {
+        self.scripts.contains(path)
+    }
+}
+
+/// Every step below `path` in the order the task lists them, parents before
+/// their children, and whether it is a script
+fn walk(
+    graph: &TaskGraph,
+    path: &mut Vec<String>,
+    def: &TaskDef,
+    steps: &mut Vec<(Vec<String>, bool)>,
+) {
+    let entries = match def {
+        TaskDef::Sequential(entries) => entries,
+        TaskDef::Complex(ComplexTask {
+            parallel: Some(entries),
+            ..
+        }) => entries,
+        _ => return,
+    };
+    for entry in entries {
+        path.push(entry.clone());
+        match graph.get_task(entry) {
+            Some(child) => {
+                let script = matches!(child, TaskDef::Simple(_))
+                    || matches!(child, TaskDef::Complex(c) if c.parallel.is_none());
+                steps.push((path.clone(), script));
+                walk(graph, path, child, steps);
+            }
+            // Validated references: anything else runs as a script
+            None => steps.push((path.clone(), is_script_ref(entry))),
+        }
+        path.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project::config::ScriptsSection;
+    use std::path::PathBuf;
+
+    fn graph() -> TaskGraph {
+        let simple = |s: &str| TaskDef::Simple(PathBuf::from(s));
+        let seq = |v: &[&str]| TaskDef::Sequential(v.iter().map(|s| s.to_string()).collect());
+        let tasks = [
+            ("clean", simple("src/clean.do")),
+            ("analyze", simple("src/analyze.do")),
+            ("tables", simple("src/tables.do")),
+            ("figures", simple("src/figures.do")),
+            (
+                "outputs",
+                TaskDef::Complex(ComplexTask {
+                    parallel: Some(vec!["tables".into(), "figures".into()]),
+                    script: None,
+                    args: None,
+                    description: None,
+                    inputs: None,
+                    outputs: None,
+                    schedule: None,
+                }),
+            ),
+            (
+                "all",
+                seq(&["clean", "analyze", "outputs", "src/appendix.do"]),
+            ),
+        ];
+        TaskGraph::from_config(&ScriptsSection {
+            tasks: tasks.into_iter().map(|(k, v)| (k.to_string(), v)).collect(),
+        })
+        .unwrap()
+    }
+
+    fn path(steps: &[&str]) -> Vec<String> {
+        std::iter::once("all")
+            .chain(steps.iter().copied())
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn test_from_runs_the_rest_of_the_task() {
+        let selection = Selection::new(&graph(), "all", Some("analyze"), &[]).unwrap();
+        assert!(!selection.includes(&path(&["clean"])));
+        assert!(selection.includes(&path(&["analyze"])));
+        assert!(selection.includes(&path(&["outputs", "figures"])));
+        assert!(selection.includes(&path(&["src/appendix.do"])));
+
+        // A nested step
+        let selection = Selection::new(&graph(), "all", Some("figures"), &[]).unwrap();
+        assert!(!selection.includes(&path(&["outputs", "tables"])));
+        assert!(selection.includes(&path(&["outputs", "figures"])));
+    }
+
+    #[test]
+    fn test_only_runs_the_steps_named() {
+        let only = vec!["outputs".to_string(), "clean".to_string()];
+        let selection = Selection::new(&graph(), "all", None, &only).unwrap();
+        assert!(selection.includes(&path(&["clean"])));
+        assert!(!selection.includes(&path(&["analyze"])));
+        assert!(selection.includes(&path(&["outputs", "tables"])));
+        assert!(!selection.includes(&path(&["src/appendix.do"])));
+
+        // With --from: the scripts both select
+        let selection = Selection::new(&graph(), "all", Some("analyze"), &only).unwrap();
+        assert!(!selection.includes(&path(&["clean"])));
+        assert!(selection.includes(&path(&["outputs", "tables"])));
+    }
+
+    #[test]
+    fn test_unknown_step_is_an_error() {
+        let err = Selection::new(&graph(), "all", Some("analyse"), &[])
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("no step 'analyse'"), "{}", err);
+        assert!(err.contains("clean, analyze, outputs, tables"), "{}", err);
+
+        let err = Selection::new(&graph(), "clean", Some("clean"), &[])
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("runs a single script"), "{}", err);
+
+        let only = vec!["clean".to_string()];
+        let err = Selection::new(&graph(), "all", Some("analyze"), &only)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("select no script"), "{}", err);
+    }
+}
@@ -0,0 +1,235 @@
+//! Opt-in anonymous usage metrics
+//!
+//! Off unless the user config says `[telemetry] enabled = true`. When on,
+//! each invocation records one event: the command name (`run`, `install`, ...),
+//! how long it took, and on failure the error category (`config`,
+//! `syntax_error`, ...). Nothing else: no arguments, paths, package names,
+//! project names or machine identifiers.
+//!
+//! Events queue in `~/.cache/stacy/telemetry/queue.jsonl` and are sent in
+//! batches to the configured endpoint. `STACY_OFFLINE=1` disables recording.
+
+use crate::project::user_config::{get_config_path, read_user_config, UserTelemetryConfig};
+use crate::utils::lock::{with_exclusive_lock, LOCK_FILE_NAME};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Events are sent once this many have queued up
+const BATCH_SIZE: usize = 20;
+
+/// The queue never grows beyond this (oldest events are dropped), so an
+/// unreachable endpoint can't fill the disk
+const MAX_QUEUED: usize = 500;
+
+/// Sending happens as the command exits, so it must be quick
+const SEND_TIMEOUT: Duration = Duration::from_secs(2);
+
+const QUEUE_FILE: &str = "queue.jsonl";
+
+/// One recorded invocation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    pub command: String,
+    pub duration_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_category: Option<String>,
+}
+
+/// What `stacy telemetry status` reports
+#[derive(Debug, Clone)]
+pub struct TelemetryStatus {
+    pub enabled: bool,
+    pub endpoint: Option<String>,
+    pub queued: usize,
+    pub queue_path: Option<PathBuf>,
+}
+
+/// Record one invocation of `command`, when telemetry is on. Called as the
+/// command finishes (see `session::finish`).
+pub fn record(command: &str, duration: Duration, error_category: Option<&str>) {
+    let Some(endpoint) = enabled_endpoint() else {
+        return;
+    };
+    let Some(queue) = queue_path() else {
+        return;
+    };
+
+    let event = Event {
+        command: command.to_string(),
+        duration_ms: duration.as_millis() as u64,
+        error_category: error_category.map(str::to_string),
+    };
+    let _ = with_exclusive_lock(&lock_path(&queue), || {
+        if append_event(&queue, &event).is_ok() {
+            if let Some(endpoint) = endpoint {
+                flush_if_full(&queue, &endpoint);
+            }
+        }
+    });
+}
+
+/// Current telemetry settings and queue
+pub fn status() -> TelemetryStatus {
+    let config = telemetry_config();
+    let queue_path = queue_path();
+    TelemetryStatus {
+        enabled: config.enabled == Some(true),
+        endpoint: config.endpoint,
+        queued: queue_path.as_deref().map_or(0, |q| read_queue(q).len()),
+        queue_path,
+    }
+}
+
+/// `Some(endpoint)` when recording is on; the endpoint itself is optional
+/// (without one, events stay in the local queue).
+fn enabled_endpoint() -> Option<Option<String>> {
+    if crate::update_check::is_offline() {
+        return None;
+    }
+    let config = telemetry_config();
+    if config.enabled != Some(true) {
+        return None;
+    }
+    Some(config.endpoint)
+}
+
+/// The `[telemetry]` section, read without validating the rest of the file
+/// (a stale `stata_binary` shouldn't silently switch telemetry off)
+fn telemetry_config() -> UserTelemetryConfig {
+    get_config_path()
+        .and_then(|path| read_user_config(&path).ok().flatten())
+        .map(|config| config.telemetry)
+        .unwrap_or_default()
+}
+
+fn queue_path() -> Option<PathBuf> {
+    Some(
+        crate::update_check::cache_dir()?
+            .join("telemetry")
+            .join(QUEUE_FILE),
+    )
+}
+
+fn lock_path(queue: &Path) -> PathBuf {
+    queue.with_file_name(LOCK_FILE_NAME)
+}
+
+/// Append one event to the queue, dropping the oldest beyond [`MAX_QUEUED`]
+fn append_event(queue: &Path, event: &Event) -> std::io::Result<()> {
+    if let Some(parent) = queue.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let line = serde_json::to_string(event)?;
+
+    let queued = read_queue(queue);
+    if queued.len() >= MAX_QUEUED {
+        let keep = &queued[queued.len() + 1 - MAX_QUEUED..];
+        write_queue(queue, keep)?;
+    }
+
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(queue)?;
+    writeln!(file, "{}", line)
+}
+
+/// Queued events; unparseable lines are skipped
+fn read_queue(queue: &Path) -> Vec<Event> {
+    std::fs::read_to_string(queue)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+fn write_queue(queue: &Path, events: &[Event]) -> std::io::Result<()> {
+    let mut content = String::new();
+    for event in events {
+        content.push_str(&serde_json::to_string(event)?);
+        content.push('\n');
+    }
+    std::fs::write(queue, content)
+}
+
+/// Send the queue once a full batch has accumulated; keep it if sending fails
+fn flush_if_full(queue: &Path, endpoint: &str) {
+    let events = read_queue(queue);
+    if events.len() < BATCH_SIZE {
+        return;
+    }
+    if send_batch(endpoint, &events) {
+        let _ = std::fs::remove_file(queue);
+    }
+}
+
+fn send_batch(endpoint: &str, events: &[Event]) -> bool {
+    let Ok(client) = crate::packages::http::client_builder()
+        .timeout(SEND_TIMEOUT)
+        .build()
+    else {
+        return false;
+    };
+    client
+        .post(endpoint)
+        .json(&serde_json::json!({ "events": events }))
+        .send()
+        .map(|r| r.status().is_success())
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn event(command: &str) -> Event {
+        Event {
+            command: command.to_string(),
+            duration_ms: 12,
+            error_category: None,
+        }
+    }
+
+    #[test]
+    fn test_event_carries_only_command_duration_and_category() {
+        let json = serde_json::to_value(Event {
+            error_category: Some("network".to_string()),
+            ..event("install")
+        })
+        .unwrap();
+        let mut keys: Vec<_> = json.as_object().unwrap().keys().cloned().collect();
+        keys.sort();
+        assert_eq!(keys, vec!["command", "duration_ms", "error_category"]);
+    }
+
+    #[test]
+    fn test_queue_append_and_cap() {
+        let temp = TempDir::new().unwrap();
+        let queue = temp.path().join("telemetry").join(QUEUE_FILE);
+
+        append_event(&queue, &event("run")).unwrap();
+        append_event(&queue, &event("install")).unwrap();
+        assert_eq!(read_queue(&queue), vec![event("run"), event("install")]);
+
+        let full: Vec<_> = (0..MAX_QUEUED).map(|_| event("run")).collect();
+        write_queue(&queue, &full).unwrap();
+        append_event(&queue, &event("lock")).unwrap();
+        let queued = read_queue(&queue);
+        assert_eq!(queued.len(), MAX_QUEUED);
+        assert_eq!(queued.last(), Some(&event("lock")));
+    }
+
+    #[test]
+    fn test_flush_keeps_queue_when_send_fails() {
+        let temp = TempDir::new().unwrap();
+        let queue = temp.path().join(QUEUE_FILE);
+        let batch: Vec<_> = (0..BATCH_SIZE).map(|_| event("run")).collect();
+        write_queue(&queue, &batch).unwrap();
+
+        flush_if_full(&queue, "http://127.0.0.1:1/events");
+        assert_eq!(read_queue(&queue).len(), BATCH_SIZE);
+    }
+}
@@ -1,10 +1,14 @@
-//! Test discovery by convention
+//! Test discovery
 //!
-//! Discovers test files using naming conventions:
+//! Without a `[test]` section, discovers test files using naming conventions:
 //! - `test_*.do`, `*_test.do` anywhere in project
 //! - All `.do` files in `tests/` or `test/` directories
+//!
+//! `[test] dirs` and `pattern` replace the convention, and `exclude` drops
+//! matching paths either way.
 
-use crate::error::Result;
+use crate::error::{Error, Result};
+use crate::project::config::TestSection;
 use std::path::{Path, PathBuf};
 
 /// Information about a discovered test
@@ -29,14 +33,37 @@ impl TestFile {
 }
 
 /// Discover tests in a project directory
-pub fn discover_tests(project_root: &Path, filters: &[String]) -> Result<Vec<TestFile>> {
+pub fn discover_tests(
+    project_root: &Path,
+    settings: &TestSection,
+    filters: &[String],
+) -> Result<Vec<TestFile>> {
     let mut tests = Vec::new();
 
-    // Strategy 1: Find test_*.do and *_test.do anywhere in project
-    discover_by_naming_convention(project_root, &mut tests)?;
+    if settings.is_conventional() {
+        // Strategy 1: Find test_*.do and *_test.do anywhere in project
+        discover_by_naming_convention(project_root, &mut tests)?;
+
+        // Strategy 2: Find all .do files in tests/ or test/ directories
+        discover_in_test_directories(project_root, &mut tests)?;
+    } else if settings.dirs.is_empty() {
+        discover_do_files(project_root, &settings.pattern, &mut tests)?;
+    } else {
+        for dir in &settings.dirs {
+            let test_dir = project_root.join(dir);
+            if !test_dir.is_dir() {
+                return Err(Error::Config(format!(
+                    "[test] dirs: {} is not a directory",
+                    dir.display()
+                )));
+            }
+            discover_do_files(&test_dir, &settings.pattern, &mut tests)?;
+        }
+    }
 
-    // Strategy 2: Find all .do files in tests/ or test/ directories
-    discover_in_test_directories(project_root, &mut tests)?;
+    if !settings.exclude.is_empty() {
+        tests.retain(|test| !is_excluded(project_root, &test.path, &settings.exclude));
+    }
 
     // Remove duplicates (same path)
     tests.sort_by(|a, b| a.path.cmp(&b.path));
@@ -58,7 +85,11 @@ pub fn discover_tests(project_root: &Path, filters: &[String]) -> Result<Vec<Tes
 }
 
 /// Find a specific test by name or path
-pub fn find_test(project_root: &Path, name_or_path: &str) -> Result<Option<TestFile>> {
+pub fn find_test(
+    project_root: &Path,
+    settings: &TestSection,
+    name_or_path: &str,
+) -> Result<Option<TestFile>> {
     // If it looks like a path (contains / or ends with .do), try to find it directly
     if name_or_path.contains('/') || name_or_path.contains('\\') || name_or_path.ends_with(".do") {
         let path = if Path::new(name_or_path).is_absolute() {
@@ -73,7 +104,7 @@ pub fn find_test(project_root: &Path, name_or_path: &str) -> Result<Option<TestF
     }
 
     // Otherwise, discover all tests and find by name
-    let tests = discover_tests(project_root, &[])?;
+    let tests = discover_tests(project_root, settings, &[])?;
 
     // Exact match first
     if let Some(test) = tests.iter().find(|t| t.name == name_or_path) {
@@ -125,14 +156,15 @@ fn discover_in_test_directories(project_root: &Path, tests: &mut Vec<TestFile>)
     for dir_name in &["tests", "test"] {
         let test_dir = project_root.join(dir_name);
         if test_dir.is_dir() {
-            discover_all_do_files(&test_dir, tests)?;
+            discover_do_files(&test_dir, &[], tests)?;
         }
     }
     Ok(())
 }
 
-/// Recursively discover all .do files in a directory
-fn discover_all_do_files(dir: &Path, tests: &mut Vec<TestFile>) -> Result<()> {
+/// Recursively discover the .do files in a directory whose name matches one
+/// of `patterns`, or all of them when there are none
+fn discover_do_files(dir: &Path, patterns: &[String], tests: &mut Vec<TestFile>) -> Result<()> {
     if !dir.is_dir() {
         return Ok(());
     }
@@ -143,17 +175,63 @@ fn discover_all_do_files(dir: &Path, tests: &mut Vec<TestFile>) -> Result<()> {
 
         if path.is_dir() {
             let name = path.file_name().unwrap_or_default().to_string_lossy();
-            if !name.starts_with('.') {
-                discover_all_do_files(&path, tests)?;
+            if !name.starts_with('.') && name != "node_modules" && name != "target" {
+                discover_do_files(&path, patterns, tests)?;
             }
         } else if path.extension().map(|e| e == "do").unwrap_or(false) {
-            tests.push(TestFile::from_path(path));
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            if patterns.is_empty() || patterns.iter().any(|p| glob_match(p, &name)) {
+                tests.push(TestFile::from_path(path));
+            }
         }
     }
 
     Ok(())
 }
 
+/// Whether `path`, or a directory it is in, matches an `exclude` glob
+fn is_excluded(project_root: &Path, path: &Path, exclude: &[String]) -> bool {
+    let rel = path.strip_prefix(project_root).unwrap_or(path);
+    let components: Vec<String> = rel
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect();
+    (1..=components.len()).any(|n| {
+        let prefix = components[..n].join("/");
+        exclude
+            .iter()
+            .any(|pattern| glob_match(pattern.trim_end_matches('/'), &prefix))
+    })
+}
+
+/// Match `text` against a glob: `*` and `?` stay within one path segment,
+/// `**` spans segments
+fn glob_match(pattern: &str, text: &str) -> bool {
+    fn matches(p: &[char], t: &[char]) -> bool {
+        match p.split_first() {
+            None => t.is_empty(),
+            Some(('*', rest)) if rest.first() == Some(&'*') => {
+                let rest = &rest[1..];
+                // `a/**/b` also matches `a/b`
+                if let Some(after) = rest.strip_prefix(&['/'][..]) {
+                    if matches(after, t) {
+                        return true;
+                    }
+                }
+                (0..=t.len()).any(|i| matches(rest, &t[i..]))
+            }
+            Some(('*', rest)) => (0..=t.len())
+                .take_while(|&i| i == 0 || t[i - 1] != '/')
+                .any(|i| matches(rest, &t[i..])),
+            Some(('?', rest)) => t.first().is_some_and(|&c| c != '/') && matches(rest, &t[1..]),
+            Some((&c, rest)) => t.first() == Some(&c) && matches(rest, &t[1..]),
+        }
+    }
+    let p: Vec<char> = pattern.chars().collect();
+    let t: Vec<char> = text.chars().collect();
+    matches(&p, &t)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -179,7 +257,7 @@ mod tests {
         create_test_file(root, "regular.do"); // Should NOT be discovered
         create_test_file(root, "src/test_nested.do");
 
-        let tests = discover_tests(root, &[]).unwrap();
+        let tests = discover_tests(root, &TestSection::default(), &[]).unwrap();
 
         assert_eq!(tests.len(), 3);
         assert!(tests.iter().any(|t| t.name == "test_foo"));
@@ -200,7 +278,7 @@ mod tests {
         // Create file in test/ directory
         create_test_file(root, "test/qux.do");
 
-        let tests = discover_tests(root, &[]).unwrap();
+        let tests = discover_tests(root, &TestSection::default(), &[]).unwrap();
 
         assert_eq!(tests.len(), 4);
         assert!(tests.iter().any(|t| t.name == "foo"));
@@ -218,7 +296,7 @@ mod tests {
         create_test_file(root, "test_bar.do");
         create_test_file(root, "test_baz.do");
 
-        let tests = discover_tests(root, &["foo".to_string()]).unwrap();
+        let tests = discover_tests(root, &TestSection::default(), &["foo".to_string()]).unwrap();
 
         assert_eq!(tests.len(), 1);
         assert_eq!(tests[0].name, "test_foo");
@@ -232,12 +310,78 @@ mod tests {
         // test_foo.do matches naming convention AND is in tests/
         create_test_file(root, "tests/test_foo.do");
 
-        let tests = discover_tests(root, &[]).unwrap();
+        let tests = discover_tests(root, &TestSection::default(), &[]).unwrap();
 
         // Should only appear once
         assert_eq!(tests.len(), 1);
     }
 
+    #[test]
+    fn test_discover_configured_dirs_and_pattern() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path();
+
+        create_test_file(root, "validation/check_totals.do");
+        create_test_file(root, "validation/helpers.do");
+        create_test_file(root, "tests/check_merge.do");
+        create_test_file(root, "tests/fixtures/check_fixture.do");
+        // The convention no longer applies
+        create_test_file(root, "src/test_old.do");
+
+        let settings = TestSection {
+            dirs: vec![PathBuf::from("tests"), PathBuf::from("validation")],
+            pattern: vec!["check_*.do".to_string()],
+            exclude: vec!["tests/fixtures".to_string()],
+            ..Default::default()
+        };
+        let tests = discover_tests(root, &settings, &[]).unwrap();
+        let names: Vec<&str> = tests.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["check_merge", "check_totals"]);
+
+        // A pattern alone searches the whole project
+        let settings = TestSection {
+            pattern: vec!["check_*.do".to_string()],
+            exclude: vec!["**/fixtures/**".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(discover_tests(root, &settings, &[]).unwrap().len(), 2);
+
+        // A directory that isn't there is a configuration error
+        let settings = TestSection {
+            dirs: vec![PathBuf::from("missing")],
+            ..Default::default()
+        };
+        assert!(discover_tests(root, &settings, &[]).is_err());
+    }
+
+    #[test]
+    fn test_exclude_applies_to_convention() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path();
+
+        create_test_file(root, "tests/foo.do");
+        create_test_file(root, "tests/setup/common.do");
+
+        let settings = TestSection {
+            exclude: vec!["tests/setup/".to_string()],
+            ..Default::default()
+        };
+        let tests = discover_tests(root, &settings, &[]).unwrap();
+        assert_eq!(tests.len(), 1);
+        assert_eq!(tests[0].name, "foo");
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("check_*.do", "check_totals.do"));
+        assert!(!glob_match("check_*.do", "precheck_totals.do"));
+        assert!(glob_match("test_?.do", "test_1.do"));
+        assert!(!glob_match("tests/*.do", "tests/sub/a.do"));
+        assert!(glob_match("tests/**/*.do", "tests/sub/a.do"));
+        assert!(glob_match("tests/**/*.do", "tests/a.do"));
+        assert!(glob_match("**/helpers_*.do", "src/helpers_merge.do"));
+    }
+
     #[test]
     fn test_find_test_by_name() {
         let temp = TempDir::new().unwrap();
@@ -246,7 +390,7 @@ mod tests {
         create_test_file(root, "test_foo.do");
         create_test_file(root, "test_bar.do");
 
-        let found = find_test(root, "test_foo").unwrap();
+        let found = find_test(root, &TestSection::default(), "test_foo").unwrap();
         assert!(found.is_some());
         assert_eq!(found.unwrap().name, "test_foo");
     }
@@ -258,7 +402,7 @@ mod tests {
 
         create_test_file(root, "tests/my_test.do");
 
-        let found = find_test(root, "tests/my_test.do").unwrap();
+        let found = find_test(root, &TestSection::default(), "tests/my_test.do").unwrap();
         assert!(found.is_some());
         assert_eq!(found.unwrap().name, "my_test");
     }
@@ -268,7 +412,7 @@ mod tests {
         let temp = TempDir::new().unwrap();
         let root = temp.path();
 
-        let found = find_test(root, "nonexistent").unwrap();
+        let found = find_test(root, &TestSection::default(), "nonexistent").unwrap();
         assert!(found.is_none());
     }
 }
@@ -0,0 +1,186 @@
+//! Pass/fail history of tests, for spotting flaky ones
+//!
+//! Every test that runs inside a project adds its outcome to
+//! `.stacy/test-history.json`, along with a hash of the code it ran: the test
+//! script, the scripts it calls and the lockfile. A test that both passed and
+//! failed on the same code is flaky: something outside the code (the network,
+//! the clock, a file another test writes) decides its outcome.
+//!
+//! The history only informs: one that can't be read or written is ignored.
+
+use crate::cache::hash::{hash_dependency_tree, hash_lockfile, hash_string};
+use crate::error::Result;
+use crate::utils::date::now_utc;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+/// History file within the project root
+const HISTORY_FILE: &str = ".stacy/test-history.json";
+
+/// History format version; a history of another version is dropped
+const HISTORY_VERSION: u32 = 1;
+
+/// Outcomes kept per test; older ones are dropped
+const MAX_RUNS: usize = 50;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct HistoryFile {
+    version: u32,
+    /// Outcomes per test, keyed by its path relative to the project root
+    tests: BTreeMap<String, Vec<Outcome>>,
+}
+
+/// One run of a test
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Outcome {
+    /// Hash of the code the test ran
+    state: String,
+    passed: bool,
+    /// When it ran, as `YYYYMMDD-HHMMSS` (UTC)
+    at: String,
+}
+
+/// How inconsistent a test has been on one state of its code
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Flakiness {
+    pub passed: usize,
+    pub failed: usize,
+}
+
+impl Flakiness {
+    /// Share of runs that disagree with the most common outcome
+    pub fn rate(&self) -> f64 {
+        self.passed.min(self.failed) as f64 / (self.passed + self.failed) as f64
+    }
+}
+
+/// The recorded outcomes of a project's tests
+#[derive(Debug)]
+pub struct TestHistory {
+    project_root: PathBuf,
+    tests: BTreeMap<String, Vec<Outcome>>,
+}
+
+impl TestHistory {
+    /// The history of the project at `project_root`
+    pub fn load(project_root: &Path) -> Self {
+        let tests = std::fs::read(project_root.join(HISTORY_FILE))
+            .ok()
+            .and_then(|content| serde_json::from_slice::<HistoryFile>(&content).ok())
+            .filter(|file| file.version == HISTORY_VERSION)
+            .map(|file| file.tests)
+            .unwrap_or_default();
+        Self {
+            project_root: project_root.to_path_buf(),
+            tests,
+        }
+    }
+
+    /// Add an outcome of `test` on the code state `state`
+    pub fn record(&mut self, test: &Path, state: &str, passed: bool) {
+        let (date, time) = now_utc();
+        let runs = self.tests.entry(self.key(test)).or_default();
+        runs.push(Outcome {
+            state: state.to_string(),
+            passed,
+            at: format!("{}-{}", date, time),
+        });
+        if runs.len() > MAX_RUNS {
+            runs.drain(..runs.len() - MAX_RUNS);
+        }
+    }
+
+    /// How flaky `test` is: its most inconsistent code state, when at least
+    /// `threshold` of the runs on it disagree with the rest
+    pub fn flakiness(&self, test: &Path, threshold: f64) -> Option<Flakiness> {
+        let mut by_state: HashMap<&str, Flakiness> = HashMap::new();
+        for run in self.tests.get(&self.key(test))? {
+            let counts = by_state.entry(&run.state).or_insert(Flakiness {
+                passed: 0,
+                failed: 0,
+            });
+            if run.passed {
+                counts.passed += 1;
+            } else {
+                counts.failed += 1;
+            }
+        }
+        by_state
+            .into_values()
+            .filter(|f| f.passed > 0 && f.failed > 0 && f.rate() >= threshold)
+            .max_by(|a, b| a.rate().total_cmp(&b.rate()))
+    }
+
+    /// Write the history back. Best effort, like the dependency cache.
+    pub fn save(&self) {
+        let path = self.project_root.join(HISTORY_FILE);
+        let Some(dir) = path.parent() else {
+            return;
+        };
+        let file = HistoryFile {
+            version: HISTORY_VERSION,
+            tests: self.tests.clone(),
+        };
+        let _ = std::fs::create_dir_all(dir).and_then(|_| {
+            // Through a temporary file, so a reader never sees half a file
+            let mut tmp = tempfile::NamedTempFile::new_in(dir)?;
+            std::io::Write::write_all(&mut tmp, &serde_json::to_vec(&file).unwrap_or_default())?;
+            tmp.persist(&path).map_err(|e| e.error)?;
+            Ok(())
+        });
+    }
+
+    fn key(&self, test: &Path) -> String {
+        let rel = test.strip_prefix(&self.project_root).unwrap_or(test);
+        rel.to_string_lossy().replace('\\', "/")
+    }
+}
+
+/// Hash of the code `test` runs: the script, the scripts it calls and the
+/// project's lockfile
+pub fn code_state(project_root: &Path, test: &Path) -> Result<String> {
+    let tree = hash_dependency_tree(test)?.combined_hash();
+    let lockfile = hash_lockfile(project_root)?.unwrap_or_default();
+    Ok(hash_string(&format!("{}{}", tree, lockfile)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_flaky_only_on_same_state() {
+        let temp = TempDir::new().unwrap();
+        let test = temp.path().join("tests/test_fetch.do");
+        let mut history = TestHistory::load(temp.path());
+
+        // Failed on old code, passed after a fix: not flaky
+        history.record(&test, "a", false);
+        history.record(&test, "b", true);
+        assert_eq!(history.flakiness(&test, 0.1), None);
+
+        // Failed and passed on the same code: flaky
+        history.record(&test, "b", true);
+        history.record(&test, "b", false);
+        let flakiness = history.flakiness(&test, 0.1).unwrap();
+        assert_eq!((flakiness.passed, flakiness.failed), (2, 1));
+        assert!(history.flakiness(&test, 0.5).is_none());
+    }
+
+    #[test]
+    fn test_history_round_trip_and_cap() {
+        let temp = TempDir::new().unwrap();
+        let test = temp.path().join("tests/test_fetch.do");
+        let mut history = TestHistory::load(temp.path());
+        for i in 0..MAX_RUNS + 5 {
+            history.record(&test, "a", i % 2 == 0);
+        }
+        history.save();
+
+        let history = TestHistory::load(temp.path());
+        assert_eq!(history.tests["tests/test_fetch.do"].len(), MAX_RUNS);
+        assert!(history.flakiness(&test, 0.1).is_some());
+    }
+}
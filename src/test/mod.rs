@@ -3,4 +3,5 @@
 //! Provides convention-based test discovery and execution for Stata do-files.
 
 pub mod discovery;
+pub mod history;
 pub mod runner;
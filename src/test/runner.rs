@@ -1,14 +1,22 @@
 //! Test execution engine
 //!
 //! Executes discovered tests sequentially or in parallel using StataExecutor.
-
+//!
+//! Each test gets a scratch directory of its own for Stata's temporary files,
+//! `$STACY_TEST_TMP` and (in strict mode) the throwaway PLUS and PERSONAL, so
+//! tests running side by side can't clobber each other's files. Tests that
+//! share a name get kept logs named after their path instead.
+
+use crate::cache::detect::CacheStatus;
+use crate::cache::step::{StepCache, StepKey};
+use crate::error::location::SourceLocation;
 use crate::error::{Result, StataError};
 use crate::executor::log_policy::LogPolicy;
 use crate::executor::StataExecutor;
 use crate::test::discovery::TestFile;
 
 /// Format a StataError into a human-readable string
-fn format_stata_error(err: &StataError) -> String {
+fn format_stata_error(err: &StataError, location: Option<&SourceLocation>) -> String {
     match err {
         StataError::StataCode {
             r_code,
@@ -16,9 +24,12 @@ fn format_stata_error(err: &StataError) -> String {
             line_number,
             ..
         } => {
-            let line_info = line_number
-                .map(|l| format!(" at line {}", l))
-                .unwrap_or_default();
+            // The do-file line when known, else the line of the log
+            let line_info = match (location, line_number) {
+                (Some(location), _) => format!(" at {}", location),
+                (None, Some(line)) => format!(" at line {}", line),
+                (None, None) => String::new(),
+            };
             format!("r({}){} - {}", r_code, line_info, message)
         }
         StataError::ProcessKilled { exit_code } => {
@@ -26,6 +37,7 @@ fn format_stata_error(err: &StataError) -> String {
         }
     }
 }
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
@@ -71,6 +83,10 @@ pub struct TestResult {
     pub error_message: Option<String>,
     /// Path to log file (for verbose error context)
     pub log_file: Option<std::path::PathBuf>,
+    /// Whether the result came from the build cache instead of a run
+    pub cached: bool,
+    /// Failed runs before this one, when a flaky test was retried
+    pub retries: u32,
 }
 
 /// Result of running all tests
@@ -139,6 +155,13 @@ pub struct TestRunner<'a> {
     working_dir: TestWorkingDir,
     /// What happens to each test's log once it has run
     log_policy: LogPolicy,
+    /// Build cache consulted before each test (`--cache`)
+    cache: Option<&'a StepCache>,
+    /// Names more than one test of the suite has
+    shared_names: HashSet<String>,
+    /// Known flaky tests, rerun up to `flaky_retries` times when they fail
+    flaky: HashSet<PathBuf>,
+    flaky_retries: u32,
 }
 
 impl<'a> TestRunner<'a> {
@@ -150,9 +173,33 @@ impl<'a> TestRunner<'a> {
             parallel: false,
             working_dir: TestWorkingDir::default(),
             log_policy: LogPolicy::new(),
+            cache: None,
+            shared_names: HashSet::new(),
+            flaky: HashSet::new(),
+            flaky_retries: 0,
         }
     }
 
+    /// Rerun a failing test from `flaky` up to `retries` times; a rerun that
+    /// passes passes the test, with a warning (`--retry-flaky`)
+    pub fn with_flaky_retries(mut self, flaky: HashSet<PathBuf>, retries: u32) -> Self {
+        self.flaky = flaky;
+        self.flaky_retries = retries;
+        self
+    }
+
+    /// The tests run together, so that same-named tests keep their logs
+    /// apart (`tests/a/check.do` and `tests/b/check.do`)
+    pub fn with_suite(mut self, tests: &[TestFile]) -> Self {
+        let mut seen = HashSet::new();
+        self.shared_names = tests
+            .iter()
+            .filter(|test| !seen.insert(test.name.as_str()))
+            .map(|test| test.name.clone())
+            .collect();
+        self
+    }
+
     /// Enable parallel test execution
     pub fn with_parallel(mut self, parallel: bool) -> Self {
         self.parallel = parallel;
@@ -171,19 +218,63 @@ impl<'a> TestRunner<'a> {
         self
     }
 
+    /// Skip tests that passed before and whose script and dependencies are
+    /// unchanged. The caller saves the cache once the suite is done.
+    pub fn with_cache(mut self, cache: &'a StepCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
     /// Run a single test
     pub fn run_test(&self, test: &TestFile) -> Result<TestResult> {
         let start = Instant::now();
 
         let working_dir = resolve_working_dir(&self.working_dir, self.project_root, &test.path);
-        let result = self
-            .stata
-            .run_in_dir(&test.path, Some(self.project_root), &working_dir)?;
+
+        let key = StepKey::new(&format!("test:{}", test.name));
+        if let Some(cache) = self.cache {
+            if let CacheStatus::Hit(entry) = cache.check(&test.path, Some(&working_dir), &key)? {
+                self.stata.report_cached(&test.path, &entry.result);
+                return Ok(TestResult {
+                    name: test.name.clone(),
+                    path: test.path.clone(),
+                    passed: entry.result.success,
+                    exit_code: entry.result.exit_code,
+                    duration: Duration::from_secs_f64(entry.result.duration_secs),
+                    error_message: None,
+                    log_file: None,
+                    cached: true,
+                    retries: 0,
+                });
+            }
+        }
+
+        let run = || -> Result<_> {
+            let scratch = tempfile::TempDir::with_prefix("stacy-test-")?;
+            self.stata.run_isolated(
+                &test.path,
+                Some(self.project_root),
+                &working_dir,
+                scratch.path(),
+            )
+        };
+        let mut result = run()?;
+        let mut retries = 0;
+        while !result.success && retries < self.flaky_retries && self.flaky.contains(&test.path) {
+            // Only the last attempt's log is reported
+            let _ = std::fs::remove_file(&result.log_file);
+            retries += 1;
+            result = run()?;
+        }
         let duration = start.elapsed();
 
+        if let Some(cache) = self.cache {
+            cache.record(&test.path, Some(&working_dir), &key, &result)?;
+        }
+
         let error_message = if !result.success {
             if let Some(err) = result.errors.first() {
-                Some(format_stata_error(err))
+                Some(format_stata_error(err, result.error_location()))
             } else {
                 Some(format!("Exit code {}", result.exit_code))
             }
@@ -191,9 +282,14 @@ impl<'a> TestRunner<'a> {
             None
         };
 
-        // A passing test's log is internal and is removed; a failing test keeps
-        // its log (in `[run] log_dir` when set) — the failure report reads it.
-        let log_file = self.log_policy.finalize(&result.log_file, result.success);
+        // A passing test's log is internal and is removed (unless --log-dir or
+        // --log-name keep it); a failing test keeps its log (in `[run] log_dir`
+        // when set) — the failure report reads it.
+        let log_file = self.log_policy.finalize_for(
+            &log_script(self.project_root, test, &self.shared_names),
+            &result.log_file,
+            result.success,
+        );
 
         Ok(TestResult {
             name: test.name.clone(),
@@ -203,6 +299,8 @@ impl<'a> TestRunner<'a> {
             duration,
             error_message,
             log_file,
+            cached: false,
+            retries,
         })
     }
 
@@ -272,10 +370,46 @@ impl<'a> TestRunner<'a> {
     }
 }
 
+/// What a test's kept log is named after: the test itself, or, when another
+/// test has its name, its path within the project (`tests_a_check`), so the
+/// two logs don't overwrite each other
+fn log_script(project_root: &Path, test: &TestFile, shared: &HashSet<String>) -> PathBuf {
+    if !shared.contains(&test.name) {
+        return test.path.clone();
+    }
+    let rel = test.path.strip_prefix(project_root).unwrap_or(&test.path);
+    let name: Vec<String> = rel
+        .with_extension("")
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect();
+    PathBuf::from(format!("{}.do", name.join("_")))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_log_script_disambiguates_shared_names() {
+        let root = Path::new("/project");
+        let tests = [
+            TestFile::from_path(PathBuf::from("/project/tests/a/check.do")),
+            TestFile::from_path(PathBuf::from("/project/tests/b/check.do")),
+            TestFile::from_path(PathBuf::from("/project/tests/test_merge.do")),
+        ];
+        let shared = HashSet::from(["check".to_string()]);
+        assert_eq!(
+            log_script(root, &tests[0], &shared),
+            PathBuf::from("tests_a_check.do")
+        );
+        assert_eq!(
+            log_script(root, &tests[1], &shared),
+            PathBuf::from("tests_b_check.do")
+        );
+        assert_eq!(log_script(root, &tests[2], &shared), tests[2].path);
+    }
+
     #[test]
     fn test_resolve_working_dir_project_root() {
         let dir = resolve_working_dir(
@@ -326,6 +460,8 @@ mod tests {
             duration: Duration::from_secs(1),
             error_message: None,
             log_file: None,
+            cached: false,
+            retries: 0,
         });
 
         assert_eq!(suite.test_count, 1);
@@ -345,6 +481,8 @@ mod tests {
             duration: Duration::from_secs(1),
             error_message: Some("r(601) - file not found".to_string()),
             log_file: None,
+            cached: false,
+            retries: 0,
         });
 
         assert_eq!(suite.test_count, 1);
@@ -364,6 +502,8 @@ mod tests {
             duration: Duration::from_secs(1),
             error_message: None,
             log_file: None,
+            cached: false,
+            retries: 0,
         });
         suite.add_result(TestResult {
             name: "test_fail".to_string(),
@@ -373,6 +513,8 @@ mod tests {
             duration: Duration::from_secs(2),
             error_message: Some("error".to_string()),
             log_file: None,
+            cached: false,
+            retries: 0,
         });
 
         assert_eq!(suite.test_count, 2);
@@ -3,13 +3,26 @@
 //! Checks for new stacy releases using cached data from GitHub Releases API.
 //! Prints a notification to stderr if an update is available, then spawns a
 //! background thread to refresh the cache for the next invocation.
+//!
+//! Checks follow the configured release channel (`update_channel`), each with
+//! its own cache file, and go through the configured proxy. `STACY_OFFLINE=1`
+//! turns off every network access made here.
+//!
+//! With `update_check_packages = true`, the same check also looks at the
+//! current project's lockfile: once a week it compares the locked packages
+//! with their sources in the background, and when some have updates it prints
+//! a one-line nudge, again at most once a week per project.
 
+use crate::history::unix_now;
+use crate::project::user_config::UpdateChannel;
 use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
 use std::io::IsTerminal;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
 
-/// Cache file storing the result of the last version check
+/// Cache file storing the result of the last version check (stable channel;
+/// other channels use `version-check-<channel>.json`)
 const CACHE_FILE: &str = "version-check.json";
 
 /// Plain-text flag file for Stata to read
@@ -18,11 +31,17 @@ const FLAG_FILE: &str = "update-available";
 /// Cache is considered fresh for 24 hours
 const CACHE_TTL_SECS: u64 = 24 * 60 * 60;
 
+/// Directory, within the cache directory, of each project's last package check
+const PACKAGE_CHECK_DIR: &str = "package-check";
+
+/// A project's packages are checked, and their nudge printed, at most weekly
+const PACKAGE_CHECK_INTERVAL_SECS: u64 = 7 * 24 * 60 * 60;
+
 /// Timeout for GitHub API requests
 const REQUEST_TIMEOUT_SECS: u64 = 3;
 
-/// GitHub API endpoint for latest release
-const RELEASES_URL: &str = "https://api.github.com/repos/janfasnacht/stacy/releases/latest";
+/// GitHub API endpoint for stacy releases (`/latest`, `/tags/<tag>`)
+pub const RELEASES_API: &str = "https://api.github.com/repos/janfasnacht/stacy/releases";
 
 /// Cached version check result, serialized to `~/.cache/stacy/version-check.json`
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,6 +52,18 @@ pub struct VersionCheckCache {
     pub update_available: bool,
 }
 
+/// Last package check of one project, serialized to
+/// `~/.cache/stacy/package-check/<hash of project root>.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageCheckCache {
+    pub project_root: PathBuf,
+    pub checked_at_unix: u64,
+    /// Locked packages with a newer version at their source
+    pub outdated: Vec<String>,
+    /// When the nudge about them was last printed
+    pub notified_at_unix: Option<u64>,
+}
+
 /// How stacy was installed, used to generate upgrade instructions
 #[derive(Debug, Clone, PartialEq)]
 pub enum InstallMethod {
@@ -50,9 +81,14 @@ pub fn maybe_notify_and_spawn() {
         return;
     }
 
+    if packages_check_enabled() {
+        maybe_nudge_packages();
+    }
+
     // Read cache and print notification if update is available
     let current = env!("CARGO_PKG_VERSION");
-    if let Some(cache) = load_cached_update() {
+    let channel = configured_channel();
+    if let Some(cache) = load_cached_update_for(channel) {
         if compare_versions(current, &cache.latest_version) {
             print_notification(current, &cache.latest_version);
         }
@@ -66,12 +102,43 @@ pub fn maybe_notify_and_spawn() {
     // Spawn background thread to refresh cache
     let current_owned = current.to_string();
     std::thread::spawn(move || {
-        refresh_cache(&current_owned);
+        refresh_cache(&current_owned, channel);
     });
 }
 
+/// Whether `STACY_OFFLINE` forbids network access (any value but empty or `0`).
+///
+/// For air-gapped machines: no update check is attempted, not even in the
+/// background, and `stacy self update` refuses to run.
+pub fn is_offline() -> bool {
+    std::env::var("STACY_OFFLINE")
+        .map(|v| !v.is_empty() && v != "0")
+        .unwrap_or(false)
+}
+
+/// The release channel from the user config (stable by default)
+pub fn configured_channel() -> UpdateChannel {
+    crate::project::user_config::load_user_config()
+        .ok()
+        .flatten()
+        .and_then(|c| c.update_channel)
+        .unwrap_or_default()
+}
+
+fn cache_file_name(channel: UpdateChannel) -> String {
+    match channel {
+        UpdateChannel::Stable => CACHE_FILE.to_string(),
+        other => format!("version-check-{}.json", other),
+    }
+}
+
 /// Check if update notifications should be suppressed.
 fn should_suppress() -> bool {
+    // Air-gapped: never touch the network
+    if is_offline() {
+        return true;
+    }
+
     // CI environments
     if std::env::var("CI").is_ok() || std::env::var("GITHUB_ACTIONS").is_ok() {
         return true;
@@ -97,13 +164,151 @@ fn should_suppress() -> bool {
     false
 }
 
-/// Load the cached version check from disk.
+/// Whether `update_check_packages` opts into package update nudges
+fn packages_check_enabled() -> bool {
+    crate::project::user_config::load_user_config()
+        .ok()
+        .flatten()
+        .and_then(|c| c.update_check_packages)
+        .unwrap_or(false)
+}
+
+/// Print the nudge about the current project's outdated packages if it is
+/// due, and recheck them in the background once the last check is a week old.
+fn maybe_nudge_packages() {
+    let Ok(Some(root)) = crate::project::root::find_project_root_from_cwd() else {
+        return;
+    };
+    let root = root.path;
+    if !root.join(crate::project::profile::lockfile_name()).exists() {
+        return;
+    }
+    let Some(path) = package_cache_path(&root) else {
+        return;
+    };
+
+    let now = unix_now();
+    let mut notified_at = None;
+    if let Some(mut cache) = std::fs::read_to_string(&path)
+        .ok()
+        .and_then(|content| serde_json::from_str::<PackageCheckCache>(&content).ok())
+    {
+        if nudge_due(&cache, now) {
+            print_package_nudge(&cache.outdated);
+            cache.notified_at_unix = Some(now);
+            write_package_cache(&path, &cache);
+        }
+        if now.saturating_sub(cache.checked_at_unix) < PACKAGE_CHECK_INTERVAL_SECS {
+            return;
+        }
+        notified_at = cache.notified_at_unix;
+    }
+
+    std::thread::spawn(move || {
+        refresh_package_cache(&root, &path, notified_at);
+    });
+}
+
+/// Whether the nudge about `cache`'s outdated packages should be printed now
+fn nudge_due(cache: &PackageCheckCache, now: u64) -> bool {
+    !cache.outdated.is_empty()
+        && cache
+            .notified_at_unix
+            .is_none_or(|at| now.saturating_sub(at) >= PACKAGE_CHECK_INTERVAL_SECS)
+}
+
+/// The package check cache of the project at `project_root`
+fn package_cache_path(project_root: &Path) -> Option<PathBuf> {
+    let key = crate::cache::hash::hash_string(&project_root.display().to_string());
+    Some(
+        cache_dir()?
+            .join(PACKAGE_CHECK_DIR)
+            .join(format!("{}.json", &key[..16])),
+    )
+}
+
+/// Print the one-line package nudge to stderr.
+fn print_package_nudge(outdated: &[String]) {
+    use colored::Colorize;
+    eprintln!(
+        "\n{} {}\nRun {} for details\n",
+        "Package updates available:".yellow().bold(),
+        package_list(outdated),
+        "`stacy outdated`".cyan(),
+    );
+}
+
+/// `a, b, c and 2 more`
+fn package_list(names: &[String]) -> String {
+    const SHOWN: usize = 3;
+    if names.len() <= SHOWN {
+        names.join(", ")
+    } else {
+        format!(
+            "{} and {} more",
+            names[..SHOWN].join(", "),
+            names.len() - SHOWN
+        )
+    }
+}
+
+/// Compare the project's locked packages with their sources and cache the
+/// outdated ones.
+fn refresh_package_cache(project_root: &Path, path: &Path, notified_at: Option<u64>) {
+    let Ok(Some(lockfile)) = crate::packages::lockfile::load_lockfile(project_root) else {
+        return;
+    };
+    let mut outdated = crate::cli::outdated::outdated_package_names(&lockfile);
+    outdated.sort();
+
+    write_package_cache(
+        path,
+        &PackageCheckCache {
+            project_root: project_root.to_path_buf(),
+            checked_at_unix: unix_now(),
+            outdated,
+            notified_at_unix: notified_at,
+        },
+    );
+}
+
+/// Write a package check cache (atomic: write to .tmp then rename)
+fn write_package_cache(path: &Path, cache: &PackageCheckCache) {
+    let Some(dir) = path.parent() else {
+        return;
+    };
+    let _ = std::fs::create_dir_all(dir);
+    if let Ok(json) = serde_json::to_string_pretty(cache) {
+        let tmp_path = path.with_extension("json.tmp");
+        if std::fs::write(&tmp_path, json).is_ok() {
+            let _ = std::fs::rename(&tmp_path, path);
+        }
+    }
+}
+
+/// Load the cached version check for the configured channel from disk.
 pub fn load_cached_update() -> Option<VersionCheckCache> {
-    let path = cache_dir()?.join(CACHE_FILE);
+    load_cached_update_for(configured_channel())
+}
+
+/// Load the cached version check for `channel` from disk.
+pub fn load_cached_update_for(channel: UpdateChannel) -> Option<VersionCheckCache> {
+    let path = cache_dir()?.join(cache_file_name(channel));
     let content = std::fs::read_to_string(path).ok()?;
     serde_json::from_str(&content).ok()
 }
 
+/// Forget the last version check, e.g. after `stacy self update` replaced the
+/// binary it was made for.
+pub fn clear_cached_update() {
+    if let Some(dir) = cache_dir() {
+        for channel in [UpdateChannel::Stable, UpdateChannel::Beta] {
+            let _ = std::fs::remove_file(dir.join(cache_file_name(channel)));
+        }
+        let _ = std::fs::remove_file(dir.join(FLAG_FILE));
+    }
+}
+
 /// Check if the cache is still fresh (less than 24 hours old).
 pub fn is_cache_fresh(cache: &VersionCheckCache) -> bool {
     let now = SystemTime::now()
@@ -129,8 +334,8 @@ fn print_notification(current: &str, latest: &str) {
 }
 
 /// Fetch latest version from GitHub and update cache + flag file.
-fn refresh_cache(current: &str) {
-    let Some(latest) = fetch_latest_version() else {
+fn refresh_cache(current: &str, channel: UpdateChannel) {
+    let Some(latest) = fetch_latest_version(channel) else {
         return;
     };
 
@@ -151,8 +356,9 @@ fn refresh_cache(current: &str) {
     if let Some(dir) = cache_dir() {
         let _ = std::fs::create_dir_all(&dir);
         if let Ok(json) = serde_json::to_string_pretty(&cache) {
-            let tmp_path = dir.join(format!("{CACHE_FILE}.tmp"));
-            let final_path = dir.join(CACHE_FILE);
+            let file_name = cache_file_name(channel);
+            let tmp_path = dir.join(format!("{file_name}.tmp"));
+            let final_path = dir.join(file_name);
             if std::fs::write(&tmp_path, json).is_ok() {
                 let _ = std::fs::rename(&tmp_path, &final_path);
             }
@@ -174,15 +380,22 @@ fn refresh_cache(current: &str) {
     }
 }
 
-/// Fetch the latest release version from GitHub Releases API.
-fn fetch_latest_version() -> Option<String> {
-    let client = reqwest::blocking::Client::builder()
+/// Fetch the latest release version on `channel` from GitHub Releases API.
+///
+/// `/releases/latest` never returns a pre-release, so the beta channel reads
+/// the release list and takes the newest version in it.
+fn fetch_latest_version(channel: UpdateChannel) -> Option<String> {
+    let client = crate::packages::http::client_builder()
         .timeout(std::time::Duration::from_secs(REQUEST_TIMEOUT_SECS))
         .build()
         .ok()?;
 
+    let url = match channel {
+        UpdateChannel::Stable => format!("{RELEASES_API}/latest"),
+        UpdateChannel::Beta => format!("{RELEASES_API}?per_page=30"),
+    };
     let resp = client
-        .get(RELEASES_URL)
+        .get(url)
         .header("User-Agent", format!("stacy/{}", env!("CARGO_PKG_VERSION")))
         .header("Accept", "application/vnd.github.v3+json")
         .send()
@@ -193,20 +406,48 @@ fn fetch_latest_version() -> Option<String> {
     }
 
     let body: serde_json::Value = resp.json().ok()?;
-    let tag = body.get("tag_name")?.as_str()?;
-    Some(tag.strip_prefix('v').unwrap_or(tag).to_string())
+    let tag = match channel {
+        UpdateChannel::Stable => body.get("tag_name")?.as_str()?.to_string(),
+        UpdateChannel::Beta => newest_tag(body.as_array()?.iter().filter_map(|release| {
+            let draft = release.get("draft").and_then(|d| d.as_bool());
+            match draft {
+                Some(true) => None,
+                _ => release.get("tag_name")?.as_str(),
+            }
+        }))?,
+    };
+    Some(tag.strip_prefix('v').unwrap_or(&tag).to_string())
+}
+
+/// The newest of a set of release tags
+pub fn newest_tag<'a>(tags: impl Iterator<Item = &'a str>) -> Option<String> {
+    tags.fold(None, |newest: Option<&str>, tag| match newest {
+        Some(n) if !compare_versions(n, tag) => Some(n),
+        _ => Some(tag),
+    })
+    .map(str::to_string)
 }
 
 /// Compare two semver-like version strings. Returns true if latest > current.
+///
+/// A pre-release (`1.3.0-beta.2`) is older than its release (`1.3.0`);
+/// pre-releases of the same version compare identifier by identifier.
 pub fn compare_versions(current: &str, latest: &str) -> bool {
-    let parse = |v: &str| -> Vec<u64> {
-        v.split('.')
+    let split = |v: &str| -> (Vec<u64>, Option<String>) {
+        let v = v.trim().trim_start_matches('v');
+        let (core, pre) = match v.split_once('-') {
+            Some((core, pre)) => (core, Some(pre.to_string())),
+            None => (v, None),
+        };
+        let parts = core
+            .split('.')
             .filter_map(|part| part.parse::<u64>().ok())
-            .collect()
+            .collect();
+        (parts, pre)
     };
 
-    let c = parse(current);
-    let l = parse(latest);
+    let (c, c_pre) = split(current);
+    let (l, l_pre) = split(latest);
 
     // Compare component by component
     for i in 0..c.len().max(l.len()) {
@@ -219,7 +460,36 @@ pub fn compare_versions(current: &str, latest: &str) -> bool {
             return false;
         }
     }
-    false
+
+    match (c_pre, l_pre) {
+        (Some(_), None) => true,
+        (Some(c), Some(l)) => compare_prerelease(&l, &c) == Ordering::Greater,
+        (None, _) => false,
+    }
+}
+
+/// Order pre-release identifiers (`beta.2` < `beta.10` < `rc.1`)
+fn compare_prerelease(a: &str, b: &str) -> Ordering {
+    let mut a_ids = a.split('.');
+    let mut b_ids = b.split('.');
+    loop {
+        match (a_ids.next(), b_ids.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) => {
+                let ord = match (x.parse::<u64>(), y.parse::<u64>()) {
+                    (Ok(x), Ok(y)) => x.cmp(&y),
+                    (Ok(_), Err(_)) => Ordering::Less,
+                    (Err(_), Ok(_)) => Ordering::Greater,
+                    (Err(_), Err(_)) => x.cmp(y),
+                };
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+            }
+        }
+    }
 }
 
 /// Detect how stacy was installed based on the executable path.
@@ -243,12 +513,12 @@ pub fn upgrade_instruction(method: &InstallMethod) -> &'static str {
     match method {
         InstallMethod::Homebrew => "brew upgrade stacy",
         InstallMethod::Cargo => "cargo install stacy",
-        InstallMethod::Manual => "download from https://github.com/janfasnacht/stacy/releases",
+        InstallMethod::Manual => "stacy self update",
     }
 }
 
 /// Get the cache directory for stacy (`~/.cache/stacy/`).
-fn cache_dir() -> Option<PathBuf> {
+pub(crate) fn cache_dir() -> Option<PathBuf> {
     if cfg!(windows) {
         std::env::var("LOCALAPPDATA")
             .map(PathBuf::from)
@@ -306,6 +576,52 @@ mod tests {
         assert!(!compare_versions("1.0.1", "1.0"));
     }
 
+    #[test]
+    fn test_compare_versions_prerelease() {
+        assert!(compare_versions("1.3.0-beta.1", "1.3.0"));
+        assert!(!compare_versions("1.3.0", "1.3.0-beta.1"));
+        assert!(compare_versions("1.3.0-beta.2", "1.3.0-beta.10"));
+        assert!(compare_versions("1.3.0-beta.2", "1.3.0-rc.1"));
+        assert!(compare_versions("1.2.0", "1.3.0-beta.1"));
+        assert!(!compare_versions("1.3.0-beta.1", "1.3.0-beta.1"));
+    }
+
+    #[test]
+    fn test_newest_tag() {
+        let tags = ["v1.2.0", "v1.3.0-beta.2", "v1.2.1", "v1.3.0-beta.1"];
+        assert_eq!(
+            newest_tag(tags.iter().copied()),
+            Some("v1.3.0-beta.2".to_string())
+        );
+        assert_eq!(newest_tag(std::iter::empty()), None);
+    }
+
+    #[test]
+    fn test_cache_file_per_channel() {
+        assert_eq!(cache_file_name(UpdateChannel::Stable), "version-check.json");
+        assert_eq!(
+            cache_file_name(UpdateChannel::Beta),
+            "version-check-beta.json"
+        );
+    }
+
+    #[test]
+    fn test_is_offline() {
+        let was = std::env::var("STACY_OFFLINE").ok();
+
+        std::env::set_var("STACY_OFFLINE", "1");
+        assert!(is_offline());
+        assert!(should_suppress());
+        std::env::set_var("STACY_OFFLINE", "0");
+        assert!(!is_offline());
+        std::env::remove_var("STACY_OFFLINE");
+        assert!(!is_offline());
+
+        if let Some(val) = was {
+            std::env::set_var("STACY_OFFLINE", val);
+        }
+    }
+
     #[test]
     fn test_cache_round_trip() {
         let cache = VersionCheckCache {
@@ -371,6 +687,36 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_package_nudge_weekly() {
+        let now = 1_700_000_000;
+        let mut cache = PackageCheckCache {
+            project_root: PathBuf::from("/work/wages"),
+            checked_at_unix: now,
+            outdated: vec!["estout".to_string()],
+            notified_at_unix: None,
+        };
+        assert!(nudge_due(&cache, now));
+
+        cache.notified_at_unix = Some(now - 3 * 24 * 3600);
+        assert!(!nudge_due(&cache, now));
+        cache.notified_at_unix = Some(now - 8 * 24 * 3600);
+        assert!(nudge_due(&cache, now));
+
+        cache.outdated.clear();
+        assert!(!nudge_due(&cache, now));
+    }
+
+    #[test]
+    fn test_package_list() {
+        let names: Vec<String> = ["a", "b", "c", "d", "e"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(package_list(&names[..2]), "a, b");
+        assert_eq!(package_list(&names), "a, b, c and 2 more");
+    }
+
     #[test]
     fn test_detect_install_method_manual() {
         // In test environment, exe path won't contain Cellar or .cargo/bin
@@ -392,7 +738,7 @@ mod tests {
         );
         assert_eq!(
             upgrade_instruction(&InstallMethod::Manual),
-            "download from https://github.com/janfasnacht/stacy/releases"
+            "stacy self update"
         );
     }
 }
@@ -1,7 +1,8 @@
-//! Date utilities for version fallback
+//! Date utilities for version fallback and log names
 //!
 //! When SSC packages lack a `distribution_date`, we use today's date
 //! in YYYYMMDD format (matching SSC's convention) as the version string.
+//! `--log-name` patterns use the same date, and the time of day.
 
 use std::time::{SystemTime, UNIX_EPOCH};
 
@@ -10,12 +11,22 @@ use std::time::{SystemTime, UNIX_EPOCH};
 /// Uses Hinnant's civil calendar algorithm to convert Unix timestamp
 /// to a calendar date without external dependencies.
 pub fn today_yyyymmdd() -> String {
+    now_utc().0
+}
+
+/// Returns the current UTC date and time as ("YYYYMMDD", "HHMMSS"), read
+/// from one clock sample so the two always agree.
+pub fn now_utc() -> (String, String) {
     let secs = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .expect("system clock before Unix epoch")
         .as_secs();
     let (y, m, d) = civil_from_days((secs / 86400) as i64);
-    format!("{:04}{:02}{:02}", y, m, d)
+    let t = secs % 86400;
+    (
+        format!("{:04}{:02}{:02}", y, m, d),
+        format!("{:02}{:02}{:02}", t / 3600, t % 3600 / 60, t % 60),
+    )
 }
 
 /// Hinnant's civil_from_days algorithm.
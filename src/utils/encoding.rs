@@ -0,0 +1,116 @@
+//! Text encoding of do-files and logs
+//!
+//! Stata 14 and later read do-files as UTF-8. Files written with older
+//! Statas are Latin-1 (Windows-1252 on Windows) and still common: reading
+//! them as UTF-8 fails, and reading them lossily turns every accented
+//! character into `�`. [`decode`] takes the bytes as UTF-8 when they are
+//! valid UTF-8 and as Windows-1252 otherwise, a superset of Latin-1's
+//! printable characters, so any file decodes to what its author typed.
+//!
+//! Hashes are taken over the bytes on disk, never over decoded text, so they
+//! don't depend on how a file was read.
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// How a file's bytes were read
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    Utf8,
+    /// Not valid UTF-8, read as Windows-1252 (Latin-1)
+    Latin1,
+}
+
+impl std::fmt::Display for Encoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Encoding::Utf8 => write!(f, "UTF-8"),
+            Encoding::Latin1 => write!(f, "Latin-1"),
+        }
+    }
+}
+
+/// Windows-1252 characters for bytes 0x80-0x9F, where it differs from
+/// Latin-1. The five bytes it leaves undefined map to the C1 controls.
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '\u{20AC}', '\u{0081}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{008D}', '\u{017D}', '\u{008F}',
+    '\u{0090}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{009D}', '\u{017E}', '\u{0178}',
+];
+
+/// Decode a file's bytes, dropping a UTF-8 byte order mark
+pub fn decode(bytes: &[u8]) -> (String, Encoding) {
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+    match std::str::from_utf8(bytes) {
+        Ok(text) => (text.to_string(), Encoding::Utf8),
+        Err(_) => (
+            bytes
+                .iter()
+                .map(|&b| match b {
+                    0x80..=0x9F => WINDOWS_1252_HIGH[(b - 0x80) as usize],
+                    _ => b as char,
+                })
+                .collect(),
+            Encoding::Latin1,
+        ),
+    }
+}
+
+/// [`decode`], for callers that don't need to know the encoding
+pub fn decode_text(bytes: &[u8]) -> String {
+    decode(bytes).0
+}
+
+/// Read a text file in whichever encoding it is in
+pub fn read_text(path: &Path) -> std::io::Result<(String, Encoding)> {
+    std::fs::read(path).map(|bytes| decode(&bytes))
+}
+
+/// Whether the file at `path` is UTF-8. A file that can't be read counts as
+/// UTF-8: there is nothing to warn about.
+pub fn is_utf8_file(path: &Path) -> bool {
+    std::fs::read(path).map_or(true, |bytes| std::str::from_utf8(&bytes).is_ok())
+}
+
+/// How to convert a Latin-1 do-file, for warnings
+pub fn translate_hint(script: &Path) -> String {
+    let name = script
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| script.display().to_string());
+    format!(
+        "convert it once in Stata, from its directory: `unicode encoding set latin1` then `unicode translate \"{}\"`",
+        name
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_utf8() {
+        let (text, encoding) = decode("display \"Zürich\"".as_bytes());
+        assert_eq!(text, "display \"Zürich\"");
+        assert_eq!(encoding, Encoding::Utf8);
+
+        let (text, _) = decode(b"\xEF\xBB\xBFdisplay 1");
+        assert_eq!(text, "display 1");
+    }
+
+    #[test]
+    fn test_decode_latin1() {
+        // "Zürich" and a Windows-1252 curly quote, as an old Stata wrote them
+        let (text, encoding) = decode(b"label var city \"Z\xFCrich\" \x93x\x94");
+        assert_eq!(text, "label var city \"Zürich\" \u{201C}x\u{201D}");
+        assert_eq!(encoding, Encoding::Latin1);
+    }
+
+    #[test]
+    fn test_translate_hint_names_the_file() {
+        let hint = translate_hint(Path::new("src/clean.do"));
+        assert!(hint.contains("unicode translate \"clean.do\""));
+    }
+}
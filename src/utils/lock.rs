@@ -0,0 +1,283 @@
+//! Advisory file locks
+//!
+//! Coordinates processes that touch the same directory at once — several
+//! users reading a shared package cache while an administrator populates it,
+//! or two `stacy install`s writing the same global cache. The locks are
+//! advisory (`flock` on Unix, `LockFileEx` on Windows): they only order
+//! processes that also take them, and they are released when the file closes.
+//!
+//! Changes to a project's state (stacy.lock, `.stacy/cache`) and removals
+//! from the global package cache go through [`with_state_lock`], which
+//! doesn't queue behind another stacy: it fails with [`Error::Locked`] unless
+//! `--wait` was given. Short saves a run makes on its way out (the build
+//! cache, the artifact registry) go through [`with_state_write_lock`]
+//! instead, which waits a while for the other process rather than losing
+//! what the run recorded. Installs into the package cache wait their turn
+//! (see `packages::installer`). A command that holds the lock while it works
+//! (`stacy add` from loading stacy.lock to saving it) can call code that
+//! takes it again: on the same thread that's a no-op.
+
+use crate::error::{Error, Result};
+use std::cell::RefCell;
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// Name of the lock file stacy keeps at the root of a package cache.
+pub const LOCK_FILE_NAME: &str = ".stacy.lock";
+
+/// How long [`with_state_write_lock`] waits for another holder
+pub const STATE_WRITE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How often a waiting [`with_state_write_lock`] tries the lock again
+const RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Whether [`with_state_lock`] waits for another holder (`--wait`)
+static WAIT: AtomicBool = AtomicBool::new(false);
+
+/// Make [`with_state_lock`] wait for the lock instead of failing
+pub fn set_wait(wait: bool) {
+    WAIT.store(wait, Ordering::Relaxed);
+}
+
+thread_local! {
+    /// Lock files this thread holds through [`with_state_lock`]. Locking one
+    /// again through another open file would find it taken.
+    static HELD: RefCell<Vec<PathBuf>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Marks a lock file held by this thread until dropped
+struct Held;
+
+impl Held {
+    fn new(lock_path: &Path) -> Self {
+        HELD.with(|held| held.borrow_mut().push(lock_path.to_path_buf()));
+        Held
+    }
+}
+
+impl Drop for Held {
+    fn drop(&mut self) {
+        HELD.with(|held| held.borrow_mut().pop());
+    }
+}
+
+/// Lock file guarding a project's state: `.stacy/.stacy.lock`
+pub fn project_lock_path(project_root: &Path) -> PathBuf {
+    project_root.join(".stacy").join(LOCK_FILE_NAME)
+}
+
+/// Run `f` while holding an exclusive lock on `lock_path`.
+///
+/// The lock file is created if needed. Blocks until every other holder
+/// (shared or exclusive) has released it.
+pub fn with_exclusive_lock<T>(lock_path: &Path, f: impl FnOnce() -> T) -> Result<T> {
+    if let Some(parent) = lock_path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| lock_error(lock_path, e))?;
+    }
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(lock_path)
+        .map_err(|e| lock_error(lock_path, e))?;
+
+    let mut lock = fd_lock::RwLock::new(file);
+    let _guard = lock.write().map_err(|e| lock_error(lock_path, e))?;
+    Ok(f())
+}
+
+/// Run `f` while holding an exclusive lock on `lock_path`, to change the
+/// state it guards. A change that reads the state first holds the lock from
+/// the read to the write (`stacy add` from loading stacy.lock to saving it),
+/// so another stacy process can't save in between and have its change
+/// overwritten.
+///
+/// When another process holds the lock, fails with [`Error::Locked`] naming
+/// the guarded directory, or with `--wait` says so and blocks until it's
+/// released. When this thread holds it already, `f` just runs.
+pub fn with_state_lock<T>(lock_path: &Path, f: impl FnOnce() -> T) -> Result<T> {
+    lock_state(lock_path, None, f)
+}
+
+/// Like [`with_state_lock`], for a short write of state that doesn't depend
+/// on a decision made earlier (merging a run's entries into the build
+/// cache): when another process holds the lock, waits up to
+/// [`STATE_WRITE_TIMEOUT`] for it before failing with [`Error::Locked`].
+pub fn with_state_write_lock<T>(lock_path: &Path, f: impl FnOnce() -> T) -> Result<T> {
+    lock_state(lock_path, Some(STATE_WRITE_TIMEOUT), f)
+}
+
+/// Take the state lock, waiting up to `timeout` for another holder (or for
+/// as long as it takes with `--wait`)
+fn lock_state<T>(lock_path: &Path, timeout: Option<Duration>, f: impl FnOnce() -> T) -> Result<T> {
+    if HELD.with(|held| held.borrow().iter().any(|p| p == lock_path)) {
+        return Ok(f());
+    }
+    if let Some(parent) = lock_path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| lock_error(lock_path, e))?;
+    }
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(lock_path)
+        .map_err(|e| lock_error(lock_path, e))?;
+
+    let guarded = lock_path.parent().unwrap_or(lock_path).to_path_buf();
+    // The same file, to block on once trying has failed
+    let mut waiting = fd_lock::RwLock::new(file.try_clone().map_err(|e| lock_error(lock_path, e))?);
+    let mut lock = fd_lock::RwLock::new(file);
+    let _guard = match lock.try_write() {
+        Ok(guard) => guard,
+        Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+            if WAIT.load(Ordering::Relaxed) {
+                eprintln!(
+                    "Waiting for another stacy process to release {}...",
+                    guarded.display()
+                );
+                waiting.write().map_err(|e| lock_error(lock_path, e))?
+            } else if let Some(timeout) = timeout {
+                let deadline = Instant::now() + timeout;
+                loop {
+                    match waiting.try_write() {
+                        Ok(guard) => break guard,
+                        Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                            if Instant::now() >= deadline {
+                                return Err(Error::Locked(guarded));
+                            }
+                            std::thread::sleep(RETRY_INTERVAL);
+                        }
+                        Err(e) => return Err(lock_error(lock_path, e)),
+                    }
+                }
+            } else {
+                return Err(Error::Locked(guarded));
+            }
+        }
+        Err(e) => return Err(lock_error(lock_path, e)),
+    };
+    let _held = Held::new(lock_path);
+    Ok(f())
+}
+
+/// Run `f` while holding a shared lock on `lock_path`.
+///
+/// Shared locks coexist with each other and wait out an exclusive one. The
+/// lock file is only opened for reading, never created: a read-only location
+/// (a network share mounted without write access) without a lock file has no
+/// writer to coordinate with, so `f` runs unlocked.
+pub fn with_shared_lock<T>(lock_path: &Path, f: impl FnOnce() -> T) -> Result<T> {
+    let Ok(file) = File::open(lock_path) else {
+        return Ok(f());
+    };
+
+    let lock = fd_lock::RwLock::new(file);
+    let _guard = lock.read().map_err(|e| lock_error(lock_path, e))?;
+    Ok(f())
+}
+
+fn lock_error(lock_path: &Path, e: std::io::Error) -> Error {
+    Error::Io(std::io::Error::new(
+        e.kind(),
+        format!("Failed to lock {}: {}", lock_path.display(), e),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_exclusive_lock_creates_lock_file() {
+        let temp = TempDir::new().unwrap();
+        let lock_path = temp.path().join("nested").join(LOCK_FILE_NAME);
+
+        let value = with_exclusive_lock(&lock_path, || 42).unwrap();
+        assert_eq!(value, 42);
+        assert!(lock_path.exists());
+    }
+
+    #[test]
+    fn test_state_lock_fails_while_held() {
+        let temp = TempDir::new().unwrap();
+        let lock_path = project_lock_path(temp.path());
+
+        let inner = with_exclusive_lock(&lock_path, || with_state_lock(&lock_path, || ())).unwrap();
+        match inner {
+            Err(Error::Locked(dir)) => assert_eq!(dir, temp.path().join(".stacy")),
+            other => panic!("expected Error::Locked, got {:?}", other),
+        }
+        assert!(with_state_lock(&lock_path, || ()).is_ok());
+    }
+
+    #[test]
+    fn test_state_write_lock_waits_for_holder() {
+        let temp = TempDir::new().unwrap();
+        let lock_path = project_lock_path(temp.path());
+        let (taken_tx, taken_rx) = std::sync::mpsc::channel();
+
+        let written = std::thread::scope(|s| {
+            s.spawn(|| {
+                with_exclusive_lock(&lock_path, || {
+                    taken_tx.send(()).unwrap();
+                    std::thread::sleep(Duration::from_millis(200));
+                })
+                .unwrap()
+            });
+            taken_rx.recv().unwrap();
+            // A plain state lock gives up, a write waits its turn
+            assert!(matches!(
+                with_state_lock(&lock_path, || ()),
+                Err(Error::Locked(_))
+            ));
+            with_state_write_lock(&lock_path, || "written")
+        });
+        assert_eq!(written.unwrap(), "written");
+    }
+
+    #[test]
+    fn test_state_lock_nests_on_one_thread() {
+        let temp = TempDir::new().unwrap();
+        let lock_path = project_lock_path(temp.path());
+
+        let inner = with_state_lock(&lock_path, || with_state_lock(&lock_path, || 7)).unwrap();
+        assert_eq!(inner.unwrap(), 7);
+        // Still taken for another thread while held here
+        let other = with_state_lock(&lock_path, || {
+            std::thread::scope(|s| {
+                s.spawn(|| matches!(with_state_lock(&lock_path, || ()), Err(Error::Locked(_))))
+                    .join()
+                    .unwrap()
+            })
+        })
+        .unwrap();
+        assert!(other);
+        assert!(with_state_lock(&lock_path, || ()).is_ok());
+    }
+
+    #[test]
+    fn test_shared_lock_without_lock_file_runs_unlocked() {
+        let temp = TempDir::new().unwrap();
+        let lock_path = temp.path().join(LOCK_FILE_NAME);
+
+        let value = with_shared_lock(&lock_path, || "read").unwrap();
+        assert_eq!(value, "read");
+        assert!(!lock_path.exists(), "shared lock must not create files");
+    }
+
+    #[test]
+    fn test_shared_locks_coexist() {
+        let temp = TempDir::new().unwrap();
+        let lock_path = temp.path().join(LOCK_FILE_NAME);
+        std::fs::write(&lock_path, "").unwrap();
+
+        let inner = with_shared_lock(&lock_path, || {
+            with_shared_lock(&lock_path, || true).unwrap()
+        })
+        .unwrap();
+        assert!(inner);
+    }
+}
@@ -1,6 +1,25 @@
 pub mod config_loader;
 pub mod date;
+pub mod encoding;
+pub mod lock;
 pub mod paths;
 pub mod temp;
+pub mod zip;
 
 // Utility functions used across modules
+
+/// Single-quote `value` for a POSIX shell; an embedded `'` becomes `'\''`
+pub fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shell_quote_escapes_single_quotes() {
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+        assert_eq!(shell_quote("$HOME"), "'$HOME'");
+    }
+}
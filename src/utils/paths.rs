@@ -1,4 +1,167 @@
 //! Path manipulation helpers
 //!
-//! Cross-platform path handling utilities.
-//! Reserved for future path normalization and resolution helpers.
+//! On Windows `std::fs::canonicalize` returns verbatim paths: `C:\proj`
+//! comes back as `\\?\C:\proj` and a network share as
+//! `\\?\UNC\server\share\proj`. The same file then has two spellings, so a
+//! cache key written with one misses a lookup made with the other, and
+//! `strip_prefix` against a project root found one way fails for a script
+//! found the other. Stata can't open verbatim paths either.
+//!
+//! [`canonicalize`] returns the plain spelling instead. Paths longer than
+//! `MAX_PATH` (260 characters) don't need the prefix kept for stacy's own
+//! use: the standard library adds it when it opens a long absolute path.
+
+use std::path::{Component, Path, PathBuf};
+
+/// Windows' classic path length limit, beyond which only verbatim paths open
+pub const MAX_PATH: usize = 260;
+
+/// `std::fs::canonicalize` without the Windows verbatim prefix
+pub fn canonicalize(path: &Path) -> std::io::Result<PathBuf> {
+    std::fs::canonicalize(path).map(|canonical| strip_verbatim(&canonical))
+}
+
+/// [`canonicalize`], or `path` as given when that fails (it doesn't exist)
+pub fn canonicalize_or_self(path: &Path) -> PathBuf {
+    canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// `path` without a `\\?\` verbatim prefix: `\\?\C:\x` becomes `C:\x` and
+/// `\\?\UNC\server\share` becomes `\\server\share`. Verbatim paths with no
+/// plain spelling (`\\?\Volume{...}`) are kept as they are.
+pub fn strip_verbatim(path: &Path) -> PathBuf {
+    match path.to_str() {
+        Some(s) => PathBuf::from(strip_verbatim_str(s)),
+        None => path.to_path_buf(),
+    }
+}
+
+/// [`strip_verbatim`] on a path string
+pub fn strip_verbatim_str(path: &str) -> String {
+    if let Some(unc) = path.strip_prefix(r"\\?\UNC\") {
+        return format!(r"\\{}", unc);
+    }
+    match path.strip_prefix(r"\\?\") {
+        Some(rest) if has_drive(rest) => rest.to_string(),
+        _ => path.to_string(),
+    }
+}
+
+/// Whether `path` is too long for programs that don't use verbatim paths,
+/// Stata among them, on Windows
+pub fn exceeds_max_path(path: &Path) -> bool {
+    strip_verbatim(path).as_os_str().len() >= MAX_PATH
+}
+
+/// `C:\` or `C:/` at the start
+fn has_drive(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    bytes.len() >= 3
+        && bytes[0].is_ascii_alphabetic()
+        && bytes[1] == b':'
+        && (bytes[2] == b'\\' || bytes[2] == b'/')
+}
+
+/// Resolve `.` and `..` components without touching the filesystem: the
+/// file may not exist (yet)
+///
+/// `..` at the root stays at the root; a relative path that climbs above its
+/// start keeps its leading `..`s.
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match out.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    out.pop();
+                }
+                Some(Component::RootDir) => {}
+                _ => out.push(".."),
+            },
+            other => out.push(other.as_os_str()),
+        }
+    }
+    out
+}
+
+/// Write `content` to `path` through a temporary file in the same
+/// directory, so a reader never sees half a file
+pub fn write_atomically(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    std::fs::create_dir_all(dir)?;
+    let mut tmp = tempfile::NamedTempFile::new_in(dir)?;
+    std::io::Write::write_all(&mut tmp, content)?;
+    tmp.persist(path).map_err(|e| e.error)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_strip_verbatim_drive_and_unc() {
+        assert_eq!(
+            strip_verbatim_str(r"\\?\C:\proj\main.do"),
+            r"C:\proj\main.do"
+        );
+        assert_eq!(
+            strip_verbatim_str(r"\\?\UNC\server\share\proj"),
+            r"\\server\share\proj"
+        );
+        // Already plain, or no plain spelling: unchanged
+        assert_eq!(strip_verbatim_str(r"C:\proj"), r"C:\proj");
+        assert_eq!(strip_verbatim_str("/home/me/proj"), "/home/me/proj");
+        assert_eq!(
+            strip_verbatim_str(r"\\?\Volume{1234}\proj"),
+            r"\\?\Volume{1234}\proj"
+        );
+    }
+
+    #[test]
+    fn test_exceeds_max_path() {
+        let long = format!(r"\\?\C:\{}", "a".repeat(MAX_PATH));
+        assert!(exceeds_max_path(Path::new(&long)));
+        assert!(!exceeds_max_path(Path::new(r"\\?\C:\proj\main.do")));
+    }
+
+    #[test]
+    fn test_normalize_parent_dirs() {
+        let cases = [
+            ("/p/src/../lib/./helpers.do", "/p/lib/helpers.do"),
+            ("./data/x.dta", "data/x.dta"),
+            ("data/../out/x.dta", "out/x.dta"),
+            // Climbing above a relative start keeps every `..`
+            ("../x.do", "../x.do"),
+            ("../../x.do", "../../x.do"),
+            ("a/../../x.do", "../x.do"),
+            // Nothing above the root
+            ("/../x.do", "/x.do"),
+            ("a/..", ""),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(
+                normalize(Path::new(path)),
+                PathBuf::from(expected),
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn test_canonicalize_matches_joined_paths() {
+        let temp = TempDir::new().unwrap();
+        let root = canonicalize(temp.path()).unwrap();
+        std::fs::write(root.join("main.do"), "").unwrap();
+        // A script found under the root strips back to a relative path
+        let script = canonicalize(&root.join("main.do")).unwrap();
+        assert_eq!(script.strip_prefix(&root).unwrap(), Path::new("main.do"));
+        assert_eq!(
+            canonicalize_or_self(Path::new("no/such/file.do")),
+            PathBuf::from("no/such/file.do")
+        );
+    }
+}
@@ -0,0 +1,139 @@
+//! Minimal zip writer for `stacy report`
+//!
+//! A bug report bundle is a handful of small text files, so entries are
+//! stored without compression: every unzip tool reads that, and stacy needs
+//! no archive crate (releases are unpacked with the system `tar`, see
+//! `self_update`).
+
+use std::io::{self, Write};
+
+/// A zip archive built in memory, entry by entry
+#[derive(Default)]
+pub struct ZipWriter {
+    data: Vec<u8>,
+    central: Vec<u8>,
+    entries: u16,
+}
+
+impl ZipWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a file at `name` (`/`-separated) holding `contents`
+    pub fn add(&mut self, name: &str, contents: &[u8]) {
+        let offset = self.data.len() as u32;
+        let crc = crc32(contents);
+        let size = contents.len() as u32;
+        let (time, date) = dos_now();
+
+        // Local file header
+        self.data.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        self.data.extend_from_slice(&20u16.to_le_bytes()); // version needed
+        self.data.extend_from_slice(&0x0800u16.to_le_bytes()); // UTF-8 names
+        self.data.extend_from_slice(&0u16.to_le_bytes()); // stored
+        self.data.extend_from_slice(&time.to_le_bytes());
+        self.data.extend_from_slice(&date.to_le_bytes());
+        self.data.extend_from_slice(&crc.to_le_bytes());
+        self.data.extend_from_slice(&size.to_le_bytes());
+        self.data.extend_from_slice(&size.to_le_bytes());
+        self.data
+            .extend_from_slice(&(name.len() as u16).to_le_bytes());
+        self.data.extend_from_slice(&0u16.to_le_bytes()); // extra field
+        self.data.extend_from_slice(name.as_bytes());
+        self.data.extend_from_slice(contents);
+
+        // Its central directory record
+        self.central
+            .extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        self.central.extend_from_slice(&20u16.to_le_bytes()); // made by
+        self.central.extend_from_slice(&20u16.to_le_bytes()); // needed
+        self.central.extend_from_slice(&0x0800u16.to_le_bytes());
+        self.central.extend_from_slice(&0u16.to_le_bytes());
+        self.central.extend_from_slice(&time.to_le_bytes());
+        self.central.extend_from_slice(&date.to_le_bytes());
+        self.central.extend_from_slice(&crc.to_le_bytes());
+        self.central.extend_from_slice(&size.to_le_bytes());
+        self.central.extend_from_slice(&size.to_le_bytes());
+        self.central
+            .extend_from_slice(&(name.len() as u16).to_le_bytes());
+        // Extra field, comment, disk, internal and external attributes
+        self.central.extend_from_slice(&[0; 12]);
+        self.central.extend_from_slice(&offset.to_le_bytes());
+        self.central.extend_from_slice(name.as_bytes());
+        self.entries += 1;
+    }
+
+    /// Write the archive out
+    pub fn finish(mut self, out: &mut impl Write) -> io::Result<()> {
+        let central_offset = self.data.len() as u32;
+        let central_size = self.central.len() as u32;
+        self.data.append(&mut self.central);
+
+        // End of central directory
+        self.data.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+        self.data.extend_from_slice(&[0; 4]); // disk numbers
+        self.data.extend_from_slice(&self.entries.to_le_bytes());
+        self.data.extend_from_slice(&self.entries.to_le_bytes());
+        self.data.extend_from_slice(&central_size.to_le_bytes());
+        self.data.extend_from_slice(&central_offset.to_le_bytes());
+        self.data.extend_from_slice(&0u16.to_le_bytes()); // comment
+        out.write_all(&self.data)
+    }
+}
+
+/// CRC-32 (IEEE) of `bytes`, as zip stores it
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// The current UTC time as MS-DOS (time, date)
+fn dos_now() -> (u16, u16) {
+    let (date, time) = crate::utils::date::now_utc();
+    let field = |s: &str, range: std::ops::Range<usize>| s[range].parse::<u16>().unwrap_or(0);
+    let dos_date = (field(&date, 0..4).saturating_sub(1980) << 9)
+        | (field(&date, 4..6) << 5)
+        | field(&date, 6..8);
+    let dos_time =
+        (field(&time, 0..2) << 11) | (field(&time, 2..4) << 5) | (field(&time, 4..6) / 2);
+    (dos_time, dos_date)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn test_zip_layout() {
+        let mut zip = ZipWriter::new();
+        zip.add("env.json", b"{}");
+        zip.add("logs/run.log", b"display 1\n");
+        let mut bytes = Vec::new();
+        zip.finish(&mut bytes).unwrap();
+
+        assert_eq!(&bytes[..4], b"PK\x03\x04");
+        // End of central directory: two entries
+        let eocd = bytes.len() - 22;
+        assert_eq!(&bytes[eocd..eocd + 4], b"PK\x05\x06");
+        assert_eq!(u16::from_le_bytes([bytes[eocd + 10], bytes[eocd + 11]]), 2);
+        let central = u32::from_le_bytes(bytes[eocd + 16..eocd + 20].try_into().unwrap()) as usize;
+        assert_eq!(&bytes[central..central + 4], b"PK\x01\x02");
+    }
+}
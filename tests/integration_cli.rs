@@ -229,6 +229,34 @@ fn test_deps_missing_file() {
         .stderr(predicate::str::contains("not found"));
 }
 
+#[test]
+fn test_deps_all_classifies_project_scripts() {
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("stacy.toml"), "[project]\n").unwrap();
+    fs::write(
+        temp.path().join("main.do"),
+        "do \"clean.do\"\ndo \"report.do\"\n",
+    )
+    .unwrap();
+    fs::write(temp.path().join("clean.do"), "do \"helpers.do\"\n").unwrap();
+    fs::write(temp.path().join("report.do"), "do \"helpers.do\"\n").unwrap();
+    fs::write(temp.path().join("helpers.do"), "display 1\n").unwrap();
+    fs::write(temp.path().join("scratch.do"), "display 2\n").unwrap();
+
+    let output = stacy()
+        .current_dir(temp.path())
+        .args(["deps", "--all", "--format", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["entry_points"], serde_json::json!(["main.do"]));
+    assert_eq!(json["orphans"], serde_json::json!(["scratch.do"]));
+    assert_eq!(json["shared"][0]["path"], "helpers.do");
+    assert_eq!(json["summary"]["script_count"], 5);
+}
+
 #[test]
 fn test_run_missing_script() {
     stacy()
@@ -496,17 +524,99 @@ fn test_doctor_shows_environment() {
         .stdout(predicate::str::contains("Environment"));
 }
 
+#[test]
+fn test_doctor_fix_creates_dirs_and_repairs_build_cache() {
+    let temp = TempDir::new().unwrap();
+    let home = TempDir::new().unwrap();
+    fs::write(
+        temp.path().join("stacy.toml"),
+        "[paths]\nado = [\"ado/local\"]\n",
+    )
+    .unwrap();
+    fs::create_dir_all(temp.path().join(".stacy/cache")).unwrap();
+    fs::write(temp.path().join(".stacy/cache/build.json"), "not json").unwrap();
+
+    let doctor = |fix: bool| {
+        let mut cmd = stacy();
+        cmd.arg("doctor")
+            .args(["--format", "json"])
+            .current_dir(temp.path())
+            .env("HOME", home.path())
+            .env("XDG_CACHE_HOME", home.path().join("cache"))
+            .env("XDG_CONFIG_HOME", home.path().join("config"))
+            .env("STATA_BINARY", "/nonexistent/stata");
+        if fix {
+            cmd.arg("--fix");
+        }
+        let output = cmd.output().unwrap();
+        serde_json::from_slice::<serde_json::Value>(&output.stdout).unwrap()
+    };
+    let check = |json: &serde_json::Value, name: &str| {
+        json["checks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|c| c["name"] == name)
+            .unwrap()
+            .clone()
+    };
+
+    let before = doctor(false);
+    assert_eq!(check(&before, "Local Ado Paths")["fixable"], true);
+    assert_eq!(check(&before, "Build Cache")["status"], "warn");
+    assert!(!temp.path().join("ado/local").exists());
+
+    let after = doctor(true);
+    assert!(after["fixed"].as_array().unwrap().len() >= 2);
+    assert_eq!(check(&after, "Local Ado Paths")["status"], "pass");
+    assert_eq!(check(&after, "Build Cache")["status"], "pass");
+    assert!(temp.path().join("ado/local").is_dir());
+    assert!(!temp.path().join(".stacy/cache/build.json").exists());
+}
+
 #[test]
 fn test_doctor_no_verbose_flag() {
-    // --verbose flag should no longer exist
+    // doctor has no --verbose of its own; only the global one is listed
+    let output = stacy().arg("doctor").arg("--help").output().unwrap();
+    assert!(output.status.success());
+    let help = String::from_utf8_lossy(&output.stdout);
+    assert!(help.contains("--format"));
+    assert_eq!(help.matches("--verbose").count(), 1, "{}", help);
+    assert!(help.contains("Print more"));
+}
+
+// ============================================================================
+// Report command tests
+// ============================================================================
+
+#[test]
+fn test_report_bundles_config_and_newest_log() {
+    let temp = TempDir::new().unwrap();
+    let home = TempDir::new().unwrap();
+    fs::write(temp.path().join("stacy.toml"), "[project]\nname = \"t\"\n").unwrap();
+    fs::create_dir(temp.path().join("logs")).unwrap();
+    let log = format!("do \"{}/clean.do\"\nr(111);\n", home.path().display());
+    fs::write(temp.path().join("logs/clean.log"), &log).unwrap();
+
     stacy()
-        .arg("doctor")
-        .arg("--help")
+        .args(["report", "--redact-paths", "-o", "bundle.zip"])
+        .current_dir(temp.path())
+        .env("HOME", home.path())
+        .env("XDG_CACHE_HOME", home.path().join("cache"))
+        .env("XDG_CONFIG_HOME", home.path().join("config"))
+        .env("STATA_BINARY", "/nonexistent/stata")
         .assert()
         .success()
-        .stdout(predicate::str::contains("--format"))
-        // Should NOT contain --verbose
-        .stdout(predicate::str::contains("--verbose").not());
+        .stdout(predicate::str::contains("doctor.json"))
+        .stdout(predicate::str::contains("stacy.toml"))
+        .stdout(predicate::str::contains("logs/clean.log"));
+
+    let bundle = fs::read(temp.path().join("bundle.zip")).unwrap();
+    assert_eq!(&bundle[..4], b"PK\x03\x04");
+    // Stored uncompressed, so the redacted log is there as written
+    let text = String::from_utf8_lossy(&bundle);
+    assert!(text.contains("do \"~/clean.do\""), "{}", text);
+    assert!(!text.contains(&home.path().display().to_string()));
 }
 
 // ============================================================================
@@ -569,6 +679,42 @@ fn test_run_quiet_flag() {
         .stderr(predicate::str::is_empty());
 }
 
+#[test]
+fn test_run_pristine_requires_git_repository() {
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("test.do"), "display \"hello\"").unwrap();
+
+    stacy()
+        .current_dir(temp.path())
+        .args(["run", "--pristine", "test.do"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("git"));
+}
+
+#[test]
+fn test_run_json_lines_missing_script_emits_events() {
+    stacy()
+        .args(["run", "--format", "json-lines", "/nonexistent/script.do"])
+        .assert()
+        .code(3)
+        .stdout(predicate::str::starts_with(
+            r#"{"event":"error-detected","script":"/nonexistent/script.do""#,
+        ))
+        .stdout(predicate::str::contains(
+            r#"{"event":"finished","script":"/nonexistent/script.do","success":false,"exit_code":3"#,
+        ));
+}
+
+#[test]
+fn test_run_vscode_missing_script_is_problem_line() {
+    stacy()
+        .args(["run", "--format", "vscode", "/nonexistent/script.do"])
+        .assert()
+        .code(3)
+        .stdout("/nonexistent/script.do: error: Script not found\n");
+}
+
 // ============================================================================
 // Inline code (-c/--code) tests
 // ============================================================================
@@ -1383,6 +1529,119 @@ fn test_lock_json_output() {
         .stdout(predicate::str::contains("\"in_sync\""));
 }
 
+#[test]
+fn test_lock_resolve_conflicts_merges_both_sides() {
+    let temp = TempDir::new().unwrap();
+    let cache = TempDir::new().unwrap();
+    fs::write(
+        temp.path().join("stacy.toml"),
+        r#"[project]
+
+[packages.dependencies]
+ftools = "ssc"
+reghdfe = "ssc"
+"#,
+    )
+    .unwrap();
+    // Each coauthor added one package on their own branch
+    fs::write(
+        temp.path().join("stacy.lock"),
+        r#"version = "1"
+
+<<<<<<< HEAD
+[packages.ftools]
+version = "20230820"
+checksum = "sha256:ccc"
+
+[packages.ftools.source]
+type = "SSC"
+name = "ftools"
+=======
+[packages.reghdfe]
+version = "20231001"
+checksum = "sha256:ddd"
+
+[packages.reghdfe.source]
+type = "SSC"
+name = "reghdfe"
+>>>>>>> coauthor
+"#,
+    )
+    .unwrap();
+
+    stacy()
+        .current_dir(temp.path())
+        .env("XDG_CACHE_HOME", cache.path())
+        .arg("lock")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--resolve-conflicts"));
+
+    stacy()
+        .current_dir(temp.path())
+        .env("XDG_CACHE_HOME", cache.path())
+        .arg("lock")
+        .arg("--resolve-conflicts")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Merged both sides"));
+
+    let lock = fs::read_to_string(temp.path().join("stacy.lock")).unwrap();
+    assert!(!lock.contains("<<<<<<<"));
+    assert!(lock.contains("[packages.ftools]"));
+    assert!(lock.contains("[packages.reghdfe]"));
+
+    stacy()
+        .current_dir(temp.path())
+        .arg("lock")
+        .arg("--check")
+        .assert()
+        .success();
+}
+
+#[test]
+fn test_lock_resolve_conflicts_keeps_markers_when_a_package_fails() {
+    let temp = TempDir::new().unwrap();
+    let cache = TempDir::new().unwrap();
+    fs::write(
+        temp.path().join("stacy.toml"),
+        "[project]\n\n[packages.dependencies]\nestout = \"ssc\"\n",
+    )
+    .unwrap();
+    // Each side pinned another estout; offline, neither can be re-resolved
+    let conflicted = r#"version = "1"
+
+[packages.estout]
+<<<<<<< HEAD
+version = "20240101"
+checksum = "sha256:aaa"
+=======
+version = "20240301"
+checksum = "sha256:bbb"
+>>>>>>> coauthor
+
+[packages.estout.source]
+type = "SSC"
+name = "estout"
+"#;
+    fs::write(temp.path().join("stacy.lock"), conflicted).unwrap();
+
+    stacy()
+        .current_dir(temp.path())
+        .env("XDG_CACHE_HOME", cache.path())
+        .env("LOCALAPPDATA", cache.path())
+        .env("STACY_OFFLINE", "1")
+        .args(["lock", "--resolve-conflicts"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("Updated").not())
+        .stderr(predicate::str::contains("estout"))
+        .stderr(predicate::str::contains("merge conflicts included"));
+
+    let lock = fs::read_to_string(temp.path().join("stacy.lock")).unwrap();
+    assert_eq!(lock, conflicted);
+}
+
 // ============================================================================
 // Add --test flag tests
 // ============================================================================
@@ -1574,6 +1833,159 @@ fn test_install_frozen_in_sync() {
         .success();
 }
 
+/// With `install_mode = "local"`, packages go to the project's ado/ and
+/// the global cache stays empty
+#[test]
+fn test_install_mode_local_installs_into_project() {
+    let temp = TempDir::new().unwrap();
+    let cache = TempDir::new().unwrap();
+
+    fs::write(
+        temp.path().join("stacy.toml"),
+        "[project]\n\n[packages]\ninstall_mode = \"local\"\n",
+    )
+    .unwrap();
+    let vendor = temp.path().join("vendor").join("testpkg");
+    fs::create_dir_all(&vendor).unwrap();
+    fs::write(vendor.join("testpkg.ado"), "program define testpkg\nend\n").unwrap();
+
+    stacy()
+        .current_dir(temp.path())
+        .args(["add", "testpkg", "--source", "local:vendor/testpkg"])
+        .env("XDG_CACHE_HOME", cache.path())
+        .env("LOCALAPPDATA", cache.path())
+        .assert()
+        .success();
+
+    let installed = |root: &std::path::Path| {
+        fs::read_dir(root.join("ado").join("testpkg"))
+            .map(|entries| {
+                entries
+                    .filter_map(|e| e.ok())
+                    .any(|e| e.path().join("testpkg.ado").is_file())
+            })
+            .unwrap_or(false)
+    };
+    assert!(installed(temp.path()));
+    let global = cache.path().join("stacy").join("packages").join("testpkg");
+    assert!(!global.exists());
+
+    // A fresh checkout installs into ado/ again
+    fs::remove_dir_all(temp.path().join("ado")).unwrap();
+    stacy()
+        .current_dir(temp.path())
+        .arg("install")
+        .env("XDG_CACHE_HOME", cache.path())
+        .env("LOCALAPPDATA", cache.path())
+        .assert()
+        .success();
+    assert!(installed(temp.path()));
+    assert!(!global.exists());
+}
+
+/// A read-only cache is checked, not installed into: missing packages are
+/// listed and nothing is written
+#[test]
+fn test_install_read_only_cache_lists_missing_packages() {
+    let temp = TempDir::new().unwrap();
+    let cache = TempDir::new().unwrap();
+
+    fs::write(temp.path().join("stacy.toml"), "[project]\n").unwrap();
+    for name in ["testpkg", "otherpkg"] {
+        let vendor = temp.path().join("vendor").join(name);
+        fs::create_dir_all(&vendor).unwrap();
+        fs::write(
+            vendor.join(format!("{}.ado", name)),
+            format!("program define {}\nend\n", name),
+        )
+        .unwrap();
+    }
+
+    stacy()
+        .current_dir(temp.path())
+        .args(["add", "testpkg", "--source", "local:vendor/testpkg"])
+        .env("XDG_CACHE_HOME", cache.path())
+        .env("LOCALAPPDATA", cache.path())
+        .assert()
+        .success();
+
+    // Everything locked is provisioned: install only checks
+    stacy()
+        .current_dir(temp.path())
+        .arg("install")
+        .env("XDG_CACHE_HOME", cache.path())
+        .env("LOCALAPPDATA", cache.path())
+        .env("STACY_CACHE_READ_ONLY", "1")
+        .assert()
+        .success();
+
+    stacy()
+        .current_dir(temp.path())
+        .args(["add", "otherpkg", "--source", "local:vendor/otherpkg"])
+        .env("XDG_CACHE_HOME", cache.path())
+        .env("LOCALAPPDATA", cache.path())
+        .env("STACY_CACHE_READ_ONLY", "1")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("read-only"));
+
+    // Lock otherpkg the way another machine would have
+    let lock_path = temp.path().join("stacy.lock");
+    let lockfile = fs::read_to_string(&lock_path).unwrap();
+    let start = lockfile.find("[packages.testpkg]").unwrap();
+    let other = lockfile[start..].replace("testpkg", "otherpkg");
+    fs::write(&lock_path, format!("{}\n{}", lockfile, other)).unwrap();
+
+    stacy()
+        .current_dir(temp.path())
+        .args(["install", "--no-verify"])
+        .env("XDG_CACHE_HOME", cache.path())
+        .env("LOCALAPPDATA", cache.path())
+        .env("STACY_CACHE_READ_ONLY", "1")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("read-only"))
+        .stderr(predicate::str::contains("otherpkg"))
+        .stderr(predicate::str::contains("testpkg").not());
+
+    let packages = if cfg!(windows) {
+        cache.path().join("stacy").join("cache").join("packages")
+    } else {
+        cache.path().join("stacy").join("packages")
+    };
+    assert!(packages.join("testpkg").exists());
+    assert!(!packages.join("otherpkg").exists());
+}
+
+#[test]
+fn test_install_target_notes_run_does_not_use_it() {
+    let temp = TempDir::new().unwrap();
+    let cache = TempDir::new().unwrap();
+
+    fs::write(temp.path().join("stacy.toml"), "[project]\n").unwrap();
+    let vendor = temp.path().join("vendor").join("testpkg");
+    fs::create_dir_all(&vendor).unwrap();
+    fs::write(vendor.join("testpkg.ado"), "program define testpkg\nend\n").unwrap();
+
+    stacy()
+        .current_dir(temp.path())
+        .args(["add", "testpkg", "--source", "local:vendor/testpkg"])
+        .env("XDG_CACHE_HOME", cache.path())
+        .env("LOCALAPPDATA", cache.path())
+        .assert()
+        .success();
+
+    stacy()
+        .current_dir(temp.path())
+        .args(["install", "--target", "ado/"])
+        .env("XDG_CACHE_HOME", cache.path())
+        .env("LOCALAPPDATA", cache.path())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("install_mode = \"local\""));
+    assert!(temp.path().join("ado").join("testpkg").is_dir());
+}
+
 #[test]
 fn test_install_frozen_out_of_sync_missing() {
     let temp = TempDir::new().unwrap();
@@ -1644,32 +2056,103 @@ name = "extrapkg"
         .stderr(predicate::str::contains("extrapkg"));
 }
 
-// ============================================================================
-// Task --frozen tests
-// ============================================================================
-
-#[test]
-fn test_task_frozen_flag_exists() {
-    stacy()
-        .arg("task")
-        .arg("--help")
-        .assert()
-        .success()
-        .stdout(predicate::str::contains("--frozen"))
-        .stdout(predicate::str::contains("CI"));
-}
-
+/// `STACY_FROZEN=1` turns on frozen mode without the flag.
 #[test]
-fn test_task_table_with_typoed_work_key_fails() {
-    // A table-form task whose work key is typo'd must error rather than succeed
-    // as a no-op (#92). The unknown key is now named outright (#100).
+fn test_install_frozen_from_env() {
     let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("stacy.toml"), "[project]\n").unwrap();
     fs::write(
-        temp.path().join("stacy.toml"),
-        r#"[project]
-name = "test"
+        temp.path().join("stacy.lock"),
+        r#"version = "1"
 
-[scripts.build]
+[packages.extrapkg]
+version = "1.0.0"
+checksum = "sha256:abc123"
+group = "production"
+
+[packages.extrapkg.source]
+type = "SSC"
+name = "extrapkg"
+"#,
+    )
+    .unwrap();
+
+    stacy()
+        .current_dir(temp.path())
+        .env("STACY_FROZEN", "1")
+        .arg("install")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("out of sync"))
+        .stderr(predicate::str::contains("extrapkg"));
+}
+
+/// `[install] frozen = true` turns on frozen mode for `task`, and
+/// `STACY_FROZEN=0` turns it off again.
+#[test]
+fn test_task_frozen_from_config() {
+    let temp = TempDir::new().unwrap();
+    fs::write(
+        temp.path().join("stacy.toml"),
+        r#"[project]
+
+[install]
+frozen = true
+
+[packages.dependencies]
+estout = "ssc"
+
+[scripts]
+build = "main.do"
+"#,
+    )
+    .unwrap();
+    fs::write(temp.path().join("main.do"), "display 1").unwrap();
+
+    stacy()
+        .current_dir(temp.path())
+        .env_remove("STACY_FROZEN")
+        .args(["task", "build"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No stacy.lock found"));
+
+    let output = stacy()
+        .current_dir(temp.path())
+        .env("STACY_FROZEN", "0")
+        .args(["task", "build"])
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!stderr.contains("No stacy.lock"), "{}", stderr);
+}
+
+// ============================================================================
+// Task --frozen tests
+// ============================================================================
+
+#[test]
+fn test_task_frozen_flag_exists() {
+    stacy()
+        .arg("task")
+        .arg("--help")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("--frozen"))
+        .stdout(predicate::str::contains("CI"));
+}
+
+#[test]
+fn test_task_table_with_typoed_work_key_fails() {
+    // A table-form task whose work key is typo'd must error rather than succeed
+    // as a no-op (#92). The unknown key is now named outright (#100).
+    let temp = TempDir::new().unwrap();
+    fs::write(
+        temp.path().join("stacy.toml"),
+        r#"[project]
+name = "test"
+
+[scripts.build]
 description = "Build everything"
 scripts = ["src/01_clean.do"]
 "#,
@@ -1864,6 +2347,157 @@ build = "main.do"
     );
 }
 
+/// `--resume` skips what a failed run completed. The fake Stata logs each
+/// script it runs and fails `02_analyze` while `fail` exists.
+#[test]
+#[cfg(unix)]
+fn test_task_resume_continues_after_failure() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp = TempDir::new().unwrap();
+    fs::write(
+        temp.path().join("stacy.toml"),
+        r#"[project]
+
+[scripts]
+clean = "src/01_clean.do"
+analyze = "src/02_analyze.do"
+report = "src/03_report.do"
+all = ["clean", "analyze", "report"]
+"#,
+    )
+    .unwrap();
+    fs::create_dir(temp.path().join("src")).unwrap();
+    for script in ["01_clean", "02_analyze", "03_report"] {
+        fs::write(
+            temp.path().join("src").join(format!("{}.do", script)),
+            "display 1\n",
+        )
+        .unwrap();
+    }
+    let calls = temp.path().join("calls.txt");
+    let fail = temp.path().join("fail");
+    let fake = temp.path().join("fake-stata");
+    fs::write(
+        &fake,
+        format!(
+            "#!/bin/sh\n\
+             for arg in \"$@\"; do last=\"$arg\"; done\n\
+             stem=$(basename \"$last\" .do)\n\
+             script=$(grep -o '0[0-9]_[a-z]*' \"$last\" | head -n 1)\n\
+             echo \"$script\" >> '{calls}'\n\
+             if [ \"$script\" = 02_analyze ] && [ -f '{fail}' ]; then\n\
+               printf '%s\\n' 'file data.dta not found' '' 'end of do-file' 'r(601);' > \"$stem.log\"\n\
+             else\n\
+               printf '%s\\n' 'ok' '' 'end of do-file' > \"$stem.log\"\n\
+             fi\n",
+            calls = calls.display(),
+            fail = fail.display()
+        ),
+    )
+    .unwrap();
+    fs::set_permissions(&fake, fs::Permissions::from_mode(0o755)).unwrap();
+
+    fs::write(&fail, "").unwrap();
+    stacy()
+        .current_dir(temp.path())
+        .env("STATA_BINARY", &fake)
+        .args(["task", "all"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("stacy task all --resume"));
+    assert_eq!(
+        fs::read_to_string(&calls).unwrap(),
+        "01_clean\n02_analyze\n"
+    );
+
+    fs::remove_file(&fail).unwrap();
+    stacy()
+        .current_dir(temp.path())
+        .env("STATA_BINARY", &fake)
+        .args(["task", "all", "--resume"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("1 completed in the last run"));
+    assert_eq!(
+        fs::read_to_string(&calls).unwrap(),
+        "01_clean\n02_analyze\n02_analyze\n03_report\n"
+    );
+    assert!(!temp.path().join(".stacy/runs/all.json").exists());
+}
+
+/// `--from` and `--only` run part of a task, and name only its steps
+#[test]
+#[cfg(unix)]
+fn test_task_from_and_only_select_steps() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp = TempDir::new().unwrap();
+    fs::write(
+        temp.path().join("stacy.toml"),
+        r#"[project]
+
+[scripts]
+clean = "src/01_clean.do"
+analyze = "src/02_analyze.do"
+report = "src/03_report.do"
+all = ["clean", "analyze", "report"]
+"#,
+    )
+    .unwrap();
+    fs::create_dir(temp.path().join("src")).unwrap();
+    for script in ["01_clean", "02_analyze", "03_report"] {
+        fs::write(
+            temp.path().join("src").join(format!("{}.do", script)),
+            "display 1\n",
+        )
+        .unwrap();
+    }
+    let calls = temp.path().join("calls.txt");
+    let fake = temp.path().join("fake-stata");
+    fs::write(
+        &fake,
+        format!(
+            "#!/bin/sh\n\
+             for arg in \"$@\"; do last=\"$arg\"; done\n\
+             stem=$(basename \"$last\" .do)\n\
+             grep -o '0[0-9]_[a-z]*' \"$last\" | head -n 1 >> '{calls}'\n\
+             printf '%s\\n' 'ok' '' 'end of do-file' > \"$stem.log\"\n",
+            calls = calls.display()
+        ),
+    )
+    .unwrap();
+    fs::set_permissions(&fake, fs::Permissions::from_mode(0o755)).unwrap();
+
+    stacy()
+        .current_dir(temp.path())
+        .env("STATA_BINARY", &fake)
+        .args(["task", "all", "--from", "analyze"])
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(&calls).unwrap(),
+        "02_analyze\n03_report\n"
+    );
+
+    fs::remove_file(&calls).unwrap();
+    stacy()
+        .current_dir(temp.path())
+        .env("STATA_BINARY", &fake)
+        .args(["task", "all", "--only", "clean,report"])
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(&calls).unwrap(), "01_clean\n03_report\n");
+
+    stacy()
+        .current_dir(temp.path())
+        .env("STATA_BINARY", &fake)
+        .args(["task", "all", "--from", "analyse"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Task 'all' has no step 'analyse'"));
+}
+
 // ============================================================================
 // Integration tests that require network (marked #[ignore])
 // ============================================================================
@@ -2008,6 +2642,221 @@ fn test_test_list_discovers_tests() {
         .stdout(predicate::str::contains("2 tests"));
 }
 
+#[test]
+fn test_error_format_json() {
+    let temp = TempDir::new().unwrap();
+
+    let output = stacy()
+        .current_dir(temp.path())
+        .args(["task", "build", "--format", "json"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    // stdout stays free for a command's own result document
+    assert!(output.stdout.is_empty());
+    let json: serde_json::Value = serde_json::from_slice(&output.stderr).unwrap();
+    assert_eq!(json["success"], false);
+    assert_eq!(json["error"]["kind"], "project_not_found");
+    assert_eq!(json["error"]["exit_code"], 1);
+    assert!(json["error"]["hint"]
+        .as_str()
+        .unwrap()
+        .contains("stacy init"));
+
+    // Human output is unchanged
+    stacy()
+        .current_dir(temp.path())
+        .args(["task", "build"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::starts_with("Error: "));
+}
+
+#[test]
+#[cfg(unix)]
+fn test_log_file_records_diagnostics() {
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("a.do"), "display 1\n").unwrap();
+    let log = temp.path().join("diag.jsonl");
+
+    // `true` exits without writing a log: the run fails, the diagnostics stay
+    stacy()
+        .current_dir(temp.path())
+        .env("STATA_BINARY", "/bin/true")
+        .env_remove("STACY_LOG")
+        .arg("--log-file")
+        .arg(&log)
+        .args(["run", "a.do"])
+        .assert()
+        .failure()
+        // Nothing on stderr without -vvv or STACY_LOG
+        .stderr(predicate::str::contains("starting Stata").not());
+
+    let content = fs::read_to_string(&log).unwrap();
+    let events: Vec<serde_json::Value> = content
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert!(events
+        .iter()
+        .any(|e| e["fields"]["message"] == "Stata binary from $STATA_BINARY"));
+    assert!(events
+        .iter()
+        .any(|e| e["fields"]["message"] == "starting Stata"
+            && e["target"] == "stacy::executor::runner"));
+
+    stacy()
+        .current_dir(temp.path())
+        .env("STATA_BINARY", "/bin/true")
+        .args(["-vvv", "run", "a.do"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Stata command line"));
+}
+
+#[test]
+fn test_global_quiet_flag() {
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("stacy.toml"), "[project]\nname = \"t\"\n").unwrap();
+
+    stacy()
+        .current_dir(temp.path())
+        .arg("lock")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Lockfile is up to date"));
+
+    // Before or after the command, the same
+    for args in [["-q", "lock"], ["lock", "--quiet"]] {
+        stacy()
+            .current_dir(temp.path())
+            .args(args)
+            .assert()
+            .success()
+            .stdout(predicate::str::is_empty());
+    }
+
+    // Failures are still reported
+    stacy()
+        .current_dir(temp.path())
+        .args(["-q", "install"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No stacy.lock found"));
+}
+
+#[test]
+fn test_global_project_flag() {
+    let temp = TempDir::new().unwrap();
+    let project = temp.path().join("proj");
+    let other = temp.path().join("other");
+    fs::create_dir_all(&project).unwrap();
+    fs::create_dir_all(&other).unwrap();
+    fs::write(project.join("stacy.toml"), "[project]\nname = \"t\"\n").unwrap();
+
+    for target in [project.clone(), project.join("stacy.toml")] {
+        let output = stacy()
+            .current_dir(&other)
+            .arg("--project")
+            .arg(&target)
+            .args(["env", "--format", "json"])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        let root = json["project"]["root"].as_str().unwrap();
+        assert!(std::path::Path::new(root).ends_with("proj"), "{}", root);
+    }
+
+    stacy()
+        .current_dir(&project)
+        .args(["list", "--project"])
+        .arg(&other)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("no stacy.toml or stacy.lock"));
+
+    // Only the project's own stacy.toml names it: another config file would
+    // not be read
+    fs::write(project.join("ci.toml"), "[project]\nname = \"ci\"\n").unwrap();
+    stacy()
+        .current_dir(&other)
+        .arg("--project")
+        .arg(project.join("ci.toml"))
+        .arg("list")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("only from stacy.toml"));
+}
+
+#[test]
+fn test_nested_projects_flag() {
+    let temp = TempDir::new().unwrap();
+    let inner = temp.path().join("studies/wages");
+    fs::create_dir_all(&inner).unwrap();
+    fs::write(temp.path().join("stacy.toml"), "[project]\nroot = true\n").unwrap();
+    fs::write(inner.join("stacy.toml"), "").unwrap();
+
+    let root_of = |args: &[&str]| {
+        let output = stacy()
+            .current_dir(&inner)
+            .args(args)
+            .args(["env", "--format", "json"])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        std::path::PathBuf::from(json["project"]["root"].as_str().unwrap())
+    };
+
+    assert!(root_of(&[]).ends_with("studies/wages"));
+    let outermost = root_of(&["--nested-projects", "outermost"]);
+    assert_eq!(
+        outermost.file_name(),
+        temp.path().canonicalize().unwrap().file_name()
+    );
+}
+
+#[test]
+fn test_test_list_format_csv() {
+    let temp = TempDir::new().unwrap();
+    fs::create_dir_all(temp.path().join("tests")).unwrap();
+    fs::write(temp.path().join("tests/test_foo.do"), "display 1").unwrap();
+
+    stacy()
+        .current_dir(temp.path())
+        .args(["test", "--list", "--format", "csv"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("name,path\n"))
+        .stdout(predicate::str::contains("test_foo,"))
+        .stdout(predicate::str::ends_with("test_foo.do\n"));
+
+    // Only the list is a table
+    stacy()
+        .current_dir(temp.path())
+        .args(["test", "--format", "csv"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--format csv requires --list"));
+}
+
+#[test]
+fn test_test_list_format_markdown() {
+    let temp = TempDir::new().unwrap();
+    fs::create_dir_all(temp.path().join("tests")).unwrap();
+    fs::write(temp.path().join("tests/test_foo.do"), "display 1").unwrap();
+
+    stacy()
+        .current_dir(temp.path())
+        .args(["test", "--list", "--format", "markdown"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with(
+            "| Test | Path |\n| --- | --- |\n| `test_foo` | ",
+        ));
+}
+
 #[test]
 fn test_test_discovers_by_naming_convention() {
     let temp = TempDir::new().unwrap();
@@ -2094,6 +2943,18 @@ fn test_test_json_output_no_tests() {
         .stdout(predicate::str::contains("\"success\": true"));
 }
 
+#[test]
+fn test_test_log_name_rejects_path() {
+    let temp = TempDir::new().unwrap();
+
+    stacy()
+        .current_dir(temp.path())
+        .args(["test", "--log-name", "ci/{script}"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--log-dir"));
+}
+
 // ============================================================================
 // Test command tests that require Stata (marked #[ignore])
 // ============================================================================
@@ -2755,22 +3616,133 @@ fn run_and_extract_log(dir: &std::path::Path, script: &str) -> String {
 }
 
 // ============================================================================
-// Cache command tests
+// Daemon command tests
 // ============================================================================
 
 #[test]
-fn test_cache_help() {
+fn test_daemon_requires_project() {
+    let temp = TempDir::new().unwrap();
+
     stacy()
-        .arg("cache")
-        .arg("--help")
+        .current_dir(temp.path())
+        .arg("daemon")
         .assert()
-        .success()
-        .stdout(predicate::str::contains("Manage the build cache"))
-        .stdout(predicate::str::contains("clean"))
-        .stdout(predicate::str::contains("info"));
+        .failure()
+        .stderr(predicate::str::contains("Not in a stacy project"));
 }
 
-#[test]
+// ============================================================================
+// Exec command tests
+// ============================================================================
+
+/// The command gets S_ADO and the project paths; with no lockfile S_ADO is
+/// just BASE.
+#[test]
+#[cfg(unix)]
+fn test_exec_sets_project_environment() {
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("stacy.toml"), "[project]\n").unwrap();
+
+    stacy()
+        .current_dir(temp.path())
+        .args([
+            "exec",
+            "--",
+            "sh",
+            "-c",
+            "echo \"ado=$S_ADO\"; echo \"root=$STACY_PROJECT_ROOT\"",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("ado=BASE\n"))
+        .stdout(predicate::str::contains("root=/"));
+}
+
+#[test]
+#[cfg(unix)]
+fn test_exec_passes_exit_code_through() {
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("stacy.toml"), "[project]\n").unwrap();
+
+    stacy()
+        .current_dir(temp.path())
+        .args(["exec", "--", "sh", "-c", "exit 3"])
+        .assert()
+        .code(3);
+}
+
+#[test]
+fn test_exec_reports_missing_command() {
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("stacy.toml"), "[project]\n").unwrap();
+
+    stacy()
+        .current_dir(temp.path())
+        .args(["exec", "--", "no-such-command-stacy-test"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Command not found: no-such-command-stacy-test",
+        ));
+}
+
+// ============================================================================
+// Dev command tests
+// ============================================================================
+
+#[test]
+fn test_dev_needs_something_to_run() {
+    stacy()
+        .arg("dev")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--run <SCRIPT>"));
+}
+
+#[test]
+fn test_dev_needs_something_to_watch() {
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("stacy.toml"), "[project]\n").unwrap();
+
+    stacy()
+        .current_dir(temp.path())
+        .args(["dev", "--test"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Nothing to watch"));
+}
+
+#[test]
+fn test_dev_missing_smoke_script() {
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("stacy.toml"), "[project]\n").unwrap();
+    fs::create_dir(temp.path().join("ado")).unwrap();
+
+    stacy()
+        .current_dir(temp.path())
+        .args(["dev", "ado", "--run", "smoke.do"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Smoke script smoke.do not found"));
+}
+
+// ============================================================================
+// Cache command tests
+// ============================================================================
+
+#[test]
+fn test_cache_help() {
+    stacy()
+        .arg("cache")
+        .arg("--help")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Manage the build cache"))
+        .stdout(predicate::str::contains("clean"))
+        .stdout(predicate::str::contains("info"));
+}
+
+#[test]
 fn test_cache_info_help() {
     stacy()
         .arg("cache")
@@ -2870,6 +3842,79 @@ fn test_cache_clean_empty_cache() {
         .stdout(predicate::str::contains("empty").or(predicate::str::contains("nothing")));
 }
 
+#[test]
+fn test_cache_clean_while_another_process_holds_the_lock() {
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("stacy.toml"), "[project]\n").unwrap();
+    fs::create_dir_all(temp.path().join(".stacy/cache")).unwrap();
+    fs::write(
+        temp.path().join(".stacy/cache/build.json"),
+        r#"{"version": 1, "entries": {}}"#,
+    )
+    .unwrap();
+
+    let file = fs::File::create(temp.path().join(".stacy/.stacy.lock")).unwrap();
+    let mut lock = fd_lock::RwLock::new(file);
+    let guard = lock.write().unwrap();
+    stacy()
+        .current_dir(temp.path())
+        .args(["cache", "clean"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Another stacy process holds the lock",
+        ))
+        .stderr(predicate::str::contains("--wait"));
+    assert!(temp.path().join(".stacy/cache/build.json").exists());
+
+    drop(guard);
+    stacy()
+        .current_dir(temp.path())
+        .args(["--wait", "cache", "clean"])
+        .assert()
+        .success();
+    assert!(!temp.path().join(".stacy/cache/build.json").exists());
+}
+
+#[test]
+fn test_remove_and_lock_while_another_process_holds_the_lock() {
+    let temp = TempDir::new().unwrap();
+    let config = "[project]\nname = \"t\"\n\n[packages.dependencies]\nestout = \"ssc\"\n";
+    fs::write(temp.path().join("stacy.toml"), config).unwrap();
+    fs::create_dir_all(temp.path().join(".stacy")).unwrap();
+
+    // Refused before anything is read or written, not at the save
+    let file = fs::File::create(temp.path().join(".stacy/.stacy.lock")).unwrap();
+    let mut lock = fd_lock::RwLock::new(file);
+    let guard = lock.write().unwrap();
+    for args in [&["remove", "estout"][..], &["lock"][..]] {
+        stacy()
+            .current_dir(temp.path())
+            .args(args)
+            .env("STACY_OFFLINE", "1")
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(
+                "Another stacy process holds the lock",
+            ));
+    }
+    assert_eq!(
+        fs::read_to_string(temp.path().join("stacy.toml")).unwrap(),
+        config
+    );
+    assert!(!temp.path().join("stacy.lock").exists());
+
+    drop(guard);
+    stacy()
+        .current_dir(temp.path())
+        .args(["remove", "estout"])
+        .assert()
+        .success();
+    assert!(!fs::read_to_string(temp.path().join("stacy.toml"))
+        .unwrap()
+        .contains("estout"));
+}
+
 #[test]
 fn test_cache_clean_json_output() {
     let temp = TempDir::new().unwrap();
@@ -2903,6 +3948,38 @@ fn test_cache_clean_quiet() {
         .stdout(predicate::str::is_empty());
 }
 
+#[test]
+fn test_cache_verify_repair_corrupt_file() {
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("stacy.toml"), "[project]\n").unwrap();
+    fs::create_dir_all(temp.path().join(".stacy/cache")).unwrap();
+    fs::write(temp.path().join(".stacy/cache/build.json"), "{ not json").unwrap();
+
+    stacy()
+        .current_dir(temp.path())
+        .args(["cache", "verify"])
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("unreadable cache file"))
+        .stdout(predicate::str::contains("--repair"));
+
+    stacy()
+        .current_dir(temp.path())
+        .args(["cache", "verify", "--repair", "--format", "json"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"status\": \"repaired\""));
+
+    assert!(!temp.path().join(".stacy/cache/build.json").exists());
+
+    stacy()
+        .current_dir(temp.path())
+        .args(["cache", "verify"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Build cache OK"));
+}
+
 // ============================================================================
 // Bench command tests
 // ============================================================================
@@ -3300,6 +4377,38 @@ fn test_cache_packages_clean_json_without_all() {
         .stdout(predicate::str::contains("\"status\": \"info\""));
 }
 
+#[test]
+fn test_cache_packages_migrate_upgrades_old_layout() {
+    let cache = TempDir::new().unwrap();
+    cache_package(cache.path(), "MyPkg", "1.0");
+
+    stacy()
+        .env("XDG_CACHE_HOME", cache.path())
+        .env("LOCALAPPDATA", cache.path())
+        .args(["cache", "packages", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("stacy cache packages migrate"));
+
+    stacy()
+        .env("XDG_CACHE_HOME", cache.path())
+        .env("LOCALAPPDATA", cache.path())
+        .args(["cache", "packages", "migrate", "--format", "json"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"from\": 1"))
+        .stdout(predicate::str::contains("MyPkg -> mypkg"))
+        .stdout(predicate::str::contains("\"recorded\": 1"));
+
+    stacy()
+        .env("XDG_CACHE_HOME", cache.path())
+        .env("LOCALAPPDATA", cache.path())
+        .args(["cache", "packages", "migrate"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("nothing to migrate"));
+}
+
 #[test]
 fn test_cache_help_shows_packages_subcommand() {
     stacy()
@@ -3532,6 +4641,24 @@ fn test_run_trace_conflicts_with_parallel() {
         .stderr(predicate::str::contains("cannot be used with"));
 }
 
+#[test]
+fn test_run_post_mortem_conflicts_with_parallel() {
+    stacy()
+        .args(["run", "--post-mortem", "--parallel", "a.do", "b.do"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+}
+
+#[test]
+fn test_run_trace_filter_requires_trace() {
+    stacy()
+        .args(["run", "--trace-filter", "user", "-c", "display 1"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--trace <DEPTH>"));
+}
+
 // Tests that require Stata installed (marked #[ignore])
 
 #[test]
@@ -3564,6 +4691,23 @@ fn test_run_trace_inline_shows_trace_verbose() {
         .stdout(predicate::str::contains("set trace on"));
 }
 
+#[test]
+#[ignore]
+fn test_run_trace_output_keeps_console_clean() {
+    let temp = TempDir::new().unwrap();
+    let trace = temp.path().join("trace.log");
+
+    stacy()
+        .current_dir(temp.path())
+        .args(["run", "--trace", "2", "-v", "--trace-output"])
+        .arg(&trace)
+        .args(["-c", "sysuse auto, clear", "-c", "summarize price"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("begin sysuse").not());
+    assert!(fs::read_to_string(&trace).unwrap().contains("begin sysuse"));
+}
+
 #[test]
 #[ignore]
 fn test_run_trace_file_success() {
@@ -3795,3 +4939,914 @@ fn test_run_timeout_does_not_interfere_with_fast_script() {
         .assert()
         .success();
 }
+
+// =============================================================================
+// submit / jobs
+// =============================================================================
+
+#[test]
+fn test_submit_dry_run_prints_batch_script() {
+    let temp = TempDir::new().unwrap();
+    fs::write(
+        temp.path().join("stacy.toml"),
+        "[project]\n\n[submit]\npartition = \"short\"\nmodules = [\"stata/18\"]\n",
+    )
+    .unwrap();
+    fs::write(temp.path().join("analysis.do"), "display 1\n").unwrap();
+
+    stacy()
+        .current_dir(temp.path())
+        .args(["submit", "analysis.do", "--time", "01:00:00", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("#SBATCH --partition=short"))
+        .stdout(predicate::str::contains("#SBATCH --time=01:00:00"))
+        .stdout(predicate::str::contains("module load stata/18"))
+        .stdout(predicate::str::contains("'run' 'analysis.do'"));
+    assert!(!temp.path().join(".stacy").join("jobs.json").exists());
+
+    stacy()
+        .current_dir(temp.path())
+        .args(["submit", "--task", "nope", "--dry-run"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown task 'nope'"));
+}
+
+/// Submits through stand-ins for `sbatch` and `squeue`
+#[test]
+#[cfg(unix)]
+fn test_submit_records_job_for_jobs() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("stacy.toml"), "[project]\n").unwrap();
+    fs::write(temp.path().join("analysis.do"), "display 1\n").unwrap();
+
+    let bin = TempDir::new().unwrap();
+    for (name, body) in [
+        ("sbatch", "echo '4242;cluster'"),
+        ("squeue", "echo RUNNING"),
+    ] {
+        let path = bin.path().join(name);
+        fs::write(&path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+    let path = format!(
+        "{}:{}",
+        bin.path().display(),
+        std::env::var("PATH").unwrap_or_default()
+    );
+
+    stacy()
+        .current_dir(temp.path())
+        .env("PATH", &path)
+        .args(["submit", "analysis.do"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Submitted job 4242 (slurm)"));
+    let jobs_dir = temp.path().join(".stacy").join("jobs");
+    assert!(fs::read_dir(&jobs_dir).unwrap().any(|e| e
+        .unwrap()
+        .path()
+        .extension()
+        .is_some_and(|x| x == "sh")));
+
+    stacy()
+        .current_dir(temp.path())
+        .env("PATH", &path)
+        .args(["jobs", "--format", "json"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"id\": \"4242\""))
+        .stdout(predicate::str::contains("\"state\": \"running\""));
+
+    stacy()
+        .current_dir(temp.path())
+        .env("PATH", &path)
+        .args(["jobs", "999"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No job 999"));
+}
+
+/// `stacy schedule install` keeps one block per project in the crontab. The
+/// fake `crontab` keeps it in a file.
+#[test]
+#[cfg(unix)]
+fn test_schedule_install_writes_crontab_block() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp = TempDir::new().unwrap();
+    fs::write(
+        temp.path().join("stacy.toml"),
+        r#"[project]
+
+[scripts]
+refresh = { script = "src/refresh.do", schedule = "0 3 * * *" }
+"#,
+    )
+    .unwrap();
+
+    let bin = TempDir::new().unwrap();
+    let crontab = bin.path().join("crontab.txt");
+    fs::write(&crontab, "MAILTO=ana\n").unwrap();
+    let fake = bin.path().join("crontab");
+    fs::write(
+        &fake,
+        format!(
+            "#!/bin/sh\nif [ \"$1\" = -l ]; then cat '{0}'; else cat > '{0}'; fi\n",
+            crontab.display()
+        ),
+    )
+    .unwrap();
+    fs::set_permissions(&fake, fs::Permissions::from_mode(0o755)).unwrap();
+    let path = format!(
+        "{}:{}",
+        bin.path().display(),
+        std::env::var("PATH").unwrap_or_default()
+    );
+
+    stacy()
+        .current_dir(temp.path())
+        .env("PATH", &path)
+        .args(["schedule", "install", "--backend", "cron", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("0 3 * * * cd "))
+        .stdout(predicate::str::contains("'task' 'refresh'"));
+    assert_eq!(fs::read_to_string(&crontab).unwrap(), "MAILTO=ana\n");
+
+    for _ in 0..2 {
+        stacy()
+            .current_dir(temp.path())
+            .env("PATH", &path)
+            .args(["schedule", "install", "--backend", "cron"])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("Scheduled 1 task(s) with cron"));
+    }
+    let installed = fs::read_to_string(&crontab).unwrap();
+    assert!(installed.starts_with("MAILTO=ana\n# BEGIN stacy "));
+    assert_eq!(installed.matches("'task' 'refresh'").count(), 1);
+
+    stacy()
+        .current_dir(temp.path())
+        .env("PATH", &path)
+        .args(["schedule", "uninstall", "--backend", "cron"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Removed 1 cron entry"));
+    assert_eq!(fs::read_to_string(&crontab).unwrap(), "MAILTO=ana\n");
+}
+
+#[test]
+fn test_invalid_task_schedule_is_rejected() {
+    let temp = TempDir::new().unwrap();
+    fs::write(
+        temp.path().join("stacy.toml"),
+        "[scripts]\nrefresh = { script = \"src/refresh.do\", schedule = \"0 25 * * *\" }\n",
+    )
+    .unwrap();
+
+    stacy()
+        .current_dir(temp.path())
+        .args(["task", "--list"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Task 'refresh': Invalid schedule '0 25 * * *': hour: 25 is out of range 0-23",
+        ));
+}
+
+/// `stacy run` records each script in the history; the fake Stata fails
+/// scripts whose name starts with `bad`
+#[test]
+#[cfg(unix)]
+fn test_history_lists_and_filters_runs() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("stacy.toml"), "[project]\n").unwrap();
+    fs::write(temp.path().join("good.do"), "display 1\n").unwrap();
+    fs::write(temp.path().join("bad.do"), "use data\n").unwrap();
+    let fake = temp.path().join("fake-stata");
+    fs::write(
+        &fake,
+        "#!/bin/sh\n\
+         for arg in \"$@\"; do last=\"$arg\"; done\n\
+         stem=$(basename \"$last\" .do)\n\
+         if grep -q 'bad.do' \"$last\"; then\n\
+           printf '%s\\n' 'use data' 'file data.dta not found' 'r(601);' '' 'end of do-file' 'r(601);' > \"$stem.log\"\n\
+         else\n\
+           printf '%s\\n' 'ok' '' 'end of do-file' > \"$stem.log\"\n\
+         fi\n",
+    )
+    .unwrap();
+    fs::set_permissions(&fake, fs::Permissions::from_mode(0o755)).unwrap();
+
+    for script in ["good.do", "bad.do", "good.do"] {
+        let _ = stacy()
+            .current_dir(temp.path())
+            .env("STATA_BINARY", &fake)
+            .args(["run", script])
+            .output()
+            .unwrap();
+    }
+
+    let output = stacy()
+        .current_dir(temp.path())
+        .args(["history", "--format", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let runs = json["runs"].as_array().unwrap();
+    let scripts: Vec<&str> = runs.iter().map(|r| r["script"].as_str().unwrap()).collect();
+    assert_eq!(scripts, ["good.do", "bad.do", "good.do"]);
+    assert_eq!(runs[1]["exit_code"], 3);
+    assert_eq!(runs[1]["error"], "r(601): file data.dta not found");
+    assert!(
+        runs[1]["log_file"].is_string(),
+        "the failed run kept its log"
+    );
+    assert!(runs[0].get("log_file").is_none());
+
+    stacy()
+        .current_dir(temp.path())
+        .args(["history", "--failed", "--since", "1d"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("bad.do"))
+        .stdout(predicate::str::contains("good.do").not());
+
+    let id = runs[1]["id"].as_u64().unwrap().to_string();
+    stacy()
+        .current_dir(temp.path())
+        .args(["history", "show", &id])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("command:   stacy run bad.do"))
+        .stdout(predicate::str::contains("error:     r(601)"));
+
+    stacy()
+        .current_dir(temp.path())
+        .args(["history", "--script", "good.do", "--format", "stata"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("run_count = 2"));
+}
+
+/// `stacy rerun` replays the last recorded command; `--failed-only` runs
+/// just its failed scripts
+#[test]
+#[cfg(unix)]
+fn test_rerun_replays_last_command() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("stacy.toml"), "[project]\n").unwrap();
+    fs::create_dir(temp.path().join("src")).unwrap();
+    fs::write(temp.path().join("src/good.do"), "display 1\n").unwrap();
+    fs::write(temp.path().join("src/bad.do"), "use data\n").unwrap();
+    let fake = temp.path().join("fake-stata");
+    fs::write(
+        &fake,
+        "#!/bin/sh\n\
+         for arg in \"$@\"; do last=\"$arg\"; done\n\
+         stem=$(basename \"$last\" .do)\n\
+         if grep -q 'bad.do' \"$last\"; then\n\
+           printf '%s\\n' 'file data.dta not found' 'r(601);' '' 'end of do-file' 'r(601);' > \"$stem.log\"\n\
+         else\n\
+           printf '%s\\n' 'ok' '' 'end of do-file' > \"$stem.log\"\n\
+         fi\n",
+    )
+    .unwrap();
+    fs::set_permissions(&fake, fs::Permissions::from_mode(0o755)).unwrap();
+    let history = |dir: &std::path::Path| -> Vec<(String, String)> {
+        let output = stacy()
+            .current_dir(dir)
+            .args(["history", "--format", "json", "-n", "100"])
+            .output()
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        json["runs"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .rev()
+            .map(|r| {
+                (
+                    r["invocation"].to_string(),
+                    r["script"].as_str().unwrap().to_string(),
+                )
+            })
+            .collect()
+    };
+
+    stacy()
+        .current_dir(temp.path())
+        .args(["rerun"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No command recorded yet"));
+
+    // From a subdirectory, with paths relative to it
+    stacy()
+        .current_dir(temp.path().join("src"))
+        .env("STATA_BINARY", &fake)
+        .args(["run", "--keep-going", "good.do", "bad.do"])
+        .assert()
+        .failure();
+
+    stacy()
+        .current_dir(temp.path())
+        .env("STATA_BINARY", &fake)
+        .args(["rerun", "--failed-only"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Rerunning: stacy"))
+        .stderr(predicate::str::contains("run src/bad.do"));
+
+    stacy()
+        .current_dir(temp.path())
+        .env("STATA_BINARY", &fake)
+        .args(["rerun"])
+        .assert()
+        .failure();
+
+    let runs = history(temp.path());
+    let expected: Vec<(String, String)> = [
+        ("1", "src/good.do"),
+        ("1", "src/bad.do"),
+        ("2", "src/bad.do"),
+        ("3", "src/bad.do"),
+    ]
+    .iter()
+    .map(|(i, s)| (i.to_string(), s.to_string()))
+    .collect();
+    assert_eq!(runs, expected);
+
+    stacy()
+        .current_dir(temp.path().join("src"))
+        .env("STATA_BINARY", &fake)
+        .args(["run", "good.do"])
+        .assert()
+        .success();
+    stacy()
+        .current_dir(temp.path())
+        .args(["rerun", "--failed-only"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Nothing to rerun"));
+}
+
+/// A script that passes much slower than its last passing runs is warned
+/// about, and fails the run under `--fail-on-regression`
+#[test]
+#[cfg(unix)]
+fn test_run_detects_slowdown_against_history() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("stacy.toml"), "[project]\n").unwrap();
+    fs::write(temp.path().join("clean.do"), "display 1\n").unwrap();
+    let fake = temp.path().join("fake-stata");
+    fs::write(
+        &fake,
+        format!(
+            "#!/bin/sh\n\
+             for arg in \"$@\"; do last=\"$arg\"; done\n\
+             [ -f {slow} ] && sleep 1.5\n\
+             printf '%s\\n' 'ok' '' 'end of do-file' > \"$(basename \"$last\" .do).log\"\n",
+            slow = temp.path().join("slow").display()
+        ),
+    )
+    .unwrap();
+    fs::set_permissions(&fake, fs::Permissions::from_mode(0o755)).unwrap();
+
+    for _ in 0..3 {
+        stacy()
+            .current_dir(temp.path())
+            .env("STATA_BINARY", &fake)
+            .args(["run", "clean.do"])
+            .assert()
+            .success()
+            .stderr(predicate::str::contains("longer than its usual").not());
+    }
+
+    fs::write(temp.path().join("slow"), "").unwrap();
+    stacy()
+        .current_dir(temp.path())
+        .env("STATA_BINARY", &fake)
+        .args(["run", "clean.do", "--format", "json-lines"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(r#""event":"regression""#))
+        .stdout(predicate::str::contains(r#""baseline_runs":3"#));
+
+    stacy()
+        .current_dir(temp.path())
+        .env("STATA_BINARY", &fake)
+        .args(["run", "clean.do", "--fail-on-regression", "20%"])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("warning: clean.do took"))
+        .stderr(predicate::str::contains(
+            "slowed down by more than 20% (--fail-on-regression)",
+        ));
+
+    stacy()
+        .current_dir(temp.path())
+        .args(["run", "clean.do", "--fail-on-regression", "fast"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("not a percentage"));
+}
+
+/// Values of secret environment variables never reach kept logs, streamed
+/// output or JSON results
+#[test]
+#[cfg(unix)]
+fn test_run_redacts_secrets() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp = TempDir::new().unwrap();
+    fs::write(
+        temp.path().join("stacy.toml"),
+        "[project]\n\n[run]\nsecrets = [\"CENSUS_*\"]\n",
+    )
+    .unwrap();
+    fs::write(temp.path().join("fetch.do"), "import delimited $url\n").unwrap();
+    let fake = temp.path().join("fake-stata");
+    fs::write(
+        &fake,
+        "#!/bin/sh\n\
+         for arg in \"$@\"; do last=\"$arg\"; done\n\
+         printf '%s\\n' \". import delimited https://api.example.org/v1?token=$FRED_API_KEY\" \
+           \"file https://api.example.org/v1?key=$CENSUS_KEY not found\" 'r(601);' '' \
+           'end of do-file' 'r(601);' > \"$(basename \"$last\" .do).log\"\n",
+    )
+    .unwrap();
+    fs::set_permissions(&fake, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let output = stacy()
+        .current_dir(temp.path())
+        .env("STATA_BINARY", &fake)
+        .env("FRED_API_KEY", "fredsecret123")
+        .env("CENSUS_KEY", "censussecret456")
+        .args(["run", "fetch.do", "--format", "json"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stdout.contains("<redacted:CENSUS_KEY>"), "{}", stdout);
+    let json: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    let log = fs::read_to_string(temp.path().join(json["log_file"].as_str().unwrap())).unwrap();
+    assert!(log.contains("token=<redacted:FRED_API_KEY>"), "{}", log);
+    for text in [&*stdout, &*stderr, &log] {
+        assert!(!text.contains("fredsecret123") && !text.contains("censussecret456"));
+    }
+
+    stacy()
+        .current_dir(temp.path())
+        .env("STATA_BINARY", &fake)
+        .env("FRED_API_KEY", "fredsecret123")
+        .env("CENSUS_KEY", "censussecret456")
+        .args(["run", "fetch.do", "--format", "json-lines"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("log-chunk"))
+        .stdout(predicate::str::contains("secret").not());
+}
+
+#[test]
+fn test_config_validate_reports_all_errors() {
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("clean.do"), "").unwrap();
+    fs::write(
+        temp.path().join("stacy.toml"),
+        r#"[project]
+nmae = "study"
+
+[scripts]
+clean = "clean.do"
+build = { script = "clean.do", parralel = ["clean"] }
+"#,
+    )
+    .unwrap();
+
+    // Loading reports every unknown key, not just the first
+    stacy()
+        .current_dir(temp.path())
+        .args(["task", "clean"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("(2 errors)"))
+        .stderr(predicate::str::contains(" --> stacy.toml:2:1"))
+        .stderr(predicate::str::contains("did you mean `name`?"))
+        .stderr(predicate::str::contains(" --> stacy.toml:6:32"))
+        .stderr(predicate::str::contains("did you mean `parallel`?"));
+
+    fs::write(
+        temp.path().join("stacy.toml"),
+        "[scripts]\nclean = \"clean.do\"\nanalyze = \"analyze.do\"\nall = [\"clen\", \"analyze\"]\n",
+    )
+    .unwrap();
+    let output = stacy()
+        .current_dir(temp.path())
+        .args(["config", "validate", "--format", "json"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["valid"], false);
+    assert_eq!(json["error_count"], 2);
+    assert_eq!(
+        json["errors"][0]["message"],
+        "task `all` references unknown task `clen`"
+    );
+    assert_eq!(json["errors"][0]["line"], 4);
+    assert_eq!(json["errors"][0]["hint"], "did you mean `clean`?");
+    assert_eq!(
+        json["errors"][1]["message"],
+        "task `analyze` runs analyze.do, which does not exist"
+    );
+
+    fs::write(temp.path().join("analyze.do"), "").unwrap();
+    fs::write(
+        temp.path().join("stacy.toml"),
+        "[scripts]\nclean = \"clean.do\"\nanalyze = \"analyze.do\"\nall = [\"clean\", \"analyze\"]\n",
+    )
+    .unwrap();
+    stacy()
+        .current_dir(temp.path())
+        .args(["config", "validate"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("is valid"));
+}
+
+#[test]
+fn test_config_schema_prints_json_schema() {
+    let output = stacy().args(["config", "schema"]).output().unwrap();
+    assert!(output.status.success());
+    let schema: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(schema["title"], "stacy.toml");
+    assert_eq!(schema["additionalProperties"], false);
+    assert!(schema["properties"]["scripts"].is_object());
+}
+
+#[test]
+fn test_migrate_updates_older_files() {
+    let temp = TempDir::new().unwrap();
+    let old_config = "[project]\nname = \"survey\"\n\n[dependencies]\nestout = \"ssc\"\n";
+    fs::write(temp.path().join("stacy.toml"), old_config).unwrap();
+    fs::write(
+        temp.path().join("stacy.lock"),
+        "[packages.estout]\nversion = \"20240115\"\n\n[packages.estout.source]\ntype = \"SSC\"\nname = \"estout\"\n",
+    )
+    .unwrap();
+
+    stacy()
+        .current_dir(temp.path())
+        .arg("lock")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("stacy migrate"));
+
+    stacy()
+        .current_dir(temp.path())
+        .args(["migrate", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Would migrate stacy.toml:"))
+        .stdout(predicate::str::contains(
+            "moved [dependencies] to [packages.dependencies]",
+        ))
+        .stdout(predicate::str::contains("Would migrate stacy.lock:"));
+    assert_eq!(
+        fs::read_to_string(temp.path().join("stacy.toml")).unwrap(),
+        old_config
+    );
+
+    stacy()
+        .current_dir(temp.path())
+        .arg("migrate")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Migrated stacy.toml (backup in stacy.toml.bak)",
+        ));
+    assert_eq!(
+        fs::read_to_string(temp.path().join("stacy.toml.bak")).unwrap(),
+        old_config
+    );
+    assert!(fs::read_to_string(temp.path().join("stacy.toml"))
+        .unwrap()
+        .contains("[packages.dependencies]\nestout = \"ssc\"\n"));
+    assert!(fs::read_to_string(temp.path().join("stacy.lock"))
+        .unwrap()
+        .contains("version = \"1\""));
+    assert!(temp.path().join("stacy.lock.bak").exists());
+
+    stacy()
+        .current_dir(temp.path())
+        .arg("migrate")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("nothing to migrate"));
+}
+
+#[test]
+fn test_add_dry_run_writes_nothing() {
+    let temp = TempDir::new().unwrap();
+    let cache = TempDir::new().unwrap();
+    fs::write(
+        temp.path().join("stacy.toml"),
+        "[project]\nname = \"survey\"\n",
+    )
+    .unwrap();
+    let pkg_dir = temp.path().join("lib").join("mypkg");
+    fs::create_dir_all(&pkg_dir).unwrap();
+    fs::write(
+        pkg_dir.join("mypkg.ado"),
+        "program define mypkg\n    require moremata\nend\n",
+    )
+    .unwrap();
+
+    stacy()
+        .current_dir(temp.path())
+        .args(["add", "mypkg", "--source", "local:lib/mypkg", "--dry-run"])
+        .env("XDG_CACHE_HOME", cache.path())
+        .env("LOCALAPPDATA", cache.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("+ mypkg"))
+        .stdout(predicate::str::contains("files: mypkg.ado"))
+        .stdout(predicate::str::contains(
+            "hint: mypkg appears to need moremata",
+        ))
+        .stdout(predicate::str::contains("+mypkg = \"local:lib/mypkg\""))
+        .stdout(predicate::str::contains("+++ stacy.lock (after stacy add)"))
+        .stdout(predicate::str::contains("Nothing written (--dry-run)."));
+
+    assert_eq!(
+        fs::read_to_string(temp.path().join("stacy.toml")).unwrap(),
+        "[project]\nname = \"survey\"\n"
+    );
+    assert!(!temp.path().join("stacy.lock").exists());
+    assert_eq!(fs::read_dir(cache.path()).unwrap().count(), 0);
+
+    let output = stacy()
+        .current_dir(temp.path())
+        .args(["add", "mypkg", "--source", "local:lib/mypkg", "--dry-run"])
+        .args(["--format", "json"])
+        .env("XDG_CACHE_HOME", cache.path())
+        .env("LOCALAPPDATA", cache.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["dry_run"], true);
+    assert_eq!(json["packages"][0]["files"][0], "mypkg.ado");
+    assert!(json["packages"][0]["checksum"]
+        .as_str()
+        .unwrap()
+        .starts_with("sha256:"));
+    assert!(json["changes"]["stacy.lock"]
+        .as_str()
+        .unwrap()
+        .contains("+[packages.mypkg]"));
+}
+
+#[test]
+fn test_remove_unused_packages() {
+    let temp = TempDir::new().unwrap();
+    let cache = TempDir::new().unwrap();
+    fs::write(temp.path().join("stacy.toml"), "[project]\n").unwrap();
+    for (name, commands) in [("acmd", &["acmd"][..]), ("bcmd", &["bcmd", "bhelper"][..])] {
+        let dir = temp.path().join("lib").join(name);
+        fs::create_dir_all(&dir).unwrap();
+        for command in commands {
+            fs::write(
+                dir.join(format!("{}.ado", command)),
+                format!("program define {}\nend\n", command),
+            )
+            .unwrap();
+        }
+        stacy()
+            .current_dir(temp.path())
+            .args(["add", name, "--source", &format!("local:lib/{}", name)])
+            .env("XDG_CACHE_HOME", cache.path())
+            .env("LOCALAPPDATA", cache.path())
+            .assert()
+            .success();
+    }
+    fs::create_dir_all(temp.path().join("code")).unwrap();
+    fs::write(
+        temp.path().join("code").join("main.do"),
+        "use data, clear\n* bcmd is only in a comment\nacmd y x\n",
+    )
+    .unwrap();
+
+    // Without a terminal to ask on, nothing is removed without --yes
+    stacy()
+        .current_dir(temp.path())
+        .args(["remove", "--unused"])
+        .env("XDG_CACHE_HOME", cache.path())
+        .env("LOCALAPPDATA", cache.path())
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("acmd at code/main.do:3"))
+        .stdout(predicate::str::contains(
+            "none of bcmd, bhelper appears in the 1 script(s)",
+        ))
+        .stderr(predicate::str::contains("pass --yes"));
+    assert!(fs::read_to_string(temp.path().join("stacy.toml"))
+        .unwrap()
+        .contains("bcmd"));
+
+    stacy()
+        .current_dir(temp.path())
+        .args(["remove", "--unused", "--yes"])
+        .env("XDG_CACHE_HOME", cache.path())
+        .env("LOCALAPPDATA", cache.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("- bcmd"));
+    let config = fs::read_to_string(temp.path().join("stacy.toml")).unwrap();
+    assert!(config.contains("acmd"));
+    assert!(!config.contains("bcmd"));
+    assert!(!fs::read_to_string(temp.path().join("stacy.lock"))
+        .unwrap()
+        .contains("bcmd"));
+
+    stacy()
+        .current_dir(temp.path())
+        .args(["remove", "--unused"])
+        .env("XDG_CACHE_HOME", cache.path())
+        .env("LOCALAPPDATA", cache.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No unused packages."));
+}
+
+#[test]
+fn test_lock_prune_drops_orphans() {
+    let temp = TempDir::new().unwrap();
+    let cache = TempDir::new().unwrap();
+    fs::write(
+        temp.path().join("stacy.toml"),
+        "[project]\n\n[packages.dependencies]\n",
+    )
+    .unwrap();
+    fs::write(
+        temp.path().join("stacy.lock"),
+        r#"version = "1"
+
+[packages.estout]
+version = "2024.03.15"
+checksum = "sha256:abc123"
+
+[packages.estout.source]
+type = "SSC"
+name = "estout"
+"#,
+    )
+    .unwrap();
+    let packages = if cfg!(windows) {
+        cache.path().join("stacy").join("cache").join("packages")
+    } else {
+        cache.path().join("stacy").join("packages")
+    };
+    let cached = packages.join("estout").join("2024.03.15");
+    fs::create_dir_all(&cached).unwrap();
+    fs::write(cached.join("estout.ado"), "program estout\nend\n").unwrap();
+    let lock = |args: &[&str]| {
+        let mut cmd = stacy();
+        cmd.current_dir(temp.path())
+            .env("XDG_CACHE_HOME", cache.path())
+            .env("LOCALAPPDATA", cache.path())
+            .args(args);
+        cmd
+    };
+
+    // Without --prune the entry is reported and kept
+    lock(&["lock"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("estout (not in stacy.toml, kept)"))
+        .stdout(predicate::str::contains("stacy lock --prune"));
+    let lockfile = fs::read_to_string(temp.path().join("stacy.lock")).unwrap();
+    assert!(lockfile.contains("[packages.estout]"));
+
+    lock(&["lock", "--check"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains(
+            "Run 'stacy lock --prune' to drop the extra entries.",
+        ));
+
+    let output = lock(&["lock", "--prune", "--format", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["pruned"], serde_json::json!(["estout"]));
+    assert_eq!(
+        json["cache_pruned"],
+        serde_json::json!(["estout@2024.03.15"])
+    );
+    assert!(!cached.exists());
+    let lockfile = fs::read_to_string(temp.path().join("stacy.lock")).unwrap();
+    assert!(!lockfile.contains("estout"));
+
+    lock(&["lock", "--check"]).assert().success();
+}
+
+#[test]
+fn test_search_and_outdated_read_ssc_index_offline() {
+    let temp = TempDir::new().unwrap();
+    let cache = TempDir::new().unwrap();
+    let stacy_cache = if cfg!(windows) {
+        cache.path().join("stacy").join("cache")
+    } else {
+        cache.path().join("stacy")
+    };
+    fs::create_dir_all(&stacy_cache).unwrap();
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let index = serde_json::json!({
+        "fetched_at_unix": now,
+        "packages": {
+            "estout": {
+                "description": "module to make regression tables",
+                "version": "2025.01.20",
+            },
+            "reghdfe": { "description": "module for linear regression with fixed effects" },
+            "ftools": { "description": "module to provide alternatives to common commands" },
+        },
+    });
+    fs::write(stacy_cache.join("ssc-index.json"), index.to_string()).unwrap();
+    let offline = |args: &[&str]| {
+        let mut cmd = stacy();
+        cmd.current_dir(temp.path())
+            .env("XDG_CACHE_HOME", cache.path())
+            .env("LOCALAPPDATA", cache.path())
+            .env("STACY_OFFLINE", "1")
+            .args(args);
+        cmd
+    };
+
+    offline(&["search", "regression"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("reghdfe"))
+        .stdout(predicate::str::contains(
+            "estout   module to make regression tables",
+        ))
+        .stdout(predicate::str::contains("ftools").not());
+
+    let output = offline(&["search", "reg", "--format", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["match_count"], 2);
+    assert_eq!(json["packages"][0]["name"], "reghdfe");
+
+    fs::write(
+        temp.path().join("stacy.toml"),
+        "[project]\n\n[packages.dependencies]\nestout = \"ssc\"\n",
+    )
+    .unwrap();
+    fs::write(
+        temp.path().join("stacy.lock"),
+        r#"version = "1"
+
+[packages.estout]
+version = "2024.03.15"
+checksum = "sha256:abc123"
+
+[packages.estout.source]
+type = "SSC"
+name = "estout"
+"#,
+    )
+    .unwrap();
+    offline(&["outdated"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("2025.01.20"));
+
+    // Without an index there is nothing to search offline
+    fs::remove_file(stacy_cache.join("ssc-index.json")).unwrap();
+    offline(&["search", "regression"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("refresh-index"));
+}
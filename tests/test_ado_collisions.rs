@@ -0,0 +1,134 @@
+//! Tests for ado name collisions: two packages shipping the same `.ado` file.
+//!
+//! Uses `local:` packages and an isolated cache, so these run offline.
+
+use assert_cmd::{cargo_bin_cmd, Command};
+use predicates::prelude::*;
+use std::fs;
+use tempfile::TempDir;
+
+/// A project with two local packages that both ship `esttab.ado`
+fn project(toml_extra: &str) -> (TempDir, TempDir) {
+    let temp = TempDir::new().unwrap();
+    let cache = TempDir::new().unwrap();
+    fs::write(
+        temp.path().join("stacy.toml"),
+        format!("[project]\nname = \"t\"\n{}", toml_extra),
+    )
+    .unwrap();
+    for pkg in ["estout", "myestout"] {
+        let dir = temp.path().join("lib").join(pkg);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join(format!("{}.ado", pkg)),
+            format!("program define {}\nend\n", pkg),
+        )
+        .unwrap();
+        fs::write(
+            dir.join("esttab.ado"),
+            format!("* esttab from {}\nprogram define esttab\nend\n", pkg),
+        )
+        .unwrap();
+    }
+    (temp, cache)
+}
+
+fn stacy(temp: &TempDir, cache: &TempDir) -> Command {
+    let mut cmd = cargo_bin_cmd!("stacy");
+    cmd.current_dir(temp.path())
+        .env("XDG_CACHE_HOME", cache.path())
+        .env("LOCALAPPDATA", cache.path());
+    cmd
+}
+
+#[test]
+fn test_add_refuses_colliding_package() {
+    let (temp, cache) = project("");
+    stacy(&temp, &cache)
+        .args(["add", "estout", "--source", "local:./lib/estout"])
+        .assert()
+        .success();
+    let lock_before = fs::read_to_string(temp.path().join("stacy.lock")).unwrap();
+
+    stacy(&temp, &cache)
+        .args(["add", "myestout", "--source", "local:./lib/myestout"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "esttab.ado is provided by estout, myestout",
+        ))
+        .stderr(predicate::str::contains("on_collision"));
+
+    // Nothing was recorded for the colliding package
+    assert_eq!(
+        fs::read_to_string(temp.path().join("stacy.lock")).unwrap(),
+        lock_before
+    );
+    let config = fs::read_to_string(temp.path().join("stacy.toml")).unwrap();
+    assert!(!config.contains("myestout"));
+}
+
+#[test]
+fn test_warn_policy_accepts_collision_and_doctor_reports_it() {
+    let (temp, cache) = project("\n[packages]\non_collision = \"warn\"\n");
+    stacy(&temp, &cache)
+        .args(["add", "estout", "--source", "local:./lib/estout"])
+        .assert()
+        .success();
+    stacy(&temp, &cache)
+        .args(["add", "myestout", "--source", "local:./lib/myestout"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("estout shadows the others"));
+
+    let config = fs::read_to_string(temp.path().join("stacy.toml")).unwrap();
+    assert!(config.contains("on_collision = \"warn\""));
+
+    stacy(&temp, &cache)
+        .args(["install"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("esttab.ado"));
+
+    let output = stacy(&temp, &cache)
+        .args(["doctor", "--format", "json"])
+        .env("STATA_BINARY", "/nonexistent/stata")
+        .output()
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let check = json["checks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|c| c["name"] == "Ado Collisions")
+        .unwrap();
+    assert_eq!(check["status"], "warn");
+    assert!(check["message"].as_str().unwrap().contains("esttab.ado"));
+}
+
+#[test]
+fn test_install_fails_on_collision_under_error_policy() {
+    let (temp, cache) = project("\n[packages]\non_collision = \"warn\"\n");
+    for pkg in ["estout", "myestout"] {
+        stacy(&temp, &cache)
+            .args(["add", pkg, "--source", &format!("local:./lib/{}", pkg)])
+            .assert()
+            .success();
+    }
+
+    // Back to the default policy
+    let config = fs::read_to_string(temp.path().join("stacy.toml")).unwrap();
+    fs::write(
+        temp.path().join("stacy.toml"),
+        config.replace("on_collision = \"warn\"\n", ""),
+    )
+    .unwrap();
+
+    stacy(&temp, &cache)
+        .args(["install"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "1 ado file(s) provided by more than one package",
+        ));
+}
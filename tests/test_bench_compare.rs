@@ -0,0 +1,127 @@
+//! `stacy bench --engine A --engine B` benchmarks the script on each engine
+//! and reports them side by side; `--arg k=a --arg k=b` on each argument
+//! value.
+
+#![cfg(unix)]
+
+use assert_cmd::cargo_bin_cmd;
+use predicates::prelude::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+/// Fake Stata writing a successful `<wrapper stem>.log` into its cwd
+fn write_fake_stata(dir: &Path, name: &str) -> PathBuf {
+    use std::os::unix::fs::PermissionsExt;
+    let path = dir.join(name);
+    fs::write(
+        &path,
+        "#!/bin/sh\n\
+         for arg in \"$@\"; do last=\"$arg\"; done\n\
+         stem=$(basename \"$last\" .do)\n\
+         printf '%s\\n' '. display 1' '1' '' 'end of do-file' > \"$stem.log\"\n",
+    )
+    .unwrap();
+    let mut perms = fs::metadata(&path).unwrap().permissions();
+    perms.set_mode(0o755);
+    fs::set_permissions(&path, perms).unwrap();
+    path
+}
+
+#[test]
+fn test_bench_compares_engines() {
+    let temp = TempDir::new().unwrap();
+    let root = temp.path();
+    fs::write(root.join("analysis.do"), "display 1\n").unwrap();
+    let se = write_fake_stata(root, "stata-se");
+    let mp = write_fake_stata(root, "stata-mp");
+
+    let output = cargo_bin_cmd!("stacy")
+        .current_dir(root)
+        .args(["bench", "analysis.do", "-n", "2", "--no-warmup"])
+        .arg("--engine")
+        .arg(&se)
+        .arg("--engine")
+        .arg(&mp)
+        .args(["--format", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let engines = json["engines"].as_array().unwrap();
+    assert_eq!(engines.len(), 2);
+    assert_eq!(engines[0]["engine"], se.display().to_string());
+    assert_eq!(engines[1]["engine"], mp.display().to_string());
+    assert_eq!(engines[0]["speedup"], 1.0);
+    assert_eq!(json["measured_runs"], 2);
+
+    cargo_bin_cmd!("stacy")
+        .current_dir(root)
+        .args(["bench", "analysis.do", "-n", "1", "--no-warmup"])
+        .arg("--engine")
+        .arg(&se)
+        .arg("--engine")
+        .arg(&mp)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("speedup"))
+        .stdout(predicate::str::contains("1 each"));
+}
+
+#[test]
+fn test_bench_sweeps_argument_values() {
+    let temp = TempDir::new().unwrap();
+    let root = temp.path();
+    fs::write(root.join("analysis.do"), "display 1\n").unwrap();
+    let se = write_fake_stata(root, "stata-se");
+
+    let output = cargo_bin_cmd!("stacy")
+        .current_dir(root)
+        .args(["bench", "analysis.do", "-n", "1", "--no-warmup"])
+        .args([
+            "--arg",
+            "obs=1e5",
+            "--arg",
+            "obs=1e6",
+            "--csv",
+            "scaling.csv",
+        ])
+        .env("STATA_BINARY", &se)
+        .args(["--format", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let sweep = json["sweep"].as_array().unwrap();
+    assert_eq!(sweep.len(), 2);
+    assert_eq!(sweep[0]["args"]["obs"], "1e5");
+    assert_eq!(sweep[1]["args"]["obs"], "1e6");
+    assert_eq!(sweep[0]["relative"], 1.0);
+
+    let csv = fs::read_to_string(root.join("scaling.csv")).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("obs,runs,mean_secs"));
+    assert!(lines[1].starts_with("1e5,1,"));
+    assert!(lines[2].starts_with("1e6,1,"));
+}
+
+#[test]
+fn test_bench_single_engine_has_no_comparison() {
+    let temp = TempDir::new().unwrap();
+    let root = temp.path();
+    fs::write(root.join("analysis.do"), "display 1\n").unwrap();
+    let se = write_fake_stata(root, "stata-se");
+
+    let output = cargo_bin_cmd!("stacy")
+        .current_dir(root)
+        .args(["bench", "analysis.do", "-n", "1", "--no-warmup"])
+        .arg("--engine")
+        .arg(&se)
+        .args(["--format", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(json.get("engines").is_none());
+}
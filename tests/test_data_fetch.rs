@@ -0,0 +1,241 @@
+//! Tests for `[data]` and `stacy data fetch`.
+//!
+//! The data files are served over HTTP on 127.0.0.1 and the data cache lives
+//! in a temp dir, so these run without an internet connection.
+
+use assert_cmd::{cargo_bin_cmd, Command};
+use predicates::prelude::*;
+use sha2::{Digest, Sha256};
+use std::fs;
+use tempfile::TempDir;
+
+const SURVEY: &[u8] = b"id,income\n1,100\n2,250\n";
+
+fn stacy() -> Command {
+    cargo_bin_cmd!("stacy")
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+/// Serve fixed routes over HTTP on 127.0.0.1. Returns the base URL.
+fn serve(routes: Vec<(String, Vec<u8>)>) -> String {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let base = format!("http://{}/", listener.local_addr().unwrap());
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { continue };
+
+            let mut buf = [0u8; 2048];
+            let read = stream.read(&mut buf).unwrap_or(0);
+            let request = String::from_utf8_lossy(&buf[..read]).to_string();
+            let path = request.split_whitespace().nth(1).unwrap_or("").to_string();
+
+            let response = match routes.iter().find(|(route, _)| *route == path) {
+                Some((_, body)) => {
+                    let mut head = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                        body.len()
+                    )
+                    .into_bytes();
+                    head.extend_from_slice(body);
+                    head
+                }
+                None => b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                    .to_vec(),
+            };
+
+            let _ = stream.write_all(&response);
+            let _ = stream.flush();
+        }
+    });
+
+    base
+}
+
+/// Project declaring one data file, plus an empty cache home
+fn project_with_data(url: &str, sha256: &str) -> (TempDir, TempDir) {
+    let project = TempDir::new().unwrap();
+    let cache = TempDir::new().unwrap();
+    fs::write(
+        project.path().join("stacy.toml"),
+        format!(
+            "[data.survey]\nurl = \"{}survey.csv\"\npath = \"data/raw/survey.csv\"\nsha256 = \"{}\"\n",
+            url, sha256
+        ),
+    )
+    .unwrap();
+    (project, cache)
+}
+
+fn data_fetch(project: &TempDir, cache: &TempDir) -> Command {
+    let mut cmd = stacy();
+    cmd.args(["data", "fetch"])
+        .current_dir(project.path())
+        .env("XDG_CACHE_HOME", cache.path())
+        .env("LOCALAPPDATA", cache.path())
+        .env_remove("STACY_OFFLINE");
+    cmd
+}
+
+#[test]
+fn test_fetch_downloads_verifies_and_caches() {
+    let url = serve(vec![("/survey.csv".to_string(), SURVEY.to_vec())]);
+    let (project, cache) = project_with_data(&url, &sha256_hex(SURVEY));
+
+    data_fetch(&project, &cache)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("downloaded"));
+    let dest = project.path().join("data/raw/survey.csv");
+    assert_eq!(fs::read(&dest).unwrap(), SURVEY);
+
+    data_fetch(&project, &cache)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("present"));
+
+    // A deleted file comes back from the cache, even offline
+    fs::remove_file(&dest).unwrap();
+    data_fetch(&project, &cache)
+        .env("STACY_OFFLINE", "1")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("cached"));
+    assert_eq!(fs::read(&dest).unwrap(), SURVEY);
+}
+
+#[test]
+fn test_fetch_rejects_checksum_mismatch() {
+    let url = serve(vec![("/survey.csv".to_string(), SURVEY.to_vec())]);
+    let (project, cache) = project_with_data(&url, &sha256_hex(b"something else"));
+
+    data_fetch(&project, &cache)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Checksum mismatch for data 'survey'",
+        ));
+    assert!(!project.path().join("data/raw/survey.csv").exists());
+}
+
+#[test]
+fn test_fetch_json_output() {
+    let url = serve(vec![("/survey.csv".to_string(), SURVEY.to_vec())]);
+    let (project, cache) = project_with_data(&url, &sha256_hex(SURVEY));
+
+    let output = data_fetch(&project, &cache)
+        .args(["--format", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["downloaded_count"], 1);
+    assert_eq!(json["files"][0]["name"], "survey");
+    assert_eq!(json["files"][0]["sha256"], sha256_hex(SURVEY));
+}
+
+#[test]
+fn test_run_verify_data_fails_before_stata() {
+    let (project, cache) = project_with_data("http://127.0.0.1:1/", &sha256_hex(SURVEY));
+    fs::write(project.path().join("main.do"), "display 1\n").unwrap();
+
+    stacy()
+        .args(["run", "main.do", "--verify-data"])
+        .current_dir(project.path())
+        .env("XDG_CACHE_HOME", cache.path())
+        .env("STATA_BINARY", "/nonexistent/stata")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("stacy data fetch"))
+        .stderr(predicate::str::contains("data/raw/survey.csv"));
+}
+
+fn md5_hex(data: &[u8]) -> String {
+    use md5::{Digest as _, Md5};
+    hex::encode(Md5::digest(data))
+}
+
+fn project_with_toml(toml: &str) -> (TempDir, TempDir) {
+    let project = TempDir::new().unwrap();
+    let cache = TempDir::new().unwrap();
+    fs::write(project.path().join("stacy.toml"), toml).unwrap();
+    (project, cache)
+}
+
+#[test]
+fn test_fetch_dataverse_dataset_by_doi() {
+    const TAB_ORIGINAL: &[u8] = b"original .dta bytes";
+    let listing = format!(
+        r#"{{"status":"OK","data":{{"versionNumber":1,"versionMinorNumber":0,"files":[
+            {{"label":"survey.tab","dataFile":{{"id":7,"filename":"survey.tab",
+              "originalFileFormat":"application/x-stata","originalFileName":"survey.dta",
+              "checksum":{{"type":"MD5","value":"{}"}}}}}}]}}}}"#,
+        md5_hex(TAB_ORIGINAL)
+    );
+    let url = serve(vec![
+        (
+            "/api/datasets/:persistentId/versions/1.0?persistentId=doi:10.7910/DVN/ABCDEF"
+                .to_string(),
+            listing.into_bytes(),
+        ),
+        (
+            "/api/access/datafile/7?format=original".to_string(),
+            TAB_ORIGINAL.to_vec(),
+        ),
+    ]);
+    let (project, cache) = project_with_toml(&format!(
+        "[data.replication]\ndoi = \"10.7910/DVN/ABCDEF\"\npath = \"data/replication\"\nversion = \"1.0\"\nserver = \"{}\"\n",
+        url.trim_end_matches('/')
+    ));
+
+    data_fetch(&project, &cache)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("data/replication/survey.dta"));
+    assert_eq!(
+        fs::read(project.path().join("data/replication/survey.dta")).unwrap(),
+        TAB_ORIGINAL
+    );
+
+    // The pinned version's listing is cached: restoring works offline
+    fs::remove_file(project.path().join("data/replication/survey.dta")).unwrap();
+    data_fetch(&project, &cache)
+        .env("STACY_OFFLINE", "1")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("cached"));
+}
+
+#[test]
+fn test_fetch_zenodo_record_rejects_bad_md5() {
+    let listing = format!(
+        r#"{{"id":42,"metadata":{{"version":"v1"}},"files":[
+            {{"key":"analysis.dta","checksum":"md5:{}"}}]}}"#,
+        md5_hex(b"what the record promised")
+    );
+    let url = serve(vec![
+        ("/api/records/42".to_string(), listing.into_bytes()),
+        (
+            "/records/42/files/analysis.dta?download=1".to_string(),
+            b"something else".to_vec(),
+        ),
+    ]);
+    let (project, cache) = project_with_toml(&format!(
+        "[data.analysis]\ndoi = \"10.5281/zenodo.42\"\npath = \"data\"\nserver = \"{}\"\n",
+        url.trim_end_matches('/')
+    ));
+
+    data_fetch(&project, &cache)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Checksum mismatch for data 'analysis'",
+        ));
+    assert!(!project.path().join("data/analysis.dta").exists());
+}
@@ -0,0 +1,82 @@
+//! `dev = true` local packages: linked into the adopath, not copied
+
+use assert_cmd::{cargo_bin_cmd, Command};
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+fn stacy(root: &Path, cache_home: &Path) -> Command {
+    let mut cmd = cargo_bin_cmd!("stacy");
+    cmd.current_dir(root)
+        .env("XDG_CACHE_HOME", cache_home)
+        .env("LOCALAPPDATA", cache_home);
+    cmd
+}
+
+#[test]
+fn test_editable_package_is_linked() {
+    let temp = TempDir::new().unwrap();
+    let cache_home = TempDir::new().unwrap();
+    let root = temp.path();
+    let lib = root.join("lib/myutils");
+    fs::create_dir_all(&lib).unwrap();
+    fs::write(lib.join("myutils.ado"), "program define myutils\nend\n").unwrap();
+    fs::write(root.join("stacy.toml"), "[project]\nname = \"t\"\n").unwrap();
+
+    stacy(root, cache_home.path())
+        .args(["add", "myutils", "--dev", "--editable"])
+        .args(["--source", "local:./lib/myutils"])
+        .assert()
+        .success();
+
+    let config = fs::read_to_string(root.join("stacy.toml")).unwrap();
+    assert!(config.contains("dev = true"), "{}", config);
+    let locked = fs::read_to_string(root.join("stacy.lock")).unwrap();
+    assert!(locked.contains("editable = true"), "{}", locked);
+    // Nothing was copied into the cache
+    assert!(fs::read_dir(cache_home.path())
+        .map(|mut d| d.next().is_none())
+        .unwrap_or(true));
+
+    // Edits need no reinstall: the lockfile stays in sync and install passes
+    fs::write(
+        lib.join("myutils.ado"),
+        "program define myutils\ndisplay 1\nend\n",
+    )
+    .unwrap();
+    stacy(root, cache_home.path())
+        .args(["lock", "--check"])
+        .assert()
+        .success();
+    stacy(root, cache_home.path())
+        .args(["install", "--with", "dev"])
+        .assert()
+        .success();
+
+    stacy(root, cache_home.path())
+        .arg("list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("local:./lib/myutils (editable)"));
+    stacy(root, cache_home.path())
+        .args(["env", "--format", "json"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("myutils"));
+}
+
+#[test]
+fn test_editable_needs_local_source() {
+    let temp = TempDir::new().unwrap();
+    let cache_home = TempDir::new().unwrap();
+    fs::write(temp.path().join("stacy.toml"), "[project]\nname = \"t\"\n").unwrap();
+
+    stacy(temp.path(), cache_home.path())
+        .args(["add", "estout", "--editable"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "--editable links a local: directory",
+        ));
+}
@@ -0,0 +1,70 @@
+//! `[exit_codes]` remaps the process exit status for a kind of failure;
+//! `stacy explain --exit-codes` shows the mapping in effect.
+
+#![cfg(unix)]
+
+use assert_cmd::cargo_bin_cmd;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+/// Fake Stata whose log ends in a syntax error, r(198)
+fn write_failing_stata(dir: &Path) -> PathBuf {
+    use std::os::unix::fs::PermissionsExt;
+    let path = dir.join("fake-stata");
+    fs::write(
+        &path,
+        "#!/bin/sh\n\
+         for arg in \"$@\"; do last=\"$arg\"; done\n\
+         stem=$(basename \"$last\" .do)\n\
+         printf '%s\\n' '. display xx' 'invalid syntax' 'r(198);' '' 'end of do-file' 'r(198);' \
+         > \"$stem.log\"\n",
+    )
+    .unwrap();
+    let mut perms = fs::metadata(&path).unwrap().permissions();
+    perms.set_mode(0o755);
+    fs::set_permissions(&path, perms).unwrap();
+    path
+}
+
+#[test]
+fn test_remapped_exit_code() {
+    let temp = TempDir::new().unwrap();
+    let root = temp.path();
+    fs::write(
+        root.join("stacy.toml"),
+        "[project]\nname = \"t\"\n\n[exit_codes]\nsyntax_error = 65\n",
+    )
+    .unwrap();
+    fs::write(root.join("analysis.do"), "display xx\n").unwrap();
+    let fake = write_failing_stata(root);
+
+    let output = cargo_bin_cmd!("stacy")
+        .current_dir(root)
+        .env("STATA_BINARY", &fake)
+        .env("XDG_CONFIG_HOME", root.join("config"))
+        .args(["run", "analysis.do", "--format", "json"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(65));
+    // The output keeps the standard code
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["exit_code"], 2);
+
+    let output = cargo_bin_cmd!("stacy")
+        .current_dir(root)
+        .env("XDG_CONFIG_HOME", root.join("config"))
+        .args(["explain", "--exit-codes", "--format", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let syntax = json["exit_codes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|entry| entry["kind"] == "syntax_error")
+        .unwrap();
+    assert_eq!(syntax["code"], 65);
+    assert_eq!(syntax["standard"], 2);
+}
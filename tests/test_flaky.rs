@@ -0,0 +1,77 @@
+//! `stacy test` records each test's outcome in `.stacy/test-history.json`;
+//! `--detect-flaky` reports tests that passed and failed on the same code and
+//! `--retry-flaky` reruns them.
+
+#![cfg(unix)]
+
+use assert_cmd::cargo_bin_cmd;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+/// Fake Stata that fails every other run, counting runs in a file next to it
+fn write_flaky_stata(dir: &Path) -> PathBuf {
+    use std::os::unix::fs::PermissionsExt;
+    let path = dir.join("fake-stata");
+    fs::write(
+        &path,
+        "#!/bin/sh\n\
+         for arg in \"$@\"; do last=\"$arg\"; done\n\
+         stem=$(basename \"$last\" .do)\n\
+         count_file=\"$(dirname \"$0\")/count\"\n\
+         count=$(cat \"$count_file\" 2>/dev/null || echo 0)\n\
+         echo $((count + 1)) > \"$count_file\"\n\
+         if [ $((count % 2)) -eq 0 ]; then\n\
+           printf '%s\\n' '. display xx' 'invalid syntax' 'r(198);' '' 'end of do-file' 'r(198);' > \"$stem.log\"\n\
+         else\n\
+           printf '%s\\n' '. display 1' '1' '' 'end of do-file' > \"$stem.log\"\n\
+         fi\n",
+    )
+    .unwrap();
+    let mut perms = fs::metadata(&path).unwrap().permissions();
+    perms.set_mode(0o755);
+    fs::set_permissions(&path, perms).unwrap();
+    path
+}
+
+fn stacy_test(root: &Path, fake: &Path, args: &[&str]) -> (Option<i32>, serde_json::Value) {
+    let output = cargo_bin_cmd!("stacy")
+        .current_dir(root)
+        .env("STATA_BINARY", fake)
+        .arg("test")
+        .args(args)
+        .args(["--format", "json"])
+        .output()
+        .unwrap();
+    let json = serde_json::from_slice(&output.stdout).unwrap();
+    (output.status.code(), json)
+}
+
+#[test]
+fn test_detect_and_retry_flaky() {
+    let temp = TempDir::new().unwrap();
+    let root = temp.path();
+    fs::write(root.join("stacy.toml"), "[project]\nname = \"t\"\n").unwrap();
+    fs::create_dir_all(root.join("tests")).unwrap();
+    fs::write(root.join("tests/test_fetch.do"), "display 1\n").unwrap();
+    let fake = write_flaky_stata(root);
+
+    // One failure: nothing to compare it with yet
+    let (code, json) = stacy_test(root, &fake, &["--detect-flaky"]);
+    assert_eq!(code, Some(1));
+    assert_eq!(json["flaky"], serde_json::json!([]));
+
+    // Then a pass on the same code
+    let (code, json) = stacy_test(root, &fake, &["--detect-flaky"]);
+    assert_eq!(code, Some(0));
+    assert_eq!(json["flaky"][0]["name"], "test_fetch");
+    assert_eq!(json["flaky"][0]["passed"], 1);
+    assert_eq!(json["flaky"][0]["failed"], 1);
+    assert!(root.join(".stacy/test-history.json").exists());
+
+    // Known flaky now: the failing run is retried and passes
+    let (code, json) = stacy_test(root, &fake, &["--retry-flaky", "2"]);
+    assert_eq!(code, Some(0));
+    assert_eq!(json["tests"][0]["retries"], 1);
+    assert!(json.get("flaky").is_none());
+}
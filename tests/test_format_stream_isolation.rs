@@ -76,7 +76,7 @@ all = ["clean"]
 }
 
 /// Every non-empty stdout line must be executable as a Stata assignment
-/// (comment, scalar, or global) — nothing else may reach stdout.
+/// (comment, scalar, global, matrix or frame) — nothing else may reach stdout.
 fn assert_stata_payload_only(label: &str, stdout: &str, ran_stata: bool) {
     assert!(
         !stdout.trim().is_empty(),
@@ -99,7 +99,13 @@ fn assert_stata_payload_only(label: &str, stdout: &str, ran_stata: bool) {
         assert!(
             line.starts_with('*')
                 || line.starts_with("scalar stacy_")
-                || line.starts_with("global stacy_"),
+                || line.starts_with("global stacy_")
+                || line.starts_with("matrix ")
+                || line.starts_with("capture frame drop stacy_")
+                || line.starts_with("frame create stacy_")
+                || line.starts_with("frame post stacy_")
+                || line == "if c(stata_version) >= 16 {"
+                || line == "}",
             "{}: line not executable as Stata assignment: '{}'",
             label,
             line
@@ -16,6 +16,7 @@ use predicates::prelude::*;
 use sha2::{Digest, Sha256};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use tempfile::TempDir;
 
 /// Contents of the vendored package the tests install from.
@@ -402,11 +403,19 @@ const NODATE_PKG: &[u8] =
     b"d 'NODATE': a package that declares no distribution date\nf nodate.ado\n";
 const NODATE_ADO: &[u8] = b"program define nodate\nend\n";
 
+/// Paths the test server answers, with their bodies
+type Routes = Vec<(String, Vec<u8>)>;
+
 /// Serve fixed routes over HTTP on 127.0.0.1. Returns the base URL.
 ///
 /// One request per connection, answered with `Connection: close`, which is all
 /// the package downloader needs and keeps the server to a few lines.
-fn serve(routes: Vec<(String, Vec<u8>)>) -> String {
+fn serve(routes: Routes) -> String {
+    serve_shared(Arc::new(Mutex::new(routes)))
+}
+
+/// As [`serve`], with routes the test can change while the server runs.
+fn serve_shared(routes: Arc<Mutex<Routes>>) -> String {
     use std::io::{Read, Write};
     use std::net::TcpListener;
 
@@ -423,6 +432,8 @@ fn serve(routes: Vec<(String, Vec<u8>)>) -> String {
             let path = request.split_whitespace().nth(1).unwrap_or("").to_string();
 
             let body = routes
+                .lock()
+                .unwrap()
                 .iter()
                 .find(|(route, _)| *route == path)
                 .map(|(_, body)| body.clone());
@@ -548,3 +559,136 @@ fn test_install_of_undated_package_fails_on_checksum_mismatch() {
         "a failed install must not rewrite stacy.lock"
     );
 }
+
+// ============================================================================
+// Re-resolving one package
+// ============================================================================
+
+/// The routes of a `net:` package whose `.pkg` declares `date`.
+fn net_package_routes(name: &str, date: &str) -> Vec<(String, Vec<u8>)> {
+    vec![
+        (
+            format!("/{}.pkg", name),
+            format!(
+                "d '{}': stub package\nd Distribution-Date: {}\nf {}.ado\n",
+                name.to_uppercase(),
+                date,
+                name
+            )
+            .into_bytes(),
+        ),
+        (
+            format!("/{}.ado", name),
+            format!("program define {}\n* {}\nend\n", name, date).into_bytes(),
+        ),
+    ]
+}
+
+/// Serve `alpha`, `beta` and `gamma` at 20240101 and add all three to a
+/// project from there. Returns the project, its cache, the server's routes
+/// and its URL.
+fn project_with_three_net_packages() -> (TempDir, TempDir, Arc<Mutex<Routes>>, String) {
+    let routes = Arc::new(Mutex::new(
+        ["alpha", "beta", "gamma"]
+            .iter()
+            .flat_map(|name| net_package_routes(name, "20240101"))
+            .collect::<Vec<_>>(),
+    ));
+    let url = serve_shared(routes.clone());
+
+    let project = TempDir::new().unwrap();
+    let cache = TempDir::new().unwrap();
+    fs::write(
+        project.path().join("stacy.toml"),
+        "[project]\nname = \"test-project\"\n",
+    )
+    .unwrap();
+    for name in ["alpha", "beta", "gamma"] {
+        stacy()
+            .args(["add", name, "--source", &format!("net:{}", url)])
+            .current_dir(project.path())
+            .env("XDG_CACHE_HOME", cache.path())
+            .env("LOCALAPPDATA", cache.path())
+            .assert()
+            .success();
+    }
+
+    (project, cache, routes, url)
+}
+
+/// Make the server publish `date` for each of `names`.
+fn publish(routes: &Mutex<Routes>, names: &[&str], date: &str) {
+    let mut routes = routes.lock().unwrap();
+    for name in names {
+        for (path, body) in net_package_routes(name, date) {
+            routes.retain(|(route, _)| *route != path);
+            routes.push((path, body));
+        }
+    }
+}
+
+/// `lock` with `name`'s tables cut out
+fn without_package(lock: &str, name: &str) -> String {
+    let prefix = format!("[packages.{}", name);
+    lock.split("\n\n")
+        .filter(|table| !table.starts_with(&prefix))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// `stacy lock --package beta` re-resolves beta alone: alpha has a newer
+/// version too, and its entry is still byte-for-byte what it was.
+#[test]
+fn test_lock_package_leaves_other_entries_byte_identical() {
+    let (project, cache, routes, url) = project_with_three_net_packages();
+    // `lock` resolves a net: source when [overrides] names it
+    let toml_path = project.path().join("stacy.toml");
+    let mut toml = fs::read_to_string(&toml_path).unwrap();
+    toml.push_str("\n[overrides]\n");
+    for name in ["alpha", "beta", "gamma"] {
+        toml.push_str(&format!("{} = \"net:{}\"\n", name, url));
+    }
+    fs::write(&toml_path, toml).unwrap();
+
+    let before = String::from_utf8(read_lock(&project)).unwrap();
+    publish(&routes, &["alpha", "beta"], "20250101");
+
+    stacy()
+        .args(["lock", "--package", "beta"])
+        .current_dir(project.path())
+        .env("XDG_CACHE_HOME", cache.path())
+        .env("LOCALAPPDATA", cache.path())
+        .assert()
+        .success();
+
+    let after = String::from_utf8(read_lock(&project)).unwrap();
+    assert_eq!(after.matches("version = \"20250101\"").count(), 1);
+    assert!(after.contains("[packages.alpha]"));
+    assert_eq!(
+        without_package(&after, "beta"),
+        without_package(&before, "beta")
+    );
+}
+
+/// `stacy update beta` rewrites beta's entry and no other
+#[test]
+fn test_update_package_leaves_other_entries_byte_identical() {
+    let (project, cache, routes, _url) = project_with_three_net_packages();
+    let before = String::from_utf8(read_lock(&project)).unwrap();
+    publish(&routes, &["alpha", "beta"], "20250101");
+
+    stacy()
+        .args(["update", "beta"])
+        .current_dir(project.path())
+        .env("XDG_CACHE_HOME", cache.path())
+        .env("LOCALAPPDATA", cache.path())
+        .assert()
+        .success();
+
+    let after = String::from_utf8(read_lock(&project)).unwrap();
+    assert_eq!(after.matches("version = \"20250101\"").count(), 1);
+    assert_eq!(
+        without_package(&after, "beta"),
+        without_package(&before, "beta")
+    );
+}
@@ -0,0 +1,79 @@
+//! Every `stacy run` adds to the project's totals in `.stacy/metrics.json`;
+//! `stacy metrics export` prints them for Prometheus, and `metrics.textfile_dir`
+//! has them rewritten for a textfile collector after every invocation.
+
+#![cfg(unix)]
+
+use assert_cmd::cargo_bin_cmd;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+/// Fake Stata that writes a clean log for the do-file it is given
+fn write_fake_stata(dir: &Path) -> PathBuf {
+    use std::os::unix::fs::PermissionsExt;
+    let path = dir.join("fake-stata");
+    fs::write(
+        &path,
+        "#!/bin/sh\n\
+         for arg in \"$@\"; do last=\"$arg\"; done\n\
+         stem=$(basename \"$last\" .do)\n\
+         printf '%s\\n' '. display 1' '1' '' 'end of do-file' > \"$stem.log\"\n",
+    )
+    .unwrap();
+    let mut perms = fs::metadata(&path).unwrap().permissions();
+    perms.set_mode(0o755);
+    fs::set_permissions(&path, perms).unwrap();
+    path
+}
+
+#[test]
+fn test_run_counted_and_exported() {
+    let temp = TempDir::new().unwrap();
+    let root = temp.path().join("proj");
+    let config = temp.path().join("config");
+    let collector = temp.path().join("collector");
+    fs::create_dir_all(&root).unwrap();
+    fs::create_dir_all(config.join("stacy")).unwrap();
+    fs::write(root.join("stacy.toml"), "[project]\nname = \"t\"\n").unwrap();
+    fs::write(root.join("main.do"), "display 1\n").unwrap();
+    fs::write(
+        config.join("stacy/config.toml"),
+        format!("[metrics]\ntextfile_dir = \"{}\"\n", collector.display()),
+    )
+    .unwrap();
+    let fake = write_fake_stata(temp.path());
+
+    let stacy = || {
+        let mut cmd = cargo_bin_cmd!("stacy");
+        cmd.current_dir(&root)
+            .env("HOME", temp.path())
+            .env("XDG_CONFIG_HOME", &config)
+            .env("STATA_BINARY", &fake);
+        cmd
+    };
+
+    stacy().args(["run", "main.do"]).assert().success();
+    stacy().args(["run", "main.do"]).assert().success();
+
+    let output = stacy().args(["metrics", "export"]).output().unwrap();
+    assert!(output.status.success());
+    let text = String::from_utf8(output.stdout).unwrap();
+    assert!(text.contains("stacy_runs_total{project=\"t\",status=\"success\"} 2\n"));
+    assert!(text.contains("stacy_runs_total{project=\"t\",status=\"failure\"} 0\n"));
+    assert!(text.contains("stacy_last_run_timestamp_seconds{project=\"t\"}"));
+
+    // The collector's copy matches the export
+    assert_eq!(
+        fs::read_to_string(collector.join("stacy-t.prom")).unwrap(),
+        text
+    );
+
+    let output = stacy()
+        .args(["metrics", "export", "--format", "json"])
+        .output()
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["project"], "t");
+    assert_eq!(json["runs"]["succeeded"], 2);
+}
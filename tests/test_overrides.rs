@@ -0,0 +1,112 @@
+//! `[overrides]` replaces a declared package's source; `stacy lock` follows
+//! it and `stacy list` marks the package.
+
+use assert_cmd::{cargo_bin_cmd, Command};
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+fn stacy(root: &Path, cache_home: &Path) -> Command {
+    let mut cmd = cargo_bin_cmd!("stacy");
+    cmd.current_dir(root)
+        .env("XDG_CACHE_HOME", cache_home)
+        .env("LOCALAPPDATA", cache_home);
+    cmd
+}
+
+fn vendor(dir: &Path, name: &str, body: &str) {
+    fs::create_dir_all(dir).unwrap();
+    fs::write(
+        dir.join(format!("{}.ado", name)),
+        format!("program define {}\n{}\nend\n", name, body),
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_override_relocks_and_is_listed() {
+    let temp = TempDir::new().unwrap();
+    let cache_home = TempDir::new().unwrap();
+    let root = temp.path();
+    vendor(&root.join("lib/alpha"), "alpha", "display 1");
+    vendor(&root.join("fork/alpha"), "alpha", "display 2");
+    fs::write(root.join("stacy.toml"), "[project]\nname = \"t\"\n").unwrap();
+
+    stacy(root, cache_home.path())
+        .args(["add", "alpha", "--source", "local:./lib/alpha"])
+        .assert()
+        .success();
+
+    let mut config = fs::read_to_string(root.join("stacy.toml")).unwrap();
+    config.push_str("\n[overrides]\nalpha = \"local:./fork/alpha\"\n");
+    fs::write(root.join("stacy.toml"), &config).unwrap();
+
+    stacy(root, cache_home.path())
+        .args(["lock", "--check"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("Locked from another source"))
+        .stdout(predicate::str::contains("alpha"));
+    stacy(root, cache_home.path())
+        .arg("lock")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("1 re-resolved"));
+    let locked = fs::read_to_string(root.join("stacy.lock")).unwrap();
+    assert!(locked.contains("./fork/alpha"), "{}", locked);
+
+    stacy(root, cache_home.path())
+        .arg("list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("local:./fork/alpha (override)"));
+    stacy(root, cache_home.path())
+        .args(["list", "--format", "json"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"overridden\": true"));
+
+    // The declaration was never touched: dropping the override goes back
+    let config = fs::read_to_string(root.join("stacy.toml")).unwrap();
+    assert!(
+        config.contains("alpha = \"local:./lib/alpha\""),
+        "{}",
+        config
+    );
+    fs::write(
+        root.join("stacy.toml"),
+        config.replace("[overrides]\nalpha = \"local:./fork/alpha\"\n", ""),
+    )
+    .unwrap();
+    stacy(root, cache_home.path())
+        .arg("lock")
+        .assert()
+        .success();
+    let locked = fs::read_to_string(root.join("stacy.lock")).unwrap();
+    assert!(locked.contains("./lib/alpha"), "{}", locked);
+    stacy(root, cache_home.path())
+        .arg("list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("(override)").not());
+}
+
+#[test]
+fn test_override_of_undeclared_package() {
+    let temp = TempDir::new().unwrap();
+    let cache_home = TempDir::new().unwrap();
+    fs::write(
+        temp.path().join("stacy.toml"),
+        "[overrides]\nestout = \"github:me/estout\"\n",
+    )
+    .unwrap();
+
+    stacy(temp.path(), cache_home.path())
+        .arg("list")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "[overrides] estout: no package 'estout' is declared",
+        ));
+}
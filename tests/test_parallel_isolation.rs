@@ -0,0 +1,92 @@
+//! `stacy test --parallel` gives every test its own scratch directory and
+//! keeps same-named tests' logs apart.
+//!
+//! Parallel tests used to share Stata's temporary directory, and two tests
+//! called `check.do` in different directories kept their logs under the same
+//! name, so one overwrote the other.
+
+#![cfg(unix)]
+
+use assert_cmd::cargo_bin_cmd;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+/// Fake Stata: writes `<wrapper stem>.log` into its cwd, as `stata -b do`
+/// does, recording `$STATATMP` and the wrapper it was handed.
+fn write_fake_stata(dir: &Path) -> PathBuf {
+    use std::os::unix::fs::PermissionsExt;
+    let path = dir.join("fake-stata");
+    fs::write(
+        &path,
+        "#!/bin/sh\n\
+         for arg in \"$@\"; do last=\"$arg\"; done\n\
+         stem=$(basename \"$last\" .do)\n\
+         { echo \"tmp: $STATATMP\"; cat \"$last\"; printf '%s\\n' '' 'end of do-file'; } \
+         > \"$stem.log\"\n",
+    )
+    .unwrap();
+    let mut perms = fs::metadata(&path).unwrap().permissions();
+    perms.set_mode(0o755);
+    fs::set_permissions(&path, perms).unwrap();
+    path
+}
+
+#[test]
+fn test_parallel_tests_get_own_scratch_and_logs() {
+    let temp = TempDir::new().unwrap();
+    let root = temp.path();
+    fs::write(root.join("stacy.toml"), "[project]\nname = \"t\"\n").unwrap();
+    for test in ["tests/a/check.do", "tests/b/check.do", "tests/merge.do"] {
+        let path = root.join(test);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "display 1\n").unwrap();
+    }
+    let fake = write_fake_stata(root);
+
+    cargo_bin_cmd!("stacy")
+        .current_dir(root)
+        .env("STATA_BINARY", &fake)
+        .args(["test", "--parallel", "--log-dir", "kept"])
+        .assert()
+        .success();
+
+    let mut kept: Vec<String> = fs::read_dir(root.join("kept"))
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    kept.sort();
+    assert_eq!(
+        kept,
+        vec!["merge.log", "tests_a_check.log", "tests_b_check.log"]
+    );
+
+    let mut scratch_dirs = Vec::new();
+    for log in &kept {
+        let content = fs::read_to_string(root.join("kept").join(log)).unwrap();
+        let tmp = content
+            .lines()
+            .find_map(|l| l.strip_prefix("tmp: "))
+            .unwrap()
+            .to_string();
+        assert!(tmp.contains("stacy-test-"), "{}: {}", log, tmp);
+        assert!(
+            content.contains("global STACY_TEST_TMP"),
+            "{}: wrapper should set $STACY_TEST_TMP",
+            log
+        );
+        // Strict mode's throwaway PLUS lives in the same scratch directory
+        let scratch = Path::new(&tmp).parent().unwrap().display().to_string();
+        assert!(
+            content.contains(&format!("sysdir set PLUS `\"{}/stacy-ado-", scratch)),
+            "{}: PLUS should be inside the scratch directory",
+            log
+        );
+        // Removed once the test is done
+        assert!(!Path::new(&tmp).exists());
+        scratch_dirs.push(tmp);
+    }
+    scratch_dirs.sort();
+    scratch_dirs.dedup();
+    assert_eq!(scratch_dirs.len(), 3, "each test needs its own scratch");
+}
@@ -0,0 +1,144 @@
+//! `--env-profile <name>` selects a `[profiles.<name>]` package set, locked in
+//! `stacy.<name>.lock` next to the project's own stacy.lock.
+
+use assert_cmd::{cargo_bin_cmd, Command};
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+fn stacy(root: &Path, cache_home: &Path) -> Command {
+    let mut cmd = cargo_bin_cmd!("stacy");
+    cmd.current_dir(root)
+        .env("XDG_CACHE_HOME", cache_home)
+        .env("LOCALAPPDATA", cache_home);
+    cmd
+}
+
+fn vendor(root: &Path, name: &str) {
+    let dir = root.join("lib").join(name);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join(format!("{}.ado", name)),
+        format!("program define {}\nend\n", name),
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_profile_locks_its_own_packages() {
+    let temp = TempDir::new().unwrap();
+    let cache_home = TempDir::new().unwrap();
+    let root = temp.path();
+    for name in ["alpha", "beta", "gamma"] {
+        vendor(root, name);
+    }
+    fs::write(
+        root.join("stacy.toml"),
+        "[project]\nname = \"t\"\n\n[profiles.replication]\ngroups = [\"dependencies\"]\n",
+    )
+    .unwrap();
+
+    stacy(root, cache_home.path())
+        .args(["add", "alpha", "--source", "local:./lib/alpha"])
+        .assert()
+        .success();
+    stacy(root, cache_home.path())
+        .args(["add", "beta", "--dev", "--source", "local:./lib/beta"])
+        .assert()
+        .success();
+    // Under the profile, add records the package as the profile's own and
+    // locks it in the profile's lockfile only
+    stacy(root, cache_home.path())
+        .args(["--env-profile", "replication", "add", "gamma"])
+        .args(["--source", "local:./lib/gamma"])
+        .assert()
+        .success();
+
+    let config = fs::read_to_string(root.join("stacy.toml")).unwrap();
+    assert!(
+        config.contains("[profiles.replication.packages]"),
+        "{}",
+        config
+    );
+    let locked = fs::read_to_string(root.join("stacy.lock")).unwrap();
+    assert!(locked.contains("[packages.beta]"), "{}", locked);
+    assert!(!locked.contains("[packages.gamma]"), "{}", locked);
+    let locked = fs::read_to_string(root.join("stacy.replication.lock")).unwrap();
+    assert!(locked.contains("[packages.gamma]"), "{}", locked);
+    assert!(!locked.contains("[packages.beta]"), "{}", locked);
+
+    stacy(root, cache_home.path())
+        .args(["lock", "--check"])
+        .assert()
+        .success();
+    // alpha belongs to the profile too, but isn't in its lockfile yet
+    stacy(root, cache_home.path())
+        .args(["--env-profile", "replication", "lock", "--check"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains(
+            "Missing from stacy.replication.lock",
+        ))
+        .stdout(predicate::str::contains("alpha"));
+
+    stacy(root, cache_home.path())
+        .args(["--env-profile", "replication", "remove", "gamma"])
+        .assert()
+        .success();
+    let config = fs::read_to_string(root.join("stacy.toml")).unwrap();
+    assert!(!config.contains("gamma"), "{}", config);
+    assert!(config.contains("alpha"), "{}", config);
+
+    stacy(root, cache_home.path())
+        .args(["--env-profile", "paper", "list"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown profile 'paper'"));
+}
+
+#[test]
+fn test_env_profile_does_not_conflict_with_run_profile() {
+    let temp = TempDir::new().unwrap();
+    let cache_home = TempDir::new().unwrap();
+    let root = temp.path();
+    fs::write(
+        root.join("stacy.toml"),
+        "[project]\nname = \"t\"\n\n[profiles.replication]\ngroups = [\"dependencies\"]\n",
+    )
+    .unwrap();
+    fs::write(root.join("main.do"), "display 1\n").unwrap();
+
+    // Both flags on run, in either order: the profile is looked up, and
+    // run's own --profile (execution metrics) still parses
+    stacy(root, cache_home.path())
+        .args(["run", "--profile", "--env-profile", "paper", "main.do"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown profile 'paper'"));
+    stacy(root, cache_home.path())
+        .args([
+            "run",
+            "--env-profile",
+            "replication",
+            "--profile",
+            "main.do",
+        ])
+        .env("STATA_BINARY", root.join("no-stata"))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown profile").not())
+        .stderr(predicate::str::contains("unexpected argument").not());
+
+    // After the command and from the environment, as before it
+    stacy(root, cache_home.path())
+        .args(["list", "--env-profile", "replication"])
+        .assert()
+        .success();
+    stacy(root, cache_home.path())
+        .arg("list")
+        .env("STACY_PROFILE", "paper")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown profile 'paper'"));
+}
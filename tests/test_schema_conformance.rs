@@ -38,6 +38,7 @@ fn test_schema_has_all_commands() {
         "lock",
         "bench",
         "task",
+        "build",
         "test",
         "cache_info",
         "cache_clean",
@@ -100,8 +101,8 @@ fn test_schema_return_types_are_valid() {
     let commands = schema.get("commands").expect("Missing commands section");
     let commands = commands.as_table().expect("Commands should be a table");
 
-    let valid_types = ["bool", "int", "float", "string", "path"];
-    let valid_stata_types = ["scalar", "local"];
+    let valid_types = ["bool", "int", "float", "string", "path", "matrix"];
+    let valid_stata_types = ["scalar", "local", "matrix"];
 
     for (cmd_name, command) in commands {
         let empty_map = toml::map::Map::new();
@@ -370,6 +371,8 @@ fn test_stata_wrapper_returns_match_schema() {
             // Check that the return statement exists
             let expected_pattern = if stata_type == "scalar" {
                 format!("return scalar {}", ret_name)
+            } else if stata_type == "matrix" {
+                format!("return matrix {}", ret_name)
             } else {
                 format!("return local {}", ret_name)
             };
@@ -942,3 +945,17 @@ fn test_generated_ado_no_compound_quotes_in_globals() {
         }
     }
 }
+
+#[test]
+fn test_generated_ado_passes_repeated_options_one_flag_each() {
+    // `engine(stata-se stata-mp)` must become `--engine stata-se --engine
+    // stata-mp`, not a single `--engine "stata-se stata-mp"`.
+    let root = std::path::Path::new(env!("CARGO_MANIFEST_DIR"));
+    let ado = std::fs::read_to_string(root.join("stata/stacy_bench.ado")).unwrap();
+
+    assert!(ado.contains("foreach _value of local engine {"));
+    assert!(ado.contains("foreach _value of local arg {"));
+    assert!(ado.contains("takes key=value pairs"));
+    assert!(ado.contains("[using/]"));
+    assert!(ado.contains("--csv \"`using'\""));
+}
@@ -0,0 +1,84 @@
+//! Integration tests for Windows batch execution
+//!
+//! Drive a real Windows Stata through `/e` batch mode: log placement, paths
+//! with spaces, error detection and the timeout watchdog. Enabled only with
+//! the `windows-stata-tests` feature on Windows, with Stata installed in a
+//! standard location (or `STATA_BINARY` set):
+//!
+//!     cargo test --features windows-stata-tests --test windows_batch_test
+
+#![cfg(all(windows, feature = "windows-stata-tests"))]
+
+use stacy::error::parser::parse_log_for_errors;
+use stacy::executor::binary::{detect_stata_binary, BatchStyle};
+use stacy::executor::run_paths::RunPaths;
+use stacy::executor::runner::{run_stata, RunOptions};
+use std::fs;
+use std::time::Duration;
+use tempfile::TempDir;
+
+fn stata() -> String {
+    detect_stata_binary(None).expect("Windows Stata not found; set STATA_BINARY")
+}
+
+#[test]
+fn test_windows_binary_uses_e_flag() {
+    assert_eq!(BatchStyle::for_binary(&stata()), BatchStyle::Windows);
+}
+
+#[test]
+fn test_log_lands_in_working_dir_with_spaces_in_path() {
+    let temp = TempDir::new().unwrap();
+    let dir = temp.path().join("my project");
+    fs::create_dir(&dir).unwrap();
+    let script = dir.join("analysis.do");
+    fs::write(&script, "display \"hello from windows\"\n").unwrap();
+
+    let paths = RunPaths::prepare(&script, &dir).unwrap();
+    let binary = stata();
+    let options = RunOptions::new(&binary)
+        .with_working_dir(&dir)
+        .with_log_file(paths.log.clone());
+    let result = run_stata(&paths.wrapper, options).expect("Failed to run Stata");
+
+    assert!(result.completed);
+    assert!(paths.log.is_file(), "log not at {}", paths.log.display());
+    let log = fs::read_to_string(&paths.log).unwrap();
+    assert!(log.contains("hello from windows"));
+    assert!(parse_log_for_errors(&paths.log).unwrap().is_empty());
+}
+
+#[test]
+fn test_error_detected_from_windows_log() {
+    let temp = TempDir::new().unwrap();
+    let script = temp.path().join("fails.do");
+    fs::write(&script, "use nonexistent_file_xyz.dta\n").unwrap();
+
+    let paths = RunPaths::prepare(&script, temp.path()).unwrap();
+    let binary = stata();
+    let options = RunOptions::new(&binary)
+        .with_working_dir(temp.path())
+        .with_log_file(paths.log.clone());
+    let result = run_stata(&paths.wrapper, options).expect("Failed to run Stata");
+
+    let errors = parse_log_for_errors(&result.log_file).unwrap();
+    assert!(errors.iter().any(|e| e.r_code() == Some(601)));
+}
+
+#[test]
+fn test_timeout_kills_windows_stata() {
+    let temp = TempDir::new().unwrap();
+    let script = temp.path().join("sleeps.do");
+    fs::write(&script, "sleep 60000\n").unwrap();
+
+    let paths = RunPaths::prepare(&script, temp.path()).unwrap();
+    let binary = stata();
+    let options = RunOptions::new(&binary)
+        .with_working_dir(temp.path())
+        .with_log_file(paths.log.clone())
+        .with_timeout(Duration::from_secs(3));
+    let result = run_stata(&paths.wrapper, options).expect("Failed to run Stata");
+
+    assert!(result.signaled, "watchdog should report the kill");
+    assert!(result.duration < Duration::from_secs(30));
+}
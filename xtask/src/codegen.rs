@@ -5,7 +5,7 @@
 //! - Stata .sthlp help files
 //! - Documentation .md files (docs/src/commands/)
 
-use crate::schema::{Command, Schema};
+use crate::schema::{Argument, Command, Schema};
 use anyhow::{bail, Context, Result};
 use similar::{ChangeTag, TextDiff};
 
@@ -265,6 +265,13 @@ fn generate_ado(name: &str, command: &Command, _schema: &Schema, version: &str)
             out.push_str(&format!("[{}] ", arg_name));
         }
     }
+    if let Some((arg_name, arg)) = command.using_arg() {
+        if arg.required {
+            out.push_str(&format!("using <{}> ", arg_name));
+        } else {
+            out.push_str(&format!("[using <{}>] ", arg_name));
+        }
+    }
 
     if !options.is_empty() {
         out.push_str("[, options]");
@@ -276,7 +283,11 @@ fn generate_ado(name: &str, command: &Command, _schema: &Schema, version: &str)
         out.push_str("    Options:\n");
         for (_, arg) in &options {
             if let Some(ref opt) = arg.stata_option {
-                out.push_str(&format!("        {:20} - {}\n", opt, arg.description));
+                out.push_str(&format!(
+                    "        {:20} - {}\n",
+                    stata_option_display(opt, arg),
+                    arg.description
+                ));
             }
         }
         out.push_str("\n");
@@ -285,10 +296,15 @@ fn generate_ado(name: &str, command: &Command, _schema: &Schema, version: &str)
     // Returns documentation
     out.push_str("    Returns:\n");
     for (ret_name, ret) in command.returns_sorted() {
-        let type_str = if ret.is_scalar() { "scalar" } else { "local" };
         out.push_str(&format!(
             "        r({:20}) - {} ({})\n",
-            ret_name, ret.description, type_str
+            ret_name, ret.description, ret.stata_type
+        ));
+    }
+    for (frame, description) in command.frames_sorted() {
+        out.push_str(&format!(
+            "        stacy_{:17} - {} (frame)\n",
+            frame, description
         ));
     }
     out.push_str("*/\n\n");
@@ -334,6 +350,20 @@ fn generate_ado(name: &str, command: &Command, _schema: &Schema, version: &str)
         out.push_str("    }\n\n");
     }
 
+    // Add the path given with `using`
+    if let Some((arg_name, arg)) = command.using_arg() {
+        let cli_flag = arg
+            .long
+            .clone()
+            .unwrap_or_else(|| arg_name.replace('_', "-"));
+        out.push_str("    if `\"`using'\"' != \"\" {\n");
+        out.push_str(&format!(
+            "        local cmd `\"`cmd' --{} \"`using'\"\"\'\n",
+            cli_flag
+        ));
+        out.push_str("    }\n\n");
+    }
+
     // Add options
     for (arg_name, arg) in command.stata_options() {
         // CLI flags use hyphens (--no-warmup)
@@ -352,6 +382,23 @@ fn generate_ado(name: &str, command: &Command, _schema: &Schema, version: &str)
             out.push_str(&format!("    if \"`{}'\" != \"\" {{\n", stata_macro));
             out.push_str(&format!("        local cmd `\"`cmd' --{}\"\'\n", cli_flag));
             out.push_str("    }\n\n");
+        } else if arg.stata_option.is_some() && arg.is_repeated() {
+            // Repeated options: one flag per item of the space-separated list
+            out.push_str(&format!("    foreach _value of local {} {{\n", stata_macro));
+            if arg.is_map() {
+                out.push_str("        if strpos(`\"`_value'\"', \"=\") == 0 {\n");
+                out.push_str(&format!(
+                    "            di as error `\"{}: {}() takes key=value pairs, not `_value'\"'\n",
+                    command.stata_command, stata_macro
+                ));
+                out.push_str("            exit 198\n");
+                out.push_str("        }\n");
+            }
+            out.push_str(&format!(
+                "        local cmd `\"`cmd' --{} \"`_value'\"\"\'\n",
+                cli_flag
+            ));
+            out.push_str("    }\n\n");
         } else if arg.stata_option.is_some() {
             // String/integer options: check if non-empty and add with value
             out.push_str(&format!("    if `\"`{}'\"' != \"\" {{\n", stata_macro));
@@ -399,6 +446,20 @@ fn generate_ado(name: &str, command: &Command, _schema: &Schema, version: &str)
         }
     }
 
+    // Then matrices, moved into r()
+    for (ret_name, ret) in command.returns_sorted() {
+        if ret.is_matrix() {
+            let internal_name = ret.internal_scalar_name(ret_name);
+            out.push_str(&format!("    capture confirm matrix {}\n", internal_name));
+            out.push_str("    if _rc == 0 {\n");
+            out.push_str(&format!(
+                "        return matrix {} = {}\n",
+                ret_name, internal_name
+            ));
+            out.push_str("    }\n\n");
+        }
+    }
+
     // Return exit code if non-zero
     out.push_str("    * Return failure if command failed\n");
     out.push_str("    if `exec_rc' != 0 {\n");
@@ -410,6 +471,18 @@ fn generate_ado(name: &str, command: &Command, _schema: &Schema, version: &str)
     Ok(out)
 }
 
+/// How an option reads in Stata documentation: a list for repeated options,
+/// `key=value` pairs for maps (e.g. `Engine(strings)`, `Arg(key=value...)`)
+fn stata_option_display(opt: &str, arg: &Argument) -> String {
+    if arg.is_map() {
+        opt.replace("(string)", "(key=value...)")
+    } else if arg.is_repeated() {
+        opt.replace("(string)", "(strings)")
+    } else {
+        opt.to_string()
+    }
+}
+
 /// Build Stata syntax statement
 fn build_stata_syntax(command: &Command) -> String {
     let mut parts = Vec::new();
@@ -423,6 +496,15 @@ fn build_stata_syntax(command: &Command) -> String {
         }
     }
 
+    // Path from the using clause; `using/` strips its quotes
+    if let Some((_, arg)) = command.using_arg() {
+        if arg.required {
+            parts.push("using/".to_string());
+        } else {
+            parts.push("[using/]".to_string());
+        }
+    }
+
     // Options
     // Stata's syntax command requires integer/real options to have a default
     // value (e.g., Runs(integer 0) not just Runs(integer)). We use (string)
@@ -508,6 +590,13 @@ fn generate_sthlp(name: &str, command: &Command, schema: &Schema) -> Result<Stri
             out.push_str(&format!("[{{it:{}}}] ", arg_name));
         }
     }
+    if let Some((arg_name, arg)) = command.using_arg() {
+        if arg.required {
+            out.push_str(&format!("{{cmd:using}} {{it:{}}} ", arg_name));
+        } else {
+            out.push_str(&format!("[{{cmd:using}} {{it:{}}}] ", arg_name));
+        }
+    }
 
     // Options
     let options = command.stata_options();
@@ -525,7 +614,7 @@ fn generate_sthlp(name: &str, command: &Command, schema: &Schema) -> Result<Stri
 
         for (_, arg) in &options {
             if let Some(ref opt) = arg.stata_option {
-                let opt_display = opt.to_lowercase();
+                let opt_display = stata_option_display(opt, arg).to_lowercase();
                 out.push_str(&format!(
                     "{{synopt:{{opt:{}}}}}{}{{p_end}}\n",
                     opt_display, arg.description
@@ -551,12 +640,21 @@ fn generate_sthlp(name: &str, command: &Command, schema: &Schema) -> Result<Stri
         out.push_str(&format!("{{marker options}}{{...}}\n"));
         out.push_str("{title:Options}\n\n");
 
+        if let Some((arg_name, arg)) = command.using_arg() {
+            out.push_str("{phang}\n");
+            out.push_str(&format!(
+                "{{cmd:using}} {{it:{}}} {}.\n\n",
+                arg_name,
+                lowercase_first(&arg.description)
+            ));
+        }
+
         for (arg_name, arg) in &options {
             out.push_str("{phang}\n");
             out.push_str(&format!(
                 "{{opt {}}} {}.\n\n",
                 arg_name,
-                arg.description.to_lowercase()
+                lowercase_first(&arg.description)
             ));
         }
         out.push_str("\n");
@@ -609,6 +707,39 @@ fn generate_sthlp(name: &str, command: &Command, schema: &Schema) -> Result<Stri
         out.push_str("\n");
     }
 
+    // Matrices
+    let matrices: Vec<_> = command
+        .returns_sorted()
+        .into_iter()
+        .filter(|(_, r)| r.is_matrix())
+        .collect();
+
+    if !matrices.is_empty() {
+        out.push_str("{p2col 5 25 29 2: Matrices}{p_end}\n");
+        for (ret_name, ret) in matrices {
+            out.push_str(&format!(
+                "{{synopt:{{cmd:r({})}}}}{}{{p_end}}\n",
+                ret_name, ret.description
+            ));
+        }
+        out.push_str("\n");
+    }
+
+    // Frames
+    let frames = command.frames_sorted();
+
+    if !frames.is_empty() {
+        out.push_str("{pstd}\n");
+        out.push_str("In Stata 16 or later, it also leaves the following frames in memory:\n\n");
+        for (frame, description) in frames {
+            out.push_str(&format!(
+                "{{synopt:{{cmd:stacy_{}}}}}{}{{p_end}}\n",
+                frame, description
+            ));
+        }
+        out.push_str("\n");
+    }
+
     // Examples
     out.push_str(&format!("\n{{marker examples}}{{...}}\n"));
     out.push_str("{title:Examples}\n\n");
@@ -859,7 +990,12 @@ fn generate_markdown(name: &str, command: &Command, schema: &Schema) -> Result<S
     out.push_str(&format!("stacy {} ", display_name));
 
     let positional = command.positional_args();
-    let options = command.stata_options();
+    // Every CLI option, including the one Stata takes from `using`
+    let options: Vec<_> = command
+        .args_sorted()
+        .into_iter()
+        .filter(|(name, arg)| !arg.positional && *name != "json")
+        .collect();
 
     for (arg_name, arg) in &positional {
         if arg.required || arg.required_unless.is_none() {
@@ -1040,6 +1176,23 @@ fn title_case(s: &str) -> String {
         .join(" ")
 }
 
+/// Lowercase the first letter of a description so it can follow an option
+/// name, keeping the case of the rest (HEAD, Stata/MP) and of a leading
+/// acronym (JSON, DVC)
+fn lowercase_first(s: &str) -> String {
+    let first_word = s.split_whitespace().next().unwrap_or("");
+    if first_word.chars().filter(|c| c.is_alphabetic()).count() > 1
+        && !first_word.chars().any(|c| c.is_lowercase())
+    {
+        return s.to_string();
+    }
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(first) => first.to_lowercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
 // =============================================================================
 // REFERENCE DOCUMENTATION GENERATION
 // =============================================================================
@@ -1108,6 +1261,42 @@ fn generate_exit_codes_md(schema: &Schema) -> Result<String> {
          return codes into a small, stable set that build tools can branch on.\n\n",
     );
 
+    // Remapping via [exit_codes]
+    out.push_str("## Remapping\n\n");
+    out.push_str(
+        "Some orchestrators reserve exit codes of their own. `[exit_codes]` in \
+         `stacy.toml`, or in the [user config](../configuration/user.md#exit_codes), \
+         gives a kind of failure another code:\n\n",
+    );
+    out.push_str("```toml\n");
+    out.push_str("[exit_codes]\n");
+    out.push_str("syntax_error = 65\n");
+    out.push_str("file_error = 66\n");
+    out.push_str("```\n\n");
+    out.push_str(
+        "The keys are `stata_error`, `syntax_error`, `file_error`, `memory_error`, \
+         `internal_error`, `statistical_error` and `environment_error`; codes must be \
+         between 1 and 255. The project's entries win over the user's. Success stays 0, \
+         and a process killed by a signal still exits with 128 plus the signal.\n\n",
+    );
+    out.push_str(
+        "Only the process exit status changes. The `exit_code` fields in `--format json` \
+         output and `r(exit_code)` in Stata keep the standard codes, so a parser sees \
+         the same contract everywhere.\n\n",
+    );
+    out.push_str(
+        "`stacy explain --exit-codes` prints the codes in effect for the current \
+         directory, with `--format json` or `--format stata` for scripts:\n\n",
+    );
+    out.push_str("```\n");
+    out.push_str("$ stacy explain --exit-codes\n");
+    out.push_str("Code  Kind               Meaning\n");
+    out.push_str("   0  success            Success\n");
+    out.push_str("   1  stata_error        Stata error (r() code detected)\n");
+    out.push_str("  65  syntax_error       Syntax error (standard: 2)\n");
+    out.push_str("   ...\n");
+    out.push_str("```\n\n");
+
     // Stability note
     out.push_str("## Stability\n\n");
     out.push_str("Exit codes 0-10 are stable and will not change meaning. ");
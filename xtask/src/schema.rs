@@ -63,6 +63,10 @@ pub struct Command {
     pub args: HashMap<String, Argument>,
     #[serde(default)]
     pub returns: HashMap<String, ReturnValue>,
+    /// Frames (Stata 16+) the output leaves in memory, beside r(): name
+    /// without the `stacy_` prefix, and description
+    #[serde(default)]
+    pub frames: HashMap<String, String>,
     /// Per-command exit codes documentation
     #[serde(default)]
     pub exit_codes: HashMap<String, String>,
@@ -107,6 +111,16 @@ pub struct Argument {
     pub description: String,
     #[serde(default)]
     pub stata_option: Option<String>,
+    /// Option may be given more than once; the Stata option takes a
+    /// space-separated list and passes each item as its own flag
+    #[serde(default)]
+    pub repeated: bool,
+    /// Path option taken from Stata's `using` clause instead of an option
+    #[serde(default)]
+    pub using: bool,
+    /// Flag with no meaning inside Stata; left out of the wrappers and help
+    #[serde(default)]
+    pub cli_only: bool,
 }
 
 /// Return value definition
@@ -158,28 +172,40 @@ impl Command {
         args
     }
 
-    /// Get return values sorted: scalars first, then locals
+    /// Get return values sorted: scalars first, then locals, then matrices
     pub fn returns_sorted(&self) -> Vec<(&String, &ReturnValue)> {
+        let rank = |ret: &ReturnValue| match ret.stata_type.as_str() {
+            "scalar" => 0,
+            "local" => 1,
+            _ => 2,
+        };
         let mut returns: Vec<_> = self.returns.iter().collect();
         returns.sort_by(|(name_a, ret_a), (name_b, ret_b)| {
-            // Scalars before locals
-            match (ret_a.stata_type.as_str(), ret_b.stata_type.as_str()) {
-                ("scalar", "local") => std::cmp::Ordering::Less,
-                ("local", "scalar") => std::cmp::Ordering::Greater,
-                _ => name_a.cmp(name_b),
-            }
+            rank(ret_a).cmp(&rank(ret_b)).then(name_a.cmp(name_b))
         });
         returns
     }
 
+    /// Get frames sorted by name
+    pub fn frames_sorted(&self) -> Vec<(&String, &String)> {
+        let mut frames: Vec<_> = self.frames.iter().collect();
+        frames.sort_by_key(|(name, _)| *name);
+        frames
+    }
+
     /// Get Stata syntax options (non-positional args with stata_option)
     pub fn stata_options(&self) -> Vec<(&String, &Argument)> {
         self.args_sorted()
             .into_iter()
-            .filter(|(name, arg)| !arg.positional && *name != "json")
+            .filter(|(name, arg)| !arg.positional && !arg.using && !arg.cli_only && *name != "json")
             .collect()
     }
 
+    /// Get the argument taken from Stata's `using` clause, if any
+    pub fn using_arg(&self) -> Option<(&String, &Argument)> {
+        self.args_sorted().into_iter().find(|(_, arg)| arg.using)
+    }
+
     /// Get positional arguments
     pub fn positional_args(&self) -> Vec<(&String, &Argument)> {
         self.args_sorted()
@@ -190,6 +216,16 @@ impl Command {
 }
 
 impl Argument {
+    /// Is this a list of `key=value` pairs?
+    pub fn is_map(&self) -> bool {
+        self.arg_type == "map"
+    }
+
+    /// May the CLI flag be given more than once? Maps always may.
+    pub fn is_repeated(&self) -> bool {
+        self.repeated || self.is_map()
+    }
+
     /// Generate Stata syntax fragment for this argument
     #[allow(dead_code)]
     pub fn to_stata_syntax(&self) -> Option<String> {
@@ -218,6 +254,11 @@ impl ReturnValue {
         self.stata_type == "local"
     }
 
+    /// Is this a matrix return value?
+    pub fn is_matrix(&self) -> bool {
+        self.stata_type == "matrix"
+    }
+
     /// Get the JSON extraction function to use
     #[allow(dead_code)]
     pub fn json_extractor(&self) -> &'static str {